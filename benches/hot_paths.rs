@@ -0,0 +1,133 @@
+//! Benchmarks of the hot paths of a frame, run on the host with `cargo bench`.
+//!
+//! The fixtures are built through the public API only, so these also check at compile time that it suffices to set
+//! up a board, a frame and a game from the outside.
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use rust_tetris_rp2040::board::{Board, BoardOps, FixedBoard};
+use rust_tetris_rp2040::cell::Cell;
+use rust_tetris_rp2040::coordinate::Coordinate;
+use rust_tetris_rp2040::fixtures::{fill_bottom_rows, fill_mid_game};
+use rust_tetris_rp2040::framebuffer::{DrawConfig, FrameBuffer};
+use rust_tetris_rp2040::game::{Game, GameState};
+use rust_tetris_rp2040::gravity::tetromino_hit;
+use rust_tetris_rp2040::grid::Array2D;
+use rust_tetris_rp2040::input::{Action, InputState};
+use rust_tetris_rp2040::palette::GUIDELINE;
+use rust_tetris_rp2040::settings::Settings;
+use rust_tetris_rp2040::tetrominoes::{Tetromino, TetrominoShape, SHAPES};
+
+/// The seed of every fixture, so the runs before and after a change measure the same boards.
+const SEED: u64 = 171;
+
+/// The number of [rows, cols] of the LED panel.
+const PANEL: [usize; 2] = [64, 32];
+
+/// The number of LEDs of the panel.
+const PIXELS: usize = PANEL[0] * PANEL[1];
+
+/// Get the cells of every shape as locked on the board, to fill mid-game boards with.
+fn locked_cells() -> Vec<Cell> {
+    SHAPES.iter().map(|shape| Cell::locked(*shape)).collect()
+}
+
+fn bench_tetromino_hit(c: &mut Criterion) {
+    // A T just above the stack of a half-full board, the check made for every move of a falling piece.
+    let mut board = Board::new(Coordinate::from_array([20, 10]), false);
+    fill_mid_game(&mut board, 10, &[true], SEED);
+    let tetromino = Tetromino::from(TetrominoShape::T);
+    let coord = Coordinate::from_array([9, 4]);
+    c.bench_function("tetromino_hit", |b| {
+        b.iter(|| {
+            tetromino_hit(black_box(coord), black_box(&board), black_box(&tetromino)).unwrap()
+        })
+    });
+}
+
+fn bench_clear_full_rows(c: &mut Criterion) {
+    // A tetris on a half-full board, dropping the 6 rows left above it.
+    let mut board = FixedBoard::<Cell, 20, 10>::new(Cell::Empty);
+    fill_mid_game(&mut board, 10, &locked_cells(), SEED);
+    fill_bottom_rows(&mut board, 4, Cell::Garbage);
+    c.bench_function("clear_full_rows", |b| {
+        b.iter_batched_ref(
+            || board,
+            |board| board.clear_full_rows(),
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+fn bench_set_mask_or(c: &mut Criterion) {
+    // Writing the 4x4 mask of an I into the middle of a half-full board, as locking a piece does.
+    let mut board = Board::new(Coordinate::from_array([20, 10]), false);
+    fill_mid_game(&mut board, 10, &[true], SEED);
+    let mask = Array2D::from_row_major(
+        &[
+            false, false, false, false, // . . . .
+            true, true, true, true, //     x x x x
+            false, false, false, false, // . . . .
+            false, false, false, false, // . . . .
+        ],
+        4,
+        4,
+    )
+    .unwrap();
+    let coord = Coordinate::from_array([8, 3]);
+    c.bench_function("set_mask_or", |b| {
+        b.iter_batched_ref(
+            || board.clone(),
+            |board| board.set_mask_or(black_box(&mask), black_box(coord)),
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+fn bench_draw_board(c: &mut Criterion) {
+    // Drawing a half-full board into a frame of the whole panel and encoding it for the LEDs.
+    let mut board = FixedBoard::<Cell, 22, 10>::new(Cell::Empty);
+    fill_mid_game(&mut board, 11, &locked_cells(), SEED);
+    let settings = Settings::default();
+    let mut frame = FrameBuffer::<PIXELS>::new(Coordinate::from_array(PANEL));
+    let mut grb = vec![0; 3 * PIXELS];
+    c.bench_function("draw_board_grb", |b| {
+        b.iter(|| {
+            frame.draw_board(black_box(&board), &DrawConfig::default(), &GUIDELINE);
+            frame.as_grb_bytes(settings.brightness, settings.gamma, &mut grb)
+        })
+    });
+}
+
+fn bench_game_tick(c: &mut Criterion) {
+    // A hard drop while playing, the busiest tick: the piece drops, locks and the next one spawns.
+    let setup = || {
+        let mut game = Game::with_board(
+            FixedBoard::<Cell, 22, 10>::new(Cell::Empty),
+            Settings::default(),
+            SEED,
+        );
+        game.restart();
+        game.tick(&InputState::from_action(Action::Start));
+        while game.get_state() != GameState::Playing {
+            game.tick(&InputState::default());
+        }
+        game
+    };
+    let input = InputState::from_action(Action::HardDrop);
+    c.bench_function("game_tick", |b| {
+        b.iter_batched_ref(
+            setup,
+            |game| game.tick(black_box(&input)),
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+criterion_group!(
+    benches,
+    bench_tetromino_hit,
+    bench_clear_full_rows,
+    bench_set_mask_or,
+    bench_draw_board,
+    bench_game_tick
+);
+criterion_main!(benches);
@@ -0,0 +1,30 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+/// The exponent of the gamma correction of the LEDs. Rather than the 2.2 of an sRGB display, 2.8 maps a half
+/// brightness of 128 to about 36, the dimmer curve of WS2812 LEDs, which `GAMMA8[128] == 37` pins in `color`.
+const GAMMA: f64 = 2.8;
+
+/// Generate the gamma correction table, so it is computed rather than typed out by hand.
+fn main() {
+    let table: Vec<String> = (0..256)
+        .map(|i| {
+            let corrected = (i as f64 / 255.0).powf(GAMMA) * 255.0;
+            format!("{}", corrected.round() as u8)
+        })
+        .collect();
+    let source = format!(
+        "/// The gamma correction table, mapping a linear channel value to the LED duty cycle with a gamma of {}.\n\
+         pub const GAMMA8: [u8; 256] = [{}];\n",
+        GAMMA,
+        table.join(", ")
+    );
+    let out = env::var("OUT_DIR").unwrap();
+    fs::write(Path::new(&out).join("gamma.rs"), source).unwrap();
+    // Put the memory layout of the RP2040 on the linker search path for the `link.x` of the firmware.
+    fs::copy("memory.x", Path::new(&out).join("memory.x")).unwrap();
+    println!("cargo:rustc-link-search={}", out);
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=memory.x");
+}
@@ -0,0 +1,66 @@
+//! Sweep the weights of the AI across seeds with headless games, run on the host with `cargo run --release --example
+//! tune`.
+//!
+//! Every row of the table plays the same seeds, so the rows differ by their weights alone.
+use rust_tetris_rp2040::ai::{AiConfig, Autopilot, Weights};
+use rust_tetris_rp2040::settings::Settings;
+use rust_tetris_rp2040::sim::run_headless;
+
+/// The number of seeds every set of weights plays.
+const SEEDS: u64 = 8;
+
+/// The number of pieces after which a game is stopped.
+const MAX_PIECES: u32 = 500;
+
+/// The penalties per row of aggregate height to try.
+const HEIGHTS: [i32; 3] = [400, 510, 620];
+
+/// The penalties per hole to try.
+const HOLES: [i32; 3] = [250, 357, 500];
+
+/// The rewards per row of the deepest well to try.
+const WELLS: [i32; 2] = [0, 250];
+
+fn main() {
+    let settings = Settings::default();
+    println!(
+        "{:>6} {:>6} {:>6} | {:>7} {:>7} {:>9} {:>8}",
+        "height", "holes", "well", "pieces", "lines", "points", "top-outs"
+    );
+    for height in HEIGHTS {
+        for holes in HOLES {
+            for well in WELLS {
+                let weights = Weights {
+                    height,
+                    holes,
+                    well,
+                    ..Weights::CLASSIC
+                };
+                let (mut pieces, mut lines, mut points, mut top_outs) = (0, 0, 0, 0);
+                for seed in 0..SEEDS {
+                    let result = run_headless(
+                        &settings,
+                        seed,
+                        MAX_PIECES,
+                        &mut Autopilot::new(AiConfig::with_weights(weights)),
+                    );
+                    pieces += result.pieces as u64;
+                    lines += result.lines as u64;
+                    points += result.points as u64;
+                    top_outs += result.top_out.is_some() as u64;
+                }
+                // The means over all seeds, and the number of games that topped out.
+                println!(
+                    "{:>6} {:>6} {:>6} | {:>7} {:>7} {:>9} {:>8}",
+                    height,
+                    holes,
+                    well,
+                    pieces / SEEDS,
+                    lines / SEEDS,
+                    points / SEEDS,
+                    top_outs
+                );
+            }
+        }
+    }
+}
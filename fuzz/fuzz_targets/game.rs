@@ -0,0 +1,24 @@
+//! Fuzz the game engine with arbitrary input, run with `cargo +nightly fuzz run game` from the root of the repository.
+//!
+//! The first 8 bytes seed the game, every byte after them is the input of a single tick, see `Game::apply_raw()`.
+//! A panic or an invariant violation of any sequence is a bug, to be turned into a regression test of `game.rs`.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rust_tetris_rp2040::game::Game;
+use rust_tetris_rp2040::settings::Settings;
+
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 8 {
+        return;
+    }
+    let (seed, inputs) = data.split_at(8);
+    let seed = u64::from_le_bytes(seed.try_into().expect("the seed has 8 bytes"));
+    let mut game = Game::new(Settings::default(), seed);
+    for (tick, byte) in inputs.iter().enumerate() {
+        game.apply_raw(*byte);
+        if let Err(violation) = game.validate() {
+            panic!("invariant violated at tick {}: {}", tick, violation);
+        }
+    }
+});
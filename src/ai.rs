@@ -0,0 +1,764 @@
+//! A simple player for the attract mode, placing every piece with a lookahead of one or two pieces.
+//!
+//! Every rotation and column of the active piece is tried on a scratch board, and the resulting board is scored
+//! with the classic heuristic of aggregate height, cleared lines, holes and bumpiness, optionally rewarding a deep
+//! well to clear multiple lines at once. Looking 2 pieces ahead, the next piece is placed after the best few
+//! placements, also trying the piece swapped in by holding. The planner then emits the
+//! `Action`s reaching the best placement, a hold first, then rotations, shifts and a hard drop.
+use crate::board::BoardOps;
+use crate::cell::Cell;
+use crate::coordinate::Coordinate;
+use crate::game::Game;
+use crate::input::{Action, InputState};
+use crate::piece::ActivePiece;
+use crate::rotation::kicks::RotationState;
+use crate::settings::{SettingsError, MAX_COLS};
+use crate::sim::Policy;
+use crate::tetrominoes::{Tetromino, TetrominoShape};
+use core::cmp::Reverse;
+use core::iter;
+
+/// The number of ticks between the actions of the `Autopilot`, so the demo plays at a human pace.
+pub const ACTION_TICKS: u8 = 6;
+
+/// The maximum number of actions of a `Plan`, enough to cross a board of 29 columns.
+pub const MAX_PLAN_ACTIONS: usize = 32;
+
+/// The deepest search of `plan_ahead()`, placing the active piece and the next one.
+pub const MAX_DEPTH: u8 = 2;
+
+/// The largest number of placements of a single piece, in every rotation and column of the widest board.
+pub const MAX_PLACEMENTS: usize = ROTATIONS.len() * MAX_COLS;
+
+/// The deepest well rewarded by `Weights::well`, as a well of 4 rows already fits the longest clear.
+pub const MAX_WELL_DEPTH: u32 = 4;
+
+/// The rotations tried for every piece, a single counter-clockwise rotation instead of three clockwise ones.
+const ROTATIONS: [&[Action]; 4] = [
+    &[],
+    &[Action::RotateCw],
+    &[Action::RotateCw, Action::RotateCw],
+    &[Action::RotateCcw],
+];
+
+/// The metrics of a board scored by the heuristic.
+/// # Attributes
+/// - `aggregate_height` - The sum of the heights of all columns
+/// - `holes` - The number of empty cells below the top filled cell of their column
+/// - `bumpiness` - The sum of the absolute height differences of all neighboring columns
+/// - `well_depth` - The depth of the deepest well, see `BoardOps::deepest_well()`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Metrics {
+    pub aggregate_height: u32,
+    pub holes: u32,
+    pub bumpiness: u32,
+    pub well_depth: u32,
+}
+
+impl Metrics {
+    /// Measure a board.
+    /// # Arguments
+    /// - `board` - A reference to the board to measure
+    /// # Returns
+    /// - `Metrics` - The metrics of the board
+    pub fn of<T: Copy + PartialEq, B: BoardOps<T>>(board: &B) -> Self {
+        let shape = board.get_shape();
+        let mut metrics = Metrics::default();
+        let mut previous = None;
+        for col in 0..shape.col {
+            let top = (0..shape.row).find(|&row| board.is_filled(Coordinate { row, col }));
+            let height = match top {
+                Some(top) => {
+                    metrics.holes += (top..shape.row)
+                        .filter(|&row| !board.is_filled(Coordinate { row, col }))
+                        .count() as u32;
+                    (shape.row - top) as u32
+                }
+                None => 0,
+            };
+            metrics.aggregate_height += height;
+            if let Some(previous) = previous {
+                metrics.bumpiness += u32::abs_diff(previous, height);
+            }
+            previous = Some(height);
+        }
+        metrics.well_depth = board.deepest_well().map_or(0, |(_, depth)| depth as u32);
+        metrics
+    }
+}
+
+/// The weights of the heuristic, in thousandths.
+/// # Attributes
+/// - `height` - The penalty per row of aggregate height
+/// - `lines` - The reward per cleared line
+/// - `holes` - The penalty per hole
+/// - `bumpiness` - The penalty per row of bumpiness
+/// - `well` - The reward per row of the deepest well, up to `MAX_WELL_DEPTH` rows
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Weights {
+    pub height: i32,
+    pub lines: i32,
+    pub holes: i32,
+    pub bumpiness: i32,
+    pub well: i32,
+}
+
+impl Weights {
+    /// The weights tuned by Yiyuan Lee for a player without lookahead, which do not reward wells.
+    pub const CLASSIC: Weights = Weights {
+        height: 510,
+        lines: 761,
+        holes: 357,
+        bumpiness: 184,
+        well: 0,
+    };
+
+    /// Score a placement, higher is better.
+    /// # Arguments
+    /// - `metrics` - A reference to the `Metrics` of the board after the placement
+    /// - `lines` - The number of lines the placement cleared
+    /// # Returns
+    /// - `i32` - The score of the placement
+    pub fn score(&self, metrics: &Metrics, lines: usize) -> i32 {
+        self.lines * lines as i32
+            - self.height * metrics.aggregate_height as i32
+            - self.holes * metrics.holes as i32
+            - self.bumpiness * metrics.bumpiness as i32
+            + self.well * metrics.well_depth.min(MAX_WELL_DEPTH) as i32
+    }
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Weights::CLASSIC
+    }
+}
+
+/// The configuration of the planner.
+/// # Attributes
+/// - `depth` - The number of pieces to look ahead, 1 for the active piece only or 2 to also place the next piece
+/// - `beam` - The number of best placements of the active piece to place the next piece after, bounding the work of
+///   a search 2 pieces deep
+/// - `weights` - The weights of the heuristic
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AiConfig {
+    pub depth: u8,
+    pub beam: u8,
+    pub weights: Weights,
+}
+
+impl AiConfig {
+    /// The planner of the attract mode, placing the next piece after each of the 4 best placements.
+    pub const ATTRACT: AiConfig = AiConfig {
+        depth: 2,
+        beam: 4,
+        weights: Weights::CLASSIC,
+    };
+
+    /// Create a planner placing the active piece only.
+    /// # Arguments
+    /// - `weights` - The `Weights` of the heuristic
+    /// # Returns
+    /// - `AiConfig` - The configuration
+    pub fn with_weights(weights: Weights) -> Self {
+        AiConfig {
+            depth: 1,
+            beam: 1,
+            weights,
+        }
+    }
+
+    /// Check if the planner can search with the configuration.
+    /// # Returns
+    /// - `Result<(), SettingsError>` - Nothing, or an error for a depth outside of 1 to `MAX_DEPTH` or an empty beam
+    pub fn validate(&self) -> Result<(), SettingsError> {
+        if !(1..=MAX_DEPTH).contains(&self.depth) {
+            return Err(SettingsError::AiDepth(self.depth));
+        }
+        if self.beam == 0 {
+            return Err(SettingsError::AiBeam);
+        }
+        Ok(())
+    }
+}
+
+impl Default for AiConfig {
+    fn default() -> Self {
+        AiConfig::with_weights(Weights::CLASSIC)
+    }
+}
+
+/// The pieces after the active piece, as far as the planner looks ahead.
+/// # Attributes
+/// - `queue` - The first 2 pieces of the queue, the second one dealt after holding into an empty hold slot
+/// - `held` - The shape in the hold slot, if any
+/// - `can_hold` - Whether the active piece can be swapped into the hold slot
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Lookahead {
+    pub queue: [Option<TetrominoShape>; 2],
+    pub held: Option<TetrominoShape>,
+    pub can_hold: bool,
+}
+
+impl Lookahead {
+    /// Look at the queue and the hold slot of a game.
+    /// # Arguments
+    /// - `game` - A reference to the `Game`
+    /// # Returns
+    /// - `Lookahead` - The pieces after the active piece
+    pub fn of<B: BoardOps<Cell> + Clone>(game: &Game<B>) -> Self {
+        let mut queue = [None; 2];
+        for (slot, shape) in queue.iter_mut().zip(game.get_queue().peek(2)) {
+            *slot = Some(*shape);
+        }
+        Lookahead {
+            queue,
+            held: game.get_hold().get_shape(),
+            can_hold: game.get_settings().hold && game.get_hold().can_hold(),
+        }
+    }
+
+    /// Get the shape swapped in by holding, and the shape dealt after it.
+    fn _hold(&self) -> Option<(TetrominoShape, Option<TetrominoShape>)> {
+        if !self.can_hold {
+            return None;
+        }
+        match self.held {
+            Some(held) => Some((held, self.queue[0])),
+            None => self.queue[0].map(|next| (next, self.queue[1])),
+        }
+    }
+}
+
+/// A placement of a piece, without the board it leaves behind.
+/// # Attributes
+/// - `rotations` - The index into `ROTATIONS` of the rotations reaching the placement
+/// - `from` - The column of the top-left element of the piece mask after the rotations
+/// - `rotation` - The rotation state of the piece when dropped
+/// - `col` - The column of the top-left element of the piece mask when dropped
+/// - `lines` - The number of lines the placement cleared
+/// - `score` - The score of the placement, see `Weights::score()`
+#[derive(Clone, Copy, Debug)]
+struct Placement {
+    rotations: usize,
+    from: usize,
+    rotation: RotationState,
+    col: usize,
+    lines: usize,
+    score: i32,
+}
+
+/// The best placement of a piece and how to get there.
+/// # Attributes
+/// - `rotation` - The rotation state of the piece when dropped
+/// - `col` - The column of the top-left element of the piece mask when dropped
+/// - `score` - The score of the placement, see `Weights::score()`, or of the best placement of the next piece after
+///   it when looking 2 pieces ahead
+/// - `nodes` - The number of placements scored to find the plan
+/// - `actions` - The actions to reach the placement from the current position, starting with `Action::Hold` when
+///   the placement is for the swapped in piece, and ending with `Action::HardDrop`.
+///   On boards too wide for `MAX_PLAN_ACTIONS`, the shifts are cut short, which the `Autopilot` does not notice
+///   as it plans again after every action
+#[derive(Clone, Debug, PartialEq)]
+pub struct Plan {
+    pub rotation: RotationState,
+    pub col: usize,
+    pub score: i32,
+    pub nodes: u32,
+    pub actions: heapless::Vec<Action, MAX_PLAN_ACTIONS>,
+}
+
+/// Find the best placement of the active piece with a one-piece lookahead.
+///
+/// Ties go to the placement found first, which prefers the current rotation, so planning again after every
+/// action keeps the same target.
+/// # Arguments
+/// - `board` - A reference to the board the piece is played on
+/// - `piece` - A reference to the `ActivePiece` to place
+/// - `weights` - A reference to the `Weights` to score the placements with
+/// # Returns
+/// - `Option<Plan>` - The best placement, or `None` if the piece cannot be dropped anywhere
+pub fn plan<B: BoardOps<Cell> + Clone>(
+    board: &B,
+    piece: &ActivePiece,
+    weights: &Weights,
+) -> Option<Plan> {
+    plan_ahead(
+        board,
+        piece,
+        &Lookahead::default(),
+        &AiConfig::with_weights(*weights),
+    )
+}
+
+/// Find the best placement of the active piece, or of the piece swapped in by holding, looking up to 2 pieces ahead.
+///
+/// Looking 2 pieces ahead, the placements of the active piece are ranked by their own score, and only the `beam`
+/// best ones are scored by the best placement of the next piece after them. A search scores at most
+/// `2 * (1 + beam) * MAX_PLACEMENTS` placements, all kept on the stack. Ties go to the placement found first, and
+/// to keeping the active piece over holding it.
+/// # Arguments
+/// - `board` - A reference to the board the piece is played on
+/// - `piece` - A reference to the `ActivePiece` to place
+/// - `lookahead` - A reference to the `Lookahead` of the pieces after the active piece
+/// - `config` - A reference to the `AiConfig` of the search
+/// # Returns
+/// - `Option<Plan>` - The best placement, or `None` if neither piece can be dropped anywhere
+pub fn plan_ahead<B: BoardOps<Cell> + Clone>(
+    board: &B,
+    piece: &ActivePiece,
+    lookahead: &Lookahead,
+    config: &AiConfig,
+) -> Option<Plan> {
+    let mut nodes = 0;
+    let mut best: Option<(Placement, i32, bool)> = None;
+    let cols = board.get_shape().col;
+    let held = lookahead
+        ._hold()
+        .map(|(shape, next)| (_spawn(shape, cols), next, true))
+        .filter(|(held, _, _)| held.fits(board));
+    let alternatives = iter::once((piece.clone(), lookahead.queue[0], false)).chain(held);
+    for (piece, next, hold) in alternatives {
+        let next = next.filter(|_| config.depth >= 2);
+        let Some((placement, score)) = _search(board, &piece, next, config, &mut nodes) else {
+            continue;
+        };
+        let better = match &best {
+            Some((_, best, _)) => score > *best,
+            None => true,
+        };
+        if better {
+            best = Some((placement, score, hold));
+        }
+    }
+    best.map(|(placement, score, hold)| {
+        let (shift, shifts) = match placement.from < placement.col {
+            true => (Action::Right, placement.col - placement.from),
+            false => (Action::Left, placement.from - placement.col),
+        };
+        let actions = iter::once(Action::Hold)
+            .take(hold as usize)
+            .chain(ROTATIONS[placement.rotations].iter().copied())
+            .chain((0..shifts).map(|_| shift))
+            .take(MAX_PLAN_ACTIONS - 1)
+            .chain(iter::once(Action::HardDrop))
+            .collect();
+        Plan {
+            rotation: placement.rotation,
+            col: placement.col,
+            score,
+            nodes,
+            actions,
+        }
+    })
+}
+
+/// Find the best placement of a piece, by its own score or by the best placement of the next piece after it.
+/// # Arguments
+/// - `board` - A reference to the board the piece is played on
+/// - `piece` - A reference to the `ActivePiece` to place
+/// - `next` - The shape of the next piece to place after it, `None` to only place the piece
+/// - `config` - A reference to the `AiConfig` of the search
+/// - `nodes` - A muteable reference to the number of scored placements
+/// # Returns
+/// - `Option<(Placement, i32)>` - The best placement and its score, or `None` if the piece cannot be dropped anywhere
+fn _search<B: BoardOps<Cell> + Clone>(
+    board: &B,
+    piece: &ActivePiece,
+    next: Option<TetrominoShape>,
+    config: &AiConfig,
+    nodes: &mut u32,
+) -> Option<(Placement, i32)> {
+    let mut placements = heapless::Vec::<Placement, MAX_PLACEMENTS>::new();
+    _for_each_placement(board, piece, |mut placement, scratch| {
+        *nodes += 1;
+        placement.score = config.weights.score(&Metrics::of(scratch), placement.lines);
+        placements
+            .push(placement)
+            .expect("a piece has at most MAX_PLACEMENTS placements");
+    });
+    let Some(next) = next else {
+        let mut best: Option<Placement> = None;
+        for placement in placements {
+            let better = match &best {
+                Some(best) => placement.score > best.score,
+                None => true,
+            };
+            if better {
+                best = Some(placement);
+            }
+        }
+        return best.map(|placement| (placement, placement.score));
+    };
+    // The placements are found in the order of their rotations and columns, which breaks the ties as before.
+    placements.sort_unstable_by_key(|placement| {
+        (Reverse(placement.score), placement.rotations, placement.col)
+    });
+    let next = _spawn(next, board.get_shape().col);
+    let mut best: Option<(Placement, i32)> = None;
+    for placement in placements.iter().take(config.beam as usize) {
+        let scratch = _place(board, piece, placement);
+        // A placement blocking out the next piece is worse than any other.
+        let mut score = i32::MIN;
+        if next.fits(&scratch) {
+            _for_each_placement(&scratch, &next, |after, placed| {
+                *nodes += 1;
+                let lines = placement.lines + after.lines;
+                score = score.max(config.weights.score(&Metrics::of(placed), lines));
+            });
+        }
+        let better = match &best {
+            Some((_, best)) => score > *best,
+            None => true,
+        };
+        if better {
+            best = Some((*placement, score));
+        }
+    }
+    best
+}
+
+/// Drop a piece in every rotation and column it can reach, walking to the left wall and then to the right wall.
+/// # Arguments
+/// - `board` - A reference to the board the piece is played on
+/// - `piece` - A reference to the `ActivePiece` to place
+/// - `visit` - The closure called with every `Placement`, scored 0, and the board after locking the piece there
+fn _for_each_placement<B: BoardOps<Cell> + Clone>(
+    board: &B,
+    piece: &ActivePiece,
+    mut visit: impl FnMut(Placement, &B),
+) {
+    let shape = piece.get_tetromino().shape;
+    for (index, rotations) in ROTATIONS.iter().enumerate() {
+        let mut rotated = piece.clone();
+        let rotates = rotations.iter().all(|action| match action {
+            Action::RotateCw => rotated.try_rotate_cw_with_kicks(board).is_rotated(),
+            _ => rotated.try_rotate_ccw_with_kicks(board).is_rotated(),
+        });
+        if !rotates {
+            continue;
+        }
+        let mut shifted = rotated.clone();
+        while shifted.try_shift_left(board) {}
+        loop {
+            let mut dropped = shifted.clone();
+            while dropped.try_drop(board) {}
+            let mut scratch = board.clone();
+            let lines = dropped.lock(&mut scratch, Cell::locked(shape)).lines;
+            let placement = Placement {
+                rotations: index,
+                from: rotated.get_coord().col,
+                rotation: shifted.get_tetromino().get_rotation(),
+                col: shifted.get_coord().col,
+                lines,
+                score: 0,
+            };
+            visit(placement, &scratch);
+            if !shifted.try_shift_right(board) {
+                break;
+            }
+        }
+    }
+}
+
+/// Lock a piece at a placement found by `_for_each_placement()`.
+/// # Arguments
+/// - `board` - A reference to the board the piece is played on
+/// - `piece` - A reference to the `ActivePiece` to place
+/// - `placement` - A reference to the `Placement`
+/// # Returns
+/// - `B` - A copy of the board with the piece locked and the completed rows cleared
+fn _place<B: BoardOps<Cell> + Clone>(board: &B, piece: &ActivePiece, placement: &Placement) -> B {
+    let mut placed = piece.clone();
+    for action in ROTATIONS[placement.rotations] {
+        match action {
+            Action::RotateCw => placed.try_rotate_cw_with_kicks(board),
+            _ => placed.try_rotate_ccw_with_kicks(board),
+        };
+    }
+    while placed.get_coord().col < placement.col && placed.try_shift_right(board) {}
+    while placed.get_coord().col > placement.col && placed.try_shift_left(board) {}
+    while placed.try_drop(board) {}
+    let mut scratch = board.clone();
+    placed.lock(&mut scratch, Cell::locked(piece.get_tetromino().shape));
+    scratch
+}
+
+/// Spawn a piece the way the game does, centered in the top row.
+/// # Arguments
+/// - `shape` - The shape of the piece
+/// - `cols` - The number of columns of the board
+/// # Returns
+/// - `ActivePiece` - The spawned piece
+pub(crate) fn _spawn(shape: TetrominoShape, cols: usize) -> ActivePiece {
+    let tetromino = Tetromino::from(shape);
+    let col = cols.saturating_sub(tetromino.get_shape().col) / 2;
+    ActivePiece::new(tetromino, Coordinate::from_array([0, col]))
+}
+
+/// The player of the attract mode, pressing one action every `ACTION_TICKS` ticks.
+///
+/// The placement is planned again before every action, so the piece falling meanwhile does not throw it off.
+/// # Attributes
+/// - `config` - The configuration of the planner
+/// - `wait` - The number of ticks until the next action
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Autopilot {
+    config: AiConfig,
+    wait: u8,
+}
+
+impl Autopilot {
+    /// Create an autopilot acting on the next tick.
+    /// # Arguments
+    /// - `config` - The `AiConfig` of the planner
+    /// # Returns
+    /// - `Autopilot` - The autopilot
+    pub fn new(config: AiConfig) -> Self {
+        Autopilot { config, wait: 0 }
+    }
+
+    /// Get the input of the next tick of the game.
+    /// # Arguments
+    /// - `board` - A reference to the board the piece is played on
+    /// - `piece` - A reference to the `ActivePiece` to place
+    /// - `lookahead` - A reference to the `Lookahead` of the pieces after the active piece
+    /// # Returns
+    /// - `InputState` - The input with the next action pressed, or without any action between actions
+    pub fn next_input<B: BoardOps<Cell> + Clone>(
+        &mut self,
+        board: &B,
+        piece: &ActivePiece,
+        lookahead: &Lookahead,
+    ) -> InputState {
+        if self.wait > 0 {
+            self.wait -= 1;
+            return InputState::default();
+        }
+        self.wait = ACTION_TICKS - 1;
+        match plan_ahead(board, piece, lookahead, &self.config) {
+            Some(plan) => InputState::from_action(plan.actions[0]),
+            None => InputState::from_action(Action::HardDrop),
+        }
+    }
+}
+
+impl Default for Autopilot {
+    fn default() -> Self {
+        Autopilot::new(AiConfig::default())
+    }
+}
+
+impl Policy for Autopilot {
+    /// Plan the placement again before every action, without the pauses of the attract mode.
+    fn decide(&mut self, game: &Game) -> Action {
+        let lookahead = Lookahead::of(game);
+        match plan_ahead(game.get_board(), game.get_piece(), &lookahead, &self.config) {
+            Some(plan) => plan.actions[0],
+            None => Action::HardDrop,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{_spawn, plan, plan_ahead, AiConfig, Autopilot, Lookahead, Metrics, Weights};
+    use crate::board::{BoardOps, FixedBoard};
+    use crate::cell::Cell;
+    use crate::coordinate::Coordinate;
+    use crate::game::{Cause, Game, GameState};
+    use crate::input::{Action, InputState};
+    use crate::piece::ActivePiece;
+    use crate::settings::{GameMode, Settings};
+    use crate::sim::run_headless;
+    use crate::tetrominoes::{Tetromino, TetrominoShape};
+
+    #[test]
+    fn test_metrics() {
+        // Create board:
+        //   0 1 2 3
+        // 0 . . . .
+        // 1 . # . .
+        // 2 # . . #
+        // Heights 1, 2, 0, 1 with a single hole below the second column.
+        let mut board = FixedBoard::<Cell, 3, 4>::new(Cell::Empty);
+        for coord in [[1, 1], [2, 0], [2, 3]] {
+            board.set(Coordinate::from_array(coord), Cell::Garbage);
+        }
+        let metrics = Metrics::of(&board);
+        assert_eq!(
+            metrics,
+            Metrics {
+                aggregate_height: 4,
+                holes: 1,
+                bumpiness: 1 + 2 + 1,
+                well_depth: 1,
+            }
+        );
+        assert_eq!(
+            Weights::CLASSIC.score(&metrics, 1),
+            761 - 4 * 510 - 357 - 4 * 184
+        );
+        let weights = Weights {
+            well: 250,
+            ..Weights::CLASSIC
+        };
+        assert_eq!(
+            weights.score(&metrics, 1),
+            761 - 4 * 510 - 357 - 4 * 184 + 250
+        );
+        // The reward stops growing beyond a well deep enough for the longest clear.
+        let deep = Metrics {
+            well_depth: 10,
+            ..Metrics::default()
+        };
+        assert_eq!(weights.score(&deep, 0), 4 * 250);
+    }
+
+    #[test]
+    fn test_plan_reaches_target() {
+        // Following the actions of the plan, the piece ends up at the planned column and rotation.
+        let mut board = FixedBoard::<Cell, 22, 10>::new(Cell::Empty);
+        for col in 0..9 {
+            board.set(Coordinate { row: 21, col }, Cell::Garbage);
+        }
+        for shape in [
+            TetrominoShape::I,
+            TetrominoShape::J,
+            TetrominoShape::L,
+            TetrominoShape::O,
+            TetrominoShape::S,
+            TetrominoShape::T,
+            TetrominoShape::Z,
+        ] {
+            let tetromino = Tetromino::from(shape);
+            let col = (10 - tetromino.get_shape().col) / 2;
+            let mut piece = ActivePiece::new(tetromino, Coordinate::from_array([0, col]));
+            let plan = plan(&board, &piece, &Weights::CLASSIC).unwrap();
+            assert_eq!(plan.actions.last(), Some(&Action::HardDrop));
+            for action in &plan.actions {
+                match action {
+                    Action::RotateCw => {
+                        assert!(piece.try_rotate_cw_with_kicks(&board).is_rotated())
+                    }
+                    Action::RotateCcw => {
+                        assert!(piece.try_rotate_ccw_with_kicks(&board).is_rotated())
+                    }
+                    Action::Left => assert!(piece.try_shift_left(&board)),
+                    Action::Right => assert!(piece.try_shift_right(&board)),
+                    _ => (),
+                }
+            }
+            assert_eq!(piece.get_coord().col, plan.col);
+            assert_eq!(piece.get_tetromino().get_rotation(), plan.rotation);
+            // The I piece fills the gap in the last column, standing upright.
+            if shape == TetrominoShape::I {
+                while piece.try_drop(&board) {}
+                assert_eq!(piece.lock(&mut board.clone(), Cell::Garbage).lines, 1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_survives() {
+        // On a seeded game the autopilot places at least 100 pieces without topping out.
+        let mut game = Game::new(Settings::default(), 157);
+        game.tick(&InputState::from_action(Action::Start));
+        let mut autopilot = Autopilot::default();
+        let mut pieces = 0;
+        while pieces < 100 {
+            let input = match game.get_state() {
+                GameState::Playing => {
+                    autopilot.next_input(game.get_board(), game.get_piece(), &Lookahead::default())
+                }
+                _ => InputState::default(),
+            };
+            let output = game.tick(&input);
+            assert!(!matches!(
+                output.state,
+                GameState::Sweep { .. } | GameState::GameOver(_)
+            ));
+            pieces += output.locked.is_some() as u32;
+        }
+        assert!(game.get_score().get_lines() > 30);
+    }
+
+    #[test]
+    fn test_lookahead() {
+        // On a regenerating cheese race at a high gravity, the one-piece lookahead blocks out, while also placing
+        // the next piece, with the option to hold, clears the cheese.
+        let settings = Settings {
+            mode: GameMode::Cheese {
+                rows: 10,
+                regenerate: true,
+            },
+            start_level: 10,
+            ..Settings::default()
+        };
+        let one = run_headless(&settings, 4, 150, &mut Autopilot::default());
+        assert_eq!(one.top_out, Some(Cause::BlockOut));
+        let two = run_headless(&settings, 4, 150, &mut Autopilot::new(AiConfig::ATTRACT));
+        assert_eq!(two.top_out, None);
+        assert!(two.pieces < 150);
+    }
+
+    #[test]
+    fn test_beam() {
+        // On an empty board, the T piece has 8 + 9 + 8 + 9 placements, and the O piece 9 in each rotation.
+        let board = FixedBoard::<Cell, 22, 10>::new(Cell::Empty);
+        let piece = _spawn(TetrominoShape::T, 10);
+        let lookahead = Lookahead {
+            queue: [Some(TetrominoShape::O), Some(TetrominoShape::I)],
+            held: None,
+            can_hold: false,
+        };
+        let one = plan_ahead(&board, &piece, &lookahead, &AiConfig::default()).unwrap();
+        assert_eq!(one.nodes, 34);
+        for beam in 1..=6 {
+            let config = AiConfig {
+                depth: 2,
+                beam,
+                weights: Weights::CLASSIC,
+            };
+            let two = plan_ahead(&board, &piece, &lookahead, &config).unwrap();
+            assert_eq!(two.nodes, 34 + beam as u32 * 36);
+        }
+        // Holding searches the O piece followed by the I piece too, within the bound of the beam.
+        let holding = Lookahead {
+            can_hold: true,
+            ..lookahead
+        };
+        let config = AiConfig::ATTRACT;
+        let two = plan_ahead(&board, &piece, &holding, &config).unwrap();
+        assert_eq!(two.nodes, 34 + 4 * 36 + 36 + 4 * 34);
+        assert!(two.nodes as usize <= 2 * (1 + config.beam as usize) * 4 * 10);
+    }
+
+    #[test]
+    fn test_hold() {
+        // With a gap at the left of the bottom row, which the S piece cannot reach, holding swaps in the I piece to
+        // fill it.
+        let mut board = FixedBoard::<Cell, 22, 10>::new(Cell::Empty);
+        for col in 1..10 {
+            board.set(Coordinate { row: 21, col }, Cell::Garbage);
+        }
+        let piece = _spawn(TetrominoShape::S, 10);
+        let lookahead = Lookahead {
+            queue: [Some(TetrominoShape::O), Some(TetrominoShape::T)],
+            held: Some(TetrominoShape::I),
+            can_hold: true,
+        };
+        for config in [AiConfig::default(), AiConfig::ATTRACT] {
+            let plan = plan_ahead(&board, &piece, &lookahead, &config).unwrap();
+            assert_eq!(plan.actions[0], Action::Hold);
+            assert_eq!(plan.actions.last(), Some(&Action::HardDrop));
+        }
+        // Without the option to hold, the S piece is placed.
+        let locked = Lookahead {
+            can_hold: false,
+            ..lookahead
+        };
+        let plan = plan_ahead(&board, &piece, &locked, &AiConfig::ATTRACT).unwrap();
+        assert_ne!(plan.actions[0], Action::Hold);
+    }
+}
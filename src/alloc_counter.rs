@@ -0,0 +1,143 @@
+//! A global allocator counting the allocations of the current thread, to keep the per-frame paths free of them.
+//!
+//! The policy of the crate: setting up a game may allocate, e.g. the board, the replay or the menu, but a tick and
+//! a render never do. Per-frame code writes into buffers owned by the caller, or returns `heapless` collections and
+//! fixed arrays. Only built for the tests with the `alloc-counter` feature, e.g.
+//! `cargo test --features alloc-counter alloc_counter`.
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+std::thread_local! {
+    /// Whether the allocations of this thread are counted.
+    static COUNTING: Cell<bool> = const { Cell::new(false) };
+    /// The number of allocations of this thread while counting.
+    static COUNT: Cell<usize> = const { Cell::new(0) };
+}
+
+/// The system allocator, counting the allocations and reallocations of the threads that count.
+struct Counter;
+
+unsafe impl GlobalAlloc for Counter {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        _count();
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        _count();
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        _count();
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static COUNTER: Counter = Counter;
+
+/// Count an allocation if this thread counts, ignoring threads that are shutting down.
+fn _count() {
+    let _ = COUNTING.try_with(|counting| {
+        if counting.get() {
+            let _ = COUNT.try_with(|count| count.set(count.get() + 1));
+        }
+    });
+}
+
+/// Run a closure, counting the allocations it makes on this thread.
+/// # Arguments
+/// - `f` - The closure
+/// # Returns
+/// - `(R, usize)` - The result of the closure and the number of allocations it made
+pub fn count_allocations<R>(f: impl FnOnce() -> R) -> (R, usize) {
+    COUNT.with(|count| count.set(0));
+    COUNTING.with(|counting| counting.set(true));
+    let result = f();
+    COUNTING.with(|counting| counting.set(false));
+    (result, COUNT.with(|count| count.get()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::count_allocations;
+    use crate::ai::{AiConfig, Autopilot, Lookahead};
+    use crate::board::{rle_max_bytes, Board, BoardOps, FixedBoard};
+    use crate::cell::Cell;
+    use crate::coordinate::Coordinate;
+    use crate::framebuffer::FrameBuffer;
+    use crate::game::{Game, GameState};
+    use crate::input::{Action, InputState};
+    use crate::settings::Settings;
+    use std::vec::Vec;
+
+    #[test]
+    fn test_count() {
+        let (vec, count) = count_allocations(|| Vec::<u8>::with_capacity(4));
+        assert_eq!((vec.capacity(), count), (4, 1));
+        assert_eq!(count_allocations(|| 1 + 1), (2, 0));
+    }
+
+    #[test]
+    fn test_ticks() {
+        // Play 1000 ticks and render every one of them, as the firmware does, without allocating after setting up:
+        // the splash and the menu first, then the autopilot playing with incoming garbage, recording its inputs.
+        let mut game = Game::with_board(
+            FixedBoard::<Cell, 22, 10>::new(Cell::Empty),
+            Settings::default(),
+            12,
+        );
+        game.set_recording(true);
+        let mut frame = FrameBuffer::<2048>::new(Coordinate::from_array([64, 32]));
+        let mut autopilot = Autopilot::new(AiConfig::ATTRACT);
+        let (pieces, count) = count_allocations(|| {
+            let mut pieces = 0;
+            for tick in 0..1000 {
+                let input = match (tick, game.get_state()) {
+                    (200, _) => InputState::from_action(Action::Start),
+                    (_, GameState::Playing) => {
+                        let lookahead = Lookahead::of(&game);
+                        autopilot.next_input(game.get_board(), game.get_piece(), &lookahead)
+                    }
+                    _ => InputState::default(),
+                };
+                if tick % 150 == 0 {
+                    game.receive_garbage(1);
+                }
+                pieces += game.tick(&input).locked.is_some() as u32;
+                game.render_into(&mut frame);
+            }
+            pieces
+        });
+        assert_eq!(game.get_state(), GameState::Playing);
+        assert!(pieces > 10);
+        assert_eq!(count, 0);
+    }
+
+    #[test]
+    fn test_rle() {
+        // Encoding a board into a buffer and decoding it back, both as runs and as raw bits, writes in place.
+        let mut buffer = [0; rle_max_bytes(20, 10)];
+        let mut decoded = Board::new(Coordinate::from_array([20, 10]), false);
+        let bottom_row = |coord: Coordinate| coord.row == 19;
+        let checkerboard = |coord: Coordinate| (coord.row + coord.col) % 2 == 1;
+        for filled in [bottom_row, checkerboard] {
+            let mut board = Board::new(Coordinate::from_array([20, 10]), false);
+            for (coord, _) in decoded.cells() {
+                board.set(coord, filled(coord));
+            }
+            let (decoded_len, count) = count_allocations(|| {
+                let len = board.encode_rle_into(&mut buffer).unwrap();
+                decoded.decode_rle(&buffer[..len], true)
+            });
+            assert!(decoded_len.is_ok());
+            assert!(decoded.cells().eq(board.cells()));
+            assert_eq!(count, 0);
+        }
+    }
+}
@@ -0,0 +1,251 @@
+//! Base64 of RFC 4648 with padding, to paste binary data like a `Replay` into text, e.g. an issue.
+//!
+//! Both directions write into buffers of the caller, so they run without the heap. The encoder takes the bytes in
+//! pieces, to encode data serialized field by field without collecting it first.
+
+/// The characters of the 64 values of 6 bits.
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// The character padding the last group of 4 characters.
+const PAD: u8 = b'=';
+
+/// Why bytes could not be encoded or decoded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Base64Error {
+    /// The buffer is shorter than the output, with the number of bytes of the output.
+    BufferTooSmall(usize),
+    /// The character at the index is no base64, or padding in the wrong place.
+    Character(usize),
+    /// The text ends within a group of 4 characters, with the number of characters without whitespace.
+    Truncated(usize),
+}
+
+/// Get the number of characters encoding a number of bytes.
+/// # Arguments
+/// - `bytes` - The number of bytes
+/// # Returns
+/// - `usize` - The number of characters, including the padding
+pub const fn encoded_len(bytes: usize) -> usize {
+    match bytes % 3 {
+        0 => bytes / 3 * 4,
+        _ => bytes / 3 * 4 + 4,
+    }
+}
+
+/// An encoder writing base64 into a buffer, fed bytes in any number of pieces.
+/// # Attributes
+/// - `out` - The buffer to write the characters into
+/// - `len` - The number of characters written
+/// - `carry` - The bytes of an incomplete group of 3, waiting for the next piece
+/// - `carried` - The number of bytes in `carry`
+/// - `fits` - Whether every character so far fit the buffer
+pub struct Encoder<'a> {
+    out: &'a mut [u8],
+    len: usize,
+    carry: [u8; 3],
+    carried: usize,
+    fits: bool,
+}
+
+impl<'a> Encoder<'a> {
+    /// Create an encoder without any bytes.
+    /// # Arguments
+    /// - `out` - The buffer to write into, at least `encoded_len()` of all bytes long
+    /// # Returns
+    /// - `Encoder<'a>` - The encoder, writing from the start of the buffer
+    pub fn new(out: &'a mut [u8]) -> Self {
+        Encoder {
+            out,
+            len: 0,
+            carry: [0; 3],
+            carried: 0,
+            fits: true,
+        }
+    }
+
+    /// Encode the next piece of the bytes.
+    /// # Arguments
+    /// - `bytes` - The bytes following the previous piece
+    pub fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.carry[self.carried] = *byte;
+            self.carried += 1;
+            if self.carried == 3 {
+                self._group(3);
+            }
+        }
+    }
+
+    /// Encode the last incomplete group with padding.
+    /// # Returns
+    /// - `Result<usize, Base64Error>` - The number of characters written, or `BufferTooSmall` with the number of
+    ///   characters of all bytes
+    pub fn finish(mut self) -> Result<usize, Base64Error> {
+        if self.carried > 0 {
+            self.carry[self.carried..].fill(0);
+            self._group(self.carried);
+        }
+        match self.fits {
+            true => Ok(self.len),
+            false => Err(Base64Error::BufferTooSmall(self.len)),
+        }
+    }
+
+    /// Write the 4 characters of the carried bytes, padding the characters past `bytes` of them.
+    fn _group(&mut self, bytes: usize) {
+        let bits = u32::from_be_bytes([0, self.carry[0], self.carry[1], self.carry[2]]);
+        for index in 0..4 {
+            let char = match index <= bytes {
+                true => ALPHABET[(bits >> (18 - 6 * index) & 0x3F) as usize],
+                false => PAD,
+            };
+            match self.out.get_mut(self.len) {
+                Some(slot) => *slot = char,
+                None => self.fits = false,
+            }
+            self.len += 1;
+        }
+        self.carried = 0;
+    }
+}
+
+/// Encode bytes at once, see `Encoder`.
+/// # Arguments
+/// - `bytes` - The bytes to encode
+/// - `out` - The buffer to write into, at least `encoded_len()` of the bytes long
+/// # Returns
+/// - `Result<usize, Base64Error>` - The number of characters written, or `BufferTooSmall`
+pub fn encode_into(bytes: &[u8], out: &mut [u8]) -> Result<usize, Base64Error> {
+    let mut encoder = Encoder::new(out);
+    encoder.write(bytes);
+    encoder.finish()
+}
+
+/// Decode base64 text, skipping whitespace so the text can be broken into lines.
+/// # Arguments
+/// - `text` - The characters
+/// - `out` - The buffer to write the bytes into
+/// # Returns
+/// - `Result<usize, Base64Error>` - The number of bytes written, or an error if the text is no base64 or does not fit
+///   the buffer
+pub fn decode_into(text: &[u8], out: &mut [u8]) -> Result<usize, Base64Error> {
+    let mut len = 0;
+    let mut fits = true;
+    let mut group = [0; 4];
+    let mut grouped = 0;
+    let mut pads = 0;
+    let mut chars = 0;
+    for (index, char) in text.iter().enumerate() {
+        if char.is_ascii_whitespace() {
+            continue;
+        }
+        // Padding only ends the last group, after at least 2 characters.
+        group[grouped] = match (*char, pads) {
+            (PAD, _) if grouped >= 2 => {
+                pads += 1;
+                0
+            }
+            (_, 1..) => return Err(Base64Error::Character(index)),
+            (char, 0) => ALPHABET
+                .iter()
+                .position(|letter| *letter == char)
+                .ok_or(Base64Error::Character(index))? as u32,
+        };
+        grouped += 1;
+        chars += 1;
+        if grouped < 4 {
+            continue;
+        }
+        let bits = group.iter().fold(0, |bits, value| bits << 6 | value);
+        for byte in &bits.to_be_bytes()[1..4 - pads] {
+            match out.get_mut(len) {
+                Some(slot) => *slot = *byte,
+                None => fits = false,
+            }
+            len += 1;
+        }
+        grouped = 0;
+    }
+    match (grouped, fits) {
+        (0, true) => Ok(len),
+        (0, false) => Err(Base64Error::BufferTooSmall(len)),
+        _ => Err(Base64Error::Truncated(chars)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_into, encode_into, encoded_len, Base64Error, Encoder};
+
+    #[test]
+    fn test_vectors() {
+        // The test vectors of RFC 4648, both ways.
+        let vectors = [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ];
+        for (bytes, text) in vectors {
+            let mut out = [0; 8];
+            let len = encode_into(bytes.as_bytes(), &mut out).unwrap();
+            assert_eq!(
+                (&out[..len], len),
+                (text.as_bytes(), encoded_len(bytes.len()))
+            );
+            let len = decode_into(text.as_bytes(), &mut out).unwrap();
+            assert_eq!(&out[..len], bytes.as_bytes());
+        }
+        // Pieces of any size encode like the whole, and lines decode like a single one.
+        let bytes: Vec<u8> = (0..=255).collect();
+        let mut whole = [0; encoded_len(256)];
+        encode_into(&bytes, &mut whole).unwrap();
+        let mut pieces = [0; encoded_len(256)];
+        let mut encoder = Encoder::new(&mut pieces);
+        for piece in bytes.chunks(7) {
+            encoder.write(piece);
+        }
+        assert_eq!(encoder.finish(), Ok(whole.len()));
+        assert_eq!(pieces, whole);
+        let lines: Vec<u8> = whole
+            .chunks(76)
+            .flat_map(|line| line.iter().chain(b"\r\n"))
+            .copied()
+            .collect();
+        let mut decoded = [0; 256];
+        assert_eq!(decode_into(&lines, &mut decoded), Ok(256));
+        assert_eq!(decoded[..], bytes[..]);
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut out = [0; 4];
+        assert_eq!(
+            encode_into(b"fooba", &mut out),
+            Err(Base64Error::BufferTooSmall(8))
+        );
+        assert_eq!(
+            decode_into(b"Zm9vYmFy", &mut out),
+            Err(Base64Error::BufferTooSmall(6))
+        );
+        assert_eq!(
+            decode_into(b"Zm9v!", &mut out),
+            Err(Base64Error::Character(4))
+        );
+        assert_eq!(
+            decode_into(b"Z===", &mut out),
+            Err(Base64Error::Character(1))
+        );
+        assert_eq!(
+            decode_into(b"Zg==Zg==", &mut out),
+            Err(Base64Error::Character(4))
+        );
+        assert_eq!(
+            decode_into(b"Zm9", &mut out),
+            Err(Base64Error::Truncated(3))
+        );
+    }
+}
@@ -0,0 +1,273 @@
+//! The firmware of the RP2040, only setting up the hardware and running the main loop.
+//!
+//! All game logic lives in the library, so it is tested on the host without linking the HAL.
+#![no_std]
+#![no_main]
+
+use core::panic::PanicInfo;
+use linked_list_allocator::LockedHeap;
+use rp2040_hal as hal;
+
+use hal::dma::DMAExt;
+use hal::multicore::{Multicore, Stack};
+use hal::pac;
+use hal::pio::PIOExt;
+use hal::rosc::RingOscillator;
+use hal::{Clock, Sio, Timer, Watchdog};
+use rust_tetris_rp2040::board::FixedBoard;
+use rust_tetris_rp2040::cell::Cell;
+use rust_tetris_rp2040::clock::TICK_HZ;
+use rust_tetris_rp2040::console::UsbConsole;
+use rust_tetris_rp2040::coordinate::Coordinate;
+use rust_tetris_rp2040::crash::show_crash;
+use rust_tetris_rp2040::effects::{Particles, PARTICLES};
+use rust_tetris_rp2040::events::GameEvent;
+use rust_tetris_rp2040::framebuffer::{FrameBuffer, PanelLayout};
+use rust_tetris_rp2040::game::Game;
+use rust_tetris_rp2040::input::buttons::Buttons;
+use rust_tetris_rp2040::loop_timing::{FixedTimestep, TickWatchdog};
+use rust_tetris_rp2040::power::Doze;
+use rust_tetris_rp2040::profiler::{Phase, Profiler};
+use rust_tetris_rp2040::render::Shared;
+use rust_tetris_rp2040::rng::rosc::seed_from_rosc_and_timer;
+use rust_tetris_rp2040::sound::{Buzzer, Sequencer};
+use rust_tetris_rp2040::storage::{Persisted, RecordStore, XipFlash, MAX_REPLAY_BYTES};
+use rust_tetris_rp2040::ws2812::{PingPong, Ws2812};
+use usb_device::bus::UsbBusAllocator;
+
+/// The second stage bootloader, for the W25Q080 flash of the Raspberry Pi Pico.
+#[link_section = ".boot2"]
+#[used]
+pub static BOOT2_FIRMWARE: [u8; 256] = rp2040_boot2::BOOT_LOADER_W25Q080;
+
+/// The frequency of the external crystal.
+const XTAL_FREQ_HZ: u32 = 12_000_000;
+
+/// The frequency of the system clock set up by `init_clocks_and_plls()`, for the panic handler.
+const SYS_HZ: u32 = 125_000_000;
+
+/// The number of bytes of RAM reserved for the heap of the game.
+const HEAP_BYTES: usize = 64 * 1024;
+
+/// The playfield, 20 visible rows with 2 spawn rows above them, stored without the heap.
+type Playfield = FixedBoard<Cell, 22, 10>;
+
+const PANEL_ROWS: usize = 64;
+const PANEL_COLS: usize = 32;
+const PANEL_PIXELS: usize = PANEL_ROWS * PANEL_COLS;
+
+/// The GPIO pin of the data line of the LEDs.
+const LED_PIN: usize = 16;
+
+/// The number of samples a button has to be stable, sampling once per frame.
+const DEBOUNCE_SAMPLES: u8 = 2;
+
+#[global_allocator]
+static HEAP: LockedHeap = LockedHeap::empty();
+
+/// The latest frame of the game on core 0, sent to the LEDs by core 1.
+static FRAME: Shared<PANEL_PIXELS> = Shared::new(Coordinate {
+    row: PANEL_ROWS,
+    col: PANEL_COLS,
+});
+
+/// The stack of core 1 of 8192 words, large enough for the copies of a frame made while drawing it.
+static CORE1_STACK: Stack<8192> = Stack::new();
+
+/// Show the crash screen with the line of the panic, then let the watchdog reboot the board.
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    let dims = Coordinate::from_array([PANEL_ROWS, PANEL_COLS]);
+    let line = info.location().map(|location| location.line());
+    show_crash(LED_PIN, dims, PanelLayout::RowMajor, line, SYS_HZ)
+}
+
+#[hal::entry]
+fn main() -> ! {
+    static mut HEAP_MEMORY: [u8; HEAP_BYTES] = [0; HEAP_BYTES];
+    static mut USB_BUS: Option<UsbBusAllocator<hal::usb::UsbBus>> = None;
+    static mut LED_FRAME: Option<FrameBuffer<PANEL_PIXELS>> = None;
+    static mut DMA_BUFFERS: [[u32; PANEL_PIXELS]; 2] = [[0; PANEL_PIXELS]; 2];
+    unsafe { HEAP.lock().init(HEAP_MEMORY.as_mut_ptr(), HEAP_BYTES) };
+
+    let mut pac = pac::Peripherals::take().expect("the peripherals are taken once");
+    let mut watchdog = Watchdog::new(pac.WATCHDOG);
+    let clocks = hal::clocks::init_clocks_and_plls(
+        XTAL_FREQ_HZ,
+        pac.XOSC,
+        pac.CLOCKS,
+        pac.PLL_SYS,
+        pac.PLL_USB,
+        &mut pac.RESETS,
+        &mut watchdog,
+    )
+    .unwrap_or_else(|_| panic!("the crystal starts and the PLLs lock"));
+    let mut timer = Timer::new(pac.TIMER, &mut pac.RESETS, &clocks);
+    let mut doze = Doze::new(timer.alarm_0().expect("alarm 0 is taken once"));
+    let mut sio = Sio::new(pac.SIO);
+    let pins = hal::gpio::Pins::new(
+        pac.IO_BANK0,
+        pac.PADS_BANK0,
+        sio.gpio_bank0,
+        &mut pac.RESETS,
+    );
+    let button_pins = [
+        pins.gpio2.into_pull_up_input().into_dyn_pin(),
+        pins.gpio3.into_pull_up_input().into_dyn_pin(),
+        pins.gpio4.into_pull_up_input().into_dyn_pin(),
+        pins.gpio5.into_pull_up_input().into_dyn_pin(),
+        pins.gpio6.into_pull_up_input().into_dyn_pin(),
+        pins.gpio7.into_pull_up_input().into_dyn_pin(),
+        pins.gpio8.into_pull_up_input().into_dyn_pin(),
+        pins.gpio9.into_pull_up_input().into_dyn_pin(),
+        pins.gpio10.into_pull_up_input().into_dyn_pin(),
+    ];
+    let (mut pio, sm0, _, _, _) = pac.PIO0.split(&mut pac.RESETS);
+    let leds = Ws2812::new(&mut pio, sm0, pins.gpio16.into_function(), &clocks);
+    let dma = pac.DMA.split(&mut pac.RESETS);
+    let pwm_slices = hal::pwm::Slices::new(pac.PWM, &mut pac.RESETS);
+    let sys_hz = clocks.system_clock.freq().to_Hz();
+    let mut buzzer = Buzzer::new(pwm_slices.pwm1, pins.gpio18, sys_hz);
+
+    // The debug console on the USB port, taking the USB clock so it comes after the other users of the clocks.
+    let usb_bus = USB_BUS.insert(UsbBusAllocator::new(hal::usb::UsbBus::new(
+        pac.USBCTRL_REGS,
+        pac.USBCTRL_DPRAM,
+        clocks.usb_clock,
+        true,
+        &mut pac.RESETS,
+    )));
+    let mut console = UsbConsole::new(usb_bus);
+
+    // Seed the game from the jitter of the ring oscillator, so every power-up deals other pieces.
+    let mut rosc = RingOscillator::new(pac.ROSC).initialize();
+    let seed = seed_from_rosc_and_timer(&mut rosc, &timer);
+
+    // The settings, high scores, sprint times and lifetime totals of the previous power cycles, or the defaults on a
+    // fresh or corrupted flash.
+    let mut store = RecordStore::new(XipFlash::new());
+    let settings = store.load_settings().unwrap_or_default();
+    let mut game = Game::with_board(Playfield::new(Cell::Empty), settings, seed);
+    let mut persisted = Persisted::restore(&store, &mut game);
+    game.set_lifetime_stats(store.load_lifetime_stats());
+    game.set_recording(true);
+    // The replay frozen on the last game over stays available to `dump` after a power cycle.
+    if let Some(replay) = store.load_replay() {
+        game.set_frozen_replay(replay);
+    }
+    // The buttons are numbered from GPIO 2, the order of the default key map.
+    let mut buttons = Buttons::new(button_pins, settings.keymap, DEBOUNCE_SAMPLES);
+
+    // Sending a frame to the LEDs takes longer than a tick, so core 1 sends the latest frame while core 0 keeps
+    // ticking. A DMA channel streams every frame while core 1 copies and encodes the next one.
+    let frame = LED_FRAME.insert(FrameBuffer::new(Coordinate::from_array([
+        PANEL_ROWS, PANEL_COLS,
+    ])));
+    let [first, second] = DMA_BUFFERS;
+    let mut multicore = Multicore::new(&mut pac.PSM, &mut pac.PPB, &mut sio.fifo);
+    multicore.cores()[1]
+        .spawn(
+            CORE1_STACK
+                .take()
+                .expect("the stack of core 1 is taken once"),
+            move || {
+                let mut leds = leds.into_dma(dma.ch0);
+                let mut buffers = PingPong::new(&mut first[..], &mut second[..]);
+                let mut sent = 0;
+                loop {
+                    if FRAME.get_frames() != sent {
+                        let snapshot = FRAME.latest();
+                        sent = snapshot.frame;
+                        snapshot.draw_into(frame);
+                        frame.encode_dma_buffer(&settings, buffers.get_back_mut());
+                        if let Some(previous) = leds.wait() {
+                            buffers.release(previous);
+                        }
+                        let buffer = buffers
+                            .send()
+                            .expect("a buffer is released before every send");
+                        leds.start_dma(buffer)
+                            .expect("the previous transfer finished");
+                    }
+                }
+            },
+        )
+        .expect("core 1 starts");
+    // Reboot when the game stops ticking, e.g. stuck in a loop, rather than freezing the LEDs.
+    let watchdog = TickWatchdog::arm(watchdog, TICK_HZ);
+    let mut clock = || timer.get_counter().ticks();
+    let mut timestep = FixedTimestep::new(TICK_HZ);
+    // Without the `profiling` feature, the profiler and all its calls compile away.
+    let mut profiler = Profiler::new(|| timer.get_counter().ticks());
+    let mut sequencer = Sequencer::new();
+    let mut particles = Particles::<PARTICLES>::new(seed);
+    let mut blank = false;
+    loop {
+        console.poll(&mut game, &profiler);
+        profiler.enter(Phase::Input);
+        buttons.sample();
+        profiler.exit(Phase::Input);
+        // Catch up on the ticks missed while the loop was busy, e.g. answering the console.
+        // Only the first of them sees the buttons pressed, the others see them held.
+        let ticks = timestep.poll(&mut clock);
+        watchdog.feed(ticks);
+        for _ in 0..ticks {
+            profiler.enter(Phase::Logic);
+            // Follow the key map of the menu, which the games start with, e.g. right after remapping.
+            buttons.set_keymap(game.get_menu().get_settings().keymap);
+            let output = game.tick(&buttons.next_input());
+            sequencer.set_finesse(game.get_settings().finesse);
+            output.dispatch(&mut sequencer);
+            output.dispatch(&mut particles);
+            // Once a game ends, its totals are in the lifetime totals, so they are written to flash once per game.
+            // Every write completes within the tick of its event, so a game abandoned with the reset chord leaves
+            // none pending: an ended game was stored when it ended, one in progress is not counted.
+            let ended = output.events.iter().any(|event| {
+                matches!(
+                    event,
+                    GameEvent::GameOver { .. } | GameEvent::Finished { .. }
+                )
+            });
+            if ended && !game.is_attract() {
+                store.store_lifetime_stats(game.get_lifetime_stats()).ok();
+            }
+            // An entered name or an option changed in the menu is stored on the tick it changes.
+            persisted.sync(&mut store, &game).ok();
+            // The most recent part of a frozen replay fitting its record is kept for a bug report.
+            let frozen = output
+                .events
+                .iter()
+                .any(|event| matches!(event, GameEvent::ReplayFrozen { .. }));
+            if frozen {
+                if let Some(replay) = game.get_recorder().get_frozen_within(MAX_REPLAY_BYTES) {
+                    store.store_replay(&replay).ok();
+                }
+            }
+            buzzer.set_frequency(sequencer.tick());
+            particles.tick();
+            profiler.exit(Phase::Logic);
+        }
+        if ticks > 0 {
+            // A black panel is sent once, the LEDs keep it without new frames.
+            let black = game.get_inactivity().get_level() == 0;
+            if !(blank && black) {
+                profiler.enter(Phase::Render);
+                let mut frame = FrameBuffer::<PANEL_PIXELS>::new(Coordinate::from_array([
+                    PANEL_ROWS, PANEL_COLS,
+                ]));
+                game.render_into(&mut frame);
+                particles.draw(&mut frame, game.get_layout());
+                profiler.exit(Phase::Render);
+                profiler.enter(Phase::Flush);
+                FRAME.publish_frame(&frame);
+                profiler.exit(Phase::Flush);
+            }
+            blank = black;
+            profiler.end_frame();
+        }
+        // While the panel is black, halt the core until the next tick instead of spinning.
+        if blank {
+            doze.until(&timer, timestep.get_next_deadline());
+        }
+    }
+}
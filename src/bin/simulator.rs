@@ -0,0 +1,433 @@
+//! The desktop simulator, playing the game of the firmware in a terminal.
+//!
+//! Every tick runs the same `Game::tick()` at the same rate as the firmware, with the same settings, and the events
+//! drive the same buzzer sequencer. The frame the LEDs would show is drawn with half blocks in true color, next to
+//! the hold slot and the score. Once a game ends, the cells the pieces locked in are drawn as a heatmap over the
+//! board, from blue for the rarely used cells to red for the most used.
+//!
+//! Terminals reporting key releases hold an action until its key goes up, like a button, so the auto-repeat
+//! behaves like on the hardware. On other terminals every key press, including the repeats of the terminal, is a
+//! tap of a single tick.
+//!
+//! `--replay <file>` plays back a replay instead of the keyboard, `--record <file>` saves the game played. The replay
+//! is either the bytes of a recording or the base64 lines of a `dump` of the console, pasted into a file, which
+//! starts mid-game from its first keyframe.
+//! `--width <cols>` and `--height <rows>` play on another board size, a replay keeps the size it was recorded on.
+use std::fs;
+use std::io::{self, Stdout, Write};
+use std::path::PathBuf;
+use std::process;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{
+    self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+    PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use crossterm::style::{Color, Colors, Print, ResetColor, SetColors, SetForegroundColor};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+use rust_tetris_rp2040::clock::TICK_HZ;
+use rust_tetris_rp2040::color::Rgb;
+use rust_tetris_rp2040::coordinate::Coordinate;
+use rust_tetris_rp2040::effects::{Particles, PARTICLES};
+use rust_tetris_rp2040::framebuffer::FrameBuffer;
+use rust_tetris_rp2040::game::{Game, GameState};
+use rust_tetris_rp2040::heatmap::PlacementHeatmap;
+use rust_tetris_rp2040::input::InputState;
+use rust_tetris_rp2040::loop_timing::FixedTimestep;
+use rust_tetris_rp2040::replay::{Replay, KEYFRAME_PIECES, MAX_REPLAY_WORDS};
+use rust_tetris_rp2040::settings::Settings;
+use rust_tetris_rp2040::sound::Sequencer;
+use rust_tetris_rp2040::tetrominoes::Tetromino;
+
+const PANEL_ROWS: usize = 64;
+const PANEL_COLS: usize = 32;
+
+/// The terminal column of the hold slot and the score, right of the panel.
+const SIDE_COL: u16 = PANEL_COLS as u16 + 3;
+
+const USAGE: &str = "usage: simulator [--seed <seed>] [--replay <file>] [--record <file>] \
+                     [--width <cols>] [--height <rows>]";
+
+/// The keys of the controls, shown next to the panel.
+const KEYS: [&str; 7] = [
+    "←/→   shift",
+    "↑/x   rotate cw",
+    "z     rotate ccw",
+    "↓     soft drop",
+    "space hard drop",
+    "c     hold",
+    "p  enter  q",
+];
+
+/// The options of the command line.
+/// # Attributes
+/// - `seed` - The seed of the game, random if not given
+/// - `replay` - The replay to play back instead of the keyboard
+/// - `record` - The file to save the replay of the game to
+/// - `settings` - The settings to play with, of the board size given
+struct Options {
+    seed: Option<u64>,
+    replay: Option<PathBuf>,
+    record: Option<PathBuf>,
+    settings: Settings,
+}
+
+impl Options {
+    /// Parse the command line arguments.
+    /// # Returns
+    /// - `Result<Options, String>` - The options, or the error to show with the usage
+    fn parse() -> Result<Self, String> {
+        let mut options = Options {
+            seed: None,
+            replay: None,
+            record: None,
+            settings: Settings::default(),
+        };
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            let value = args
+                .next()
+                .ok_or_else(|| format!("missing the value of {}", arg))?;
+            match arg.as_str() {
+                "--seed" => {
+                    options.seed = Some(
+                        value
+                            .parse()
+                            .map_err(|_| format!("invalid seed {}", value))?,
+                    )
+                }
+                "--replay" => options.replay = Some(PathBuf::from(value)),
+                "--record" => options.record = Some(PathBuf::from(value)),
+                "--width" => {
+                    options.settings.cols = value
+                        .parse()
+                        .map_err(|_| format!("invalid width {}", value))?
+                }
+                "--height" => {
+                    options.settings.rows = value
+                        .parse()
+                        .map_err(|_| format!("invalid height {}", value))?
+                }
+                _ => return Err(format!("unknown argument {}", arg)),
+            }
+        }
+        options
+            .settings
+            .validate()
+            .map_err(|error| format!("invalid board size: {}", error))?;
+        Ok(options)
+    }
+}
+
+/// The terminal in raw mode on the alternate screen, restored when dropped.
+/// # Attributes
+/// - `stdout` - The standard output
+/// - `releases` - Whether (`true`) or not (`false`) the terminal reports key releases
+struct Terminal {
+    stdout: Stdout,
+    releases: bool,
+}
+
+impl Terminal {
+    /// Switch the terminal to raw mode and the alternate screen, and ask for key releases where supported.
+    fn enter() -> io::Result<Self> {
+        let mut stdout = io::stdout();
+        terminal::enable_raw_mode()?;
+        execute!(stdout, EnterAlternateScreen, Hide, Clear(ClearType::All))?;
+        let releases = terminal::supports_keyboard_enhancement().unwrap_or(false);
+        if releases {
+            execute!(
+                stdout,
+                PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+            )?;
+        }
+        Ok(Terminal { stdout, releases })
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        if self.releases {
+            execute!(self.stdout, PopKeyboardEnhancementFlags).ok();
+        }
+        execute!(self.stdout, ResetColor, Show, LeaveAlternateScreen).ok();
+        terminal::disable_raw_mode().ok();
+    }
+}
+
+/// The keys held on the keyboard, numbered like the buttons of the firmware so the default `KeyMap` fits both.
+/// # Attributes
+/// - `held` - The bit set of held keys, bit `n` for input `n` of the `KeyMap`
+/// - `releases` - Whether (`true`) or not (`false`) key releases are reported, otherwise every press is a tap
+struct Keyboard {
+    held: u16,
+    releases: bool,
+}
+
+impl Keyboard {
+    /// Press or release the input of a key.
+    /// # Arguments
+    /// - `key` - The `KeyEvent` from the terminal
+    fn update(&mut self, key: &KeyEvent) {
+        let input = match key.code {
+            KeyCode::Left => 0,
+            KeyCode::Right => 1,
+            KeyCode::Up | KeyCode::Char('x') => 2,
+            KeyCode::Char('z') => 3,
+            KeyCode::Down => 4,
+            KeyCode::Char(' ') => 5,
+            KeyCode::Char('c') => 6,
+            KeyCode::Char('p') | KeyCode::Esc => 7,
+            KeyCode::Enter => 8,
+            _ => return,
+        };
+        match key.kind {
+            KeyEventKind::Release => self.held &= !(1 << input),
+            _ => self.held |= 1 << input,
+        }
+    }
+
+    /// Get the keys held during the next tick, forgetting the taps without key releases.
+    fn take_held(&mut self) -> u16 {
+        let held = self.held;
+        if !self.releases {
+            self.held = 0;
+        }
+        held
+    }
+}
+
+/// Convert a color to the true color of the terminal.
+fn _color(rgb: Rgb) -> Color {
+    let [r, g, b] = rgb.to_array();
+    Color::Rgb { r, g, b }
+}
+
+/// Draw the frame two panel rows per terminal row, the upper in the foreground of a half block.
+fn _draw_frame<W: Write, const N: usize>(out: &mut W, frame: &FrameBuffer<N>) -> io::Result<()> {
+    for line in 0..PANEL_ROWS / 2 {
+        queue!(out, MoveTo(0, line as u16))?;
+        for col in 0..PANEL_COLS {
+            let pixel = |row| {
+                frame
+                    .get_pixel(Coordinate { row, col })
+                    .unwrap_or(Rgb::BLACK)
+            };
+            let colors = Colors::new(_color(pixel(2 * line)), _color(pixel(2 * line + 1)));
+            queue!(out, SetColors(colors), Print('▀'))?;
+        }
+        queue!(out, ResetColor)?;
+    }
+    Ok(())
+}
+
+/// Draw the hold slot, the score and the controls right of the panel.
+fn _draw_side<W: Write>(
+    out: &mut W,
+    game: &Game,
+    note: Option<u16>,
+    status: &str,
+) -> io::Result<()> {
+    queue!(out, MoveTo(SIDE_COL, 0), Print("HOLD"))?;
+    let hold = game.get_hold().get_shape().map(Tetromino::from);
+    for row in 0..4 {
+        queue!(out, MoveTo(SIDE_COL, 1 + row as u16))?;
+        for col in 0..4 {
+            let filled = match &hold {
+                Some(tetromino) => {
+                    tetromino.get_shape().row > row
+                        && tetromino.get_shape().col > col
+                        && tetromino.get_mask()[(row, col)]
+                }
+                None => false,
+            };
+            match (filled, &hold) {
+                (true, Some(tetromino)) => {
+                    let color = game.get_palette().get_color(tetromino.shape);
+                    queue!(
+                        out,
+                        SetForegroundColor(_color(color)),
+                        Print("██"),
+                        ResetColor
+                    )?
+                }
+                _ => queue!(out, Print("  "))?,
+            }
+        }
+    }
+    let score = game.get_score();
+    let note = match note {
+        Some(freq) => format!("♪ {} Hz", freq),
+        None => String::new(),
+    };
+    let lines = [
+        format!("SCORE {}", score.get_points()),
+        format!("LINES {}", score.get_lines()),
+        format!("LEVEL {}", score.get_level()),
+        format!("FAULTS {}", game.get_finesse().get_faults()),
+        format!("{:?}", game.get_state()),
+        note,
+        String::from(status),
+    ];
+    for (row, text) in lines
+        .iter()
+        .chain(KEYS.map(String::from).iter())
+        .enumerate()
+    {
+        queue!(
+            out,
+            MoveTo(SIDE_COL, 6 + row as u16),
+            Print(text),
+            Clear(ClearType::UntilNewLine)
+        )?;
+    }
+    Ok(())
+}
+
+fn main() -> io::Result<()> {
+    let options = Options::parse().unwrap_or_else(|error| {
+        eprintln!("{}\n{}", error, USAGE);
+        process::exit(2);
+    });
+    let replay = match &options.replay {
+        Some(path) => {
+            let bytes = fs::read(path)?;
+            let mut buf = vec![0; bytes.len()];
+            let replay = Replay::from_bytes(&bytes)
+                .or_else(|error| match bytes.starts_with(b"TRPL") {
+                    true => Err(error),
+                    false => Replay::from_base64(&bytes, &mut buf),
+                })
+                .map_err(|error| {
+                    io::Error::new(io::ErrorKind::InvalidData, format!("{:?}", error))
+                })?;
+            Some(replay)
+        }
+        None => None,
+    };
+    let (settings, seed) = match &replay {
+        Some(replay) => (*replay.get_settings(), replay.get_seed()),
+        None => (
+            options.settings,
+            options.seed.unwrap_or_else(|| {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default();
+                now.as_nanos() as u64
+            }),
+        ),
+    };
+    let mut game = match &replay {
+        Some(replay) => Game::seek_replay(replay, 0)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, format!("{:?}", error)))?,
+        None => Game::new(settings, seed),
+    };
+    let mut recording = Replay::new(settings, seed, MAX_REPLAY_WORDS);
+    recording.set_keyframe_pieces(KEYFRAME_PIECES);
+    let mut replayed = replay.as_ref().map(|replay| replay.inputs());
+    let mut frame = FrameBuffer::<{ PANEL_ROWS * PANEL_COLS }>::new(Coordinate::from_array([
+        PANEL_ROWS, PANEL_COLS,
+    ]));
+    let mut sequencer = Sequencer::new();
+    let mut heatmap = PlacementHeatmap::<PANEL_ROWS, PANEL_COLS>::new();
+    let mut particles = Particles::<PARTICLES>::new(seed);
+    let mut note = None;
+    let mut input = InputState::default();
+    let mut keys = 0;
+
+    let mut terminal = Terminal::enter()?;
+    let mut keyboard = Keyboard {
+        held: 0,
+        releases: terminal.releases,
+    };
+    let start = Instant::now();
+    let mut clock = || start.elapsed().as_micros() as u64;
+    let mut timestep = FixedTimestep::new(TICK_HZ);
+    let mut finished = false;
+    'game: loop {
+        // Wait for the next tick or a key, then read all pending keys.
+        let timeout = match finished {
+            true => Duration::from_millis(100),
+            false => Duration::from_micros(timestep.get_next_deadline().saturating_sub(clock())),
+        };
+        let mut ready = event::poll(timeout)?;
+        while ready {
+            if let Event::Key(key) = event::read()? {
+                let ctrl_c =
+                    key.code == KeyCode::Char('c') && key.modifiers == KeyModifiers::CONTROL;
+                if key.code == KeyCode::Char('q') || ctrl_c {
+                    break 'game;
+                }
+                keyboard.update(&key);
+            }
+            ready = event::poll(Duration::ZERO)?;
+        }
+        let ticks = match finished {
+            true => 0,
+            false => timestep.poll(&mut clock),
+        };
+        for _ in 0..ticks {
+            input = match &mut replayed {
+                Some(inputs) => match inputs.next() {
+                    Some(input) => input,
+                    None => {
+                        finished = true;
+                        break;
+                    }
+                },
+                None => {
+                    // Resolve the keys through the key map of the menu, which the games start with.
+                    let previous = keys;
+                    keys = keyboard.take_held();
+                    let keymap = game.get_menu().get_settings().keymap;
+                    input
+                        .next_bits(keymap.to_held_bits(keys))
+                        .with_raw_pressed(keys & !previous)
+                }
+            };
+            recording.record(&input);
+            let output = game.tick(&input);
+            // The attract mode cannot be saved, so it keeps no keyframes.
+            if output.locked.is_some() {
+                recording.record_lock(&game).ok();
+            }
+            sequencer.set_finesse(game.get_settings().finesse);
+            output.dispatch(&mut sequencer);
+            output.dispatch(&mut heatmap);
+            output.dispatch(&mut particles);
+            particles.tick();
+            // No piece locks during the countdown, so a new game starts with a clean heatmap.
+            if let GameState::Countdown(_) = output.state {
+                heatmap.reset();
+                particles.clear();
+            }
+            note = sequencer.tick();
+        }
+        if ticks == 0 && !finished {
+            continue;
+        }
+        let status = match (&replayed, finished) {
+            (Some(_), true) => "replay finished",
+            (Some(_), false) => "replaying",
+            (None, _) => "",
+        };
+        game.render_into(&mut frame);
+        particles.draw(&mut frame, game.get_layout());
+        if matches!(
+            game.get_state(),
+            GameState::GameOver(_) | GameState::Finished(_)
+        ) {
+            heatmap.draw_into(&mut frame, game.get_layout().field.top_left);
+        }
+        _draw_frame(&mut terminal.stdout, &frame)?;
+        _draw_side(&mut terminal.stdout, &game, note, status)?;
+        terminal.stdout.flush()?;
+    }
+    drop(terminal);
+    if let Some(path) = &options.record {
+        fs::write(path, recording.to_bytes())?;
+    }
+    Ok(())
+}
@@ -1,14 +1,118 @@
-#![allow(dead_code)]
-
-use crate::coordinate::Coordinate;
-use array2d::{Array2D, Error};
-use std::cmp::{max, min};
+use crate::coordinate::{Coordinate, Region};
+use crate::error::TetrisError;
+use crate::grid::{Array2D, Error};
+use crate::invariants::{validate_shape, InvariantViolation};
+use alloc::borrow::ToOwned;
+use alloc::vec::Vec;
+use core::cmp::{max, min};
+use core::fmt;
+use core::marker::PhantomData;
 
+#[derive(Clone, Debug)]
 pub struct Board<T: Copy> {
     board: Array2D<T>,
     negative: T,
 }
 
+/// The errors of writing to a `Board`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BoardError {
+    /// The mask, placed at the coordinate, exceeds the board.
+    MaskDoesNotFit,
+    /// The range of a slice exceeds the board.
+    SliceOutOfBounds,
+    /// The buffer is shorter than the encoded board, with the number of bytes it needs.
+    BufferTooSmall(usize),
+    /// The bytes are no run-length encoded board of this size.
+    Encoding,
+}
+
+impl fmt::Display for BoardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BoardError::MaskDoesNotFit => f.write_str("the mask does not fit on the board"),
+            BoardError::SliceOutOfBounds => f.write_str("the slice exceeds the board"),
+            BoardError::BufferTooSmall(length) => {
+                write!(
+                    f,
+                    "the buffer is shorter than the {} bytes of the board",
+                    length
+                )
+            }
+            BoardError::Encoding => f.write_str("not an encoded board of this size"),
+        }
+    }
+}
+
+/// The first byte of a board encoded as runs of empty and filled cells.
+const RLE_RUNS: u8 = 0;
+
+/// The first byte of a board encoded as raw bits, when the runs would take more bytes.
+const RLE_RAW: u8 = 1;
+
+/// Get the largest number of bytes of a run-length encoded board, its raw bits and the leading byte.
+/// # Arguments
+/// - `rows` - The number of rows of the board
+/// - `cols` - The number of columns of the board
+/// # Returns
+/// - `usize` - The number of bytes, e.g. 26 for the standard 20 by 10 board
+pub const fn rle_max_bytes(rows: usize, cols: usize) -> usize {
+    1 + ((rows * cols + 7) >> 3)
+}
+
+/// Get the number of bytes of a run length as an unsigned LEB128 varint, 7 bits per byte.
+fn _varint_bytes(mut value: usize) -> usize {
+    let mut bytes = 1;
+    while value >= 0x80 {
+        value >>= 7;
+        bytes += 1;
+    }
+    bytes
+}
+
+/// Read the runs of a board encoded as runs, see `Board::encode_rle_into()`.
+/// # Arguments
+/// - `bytes` - The encoded board, starting with `RLE_RUNS`
+/// - `cells` - The number of cells of the board
+/// - `f` - The closure called with the length of every run and whether its cells are filled
+/// # Returns
+/// - `Result<usize, BoardError>` - The number of bytes read, or a `BoardError::Encoding` if the runs do not cover the
+///   cells exactly, after calling `f` with the runs before the error
+fn _decode_runs(
+    bytes: &[u8],
+    cells: usize,
+    mut f: impl FnMut(usize, bool),
+) -> Result<usize, BoardError> {
+    let mut index = 1;
+    let mut decoded = 0;
+    let mut filled = false;
+    while decoded < cells {
+        let mut run = 0usize;
+        let mut shift = 0;
+        loop {
+            let byte = *bytes.get(index).ok_or(BoardError::Encoding)?;
+            index += 1;
+            // No board has runs of 2^28 cells, which keeps the shift in range.
+            if shift > 21 {
+                return Err(BoardError::Encoding);
+            }
+            run |= ((byte & 0x7F) as usize) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        if decoded + run > cells {
+            return Err(BoardError::Encoding);
+        }
+        f(run, filled);
+        decoded += run;
+        filled = !filled;
+    }
+    Ok(index)
+}
+
+#[derive(Clone, Copy, Debug)]
 pub enum BitLogic {
     And,
     Or,
@@ -18,11 +122,7 @@ pub enum BitLogic {
 
 impl<T> Board<T>
 where
-    T: Copy
-        + Clone
-        + std::ops::BitAnd<T, Output = T>
-        + std::ops::BitOr<Output = T>
-        + std::ops::BitXor<T, Output = T>,
+    T: Copy,
 {
     /// Create a board filled with false, indicating empty cells.
     /// # Arguments
@@ -72,21 +172,132 @@ where
             col: self.board.num_columns(),
         }
     }
+}
 
-    /// Get the bottom right coordinate of the current board state.
+impl<T: Copy + PartialEq> Board<T> {
+    /// Encode which cells are filled, e.g. for the keyframes of a `Replay`.
+    ///
+    /// The cells are walked in row major order as alternating runs of empty and filled cells, starting with an
+    /// empty run that may be 0 long, every run length an unsigned LEB128 varint, so an empty board takes 3 bytes.
+    /// When the runs would take more bytes than the raw bits, e.g. for a checkerboard, the bits are stored instead,
+    /// 8 cells per byte starting at the lowest bit. The leading byte tells both apart.
+    /// # Arguments
+    /// - `out` - A muteable reference to the buffer to write to, at most `rle_max_bytes()` bytes are written
     /// # Returns
-    /// - `Coordinate` - The bottom right coordinate, equal to [row - 1, col - 1]
-    pub fn get_coords(&self) -> Coordinate {
-        self.get_shape() - [1, 1]
+    /// - `Result<usize, BoardError>` - The number of bytes written, or a `BoardError::BufferTooSmall` if they do not
+    ///   fit, leaving the buffer unchanged
+    pub fn encode_rle_into(&self, out: &mut [u8]) -> Result<usize, BoardError> {
+        let shape = self.get_shape();
+        let raw_bytes = rle_max_bytes(shape.row, shape.col);
+        let mut run_bytes = 1;
+        self._for_each_run(|run| run_bytes += _varint_bytes(run));
+        let len = run_bytes.min(raw_bytes);
+        let out = out.get_mut(..len).ok_or(BoardError::BufferTooSmall(len))?;
+        match run_bytes <= raw_bytes {
+            true => {
+                out[0] = RLE_RUNS;
+                let mut index = 1;
+                self._for_each_run(|mut run| {
+                    while run >= 0x80 {
+                        out[index] = (run & 0x7F) as u8 | 0x80;
+                        run >>= 7;
+                        index += 1;
+                    }
+                    out[index] = run as u8;
+                    index += 1;
+                });
+            }
+            false => {
+                out.fill(0);
+                out[0] = RLE_RAW;
+                for (index, (coord, _)) in self.cells().enumerate() {
+                    out[1 + index / 8] |= (self.is_filled(coord) as u8) << (index % 8);
+                }
+            }
+        }
+        Ok(len)
+    }
+
+    /// Walk the alternating runs of empty and filled cells in row major order, starting with an empty run.
+    /// # Arguments
+    /// - `f` - The closure called with the length of every run
+    fn _for_each_run(&self, mut f: impl FnMut(usize)) {
+        let mut filled = false;
+        let mut run = 0;
+        for (coord, _) in self.cells() {
+            if self.is_filled(coord) != filled {
+                f(run);
+                filled = !filled;
+                run = 0;
+            }
+            run += 1;
+        }
+        f(run);
+    }
+
+    /// Decode a board written by `.encode_rle_into()` of a board of the same size.
+    ///
+    /// The bytes are checked before the first cell is written, so nothing is buffered in between.
+    /// # Arguments
+    /// - `bytes` - The encoded board, possibly followed by other bytes
+    /// - `value` - The value of the filled cells, the empty cells get the negative element
+    /// # Returns
+    /// - `Result<usize, BoardError>` - The number of bytes read, or a `BoardError::Encoding` if they are no encoded
+    ///   board of this size, leaving the board unchanged
+    pub fn decode_rle(&mut self, bytes: &[u8], value: T) -> Result<usize, BoardError> {
+        let shape = self.get_shape();
+        let negative = self.get_negative();
+        let cell = |filled: bool| match filled {
+            true => value,
+            false => negative,
+        };
+        let coord = |index: usize| Coordinate {
+            row: index / shape.col,
+            col: index % shape.col,
+        };
+        match bytes.first() {
+            Some(&RLE_RUNS) => {
+                let len = _decode_runs(bytes, shape.row * shape.col, |_, _| ())?;
+                let mut index = 0;
+                _decode_runs(bytes, shape.row * shape.col, |run, filled| {
+                    for _ in 0..run {
+                        self.set(coord(index), cell(filled));
+                        index += 1;
+                    }
+                })?;
+                Ok(len)
+            }
+            Some(&RLE_RAW) => {
+                let len = rle_max_bytes(shape.row, shape.col);
+                let raw = bytes.get(1..len).ok_or(BoardError::Encoding)?;
+                for index in 0..shape.row * shape.col {
+                    self.set(coord(index), cell(raw[index / 8] >> (index % 8) & 1 == 1));
+                }
+                Ok(len)
+            }
+            _ => Err(BoardError::Encoding),
+        }
     }
+}
 
+impl<T> Board<T>
+where
+    T: Copy
+        + Clone
+        + core::ops::BitAnd<T, Output = T>
+        + core::ops::BitOr<Output = T>
+        + core::ops::BitXor<T, Output = T>,
+{
     /// Get a slice from an array that is inclusive at the low and exclusive at the high end.
+    ///
+    /// The slice is a copy on the heap, per-frame code uses the `BoardOps::view()` instead.
     /// # Arguments
     /// - `coord1` - The lower coordinate for slicing
     /// - `coord2` - The higher coordinate for slicing
     /// # Returns
-    /// - `Option<Board<T>>` - A slice of the board if both coordinates are in bounds and `None` otherwise
-    pub fn slice(&self, coord1: Coordinate, coord2: Coordinate) -> Option<Board<T>> {
+    /// - `Result<Board<T>, TetrisError>` - A slice of the board, or a `BoardError::SliceOutOfBounds` if either
+    ///   coordinate is out of bounds
+    pub fn slice(&self, coord1: Coordinate, coord2: Coordinate) -> Result<Board<T>, TetrisError> {
         let coord_low = Coordinate {
             row: min(coord1.row, coord2.row),
             col: min(coord1.col, coord2.col),
@@ -100,19 +311,18 @@ where
         match coord_low.is_within_bounds(origin, self.get_shape())
             && coord_high.is_within_bounds(origin, self.get_shape())
         {
-            false => None,
+            false => Err(BoardError::SliceOutOfBounds.into()),
             true => {
                 let dest = coord_high - coord_low;
-                let mut row_major = Vec::with_capacity(dest.inner_product());
-                for r in coord_low.row..coord_high.row {
-                    for c in coord_low.col..coord_high.col {
-                        row_major.push(*self.get_array().get(r, c).unwrap())
-                    }
-                }
-                Some(Board::from_array(
-                    &Array2D::from_row_major(&row_major, dest.row, dest.col).unwrap(),
-                    self.get_negative(),
-                ))
+                let cells = self
+                    .get_array()
+                    .rows_iter()
+                    .skip(coord_low.row)
+                    .take(dest.row)
+                    .flat_map(|row| row[coord_low.col..coord_high.col].iter().copied());
+                let array = Array2D::from_iter_row_major(cells, dest.row, dest.col)
+                    .expect("a slice within the board has all its cells");
+                Ok(Board::from_array(&array, self.get_negative()))
             }
         }
     }
@@ -123,7 +333,14 @@ where
     /// - `value` - A generic of the same type to overwrite the board's values with
     /// - `coord` - The starting coordinate [row, col] as a `Coordinate`
     /// - `dims` - The dimensions of the board range to be set [rows, cols] as a `Coordinate`
-    pub fn set_value(&mut self, value: T, coord: Coordinate, dims: Coordinate) {
+    /// # Returns
+    /// - `Result<(), TetrisError>` - A `BoardError::MaskDoesNotFit` if the range exceeds the board, leaving it unchanged
+    pub fn set_value(
+        &mut self,
+        value: T,
+        coord: Coordinate,
+        dims: Coordinate,
+    ) -> Result<(), TetrisError> {
         // Simple wrapper for set_mask.
         let mask = Array2D::filled_with(value, dims.row, dims.col);
         self.set_mask(&mask, coord)
@@ -134,7 +351,9 @@ where
     /// - `board` - A muteable reference to an `Array2D` containing some generic
     /// - `mask` - A second `Array2D` containing a generic of the same type to overwrite the board's values with
     /// - `coord` - The starting coordinate [row, col] as a `Coordinate`
-    pub fn set_mask(&mut self, mask: &Array2D<T>, coord: Coordinate) {
+    /// # Returns
+    /// - `Result<(), TetrisError>` - A `BoardError::MaskDoesNotFit` if the mask exceeds the board, leaving it unchanged
+    pub fn set_mask(&mut self, mask: &Array2D<T>, coord: Coordinate) -> Result<(), TetrisError> {
         self._set_mask(mask, coord, BitLogic::None)
     }
 
@@ -143,7 +362,13 @@ where
     /// - `board` - A muteable reference to an `Array2D` containing some generic
     /// - `mask` - A second `Array2D` containing a generic of the same type to overwrite the board's values with
     /// - `coord` - The starting coordinate [row, col] as a `Coordinate`
-    pub fn set_mask_and(&mut self, mask: &Array2D<T>, coord: Coordinate) {
+    /// # Returns
+    /// - `Result<(), TetrisError>` - A `BoardError::MaskDoesNotFit` if the mask exceeds the board, leaving it unchanged
+    pub fn set_mask_and(
+        &mut self,
+        mask: &Array2D<T>,
+        coord: Coordinate,
+    ) -> Result<(), TetrisError> {
         self._set_mask(mask, coord, BitLogic::And)
     }
 
@@ -152,7 +377,9 @@ where
     /// - `board` - A muteable reference to an `Array2D` containing some generic
     /// - `mask` - A second `Array2D` containing a generic of the same type to overwrite the board's values with
     /// - `coord` - The starting coordinate [row, col] as a `Coordinate`
-    pub fn set_mask_or(&mut self, mask: &Array2D<T>, coord: Coordinate) {
+    /// # Returns
+    /// - `Result<(), TetrisError>` - A `BoardError::MaskDoesNotFit` if the mask exceeds the board, leaving it unchanged
+    pub fn set_mask_or(&mut self, mask: &Array2D<T>, coord: Coordinate) -> Result<(), TetrisError> {
         self._set_mask(mask, coord, BitLogic::Or)
     }
 
@@ -161,45 +388,51 @@ where
     /// - `board` - A muteable reference to an `Array2D` containing some generic
     /// - `mask` - A second `Array2D` containing a generic of the same type to overwrite the board's values with
     /// - `coord` - The starting coordinate [row, col] as a `Coordinate`
-    pub fn set_mask_xor(&mut self, mask: &Array2D<T>, coord: Coordinate) {
+    /// # Returns
+    /// - `Result<(), TetrisError>` - A `BoardError::MaskDoesNotFit` if the mask exceeds the board, leaving it unchanged
+    pub fn set_mask_xor(
+        &mut self,
+        mask: &Array2D<T>,
+        coord: Coordinate,
+    ) -> Result<(), TetrisError> {
         self._set_mask(mask, coord, BitLogic::Xor)
     }
 
     /// Backend for `.set_mask()`, `.set_mask_and()`, `.set_mask_or()` and `.set_mask_xor()` convenience methods.
-    fn _set_mask(&mut self, mask: &Array2D<T>, coord: Coordinate, logic: BitLogic) {
-        // Checking if subslice is valid
-        // let origin = Coordinate::from_array([0, 0]);
-        let mask_size = Coordinate::from_array([mask.num_rows(), mask.num_columns()]);
-        // let board_size = Coordinate::from_array([self.get_shape().row, self.get_shape().col]);
-        // let dest = coord + mask_size - [1, 1];
-
-        for r in 0..mask_size.row {
-            for c in 0..mask_size.col {
-                let coord_board = coord + Coordinate::from_array([r, c]);
-                self.board
-                    .set(
-                        coord_board.row,
-                        coord_board.col,
-                        // Checking logic operation for setting.
-                        match logic {
-                            BitLogic::And => {
-                                *mask.get(r, c).unwrap()
-                                    & *self.board.get(coord_board.row, coord_board.col).unwrap()
-                            }
-                            BitLogic::Or => {
-                                *mask.get(r, c).unwrap()
-                                    | *self.board.get(coord_board.row, coord_board.col).unwrap()
-                            }
-                            BitLogic::Xor => {
-                                *mask.get(r, c).unwrap()
-                                    ^ *self.board.get(coord_board.row, coord_board.col).unwrap()
-                            }
-                            BitLogic::None => *mask.get(r, c).unwrap(),
-                        },
-                    )
-                    .unwrap();
+    ///
+    /// The destination is checked once, after which the rows of the mask and the board are walked as slices
+    /// without checking every cell.
+    fn _set_mask(
+        &mut self,
+        mask: &Array2D<T>,
+        coord: Coordinate,
+        logic: BitLogic,
+    ) -> Result<(), TetrisError> {
+        // Saturating, so a huge coordinate never fits rather than wrapping around.
+        let end = Coordinate {
+            row: coord.row.saturating_add(mask.num_rows()),
+            col: coord.col.saturating_add(mask.num_columns()),
+        };
+        let shape = self.get_shape();
+        if end.row > shape.row || end.col > shape.col {
+            return Err(BoardError::MaskDoesNotFit.into());
+        }
+        let rows = self
+            .board
+            .rows_iter_mut()
+            .skip(coord.row)
+            .zip(mask.rows_iter());
+        for (row, mask_row) in rows {
+            for (cell, value) in row[coord.col..end.col].iter_mut().zip(mask_row) {
+                *cell = match logic {
+                    BitLogic::And => *value & *cell,
+                    BitLogic::Or => *value | *cell,
+                    BitLogic::Xor => *value ^ *cell,
+                    BitLogic::None => *value,
+                };
             }
         }
+        Ok(())
     }
 
     /// Compute the logical AND of the current board state with another board state of similar dimensions.
@@ -264,15 +497,16 @@ where
 
         // Reconstructing the logical array from the column major.
         let mut clone = Board::from_array(self.get_array(), self.get_negative());
-        clone.set_mask(
-            &Array2D::from_column_major(
-                &logic_column_major,
-                self.get_shape().row,
-                self.get_shape().col,
+        clone
+            .set_mask(
+                &Array2D::from_column_major(
+                    &logic_column_major,
+                    self.get_shape().row,
+                    self.get_shape().col,
+                )?,
+                Coordinate::from_array([0, 0]),
             )
-            .unwrap(),
-            Coordinate::from_array([0, 0]),
-        );
+            .map_err(|_| Error::DimensionMismatch)?;
         Ok(clone)
     }
 
@@ -282,11 +516,549 @@ where
     }
 }
 
+/// The operations of a playfield, independent of how its cells are stored.
+///
+/// Only the shape, the negative element and single cell access are required, everything else is
+/// built on top of those, so the heap-backed `Board` and the heap-free `FixedBoard` behave the same.
+pub trait BoardOps<T: Copy + PartialEq> {
+    /// Get the shape of the board.
+    /// # Returns
+    /// - `Coordinate` - The number of [rows, cols] of the board
+    fn get_shape(&self) -> Coordinate;
+
+    /// Get the value of the negative element.
+    /// # Returns
+    /// - `T` - The value representing an empty cell
+    fn get_negative(&self) -> T;
+
+    /// Get the value of a cell.
+    /// # Arguments
+    /// - `coord` - The [row, col] of the cell as a `Coordinate`
+    /// # Returns
+    /// - `Option<T>` - The value of the cell or `None` if it is outside of the board
+    fn get(&self, coord: Coordinate) -> Option<T>;
+
+    /// Overwrite a cell.
+    /// # Arguments
+    /// - `coord` - The [row, col] of the cell as a `Coordinate`, panicking if it is outside of the board
+    /// - `value` - The new value of the cell
+    fn set(&mut self, coord: Coordinate, value: T);
+
+    /// Get the bottom right coordinate of the board.
+    /// # Returns
+    /// - `Coordinate` - The bottom right coordinate, equal to [row - 1, col - 1]
+    fn get_coords(&self) -> Coordinate {
+        self.get_shape() - [1, 1]
+    }
+
+    /// Empty every cell of the board.
+    fn clear(&mut self) {
+        let negative = self.get_negative();
+        let shape = self.get_shape();
+        for row in 0..shape.row {
+            for col in 0..shape.col {
+                self.set(Coordinate { row, col }, negative);
+            }
+        }
+    }
+
+    /// Iterate over all cells of the board, from the top-left to the bottom-right in row major order.
+    /// # Returns
+    /// - `Cells<T, Self>` - An iterator over the [row, col] and the value of every cell
+    fn cells(&self) -> Cells<'_, T, Self> {
+        Cells {
+            board: self,
+            index: 0,
+            _element: PhantomData,
+        }
+    }
+
+    /// Get a read-only view of a rectangular region of the board, without copying it.
+    /// # Arguments
+    /// - `coord1` - The lower coordinate of the region, inclusive
+    /// - `coord2` - The higher coordinate of the region, exclusive
+    /// # Returns
+    /// - `Option<BoardView<T, Self>>` - The view if both coordinates are in bounds and `None` otherwise
+    fn view(&self, coord1: Coordinate, coord2: Coordinate) -> Option<BoardView<'_, T, Self>> {
+        let origin = Coordinate {
+            row: min(coord1.row, coord2.row),
+            col: min(coord1.col, coord2.col),
+        };
+        let high = Coordinate {
+            row: max(coord1.row, coord2.row),
+            col: max(coord1.col, coord2.col),
+        };
+        match high.row <= self.get_shape().row && high.col <= self.get_shape().col {
+            true => Some(BoardView {
+                board: self,
+                origin,
+                shape: high - origin,
+                _element: PhantomData,
+            }),
+            false => None,
+        }
+    }
+
+    /// Overwrite a rectangular region of the board with a mask, without logic.
+    /// # Arguments
+    /// - `mask` - A reference to an `Array2D` with the new values
+    /// - `coord` - The position of the top-left element of the mask on the board
+    /// # Returns
+    /// - `Result<(), TetrisError>` - A `BoardError::MaskDoesNotFit` if the mask exceeds the board, leaving it unchanged
+    fn set_mask(&mut self, mask: &Array2D<T>, coord: Coordinate) -> Result<(), TetrisError> {
+        // Saturating, so a huge coordinate never fits rather than wrapping around.
+        let shape = self.get_shape();
+        if coord.row.saturating_add(mask.num_rows()) > shape.row
+            || coord.col.saturating_add(mask.num_columns()) > shape.col
+        {
+            return Err(BoardError::MaskDoesNotFit.into());
+        }
+        for r in 0..mask.num_rows() {
+            for c in 0..mask.num_columns() {
+                self.set(coord + [r, c], mask[(r, c)]);
+            }
+        }
+        Ok(())
+    }
+
+    /// Check if a cell of the board is filled, i.e. differs from the negative element.
+    /// # Arguments
+    /// - `coord` - The [row, col] of the cell as a `Coordinate`
+    /// # Returns
+    /// - `bool` - Whether (`true`) or not (`false`) the cell is within the board and filled
+    fn is_filled(&self, coord: Coordinate) -> bool {
+        match self.get(coord) {
+            Some(el) => el != self.get_negative(),
+            None => false,
+        }
+    }
+
+    /// Check if a binary mask fits on the board, i.e. is within bounds and only covers empty cells.
+    /// # Arguments
+    /// - `mask` - A reference to the binary mask as an `Array2D<bool>`
+    /// - `coord` - The position of the top-left element of the mask on the board
+    /// # Returns
+    /// - `bool` - Whether (`true`) or not (`false`) the mask fits at that coordinate
+    fn fits(&self, mask: &Array2D<bool>, coord: Coordinate) -> bool {
+        let mask_size = Coordinate::from_array([mask.num_rows(), mask.num_columns()]);
+        if (coord + mask_size).row > self.get_shape().row
+            || (coord + mask_size).col > self.get_shape().col
+        {
+            return false;
+        }
+        for r in 0..mask_size.row {
+            for c in 0..mask_size.col {
+                if mask[(r, c)] && self.is_filled(coord + [r, c]) {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    /// Set every cell covered by the filled elements of a binary mask to a value.
+    /// # Arguments
+    /// - `mask` - A reference to the binary mask as an `Array2D<bool>`
+    /// - `coord` - The position of the top-left element of the mask on the board
+    /// - `value` - The value to write in the covered cells
+    fn fill_mask(&mut self, mask: &Array2D<bool>, coord: Coordinate, value: T) {
+        for r in 0..mask.num_rows() {
+            for c in 0..mask.num_columns() {
+                if mask[(r, c)] {
+                    self.set(coord + [r, c], value);
+                }
+            }
+        }
+    }
+
+    /// Check if a row has no empty cell.
+    /// # Arguments
+    /// - `row` - The index of the row
+    /// # Returns
+    /// - `bool` - Whether (`true`) or not (`false`) the row is within the board and full
+    fn is_row_full(&self, row: usize) -> bool {
+        row < self.get_shape().row
+            && (0..self.get_shape().col).all(|col| self.is_filled(Coordinate { row, col }))
+    }
+
+    /// Iterate over the indices of all rows without any empty cell.
+    /// # Returns
+    /// - `FullRows<T, Self>` - An iterator over the indices of the full rows, from top to bottom
+    fn full_rows(&self) -> FullRows<'_, T, Self> {
+        FullRows {
+            board: self,
+            row: 0,
+            _element: PhantomData,
+        }
+    }
+
+    /// Measure the height of a column, from the bottom of the board to its top filled cell.
+    /// # Arguments
+    /// - `col` - The index of the column
+    /// # Returns
+    /// - `usize` - The height of the column, `0` if it is empty or not on the board
+    fn column_height(&self, col: usize) -> usize {
+        let rows = self.get_shape().row;
+        (0..rows)
+            .find(|&row| self.is_filled(Coordinate { row, col }))
+            .map_or(0, |top| rows - top)
+    }
+
+    /// Iterate over the heights of all columns, see `BoardOps::column_height()`.
+    /// # Returns
+    /// - `SurfaceProfile<T, Self>` - An iterator over the column heights, from left to right
+    fn surface_profile(&self) -> SurfaceProfile<'_, T, Self> {
+        SurfaceProfile {
+            board: self,
+            col: 0,
+            _element: PhantomData,
+        }
+    }
+
+    /// Iterate over all wells, the columns lower than both of their neighbors.
+    ///
+    /// The walls count as infinitely tall, so an edge column is a well as soon as it is lower than its single
+    /// neighbor. A board of a single column has no neighbors at all, and is measured against its number of rows.
+    /// # Returns
+    /// - `Wells<T, Self>` - An iterator over the (column, depth) of the wells, from left to right, where the depth
+    ///   is the height of the lowest neighbor above the column
+    fn well_depths(&self) -> Wells<'_, T, Self> {
+        Wells {
+            board: self,
+            col: 0,
+            _element: PhantomData,
+        }
+    }
+
+    /// Find the deepest well, see `BoardOps::well_depths()`.
+    /// # Returns
+    /// - `Option<(usize, usize)>` - The (column, depth) of the deepest well, the leftmost one on a tie, or `None`
+    ///   if the board has no wells
+    fn deepest_well(&self) -> Option<(usize, usize)> {
+        self.well_depths()
+            .fold(None, |deepest, (col, depth)| match deepest {
+                Some((_, max)) if max >= depth => deepest,
+                _ => Some((col, depth)),
+            })
+    }
+
+    /// Remove all full rows, shifting the rows above down and filling the top with empty rows.
+    /// # Returns
+    /// - `usize` - The number of cleared rows
+    fn clear_full_rows(&mut self) -> usize {
+        let shape = self.get_shape();
+        let mut cleared = 0;
+        // Walk up from the bottom, moving every kept row down by the number of full rows below it.
+        for r in (0..shape.row).rev() {
+            if self.is_row_full(r) {
+                cleared += 1;
+            } else if cleared > 0 {
+                for c in 0..shape.col {
+                    let value = self
+                        .get(Coordinate { row: r, col: c })
+                        .expect("the row is on the board");
+                    self.set(Coordinate::from_array([r + cleared, c]), value);
+                }
+            }
+        }
+        for r in 0..cleared {
+            for c in 0..shape.col {
+                self.set(Coordinate { row: r, col: c }, self.get_negative());
+            }
+        }
+        cleared
+    }
+
+    /// Push rows in from the bottom, each filled with a value except for a single gap.
+    /// # Arguments
+    /// - `gaps` - The column of the gap of every inserted row, from top to bottom
+    /// - `value` - The value to write in the inserted rows
+    /// # Returns
+    /// - `bool` - Whether (`true`) or not (`false`) filled cells were pushed out at the top
+    fn insert_garbage_rows(&mut self, gaps: &[usize], value: T) -> bool {
+        let shape = self.get_shape();
+        let count = gaps.len().min(shape.row);
+        let overflow = (0..count)
+            .any(|r| (0..shape.col).any(|c| self.is_filled(Coordinate { row: r, col: c })));
+        for r in count..shape.row {
+            for c in 0..shape.col {
+                let value = self
+                    .get(Coordinate { row: r, col: c })
+                    .expect("the row is on the board");
+                self.set(Coordinate::from_array([r - count, c]), value);
+            }
+        }
+        for (index, gap) in gaps[gaps.len() - count..].iter().enumerate() {
+            for c in 0..shape.col {
+                self.set(
+                    Coordinate::from_array([shape.row - count + index, c]),
+                    match c == *gap {
+                        true => self.get_negative(),
+                        false => value,
+                    },
+                );
+            }
+        }
+        overflow
+    }
+
+    /// Find the cells that differ from another board, e.g. to only redraw the pixels of the board that changed.
+    /// # Arguments
+    /// - `other` - A reference to the other board, with the same shape
+    /// # Returns
+    /// - `Option<Region>` - The bounding box of the cells that differ, or `None` if the boards are equal
+    fn diff(&self, other: &Self) -> Option<Region> {
+        self.cells()
+            .filter(|(coord, value)| other.get(*coord) != Some(*value))
+            .fold(None, |region, (coord, _)| {
+                Some(Region::including(region, coord))
+            })
+    }
+
+    /// Check the invariants of the board, see the `invariants` module.
+    ///
+    /// The board has 1 to `MAX_ROWS` rows and at least one column. The cells have no reserved values to check, any
+    /// value besides the negative element is a filled cell.
+    /// # Returns
+    /// - `Result<(), InvariantViolation>` - The first invariant that does not hold, if any
+    fn validate(&self) -> Result<(), InvariantViolation> {
+        validate_shape(self.get_shape())
+    }
+}
+
+/// An iterator over the [row, col] and the value of every cell of a board, see `BoardOps::cells()`.
+pub struct Cells<'a, T, B: ?Sized> {
+    board: &'a B,
+    index: usize,
+    _element: PhantomData<T>,
+}
+
+impl<'a, T: Copy + PartialEq, B: BoardOps<T> + ?Sized> Iterator for Cells<'a, T, B> {
+    type Item = (Coordinate, T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let coord = Coordinate::from_row_major(self.index, self.board.get_shape())?;
+        self.index += 1;
+        Some((coord, self.board.get(coord)?))
+    }
+}
+
+/// An iterator over the indices of the full rows of a board, see `BoardOps::full_rows()`.
+pub struct FullRows<'a, T, B: ?Sized> {
+    board: &'a B,
+    row: usize,
+    _element: PhantomData<T>,
+}
+
+impl<'a, T: Copy + PartialEq, B: BoardOps<T> + ?Sized> Iterator for FullRows<'a, T, B> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.row < self.board.get_shape().row {
+            self.row += 1;
+            if self.board.is_row_full(self.row - 1) {
+                return Some(self.row - 1);
+            }
+        }
+        None
+    }
+}
+
+/// An iterator over the column heights of a board, see `BoardOps::surface_profile()`.
+pub struct SurfaceProfile<'a, T, B: ?Sized> {
+    board: &'a B,
+    col: usize,
+    _element: PhantomData<T>,
+}
+
+impl<'a, T: Copy + PartialEq, B: BoardOps<T> + ?Sized> Iterator for SurfaceProfile<'a, T, B> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.col >= self.board.get_shape().col {
+            return None;
+        }
+        self.col += 1;
+        Some(self.board.column_height(self.col - 1))
+    }
+}
+
+/// An iterator over the wells of a board, see `BoardOps::well_depths()`.
+pub struct Wells<'a, T, B: ?Sized> {
+    board: &'a B,
+    col: usize,
+    _element: PhantomData<T>,
+}
+
+impl<'a, T: Copy + PartialEq, B: BoardOps<T> + ?Sized> Iterator for Wells<'a, T, B> {
+    type Item = (usize, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let shape = self.board.get_shape();
+        while self.col < shape.col {
+            let col = self.col;
+            self.col += 1;
+            // A wall is never lower than a neighboring column, so only the columns on the board bound the well.
+            let left = col.checked_sub(1).map(|c| self.board.column_height(c));
+            let right = (col + 1 < shape.col).then(|| self.board.column_height(col + 1));
+            let rim = match (left, right) {
+                (Some(left), Some(right)) => left.min(right),
+                (Some(side), None) | (None, Some(side)) => side,
+                (None, None) => shape.row,
+            };
+            let height = self.board.column_height(col);
+            if rim > height {
+                return Some((col, rim - height));
+            }
+        }
+        None
+    }
+}
+
+/// A read-only view of a rectangular region of a board, see `BoardOps::view()`.
+/// # Attributes
+/// - `board` - A reference to the viewed board
+/// - `origin` - The position of the top-left cell of the view on the board
+/// - `shape` - The number of [rows, cols] of the view
+pub struct BoardView<'a, T, B: ?Sized> {
+    board: &'a B,
+    origin: Coordinate,
+    shape: Coordinate,
+    _element: PhantomData<T>,
+}
+
+impl<'a, T: Copy + PartialEq, B: BoardOps<T> + ?Sized> BoardView<'a, T, B> {
+    /// Get the shape of the view.
+    /// # Returns
+    /// - `Coordinate` - The number of [rows, cols] of the view
+    pub fn get_shape(&self) -> Coordinate {
+        self.shape
+    }
+
+    /// Get the value of a cell, relative to the top-left cell of the view.
+    /// # Arguments
+    /// - `coord` - The [row, col] of the cell within the view
+    /// # Returns
+    /// - `Option<T>` - The value of the cell or `None` if it is outside of the view
+    pub fn get(&self, coord: Coordinate) -> Option<T> {
+        match coord.row < self.shape.row && coord.col < self.shape.col {
+            true => self.board.get(self.origin + coord),
+            false => None,
+        }
+    }
+
+    /// Check if a cell of the view is filled, relative to the top-left cell of the view.
+    /// # Arguments
+    /// - `coord` - The [row, col] of the cell within the view
+    /// # Returns
+    /// - `bool` - Whether (`true`) or not (`false`) the cell is within the view and filled
+    pub fn is_filled(&self, coord: Coordinate) -> bool {
+        match self.get(coord) {
+            Some(el) => el != self.board.get_negative(),
+            None => false,
+        }
+    }
+}
+
+impl<T> BoardOps<T> for Board<T>
+where
+    T: Copy + PartialEq,
+{
+    fn get_shape(&self) -> Coordinate {
+        Board::get_shape(self)
+    }
+
+    fn get_negative(&self) -> T {
+        self.negative
+    }
+
+    fn get(&self, coord: Coordinate) -> Option<T> {
+        self.board.get(coord.row, coord.col).copied()
+    }
+
+    fn set(&mut self, coord: Coordinate, value: T) {
+        self.board[(coord.row, coord.col)] = value;
+    }
+
+    /// Check the invariants of the board, also that it stores a cell for every row and column.
+    fn validate(&self) -> Result<(), InvariantViolation> {
+        let shape = self.get_shape();
+        if self.board.num_elements() != shape.row * shape.col {
+            return Err(InvariantViolation::CellCount {
+                expected: shape.row * shape.col,
+                found: self.board.num_elements(),
+            });
+        }
+        validate_shape(shape)
+    }
+}
+
+/// A board with its size fixed at compile time, stored inline without any heap allocation.
+///
+/// Used on the target, where the playfield never changes size, e.g. `FixedBoard<Cell, 22, 10>`.
+/// # Attributes
+/// - `cells` - The cells, row after row
+/// - `negative` - The value representing an empty cell
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FixedBoard<T, const ROWS: usize, const COLS: usize> {
+    cells: [[T; COLS]; ROWS],
+    negative: T,
+}
+
+impl<T: Copy, const ROWS: usize, const COLS: usize> FixedBoard<T, ROWS, COLS> {
+    /// Create an empty board, usable in a `static` or `const`.
+    /// # Arguments
+    /// - `negative` - The value representing an empty cell, filling the whole board
+    /// # Returns
+    /// - `FixedBoard<T, ROWS, COLS>` - The empty board
+    pub const fn new(negative: T) -> Self {
+        FixedBoard {
+            cells: [[negative; COLS]; ROWS],
+            negative,
+        }
+    }
+
+    /// Get a reference to the rows of the board.
+    pub fn get_rows(&self) -> &[[T; COLS]; ROWS] {
+        &self.cells
+    }
+}
+
+impl<T, const ROWS: usize, const COLS: usize> BoardOps<T> for FixedBoard<T, ROWS, COLS>
+where
+    T: Copy + PartialEq,
+{
+    fn get_shape(&self) -> Coordinate {
+        Coordinate {
+            row: ROWS,
+            col: COLS,
+        }
+    }
+
+    fn get_negative(&self) -> T {
+        self.negative
+    }
+
+    fn get(&self, coord: Coordinate) -> Option<T> {
+        self.cells.get(coord.row)?.get(coord.col).copied()
+    }
+
+    fn set(&mut self, coord: Coordinate, value: T) {
+        self.cells[coord.row][coord.col] = value;
+    }
+
+    fn clear(&mut self) {
+        self.cells = [[self.negative; COLS]; ROWS];
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::board::Board;
-    use crate::coordinate::Coordinate;
-    use array2d::Array2D;
+    use crate::board::{rle_max_bytes, BitLogic, Board, BoardError, BoardOps, FixedBoard};
+    use crate::coordinate::{Coordinate, Region};
+    use crate::grid::Array2D;
+    use crate::invariants::InvariantViolation;
+    use alloc::borrow::ToOwned;
 
     #[test]
     fn test_set_mask() {
@@ -314,7 +1086,9 @@ mod tests {
             2,
         )
         .unwrap();
-        board.set_mask(&mask, Coordinate::from_array([1, 2]));
+        board
+            .set_mask(&mask, Coordinate::from_array([1, 2]))
+            .unwrap();
         let target = Array2D::from_row_major(
             &[
                 false, false, false, false, //
@@ -329,7 +1103,6 @@ mod tests {
     }
 
     #[test]
-    #[should_panic]
     fn test_set_mask_error() {
         // Create board:
         //   0 1 2 3 4
@@ -350,7 +1123,112 @@ mod tests {
             3,
         )
         .unwrap();
-        board.set_mask(&mask, Coordinate::from_array([3, 0]))
+        assert_eq!(
+            board.set_mask(&mask, Coordinate::from_array([3, 0])),
+            Err(BoardError::MaskDoesNotFit.into())
+        );
+        assert!(board.get_array().elements_row_major_iter().all(|el| !el));
+    }
+
+    #[test]
+    fn test_set_mask_overhang() {
+        // Create board:
+        //   0 1 2
+        // 0 f f f
+        // 1 f f f
+        // Overhang a mask of t t by a column on the right, which used to write the first cell and then panic.
+        let mut board = Board::new(Coordinate::from_array([2, 3]), false);
+        let mask = Array2D::filled_with(true, 1, 2);
+        for logic in [BitLogic::None, BitLogic::And, BitLogic::Or, BitLogic::Xor] {
+            assert_eq!(
+                board._set_mask(&mask, Coordinate::from_array([1, 2]), logic),
+                Err(BoardError::MaskDoesNotFit.into())
+            );
+        }
+        assert_eq!(
+            board.set_mask(&mask, Coordinate::from_array([usize::MAX, usize::MAX])),
+            Err(BoardError::MaskDoesNotFit.into())
+        );
+        assert_eq!(board.get_array(), &Array2D::filled_with(false, 2, 3));
+        // An empty mask fits anywhere on the board.
+        let empty = Array2D::filled_with(true, 0, 0);
+        assert_eq!(
+            board.set_mask(&empty, Coordinate::from_array([2, 3])),
+            Ok(())
+        );
+        // Through the trait, both storages reject it the same way.
+        let mut fixed = FixedBoard::<bool, 2, 3>::new(false);
+        for coord in [[1, 2], [usize::MAX, usize::MAX]] {
+            let coord = Coordinate::from_array(coord);
+            assert_eq!(
+                BoardOps::set_mask(&mut board, &mask, coord),
+                Err(BoardError::MaskDoesNotFit.into())
+            );
+            assert_eq!(
+                fixed.set_mask(&mask, coord),
+                Err(BoardError::MaskDoesNotFit.into())
+            );
+        }
+        assert!(board.cells().chain(fixed.cells()).all(|(_, el)| !el));
+        assert_eq!(
+            fixed.set_mask(&mask, Coordinate::from_array([1, 1])),
+            Ok(())
+        );
+    }
+
+    /// The cell by cell `_set_mask()` from before, as the reference of the row slices.
+    fn set_mask_per_cell(
+        board: &mut Board<u8>,
+        mask: &Array2D<u8>,
+        coord: Coordinate,
+        logic: BitLogic,
+    ) {
+        let mut array = board.get_array().to_owned();
+        for r in 0..mask.num_rows() {
+            for c in 0..mask.num_columns() {
+                let (row, col) = (coord.row + r, coord.col + c);
+                let value = *mask.get(r, c).unwrap();
+                let old = *array.get(row, col).unwrap();
+                let new = match logic {
+                    BitLogic::And => value & old,
+                    BitLogic::Or => value | old,
+                    BitLogic::Xor => value ^ old,
+                    BitLogic::None => value,
+                };
+                array.set(row, col, new).unwrap();
+            }
+        }
+        *board = Board::from_array(&array, board.get_negative());
+    }
+
+    #[test]
+    fn test_set_mask_per_cell() {
+        // Place a mask at every position it fits on a busy board, with every logic, and compare to the old loop.
+        let dims = Coordinate::from_array([7, 6]);
+        let board = Board::from_array(
+            &Array2D::from_iter_row_major(
+                (0..).map(|index: u8| index.wrapping_mul(37)),
+                dims.row,
+                dims.col,
+            )
+            .unwrap(),
+            0,
+        );
+        let mask =
+            Array2D::from_iter_row_major((1..).map(|index: u8| index.wrapping_mul(91)), 3, 4)
+                .unwrap();
+        for logic in [BitLogic::None, BitLogic::And, BitLogic::Or, BitLogic::Xor] {
+            for row in 0..=dims.row - mask.num_rows() {
+                for col in 0..=dims.col - mask.num_columns() {
+                    let coord = Coordinate::from_array([row, col]);
+                    let mut expected = board.clone();
+                    set_mask_per_cell(&mut expected, &mask, coord, logic);
+                    let mut actual = board.clone();
+                    actual._set_mask(&mask, coord, logic).unwrap();
+                    assert_eq!(actual.get_array(), expected.get_array());
+                }
+            }
+        }
     }
 
     #[test]
@@ -373,11 +1251,13 @@ mod tests {
         // 2 f t t
         // 3 f f f
         let mut board = Board::new(Coordinate::from_array([4, 3]), false);
-        board.set_value(
-            true,
-            Coordinate::from_array([0, 1]),
-            Coordinate::from_array([3, 1]),
-        );
+        board
+            .set_value(
+                true,
+                Coordinate::from_array([0, 1]),
+                Coordinate::from_array([3, 1]),
+            )
+            .unwrap();
         let target = Array2D::from_row_major(
             &[
                 false, true, false, //
@@ -393,7 +1273,6 @@ mod tests {
     }
 
     #[test]
-    #[should_panic]
     fn test_set_value_error() {
         // Create board with coordinate X:
         //   0 1
@@ -406,10 +1285,268 @@ mod tests {
         //   0 1 2
         // 0 t t t
         let mut board = Board::new(Coordinate::from_array([5, 2]), false);
-        board.set_value(
-            true,
-            Coordinate::from_array([0, 0]),
-            Coordinate::from_array([1, 3]),
-        )
+        assert_eq!(
+            board.set_value(
+                true,
+                Coordinate::from_array([0, 0]),
+                Coordinate::from_array([1, 3]),
+            ),
+            Err(BoardError::MaskDoesNotFit.into())
+        );
+    }
+
+    #[test]
+    fn test_well_depths() {
+        // A flat stack of 4 rows over the first 9 columns leaves a single well at the right wall.
+        let mut board = FixedBoard::<bool, 20, 10>::new(false);
+        for row in 16..20 {
+            for col in 0..9 {
+                board.set(Coordinate { row, col }, true);
+            }
+        }
+        assert!(board.surface_profile().eq([4, 4, 4, 4, 4, 4, 4, 4, 4, 0]));
+        assert!(board.well_depths().eq([(9, 4)]));
+        assert_eq!(board.deepest_well(), Some((9, 4)));
+
+        // Create board with heights 0, 3, 1, 2, 2, 0:
+        //   0 1 2 3 4 5
+        // 0 . # . . . .
+        // 1 . # . # # .
+        // 2 . # # # # .
+        // Wells at both walls, against a single neighbor, and in column 2, against the lower neighbor.
+        let mut board = FixedBoard::<bool, 3, 6>::new(false);
+        for coord in [
+            [0, 1],
+            [1, 1],
+            [2, 1],
+            [2, 2],
+            [1, 3],
+            [2, 3],
+            [1, 4],
+            [2, 4],
+        ] {
+            board.set(Coordinate::from_array(coord), true);
+        }
+        assert!(board.surface_profile().eq([0, 3, 1, 2, 2, 0]));
+        assert!(board.well_depths().eq([(0, 3), (2, 1), (5, 2)]));
+        assert_eq!(board.deepest_well(), Some((0, 3)));
+
+        // An empty board has no wells, while a single column is measured against the rows of the board.
+        assert_eq!(FixedBoard::<bool, 3, 6>::new(false).deepest_well(), None);
+        assert!(FixedBoard::<bool, 3, 1>::new(false)
+            .well_depths()
+            .eq([(0, 3)]));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(
+            Board::new(Coordinate::from_array([64, 10]), 0).validate(),
+            Ok(())
+        );
+        assert_eq!(FixedBoard::<u8, 20, 10>::new(0).validate(), Ok(()));
+        // Clearing marks the completed rows in a u64, so taller boards cannot clear every row.
+        assert_eq!(
+            Board::new(Coordinate::from_array([65, 10]), 0).validate(),
+            Err(InvariantViolation::BoardShape { rows: 65, cols: 10 })
+        );
+        assert_eq!(
+            FixedBoard::<u8, 20, 0>::new(0).validate(),
+            Err(InvariantViolation::BoardShape { rows: 20, cols: 0 })
+        );
+    }
+
+    /// Copy a row major array into an empty board of any storage.
+    fn load<T: Copy + PartialEq, B: BoardOps<T>>(mut board: B, row_major: &[T]) -> B {
+        let shape = board.get_shape();
+        board
+            .set_mask(
+                &Array2D::from_row_major(row_major, shape.row, shape.col).unwrap(),
+                Coordinate::from_array([0, 0]),
+            )
+            .unwrap();
+        board
+    }
+
+    fn check_fits<B: BoardOps<bool>>(board: B) {
+        // Create board:
+        //   0 1 2
+        // 0 f f f
+        // 1 f f t
+        // Fit mask t t at [0, 1] but not at [1, 1] or [1, 2].
+        let board = load(
+            board,
+            &[
+                false, false, false, //
+                false, false, true, //
+            ],
+        );
+        let mask = Array2D::from_row_major(&[true, true], 1, 2).unwrap();
+        assert!(board.fits(&mask, Coordinate::from_array([0, 1])));
+        assert!(!board.fits(&mask, Coordinate::from_array([1, 1])));
+        assert!(!board.fits(&mask, Coordinate::from_array([1, 2])));
+    }
+
+    fn check_clear_full_rows<B: BoardOps<bool>>(board: B) {
+        // Create board:
+        //   0 1 2
+        // 0 f t f
+        // 1 t t t
+        // 2 t f t
+        // 3 t t t
+        // Expect target:
+        //   0 1 2
+        // 0 f f f
+        // 1 f f f
+        // 2 f t f
+        // 3 t f t
+        let mut board = load(
+            board,
+            &[
+                false, true, false, //
+                true, true, true, //
+                true, false, true, //
+                true, true, true, //
+            ],
+        );
+        assert_eq!(board.full_rows().collect::<Vec<usize>>(), vec![1, 3]);
+        assert_eq!(board.clear_full_rows(), 2);
+        let target = vec![
+            false, false, false, //
+            false, false, false, //
+            false, true, false, //
+            true, false, true, //
+        ];
+        assert_eq!(
+            board.cells().map(|(_, el)| el).collect::<Vec<bool>>(),
+            target
+        );
+    }
+
+    fn check_insert_garbage_rows<B: BoardOps<u8>>(board: B) {
+        // 0 0 0 0         0 1 1 0
+        // 0 0 0 0   ->    1 1 1 0
+        // 0 1 1 0         1 0 1 1
+        // 1 1 1 0         1 1 0 1
+        let mut board = load(board, &[0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 0, 1, 1, 1, 0]);
+        assert!(!board.insert_garbage_rows(&[1, 2], 1));
+        assert_eq!(
+            board.cells().map(|(_, el)| el).collect::<Vec<u8>>(),
+            vec![0, 1, 1, 0, 1, 1, 1, 0, 1, 0, 1, 1, 1, 1, 0, 1]
+        );
+        // Any further row pushes the stack out at the top.
+        assert!(board.insert_garbage_rows(&[0], 1));
+    }
+
+    fn check_view<B: BoardOps<u8>>(board: B) {
+        // 0 1 2
+        // 3 4 5
+        // 6 7 8
+        let mut board = load(board, &[0, 1, 2, 3, 4, 5, 6, 7, 8]);
+        let view = board
+            .view(
+                Coordinate::from_array([3, 3]),
+                Coordinate::from_array([1, 1]),
+            )
+            .unwrap();
+        assert_eq!(view.get_shape(), Coordinate::from_array([2, 2]));
+        assert_eq!(view.get(Coordinate::from_array([1, 0])), Some(7));
+        assert_eq!(view.get(Coordinate::from_array([2, 0])), None);
+        assert!(view.is_filled(Coordinate::from_array([0, 0])));
+        assert!(board
+            .view(
+                Coordinate::from_array([0, 0]),
+                Coordinate::from_array([4, 3])
+            )
+            .is_none());
+        board.clear();
+        assert!(board.cells().all(|(_, el)| el == 0));
+    }
+
+    fn check_diff<B: BoardOps<u8> + Clone>(board: B) {
+        // 0 1 2      0 1 2
+        // 3 4 5  ->  3 9 5
+        // 6 7 8      6 7 9
+        let board = load(board, &[0, 1, 2, 3, 4, 5, 6, 7, 8]);
+        let mut other = board.clone();
+        assert_eq!(board.diff(&other), None);
+        other.set(Coordinate::from_array([1, 1]), 9);
+        other.set(Coordinate::from_array([2, 2]), 9);
+        assert_eq!(
+            board.diff(&other),
+            Some(Region {
+                top_left: Coordinate::from_array([1, 1]),
+                bottom_right: Coordinate::from_array([2, 2]),
+            })
+        );
+    }
+
+    #[test]
+    fn test_fits() {
+        check_fits(Board::new(Coordinate::from_array([2, 3]), false));
+        check_fits(FixedBoard::<bool, 2, 3>::new(false));
+    }
+
+    #[test]
+    fn test_clear_full_rows() {
+        check_clear_full_rows(Board::new(Coordinate::from_array([4, 3]), false));
+        check_clear_full_rows(FixedBoard::<bool, 4, 3>::new(false));
+    }
+
+    #[test]
+    fn test_insert_garbage_rows() {
+        check_insert_garbage_rows(Board::new(Coordinate::from_array([4, 4]), 0));
+        check_insert_garbage_rows(FixedBoard::<u8, 4, 4>::new(0));
+    }
+
+    #[test]
+    fn test_view() {
+        check_view(Board::new(Coordinate::from_array([3, 3]), 0));
+        check_view(FixedBoard::<u8, 3, 3>::new(0));
+    }
+
+    #[test]
+    fn test_diff() {
+        check_diff(Board::new(Coordinate::from_array([3, 3]), 0));
+        check_diff(FixedBoard::<u8, 3, 3>::new(0));
+    }
+
+    #[test]
+    fn test_rle() {
+        // An empty board takes a byte for the kind and two for its single run of 200 cells.
+        let mut board = Board::new(Coordinate::from_array([20, 10]), 0u8);
+        let raw = rle_max_bytes(20, 10);
+        assert_eq!(raw, 26);
+        let mut buffer = [0xAA; 26];
+        assert_eq!(board.encode_rle_into(&mut buffer), Ok(3));
+        assert_eq!(buffer[..3], [0, 0xC8, 0x01]);
+        // A sparse board stays far below the raw bits, with runs of 190 empty, 3 filled, 5 empty and 1 filled cells.
+        for col in [0, 1, 2, 9] {
+            board.set(Coordinate::from_array([19, col]), 3);
+        }
+        let len = board.encode_rle_into(&mut buffer).unwrap();
+        assert_eq!(len, 1 + 2 + 1 + 1 + 1);
+        let mut decoded = Board::new(Coordinate::from_array([20, 10]), 0u8);
+        assert_eq!(decoded.decode_rle(&buffer[..len], 3), Ok(len));
+        assert!(decoded.cells().eq(board.cells()));
+        // A checkerboard, the worst case of the runs, falls back to the raw bits.
+        let mut checkerboard = Board::new(Coordinate::from_array([20, 10]), false);
+        for (coord, _) in board.cells() {
+            checkerboard.set(coord, (coord.row + coord.col) % 2 == 1);
+        }
+        assert_eq!(checkerboard.encode_rle_into(&mut buffer), Ok(raw));
+        assert_eq!(buffer[..2], [1, 0xAA]);
+        let mut decoded = Board::new(Coordinate::from_array([20, 10]), false);
+        assert_eq!(decoded.decode_rle(&buffer, true), Ok(raw));
+        assert!(decoded.cells().eq(checkerboard.cells()));
+        // Short buffers and bytes of another board are rejected, leaving the board unchanged.
+        assert_eq!(
+            checkerboard.encode_rle_into(&mut buffer[..25]),
+            Err(BoardError::BufferTooSmall(26))
+        );
+        for bytes in [&[2, 0][..], &[0, 0xC8], &[0, 0xC9, 0x01], &[1, 0xAA]] {
+            assert_eq!(decoded.decode_rle(bytes, true), Err(BoardError::Encoding));
+        }
+        assert!(decoded.cells().eq(checkerboard.cells()));
     }
 }
@@ -0,0 +1,40 @@
+use crate::tetrominoes::TetrominoShape;
+
+/// The brightness of the cells of a locked piece, dimmer than the active piece at full brightness.
+pub const LOCKED_LUM: u8 = 200;
+
+/// The content of a single cell of the playfield.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Cell {
+    #[default]
+    Empty,
+    /// Part of a piece of `shape`, drawn at a brightness of `lum` out of 255.
+    Filled { shape: TetrominoShape, lum: u8 },
+    /// Sent by the opponent in versus.
+    Garbage,
+}
+
+impl Cell {
+    /// Create a cell of a locked piece.
+    /// # Arguments
+    /// - `shape` - The `TetrominoShape` of the piece
+    /// # Returns
+    /// - `Cell` - The filled cell at the `LOCKED_LUM` brightness
+    pub fn locked(shape: TetrominoShape) -> Self {
+        Cell::Filled {
+            shape,
+            lum: LOCKED_LUM,
+        }
+    }
+
+    /// Encode the cell as a single byte, e.g. for hashing the board, ignoring the brightness.
+    /// # Returns
+    /// - `u8` - 0 for an empty cell, 1 to 7 for the shapes in the order of `TetrominoShape` and 8 for garbage
+    pub fn to_u8(self) -> u8 {
+        match self {
+            Cell::Empty => 0,
+            Cell::Filled { shape, .. } => shape as u8 + 1,
+            Cell::Garbage => 8,
+        }
+    }
+}
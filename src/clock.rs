@@ -0,0 +1,141 @@
+//! The game clock, measuring the time played from the tick counter alone, so a replay shows the exact same times.
+//!
+//! Times are shown as `M:SS.CC`, minutes, seconds and hundredths of a second, written as ASCII into a byte buffer
+//! without `core::fmt`, for the HUD and the high-score table of the sprint mode.
+use crate::error::TetrisError;
+use crate::savestate::{Persist, Reader, Writer};
+
+/// The nominal number of ticks per second, the rate the firmware and the simulator run the game at.
+pub const TICK_HZ: u32 = 60;
+
+/// The largest number of bytes of a formatted time, 6 digits of minutes for any time in a `u32` of hundredths.
+pub const TIME_BYTES: usize = 6 + ":SS.CC".len();
+
+/// The time spent playing a game.
+/// # Attributes
+/// - `ticks_elapsed` - The number of ticks spent playing, excluding pauses and countdowns, 64 bits so it never wraps
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct GameClock {
+    ticks_elapsed: u64,
+}
+
+impl GameClock {
+    /// Create a clock at zero.
+    /// # Returns
+    /// - `GameClock` - The clock without any ticks
+    pub fn new() -> Self {
+        GameClock { ticks_elapsed: 0 }
+    }
+
+    /// Create a clock at a number of ticks, e.g. to show the time of a recorded game.
+    /// # Arguments
+    /// - `ticks_elapsed` - The number of ticks spent playing
+    /// # Returns
+    /// - `GameClock` - The clock at the ticks
+    pub fn from_ticks(ticks_elapsed: u64) -> Self {
+        GameClock { ticks_elapsed }
+    }
+
+    /// Advance the clock by a single tick of play.
+    pub fn tick(&mut self) {
+        self.ticks_elapsed += 1;
+    }
+
+    /// Get the number of ticks spent playing.
+    /// # Returns
+    /// - `u64` - The number of ticks, excluding pauses and countdowns
+    pub fn get_ticks_elapsed(&self) -> u64 {
+        self.ticks_elapsed
+    }
+
+    /// Convert the ticks to time, rounding down so a time only shows once it was fully played.
+    /// # Arguments
+    /// - `tick_hz` - The number of ticks per second, larger than 0
+    /// # Returns
+    /// - `u32` - The time played in hundredths of a second, saturating after more than 497 days
+    pub fn elapsed_centis(&self, tick_hz: u32) -> u32 {
+        ticks_to_centis(self.ticks_elapsed, tick_hz)
+    }
+}
+
+/// Convert a number of ticks to time, rounding down.
+/// # Arguments
+/// - `ticks` - The number of ticks
+/// - `tick_hz` - The number of ticks per second, larger than 0
+/// # Returns
+/// - `u32` - The time in hundredths of a second, saturating at `u32::MAX`
+pub fn ticks_to_centis(ticks: u64, tick_hz: u32) -> u32 {
+    let centis = ticks.saturating_mul(100) / tick_hz as u64;
+    u32::try_from(centis).unwrap_or(u32::MAX)
+}
+
+/// Format a time as `M:SS.CC`, with as many digits of minutes as needed.
+/// # Arguments
+/// - `centis` - The time in hundredths of a second
+/// - `out` - A muteable reference to the buffer to write the ASCII characters into
+/// # Returns
+/// - `usize` - The number of bytes written to the start of the buffer
+pub fn format_time(centis: u32, out: &mut [u8; TIME_BYTES]) -> usize {
+    let minutes = centis / 6000;
+    let mut len = 1;
+    while len < 6 && minutes >= 10u32.pow(len as u32) {
+        len += 1;
+    }
+    let mut rest = minutes;
+    for index in (0..len).rev() {
+        out[index] = b'0' + (rest % 10) as u8;
+        rest /= 10;
+    }
+    let seconds = centis / 100 % 60;
+    let hundredths = centis % 100;
+    out[len..len + 6].copy_from_slice(&[
+        b':',
+        b'0' + (seconds / 10) as u8,
+        b'0' + (seconds % 10) as u8,
+        b'.',
+        b'0' + (hundredths / 10) as u8,
+        b'0' + (hundredths % 10) as u8,
+    ]);
+    len + 6
+}
+
+impl Persist for GameClock {
+    fn save(&self, out: &mut Writer) {
+        out.u64(self.ticks_elapsed);
+    }
+
+    fn restore(&mut self, input: &mut Reader) -> Result<(), TetrisError> {
+        self.ticks_elapsed = input.u64()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_time, GameClock, TICK_HZ, TIME_BYTES};
+
+    fn formatted(ticks: u64) -> String {
+        let mut clock = GameClock::new();
+        for _ in 0..ticks {
+            clock.tick();
+        }
+        let mut buffer = [0; TIME_BYTES];
+        let len = format_time(clock.elapsed_centis(TICK_HZ), &mut buffer);
+        String::from_utf8(buffer[..len].to_vec()).unwrap()
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(formatted(0), "0:00.00");
+        // A single tick at 60 Hz takes 1.67 hundredths, rounded down.
+        assert_eq!(formatted(1), "0:00.01");
+        assert_eq!(formatted(61), "0:01.01");
+        assert_eq!(formatted(59 * 60 + 59), "0:59.98");
+        assert_eq!(formatted(60 * 60), "1:00.00");
+        assert_eq!(formatted(10 * 60 * 60 + 90), "10:01.50");
+        // The largest time still fits the buffer.
+        let mut buffer = [0; TIME_BYTES];
+        assert_eq!(format_time(u32::MAX, &mut buffer), TIME_BYTES);
+        assert_eq!(&buffer, b"715827:52.95");
+    }
+}
@@ -1,20 +1,39 @@
-#![allow(dead_code)]
+use crate::rng::RngSource;
 
-pub struct ColorRgb {
-    /// A simple struct to model the RGB colorspace.
+include!(concat!(env!("OUT_DIR"), "/gamma.rs"));
+
+/// Why a hex color could not be parsed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorParseError {
+    /// The number of hex digits, after an optional `#`, is neither 3 nor 6.
+    Length(usize),
+    /// A character is not a hex digit.
+    Character(char),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rgb {
+    /// A simple struct to model the RGB colorspace, as sent to the LEDs.
     pub r: u8,
     pub g: u8,
     pub b: u8,
 }
 
-impl ColorRgb {
+impl Rgb {
+    /// All LEDs off.
+    pub const BLACK: Rgb = Rgb::from_array(&[0, 0, 0]);
+    /// All LEDs at full brightness.
+    pub const WHITE: Rgb = Rgb::from_array(&[255, 255, 255]);
+    /// A dim white, e.g. for garbage.
+    pub const GRAY: Rgb = Rgb::from_array(&[64, 64, 64]);
+
     /// Convert an array of u8's to an RGB color.
     /// # Arguments
     /// - `array` - A reference to the array of u8's to convert
     /// # Returns
-    /// - `ColorRgb` - An RGB color
-    pub fn from_array(arr: &[u8; 3]) -> ColorRgb {
-        ColorRgb {
+    /// - `Rgb` - An RGB color
+    pub const fn from_array(arr: &[u8; 3]) -> Rgb {
+        Rgb {
             r: arr[0],
             g: arr[1],
             b: arr[2],
@@ -24,33 +43,751 @@ impl ColorRgb {
     /// Convert an RGB color to an array of u8's.
     /// # Returns
     /// - `[u8; 3]` - An array representation of the RGB colorspace
-    pub fn to_array(&self) -> [u8; 3] {
+    pub fn to_array(self) -> [u8; 3] {
         [self.r, self.g, self.b]
     }
+
+    /// Convert an array of u8's in the GRB order of WS2812 LEDs to an RGB color.
+    /// # Arguments
+    /// - `grb` - A reference to the [green, red, blue] array
+    /// # Returns
+    /// - `Rgb` - An RGB color
+    pub fn from_grb(grb: &[u8; 3]) -> Rgb {
+        Rgb::from_array(&[grb[1], grb[0], grb[2]])
+    }
+
+    /// Convert the color to the GRB byte order of WS2812 LEDs.
+    /// # Returns
+    /// - `[u8; 3]` - The [green, red, blue] array
+    pub fn to_grb(self) -> [u8; 3] {
+        [self.g, self.r, self.b]
+    }
+
+    /// Pack the color in a u32 in the byte order of WS2812 LEDs, e.g. for a DMA buffer with one word per LED.
+    /// # Returns
+    /// - `u32` - The color in the 0x00GGRRBB layout
+    pub fn to_u32_grb(self) -> u32 {
+        u32::from_be_bytes([0, self.g, self.r, self.b])
+    }
+
+    /// Unpack a color packed by `.to_u32_grb()`.
+    /// # Arguments
+    /// - `packed` - The color in the 0x00GGRRBB layout, the highest byte is ignored
+    /// # Returns
+    /// - `Rgb` - An RGB color
+    pub fn from_u32_grb(packed: u32) -> Rgb {
+        let [_, g, r, b] = packed.to_be_bytes();
+        Rgb { r, g, b }
+    }
+
+    /// Pack the color in a u32, e.g. for storing it in the configuration.
+    /// # Returns
+    /// - `u32` - The color in the 0x00RRGGBB layout
+    pub fn to_u32_rgb(self) -> u32 {
+        u32::from_be_bytes([0, self.r, self.g, self.b])
+    }
+
+    /// Unpack a color packed by `.to_u32_rgb()`.
+    /// # Arguments
+    /// - `packed` - The color in the 0x00RRGGBB layout, the highest byte is ignored
+    /// # Returns
+    /// - `Rgb` - An RGB color
+    pub fn from_u32_rgb(packed: u32) -> Rgb {
+        let [_, r, g, b] = packed.to_be_bytes();
+        Rgb { r, g, b }
+    }
+
+    /// Write the color in GRB byte order into a transmission buffer.
+    /// # Arguments
+    /// - `buf` - A muteable reference to the buffer, written from its start
+    /// # Returns
+    /// - `usize` - The number of bytes written, 3, or 0 if the buffer is too short
+    pub fn write_grb_into(self, buf: &mut [u8]) -> usize {
+        match buf.get_mut(..3) {
+            Some(bytes) => {
+                bytes.copy_from_slice(&self.to_grb());
+                3
+            }
+            None => 0,
+        }
+    }
+
+    /// Quantize the color to the 16 bit RGB565 format of TFT displays.
+    ///
+    /// Every channel is rounded to the nearest level of its 5 or 6 bit field, so 255 maps to the maximum level.
+    /// # Returns
+    /// - `u16` - The color with red in the 5 highest, green in the 6 middle and blue in the 5 lowest bits
+    pub fn to_rgb565(self) -> u16 {
+        let quantize = |channel: u8, max: u32| (channel as u32 * max + 127) / 255;
+        (quantize(self.r, 31) << 11 | quantize(self.g, 63) << 5 | quantize(self.b, 31)) as u16
+    }
+
+    /// Expand an RGB565 color, replicating the highest bits of every field into the lowest bits.
+    /// # Arguments
+    /// - `packed` - The color in the RGB565 format, see `.to_rgb565()`
+    /// # Returns
+    /// - `Rgb` - An RGB color
+    pub fn from_rgb565(packed: u16) -> Rgb {
+        let r = (packed >> 11 & 0x1F) as u8;
+        let g = (packed >> 5 & 0x3F) as u8;
+        let b = (packed & 0x1F) as u8;
+        Rgb {
+            r: r << 3 | r >> 2,
+            g: g << 2 | g >> 4,
+            b: b << 3 | b >> 2,
+        }
+    }
+
+    /// Parse a color in the `#RRGGBB`, `RRGGBB`, `#RGB` or `RGB` form, with upper or lower case digits.
+    ///
+    /// The short form repeats every digit, so `#F80` is `#FF8800`.
+    /// # Arguments
+    /// - `s` - The hex string
+    /// # Returns
+    /// - `Result<Rgb, ColorParseError>` - The color, or an error describing the bad length or character
+    pub fn from_hex(s: &str) -> Result<Rgb, ColorParseError> {
+        let digits = s.strip_prefix('#').unwrap_or(s);
+        // Every short digit fills a whole channel, e.g. 0x8 becomes 0x88.
+        let (shift, repeat) = match digits.len() {
+            3 => (8, 0x11),
+            6 => (4, 0x1),
+            len => return Err(ColorParseError::Length(len)),
+        };
+        let mut value = 0;
+        for char in digits.chars() {
+            let digit = char.to_digit(16).ok_or(ColorParseError::Character(char))?;
+            value = (value << shift) | (digit * repeat);
+        }
+        Ok(Rgb::from_u32_rgb(value))
+    }
+
+    /// Format the color as `#RRGGBB` with upper case digits, without allocating.
+    /// # Arguments
+    /// - `buf` - A mutable reference to the buffer to format into
+    /// # Returns
+    /// - `&str` - The formatted color, borrowed from `buf`
+    pub fn to_hex(self, buf: &mut [u8; 7]) -> &str {
+        const DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+        buf[0] = b'#';
+        for (index, channel) in self.to_array().iter().enumerate() {
+            buf[1 + 2 * index] = DIGITS[(channel >> 4) as usize];
+            buf[2 + 2 * index] = DIGITS[(channel & 0xF) as usize];
+        }
+        core::str::from_utf8(buf).expect("hex digits are ASCII")
+    }
+
+    /// Convert the color to the HSV colorspace, the inverse of `Hsv::to_rgb()` up to rounding.
+    /// # Returns
+    /// - `Hsv` - The color, with a hue of 0 for grays
+    pub fn to_hsv(self) -> Hsv {
+        let max = self.r.max(self.g).max(self.b) as i32;
+        let min = self.r.min(self.g).min(self.b) as i32;
+        let delta = max - min;
+        if delta == 0 {
+            return Hsv {
+                h: 0,
+                s: 0,
+                v: max as u8,
+            };
+        }
+        let (r, g, b) = (self.r as i32, self.g as i32, self.b as i32);
+        let h = match max {
+            _ if max == r => HUE_SEXTANT * (g - b) / delta,
+            _ if max == g => 2 * HUE_SEXTANT + HUE_SEXTANT * (b - r) / delta,
+            _ => 4 * HUE_SEXTANT + HUE_SEXTANT * (r - g) / delta,
+        };
+        Hsv {
+            h: h.rem_euclid(6 * HUE_SEXTANT).min(255) as u8,
+            s: (delta * 255 / max) as u8,
+            v: max as u8,
+        }
+    }
+
+    /// Scale the color by a brightness, e.g. to limit the current drawn by the LEDs.
+    ///
+    /// Every channel becomes `channel * brightness / 255`, rounded to the nearest integer with halves rounding up,
+    /// so dim channels survive moderate dimming: (255, 127, 1) at 128 becomes (128, 64, 1).
+    /// # Arguments
+    /// - `brightness` - The brightness, from 0 (off) to 255 (unchanged)
+    /// # Returns
+    /// - `Rgb` - The scaled color
+    pub fn scaled(self, brightness: u8) -> Rgb {
+        self.scaled_fraction(brightness, u8::MAX)
+    }
+
+    /// Scale the color by a fraction, e.g. 1/4 to dim the ghost piece.
+    ///
+    /// Every channel becomes `channel * num / den`, rounded like `.scaled()` and saturating at 255.
+    /// # Arguments
+    /// - `num` - The numerator of the fraction
+    /// - `den` - The denominator of the fraction, larger than 0
+    /// # Returns
+    /// - `Rgb` - The scaled color
+    pub fn scaled_fraction(self, num: u8, den: u8) -> Rgb {
+        let scale = |channel: u8| {
+            let scaled = (channel as u32 * num as u32 + den as u32 / 2) / den as u32;
+            scaled.min(u8::MAX as u32) as u8
+        };
+        Rgb::from_array(&self.to_array().map(scale))
+    }
+
+    /// Linearly interpolate from the color toward another color, rounding to the nearest channel value.
+    ///
+    /// The endpoints are exact, and the midpoint `t = 128` of black and white is 128.
+    /// # Arguments
+    /// - `other` - The color at `t = 255`
+    /// - `t` - The position between the color at 0 and `other` at 255
+    /// # Returns
+    /// - `Rgb` - The interpolated color
+    pub fn lerp(self, other: Rgb, t: u8) -> Rgb {
+        let t = t as u32;
+        self._zip(other, |a, b| {
+            ((a as u32 * (255 - t) + b as u32 * t + 127) / 255) as u8
+        })
+    }
+
+    /// Add another color channel by channel, clamping at full brightness, e.g. to flash a cell.
+    /// # Arguments
+    /// - `other` - The color to add
+    /// # Returns
+    /// - `Rgb` - The brightened color
+    pub fn saturating_add(self, other: Rgb) -> Rgb {
+        self._zip(other, u8::saturating_add)
+    }
+
+    /// Subtract another color channel by channel, clamping at black.
+    /// # Arguments
+    /// - `other` - The color to subtract
+    /// # Returns
+    /// - `Rgb` - The darkened color
+    pub fn saturating_sub(self, other: Rgb) -> Rgb {
+        self._zip(other, u8::saturating_sub)
+    }
+
+    /// Add another color weighted by a factor, clamping at full brightness.
+    /// # Arguments
+    /// - `other` - The color to add
+    /// - `weight` - The weight of `other`, from 0 (nothing added) to 255 (added fully)
+    /// # Returns
+    /// - `Rgb` - The sum of the color and the scaled `other`
+    pub fn add_weighted(self, other: Rgb, weight: u8) -> Rgb {
+        self.saturating_add(other.scaled(weight))
+    }
+
+    /// Average some colors channel by channel, rounding to the nearest channel value.
+    /// # Arguments
+    /// - `colors` - A slice of the colors to average
+    /// # Returns
+    /// - `Rgb` - The average color, black for an empty slice
+    pub fn average(colors: &[Rgb]) -> Rgb {
+        if colors.is_empty() {
+            return Rgb::BLACK;
+        }
+        let len = colors.len() as u32;
+        let mut sums = [0u32; 3];
+        for color in colors {
+            for (sum, channel) in sums.iter_mut().zip(color.to_array()) {
+                *sum += channel as u32;
+            }
+        }
+        Rgb::from_array(&sums.map(|sum| ((sum + len / 2) / len) as u8))
+    }
+
+    /// Composite the color over a background, e.g. the ghost piece over the grid.
+    /// # Arguments
+    /// - `background` - The color underneath
+    /// - `alpha` - The opacity of the color, from transparent at 0 to opaque at 255
+    /// # Returns
+    /// - `Rgb` - The composited color
+    pub fn blend_over(self, background: Rgb, alpha: u8) -> Rgb {
+        background.lerp(self, alpha)
+    }
+
+    /// Combine 2 colors channel by channel.
+    fn _zip(self, other: Rgb, f: impl Fn(u8, u8) -> u8) -> Rgb {
+        Rgb {
+            r: f(self.r, other.r),
+            g: f(self.g, other.g),
+            b: f(self.b, other.b),
+        }
+    }
+
+    /// Correct the color for the non-linear brightness perception, using the `GAMMA8` table on every channel.
+    ///
+    /// Correct after scaling with `.scaled()`: the brightness then scales the perceived rather than the emitted light,
+    /// and no dim channel is rounded twice.
+    /// # Returns
+    /// - `Rgb` - The corrected color
+    pub fn gamma_corrected(self) -> Rgb {
+        Rgb::from_array(&self.to_array().map(|channel| GAMMA8[channel as usize]))
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for Rgb {
+    /// Log the color as `#RRGGBB`.
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(f, "#{=u8:02X}{=u8:02X}{=u8:02X}", self.r, self.g, self.b)
+    }
 }
 
-#[derive(Default)]
+impl From<u32> for Rgb {
+    /// Unpack a color in the 0x00RRGGBB layout, see `Rgb::from_u32_rgb()`.
+    fn from(packed: u32) -> Self {
+        Rgb::from_u32_rgb(packed)
+    }
+}
+
+impl From<Rgb> for u32 {
+    /// Pack a color in the 0x00RRGGBB layout, see `Rgb::to_u32_rgb()`.
+    fn from(color: Rgb) -> Self {
+        color.to_u32_rgb()
+    }
+}
+
+#[cfg(feature = "embedded-graphics")]
+impl From<embedded_graphics::pixelcolor::Rgb888> for Rgb {
+    /// Convert a color of `embedded-graphics`, e.g. to draw its fonts and primitives on a `FrameBuffer`.
+    fn from(color: embedded_graphics::pixelcolor::Rgb888) -> Self {
+        use embedded_graphics::pixelcolor::RgbColor;
+        Rgb::from_array(&[color.r(), color.g(), color.b()])
+    }
+}
+
+#[cfg(feature = "embedded-graphics")]
+impl From<Rgb> for embedded_graphics::pixelcolor::Rgb888 {
+    /// Convert a color to `embedded-graphics`, e.g. to draw text in a palette color.
+    fn from(color: Rgb) -> Self {
+        embedded_graphics::pixelcolor::Rgb888::new(color.r, color.g, color.b)
+    }
+}
+
+/// The width of one of the 6 sextants of the hue circle, which wraps at 256.
+const HUE_SEXTANT: i32 = 43;
+
+/// A color in the HSV colorspace, for smooth hue sweeps.
+/// # Attributes
+/// - `h` - The hue, wrapping from red at 0 through yellow, green, cyan, blue and magenta back to red
+/// - `s` - The saturation, from gray at 0 to the pure hue at 255
+/// - `v` - The value, from black at 0 to full brightness at 255
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Hsv {
+    pub h: u8,
+    pub s: u8,
+    pub v: u8,
+}
+
+impl Hsv {
+    /// Convert the color to RGB with integer math only, splitting the hue circle in 6 sextants of 43 hues.
+    ///
+    /// The pure colors sit at the start of every sextant: red at 0, yellow at 43, green at 86,
+    /// cyan at 129, blue at 172 and magenta at 215.
+    /// # Returns
+    /// - `Rgb` - The RGB color
+    pub fn to_rgb(self) -> Rgb {
+        let v = self.v as u32;
+        let s = self.s as u32;
+        // Multiply 2 values in [0, 255] as fractions of 255, rounding to the nearest.
+        let mul = |a: u32, b: u32| (a * b + 127) / 255;
+        let sextant = self.h as u32 / HUE_SEXTANT as u32;
+        let fraction = (self.h as u32 % HUE_SEXTANT as u32) * 255 / HUE_SEXTANT as u32;
+        let p = mul(v, 255 - s) as u8;
+        let q = mul(v, 255 - mul(s, fraction)) as u8;
+        let t = mul(v, 255 - mul(s, 255 - fraction)) as u8;
+        let v = self.v;
+        let [r, g, b] = match sextant {
+            0 => [v, t, p],
+            1 => [q, v, p],
+            2 => [p, v, t],
+            3 => [p, q, v],
+            4 => [t, p, v],
+            _ => [v, p, q],
+        };
+        Rgb { r, g, b }
+    }
+}
+
+/// Get a fully saturated color of a rainbow sweeping along a strip or row of LEDs.
+/// # Arguments
+/// - `offset` - The hue of the first LED, advance it every frame to animate the rainbow
+/// - `index` - The index of the LED
+/// - `scale` - The hue step between neighboring LEDs
+/// # Returns
+/// - `Rgb` - The color of the LED
+pub fn rainbow(offset: u8, index: u8, scale: u8) -> Rgb {
+    Hsv {
+        h: offset.wrapping_add(index.wrapping_mul(scale)),
+        s: 255,
+        v: 255,
+    }
+    .to_rgb()
+}
+
+/// A per-channel calibration of an LED panel, e.g. to compensate for a blue cast.
+/// # Attributes
+/// - `r` - The multiplier of the red channel, from 0 (off) to 255 (unchanged)
+/// - `g` - The multiplier of the green channel, from 0 (off) to 255 (unchanged)
+/// - `b` - The multiplier of the blue channel, from 0 (off) to 255 (unchanged)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ColorCorrection {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl ColorCorrection {
+    /// No correction.
+    pub const UNCORRECTED: ColorCorrection = ColorCorrection {
+        r: 255,
+        g: 255,
+        b: 255,
+    };
+    /// The typical correction of 5050 SMD LEDs like the WS2812, which are too green and slightly too blue.
+    pub const TYPICAL_SMD5050: ColorCorrection = ColorCorrection {
+        r: 255,
+        g: 176,
+        b: 240,
+    };
+
+    /// Correct a color, rounding every channel like `Rgb::scaled()`.
+    /// # Arguments
+    /// - `color` - The color to correct
+    /// # Returns
+    /// - `Rgb` - The corrected color
+    pub fn apply(&self, color: Rgb) -> Rgb {
+        Rgb {
+            r: color.scaled(self.r).r,
+            g: color.scaled(self.g).g,
+            b: color.scaled(self.b).b,
+        }
+    }
+}
+
+impl Default for ColorCorrection {
+    fn default() -> Self {
+        ColorCorrection::UNCORRECTED
+    }
+}
+
+/// Convert a row of colors into an RGB565 scanline for a TFT display.
+/// # Arguments
+/// - `row` - The colors of the row
+/// - `scanline` - A muteable reference to the scanline, of which the first `row.len()` pixels are written
+/// # Returns
+/// - `usize` - The number of converted pixels, the shortest of both lengths
+pub fn to_rgb565_scanline(row: &[Rgb], scanline: &mut [u16]) -> usize {
+    for (pixel, color) in scanline.iter_mut().zip(row) {
+        *pixel = color.to_rgb565();
+    }
+    row.len().min(scanline.len())
+}
+
+/// The former name of `Rgb`.
+#[deprecated(note = "renamed to `Rgb`")]
+pub type ColorRgb = Rgb;
+
+/// The named colors of the tetrominoes and the UI, see `From<Color> for Rgb` for their RGB values.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Color {
+    Black,
     Blue,
     Cyan,
+    Gray,
     #[default]
     Green,
     Orange,
     Purple,
     Red,
+    White,
     Yellow,
 }
 
-impl From<Color> for ColorRgb {
+impl Color {
+    /// All named colors, in the order of their `u8` encoding.
+    pub const ALL: [Color; 10] = [
+        Color::Black,
+        Color::Blue,
+        Color::Cyan,
+        Color::Gray,
+        Color::Green,
+        Color::Orange,
+        Color::Purple,
+        Color::Red,
+        Color::White,
+        Color::Yellow,
+    ];
+
+    /// Draw a uniformly random named color.
+    /// # Arguments
+    /// - `rng` - A mutable reference to the source of randomness
+    /// # Returns
+    /// - `Color` - The random color
+    pub fn random(rng: &mut impl RngSource) -> Color {
+        Color::ALL[rng.below(Color::ALL.len() as u32) as usize]
+    }
+}
+
+impl From<Color> for u8 {
+    /// Encode the color as its index in `Color::ALL`.
+    fn from(color: Color) -> Self {
+        color as u8
+    }
+}
+
+impl TryFrom<u8> for Color {
+    type Error = u8;
+
+    /// Decode a color encoded with `u8::from()`, returning the byte as error if it is out of range.
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Color::ALL.get(value as usize).copied().ok_or(value)
+    }
+}
+
+impl From<Color> for Rgb {
     fn from(color: Color) -> Self {
         match color {
-            Color::Blue => ColorRgb::from_array(&[0, 0, 255]),
-            Color::Cyan => ColorRgb::from_array(&[0, 255, 255]),
-            Color::Green => ColorRgb::from_array(&[0, 255, 0]),
-            Color::Orange => ColorRgb::from_array(&[255, 127, 0]),
-            Color::Purple => ColorRgb::from_array(&[255, 0, 255]),
-            Color::Red => ColorRgb::from_array(&[255, 0, 0]),
-            Color::Yellow => ColorRgb::from_array(&[255, 255, 0]),
+            Color::Black => Rgb::BLACK,
+            Color::Blue => Rgb::from_array(&[0, 0, 255]),
+            Color::Cyan => Rgb::from_array(&[0, 255, 255]),
+            Color::Gray => Rgb::GRAY,
+            Color::Green => Rgb::from_array(&[0, 255, 0]),
+            Color::Orange => Rgb::from_array(&[255, 127, 0]),
+            Color::Purple => Rgb::from_array(&[255, 0, 255]),
+            Color::Red => Rgb::from_array(&[255, 0, 0]),
+            Color::White => Rgb::WHITE,
+            Color::Yellow => Rgb::from_array(&[255, 255, 0]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        rainbow, to_rgb565_scanline, Color, ColorCorrection, ColorParseError, Hsv, Rgb, GAMMA8,
+    };
+    use crate::rng::GameRng;
+    use test_case::test_case;
+
+    #[test]
+    fn test_wire_formats() {
+        let color = Rgb::from_array(&[0x12, 0x34, 0x56]);
+        assert_eq!(color.to_grb(), [0x34, 0x12, 0x56]);
+        assert_eq!(Rgb::from_grb(&color.to_grb()), color);
+        assert_eq!(color.to_u32_grb(), 0x0034_1256);
+        assert_eq!(Rgb::from_u32_grb(color.to_u32_grb()), color);
+        assert_eq!(color.to_u32_rgb(), 0x0012_3456);
+        assert_eq!(Rgb::from_u32_rgb(color.to_u32_rgb()), color);
+        assert_eq!(u32::from(color), 0x0012_3456);
+        assert_eq!(Rgb::from(0xFF12_3456), color);
+    }
+
+    #[test]
+    fn test_write_grb_into() {
+        // A strip of a red, a green and a blue LED.
+        let strip = [Color::Red, Color::Green, Color::Blue].map(Rgb::from);
+        let mut buf = [0xAA; 10];
+        let mut written = 0;
+        for led in strip {
+            written += led.write_grb_into(&mut buf[written..]);
+        }
+        assert_eq!(written, 9);
+        assert_eq!(buf, [0, 255, 0, 255, 0, 0, 0, 0, 255, 0xAA]);
+        assert_eq!(strip[0].write_grb_into(&mut buf[8..]), 0);
+    }
+
+    #[test_case([255, 0, 0] => 0xF800; "red")]
+    #[test_case([0, 255, 0] => 0x07E0; "green")]
+    #[test_case([0, 0, 255] => 0x001F; "blue")]
+    #[test_case([255, 255, 255] => 0xFFFF; "white")]
+    #[test_case([0, 0, 0] => 0x0000; "black")]
+    fn test_to_rgb565(color: [u8; 3]) -> u16 {
+        let packed = Rgb::from_array(&color).to_rgb565();
+        assert_eq!(Rgb::from_rgb565(packed), Rgb::from_array(&color));
+        packed
+    }
+
+    #[test]
+    fn test_rgb565_error() {
+        // Converting back and forth stays within half a level of the 5 and 6 bit fields.
+        for value in 0..=255u8 {
+            let color = Rgb::from_array(&[value, value, value]);
+            let back = Rgb::from_rgb565(color.to_rgb565());
+            assert!((back.r as i32 - value as i32).abs() <= 4);
+            assert!((back.g as i32 - value as i32).abs() <= 2);
+            assert!((back.b as i32 - value as i32).abs() <= 4);
+        }
+        let row = [Color::Red, Color::Cyan].map(Rgb::from);
+        let mut scanline = [0; 3];
+        assert_eq!(to_rgb565_scanline(&row, &mut scanline), 2);
+        assert_eq!(scanline, [0xF800, 0x07FF, 0]);
+    }
+
+    #[test_case(0 => [255, 0, 0]; "red")]
+    #[test_case(43 => [255, 255, 0]; "yellow")]
+    #[test_case(86 => [0, 255, 0]; "green")]
+    #[test_case(129 => [0, 255, 255]; "cyan")]
+    #[test_case(172 => [0, 0, 255]; "blue")]
+    #[test_case(215 => [255, 0, 255]; "magenta")]
+    fn test_hsv_to_rgb(h: u8) -> [u8; 3] {
+        let rgb = Hsv { h, s: 255, v: 255 }.to_rgb();
+        assert_eq!(rgb.to_hsv(), Hsv { h, s: 255, v: 255 });
+        rgb.to_array()
+    }
+
+    #[test]
+    fn test_hsv_black() {
+        for h in (0..=255).step_by(5) {
+            for s in (0..=255).step_by(5) {
+                assert_eq!(Hsv { h, s, v: 0 }.to_rgb(), Rgb::from_array(&[0, 0, 0]));
+            }
+        }
+        assert_eq!(rainbow(0, 2, 43), Rgb::from_array(&[0, 255, 0]));
+        assert_eq!(rainbow(250, 1, 6), Rgb::from_array(&[255, 0, 0]));
+    }
+
+    #[test_case("#12AB5f" => Ok([0x12, 0xAB, 0x5F]); "long")]
+    #[test_case("12ab5F" => Ok([0x12, 0xAB, 0x5F]); "long without hash")]
+    #[test_case("#f80" => Ok([0xFF, 0x88, 0x00]); "short")]
+    #[test_case("F80" => Ok([0xFF, 0x88, 0x00]); "short without hash")]
+    #[test_case("#GGHHII" => Err(ColorParseError::Character('G')); "bad character")]
+    #[test_case("#12AB5" => Err(ColorParseError::Length(5)); "bad length")]
+    #[test_case("" => Err(ColorParseError::Length(0)); "empty")]
+    fn test_from_hex(s: &str) -> Result<[u8; 3], ColorParseError> {
+        Rgb::from_hex(s).map(Rgb::to_array)
+    }
+
+    #[test]
+    fn test_to_hex() {
+        let mut buf = [0; 7];
+        let color = Rgb::from_array(&[0x0A, 0xBC, 0xFF]);
+        assert_eq!(color.to_hex(&mut buf), "#0ABCFF");
+        assert_eq!(Rgb::from_hex(color.to_hex(&mut buf)), Ok(color));
+    }
+
+    #[test]
+    fn test_lerp() {
+        let black = Rgb::from_array(&[0, 0, 0]);
+        let white = Rgb::from_array(&[255, 255, 255]);
+        let color = Rgb::from_array(&[12, 200, 99]);
+        assert_eq!(black.lerp(color, 0), black);
+        assert_eq!(black.lerp(color, 255), color);
+        assert_eq!(color.lerp(white, 0), color);
+        assert_eq!(color.lerp(white, 255), white);
+        assert_eq!(black.lerp(white, 128), Rgb::from_array(&[128, 128, 128]));
+        assert_eq!(white.lerp(black, 128), Rgb::from_array(&[127, 127, 127]));
+        assert_eq!(color.blend_over(black, 255), color);
+        assert_eq!(color.blend_over(black, 0), black);
+    }
+
+    #[test]
+    fn test_average() {
+        let color = Rgb::from_array(&[12, 200, 99]);
+        assert_eq!(
+            Rgb::average(&[Rgb::BLACK, Rgb::WHITE]),
+            Rgb::from_array(&[128, 128, 128])
+        );
+        assert_eq!(Rgb::average(&[color; 4]), color);
+        assert_eq!(Rgb::average(&[]), Rgb::BLACK);
+        assert_eq!(
+            color.add_weighted(Rgb::WHITE, 128),
+            Rgb::from_array(&[140, 255, 227])
+        );
+    }
+
+    #[test]
+    fn test_saturating() {
+        let color = Rgb::from_array(&[200, 100, 0]);
+        let flash = Rgb::from_array(&[100, 100, 100]);
+        assert_eq!(
+            color.saturating_add(flash),
+            Rgb::from_array(&[255, 200, 100])
+        );
+        assert_eq!(color.saturating_sub(flash), Rgb::from_array(&[100, 0, 0]));
+    }
+
+    #[test]
+    fn test_gamma_corrected() {
+        assert_eq!(GAMMA8[0], 0);
+        assert_eq!(GAMMA8[128], 37);
+        assert_eq!(GAMMA8[255], 255);
+        assert!(GAMMA8.windows(2).all(|w| w[0] <= w[1]));
+        let color = Rgb::from_array(&[255, 128, 7]);
+        assert_eq!(
+            color.gamma_corrected(),
+            Rgb::from_array(&[GAMMA8[255], GAMMA8[128], GAMMA8[7]])
+        );
+    }
+
+    #[test]
+    fn test_scaled() {
+        let color = Rgb::from_array(&[255, 127, 1]);
+        assert_eq!(color.scaled(255), color);
+        assert_eq!(color.scaled(0), Rgb::from_array(&[0, 0, 0]));
+        assert_eq!(color.scaled(128), Rgb::from_array(&[128, 64, 1]));
+        assert_eq!(color.scaled_fraction(1, 4), Rgb::from_array(&[64, 32, 0]));
+        assert_eq!(color.scaled_fraction(2, 1), Rgb::from_array(&[255, 254, 2]));
+    }
+
+    #[test_case(Color::Black => [0, 0, 0]; "black")]
+    #[test_case(Color::Blue => [0, 0, 255]; "blue")]
+    #[test_case(Color::Cyan => [0, 255, 255]; "cyan")]
+    #[test_case(Color::Gray => [64, 64, 64]; "gray")]
+    #[test_case(Color::Green => [0, 255, 0]; "green")]
+    #[test_case(Color::Orange => [255, 127, 0]; "orange")]
+    #[test_case(Color::Purple => [255, 0, 255]; "purple")]
+    #[test_case(Color::Red => [255, 0, 0]; "red")]
+    #[test_case(Color::White => [255, 255, 255]; "white")]
+    #[test_case(Color::Yellow => [255, 255, 0]; "yellow")]
+    fn test_from_color(color: Color) -> [u8; 3] {
+        Rgb::from(color).to_array()
+    }
+
+    #[test]
+    fn test_debug() {
+        let color = Rgb::from_array(&[1, 2, 3]);
+        assert_eq!(format!("{:?}", color), "Rgb { r: 1, g: 2, b: 3 }");
+        assert_eq!(
+            format!("{:?}", Rgb::from_array(&[0, 0, 255]).to_hsv()),
+            "Hsv { h: 172, s: 255, v: 255 }"
+        );
+        assert_eq!(format!("{:?}", Color::Gray), "Gray");
+    }
+
+    #[test]
+    fn test_color_correction() {
+        let color = Rgb::from_array(&[12, 200, 255]);
+        assert_eq!(ColorCorrection::UNCORRECTED.apply(color), color);
+        assert_eq!(ColorCorrection::default(), ColorCorrection::UNCORRECTED);
+        // Green is scaled by 176/255 and blue by 240/255, red is kept.
+        assert_eq!(
+            ColorCorrection::TYPICAL_SMD5050.apply(color),
+            Rgb::from_array(&[12, 138, 240])
+        );
+    }
+
+    #[test]
+    fn test_u8() {
+        for (index, color) in Color::ALL.iter().enumerate() {
+            assert_eq!(u8::from(*color), index as u8);
+            assert_eq!(Color::try_from(index as u8), Ok(*color));
         }
+        assert_eq!(Color::try_from(Color::ALL.len() as u8), Err(10));
+    }
+
+    #[test]
+    fn test_random() {
+        let mut rng = GameRng::new(7);
+        let colors: Vec<Color> = (0..300).map(|_| Color::random(&mut rng)).collect();
+        assert!(Color::ALL.iter().all(|color| colors.contains(color)));
+    }
+
+    #[test]
+    fn test_const() {
+        const DIM: Rgb = Rgb::from_array(&[32, 32, 32]);
+        const STRIP: [Rgb; 3] = [Rgb::BLACK, DIM, Rgb::WHITE];
+        assert_eq!(STRIP[1], Rgb::GRAY.scaled_fraction(1, 2));
+        assert_eq!(STRIP[0], Rgb::from(Color::Black));
     }
 }
@@ -0,0 +1,671 @@
+//! A line-oriented debug console, to inspect and steer a running game over a serial port during bring-up.
+//!
+//! Every line is a command with at most one argument, separated by whitespace:
+//! - `board` - Print the board, `.` for empty cells, `#` for garbage, the uppercase letter of the shape for locked
+//!   cells and the lowercase letter for the active piece
+//! - `score` - Print the points, lines and level
+//! - `seed` - Print the seed of the game
+//! - `spawn <shape>` - Replace the active piece with a piece of the shape, e.g. `spawn I`
+//! - `gravity <level>` - Jump to a level, changing the gravity, e.g. `gravity 5`
+//! - `pause` - Toggle the pause, like `Action::Pause`
+//! - `reset` - Start over in the menu, with the same seed
+//! - `debug <das|lock|reset|off>` - Toggle a debug overlay of the timings, see `DebugOverlay`, or turn all of them
+//!   off
+//! - `stack <piece|mono|rows>` - Switch the colors of the locked cells, see `StackColorMode`
+//! - `prof` - Print the time every phase of the main loop takes, see `profiler`
+//! - `log [count]` - Print the last records of the log of the game, 16 unless counted otherwise, see `gamelog`
+//! - `stats` - Print how often every shape was dealt, its gaps and the chi-square statistic, see `randomizer`
+//! - `dump` - Print the replay frozen on the last game over or chord as base64 lines to paste into a bug report, see
+//!   `recorder`
+//! - `help` - Print the commands
+//!
+//! The console does not echo the input, so use a terminal with local echo, e.g. `picocom --echo`.
+use crate::board::BoardOps;
+use crate::cell::Cell;
+use crate::coordinate::Coordinate;
+use crate::game::{Game, GameState};
+use crate::gravity::ticks_per_row;
+use crate::input::{Action, InputState};
+use crate::profiler::Report;
+use crate::settings::{DebugOverlay, StackColorMode};
+use crate::tetrominoes::TetrominoShape;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// The maximum number of characters of a line, longer lines are rejected as a whole.
+pub const MAX_LINE: usize = 32;
+
+/// The response to unknown commands and `help`.
+pub const HELP: &str =
+    "commands: board, score, seed, spawn <I|J|L|O|S|T|Z>, gravity <level>, pause, reset, \
+     debug <das|lock|reset|off>, stack <piece|mono|rows>, prof, log [count], stats, dump, help";
+
+/// The response to a `spawn` without a valid shape.
+const SPAWN_USAGE: &str = "usage: spawn <I|J|L|O|S|T|Z>";
+
+/// The response to a `gravity` without a valid level.
+const GRAVITY_USAGE: &str = "usage: gravity <level from 1 to 255>";
+
+/// The response to a `debug` without a valid overlay.
+const DEBUG_USAGE: &str = "usage: debug <das|lock|reset|off>";
+
+/// The response to a `stack` without a valid mode.
+const STACK_USAGE: &str = "usage: stack <piece|mono|rows>";
+
+/// The response to a `log` with an invalid count.
+const LOG_USAGE: &str = "usage: log [count from 1]";
+
+/// The response to a `dump` before any replay was frozen.
+const NO_REPLAY: &str = "no replay, one freezes on a game over or by holding hold and pause";
+
+/// The number of base64 characters per line of a `dump`, the line length of MIME.
+const DUMP_LINE: usize = 76;
+
+/// The number of records printed by a `log` without a count.
+const LOG_LINES: usize = 16;
+
+/// The debug overlays with their names in the console.
+const DEBUG_OVERLAYS: [(&str, DebugOverlay); 3] = [
+    ("das", DebugOverlay::DAS),
+    ("lock", DebugOverlay::LOCK_DELAY),
+    ("reset", DebugOverlay::FINAL_RESET),
+];
+
+/// The stack color modes with their names in the console.
+const STACK_MODES: [(&str, StackColorMode); 3] = [
+    ("piece", StackColorMode::PerPiece),
+    ("mono", StackColorMode::MONOCHROME),
+    ("rows", StackColorMode::GRADIENT),
+];
+
+/// A command of the console.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+    /// Print the board with the active piece.
+    Board,
+    /// Print the points, lines and level.
+    Score,
+    /// Print the seed of the game.
+    Seed,
+    /// Replace the active piece with a piece of the shape.
+    Spawn(TetrominoShape),
+    /// Jump to the level.
+    Gravity(u8),
+    /// Toggle the pause.
+    Pause,
+    /// Start over in the menu.
+    Reset,
+    /// Toggle the debug overlays, or turn all of them off with `None`.
+    Debug(Option<DebugOverlay>),
+    /// Switch the colors of the locked cells.
+    Stack(StackColorMode),
+    /// Print the time spent in every phase of the main loop.
+    Prof,
+    /// Print the last records of the log of the game.
+    Log(usize),
+    /// Print the statistics of the randomizer.
+    Stats,
+    /// Print the frozen replay as base64.
+    Dump,
+    /// Print the commands.
+    Help,
+}
+
+/// Parse a line into a command, ignoring the case of the command and the surrounding whitespace.
+/// # Arguments
+/// - `line` - The line, without the line ending
+/// # Returns
+/// - `Result<Command, &'static str>` - The command, or the response explaining why the line is not a command:
+///   the usage of a command with a missing or invalid argument, or `HELP` for an unknown command
+pub fn parse(line: &str) -> Result<Command, &'static str> {
+    let mut words = line.split_whitespace();
+    let name = words.next().unwrap_or("").to_ascii_lowercase();
+    let argument = words.next();
+    if words.next().is_some() {
+        return Err(HELP);
+    }
+    match (name.as_str(), argument) {
+        ("board", None) => Ok(Command::Board),
+        ("score", None) => Ok(Command::Score),
+        ("seed", None) => Ok(Command::Seed),
+        ("spawn", argument) => {
+            let mut letters = argument.ok_or(SPAWN_USAGE)?.chars();
+            match (letters.next(), letters.next()) {
+                (Some(letter), None) => TetrominoShape::try_from(letter)
+                    .map(Command::Spawn)
+                    .map_err(|_| SPAWN_USAGE),
+                _ => Err(SPAWN_USAGE),
+            }
+        }
+        ("gravity", argument) => match argument.ok_or(GRAVITY_USAGE)?.parse::<u8>() {
+            Ok(level) if level > 0 => Ok(Command::Gravity(level)),
+            _ => Err(GRAVITY_USAGE),
+        },
+        ("pause", None) => Ok(Command::Pause),
+        ("reset", None) => Ok(Command::Reset),
+        ("debug", Some(overlay)) if overlay.eq_ignore_ascii_case("off") => Ok(Command::Debug(None)),
+        ("debug", argument) => DEBUG_OVERLAYS
+            .iter()
+            .find(|(name, _)| argument.is_some_and(|argument| argument.eq_ignore_ascii_case(name)))
+            .map(|(_, overlay)| Command::Debug(Some(*overlay)))
+            .ok_or(DEBUG_USAGE),
+        ("stack", argument) => STACK_MODES
+            .iter()
+            .find(|(name, _)| argument.is_some_and(|argument| argument.eq_ignore_ascii_case(name)))
+            .map(|(_, mode)| Command::Stack(*mode))
+            .ok_or(STACK_USAGE),
+        ("prof", None) => Ok(Command::Prof),
+        ("log", None) => Ok(Command::Log(LOG_LINES)),
+        ("log", Some(count)) => match count.parse::<usize>() {
+            Ok(count) if count > 0 => Ok(Command::Log(count)),
+            _ => Err(LOG_USAGE),
+        },
+        ("stats", None) => Ok(Command::Stats),
+        ("dump", None) => Ok(Command::Dump),
+        ("help", None) => Ok(Command::Help),
+        _ => Err(HELP),
+    }
+}
+
+/// Run a command on a game.
+/// # Arguments
+/// - `game` - A muteable reference to the `Game`
+/// - `profiler` - A reference to the profiler of the main loop, for `prof`
+/// - `command` - The `Command` to run
+/// # Returns
+/// - `String` - The response, lines separated by `\n` without a trailing line ending
+pub fn execute<B: BoardOps<Cell> + Clone>(
+    game: &mut Game<B>,
+    profiler: &dyn Report,
+    command: Command,
+) -> String {
+    match command {
+        Command::Board => _draw_board(game),
+        Command::Score => {
+            let score = game.get_score();
+            format!(
+                "points {}, lines {}, level {}",
+                score.get_points(),
+                score.get_lines(),
+                score.get_level()
+            )
+        }
+        Command::Seed => format!("seed {}", game.seed()),
+        Command::Spawn(shape) => {
+            game.spawn(shape);
+            format!("spawned {}", char::from(shape))
+        }
+        Command::Gravity(level) => {
+            game.set_level(level);
+            format!("level {level}, {} ticks per row", ticks_per_row(level))
+        }
+        Command::Pause => {
+            let paused = game.get_state() == GameState::Paused;
+            game.tick(&InputState::from_action(Action::Pause));
+            match (paused, game.get_state() == GameState::Paused) {
+                (false, true) => "paused",
+                (true, false) => "resumed",
+                _ => "not playing",
+            }
+            .to_string()
+        }
+        Command::Reset => {
+            game.restart();
+            "reset".to_string()
+        }
+        Command::Debug(overlay) => {
+            let mut debug = game.get_settings().debug;
+            match overlay {
+                Some(overlay) => debug.toggle(overlay),
+                None => debug = DebugOverlay::NONE,
+            }
+            game.set_debug_overlay(debug);
+            let names: Vec<&str> = DEBUG_OVERLAYS
+                .iter()
+                .filter(|(_, overlay)| debug.contains(*overlay))
+                .map(|(name, _)| *name)
+                .collect();
+            match names.is_empty() {
+                true => "debug off".to_string(),
+                false => format!("debug {}", names.join(" ")),
+            }
+        }
+        Command::Stack(mode) => {
+            game.set_stack_colors(mode);
+            let (name, _) = STACK_MODES
+                .iter()
+                .find(|(_, stack_mode)| *stack_mode == mode)
+                .expect("the mode was parsed from its name");
+            format!("stack {name}")
+        }
+        Command::Prof => {
+            let mut report = String::new();
+            // Writing into a `String` never fails.
+            let _ = profiler.report_into(&mut report);
+            report
+        }
+        Command::Log(count) => {
+            let mut report = String::new();
+            // Writing into a `String` never fails.
+            let _ = game.get_log().report_into(&mut report, count);
+            match report.is_empty() {
+                true => "log empty".to_string(),
+                false => report,
+            }
+        }
+        Command::Stats => {
+            let mut report = String::new();
+            // Writing into a `String` never fails.
+            let _ = game.get_queue().get_stats().report_into(&mut report);
+            report
+        }
+        Command::Dump => match game.get_recorder().get_frozen() {
+            Some(replay) => {
+                let mut text = vec![0; replay.get_base64_size()];
+                replay
+                    .to_base64_into(&mut text)
+                    .expect("the buffer fits the replay");
+                let mut response = format!("replay of {} ticks", replay.get_ticks());
+                for line in text.chunks(DUMP_LINE) {
+                    response.push('\n');
+                    response.push_str(core::str::from_utf8(line).expect("base64 is ASCII"));
+                }
+                response
+            }
+            None => NO_REPLAY.to_string(),
+        },
+        Command::Help => HELP.to_string(),
+    }
+}
+
+/// Parse a line and run it on a game, see `parse()` and `execute()`.
+/// # Arguments
+/// - `game` - A muteable reference to the `Game`
+/// - `profiler` - A reference to the profiler of the main loop, for `prof`
+/// - `line` - The line, without the line ending
+/// # Returns
+/// - `String` - The response, lines separated by `\n` without a trailing line ending
+pub fn handle_line<B: BoardOps<Cell> + Clone>(
+    game: &mut Game<B>,
+    profiler: &dyn Report,
+    line: &str,
+) -> String {
+    match parse(line) {
+        Ok(command) => execute(game, profiler, command),
+        Err(response) => response.to_string(),
+    }
+}
+
+/// Draw the board with the active piece as text, a line per row from the top.
+fn _draw_board<B: BoardOps<Cell> + Clone>(game: &Game<B>) -> String {
+    let board = game.get_board();
+    let shape = board.get_shape();
+    let mut rows: Vec<Vec<char>> = (0..shape.row)
+        .map(|row| {
+            (0..shape.col)
+                .map(|col| match board.get(Coordinate { row, col }) {
+                    Some(Cell::Filled { shape, .. }) => char::from(shape),
+                    Some(Cell::Garbage) => '#',
+                    _ => '.',
+                })
+                .collect()
+        })
+        .collect();
+    let piece = game.get_piece();
+    let tetromino = piece.get_tetromino();
+    for r in 0..tetromino.get_shape().row {
+        for c in 0..tetromino.get_shape().col {
+            let coord = piece.get_coord() + [r, c];
+            if let (true, Some(cell)) = (
+                tetromino.get_mask()[(r, c)],
+                rows.get_mut(coord.row)
+                    .and_then(|row| row.get_mut(coord.col)),
+            ) {
+                *cell = char::from(tetromino.shape).to_ascii_lowercase();
+            }
+        }
+    }
+    rows.iter()
+        .map(|row| row.iter().collect::<String>())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// A console collecting received bytes into lines and answering every complete line.
+/// # Attributes
+/// - `line` - The characters received since the last line ending
+/// - `overflow` - Whether the current line exceeded `MAX_LINE` characters
+#[derive(Clone, Debug, Default)]
+pub struct Console {
+    line: heapless::String<MAX_LINE>,
+    overflow: bool,
+}
+
+impl Console {
+    /// Create a console without any received characters.
+    pub fn new() -> Self {
+        Console::default()
+    }
+
+    /// Receive bytes from the serial port, running every line they complete on a game.
+    ///
+    /// Lines end with `\r` or `\n`, so empty lines between `\r\n` are skipped. Backspace and delete remove the
+    /// last character, other control and non-ASCII bytes are dropped.
+    /// # Arguments
+    /// - `bytes` - The received bytes
+    /// - `game` - A muteable reference to the `Game`
+    /// - `profiler` - A reference to the profiler of the main loop, for `prof`
+    /// - `out` - A muteable reference to the `String` to append the responses to, every line ending in `\r\n`
+    pub fn receive<B: BoardOps<Cell> + Clone>(
+        &mut self,
+        bytes: &[u8],
+        game: &mut Game<B>,
+        profiler: &dyn Report,
+        out: &mut String,
+    ) {
+        for byte in bytes {
+            match byte {
+                b'\r' | b'\n' => {
+                    let response = match self.overflow {
+                        true => format!("line longer than {MAX_LINE} characters"),
+                        false => handle_line(game, profiler, &self.line),
+                    };
+                    if self.overflow || !self.line.trim().is_empty() {
+                        for line in response.lines() {
+                            out.push_str(line);
+                            out.push_str("\r\n");
+                        }
+                    }
+                    self.line.clear();
+                    self.overflow = false;
+                }
+                0x08 | 0x7F => {
+                    self.line.pop();
+                }
+                b' '..=b'~' if self.line.push(*byte as char).is_err() => self.overflow = true,
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(feature = "rp2040")]
+pub use usb::UsbConsole;
+
+#[cfg(feature = "rp2040")]
+mod usb {
+    use super::Console;
+    use crate::board::BoardOps;
+    use crate::cell::Cell;
+    use crate::game::Game;
+    use crate::profiler::Report;
+    use alloc::string::String;
+    use alloc::vec::Vec;
+    use usb_device::bus::{UsbBus, UsbBusAllocator};
+    use usb_device::device::{StringDescriptors, UsbDevice, UsbDeviceBuilder, UsbVidPid};
+    use usbd_serial::SerialPort;
+
+    /// The vendor and product ID of the shared ID of pid.codes for testing CDC-ACM devices.
+    const VID_PID: UsbVidPid = UsbVidPid(0x16C0, 0x27DD);
+
+    /// The number of bytes read from the serial port at once, a full-speed bulk packet.
+    const PACKET_BYTES: usize = 64;
+
+    /// The debug console as a USB CDC-ACM serial port.
+    ///
+    /// The USB device only answers the host while polled, so poll it at least every few milliseconds. Polling
+    /// once per iteration of a main loop blocking on the LEDs still enumerates, but slowly.
+    /// # Attributes
+    /// - `device` - The USB device
+    /// - `serial` - The serial port class of the device
+    /// - `console` - The `Console` collecting the received lines
+    /// - `pending` - The bytes of the responses not yet accepted by the serial port
+    pub struct UsbConsole<'a, U: UsbBus> {
+        device: UsbDevice<'a, U>,
+        serial: SerialPort<'a, U>,
+        console: Console,
+        pending: Vec<u8>,
+    }
+
+    impl<'a, U: UsbBus> UsbConsole<'a, U> {
+        /// Create the serial port and the USB device on a bus.
+        /// # Arguments
+        /// - `bus` - A reference to the `UsbBusAllocator`, living as long as the console
+        /// # Returns
+        /// - `UsbConsole<'a, U>` - The console, enumerating once polled
+        pub fn new(bus: &'a UsbBusAllocator<U>) -> Self {
+            let serial = SerialPort::new(bus);
+            let device = UsbDeviceBuilder::new(bus, VID_PID)
+                .strings(&[StringDescriptors::default()
+                    .manufacturer("rust-tetris-rp2040")
+                    .product("Tetris debug console")
+                    .serial_number("0")])
+                .expect("a single language of strings is supported")
+                .device_class(usbd_serial::USB_CLASS_CDC)
+                .build();
+            UsbConsole {
+                device,
+                serial,
+                console: Console::new(),
+                pending: Vec::new(),
+            }
+        }
+
+        /// Answer the host, run the received lines on a game and send as much of the responses as fits.
+        /// # Arguments
+        /// - `game` - A muteable reference to the `Game`
+        /// - `profiler` - A reference to the profiler of the main loop, for `prof`
+        pub fn poll<B: BoardOps<Cell> + Clone>(
+            &mut self,
+            game: &mut Game<B>,
+            profiler: &dyn Report,
+        ) {
+            if self.device.poll(&mut [&mut self.serial]) {
+                let mut buffer = [0; PACKET_BYTES];
+                if let Ok(count) = self.serial.read(&mut buffer) {
+                    let mut out = String::new();
+                    self.console
+                        .receive(&buffer[..count], game, profiler, &mut out);
+                    self.pending.extend_from_slice(out.as_bytes());
+                }
+            }
+            if !self.pending.is_empty() {
+                if let Ok(count) = self.serial.write(&self.pending) {
+                    self.pending.drain(..count);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{handle_line, parse, Command, Console, DUMP_LINE, HELP, MAX_LINE, NO_REPLAY};
+    use crate::board::FixedBoard;
+    use crate::cell::Cell;
+    use crate::game::{Game, GameState};
+    use crate::input::{Action, InputState};
+    use crate::profiler::{Profiler, Report};
+    use crate::recorder::{CHORD, CHORD_TICKS};
+    use crate::replay::Replay;
+    use crate::settings::{DebugOverlay, Settings, StackColorMode};
+    use crate::tetrominoes::TetrominoShape;
+    use alloc::string::String;
+
+    /// A small game on a fixed board, started and counted down until playing.
+    fn playing() -> Game<FixedBoard<Cell, 6, 5>> {
+        let mut game = Game::with_board(FixedBoard::new(Cell::Empty), Settings::default(), 152);
+        game.tick(&InputState::from_action(Action::Start));
+        while game.get_state() != GameState::Playing {
+            game.tick(&InputState::default());
+        }
+        game
+    }
+
+    /// A profiler without any measured frames.
+    fn profiler() -> Profiler<fn() -> u64> {
+        Profiler::new(|| 0)
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("board"), Ok(Command::Board));
+        assert_eq!(parse("  SCORE "), Ok(Command::Score));
+        assert_eq!(parse("spawn i"), Ok(Command::Spawn(TetrominoShape::I)));
+        assert_eq!(parse("gravity 5"), Ok(Command::Gravity(5)));
+        assert_eq!(parse("log"), Ok(Command::Log(16)));
+        assert_eq!(parse("log 3"), Ok(Command::Log(3)));
+        // Missing or invalid arguments get the usage of the command.
+        assert!(parse("spawn").unwrap_err().starts_with("usage: spawn"));
+        assert!(parse("spawn X").unwrap_err().starts_with("usage: spawn"));
+        assert!(parse("spawn IJ").unwrap_err().starts_with("usage: spawn"));
+        assert!(parse("gravity 0")
+            .unwrap_err()
+            .starts_with("usage: gravity"));
+        assert!(parse("gravity 256")
+            .unwrap_err()
+            .starts_with("usage: gravity"));
+        assert!(parse("log 0").unwrap_err().starts_with("usage: log"));
+        // Unknown commands and extra arguments get the help.
+        assert_eq!(parse("fly"), Err(HELP));
+        assert_eq!(parse("board 2"), Err(HELP));
+        assert_eq!(parse("pause now please"), Err(HELP));
+        assert_eq!(handle_line(&mut playing(), &profiler(), "fly"), HELP);
+    }
+
+    #[test]
+    fn test_commands() {
+        let mut game = playing();
+        assert_eq!(handle_line(&mut game, &profiler(), "seed"), "seed 152");
+        assert_eq!(
+            handle_line(&mut game, &profiler(), "score"),
+            "points 0, lines 0, level 1"
+        );
+        // A spawned piece replaces the active piece at the top, lowercase on the board.
+        assert_eq!(handle_line(&mut game, &profiler(), "spawn O"), "spawned O");
+        assert_eq!(game.get_piece().get_tetromino().shape, TetrominoShape::O);
+        assert_eq!(
+            handle_line(&mut game, &profiler(), "board"),
+            ".oo..\n.oo..\n.....\n.....\n.....\n.....",
+        );
+        // Locked cells are uppercase.
+        game.tick(&InputState::from_action(Action::HardDrop));
+        assert_eq!(handle_line(&mut game, &profiler(), "spawn I"), "spawned I");
+        assert_eq!(
+            handle_line(&mut game, &profiler(), "board"),
+            "iiii.\n.....\n.....\n.....\n.OO..\n.OO..",
+        );
+        assert_eq!(
+            handle_line(&mut game, &profiler(), "gravity 5"),
+            "level 5, 21 ticks per row"
+        );
+        assert_eq!(game.get_score().get_level(), 5);
+        // The profiler reports itself.
+        let mut report = String::new();
+        profiler().report_into(&mut report).unwrap();
+        assert_eq!(handle_line(&mut game, &profiler(), "PROF"), report);
+        // Pausing toggles, and is refused outside of a game.
+        assert_eq!(handle_line(&mut game, &profiler(), "pause"), "paused");
+        assert_eq!(game.get_state(), GameState::Paused);
+        assert_eq!(handle_line(&mut game, &profiler(), "pause"), "resumed");
+        assert_eq!(game.get_state(), GameState::Playing);
+        // The log ends with the pause, a record per line with its tick.
+        assert_eq!(
+            handle_line(&mut game, &profiler(), "log 3"),
+            "     183 action Pause\n     183 state Paused\n     184 state Playing"
+        );
+        assert!(handle_line(&mut game, &profiler(), "log").starts_with("       1 action Start\n"));
+        // The debug overlays toggle one by one, and turn off together.
+        assert_eq!(
+            handle_line(&mut game, &profiler(), "debug das"),
+            "debug das"
+        );
+        assert_eq!(
+            handle_line(&mut game, &profiler(), "DEBUG Reset"),
+            "debug das reset"
+        );
+        assert_eq!(
+            handle_line(&mut game, &profiler(), "debug das"),
+            "debug reset"
+        );
+        assert_eq!(game.get_settings().debug, DebugOverlay::FINAL_RESET);
+        assert_eq!(
+            handle_line(&mut game, &profiler(), "debug off"),
+            "debug off"
+        );
+        assert!(handle_line(&mut game, &profiler(), "debug").starts_with("usage: debug"));
+        // The stack colors switch for this game and the next.
+        assert_eq!(
+            handle_line(&mut game, &profiler(), "stack Rows"),
+            "stack rows"
+        );
+        assert_eq!(game.get_settings().stack_colors, StackColorMode::GRADIENT);
+        assert!(handle_line(&mut game, &profiler(), "stack red").starts_with("usage: stack"));
+        // The statistics of the randomizer span the reset, counting the first piece of the new game.
+        let dealt = game.get_queue().get_stats().get_dealt();
+        assert_eq!(handle_line(&mut game, &profiler(), "reset"), "reset");
+        assert_eq!(game.get_queue().get_stats().get_dealt(), dealt + 1);
+        let stats = handle_line(&mut game, &profiler(), "stats");
+        assert_eq!(stats.lines().count(), 8);
+        assert!(stats.contains(&format!("dealt {}, ", dealt + 1)));
+        assert_eq!(game.get_state(), GameState::Menu);
+        assert_eq!(game.get_score().get_level(), 1);
+        assert_eq!(handle_line(&mut game, &profiler(), "pause"), "not playing");
+        assert_eq!(game.get_state(), GameState::Menu);
+    }
+
+    #[test]
+    fn test_dump() {
+        // Nothing to dump until the chord freezes the window, then the lines paste back into the replay.
+        let mut game = Game::new(Settings::default(), 152);
+        game.set_recording(true);
+        game.tick(&InputState::from_action(Action::Start));
+        game.tick(&InputState::default());
+        assert_eq!(handle_line(&mut game, &profiler(), "dump"), NO_REPLAY);
+        let mut input = InputState::default();
+        for _ in 0..CHORD_TICKS {
+            input = input.next(&CHORD);
+            game.tick(&input);
+        }
+        let dump = handle_line(&mut game, &profiler(), "dump");
+        let replay = game.get_recorder().get_frozen().unwrap();
+        let mut lines = dump.lines();
+        assert_eq!(
+            lines.next(),
+            Some(format!("replay of {} ticks", replay.get_ticks()).as_str())
+        );
+        let text: String = lines
+            .inspect(|line| assert!(line.len() <= DUMP_LINE))
+            .collect();
+        let mut buf = vec![0; text.len()];
+        let pasted = Replay::from_base64(text.as_bytes(), &mut buf).unwrap();
+        assert_eq!(pasted.to_bytes(), replay.to_bytes());
+    }
+
+    #[test]
+    fn test_console() {
+        let mut game = playing();
+        let mut console = Console::new();
+        let mut out = String::new();
+        // A line split over several reads is answered once complete, empty lines are skipped.
+        console.receive(b"se", &mut game, &profiler(), &mut out);
+        assert_eq!(out, "");
+        console.receive(b"ed\r\n\r\nscx\x7Fore\n", &mut game, &profiler(), &mut out);
+        assert_eq!(out, "seed 152\r\npoints 0, lines 0, level 1\r\n");
+        // Every line of a multi-line response ends in \r\n.
+        out.clear();
+        console.receive(b"board\r", &mut game, &profiler(), &mut out);
+        assert_eq!(out.matches("\r\n").count(), 6);
+        // A line that is too long is rejected as a whole.
+        out.clear();
+        let long = [b'x'; MAX_LINE + 1];
+        console.receive(&long, &mut game, &profiler(), &mut out);
+        console.receive(b"\rfly\r", &mut game, &profiler(), &mut out);
+        assert_eq!(
+            out,
+            alloc::format!("line longer than {MAX_LINE} characters\r\n{HELP}\r\n")
+        );
+    }
+}
@@ -1,7 +1,7 @@
-#![allow(dead_code)]
-use std::ops;
+use core::ops;
 
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct Coordinate {
     /// A basic struct modelling a coordinate as row and a column
     pub row: usize,
@@ -71,6 +71,103 @@ impl Coordinate {
     }
 }
 
+/// An inclusive rectangle of coordinates, e.g. the pixels of a frame that changed since it was last sent.
+/// # Attributes
+/// - `top_left` - The coordinate with the lowest row and column in the region
+/// - `bottom_right` - The coordinate with the highest row and column in the region
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Region {
+    pub top_left: Coordinate,
+    pub bottom_right: Coordinate,
+}
+
+impl Region {
+    /// Instantiate a `Region` covering a single coordinate.
+    /// # Arguments
+    /// - `coord` - The only `Coordinate` in the region
+    /// # Returns
+    /// - `Region` - The region of 1 x 1
+    pub fn from_coord(coord: Coordinate) -> Self {
+        Region {
+            top_left: coord,
+            bottom_right: coord,
+        }
+    }
+
+    /// Grow an optional region to include a coordinate, e.g. to fold coordinates into their bounding box.
+    /// # Arguments
+    /// - `region` - The region to grow, or `None` for an empty region
+    /// - `coord` - The `Coordinate` to include
+    /// # Returns
+    /// - `Region` - The smallest region covering both the region and the coordinate
+    pub fn including(region: Option<Region>, coord: Coordinate) -> Self {
+        match region {
+            Some(region) => region.union(Region::from_coord(coord)),
+            None => Region::from_coord(coord),
+        }
+    }
+
+    /// Get the bounding box of two regions.
+    /// # Arguments
+    /// - `other` - The other `Region`
+    /// # Returns
+    /// - `Region` - The smallest region covering both regions
+    pub fn union(self, other: Region) -> Self {
+        Region {
+            top_left: Coordinate::from_array([
+                self.top_left.row.min(other.top_left.row),
+                self.top_left.col.min(other.top_left.col),
+            ]),
+            bottom_right: Coordinate::from_array([
+                self.bottom_right.row.max(other.bottom_right.row),
+                self.bottom_right.col.max(other.bottom_right.col),
+            ]),
+        }
+    }
+
+    /// Move the region, e.g. from the coordinates of a board to the pixels of the panel it is drawn on.
+    /// # Arguments
+    /// - `offset` - The `Coordinate` to add to both corners
+    /// # Returns
+    /// - `Region` - The moved region
+    pub fn translated(self, offset: Coordinate) -> Self {
+        Region {
+            top_left: self.top_left + offset,
+            bottom_right: self.bottom_right + offset,
+        }
+    }
+
+    /// Check if a coordinate lies within the region.
+    /// # Arguments
+    /// - `coord` - The `Coordinate` to check
+    /// # Returns
+    /// - `bool` - Whether (`true`) or not (`false`) the coordinate is covered by the region
+    pub fn contains(&self, coord: Coordinate) -> bool {
+        coord.is_within_bounds(self.top_left, self.bottom_right)
+    }
+
+    /// Check if two regions share a coordinate.
+    /// # Arguments
+    /// - `other` - A reference to the other `Region`
+    /// # Returns
+    /// - `bool` - Whether (`true`) or not (`false`) any coordinate lies in both regions
+    pub fn overlaps(&self, other: &Region) -> bool {
+        self.top_left.row <= other.bottom_right.row
+            && other.top_left.row <= self.bottom_right.row
+            && self.top_left.col <= other.bottom_right.col
+            && other.top_left.col <= self.bottom_right.col
+    }
+
+    /// Iterate over the coordinates of the region in row major order.
+    pub fn coords(&self) -> impl Iterator<Item = Coordinate> {
+        let (top_left, bottom_right) = (self.top_left, self.bottom_right);
+        (top_left.row..=bottom_right.row).flat_map(move |row| {
+            (top_left.col..=bottom_right.col).map(move |col| Coordinate::from_array([row, col]))
+        })
+    }
+}
+
 /// Overloading + and - operators for other Coordinate
 impl ops::Add<Coordinate> for Coordinate {
     type Output = Coordinate;
@@ -113,8 +210,8 @@ impl ops::Sub<[usize; 2]> for Coordinate {
 
 #[cfg(test)]
 mod tests {
-    use crate::coordinate::Coordinate;
-    use array2d::Array2D;
+    use crate::coordinate::{Coordinate, Region};
+    use crate::grid::Array2D;
 
     #[test]
     fn test_from_row_major() {
@@ -154,4 +251,26 @@ mod tests {
             array.get_column_major(index)
         )
     }
+
+    #[test]
+    fn test_region() {
+        let region = [[2, 1], [0, 3], [1, 2]]
+            .into_iter()
+            .fold(None, |region, coord| {
+                Some(Region::including(region, Coordinate::from_array(coord)))
+            })
+            .unwrap();
+        assert_eq!(region.top_left, Coordinate::from_array([0, 1]));
+        assert_eq!(region.bottom_right, Coordinate::from_array([2, 3]));
+        assert_eq!(region.coords().count(), 9);
+        assert!(region.contains(Coordinate::from_array([1, 1])));
+        assert!(!region.contains(Coordinate::from_array([1, 0])));
+        let moved = region.translated(Coordinate::from_array([1, 1]));
+        assert_eq!(moved.bottom_right, Coordinate::from_array([3, 4]));
+        assert_eq!(region.union(moved).coords().count(), 16);
+        // Regions sharing only a corner overlap, regions side by side do not.
+        assert!(region.overlaps(&moved));
+        assert!(region.overlaps(&Region::from_coord(Coordinate::from_array([2, 3]))));
+        assert!(!region.overlaps(&Region::from_coord(Coordinate::from_array([3, 3]))));
+    }
 }
@@ -0,0 +1,266 @@
+//! The crash screen of the firmware, telling a panic apart from a hang at a glance.
+//!
+//! The panic handler draws a red checkerboard on the panel, with the line number of the panic as a binary number
+//! on the top row: a white LED for every 1, a dark one for every 0, the least significant bit in the rightmost
+//! column. Then it stops feeding the watchdog, which reboots the board after `CRASH_MICROS`.
+//!
+//! Nothing of the normal LED path can be trusted after a panic, the other core may be halfway through a DMA
+//! transfer. So the screen is sent by toggling the data pin from the CPU, timed by counting cycles of the
+//! SysTick timer, without the PIO, the DMA or any allocation.
+use crate::color::Rgb;
+use crate::coordinate::Coordinate;
+use crate::ws2812::{BIT_RATE_HZ, CYCLES_PER_BIT, T1, T2};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// The red of the checkerboard, dim so the whole panel does not overload the supply.
+pub const CRASH_RED: Rgb = Rgb::from_array(&[48, 0, 0]);
+
+/// The color of the 1 bits of the line number.
+pub const LINE_WHITE: Rgb = Rgb::from_array(&[48, 48, 48]);
+
+/// The number of microseconds the crash screen is shown before the watchdog reboots the board.
+pub const CRASH_MICROS: u32 = 3_000_000;
+
+/// Whether (`true`) or not (`false`) a core panicked, so the other core stops feeding the watchdog.
+static PANICKED: AtomicBool = AtomicBool::new(false);
+
+/// Record a panic, so the watchdog is no longer fed and reboots the board.
+pub fn set_panicked() {
+    PANICKED.store(true, Ordering::Release);
+}
+
+/// Check whether a core panicked since the boot.
+pub fn has_panicked() -> bool {
+    PANICKED.load(Ordering::Acquire)
+}
+
+/// Get a bit of the line number shown on a row of LEDs.
+/// # Arguments
+/// - `line` - The line number
+/// - `col` - The column of the LED
+/// - `cols` - The number of columns of the row
+/// # Returns
+/// - `bool` - Whether (`true`) or not (`false`) the bit shown in the column is set
+fn _line_bit(line: u32, col: usize, cols: usize) -> bool {
+    let bit = cols - 1 - col;
+    bit < u32::BITS as usize && (line >> bit) & 1 == 1
+}
+
+/// Encode a line number as a row of LEDs, the least significant bit in the last column.
+///
+/// A row of 32 columns shows any line number, narrower rows only its lowest bits.
+/// # Arguments
+/// - `line` - The line number
+/// - `out` - A muteable reference to the row to write the bits into, one per column
+pub fn line_pattern(line: u32, out: &mut [bool]) {
+    let cols = out.len();
+    for (col, bit) in out.iter_mut().enumerate() {
+        *bit = _line_bit(line, col, cols);
+    }
+}
+
+/// Get the color of a pixel of the crash screen.
+/// # Arguments
+/// - `coord` - The [row, col] of the pixel
+/// - `dims` - The number of [rows, cols] of the panel
+/// - `line` - The line number to show on the top row, or `None` for a checkerboard only
+/// # Returns
+/// - `Rgb` - The color of the pixel
+pub fn crash_pixel(coord: Coordinate, dims: Coordinate, line: Option<u32>) -> Rgb {
+    match (coord.row, line) {
+        (0, Some(line)) => match _line_bit(line, coord.col, dims.col) {
+            true => LINE_WHITE,
+            false => Rgb::BLACK,
+        },
+        _ => match (coord.row + coord.col) & 1 {
+            0 => CRASH_RED,
+            _ => Rgb::BLACK,
+        },
+    }
+}
+
+/// The timing of a bit sent by the CPU, in cycles of the system clock.
+/// # Attributes
+/// - `one_high` - The number of cycles a 1 is high
+/// - `zero_high` - The number of cycles a 0 is high
+/// - `period` - The number of cycles of every bit
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BitCycles {
+    pub one_high: u32,
+    pub zero_high: u32,
+    pub period: u32,
+}
+
+impl BitCycles {
+    /// Derive the timing of the PIO program, see `ws2812`, for a system clock.
+    /// # Arguments
+    /// - `sys_hz` - The frequency of the system clock
+    /// # Returns
+    /// - `BitCycles` - The timing, rounded down to whole cycles
+    pub fn new(sys_hz: u32) -> Self {
+        let cycles = |pio_cycles: u32| {
+            (pio_cycles as u64 * sys_hz as u64 / (BIT_RATE_HZ * CYCLES_PER_BIT) as u64) as u32
+        };
+        BitCycles {
+            one_high: cycles((T1 + T2) as u32),
+            zero_high: cycles(T1 as u32),
+            period: cycles(CYCLES_PER_BIT),
+        }
+    }
+}
+
+#[cfg(feature = "rp2040")]
+pub use bitbang::show_crash;
+
+#[cfg(feature = "rp2040")]
+mod bitbang {
+    use super::{crash_pixel, BitCycles, CRASH_MICROS};
+    use crate::coordinate::Coordinate;
+    use crate::framebuffer::PanelLayout;
+    use crate::ws2812::{to_word, RESET_MICROS};
+    use cortex_m::peripheral::syst::SystClkSource;
+    use cortex_m::peripheral::SYST;
+    use rp2040_hal::fugit::ExtU32;
+    use rp2040_hal::pac;
+    use rp2040_hal::Watchdog;
+
+    /// The largest value of the 24 bit SysTick counter.
+    const SYST_MAX: u32 = 0x00FF_FFFF;
+
+    /// Wait until a number of cycles passed since a reading of the SysTick counter, which counts down.
+    fn _wait_since(start: u32, cycles: u32) {
+        while start.wrapping_sub(SYST::get_current()) & SYST_MAX < cycles {}
+    }
+
+    /// Show the crash screen and let the watchdog reboot the board, from a panic handler.
+    ///
+    /// Takes over the hardware without asking the HAL: the PIO and the DMA are reset to stop any transfer in
+    /// flight, the data pin is switched to the SIO and the watchdog is armed with `CRASH_MICROS`.
+    /// # Arguments
+    /// - `pin` - The GPIO number of the data line of the LEDs
+    /// - `dims` - The number of [rows, cols] of the panel
+    /// - `layout` - The order in which the LEDs are chained
+    /// - `line` - The line number of the panic, if known
+    /// - `sys_hz` - The frequency of the system clock
+    pub fn show_crash(
+        pin: usize,
+        dims: Coordinate,
+        layout: PanelLayout,
+        line: Option<u32>,
+        sys_hz: u32,
+    ) -> ! {
+        cortex_m::interrupt::disable();
+        super::set_panicked();
+        // SAFETY: the panicking core owns the board from here on, the other one only stops feeding the watchdog.
+        let pac = unsafe { pac::Peripherals::steal() };
+        let mut syst = unsafe { cortex_m::Peripherals::steal() }.SYST;
+        pac.RESETS
+            .reset()
+            .modify(|_, w| w.pio0().set_bit().dma().set_bit());
+        pac.IO_BANK0
+            .gpio(pin)
+            .gpio_ctrl()
+            .write(|w| w.funcsel().sio());
+        pac.SIO.gpio_oe_set().write(|w| unsafe { w.bits(1 << pin) });
+        pac.SIO
+            .gpio_out_clr()
+            .write(|w| unsafe { w.bits(1 << pin) });
+        Watchdog::new(pac.WATCHDOG).start(CRASH_MICROS.micros());
+        syst.set_clock_source(SystClkSource::Core);
+        syst.set_reload(SYST_MAX);
+        syst.clear_current();
+        syst.enable_counter();
+
+        let bit = BitCycles::new(sys_hz);
+        // Latch whatever half a frame the LEDs received before the panic.
+        let latch = RESET_MICROS * (sys_hz / 1_000_000);
+        _wait_since(SYST::get_current(), latch);
+        for index in 0..dims.inner_product() {
+            let coord = layout
+                .to_coord(index, dims)
+                .expect("the layout maps every LED into the panel");
+            let word = to_word(crash_pixel(coord, dims, line));
+            for shift in (8..32).rev() {
+                let high = match (word >> shift) & 1 {
+                    1 => bit.one_high,
+                    _ => bit.zero_high,
+                };
+                let start = SYST::get_current();
+                pac.SIO
+                    .gpio_out_set()
+                    .write(|w| unsafe { w.bits(1 << pin) });
+                _wait_since(start, high);
+                pac.SIO
+                    .gpio_out_clr()
+                    .write(|w| unsafe { w.bits(1 << pin) });
+                _wait_since(start, bit.period);
+            }
+        }
+        loop {
+            cortex_m::asm::wfi();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{crash_pixel, line_pattern, BitCycles, CRASH_RED, LINE_WHITE};
+    use crate::color::Rgb;
+    use crate::coordinate::Coordinate;
+    use std::vec::Vec;
+
+    #[test]
+    fn test_line_pattern() {
+        let mut row = [true; 8];
+        line_pattern(0b1011_0010, &mut row);
+        assert_eq!(row, [true, false, true, true, false, false, true, false]);
+        // A narrow row only shows the lowest bits.
+        let mut row = [false; 4];
+        line_pattern(0x1F3, &mut row);
+        assert_eq!(row, [false, false, true, true]);
+        // A wide row pads the line number with zeroes on the left.
+        let mut row = [true; 40];
+        line_pattern(u32::MAX, &mut row);
+        assert!(row[..8].iter().all(|bit| !bit));
+        assert!(row[8..].iter().all(|bit| *bit));
+    }
+
+    #[test]
+    fn test_crash_pixel() {
+        let dims = Coordinate { row: 64, col: 32 };
+        // Line 5 lights the last and the third to last LED of the top row.
+        let top: Vec<Rgb> = (0..32)
+            .map(|col| crash_pixel(Coordinate { row: 0, col }, dims, Some(5)))
+            .collect();
+        assert_eq!(top[31], LINE_WHITE);
+        assert_eq!(top[30], Rgb::BLACK);
+        assert_eq!(top[29], LINE_WHITE);
+        assert!(top[..29].iter().all(|color| *color == Rgb::BLACK));
+        // The rest is a checkerboard, also the top row without a line number.
+        for (row, col, color) in [
+            (1, 0, Rgb::BLACK),
+            (1, 1, CRASH_RED),
+            (2, 0, CRASH_RED),
+            (63, 31, CRASH_RED),
+        ] {
+            assert_eq!(crash_pixel(Coordinate { row, col }, dims, Some(5)), color);
+        }
+        assert_eq!(
+            crash_pixel(Coordinate { row: 0, col: 0 }, dims, None),
+            CRASH_RED
+        );
+    }
+
+    #[test]
+    fn test_bit_cycles() {
+        // 15.625 system cycles per PIO cycle at 125 MHz.
+        assert_eq!(
+            BitCycles::new(125_000_000),
+            BitCycles {
+                one_high: 109,
+                zero_high: 31,
+                period: 156,
+            }
+        );
+    }
+}
@@ -0,0 +1,128 @@
+//! Glyphs of the actions of the player, and the input display drawing the recent actions with them.
+//!
+//! The glyphs are 3x3 pixels, so the full `InputHistory` of 8 actions fits the 32 columns of the panel in a single
+//! row, newest on the left. Every action fades out over `FADE_TICKS` ticks and disappears after.
+use crate::clock::TICK_HZ;
+use crate::color::Rgb;
+use crate::coordinate::Coordinate;
+use crate::framebuffer::FrameBuffer;
+use crate::input::history::InputHistory;
+use crate::input::Action;
+
+/// The number of rows of an action glyph.
+pub const ACTION_GLYPH_ROWS: usize = 3;
+
+/// The number of columns of an action glyph.
+pub const ACTION_GLYPH_COLS: usize = 3;
+
+/// The number of ticks an action takes to fade out, 2 seconds at the nominal 60 ticks per second.
+pub const FADE_TICKS: u32 = 2 * TICK_HZ;
+
+/// Get the glyph of an action, every row stored in the 3 lowest bits with the leftmost pixel as MSB.
+/// # Arguments
+/// - `action` - The `Action` to draw
+/// # Returns
+/// - `[u8; ACTION_GLYPH_ROWS]` - The rows of the glyph: arrows to move, arcs to rotate, an H to hold, bars to pause
+///   and a triangle to start
+pub fn action_glyph(action: Action) -> [u8; ACTION_GLYPH_ROWS] {
+    match action {
+        Action::Left => [0b010, 0b100, 0b010],
+        Action::Right => [0b010, 0b001, 0b010],
+        Action::RotateCw => [0b110, 0b001, 0b011],
+        Action::RotateCcw => [0b011, 0b100, 0b110],
+        Action::SoftDrop => [0b000, 0b101, 0b010],
+        Action::HardDrop => [0b101, 0b010, 0b111],
+        Action::Hold => [0b101, 0b111, 0b101],
+        Action::Pause => [0b101, 0b101, 0b101],
+        Action::Start => [0b100, 0b110, 0b100],
+    }
+}
+
+/// Draw the recent actions of the player in a row, each fading out with its age.
+/// # Arguments
+/// - `fb` - A muteable reference to the `FrameBuffer` to draw into
+/// - `history` - A reference to the `InputHistory` to show
+/// - `origin` - The position of the top-left pixel of the newest action
+/// - `color` - The color of a fresh action
+pub fn draw_input_history<const N: usize>(
+    fb: &mut FrameBuffer<N>,
+    history: &InputHistory,
+    origin: Coordinate,
+    color: Rgb,
+) {
+    for (index, (action, age)) in history.iter().enumerate() {
+        if age >= FADE_TICKS {
+            continue;
+        }
+        let brightness = (FADE_TICKS - age) * u8::MAX as u32 / FADE_TICKS;
+        let color = color.scaled(brightness as u8);
+        let left = index * (ACTION_GLYPH_COLS + 1);
+        for (r, row) in action_glyph(action).iter().enumerate() {
+            for c in 0..ACTION_GLYPH_COLS {
+                if row >> (ACTION_GLYPH_COLS - 1 - c) & 1 == 1 {
+                    fb.set_pixel(origin + [r, left + c], color);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{action_glyph, draw_input_history, FADE_TICKS};
+    use crate::color::Rgb;
+    use crate::coordinate::Coordinate;
+    use crate::framebuffer::FrameBuffer;
+    use crate::input::history::InputHistory;
+    use crate::input::{Action, ACTIONS};
+
+    #[test]
+    fn test_glyphs() {
+        // Every action has a glyph of its own.
+        for (index, action) in ACTIONS.iter().enumerate() {
+            let glyph = action_glyph(*action);
+            assert!(glyph.iter().any(|row| *row != 0));
+            assert!(glyph.iter().all(|row| *row < 1 << 3));
+            assert!(ACTIONS[..index]
+                .iter()
+                .all(|other| action_glyph(*other) != glyph));
+        }
+    }
+
+    #[test]
+    fn test_draw_fading() {
+        let mut history = InputHistory::new();
+        history.record(Action::HardDrop);
+        for _ in 0..FADE_TICKS / 2 {
+            history.tick();
+        }
+        history.record(Action::Left);
+        let mut frame = FrameBuffer::<24>::new(Coordinate::from_array([3, 8]));
+        draw_input_history(
+            &mut frame,
+            &history,
+            Coordinate::from_array([0, 0]),
+            Rgb::WHITE,
+        );
+        // The fresh shift is drawn at full brightness on the left, the older hard drop at half next to it.
+        let pixel = |row, col| frame.get_pixel(Coordinate::from_array([row, col])).unwrap();
+        assert_eq!(pixel(1, 0), Rgb::WHITE);
+        assert_eq!(pixel(0, 0), Rgb::BLACK);
+        assert_eq!(pixel(2, 5), Rgb::WHITE.scaled(127));
+        // A faded out action is not drawn anymore.
+        for _ in 0..FADE_TICKS / 2 {
+            history.tick();
+        }
+        let mut frame = FrameBuffer::<24>::new(Coordinate::from_array([3, 8]));
+        draw_input_history(
+            &mut frame,
+            &history,
+            Coordinate::from_array([0, 0]),
+            Rgb::WHITE,
+        );
+        assert_eq!(
+            frame.get_pixel(Coordinate::from_array([2, 5])),
+            Some(Rgb::BLACK)
+        );
+    }
+}
@@ -0,0 +1,158 @@
+use crate::color::Rgb;
+use crate::coordinate::Coordinate;
+use crate::framebuffer::FrameBuffer;
+
+/// The number of decimal digits of the largest `u32`.
+pub const MAX_DIGITS: usize = 10;
+
+/// The number of rows of a glyph.
+pub const GLYPH_ROWS: usize = 5;
+
+/// The number of columns of a glyph.
+pub const GLYPH_COLS: usize = 3;
+
+/// A 3x5 pixel font of the digits 0 to 9, every row stored in the 3 lowest bits with the leftmost pixel as MSB.
+const DIGITS: [[u8; GLYPH_ROWS]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b011, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b010, 0b010, 0b010],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+
+/// Get the binary mask of a digit, to draw it on the panel without allocating.
+/// # Arguments
+/// - `digit` - The digit, from 0 to 9
+/// # Returns
+/// - `[[bool; GLYPH_COLS]; GLYPH_ROWS]` - The rows of the mask of the digit
+pub fn digit_mask(digit: u8) -> [[bool; GLYPH_COLS]; GLYPH_ROWS] {
+    DIGITS[digit as usize % 10].map(|row| {
+        let mut pixels = [false; GLYPH_COLS];
+        for (c, pixel) in pixels.iter_mut().enumerate() {
+            *pixel = row >> (GLYPH_COLS - 1 - c) & 1 == 1;
+        }
+        pixels
+    })
+}
+
+/// Draw a digit on the panel.
+/// # Arguments
+/// - `fb` - A muteable reference to the `FrameBuffer` to draw into
+/// - `origin` - The position of the top-left pixel of the glyph
+/// - `digit` - The digit, from 0 to 9
+/// - `color` - The color of the lit pixels
+pub fn draw_digit<const N: usize>(
+    fb: &mut FrameBuffer<N>,
+    origin: Coordinate,
+    digit: u8,
+    color: Rgb,
+) {
+    let mask = digit_mask(digit);
+    for (r, row) in mask.iter().enumerate() {
+        for (c, lit) in row.iter().enumerate() {
+            if *lit {
+                fb.set_pixel(origin + [r, c], color);
+            }
+        }
+    }
+}
+
+/// Write the decimal digits of a value right-aligned into a buffer, without allocating or formatting.
+/// # Arguments
+/// - `value` - The value to write
+/// - `out` - A muteable reference to the buffer, its leading unused bytes are set to 0
+/// # Returns
+/// - `usize` - The number of significant digits, at least 1 and at most `out.len()`
+pub fn to_digits(mut value: u32, out: &mut [u8]) -> usize {
+    out.fill(0);
+    let mut count = 0;
+    for digit in out.iter_mut().rev() {
+        *digit = (value % 10) as u8;
+        value /= 10;
+        count += 1;
+        if value == 0 {
+            break;
+        }
+    }
+    count
+}
+
+/// Get the binary coded decimal digits of a value.
+/// # Arguments
+/// - `value` - The value to convert
+/// # Returns
+/// - `[u8; MAX_DIGITS]` - The digits, most significant first and padded with leading zeros
+pub fn to_bcd(value: u32) -> [u8; MAX_DIGITS] {
+    let mut digits = [0; MAX_DIGITS];
+    to_digits(value, &mut digits);
+    digits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{digit_mask, draw_digit, to_bcd, to_digits};
+    use crate::color::Rgb;
+    use crate::coordinate::Coordinate;
+    use crate::framebuffer::FrameBuffer;
+
+    #[test]
+    fn test_digit_mask() {
+        // 1 1 1
+        // 0 0 1
+        // 1 1 1
+        // 1 0 0
+        // 1 1 1
+        assert_eq!(
+            digit_mask(2).concat(),
+            vec![
+                true, true, true, false, false, true, true, true, true, true, false, false, true,
+                true, true
+            ]
+        );
+    }
+
+    #[test]
+    fn test_to_digits() {
+        let mut out = [9; 4];
+        assert_eq!(to_digits(0, &mut out), 1);
+        assert_eq!(out, [0, 0, 0, 0]);
+        assert_eq!(to_digits(7, &mut out), 1);
+        assert_eq!(out, [0, 0, 0, 7]);
+        assert_eq!(to_digits(1234, &mut out), 4);
+        assert_eq!(out, [1, 2, 3, 4]);
+        // Values that do not fit keep their lowest digits.
+        assert_eq!(to_digits(98_765, &mut out), 4);
+        assert_eq!(out, [8, 7, 6, 5]);
+        assert_eq!(to_bcd(u32::MAX), [4, 2, 9, 4, 9, 6, 7, 2, 9, 5]);
+        assert_eq!(to_bcd(0), [0; 10]);
+    }
+
+    #[test]
+    fn test_draw_digit() {
+        // 1 1 1
+        // 0 0 1
+        // 0 1 0
+        // 0 1 0
+        // 0 1 0
+        let mut fb = FrameBuffer::<64>::new(Coordinate::from_array([8, 8]));
+        let color = Rgb::from_array(&[255, 0, 0]);
+        draw_digit(&mut fb, Coordinate::from_array([1, 2]), 7, color);
+        let lit: Vec<bool> = (0..5)
+            .flat_map(|r| (0..3).map(move |c| Coordinate::from_array([1 + r, 2 + c])))
+            .map(|coord| fb.get_pixel(coord) == Some(color))
+            .collect();
+        assert_eq!(lit, digit_mask(7).concat());
+        assert_eq!(
+            lit,
+            vec![
+                true, true, true, false, false, true, false, true, false, false, true, false,
+                false, true, false
+            ]
+        );
+    }
+}
@@ -0,0 +1,161 @@
+use crate::color::Rgb;
+use crate::coordinate::Coordinate;
+use crate::display::digits::digit_mask;
+use crate::framebuffer::{FrameBuffer, IN_FRAME};
+use alloc::vec;
+use alloc::vec::Vec;
+
+pub struct Overlay {
+    /// A translucent layer drawn over a `FrameBuffer`, e.g. for menus over a dimmed playfield.
+    /// # Attributes
+    /// - `dims` - The number of [rows, cols] of the panel
+    /// - `cells` - The color and the opacity of every pixel, or `None` for the pixels showing the frame unchanged
+    dims: Coordinate,
+    cells: Vec<Option<(Rgb, u8)>>,
+}
+
+impl Overlay {
+    /// Create a transparent overlay.
+    /// # Arguments
+    /// - `dims` - The number of [rows, cols] of the panel as a `Coordinate`
+    /// # Returns
+    /// - `Overlay` - The overlay without any drawn pixels
+    pub fn new(dims: Coordinate) -> Self {
+        Overlay {
+            dims,
+            cells: vec![None; dims.inner_product()],
+        }
+    }
+
+    /// Get the shape of the panel.
+    /// # Returns
+    /// - `Coordinate` - The number of [rows, cols] of the panel
+    pub fn get_shape(&self) -> Coordinate {
+        self.dims
+    }
+
+    /// Make all pixels transparent.
+    pub fn clear(&mut self) {
+        self.cells.fill(None);
+    }
+
+    /// Get the color and the opacity of a pixel.
+    /// # Arguments
+    /// - `coord` - The [row, col] of the pixel
+    /// # Returns
+    /// - `Option<(Rgb, u8)>` - The color and the opacity, or `None` if the pixel is transparent or outside of the panel
+    pub fn get_cell(&self, coord: Coordinate) -> Option<(Rgb, u8)> {
+        match coord.row < self.dims.row && coord.col < self.dims.col {
+            true => self.cells[coord.row * self.dims.col + coord.col],
+            false => None,
+        }
+    }
+
+    /// Draw a pixel, replacing what was drawn there before and ignoring pixels outside of the panel.
+    /// # Arguments
+    /// - `coord` - The [row, col] of the pixel
+    /// - `color` - The color
+    /// - `alpha` - The opacity, from transparent at 0 to opaque at 255
+    pub fn set_cell(&mut self, coord: Coordinate, color: Rgb, alpha: u8) {
+        if coord.row < self.dims.row && coord.col < self.dims.col {
+            self.cells[coord.row * self.dims.col + coord.col] = Some((color, alpha));
+        }
+    }
+
+    /// Draw a rectangle.
+    /// # Arguments
+    /// - `origin` - The position of the top-left pixel of the rectangle
+    /// - `size` - The number of [rows, cols] of the rectangle
+    /// - `color` - The color
+    /// - `alpha` - The opacity, from transparent at 0 to opaque at 255
+    pub fn fill_rect(&mut self, origin: Coordinate, size: Coordinate, color: Rgb, alpha: u8) {
+        for r in 0..size.row {
+            for c in 0..size.col {
+                self.set_cell(origin + [r, c], color, alpha);
+            }
+        }
+    }
+
+    /// Draw a digit of the font of `display::digits`.
+    /// # Arguments
+    /// - `origin` - The position of the top-left pixel of the glyph
+    /// - `digit` - The digit, from 0 to 9
+    /// - `color` - The color of the lit pixels
+    /// - `alpha` - The opacity of the lit pixels, from transparent at 0 to opaque at 255
+    pub fn draw_digit(&mut self, origin: Coordinate, digit: u8, color: Rgb, alpha: u8) {
+        let mask = digit_mask(digit);
+        for (r, row) in mask.iter().enumerate() {
+            for (c, lit) in row.iter().enumerate() {
+                if *lit {
+                    self.set_cell(origin + [r, c], color, alpha);
+                }
+            }
+        }
+    }
+
+    /// Dim the whole panel, by covering it with black.
+    /// # Arguments
+    /// - `alpha` - The opacity of the black, from unchanged at 0 to off at 255
+    pub fn dim_all(&mut self, alpha: u8) {
+        self.cells.fill(Some((Rgb::BLACK, alpha)));
+    }
+
+    /// Composite the overlay over a single pixel of a frame with `Rgb::blend_over()`.
+    /// # Arguments
+    /// - `coord` - The [row, col] of the pixel
+    /// - `base` - The color of the pixel in the frame
+    /// # Returns
+    /// - `Rgb` - The blended color, or `base` where the overlay is transparent
+    pub fn composite_pixel(&self, coord: Coordinate, base: Rgb) -> Rgb {
+        match self.get_cell(coord) {
+            Some((color, alpha)) => color.blend_over(base, alpha),
+            None => base,
+        }
+    }
+
+    /// Composite the overlay over a whole frame, e.g. to inspect the result.
+    /// # Arguments
+    /// - `frame` - A mutable reference to the `FrameBuffer` to draw over
+    pub fn composite_onto<const N: usize>(&self, frame: &mut FrameBuffer<N>) {
+        for r in 0..frame.get_shape().row {
+            for c in 0..frame.get_shape().col {
+                let coord = Coordinate { row: r, col: c };
+                let base = frame.get_pixel(coord).expect(IN_FRAME);
+                frame.set_pixel(coord, self.composite_pixel(coord, base));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Overlay;
+    use crate::color::Rgb;
+    use crate::coordinate::Coordinate;
+    use crate::framebuffer::FrameBuffer;
+
+    #[test]
+    fn test_composite() {
+        // A half-alpha white rectangle over the left pixel, the right pixel is untouched.
+        let color = Rgb::from_array(&[200, 100, 0]);
+        let mut frame = FrameBuffer::<2>::new(Coordinate::from_array([1, 2]));
+        frame.set_pixel(Coordinate::from_array([0, 0]), color);
+        frame.set_pixel(Coordinate::from_array([0, 1]), color);
+        let mut overlay = Overlay::new(frame.get_shape());
+        overlay.fill_rect(
+            Coordinate::from_array([0, 0]),
+            Coordinate::from_array([1, 1]),
+            Rgb::WHITE,
+            128,
+        );
+        overlay.composite_onto(&mut frame);
+        assert_eq!(
+            frame.get_row(0),
+            Some(&[Rgb::from_array(&[228, 178, 128]), color][..])
+        );
+        // Dimming everything at full opacity turns the panel off.
+        overlay.dim_all(255);
+        overlay.composite_onto(&mut frame);
+        assert_eq!(frame.get_row(0), Some(&[Rgb::BLACK; 2][..]));
+    }
+}
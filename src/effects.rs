@@ -0,0 +1,393 @@
+use crate::color::Rgb;
+use crate::coordinate::Coordinate;
+use crate::events::{EventSink, GameEvent};
+use crate::framebuffer::FrameBuffer;
+use crate::layout::Layout;
+use crate::palette::CellPattern;
+use crate::rng::{GameRng, RngSource};
+
+/// The number of particles of a line clear burst, enough for a burst of every row of a tetris.
+pub const PARTICLES: usize = 32;
+
+/// The number of particles spawned per cleared row, so a tetris spawns at most 4 times as many.
+pub const BURST_PARTICLES: usize = 8;
+
+/// The number of ticks a particle of a burst lives, fading from its color to black.
+pub const PARTICLE_TICKS: u8 = 24;
+
+/// The acceleration of the particles in 8.8 fixed point pixels per tick per tick, pulling them down.
+pub const GRAVITY: i16 = 12;
+
+/// The largest speed of a particle leaving its row in 8.8 fixed point pixels per tick.
+const BURST_SPEED: i16 = 96;
+
+/// The color of the particles of a burst.
+const BURST_COLOR: Rgb = Rgb::from_array(&[255, 224, 128]);
+
+/// Fade a color in and out, along a triangle wave from black at the start of every period to the color halfway.
+/// # Arguments
+/// - `base` - The color at the peak of the pulse
+/// - `period` - The number of ticks of a full pulse, 0 keeps the color
+/// - `tick` - The current tick
+/// # Returns
+/// - `Rgb` - The color at the tick
+pub fn pulse(base: Rgb, period: u16, tick: u32) -> Rgb {
+    let half = period as u32 / 2;
+    if half == 0 {
+        return base;
+    }
+    let phase = tick % period as u32;
+    let distance = match phase < half {
+        true => phase,
+        false => period as u32 - phase,
+    };
+    base.scaled(((distance * 255 + half / 2) / half).min(255) as u8)
+}
+
+/// Toggle between 2 colors with a 50% duty cycle.
+/// # Arguments
+/// - `on` - The color during the first half of every period
+/// - `off` - The color during the second half of every period
+/// - `period` - The number of ticks of a full cycle, 0 keeps the `on` color
+/// - `tick` - The current tick
+/// # Returns
+/// - `Rgb` - The color at the tick
+pub fn flash(on: Rgb, off: Rgb, period: u16, tick: u32) -> Rgb {
+    if period == 0 {
+        return on;
+    }
+    let period = period as u32;
+    match tick % period < period / 2 {
+        true => on,
+        false => off,
+    }
+}
+
+/// Randomly light up a pixel in white, like a glitter.
+/// # Arguments
+/// - `base` - The color of the pixel when it does not sparkle
+/// - `rng` - A mutable reference to the `GameRng` deciding which pixels sparkle
+/// - `density` - The chance of a sparkle out of 256
+/// # Returns
+/// - `Rgb` - White for a sparkling pixel, `base` otherwise
+pub fn sparkle(base: Rgb, rng: &mut GameRng, density: u8) -> Rgb {
+    match rng.below(256) < density as u32 {
+        true => Rgb::WHITE,
+        false => base,
+    }
+}
+
+/// A single pixel flying away from a cleared row.
+/// # Attributes
+/// - `pos` - The [row, col] of the particle in 8.8 fixed point pixels, negative above or left of the panel
+/// - `vel` - The [row, col] velocity of the particle in 8.8 fixed point pixels per tick
+/// - `ticks_left` - The number of ticks the particle lives on, at most `lifetime`
+/// - `lifetime` - The number of ticks the particle lived when spawned, fading out over them
+/// - `color` - The color of the particle when spawned
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Particle {
+    pub pos: [i16; 2],
+    pub vel: [i16; 2],
+    pub ticks_left: u8,
+    pub lifetime: u8,
+    pub color: Rgb,
+}
+
+impl Particle {
+    /// Get the color of the particle, fading to black with its age.
+    /// # Returns
+    /// - `Rgb` - The color at the current tick
+    pub fn get_color(&self) -> Rgb {
+        self.color
+            .scaled_fraction(self.ticks_left, self.lifetime.max(1))
+    }
+
+    /// Get the pixel the particle covers.
+    /// # Returns
+    /// - `Option<Coordinate>` - The [row, col] of the pixel, or `None` above or left of the panel
+    pub fn get_pixel(&self) -> Option<Coordinate> {
+        // The arithmetic shift rounds down, so a particle just left of the panel stays off it.
+        let [row, col] = self.pos.map(|value| value >> 8);
+        match row >= 0 && col >= 0 {
+            true => Some(Coordinate::from_array([row as usize, col as usize])),
+            false => None,
+        }
+    }
+}
+
+/// A fixed number of particles, e.g. bursting out of the cleared rows.
+///
+/// As an `EventSink`, a burst of `BURST_PARTICLES` is spawned from every row of a `GameEvent::RowsCleared`. The
+/// number of particles never exceeds `N`, so the work of a tick is bounded however fast the rows are cleared.
+/// # Attributes
+/// - `slots` - The live particles, `None` for a free slot
+/// - `rng` - The `GameRng` spreading the particles of a burst
+#[derive(Debug)]
+pub struct Particles<const N: usize> {
+    slots: [Option<Particle>; N],
+    rng: GameRng,
+}
+
+impl<const N: usize> Particles<N> {
+    /// Create a particle system without any particles.
+    /// # Arguments
+    /// - `seed` - The seed of the random number generator spreading the bursts
+    /// # Returns
+    /// - `Particles<N>` - The particle system
+    pub fn new(seed: u64) -> Self {
+        Particles {
+            slots: [None; N],
+            rng: GameRng::new(seed),
+        }
+    }
+
+    /// Get the number of live particles.
+    /// # Returns
+    /// - `usize` - The number of occupied slots, at most `N`
+    pub fn get_len(&self) -> usize {
+        self.slots.iter().flatten().count()
+    }
+
+    /// Iterate over the live particles.
+    /// # Returns
+    /// - `impl Iterator<Item = &Particle>` - The particles in the order of their slots
+    pub fn particles(&self) -> impl Iterator<Item = &Particle> {
+        self.slots.iter().flatten()
+    }
+
+    /// Spawn a particle in the first free slot.
+    /// # Arguments
+    /// - `particle` - The `Particle` to spawn, ignored without any ticks left
+    /// # Returns
+    /// - `bool` - Whether (`true`) or not (`false`) the particle was spawned, `false` when all slots are taken
+    pub fn spawn(&mut self, particle: Particle) -> bool {
+        if particle.ticks_left == 0 {
+            return false;
+        }
+        match self.slots.iter_mut().find(|slot| slot.is_none()) {
+            Some(slot) => {
+                *slot = Some(particle);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Spawn a burst of particles spread over a row, flying up and away from it.
+    /// # Arguments
+    /// - `row` - The row of the burst
+    /// - `cols` - The number of columns of the row
+    /// # Returns
+    /// - `usize` - The number of spawned particles, fewer than `BURST_PARTICLES` when the slots run out
+    pub fn burst(&mut self, row: usize, cols: usize) -> usize {
+        let mut spawned = 0;
+        for index in 0..BURST_PARTICLES {
+            let col = (index * cols) / BURST_PARTICLES;
+            let range = 2 * BURST_SPEED as u32 + 1;
+            let particle = Particle {
+                pos: [(row << 8) as i16 + 128, (col << 8) as i16 + 128],
+                vel: [
+                    -(self.rng.below(BURST_SPEED as u32 + 1) as i16),
+                    self.rng.below(range) as i16 - BURST_SPEED,
+                ],
+                ticks_left: PARTICLE_TICKS,
+                lifetime: PARTICLE_TICKS,
+                color: BURST_COLOR,
+            };
+            match self.spawn(particle) {
+                true => spawned += 1,
+                false => break,
+            }
+        }
+        spawned
+    }
+
+    /// Advance every particle by a tick: accelerate it by the gravity, move it by its velocity and age it, freeing
+    /// the slots of the particles without any ticks left.
+    pub fn tick(&mut self) {
+        for slot in self.slots.iter_mut() {
+            let Some(particle) = slot else {
+                continue;
+            };
+            particle.ticks_left -= 1;
+            if particle.ticks_left == 0 {
+                *slot = None;
+                continue;
+            }
+            particle.vel[0] = particle.vel[0].saturating_add(GRAVITY);
+            particle.pos[0] = particle.pos[0].saturating_add(particle.vel[0]);
+            particle.pos[1] = particle.pos[1].saturating_add(particle.vel[1]);
+        }
+    }
+
+    /// Remove all particles, e.g. when a new game starts.
+    pub fn clear(&mut self) {
+        self.slots = [None; N];
+    }
+
+    /// Draw the live particles over the frame as cells of the field, skipping the particles outside of it.
+    /// # Arguments
+    /// - `frame` - A muteable reference to the `FrameBuffer` to draw into
+    /// - `layout` - A reference to the `Layout` placing the field on the panel
+    pub fn draw<const M: usize>(&self, frame: &mut FrameBuffer<M>, layout: &Layout) {
+        let config = layout.get_config();
+        for particle in self.particles() {
+            if let Some(coord) = particle.get_pixel() {
+                frame.draw_cell(coord, &config, particle.get_color(), CellPattern::Solid);
+            }
+        }
+    }
+}
+
+impl<const N: usize> EventSink for Particles<N> {
+    fn on_event(&mut self, event: &GameEvent) {
+        match event {
+            GameEvent::RowsCleared { rows_mask, cols } => {
+                for row in (0..u64::BITS as usize).filter(|row| rows_mask & (1 << row) != 0) {
+                    self.burst(row, *cols as usize);
+                }
+            }
+            // The field they fly over is gone, so none of them are left over the menu.
+            GameEvent::Aborted { .. } => self.clear(),
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        flash, pulse, sparkle, Particle, Particles, BURST_PARTICLES, GRAVITY, PARTICLES,
+        PARTICLE_TICKS,
+    };
+    use crate::color::Rgb;
+    use crate::coordinate::Coordinate;
+    use crate::events::{EventSink, GameEvent};
+    use crate::framebuffer::FrameBuffer;
+    use crate::layout::Layout;
+    use crate::rng::GameRng;
+
+    #[test]
+    fn test_pulse() {
+        let waveform: Vec<u8> = (0..9).map(|tick| pulse(Rgb::WHITE, 8, tick).r).collect();
+        assert_eq!(waveform, vec![0, 64, 128, 191, 255, 191, 128, 64, 0]);
+        assert_eq!(pulse(Rgb::WHITE, 0, 3), Rgb::WHITE);
+    }
+
+    #[test]
+    fn test_flash() {
+        let on = (0..60)
+            .filter(|tick| flash(Rgb::WHITE, Rgb::BLACK, 12, *tick) == Rgb::WHITE)
+            .count();
+        assert_eq!(on, 30);
+        assert_eq!(flash(Rgb::WHITE, Rgb::BLACK, 12, 5), Rgb::WHITE);
+        assert_eq!(flash(Rgb::WHITE, Rgb::BLACK, 12, 6), Rgb::BLACK);
+    }
+
+    #[test]
+    fn test_sparkle() {
+        let base = Rgb::from_array(&[255, 0, 0]);
+        let mut rng = GameRng::new(7);
+        assert!((0..100).all(|_| sparkle(base, &mut rng, 0) == base));
+        let sparkles = (0..1000)
+            .filter(|_| sparkle(base, &mut rng, 64) == Rgb::WHITE)
+            .count();
+        assert!((150..350).contains(&sparkles));
+    }
+
+    /// A white particle at the [row, col] pixel, living for a number of ticks.
+    fn particle(pos: [i16; 2], vel: [i16; 2], ticks: u8) -> Particle {
+        Particle {
+            pos,
+            vel,
+            ticks_left: ticks,
+            lifetime: ticks,
+            color: Rgb::WHITE,
+        }
+    }
+
+    #[test]
+    fn test_integration() {
+        // Thrown up at 2 pixels per tick, the particle slows down by the gravity every tick, in 8.8 fixed point.
+        let mut particles = Particles::<1>::new(0);
+        assert!(particles.spawn(particle([20 << 8, 3 << 8], [-512, 64], 100)));
+        let mut row = 20 << 8;
+        let mut speed = -512;
+        for tick in 1..=10 {
+            particles.tick();
+            speed += GRAVITY;
+            row += speed;
+            let moved = particles.particles().next().expect("the particle lives");
+            assert_eq!(moved.vel, [speed, 64]);
+            assert_eq!(moved.pos, [row, (3 << 8) + 64 * tick]);
+        }
+        // After 10 ticks, 10 * 512 - 12 * 55 = 4460 up, or 17.4 pixels, and 2.5 pixels to the right.
+        let moved = particles.particles().next().expect("the particle lives");
+        assert_eq!(moved.pos, [(20 << 8) - 4460, (3 << 8) + 640]);
+        assert_eq!(moved.get_pixel(), Some(Coordinate::from_array([2, 5])));
+        // A particle above the panel covers no pixel.
+        assert_eq!(particle([-1, 0], [0, 0], 1).get_pixel(), None);
+    }
+
+    #[test]
+    fn test_lifetime() {
+        // The colors fade with the age, and the slots are freed after the lifetime.
+        let mut particles = Particles::<2>::new(0);
+        assert!(particles.spawn(particle([0, 0], [0, 0], 4)));
+        assert!(particles.spawn(particle([256, 0], [0, 0], 2)));
+        assert!(!particles.spawn(particle([0, 256], [0, 0], 4)));
+        assert!(!Particles::<2>::new(0).spawn(particle([0, 0], [0, 0], 0)));
+        let colors: Vec<u8> = particles.particles().map(|p| p.get_color().r).collect();
+        assert_eq!(colors, vec![255, 255]);
+        particles.tick();
+        let colors: Vec<u8> = particles.particles().map(|p| p.get_color().r).collect();
+        assert_eq!(colors, vec![191, 128]);
+        particles.tick();
+        assert_eq!(particles.get_len(), 1);
+        assert!(particles.spawn(particle([0, 256], [0, 0], 4)));
+        particles.tick();
+        particles.tick();
+        assert_eq!(particles.get_len(), 1);
+        particles.tick();
+        particles.tick();
+        assert_eq!(particles.get_len(), 0);
+    }
+
+    #[test]
+    fn test_saturation() {
+        // A tetris fills the slots, and more bursts are dropped instead of overflowing.
+        let mut particles = Particles::<PARTICLES>::new(3);
+        let tetris = GameEvent::RowsCleared {
+            rows_mask: 0b1111 << 16,
+            cols: 10,
+        };
+        particles.on_event(&tetris);
+        assert_eq!(particles.get_len(), 4 * BURST_PARTICLES);
+        particles.on_event(&tetris);
+        assert_eq!(particles.get_len(), PARTICLES);
+        assert_eq!(particles.burst(0, 10), 0);
+        // Every particle starts in the middle of a pixel of the cleared rows, flying up.
+        for particle in particles.particles() {
+            let pixel = particle.get_pixel().expect("on the panel");
+            assert!((16..20).contains(&pixel.row) && pixel.col < 10);
+            assert!(particle.vel[0] <= 0);
+        }
+        // The particles are drawn with their colors until they expire.
+        let mut frame = FrameBuffer::<{ 24 * 16 }>::new(Coordinate::from_array([24, 16]));
+        let board = Coordinate::from_array([20, 10]);
+        particles.draw(&mut frame, &Layout::top_left(board, 3));
+        assert_eq!(
+            frame.get_pixel(Coordinate::from_array([16, 0])),
+            Some(Rgb::from_array(&[255, 224, 128]))
+        );
+        for _ in 0..PARTICLE_TICKS {
+            particles.tick();
+        }
+        assert_eq!(particles.get_len(), 0);
+        particles.clear();
+        // An abandoned game takes its particles with it.
+        particles.on_event(&tetris);
+        particles.on_event(&GameEvent::Aborted { centis: 0 });
+        assert_eq!(particles.get_len(), 0);
+    }
+}
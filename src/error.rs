@@ -0,0 +1,174 @@
+//! The error type of the crate, wrapping the errors of the board, the pieces, the settings, the layout, the link
+//! protocol and the save states.
+//!
+//! Every module keeps its own error enum describing what went wrong, and converts into `TetrisError` so callers can
+//! bubble any of them up with `?`. Only broken invariants of the crate itself, like a malformed constant mask table,
+//! panic with an `expect()` message, anything reachable from user input or hardware returns a `Result`.
+use crate::board::BoardError;
+use crate::layout::LayoutError;
+use crate::link::ProtocolError;
+use crate::piece::PieceError;
+use crate::savestate::SaveError;
+use crate::settings::SettingsError;
+use core::fmt;
+
+/// Any error of the crate.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TetrisError {
+    /// Writing or reading a board failed.
+    Board(BoardError),
+    /// A piece does not fit where it was placed.
+    Piece(PieceError),
+    /// Settings are invalid or could not be read.
+    Settings(SettingsError),
+    /// The playfield and the HUD do not fit on the panel.
+    Layout(LayoutError),
+    /// A frame of the link could not be encoded or decoded.
+    Protocol(ProtocolError),
+    /// A save state could not be written or restored.
+    Save(SaveError),
+}
+
+impl From<BoardError> for TetrisError {
+    fn from(error: BoardError) -> Self {
+        TetrisError::Board(error)
+    }
+}
+
+impl From<PieceError> for TetrisError {
+    fn from(error: PieceError) -> Self {
+        TetrisError::Piece(error)
+    }
+}
+
+impl From<SettingsError> for TetrisError {
+    fn from(error: SettingsError) -> Self {
+        TetrisError::Settings(error)
+    }
+}
+
+impl From<LayoutError> for TetrisError {
+    fn from(error: LayoutError) -> Self {
+        TetrisError::Layout(error)
+    }
+}
+
+impl From<ProtocolError> for TetrisError {
+    fn from(error: ProtocolError) -> Self {
+        TetrisError::Protocol(error)
+    }
+}
+
+impl From<SaveError> for TetrisError {
+    fn from(error: SaveError) -> Self {
+        TetrisError::Save(error)
+    }
+}
+
+impl fmt::Display for TetrisError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TetrisError::Board(error) => write!(f, "board: {}", error),
+            TetrisError::Piece(error) => write!(f, "piece: {}", error),
+            TetrisError::Settings(error) => write!(f, "settings: {}", error),
+            TetrisError::Layout(error) => write!(f, "layout: {}", error),
+            TetrisError::Protocol(error) => write!(f, "link: {}", error),
+            TetrisError::Save(error) => write!(f, "save state: {}", error),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TetrisError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TetrisError::Board(error) => Some(error),
+            TetrisError::Piece(error) => Some(error),
+            TetrisError::Settings(error) => Some(error),
+            TetrisError::Layout(error) => Some(error),
+            TetrisError::Protocol(error) => Some(error),
+            TetrisError::Save(error) => Some(error),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BoardError {}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PieceError {}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SettingsError {}
+
+#[cfg(feature = "std")]
+impl std::error::Error for LayoutError {}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ProtocolError {}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SaveError {}
+
+#[cfg(feature = "std")]
+impl std::error::Error for crate::invariants::InvariantViolation {}
+
+#[cfg(test)]
+mod tests {
+    use super::TetrisError;
+    use crate::board::BoardError;
+    use crate::layout::LayoutError;
+    use crate::link::ProtocolError;
+    use crate::piece::PieceError;
+    use crate::savestate::SaveError;
+    use crate::settings::SettingsError;
+    use std::string::ToString;
+
+    #[test]
+    fn test_display() {
+        let errors: [(TetrisError, &str); 6] = [
+            (
+                BoardError::MaskDoesNotFit.into(),
+                "board: the mask does not fit on the board",
+            ),
+            (
+                PieceError::OutOfBounds.into(),
+                "piece: the piece exceeds the board",
+            ),
+            (
+                SettingsError::Cols(2).into(),
+                "settings: 2 columns, expected 4 to 27",
+            ),
+            (
+                LayoutError::Rows {
+                    needed: 21,
+                    panel: 16,
+                }
+                .into(),
+                "layout: 21 rows needed for the field and the HUD, the panel has 16",
+            ),
+            (
+                ProtocolError::Crc.into(),
+                "link: the CRC of the frame is wrong",
+            ),
+            (
+                SaveError::Version(2).into(),
+                "save state: unsupported version 2",
+            ),
+        ];
+        for (error, message) in errors {
+            assert_eq!(error.to_string(), message);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_source() {
+        use std::error::Error;
+        let error = TetrisError::from(PieceError::OutOfBounds);
+        assert_eq!(
+            error.source().map(|source| source.to_string()),
+            Some(PieceError::OutOfBounds.to_string())
+        );
+    }
+}
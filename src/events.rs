@@ -0,0 +1,55 @@
+use crate::game::{Cause, Outcome};
+use crate::scoring::TSpin;
+use crate::tetrominoes::TetrominoShape;
+
+/// The maximum number of events a single tick can produce.
+pub const MAX_EVENTS: usize = 9;
+
+/// The events of a single tick, stored without allocating.
+pub type GameEvents = heapless::Vec<GameEvent, MAX_EVENTS>;
+
+/// Something noteworthy that happened during a tick, for the sound and LED layers to react to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum GameEvent {
+    /// Rows were completed, `b2b` if the clear got the back-to-back bonus.
+    LinesCleared { n: usize, t_spin: TSpin, b2b: bool },
+    /// The completed rows of a clear, bit `r` of `rows_mask` set for every completed row `r` of a board `cols` wide,
+    /// e.g. for the particles bursting out of them.
+    RowsCleared { rows_mask: u64, cols: u8 },
+    /// The active piece locked on the board, covering the [row, col] of each of `cells`.
+    PieceLocked {
+        shape: TetrominoShape,
+        cells: [[u8; 2]; 4],
+    },
+    /// The active piece locked after `excess` more inputs than the fewest reaching its placement, see `finesse`.
+    FinesseFault { excess: u8 },
+    /// The active piece rotated, `kicked` if it needed a wall kick instead of rotating in place.
+    Rotated { kicked: bool },
+    /// The active piece could not rotate, as none of the wall kicks fit.
+    RotationFailed,
+    /// The active piece was swapped into the hold slot.
+    PieceHeld,
+    /// The level advanced.
+    LevelUp { level: u8 },
+    /// Incoming garbage rows were pushed onto the board.
+    GarbageReceived { lines: u32 },
+    /// The stack topped out, after `centis` hundredths of a second of play at `clock::TICK_HZ`.
+    GameOver { cause: Cause, centis: u32 },
+    /// The goal of the game mode was reached, after `centis` hundredths of a second of play at `clock::TICK_HZ`.
+    Finished { outcome: Outcome, centis: u32 },
+    /// The game was abandoned for the menu by holding the reset chord, after `centis` hundredths of a second of play
+    /// at `clock::TICK_HZ`, see `Game::set_reset_chord()`.
+    Aborted { centis: u32 },
+    /// The recent inputs were frozen into a replay of `ticks` ticks, see `recorder::ReplayRecorder`.
+    ReplayFrozen { ticks: u32 },
+}
+
+/// A consumer of game events, e.g. the buzzer or the LED animations of the firmware.
+pub trait EventSink {
+    /// React to a single event.
+    /// # Arguments
+    /// - `event` - A reference to the `GameEvent`
+    fn on_event(&mut self, event: &GameEvent);
+}
@@ -0,0 +1,342 @@
+//! Finesse, placing every piece with as few inputs as possible.
+//!
+//! The `FinesseTable` holds the fewest inputs reaching every rotation and column of every shape on the standard
+//! board, found with a breadth-first search from the spawn column on an empty board with gravity disabled. A tap
+//! left or right, a shift to the wall with DAS and a rotation either way all count as a single input, the drop does
+//! not count. Placements covering the same cells once dropped, e.g. the two vertical rotations of an S piece, share
+//! the fewest inputs of either. `Finesse` counts the inputs of the active piece and judges its placement when it
+//! locks, counting the placements that took more inputs than needed as faults.
+use crate::ai::_spawn;
+use crate::board::{BoardOps, FixedBoard};
+use crate::coordinate::Coordinate;
+use crate::input::{Action, InputState};
+use crate::piece::ActivePiece;
+use crate::rotation::kicks::RotationState;
+use crate::tetrominoes::{TetrominoShape, SHAPES};
+
+/// The number of columns of the standard board the table is computed for, placements on other boards are not judged.
+pub const FINESSE_COLS: usize = 10;
+
+/// The number of rows of the empty board of the search, only the rows the kicks reach from the spawn row matter.
+const SEARCH_ROWS: usize = 20;
+
+/// The row the search spawns the pieces in, low enough for the kicks to never hit the top of the board, like the
+/// hidden rows above the board of the guideline. A piece rotating in the top row of the game kicks differently,
+/// but it only stays there for the first few ticks.
+const SEARCH_SPAWN_ROW: usize = 2;
+
+/// The most positions of a piece the search visits, every rotation and column over the few rows the wall kicks
+/// move it by.
+const MAX_POSITIONS: usize = 4 * FINESSE_COLS * 4;
+
+/// The panic message of a search visiting more positions than `MAX_POSITIONS`.
+const POSITIONS: &str = "a piece has fewer positions near the spawn row than MAX_POSITIONS";
+
+/// The entry of a rotation and column no input reaches.
+const UNREACHABLE: u8 = u8::MAX;
+
+/// The inputs of the search, an action and whether it is held until the piece reaches the wall.
+const INPUTS: [(Action, bool); 6] = [
+    (Action::Left, false),
+    (Action::Right, false),
+    (Action::Left, true),
+    (Action::Right, true),
+    (Action::RotateCw, false),
+    (Action::RotateCcw, false),
+];
+
+/// The actions counted as inputs of a placement.
+const COUNTED: [Action; 4] = [
+    Action::Left,
+    Action::Right,
+    Action::RotateCw,
+    Action::RotateCcw,
+];
+
+/// Apply an input of the search to a piece.
+/// # Arguments
+/// - `piece` - A muteable reference to the `ActivePiece` to move
+/// - `action` - The `Action` of the input
+/// - `das` - Whether (`true`) or not (`false`) a shift repeats until the piece reaches the wall
+/// - `board` - A reference to the board the piece moves on
+/// # Returns
+/// - `bool` - Whether (`true`) or not (`false`) the piece moved
+fn _apply<B: BoardOps<bool>>(
+    piece: &mut ActivePiece,
+    action: Action,
+    das: bool,
+    board: &B,
+) -> bool {
+    let shift = |piece: &mut ActivePiece| match action {
+        Action::Left => piece.try_shift_left(board),
+        _ => piece.try_shift_right(board),
+    };
+    match action {
+        Action::RotateCw => piece.try_rotate_cw_with_kicks(board).is_rotated(),
+        Action::RotateCcw => piece.try_rotate_ccw_with_kicks(board).is_rotated(),
+        _ => {
+            let moved = shift(piece);
+            while das && moved && shift(piece) {}
+            moved
+        }
+    }
+}
+
+/// Search the fewest inputs of every rotation and column of a shape.
+/// # Arguments
+/// - `shape` - The `TetrominoShape` to search
+/// # Returns
+/// - `[[u8; FINESSE_COLS]; 4]` - The fewest inputs per rotation and column, `UNREACHABLE` where the piece does not fit
+fn _search(shape: TetrominoShape) -> [[u8; FINESSE_COLS]; 4] {
+    let board = FixedBoard::<bool, SEARCH_ROWS, FINESSE_COLS>::new(false);
+    let mut inputs = [[UNREACHABLE; FINESSE_COLS]; 4];
+    let mut landed = [[None; FINESSE_COLS]; 4];
+    let mut visited: heapless::Vec<(usize, Coordinate), MAX_POSITIONS> = heapless::Vec::new();
+    let mut queue: heapless::Deque<(ActivePiece, u8), MAX_POSITIONS> = heapless::Deque::new();
+    let mut spawned = _spawn(shape, FINESSE_COLS);
+    for _ in 0..SEARCH_SPAWN_ROW {
+        spawned.try_drop(&board);
+    }
+    let key = |piece: &ActivePiece| {
+        (
+            piece.get_tetromino().get_rotation() as usize,
+            piece.get_coord(),
+        )
+    };
+    visited.push(key(&spawned)).expect(POSITIONS);
+    queue.push_back((spawned, 0)).expect(POSITIONS);
+    while let Some((piece, count)) = queue.pop_front() {
+        let (rotation, coord) = key(&piece);
+        // Breadth first, so the first visit of a rotation and column is the cheapest.
+        if inputs[rotation][coord.col] == UNREACHABLE {
+            inputs[rotation][coord.col] = count;
+            let mut dropped = piece.clone();
+            while dropped.try_drop(&board) {}
+            landed[rotation][coord.col] = Some(dropped.get_cells());
+        }
+        for (action, das) in INPUTS {
+            let mut next = piece.clone();
+            if !_apply(&mut next, action, das, &board) || visited.contains(&key(&next)) {
+                continue;
+            }
+            visited.push(key(&next)).expect(POSITIONS);
+            queue.push_back((next, count + 1)).expect(POSITIONS);
+        }
+    }
+    let fewest = inputs;
+    for (rotation, cols) in landed.iter().enumerate() {
+        for (col, cells) in cols.iter().enumerate() {
+            let Some(cells) = cells else {
+                continue;
+            };
+            for (other, other_cols) in landed.iter().enumerate() {
+                for (other_col, other_cells) in other_cols.iter().enumerate() {
+                    if other_cells.as_ref() == Some(cells) {
+                        let other_inputs = fewest[other][other_col];
+                        inputs[rotation][col] = inputs[rotation][col].min(other_inputs);
+                    }
+                }
+            }
+        }
+    }
+    inputs
+}
+
+/// The fewest inputs of every placement on the standard board.
+/// # Attributes
+/// - `inputs` - The fewest inputs per shape, rotation and column of the top-left corner of the mask of the piece
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FinesseTable {
+    inputs: [[[u8; FINESSE_COLS]; 4]; 7],
+}
+
+impl FinesseTable {
+    /// Search the fewest inputs of every placement.
+    /// # Returns
+    /// - `FinesseTable` - The table of all seven shapes
+    pub fn new() -> Self {
+        let mut inputs = [[[UNREACHABLE; FINESSE_COLS]; 4]; 7];
+        for shape in SHAPES {
+            inputs[shape as usize] = _search(shape);
+        }
+        FinesseTable { inputs }
+    }
+
+    /// Get the fewest inputs of a placement.
+    /// # Arguments
+    /// - `shape` - The `TetrominoShape` of the piece
+    /// - `rotation` - The `RotationState` of the piece
+    /// - `col` - The column of the top-left corner of the mask of the piece
+    /// # Returns
+    /// - `Option<u8>` - The fewest inputs, or `None` if the piece does not fit in the column
+    pub fn get_inputs(
+        &self,
+        shape: TetrominoShape,
+        rotation: RotationState,
+        col: usize,
+    ) -> Option<u8> {
+        self.inputs[shape as usize][rotation as usize]
+            .get(col)
+            .copied()
+            .filter(|inputs| *inputs != UNREACHABLE)
+    }
+}
+
+impl Default for FinesseTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The inputs of the active piece and the faults of a game.
+/// # Attributes
+/// - `table` - The fewest inputs of every placement
+/// - `inputs` - The number of inputs of the active piece so far
+/// - `soft_dropped` - Whether the active piece was soft dropped, e.g. for a tuck or a spin, which is not judged
+/// - `faults` - The number of placements that took more inputs than needed
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Finesse {
+    table: FinesseTable,
+    inputs: u8,
+    soft_dropped: bool,
+    faults: u32,
+}
+
+impl Finesse {
+    /// Create the tracker of a new game, searching the table.
+    /// # Returns
+    /// - `Finesse` - The tracker without any inputs or faults
+    pub fn new() -> Self {
+        Finesse {
+            table: FinesseTable::new(),
+            inputs: 0,
+            soft_dropped: false,
+            faults: 0,
+        }
+    }
+
+    /// Start counting the inputs of a new piece.
+    pub fn start_piece(&mut self) {
+        self.inputs = 0;
+        self.soft_dropped = false;
+    }
+
+    /// Count the inputs pressed during a tick, holding a shift counts once.
+    /// # Arguments
+    /// - `input` - A reference to the `InputState` of the tick
+    pub fn count(&mut self, input: &InputState) {
+        let pressed = COUNTED
+            .iter()
+            .filter(|action| input.is_pressed(**action))
+            .count();
+        self.inputs = self.inputs.saturating_add(pressed as u8);
+        self.soft_dropped |= input.is_pressed(Action::SoftDrop);
+    }
+
+    /// Judge the placement of the active piece as it locks.
+    /// # Arguments
+    /// - `piece` - A reference to the locking `ActivePiece`
+    /// # Returns
+    /// - `Option<u8>` - The number of inputs more than needed, or `None` if the placement was fine or is not judged
+    pub fn judge(&mut self, piece: &ActivePiece) -> Option<u8> {
+        if self.soft_dropped {
+            return None;
+        }
+        let tetromino = piece.get_tetromino();
+        let fewest = self.table.get_inputs(
+            tetromino.shape,
+            tetromino.get_rotation(),
+            piece.get_coord().col,
+        )?;
+        let excess = self
+            .inputs
+            .checked_sub(fewest)
+            .filter(|excess| *excess > 0)?;
+        self.faults = self.faults.saturating_add(1);
+        Some(excess)
+    }
+
+    /// Get the number of inputs of the active piece so far.
+    pub fn get_inputs(&self) -> u8 {
+        self.inputs
+    }
+
+    /// Get the number of placements that took more inputs than needed.
+    pub fn get_faults(&self) -> u32 {
+        self.faults
+    }
+
+    /// Get a reference to the table of the fewest inputs.
+    pub fn get_table(&self) -> &FinesseTable {
+        &self.table
+    }
+}
+
+impl Default for Finesse {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Finesse, FinesseTable, FINESSE_COLS};
+    use crate::ai::_spawn;
+    use crate::board::FixedBoard;
+    use crate::input::{Action, InputState};
+    use crate::rotation::kicks::RotationState::{Left, Right, Spawn};
+    use crate::tetrominoes::TetrominoShape::{I, L, O, T};
+
+    #[test]
+    fn test_table() {
+        let table = FinesseTable::new();
+        let row = |shape, rotation| {
+            (0..FINESSE_COLS)
+                .map(|col| table.get_inputs(shape, rotation, col))
+                .collect::<Vec<Option<u8>>>()
+        };
+        // A flat T from its spawn columns 3 to 5: DAS to the far left, two taps, a tap, nothing, and mirrored.
+        let flat = [1, 2, 1, 0, 1, 2, 2, 1].map(Some);
+        assert_eq!(row(T, Spawn)[..8], flat);
+        assert_eq!(row(T, Spawn)[8..], [None, None]);
+        assert_eq!(row(O, Spawn)[..9], [1, 2, 2, 1, 0, 1, 2, 2, 1].map(Some));
+        // An L far left needs only DAS in its spawn rotation, and a rotation on top when standing up.
+        assert_eq!(table.get_inputs(L, Spawn, 0), Some(1));
+        assert_eq!(table.get_inputs(L, Right, 0), Some(2));
+        assert_eq!(table.get_inputs(L, Right, 4), Some(1));
+        assert_eq!(table.get_inputs(L, Right, 2), Some(3));
+        // Both vertical I pieces reach every column in at most 2 inputs, rotating before or after DAS.
+        let vertical = [2, 2, 2, 2, 1, 1, 2, 2, 2, 2].map(Some);
+        assert_eq!(row(I, Right), vertical);
+        assert_eq!(row(I, Left), vertical);
+    }
+
+    #[test]
+    fn test_judge() {
+        let mut finesse = Finesse::new();
+        let mut piece = _spawn(T, FINESSE_COLS);
+        // Tapping right and back left wastes both taps, holding a direction counts once.
+        finesse.count(&InputState::from_action(Action::Right));
+        let mut held = InputState::from_action(Action::Left);
+        finesse.count(&held);
+        held = held.next(&[Action::Left]);
+        finesse.count(&held);
+        assert_eq!(finesse.get_inputs(), 2);
+        assert_eq!(finesse.judge(&piece), Some(2));
+        assert_eq!(finesse.get_faults(), 1);
+        // A single rotation in place is fine.
+        finesse.start_piece();
+        finesse.count(&InputState::from_action(Action::RotateCw));
+        let board = FixedBoard::<bool, 20, 10>::new(false);
+        piece.try_drop(&board);
+        assert!(piece.try_rotate_cw_with_kicks(&board).is_rotated());
+        assert_eq!(finesse.judge(&piece), None);
+        // A soft dropped piece is never judged.
+        finesse.start_piece();
+        for action in [Action::Left, Action::Right, Action::SoftDrop] {
+            finesse.count(&InputState::from_action(action));
+        }
+        assert_eq!(finesse.judge(&piece), None);
+        assert_eq!(finesse.get_faults(), 1);
+    }
+}
@@ -0,0 +1,101 @@
+//! Seeded boards in the middle of a game, shared between the tests and the benchmarks in `benches/`.
+//!
+//! The boards are built through `BoardOps` only, so they work for the heap-backed `Board` and the `FixedBoard` alike,
+//! and the same seed always gives the same board on every platform.
+use crate::board::BoardOps;
+use crate::coordinate::Coordinate;
+use crate::rng::{GameRng, RngSource};
+
+/// The chance in percent that a cell below the surface of a mid-game board is filled.
+const FILL_PERCENT: u32 = 75;
+
+/// Fill the bottom rows of a board like a game in progress, leaving the rows above empty.
+///
+/// Every filled row gets at least one hole, so the board never has any full rows, and the other cells are filled with
+/// a chance of `FILL_PERCENT` with one of the given values.
+/// # Arguments
+/// - `board` - A muteable reference to the board, emptied first
+/// - `height` - The number of rows to fill from the bottom, at most the number of rows of the board
+/// - `values` - The values to fill the cells with, picked at random, not empty
+/// - `seed` - The seed for the random number generator, the same seed gives the same board
+pub fn fill_mid_game<T, B>(board: &mut B, height: usize, values: &[T], seed: u64)
+where
+    T: Copy + PartialEq,
+    B: BoardOps<T> + ?Sized,
+{
+    let mut rng = GameRng::new(seed);
+    let shape = board.get_shape();
+    board.clear();
+    for row in shape.row - height.min(shape.row)..shape.row {
+        let hole = rng.below(shape.col as u32) as usize;
+        for col in (0..shape.col).filter(|col| *col != hole) {
+            if rng.below(100) < FILL_PERCENT {
+                let value = values[rng.below(values.len() as u32) as usize];
+                board.set(Coordinate { row, col }, value);
+            }
+        }
+    }
+}
+
+/// Fill the bottom rows of a board completely, e.g. to clear them.
+/// # Arguments
+/// - `board` - A muteable reference to the board
+/// - `rows` - The number of rows to fill from the bottom, at most the number of rows of the board
+/// - `value` - The value to fill the cells with
+pub fn fill_bottom_rows<T, B>(board: &mut B, rows: usize, value: T)
+where
+    T: Copy + PartialEq,
+    B: BoardOps<T> + ?Sized,
+{
+    let shape = board.get_shape();
+    for row in shape.row - rows.min(shape.row)..shape.row {
+        for col in 0..shape.col {
+            board.set(Coordinate { row, col }, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fill_bottom_rows, fill_mid_game};
+    use crate::board::{Board, BoardOps, FixedBoard};
+    use crate::coordinate::Coordinate;
+
+    #[test]
+    fn test_fill_mid_game() {
+        let mut board = FixedBoard::<u8, 20, 10>::new(0);
+        fill_mid_game(&mut board, 10, &[1, 2], 3);
+        // The top half stays empty, the bottom half is mostly filled but has no full rows.
+        assert!(board.cells().all(|(coord, el)| coord.row >= 10 || el == 0));
+        assert!(board.cells().all(|(_, el)| el <= 2));
+        assert_eq!(board.full_rows().count(), 0);
+        let filled = board.cells().filter(|(_, el)| *el != 0).count();
+        assert!((50..90).contains(&filled));
+        // The same seed gives the same board, also on the heap-backed board.
+        let mut other = Board::new(Coordinate::from_array([20, 10]), 0);
+        fill_mid_game(&mut other, 10, &[1, 2], 3);
+        assert!(board.cells().eq(other.cells()));
+        fill_mid_game(&mut other, 10, &[1, 2], 4);
+        assert!(!board.cells().eq(other.cells()));
+    }
+
+    #[test]
+    fn test_fill_bottom_rows() {
+        let mut board = FixedBoard::<bool, 20, 10>::new(false);
+        fill_mid_game(&mut board, 10, &[true], 3);
+        fill_bottom_rows(&mut board, 4, true);
+        assert_eq!(
+            board.full_rows().collect::<Vec<usize>>(),
+            vec![16, 17, 18, 19]
+        );
+        // Clearing them drops the rest of the stack by 4 rows.
+        let before = board;
+        assert_eq!(board.clear_full_rows(), 4);
+        for (coord, el) in board.cells() {
+            match coord.row < 4 {
+                true => assert!(!el),
+                false => assert_eq!(Some(el), before.get(coord - [4, 0])),
+            }
+        }
+    }
+}
@@ -0,0 +1,1411 @@
+use crate::board::BoardOps;
+use crate::cell::Cell;
+use crate::color::Rgb;
+use crate::coordinate::{Coordinate, Region};
+use crate::display::overlay::Overlay;
+use crate::grid::Array2D;
+use crate::hub75::{pixel_bits, BITPLANES};
+use crate::palette::{CellPattern, Palette};
+use crate::piece::ActivePiece;
+use crate::settings::Settings;
+use crate::tetrominoes::{Tetromino, TetrominoShape};
+use crate::ws2812::to_word;
+
+/// The panic message of a pixel outside of its own frame, a bug in the iteration over the frame.
+pub(crate) const IN_FRAME: &str = "the coordinate is within the frame";
+
+/// The most filled cells averaged into a single pixel of a board thumbnail, e.g. a 4 by 4 block, any further cells
+/// still light the pixel but do not change its color.
+pub const THUMBNAIL_BUCKET: usize = 16;
+
+/// The number of rows and columns of the slot of `.draw_piece_thumbnail()`, fitting any piece.
+pub const PIECE_SLOT: usize = 4;
+
+/// The order in which the LEDs of a panel are chained, i.e. which pixel receives the n-th color sent.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum PanelLayout {
+    /// Every row runs from left to right, like the scanlines of a HUB75 panel.
+    #[default]
+    RowMajor,
+    /// The rows alternate direction, starting from left to right, like most WS2812 matrices.
+    Serpentine,
+    /// Every column runs from top to bottom.
+    ColumnMajor,
+    /// The columns alternate direction, starting from top to bottom, like WS2812 matrices mounted on their side.
+    ColumnSerpentine,
+}
+
+impl PanelLayout {
+    /// Get the pixel at a position along the LED chain.
+    /// # Arguments
+    /// - `index` - The position along the chain, starting at the first LED
+    /// - `dims` - The number of [rows, cols] of the panel
+    /// # Returns
+    /// - `Option<Coordinate>` - The [row, col] of the pixel, or `None` if the index is beyond the last LED
+    pub fn to_coord(self, index: usize, dims: Coordinate) -> Option<Coordinate> {
+        if index >= dims.inner_product() {
+            return None;
+        }
+        let (major, minor) = match self {
+            PanelLayout::RowMajor | PanelLayout::Serpentine => (index / dims.col, index % dims.col),
+            PanelLayout::ColumnMajor | PanelLayout::ColumnSerpentine => {
+                (index / dims.row, index % dims.row)
+            }
+        };
+        Some(match self {
+            PanelLayout::RowMajor => Coordinate::from_array([major, minor]),
+            PanelLayout::Serpentine => match major & 1 {
+                0 => Coordinate::from_array([major, minor]),
+                _ => Coordinate::from_array([major, dims.col - 1 - minor]),
+            },
+            PanelLayout::ColumnMajor => Coordinate::from_array([minor, major]),
+            PanelLayout::ColumnSerpentine => match major & 1 {
+                0 => Coordinate::from_array([minor, major]),
+                _ => Coordinate::from_array([dims.row - 1 - minor, major]),
+            },
+        })
+    }
+
+    /// Get the position of a pixel along the LED chain, the inverse of `.to_coord()`.
+    /// # Arguments
+    /// - `coord` - The [row, col] of the pixel
+    /// - `dims` - The number of [rows, cols] of the panel
+    /// # Returns
+    /// - `Option<usize>` - The position along the chain, or `None` if the pixel is outside of the panel
+    pub fn to_index(self, coord: Coordinate, dims: Coordinate) -> Option<usize> {
+        if coord.row >= dims.row || coord.col >= dims.col {
+            return None;
+        }
+        Some(match self {
+            PanelLayout::RowMajor => coord.row * dims.col + coord.col,
+            PanelLayout::Serpentine => match coord.row & 1 {
+                0 => coord.row * dims.col + coord.col,
+                _ => coord.row * dims.col + dims.col - 1 - coord.col,
+            },
+            PanelLayout::ColumnMajor => coord.col * dims.row + coord.row,
+            PanelLayout::ColumnSerpentine => match coord.col & 1 {
+                0 => coord.col * dims.row + coord.row,
+                _ => coord.col * dims.row + dims.row - 1 - coord.row,
+            },
+        })
+    }
+}
+
+/// Where and at which size to draw a board, see `FrameBuffer::draw_board()` and `FrameBuffer::draw_piece()`.
+/// # Attributes
+/// - `origin` - The position of the top-left cell of the board on the panel, in cells so it scales with the board
+/// - `scale` - The number of rows and columns of LEDs every cell is drawn as, 0 draws cells as single LEDs too
+/// - `grid` - Whether to draw every other empty cell in the grid color of the palette, a faint checkerboard
+/// - `cell_border` - The color of the line between the cells at a scale of 2 or more, drawn over the last row and
+///   column of every block, or `None` to fill the whole block. The edge of the board is left to its own border.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DrawConfig {
+    pub origin: Coordinate,
+    pub scale: u8,
+    pub grid: bool,
+    pub cell_border: Option<Rgb>,
+}
+
+impl Default for DrawConfig {
+    fn default() -> Self {
+        DrawConfig {
+            origin: Coordinate::from_array([0, 0]),
+            scale: 1,
+            grid: false,
+            cell_border: None,
+        }
+    }
+}
+
+/// The pixels of the LED panel, stored inline in row major order without any heap allocation.
+///
+/// The storage is sized at compile time with `N`, which must equal the number of pixels of the panel.
+/// The `PanelLayout` only affects the order in which the pixels are sent to the LEDs.
+/// # Attributes
+/// - `dims` - The number of [rows, cols] of the panel
+/// - `layout` - The order in which the LEDs are chained
+/// - `pixels` - The color of every pixel
+/// - `patterns` - The pattern of every pixel, applied by `.upscaled()`
+/// - `dirty` - The bounding box of the pixels changed since the frame was last sent with `.write_dirty()`
+#[derive(Debug)]
+pub struct FrameBuffer<const N: usize> {
+    dims: Coordinate,
+    layout: PanelLayout,
+    pixels: [Rgb; N],
+    patterns: [CellPattern; N],
+    dirty: Option<Region>,
+}
+
+impl<const N: usize> FrameBuffer<N> {
+    /// Create a black frame buffer for a panel chained in row major order.
+    /// # Arguments
+    /// - `dims` - The number of [rows, cols] of the panel as a `Coordinate`, covering exactly `N` pixels
+    /// # Returns
+    /// - `FrameBuffer<N>` - The frame buffer with all pixels off
+    pub fn new(dims: Coordinate) -> Self {
+        FrameBuffer::with_layout(dims, PanelLayout::RowMajor)
+    }
+
+    /// Create a black frame buffer for a panel with its LEDs chained in any order.
+    /// # Arguments
+    /// - `dims` - The number of [rows, cols] of the panel as a `Coordinate`, covering exactly `N` pixels
+    /// - `layout` - The `PanelLayout` of the LED chain
+    /// # Returns
+    /// - `FrameBuffer<N>` - The frame buffer with all pixels off
+    pub fn with_layout(dims: Coordinate, layout: PanelLayout) -> Self {
+        assert_eq!(dims.inner_product(), N, "the panel must have N pixels");
+        FrameBuffer {
+            dims,
+            layout,
+            pixels: [Rgb::BLACK; N],
+            patterns: [CellPattern::Solid; N],
+            dirty: None,
+        }
+    }
+
+    /// Get the order in which the LEDs of the panel are chained.
+    pub fn get_layout(&self) -> PanelLayout {
+        self.layout
+    }
+
+    /// Get the shape of the panel.
+    /// # Returns
+    /// - `Coordinate` - The number of [rows, cols] of the panel
+    pub fn get_shape(&self) -> Coordinate {
+        self.dims
+    }
+
+    /// Turn all pixels off.
+    pub fn clear(&mut self) {
+        for index in 0..N {
+            let coord = Coordinate::from_row_major(index, self.dims).expect(IN_FRAME);
+            self.set_pixel(coord, Rgb::BLACK);
+        }
+    }
+
+    /// Get the color of a pixel.
+    /// # Arguments
+    /// - `coord` - The [row, col] of the pixel
+    /// # Returns
+    /// - `Option<Rgb>` - The color, or `None` if the pixel is outside of the panel
+    pub fn get_pixel(&self, coord: Coordinate) -> Option<Rgb> {
+        match coord.row < self.dims.row && coord.col < self.dims.col {
+            true => Some(self.pixels[coord.row * self.dims.col + coord.col]),
+            false => None,
+        }
+    }
+
+    /// Get the colors of a row of pixels, e.g. to convert it with `color::to_rgb565_scanline()`.
+    /// # Arguments
+    /// - `row` - The index of the row
+    /// # Returns
+    /// - `Option<&[Rgb]>` - The colors from left to right, or `None` if the row is outside of the panel
+    pub fn get_row(&self, row: usize) -> Option<&[Rgb]> {
+        match row < self.dims.row {
+            true => Some(&self.pixels[row * self.dims.col..(row + 1) * self.dims.col]),
+            false => None,
+        }
+    }
+
+    /// Set the color of a pixel, ignoring pixels outside of the panel.
+    /// # Arguments
+    /// - `coord` - The [row, col] of the pixel
+    /// - `color` - The new color
+    pub fn set_pixel(&mut self, coord: Coordinate, color: Rgb) {
+        self.set_patterned_pixel(coord, color, CellPattern::Solid);
+    }
+
+    /// Set the color and the pattern of a pixel, ignoring pixels outside of the panel.
+    /// # Arguments
+    /// - `coord` - The [row, col] of the pixel
+    /// - `color` - The new color
+    /// - `pattern` - The `CellPattern` to draw the pixel with when upscaled
+    pub fn set_patterned_pixel(&mut self, coord: Coordinate, color: Rgb, pattern: CellPattern) {
+        if coord.row < self.dims.row && coord.col < self.dims.col {
+            let index = coord.row * self.dims.col + coord.col;
+            if self.pixels[index] != color || self.patterns[index] != pattern {
+                self.pixels[index] = color;
+                self.patterns[index] = pattern;
+                self.dirty = Some(Region::including(self.dirty, coord));
+            }
+        }
+    }
+
+    /// Get the pixels changed since the frame was last sent with `.write_dirty()`.
+    /// # Returns
+    /// - `Option<Region>` - The bounding box of the changed pixels, or `None` if no pixel changed
+    pub fn get_dirty(&self) -> Option<Region> {
+        self.dirty
+    }
+
+    /// Mark a region as changed, e.g. one found by `BoardOps::diff()`, so `.write_dirty()` sends it.
+    /// # Arguments
+    /// - `region` - The `Region` of pixels to send, pixels outside of the panel are ignored
+    pub fn mark_dirty(&mut self, region: Region) {
+        self.dirty = Some(match self.dirty {
+            Some(dirty) => dirty.union(region),
+            None => region,
+        });
+    }
+
+    /// Get the number of LEDs to send to update all changed pixels.
+    ///
+    /// A WS2812 chain latches the first LEDs of a frame and keeps the colors of the LEDs it did not receive,
+    /// but it cannot skip LEDs. So the shortest update is the prefix of the chain up to the last changed pixel
+    /// in the order of the `PanelLayout`, which for a serpentine panel covers the whole rows above the region.
+    /// # Returns
+    /// - `usize` - The number of LEDs from the start of the chain, 0 if no pixel changed
+    pub fn get_dirty_prefix(&self) -> usize {
+        match self.dirty {
+            Some(region) => region
+                .coords()
+                .filter_map(|coord| self.layout.to_index(coord, self.dims))
+                .max()
+                .map_or(0, |index| index + 1),
+            None => 0,
+        }
+    }
+
+    /// Send the changed pixels to the LEDs like `.flush()`, as the shortest prefix of the chain covering them.
+    /// # Arguments
+    /// - `settings` - A reference to the `Settings` holding the color correction, the brightness and the gamma
+    ///   correction flag
+    /// - `write` - The function writing a single pixel to the LEDs
+    /// # Returns
+    /// - `usize` - The number of LEDs written, see `.get_dirty_prefix()`
+    pub fn write_dirty<F: FnMut(Rgb)>(&mut self, settings: &Settings, mut write: F) -> usize {
+        let prefix = self.get_dirty_prefix();
+        for coord in self._chain().take(prefix) {
+            write(_correct(self.get_pixel(coord).expect(IN_FRAME), settings));
+        }
+        self.dirty = None;
+        prefix
+    }
+
+    /// Set the color of every pixel covered by the filled elements of a binary mask.
+    /// # Arguments
+    /// - `mask` - A reference to the binary mask as an `Array2D<bool>`
+    /// - `coord` - The position of the top-left element of the mask on the panel
+    /// - `color` - The color to draw the mask in
+    pub fn set_mask(&mut self, mask: &Array2D<bool>, coord: Coordinate, color: Rgb) {
+        self.set_patterned_mask(mask, coord, color, CellPattern::Solid);
+    }
+
+    /// Set the color and the pattern of every pixel covered by the filled elements of a binary mask.
+    /// # Arguments
+    /// - `mask` - A reference to the binary mask as an `Array2D<bool>`
+    /// - `coord` - The position of the top-left element of the mask on the panel
+    /// - `color` - The color to draw the mask in
+    /// - `pattern` - The `CellPattern` to draw the mask with when upscaled
+    pub fn set_patterned_mask(
+        &mut self,
+        mask: &Array2D<bool>,
+        coord: Coordinate,
+        color: Rgb,
+        pattern: CellPattern,
+    ) {
+        for r in 0..mask.num_rows() {
+            for c in 0..mask.num_columns() {
+                if mask[(r, c)] {
+                    self.set_patterned_pixel(coord + [r, c], color, pattern);
+                }
+            }
+        }
+    }
+
+    /// Draw a binary mask at half the size, e.g. to fit a piece into a small preview slot.
+    ///
+    /// Every 2x2 block of the mask becomes a single pixel with the average color of its elements, counting the
+    /// empty elements and the elements beyond the edge of the mask as black. Blocks without filled elements are
+    /// not drawn.
+    /// # Arguments
+    /// - `mask` - A reference to the binary mask as an `Array2D<bool>`
+    /// - `coord` - The position of the top-left pixel on the panel
+    /// - `color` - The color of the filled elements
+    pub fn blit_scaled_down(&mut self, mask: &Array2D<bool>, coord: Coordinate, color: Rgb) {
+        for (r, top) in (0..mask.num_rows()).step_by(2).enumerate() {
+            for (c, left) in (0..mask.num_columns()).step_by(2).enumerate() {
+                let mut block = [Rgb::BLACK; 4];
+                for (index, pixel) in block.iter_mut().enumerate() {
+                    if let Some(true) = mask.get(top + index / 2, left + index % 2) {
+                        *pixel = color;
+                    }
+                }
+                if block.iter().any(|pixel| *pixel != Rgb::BLACK) {
+                    self.set_pixel(coord + [r, c], Rgb::average(&block));
+                }
+            }
+        }
+    }
+
+    /// Draw a gauge as a column of pixels filling up from the bottom, e.g. for the debug overlays of the timers.
+    ///
+    /// Only the filled pixels are drawn, rounding down, so the gauge is full only at a level of 255.
+    /// # Arguments
+    /// - `bottom` - The position of the bottom pixel of the gauge on the panel
+    /// - `len` - The number of pixels of the full gauge, at most `bottom.row + 1`
+    /// - `level` - How full the gauge is, from 0 (empty) to 255 (full)
+    /// - `color` - The color of the filled pixels
+    pub fn draw_column_gauge(&mut self, bottom: Coordinate, len: usize, level: u8, color: Rgb) {
+        for offset in 0.._gauge_pixels(len, level).min(bottom.row + 1) {
+            self.set_pixel(bottom - [offset, 0], color);
+        }
+    }
+
+    /// Draw a gauge as a row of pixels filling up from the left, see `.draw_column_gauge()`.
+    /// # Arguments
+    /// - `left` - The position of the leftmost pixel of the gauge on the panel
+    /// - `len` - The number of pixels of the full gauge
+    /// - `level` - How full the gauge is, from 0 (empty) to 255 (full)
+    /// - `color` - The color of the filled pixels
+    pub fn draw_row_gauge(&mut self, left: Coordinate, len: usize, level: u8, color: Rgb) {
+        for offset in 0.._gauge_pixels(len, level) {
+            self.set_pixel(left + [0, offset], color);
+        }
+    }
+
+    /// Draw the meter of the incoming garbage, a pixel per queued row stacking up from the bottom of a column.
+    /// # Arguments
+    /// - `column` - The region of the meter, its leftmost column used, e.g. `Layout::garbage`
+    /// - `lines` - The number of queued rows, cut off at the height of the column
+    /// - `color` - The color of the lit pixels
+    pub fn draw_garbage_meter(&mut self, column: Region, lines: u32, color: Rgb) {
+        let bottom = Coordinate::from_array([column.bottom_right.row, column.top_left.col]);
+        let height = column.bottom_right.row + 1 - column.top_left.row;
+        for offset in 0..(lines as usize).min(height) {
+            self.set_pixel(bottom - [offset, 0], color);
+        }
+    }
+
+    /// Draw a board scaled down to a thumbnail, e.g. for the high-score screen or to pick a save state.
+    ///
+    /// Every pixel covers the nearest bucket of cells, so any ratio works, also a non-integer one. A pixel is lit if
+    /// any cell of its bucket is filled, with the average color of the filled cells, of at most `THUMBNAIL_BUCKET`
+    /// of them. The pixels of empty buckets get the background color.
+    /// # Arguments
+    /// - `board` - A reference to the board
+    /// - `origin` - The position of the top-left pixel of the thumbnail on the panel
+    /// - `target_dims` - The number of [rows, cols] of pixels of the thumbnail
+    /// - `palette` - A reference to the `Palette` to draw with
+    pub fn draw_board_thumbnail<B: BoardOps<Cell>>(
+        &mut self,
+        board: &B,
+        origin: Coordinate,
+        target_dims: Coordinate,
+        palette: &Palette,
+    ) {
+        let shape = board.get_shape();
+        // The cells of a pixel along an axis, at least one so a thumbnail larger than the board repeats cells.
+        let bucket = |pixel: usize, cells: usize, pixels: usize| {
+            let start = pixel * cells / pixels;
+            start..((pixel + 1) * cells / pixels).max(start + 1)
+        };
+        for r in 0..target_dims.row {
+            for c in 0..target_dims.col {
+                let mut colors = [Rgb::BLACK; THUMBNAIL_BUCKET];
+                let mut filled = 0;
+                for row in bucket(r, shape.row, target_dims.row) {
+                    for col in bucket(c, shape.col, target_dims.col) {
+                        let color = match board.get(Coordinate { row, col }) {
+                            Some(Cell::Filled { shape, lum }) => {
+                                palette.get_color(shape).scaled(lum)
+                            }
+                            Some(Cell::Garbage) => palette.garbage,
+                            _ => continue,
+                        };
+                        if let Some(slot) = colors.get_mut(filled) {
+                            *slot = color;
+                        }
+                        filled += 1;
+                    }
+                }
+                let color = match filled {
+                    0 => palette.background,
+                    _ => Rgb::average(&colors[..filled.min(THUMBNAIL_BUCKET)]),
+                };
+                self.set_pixel(origin + [r, c], color);
+            }
+        }
+    }
+
+    /// Draw a piece in its spawn rotation centered in a `PIECE_SLOT` by `PIECE_SLOT` slot, e.g. the held piece.
+    ///
+    /// The mask is trimmed to the bounding box of its cells first, so every shape sits in the middle of the slot,
+    /// rounded to the top-left for the odd sizes. The pixels of the slot around the piece are left as they are.
+    /// # Arguments
+    /// - `shape` - The `TetrominoShape` to draw
+    /// - `origin` - The position of the top-left pixel of the slot on the panel
+    /// - `color` - The `Rgb` color of the piece
+    /// - `dimmed` - Whether (`true`) or not (`false`) to draw the piece in `Rgb::GRAY` instead, e.g. while it cannot be
+    ///   used
+    pub fn draw_piece_thumbnail(
+        &mut self,
+        shape: TetrominoShape,
+        origin: Coordinate,
+        color: Rgb,
+        dimmed: bool,
+    ) {
+        let tetromino = Tetromino::from(shape);
+        let mask = tetromino.get_mask();
+        let (mut top_left, mut bottom_right) = (
+            Coordinate::from_array([PIECE_SLOT; 2]),
+            Coordinate::from_array([0, 0]),
+        );
+        for row in 0..mask.num_rows() {
+            for col in 0..mask.num_columns() {
+                if mask.get(row, col) == Some(&true) {
+                    top_left =
+                        Coordinate::from_array([top_left.row.min(row), top_left.col.min(col)]);
+                    bottom_right = Coordinate::from_array([
+                        bottom_right.row.max(row),
+                        bottom_right.col.max(col),
+                    ]);
+                }
+            }
+        }
+        let offset = Coordinate::from_array([
+            (PIECE_SLOT - (bottom_right.row - top_left.row + 1)) / 2,
+            (PIECE_SLOT - (bottom_right.col - top_left.col + 1)) / 2,
+        ]);
+        let color = match dimmed {
+            true => Rgb::GRAY,
+            false => color,
+        };
+        for row in top_left.row..=bottom_right.row {
+            for col in top_left.col..=bottom_right.col {
+                if mask.get(row, col) == Some(&true) {
+                    self.set_pixel(
+                        origin + offset + [row - top_left.row, col - top_left.col],
+                        color,
+                    );
+                }
+            }
+        }
+    }
+
+    /// Draw a single cell of a board as a block of `DrawConfig::scale` by `DrawConfig::scale` pixels.
+    ///
+    /// A cell drawn as a single pixel keeps its pattern for `.upscaled()`, while a larger block applies the pattern
+    /// at once, turning off the sub-pixels it does not light. With `DrawConfig::cell_border`, the pattern only shows
+    /// in the inner area of the block, the border is drawn over its last row and column.
+    /// # Arguments
+    /// - `coord` - The [row, col] of the cell on the board
+    /// - `config` - A reference to the `DrawConfig` placing the board on the panel
+    /// - `color` - The color of the cell
+    /// - `pattern` - The `CellPattern` of the cell
+    pub fn draw_cell(
+        &mut self,
+        coord: Coordinate,
+        config: &DrawConfig,
+        color: Rgb,
+        pattern: CellPattern,
+    ) {
+        let scale = config.scale.max(1) as usize;
+        let top_left = Coordinate::from_array([
+            (config.origin.row + coord.row) * scale,
+            (config.origin.col + coord.col) * scale,
+        ]);
+        if scale == 1 {
+            self.set_patterned_pixel(top_left, color, pattern);
+            return;
+        }
+        for r in 0..scale {
+            for c in 0..scale {
+                let edge = r == scale - 1 || c == scale - 1;
+                let lit = match (config.cell_border, pattern.is_lit(r, c)) {
+                    (Some(border), _) if edge => border,
+                    (_, true) => color,
+                    (_, false) => Rgb::BLACK,
+                };
+                self.set_pixel(top_left + [r, c], lit);
+            }
+        }
+    }
+
+    /// Draw the cells of a board, the filled cells in their palette color and pattern and the empty cells in the
+    /// background color, or checkered with the grid color.
+    /// # Arguments
+    /// - `board` - A reference to the board
+    /// - `config` - A reference to the `DrawConfig` placing the board on the panel
+    /// - `palette` - A reference to the `Palette` to draw with
+    pub fn draw_board<B: BoardOps<Cell>>(
+        &mut self,
+        board: &B,
+        config: &DrawConfig,
+        palette: &Palette,
+    ) {
+        for (coord, cell) in board.cells() {
+            let (color, pattern) = match cell {
+                Cell::Empty => match config.grid && (coord.row + coord.col) % 2 == 1 {
+                    true => (palette.grid, CellPattern::Solid),
+                    false => (palette.background, CellPattern::Solid),
+                },
+                Cell::Filled { shape, lum } => (
+                    palette.get_color(shape).scaled(lum),
+                    palette.get_pattern(shape),
+                ),
+                Cell::Garbage => (palette.garbage, CellPattern::Solid),
+            };
+            self.draw_cell(coord, config, color, pattern);
+        }
+    }
+
+    /// Draw a piece in its palette color and pattern, optionally with its ghost where it would land.
+    /// # Arguments
+    /// - `piece` - A reference to the `ActivePiece`
+    /// - `board` - A reference to the board the piece is on, to find the position of the ghost
+    /// - `config` - A reference to the `DrawConfig` placing the board on the panel
+    /// - `palette` - A reference to the `Palette` to draw with
+    /// - `ghost` - Whether (`true`) or not (`false`) to draw the ghost, solid in the ghost color of the palette or
+    ///   else the piece color at a quarter brightness
+    pub fn draw_piece<B: BoardOps<Cell>>(
+        &mut self,
+        piece: &ActivePiece,
+        board: &B,
+        config: &DrawConfig,
+        palette: &Palette,
+        ghost: bool,
+    ) {
+        let tetromino = piece.get_tetromino();
+        let color = palette.get_color(tetromino.shape);
+        if ghost {
+            self._draw_cells(
+                tetromino.get_mask(),
+                piece.ghost_coord(board),
+                config,
+                palette.ghost.unwrap_or(color.scaled_fraction(1, 4)),
+                CellPattern::Solid,
+            );
+        }
+        self._draw_cells(
+            tetromino.get_mask(),
+            piece.get_coord(),
+            config,
+            color,
+            palette.get_pattern(tetromino.shape),
+        );
+    }
+
+    /// Draw the filled elements of a binary mask as cells of a board, see `.draw_cell()`.
+    fn _draw_cells(
+        &mut self,
+        mask: &Array2D<bool>,
+        coord: Coordinate,
+        config: &DrawConfig,
+        color: Rgb,
+        pattern: CellPattern,
+    ) {
+        for r in 0..mask.num_rows() {
+            for c in 0..mask.num_columns() {
+                if mask[(r, c)] {
+                    self.draw_cell(coord + [r, c], config, color, pattern);
+                }
+            }
+        }
+    }
+
+    /// Upscale the frame for a panel with more pixels than cells, drawing every pixel as a block.
+    ///
+    /// The sub-pixels of a block that are not lit by its `CellPattern` are turned off.
+    /// # Arguments
+    /// - `scale` - The number of rows and columns of every block, so `M` must equal `N * scale * scale`
+    /// # Returns
+    /// - `FrameBuffer<M>` - The upscaled frame with the same layout, with all patterns applied
+    pub fn upscaled<const M: usize>(&self, scale: usize) -> FrameBuffer<M> {
+        let mut upscaled = FrameBuffer::with_layout(
+            Coordinate::from_array([self.dims.row * scale, self.dims.col * scale]),
+            self.layout,
+        );
+        for (index, (pixel, pattern)) in self.pixels.iter().zip(&self.patterns).enumerate() {
+            let coord = Coordinate::from_row_major(index, self.dims).expect(IN_FRAME);
+            for r in 0..scale {
+                for c in 0..scale {
+                    if pattern.is_lit(r, c) {
+                        upscaled.set_pixel(
+                            Coordinate::from_array([coord.row * scale + r, coord.col * scale + c]),
+                            *pixel,
+                        );
+                    }
+                }
+            }
+        }
+        upscaled
+    }
+
+    /// Send every pixel to the LEDs in the order of the `PanelLayout`, color corrected, scaled by the global
+    /// brightness and then gamma corrected.
+    ///
+    /// The white balance is corrected first, so the calibration is independent of the brightness, and the gamma
+    /// correction last, so the brightness scales the perceived rather than the emitted light.
+    /// # Arguments
+    /// - `settings` - A reference to the `Settings` holding the color correction, the brightness and the gamma
+    ///   correction flag
+    /// - `write` - The function writing a single pixel to the LEDs
+    pub fn flush<F: FnMut(Rgb)>(&self, settings: &Settings, mut write: F) {
+        for coord in self._chain() {
+            write(_correct(self.get_pixel(coord).expect(IN_FRAME), settings));
+        }
+    }
+
+    /// Send every pixel to the LEDs like `.flush()`, with a translucent overlay composited over the frame first.
+    /// # Arguments
+    /// - `overlay` - A reference to the `Overlay` with the same shape as the frame
+    /// - `settings` - A reference to the `Settings` holding the color correction, the brightness and the gamma
+    ///   correction flag
+    /// - `write` - The function writing a single pixel to the LEDs
+    pub fn flush_with_overlay<F: FnMut(Rgb)>(
+        &self,
+        overlay: &Overlay,
+        settings: &Settings,
+        mut write: F,
+    ) {
+        for coord in self._chain() {
+            let pixel = self.get_pixel(coord).expect(IN_FRAME);
+            write(_correct(overlay.composite_pixel(coord, pixel), settings));
+        }
+    }
+
+    /// Write the frame as the byte stream of a WS2812 chain, 3 bytes per LED in the order of the `PanelLayout`.
+    ///
+    /// The bytes can be handed as-is to a PIO state machine or a DMA transfer driving the LEDs.
+    /// # Arguments
+    /// - `brightness` - The global brightness, from 0 (off) to 255 (unchanged)
+    /// - `gamma` - Whether (`true`) or not (`false`) to gamma correct every pixel after scaling it
+    /// - `out` - The buffer to write the G, R and B byte of every LED into, ideally `3 * N` bytes long
+    /// # Returns
+    /// - `usize` - The number of bytes written, stopping at the last LED that fits in `out`
+    pub fn as_grb_bytes(&self, brightness: u8, gamma: bool, out: &mut [u8]) -> usize {
+        let mut written = 0;
+        for coord in self._chain() {
+            let scaled = self.get_pixel(coord).expect(IN_FRAME).scaled(brightness);
+            let pixel = match gamma {
+                true => scaled.gamma_corrected(),
+                false => scaled,
+            };
+            match pixel.write_grb_into(&mut out[written..]) {
+                0 => break,
+                bytes => written += bytes,
+            }
+        }
+        written
+    }
+
+    /// Encode the frame as the words pushed into the TX FIFO of the WS2812 state machine, one per LED in the order
+    /// of the `PanelLayout` and corrected like `.flush()`, for a DMA transfer streaming it without the CPU.
+    /// # Arguments
+    /// - `settings` - A reference to the `Settings` holding the color correction, the brightness and the gamma
+    ///   correction flag
+    /// - `out` - The buffer to write the word of every LED into, see `ws2812::to_word()`, ideally `N` words long
+    /// # Returns
+    /// - `usize` - The number of words written, stopping at the last LED that fits in `out`
+    pub fn encode_dma_buffer(&self, settings: &Settings, out: &mut [u32]) -> usize {
+        let mut written = 0;
+        for (coord, word) in self._chain().zip(out.iter_mut()) {
+            *word = to_word(_correct(self.get_pixel(coord).expect(IN_FRAME), settings));
+            written += 1;
+        }
+        written
+    }
+
+    /// Encode the frame as the bitplanes of a HUB75 panel with the rows and columns of the frame, see `hub75`.
+    ///
+    /// Every byte holds a bitplane of a pixel and the pixel half a panel below it, see `hub75::pixel_bits()`. The
+    /// bytes run scan line by scan line, then bitplane by bitplane from the least significant one, then column by
+    /// column, regardless of the `PanelLayout`, as the panel addresses its rows itself.
+    /// # Arguments
+    /// - `out` - The buffer to write the bitplanes into, ideally `hub75::plane_bytes()` bytes long
+    /// # Returns
+    /// - `usize` - The number of bytes written, stopping at the last byte that fits in `out`
+    pub fn encode_bitplanes(&self, out: &mut [u8]) -> usize {
+        let scan_lines = self.dims.row / 2;
+        let mut slots = out.iter_mut();
+        let mut written = 0;
+        for row in 0..scan_lines {
+            for plane in 0..BITPLANES {
+                for col in 0..self.dims.col {
+                    let Some(slot) = slots.next() else {
+                        return written;
+                    };
+                    let upper = self.get_pixel(Coordinate { row, col }).expect(IN_FRAME);
+                    let lower = self
+                        .get_pixel(Coordinate {
+                            row: row + scan_lines,
+                            col,
+                        })
+                        .expect(IN_FRAME);
+                    *slot = pixel_bits(upper, lower, plane);
+                    written += 1;
+                }
+            }
+        }
+        written
+    }
+
+    /// Iterate over the pixels in the order of the LED chain.
+    fn _chain(&self) -> impl Iterator<Item = Coordinate> + '_ {
+        (0..N).map(move |index| {
+            self.layout
+                .to_coord(index, self.dims)
+                .expect("the layout maps every LED into the frame")
+        })
+    }
+}
+
+/// A pair of frame buffers, drawing into the back buffer while the front buffer is being sent to the LEDs.
+///
+/// The back buffer always starts as a copy of the front buffer, so its changed pixels are exactly the ones
+/// to send after a swap, whether the frame is redrawn from scratch or only partially.
+/// # Attributes
+/// - `front` - The frame on the LEDs, or being sent to them
+/// - `back` - The frame being drawn
+#[derive(Debug)]
+pub struct DoubleBuffer<const N: usize> {
+    front: FrameBuffer<N>,
+    back: FrameBuffer<N>,
+}
+
+impl<const N: usize> DoubleBuffer<N> {
+    /// Create a pair of black frame buffers for a panel chained in row major order.
+    /// # Arguments
+    /// - `dims` - The number of [rows, cols] of the panel as a `Coordinate`, covering exactly `N` pixels
+    /// # Returns
+    /// - `DoubleBuffer<N>` - The frame buffers with all pixels off
+    pub fn new(dims: Coordinate) -> Self {
+        DoubleBuffer::with_layout(dims, PanelLayout::RowMajor)
+    }
+
+    /// Create a pair of black frame buffers for a panel chained in any order.
+    /// # Arguments
+    /// - `dims` - The number of [rows, cols] of the panel as a `Coordinate`, covering exactly `N` pixels
+    /// - `layout` - The `PanelLayout` of the LED chain
+    /// # Returns
+    /// - `DoubleBuffer<N>` - The frame buffers with all pixels off
+    pub fn with_layout(dims: Coordinate, layout: PanelLayout) -> Self {
+        DoubleBuffer {
+            front: FrameBuffer::with_layout(dims, layout),
+            back: FrameBuffer::with_layout(dims, layout),
+        }
+    }
+
+    /// Get the frame on the LEDs.
+    pub fn get_front(&self) -> &FrameBuffer<N> {
+        &self.front
+    }
+
+    /// Get the frame to draw the next frame into.
+    pub fn get_back_mut(&mut self) -> &mut FrameBuffer<N> {
+        &mut self.back
+    }
+
+    /// Show the drawn frame, marking the pixels that differ from the previous frame as dirty.
+    ///
+    /// Pixels drawn over with their previous color, e.g. after clearing the frame, are not dirty.
+    pub fn swap(&mut self) {
+        let changed = self.back.dirty.take().and_then(|region| {
+            region
+                .coords()
+                .filter(|coord| {
+                    let index = coord.row * self.back.dims.col + coord.col;
+                    self.back.pixels[index] != self.front.pixels[index]
+                        || self.back.patterns[index] != self.front.patterns[index]
+                })
+                .fold(None, |region, coord| Some(Region::including(region, coord)))
+        });
+        // Keep the pixels of the previous frame that were never sent.
+        let unsent = self.front.dirty.take();
+        core::mem::swap(&mut self.front, &mut self.back);
+        self.front.dirty = match (unsent, changed) {
+            (Some(unsent), Some(changed)) => Some(unsent.union(changed)),
+            (unsent, changed) => unsent.or(changed),
+        };
+        self.back.pixels = self.front.pixels;
+        self.back.patterns = self.front.patterns;
+    }
+
+    /// Send the changed pixels of the front buffer to the LEDs, see `FrameBuffer::write_dirty()`.
+    /// # Arguments
+    /// - `settings` - A reference to the `Settings` holding the color correction, the brightness and the gamma
+    ///   correction flag
+    /// - `write` - The function writing a single pixel to the LEDs
+    /// # Returns
+    /// - `usize` - The number of LEDs written
+    pub fn write_dirty<F: FnMut(Rgb)>(&mut self, settings: &Settings, write: F) -> usize {
+        self.front.write_dirty(settings, write)
+    }
+}
+
+#[cfg(feature = "embedded-graphics")]
+impl<const N: usize> embedded_graphics::geometry::OriginDimensions for FrameBuffer<N> {
+    /// Get the size of the panel, with x along the columns and y along the rows.
+    fn size(&self) -> embedded_graphics::geometry::Size {
+        embedded_graphics::geometry::Size::new(self.dims.col as u32, self.dims.row as u32)
+    }
+}
+
+#[cfg(feature = "embedded-graphics")]
+impl<const N: usize> embedded_graphics::draw_target::DrawTarget for FrameBuffer<N> {
+    type Color = embedded_graphics::pixelcolor::Rgb888;
+    type Error = core::convert::Infallible;
+
+    /// Draw pixels at [y, x], ignoring pixels outside of the panel, see `.set_pixel()`.
+    ///
+    /// The pixels are sent in the order of the `PanelLayout` by `.flush()`, so the drawing is layout independent.
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = embedded_graphics::Pixel<Self::Color>>,
+    {
+        for embedded_graphics::Pixel(point, color) in pixels {
+            if let (Ok(row), Ok(col)) = (usize::try_from(point.y), usize::try_from(point.x)) {
+                self.set_pixel(Coordinate::from_array([row, col]), color.into());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Get the number of filled pixels of a gauge, rounding down.
+fn _gauge_pixels(len: usize, level: u8) -> usize {
+    len * level as usize / u8::MAX as usize
+}
+
+/// Correct the color of a pixel for the LEDs, in the order documented at `FrameBuffer::flush()`.
+fn _correct(pixel: Rgb, settings: &Settings) -> Rgb {
+    let scaled = settings.correction.apply(pixel).scaled(settings.brightness);
+    match settings.gamma {
+        true => scaled.gamma_corrected(),
+        false => scaled,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DoubleBuffer, DrawConfig, FrameBuffer, PanelLayout};
+    use crate::board::{Board, BoardOps, FixedBoard};
+    use crate::cell::Cell;
+    use crate::color::{ColorCorrection, Rgb};
+    use crate::coordinate::{Coordinate, Region};
+    use crate::display::overlay::Overlay;
+    use crate::hub75::{B1, B2, G2, R1, R2};
+    use crate::palette::{CellPattern, Palette, GUIDELINE};
+    use crate::piece::ActivePiece;
+    use crate::settings::Settings;
+    use crate::tetrominoes::{Tetromino, TetrominoShape};
+    use crate::ws2812::to_word;
+    use test_case::test_case;
+
+    #[test_case(PanelLayout::RowMajor, [[0, 0], [0, 1], [0, 2], [1, 0], [1, 1], [1, 2]]; "row major")]
+    #[test_case(PanelLayout::Serpentine, [[0, 0], [0, 1], [0, 2], [1, 2], [1, 1], [1, 0]]; "serpentine")]
+    #[test_case(PanelLayout::ColumnMajor, [[0, 0], [1, 0], [0, 1], [1, 1], [0, 2], [1, 2]]; "column major")]
+    #[test_case(PanelLayout::ColumnSerpentine, [[0, 0], [1, 0], [1, 1], [0, 1], [0, 2], [1, 2]]; "column serpentine")]
+    fn test_layout(layout: PanelLayout, chain: [[usize; 2]; 6]) {
+        let dims = Coordinate::from_array([2, 3]);
+        for (index, coord) in chain.into_iter().map(Coordinate::from_array).enumerate() {
+            assert_eq!(layout.to_coord(index, dims), Some(coord));
+            assert_eq!(layout.to_index(coord, dims), Some(index));
+        }
+        assert_eq!(layout.to_coord(6, dims), None);
+        assert_eq!(layout.to_index(Coordinate::from_array([2, 0]), dims), None);
+    }
+
+    #[test]
+    fn test_grb_bytes() {
+        // Create board:
+        //   0 1 2
+        // 0 . . G
+        // 1 T . .
+        // Chained as a serpentine, the garbage is the 3rd LED and the T cell the 6th.
+        let mut board = FixedBoard::<Cell, 2, 3>::new(Cell::Empty);
+        board.set(Coordinate::from_array([0, 2]), Cell::Garbage);
+        board.set(
+            Coordinate::from_array([1, 0]),
+            Cell::Filled {
+                shape: TetrominoShape::T,
+                lum: 255,
+            },
+        );
+        let mut frame =
+            FrameBuffer::<6>::with_layout(Coordinate::from_array([2, 3]), PanelLayout::Serpentine);
+        frame.draw_board(&board, &DrawConfig::default(), &GUIDELINE);
+        let mut bytes = [0; 18];
+        assert_eq!(frame.as_grb_bytes(255, false, &mut bytes), 18);
+        assert_eq!(bytes[0..6], [0; 6]);
+        assert_eq!(bytes[6..9], [64, 64, 64]);
+        assert_eq!(bytes[9..15], [0; 6]);
+        assert_eq!(bytes[15..18], [0, 255, 255]);
+        // The brightness is applied before the gamma correction, and a short buffer holds whole LEDs only.
+        let mut bytes = [0; 10];
+        assert_eq!(frame.as_grb_bytes(128, true, &mut bytes), 9);
+        assert_eq!(
+            bytes[6..9],
+            Rgb::from_array(&[32, 32, 32]).gamma_corrected().to_grb()
+        );
+    }
+
+    #[test]
+    fn test_encode_dma_buffer() {
+        // The words follow the chain of a serpentine panel:
+        //   0 1 2
+        // 0 . . G
+        // 1 T . .
+        let mut frame =
+            FrameBuffer::<6>::with_layout(Coordinate::from_array([2, 3]), PanelLayout::Serpentine);
+        frame.set_pixel(
+            Coordinate::from_array([0, 2]),
+            Rgb::from_array(&[64, 64, 64]),
+        );
+        frame.set_pixel(
+            Coordinate::from_array([1, 0]),
+            Rgb::from_array(&[255, 0, 255]),
+        );
+        let settings = Settings {
+            brightness: 128,
+            gamma: false,
+            ..Default::default()
+        };
+        let mut words = [1; 7];
+        assert_eq!(frame.encode_dma_buffer(&settings, &mut words), 6);
+        assert_eq!(words, [0, 0, 0x2020_2000, 0, 0, 0x0080_8000, 1]);
+        // The same words as pushed one by one by `.flush()`, and a short buffer holds the start of the chain.
+        let mut flushed = Vec::new();
+        frame.flush(&settings, |pixel| flushed.push(to_word(pixel)));
+        assert_eq!(words[..6], flushed[..]);
+        let mut words = [0; 4];
+        assert_eq!(frame.encode_dma_buffer(&settings, &mut words), 4);
+        assert_eq!(words[..], flushed[..4]);
+    }
+
+    #[test]
+    fn test_dirty() {
+        // A serpentine panel of 3 x 4 chains the LEDs as:
+        //  0  1  2  3
+        //  7  6  5  4
+        //  8  9 10 11
+        let dims = Coordinate::from_array([3, 4]);
+        let mut frame = FrameBuffer::<12>::with_layout(dims, PanelLayout::Serpentine);
+        assert_eq!(frame.get_dirty(), None);
+        assert_eq!(frame.get_dirty_prefix(), 0);
+        // Setting a pixel to its current color changes nothing.
+        frame.set_pixel(Coordinate::from_array([1, 1]), Rgb::BLACK);
+        assert_eq!(frame.get_dirty(), None);
+        frame.set_pixel(Coordinate::from_array([1, 2]), Rgb::WHITE);
+        frame.set_pixel(Coordinate::from_array([0, 3]), Rgb::WHITE);
+        assert_eq!(
+            frame.get_dirty(),
+            Some(Region {
+                top_left: Coordinate::from_array([0, 2]),
+                bottom_right: Coordinate::from_array([1, 3]),
+            })
+        );
+        // The region covers the LEDs 2, 3, 4 and 5, so the first 6 LEDs are sent.
+        assert_eq!(frame.get_dirty_prefix(), 6);
+        let mut sent = 0;
+        assert_eq!(frame.write_dirty(&Settings::default(), |_| sent += 1), 6);
+        assert_eq!(sent, 6);
+        assert_eq!(frame.write_dirty(&Settings::default(), |_| sent += 1), 0);
+        // Regions outside of the panel are clipped.
+        frame.mark_dirty(Region {
+            top_left: Coordinate::from_array([2, 0]),
+            bottom_right: Coordinate::from_array([5, 5]),
+        });
+        assert_eq!(frame.get_dirty_prefix(), 12);
+    }
+
+    #[test]
+    fn test_double_buffer() {
+        let draw = |frame: &mut FrameBuffer<12>, col: usize| {
+            frame.clear();
+            frame.set_pixel(Coordinate::from_array([0, col]), Rgb::WHITE);
+            frame.set_pixel(Coordinate::from_array([2, 3]), Rgb::WHITE);
+        };
+        let settings = Settings::default();
+        let mut buffers = DoubleBuffer::<12>::with_layout(
+            Coordinate::from_array([3, 4]),
+            PanelLayout::Serpentine,
+        );
+        draw(buffers.get_back_mut(), 0);
+        buffers.swap();
+        assert_eq!(buffers.write_dirty(&settings, |_| {}), 12);
+        // Redrawing the same frame from scratch schedules nothing.
+        draw(buffers.get_back_mut(), 0);
+        buffers.swap();
+        assert_eq!(buffers.get_front().get_dirty(), None);
+        assert_eq!(buffers.write_dirty(&settings, |_| {}), 0);
+        // Moving a pixel in the top row only sends the start of the chain.
+        draw(buffers.get_back_mut(), 2);
+        buffers.swap();
+        assert_eq!(buffers.write_dirty(&settings, |_| {}), 3);
+        assert_eq!(
+            buffers
+                .get_front()
+                .get_pixel(Coordinate::from_array([0, 2])),
+            Some(Rgb::WHITE)
+        );
+        // Frames swapped without being sent accumulate their changes.
+        draw(buffers.get_back_mut(), 1);
+        buffers.swap();
+        draw(buffers.get_back_mut(), 3);
+        buffers.swap();
+        assert_eq!(buffers.write_dirty(&settings, |_| {}), 4);
+    }
+
+    #[cfg(feature = "embedded-graphics")]
+    #[test]
+    fn test_draw_target() {
+        use embedded_graphics::pixelcolor::Rgb888;
+        use embedded_graphics::prelude::{Point, Primitive, Size};
+        use embedded_graphics::primitives::{PrimitiveStyle, Rectangle};
+        use embedded_graphics::Drawable;
+
+        let dims = Coordinate::from_array([3, 4]);
+        let mut frame = FrameBuffer::<12>::with_layout(dims, PanelLayout::Serpentine);
+        Rectangle::new(Point::new(1, 1), Size::new(1, 1))
+            .into_styled(PrimitiveStyle::with_fill(Rgb888::new(255, 0, 0)))
+            .draw(&mut frame)
+            .unwrap();
+        // Pixels off the panel are clipped.
+        Rectangle::new(Point::new(-1, 2), Size::new(2, 2))
+            .into_styled(PrimitiveStyle::with_fill(Rgb888::new(0, 0, 255)))
+            .draw(&mut frame)
+            .unwrap();
+        let mut chain = Vec::new();
+        frame.flush(&Settings::default(), |pixel| {
+            chain.push(pixel != Rgb::BLACK)
+        });
+        // [1, 1] is the 7th LED of the serpentine chain, [2, 0] the 9th.
+        let lit: Vec<usize> = (0..12).filter(|index| chain[*index]).collect();
+        assert_eq!(lit, vec![6, 8]);
+        assert_eq!(
+            frame.get_pixel(Coordinate::from_array([1, 1])),
+            Some(Rgb::from_array(&[255, 0, 0]))
+        );
+        assert_eq!(
+            frame.get_pixel(Coordinate::from_array([2, 0])),
+            Some(Rgb::from_array(&[0, 0, 255]))
+        );
+    }
+
+    #[test]
+    fn test_draw_piece() {
+        // The ghost of a flat I piece lands on the bottom row of an empty board, dimmed to a quarter.
+        let board = FixedBoard::<Cell, 6, 4>::new(Cell::Empty);
+        let piece = ActivePiece::new(
+            Tetromino::from(TetrominoShape::I),
+            Coordinate::from_array([0, 0]),
+        );
+        let mut frame = FrameBuffer::<24>::new(Coordinate::from_array([6, 4]));
+        frame.draw_piece(&piece, &board, &DrawConfig::default(), &GUIDELINE, true);
+        let color = GUIDELINE.get_color(TetrominoShape::I);
+        let ghost = piece.ghost_coord(&board);
+        assert_eq!(ghost, Coordinate::from_array([5, 0]));
+        assert_eq!(frame.get_pixel(piece.get_coord() + [0, 3]), Some(color));
+        assert_eq!(
+            frame.get_pixel(ghost + [0, 3]),
+            Some(color.scaled_fraction(1, 4))
+        );
+    }
+
+    /// Draw a board with a single filled cell at [0, 1], one cell below the top of the panel, and get the
+    /// positions along the LED chain of the lit LEDs.
+    fn lit_leds<const N: usize>(scale: u8, layout: PanelLayout) -> Vec<usize> {
+        let mut board = FixedBoard::<Cell, 2, 2>::new(Cell::Empty);
+        board.set(
+            Coordinate::from_array([0, 1]),
+            Cell::Filled {
+                shape: TetrominoShape::T,
+                lum: 255,
+            },
+        );
+        let size = scale as usize;
+        let dims = Coordinate::from_array([3 * size, 2 * size]);
+        let mut frame = FrameBuffer::<N>::with_layout(dims, layout);
+        let config = DrawConfig {
+            origin: Coordinate::from_array([1, 0]),
+            scale,
+            grid: false,
+            cell_border: None,
+        };
+        frame.draw_board(&board, &config, &GUIDELINE);
+        (0..N)
+            .filter(|index| {
+                let coord = layout.to_coord(*index, dims).unwrap();
+                frame.get_pixel(coord) != Some(Rgb::BLACK)
+            })
+            .collect()
+    }
+
+    /// The color of a locked cell of a shape, at the luminance it locks with.
+    fn locked_color(shape: TetrominoShape) -> Rgb {
+        match Cell::locked(shape) {
+            Cell::Filled { shape, lum } => GUIDELINE.get_color(shape).scaled(lum),
+            _ => unreachable!("a locked cell is filled"),
+        }
+    }
+
+    #[test_case([24, 12], [12, 6], [6, 3]; "2 to 1")]
+    #[test_case([24, 12], [8, 4], [4, 2]; "3 to 1")]
+    #[test_case([20, 10], [7, 3], [4, 2]; "non-integer")]
+    fn test_board_thumbnail(board: [usize; 2], target: [usize; 2], lit: [usize; 2]) {
+        // The top-left quadrant of the board is filled.
+        let mut board = Board::new(Coordinate::from_array(board), Cell::Empty);
+        let quadrant =
+            Coordinate::from_array([board.get_shape().row / 2, board.get_shape().col / 2]);
+        for row in 0..quadrant.row {
+            for col in 0..quadrant.col {
+                board.set(Coordinate { row, col }, Cell::locked(TetrominoShape::T));
+            }
+        }
+        let mut frame = FrameBuffer::<200>::new(Coordinate::from_array([20, 10]));
+        let origin = Coordinate::from_array([1, 2]);
+        let target = Coordinate::from_array(target);
+        frame.draw_board_thumbnail(&board, origin, target, &GUIDELINE);
+        for row in 0..target.row {
+            for col in 0..target.col {
+                let expected = match row < lit[0] && col < lit[1] {
+                    true => locked_color(TetrominoShape::T),
+                    false => GUIDELINE.background,
+                };
+                assert_eq!(frame.get_pixel(origin + [row, col]), Some(expected));
+            }
+        }
+    }
+
+    #[test]
+    fn test_board_thumbnail_colors() {
+        // A pixel averages the colors of the filled cells of its bucket only.
+        let mut board = Board::new(Coordinate::from_array([2, 2]), Cell::Empty);
+        board.set(Coordinate::from_array([0, 0]), Cell::Garbage);
+        board.set(
+            Coordinate::from_array([1, 1]),
+            Cell::locked(TetrominoShape::I),
+        );
+        let mut frame = FrameBuffer::<1>::new(Coordinate::from_array([1, 1]));
+        let origin = Coordinate::from_array([0, 0]);
+        frame.draw_board_thumbnail(&board, origin, Coordinate::from_array([1, 1]), &GUIDELINE);
+        let expected = Rgb::average(&[GUIDELINE.garbage, locked_color(TetrominoShape::I)]);
+        assert_eq!(frame.get_pixel(origin), Some(expected));
+    }
+
+    #[test_case(TetrominoShape::I, &[[1, 0], [1, 1], [1, 2], [1, 3]]; "I")]
+    #[test_case(TetrominoShape::O, &[[1, 1], [1, 2], [2, 1], [2, 2]]; "O")]
+    #[test_case(TetrominoShape::T, &[[1, 1], [2, 0], [2, 1], [2, 2]]; "T")]
+    fn test_piece_thumbnail(shape: TetrominoShape, lit: &[[usize; 2]]) {
+        // The trimmed piece is centered in the slot, only lighting its own pixels.
+        let mut frame = FrameBuffer::<64>::new(Coordinate::from_array([8, 8]));
+        let origin = Coordinate::from_array([2, 3]);
+        let color = GUIDELINE.get_color(shape);
+        for dimmed in [false, true] {
+            frame.clear();
+            frame.draw_piece_thumbnail(shape, origin, color, dimmed);
+            let expected = match dimmed {
+                true => Rgb::GRAY,
+                false => color,
+            };
+            for row in 0..8 {
+                for col in 0..8 {
+                    let inside = row >= origin.row && col >= origin.col;
+                    let is_lit = inside && lit.contains(&[row - origin.row, col - origin.col]);
+                    let pixel = frame.get_pixel(Coordinate { row, col });
+                    match is_lit {
+                        true => assert_eq!(pixel, Some(expected)),
+                        false => assert_eq!(pixel, Some(Rgb::BLACK)),
+                    }
+                }
+            }
+        }
+    }
+
+    #[test_case(2, PanelLayout::Serpentine, &[10, 11, 12, 13]; "scale 2 serpentine")]
+    #[test_case(2, PanelLayout::ColumnSerpentine, &[14, 15, 20, 21]; "scale 2 column serpentine")]
+    #[test_case(3, PanelLayout::Serpentine, &[18, 19, 20, 27, 28, 29, 30, 31, 32]; "scale 3 serpentine")]
+    #[test_case(3, PanelLayout::ColumnSerpentine, &[30, 31, 32, 39, 40, 41, 48, 49, 50]; "scale 3 column serpentine")]
+    fn test_draw_scaled(scale: u8, layout: PanelLayout, expected: &[usize]) {
+        // The cell at [1, 1] in cells covers rows and columns `scale` to `2 * scale` of the panel.
+        let lit = match scale {
+            2 => lit_leds::<24>(scale, layout),
+            _ => lit_leds::<54>(scale, layout),
+        };
+        assert_eq!(lit, expected);
+    }
+
+    #[test]
+    fn test_draw_scaled_patterns() {
+        // At scale 2 checkered cells light only their diagonal, the ghost stays solid, and the grid checkers whole
+        // blocks.
+        let board = FixedBoard::<Cell, 4, 4>::new(Cell::Empty);
+        let piece = ActivePiece::new(
+            Tetromino::from(TetrominoShape::O),
+            Coordinate::from_array([0, 1]),
+        );
+        let palette = Palette {
+            patterns: [CellPattern::Checker; 7],
+            grid: Rgb::WHITE,
+            ..GUIDELINE
+        };
+        let config = DrawConfig {
+            origin: Coordinate::from_array([0, 0]),
+            scale: 2,
+            grid: true,
+            cell_border: None,
+        };
+        let mut frame = FrameBuffer::<64>::new(Coordinate::from_array([8, 8]));
+        frame.draw_board(&board, &config, &palette);
+        frame.draw_piece(&piece, &board, &config, &palette, true);
+        let color = palette.get_color(TetrominoShape::O);
+        let ghost = palette.ghost.unwrap_or(color.scaled_fraction(1, 4));
+        let (bg, grid) = (palette.background, palette.grid);
+        assert_eq!(
+            frame.get_row(0),
+            Some(&[bg, bg, color, Rgb::BLACK, color, Rgb::BLACK, grid, grid][..])
+        );
+        assert_eq!(
+            frame.get_row(1),
+            Some(&[bg, bg, Rgb::BLACK, color, Rgb::BLACK, color, grid, grid][..])
+        );
+        assert_eq!(
+            frame.get_row(6),
+            Some(&[grid, grid, ghost, ghost, ghost, ghost, bg, bg][..])
+        );
+    }
+
+    #[test]
+    fn test_cell_border() {
+        // A checkered cell at scale 3, with the border drawn over the pattern in the last row and column.
+        let mut frame = FrameBuffer::<9>::new(Coordinate::from_array([3, 3]));
+        let (color, off, border) = (Rgb::WHITE, Rgb::BLACK, Rgb::GRAY);
+        let mut config = DrawConfig {
+            scale: 3,
+            ..Default::default()
+        };
+        let origin = Coordinate::from_array([0, 0]);
+        frame.draw_cell(origin, &config, color, CellPattern::Checker);
+        assert_eq!(
+            frame.pixels,
+            [color, off, color, off, color, off, color, off, color]
+        );
+        config.cell_border = Some(border);
+        frame.draw_cell(origin, &config, color, CellPattern::Checker);
+        assert_eq!(
+            frame.pixels,
+            [color, off, border, off, color, border, border, border, border]
+        );
+        // A single pixel per cell has no room for a border.
+        config.scale = 1;
+        frame.draw_cell(origin, &config, color, CellPattern::Solid);
+        assert_eq!(frame.get_pixel(origin), Some(color));
+    }
+
+    #[test]
+    fn test_encode_bitplanes() {
+        // A 4 by 4 panel scans rows 0 and 2, then rows 1 and 3, each in 4 bitplanes of 4 pixel pairs.
+        let mut frame = FrameBuffer::<16>::new(Coordinate::from_array([4, 4]));
+        frame.set_pixel(
+            Coordinate::from_array([0, 0]),
+            Rgb::from_array(&[0xF0, 0, 0]),
+        );
+        frame.set_pixel(
+            Coordinate::from_array([2, 1]),
+            Rgb::from_array(&[0, 0x50, 0]),
+        );
+        frame.set_pixel(Coordinate::from_array([0, 2]), Rgb::from_array(&[0x0F; 3]));
+        frame.set_pixel(
+            Coordinate::from_array([1, 3]),
+            Rgb::from_array(&[0x30, 0, 0xA0]),
+        );
+        frame.set_pixel(Coordinate::from_array([3, 3]), Rgb::WHITE);
+        let mut out = [0xFF; 32];
+        assert_eq!(frame.encode_bitplanes(&mut out), 32);
+        let lower = R2 | G2 | B2;
+        // Rows 0 and 2 from the least significant bitplane, then rows 1 and 3.
+        let expected = [
+            [R1, G2, 0, 0],
+            [R1, 0, 0, 0],
+            [R1, G2, 0, 0],
+            [R1, 0, 0, 0],
+            [0, 0, 0, R1 | lower],
+            [0, 0, 0, R1 | B1 | lower],
+            [0, 0, 0, lower],
+            [0, 0, 0, B1 | lower],
+        ];
+        assert_eq!(out, expected.concat()[..]);
+        // A short buffer holds the start of the first scan line.
+        let mut short = [0; 6];
+        assert_eq!(frame.encode_bitplanes(&mut short), 6);
+        assert_eq!(short, expected.concat()[..6]);
+    }
+
+    #[test]
+    fn test_flush() {
+        let mut frame = FrameBuffer::<2>::new(Coordinate::from_array([1, 2]));
+        frame.set_pixel(
+            Coordinate::from_array([0, 1]),
+            Rgb::from_array(&[255, 128, 2]),
+        );
+        let mut settings = Settings {
+            brightness: 128,
+            gamma: false,
+            ..Default::default()
+        };
+        let mut pixels = Vec::new();
+        frame.flush(&settings, |pixel| pixels.push(pixel.to_array()));
+        assert_eq!(pixels, vec![[0, 0, 0], [128, 64, 1]]);
+        assert_eq!(
+            frame.get_row(0),
+            Some(&[Rgb::from_array(&[0, 0, 0]), Rgb::from_array(&[255, 128, 2])][..])
+        );
+        assert_eq!(frame.get_row(1), None);
+        // The gamma correction is applied after scaling.
+        settings.gamma = true;
+        pixels.clear();
+        frame.flush(&settings, |pixel| pixels.push(pixel.to_array()));
+        assert_eq!(
+            pixels[1],
+            Rgb::from_array(&[128, 64, 1]).gamma_corrected().to_array()
+        );
+        // The color correction is applied before scaling.
+        settings.gamma = false;
+        settings.correction = ColorCorrection::TYPICAL_SMD5050;
+        pixels.clear();
+        frame.flush(&settings, |pixel| pixels.push(pixel.to_array()));
+        assert_eq!(pixels[1], [128, 44, 1]);
+        // An overlay is composited before correcting, leaving the frame itself untouched.
+        let mut overlay = Overlay::new(frame.get_shape());
+        overlay.dim_all(255);
+        pixels.clear();
+        frame.flush_with_overlay(&overlay, &settings, |pixel| pixels.push(pixel.to_array()));
+        assert_eq!(pixels, vec![[0, 0, 0], [0, 0, 0]]);
+        assert_ne!(
+            frame.get_pixel(Coordinate::from_array([0, 1])),
+            Some(Rgb::BLACK)
+        );
+    }
+
+    #[test]
+    fn test_upscaled() {
+        // A checkered red cell next to a solid white one, upscaled to 2x2 blocks.
+        let red = Rgb::from_array(&[255, 0, 0]);
+        let mut frame = FrameBuffer::<2>::new(Coordinate::from_array([1, 2]));
+        frame.set_patterned_pixel(Coordinate::from_array([0, 0]), red, CellPattern::Checker);
+        frame.set_pixel(Coordinate::from_array([0, 1]), Rgb::WHITE);
+        let upscaled: FrameBuffer<8> = frame.upscaled(2);
+        assert_eq!(upscaled.get_shape(), Coordinate::from_array([2, 4]));
+        assert_eq!(
+            upscaled.get_row(0),
+            Some(&[red, Rgb::BLACK, Rgb::WHITE, Rgb::WHITE][..])
+        );
+        assert_eq!(
+            upscaled.get_row(1),
+            Some(&[Rgb::BLACK, red, Rgb::WHITE, Rgb::WHITE][..])
+        );
+    }
+
+    #[test]
+    fn test_blit_scaled_down() {
+        // The flat I piece covers the top half of its 2 blocks, so it is drawn at half brightness.
+        let i = Tetromino::from(TetrominoShape::I);
+        let mut frame = FrameBuffer::<6>::new(Coordinate::from_array([2, 3]));
+        frame.blit_scaled_down(i.get_mask(), Coordinate::from_array([0, 1]), i.color);
+        let half = Rgb::average(&[i.color, i.color, Rgb::BLACK, Rgb::BLACK]);
+        assert_eq!(frame.get_row(0), Some(&[Rgb::BLACK, half, half][..]));
+        assert_eq!(frame.get_row(1), Some(&[Rgb::BLACK; 3][..]));
+    }
+}
@@ -0,0 +1,4012 @@
+use crate::ai::{Autopilot, Lookahead};
+use crate::board::{Board, BoardOps};
+use crate::cell::Cell;
+use crate::clock::{GameClock, TICK_HZ};
+use crate::color::Rgb;
+use crate::coordinate::{Coordinate, Region};
+use crate::display::actions::draw_input_history;
+use crate::display::digits::{draw_digit, to_digits, GLYPH_COLS, GLYPH_ROWS, MAX_DIGITS};
+use crate::display::overlay::Overlay as UiOverlay;
+use crate::effects::{flash, pulse, sparkle};
+use crate::error::TetrisError;
+use crate::events::{EventSink, GameEvent, GameEvents};
+use crate::finesse::{Finesse, FINESSE_COLS};
+use crate::framebuffer::{FrameBuffer, IN_FRAME};
+use crate::gamelog::{GameLog, LogEntry, LOG_ENTRIES};
+use crate::garbage::{attack, CheeseRows, GarbageGap, GarbageQueue};
+use crate::gravity::{ticks_per_row, GravityEngine};
+use crate::highscores::{HighScores, Ranking};
+use crate::hold::HoldSlot;
+use crate::input::history::InputHistory;
+use crate::input::{Action, Chord, HorizontalRepeat, InputState};
+use crate::invariants::InvariantViolation;
+use crate::layout::{Layout, PREVIEW_ROWS};
+use crate::lifetime::LifetimeStats;
+use crate::menu::{MenuOutput, MenuState};
+use crate::palette::{CellPattern, Palette, ACCESSIBLE, GUIDELINE};
+use crate::piece::{ActivePiece, RotationResult};
+use crate::power::{Inactivity, Power};
+use crate::queue::PieceQueue;
+use crate::recorder::{mask_chord, ReplayRecorder};
+use crate::replay::{Replay, ReplayResult};
+use crate::rng::GameRng;
+use crate::savestate::{
+    cell_from_u8, open_save_state, save_state_bytes, save_state_shape, Persist, SaveError, Writer,
+    MAGIC, SAVE_VERSION, STATE_BYTES,
+};
+use crate::scoring::{is_difficult, ClearEvent, Score};
+use crate::settings::{
+    ColorScheme, DebugOverlay, GameMode, Settings, StackColorMode, SETTINGS_BYTES,
+};
+use crate::storage::_crc32;
+use crate::tetrominoes::{preload_masks, Tetromino, TetrominoShape};
+
+/// The number of ticks without input on the menu before the game demos itself, 60 seconds at 60 ticks per second.
+pub const ATTRACT_TICKS: u32 = 60 * 60;
+
+/// The actions to hold together to abandon a game for the menu, see `Game::set_reset_chord()`.
+pub const RESET_CHORD: [Action; 2] = [Action::Start, Action::Hold];
+
+/// The number of ticks to hold the `RESET_CHORD` for, 1 second at `TICK_HZ`.
+pub const RESET_TICKS: u32 = TICK_HZ;
+
+/// The number of steps of the countdown before a game starts.
+const COUNTDOWN_STEPS: u8 = 3;
+
+/// The number of frames the clearing rows stay white or keep their colors before toggling.
+const FLASH_FRAMES: u8 = 4;
+
+/// The number of ticks of a full pulse of the pause indicator.
+const PAUSE_PULSE_TICKS: u16 = 60;
+
+/// The chance out of 256 of a cell of the board sparkling while entering a new high score.
+const SPARKLE_DENSITY: u8 = 8;
+
+/// The brightness of the bottom rows of the board with `depth_fog`, from the bottom row up.
+const FOG: [u8; 4] = [128, 160, 192, 224];
+
+/// The opacity of the black covering the panel while paused or entering a name.
+const DIM_ALPHA: u8 = 160;
+
+/// The color of the clearing rows while flashing.
+const FLASH_COLOR: Rgb = Rgb::WHITE;
+
+/// The color the board fills up with after a game over.
+const SWEEP_COLOR: Rgb = Rgb::from_array(&[96, 96, 96]);
+
+/// The color of the filled part of the debug gauges of the auto-shift charge and the lock delay.
+const GAUGE_COLOR: Rgb = Rgb::from_array(&[255, 160, 0]);
+
+/// The color of the meter of the incoming garbage.
+const GARBAGE_COLOR: Rgb = Rgb::from_array(&[255, 0, 0]);
+
+/// The number of ticks of a flash of the garbage meter, once the garbage is about to be applied.
+const GARBAGE_FLASH_TICKS: u16 = 8;
+
+/// How far the active piece is tinted towards white after its last lock delay reset, out of 255.
+const FINAL_RESET_TINT: u8 = 96;
+
+/// Why a game ended.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Cause {
+    /// A new piece overlapped the stack when spawning.
+    BlockOut,
+    /// Incoming garbage pushed the stack out at the top.
+    TopOut,
+}
+
+/// The result of a game that reached the goal of its `GameMode`.
+/// # Attributes
+/// - `ticks` - The number of ticks played
+/// - `points` - The final number of points
+/// - `lines` - The final number of cleared lines
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Outcome {
+    pub ticks: u64,
+    pub points: u32,
+    pub lines: u32,
+}
+
+/// The name entry after a game that made it into the high-score table.
+/// # Attributes
+/// - `initials` - The 3 ASCII initials entered so far
+/// - `cursor` - The index of the initial being edited
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NameEntry {
+    pub initials: [u8; 3],
+    pub cursor: usize,
+}
+
+/// The top-level state of a game.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum GameState {
+    /// Showing the splash and the menu, see `MenuState`, until it starts a game with the chosen settings.
+    #[default]
+    Menu,
+    /// Counting down the remaining steps before play starts.
+    Countdown(u8),
+    Playing,
+    /// Flashing the completed rows, with bit `r` of `rows_mask` set for every completed row `r`.
+    /// Gravity is suspended and all input except `Action::Pause` is ignored until `frames_left` reaches 0.
+    Clearing {
+        rows_mask: u64,
+        frames_left: u8,
+    },
+    /// Frozen until `Action::Pause` or `Action::Start` resumes the previous state.
+    Paused,
+    /// Frozen and fading to black after `Settings::sleep_ticks` ticks without input, until any input resumes the
+    /// previous state.
+    Sleep,
+    /// Filling the board with gray from the bottom up, for `Settings::sweep_ticks` ticks before the game over.
+    Sweep {
+        cause: Cause,
+        ticks: u32,
+    },
+    GameOver(Cause),
+    /// The goal of the `GameMode` was reached.
+    Finished(Outcome),
+    /// Entering initials for the high-score table, before the game over or finished state.
+    /// `Action::Left` and `Action::Right` cycle the letter, `Action::RotateCw` accepts it and `Action::RotateCcw` goes back.
+    NameEntry(NameEntry),
+}
+
+/// What the renderer should draw on top of the playfield.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Overlay {
+    #[default]
+    None,
+    /// A big digit with the remaining steps of the countdown.
+    Countdown(u8),
+    /// The number of bottom rows filled with gray by the game-over sweep.
+    Sweep(usize),
+}
+
+/// What happened during a single tick of the game.
+/// # Attributes
+/// - `locked` - The outcome of locking the active piece, if it locked this tick
+/// - `points` - The number of points awarded this tick
+/// - `state` - The state of the game after the tick
+/// - `dim` - Whether the renderer should dim the playfield, e.g. while paused
+/// - `power` - How much power the panel is allowed to draw after the ticks without input
+/// - `finished` - The result of the game, if it reached its goal this tick
+/// - `sent` - The number of garbage rows to send to the opponent, after cancelling incoming garbage
+/// - `overlay` - What to draw on top of the playfield
+/// - `events` - The events of the tick, in the order they happened
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TickOutput {
+    pub locked: Option<ClearEvent>,
+    pub points: u32,
+    pub state: GameState,
+    pub dim: bool,
+    pub power: Power,
+    pub finished: Option<Outcome>,
+    pub sent: u32,
+    pub overlay: Overlay,
+    pub events: GameEvents,
+}
+
+impl TickOutput {
+    /// Pass the events of the tick to a sink, in the order they happened.
+    /// # Arguments
+    /// - `sink` - A muteable reference to the `EventSink`
+    pub fn dispatch<S: EventSink>(&self, sink: &mut S) {
+        for event in self.events.iter() {
+            sink.on_event(event);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Game<B = Board<Cell>> {
+    /// A game in progress.
+    /// # Attributes
+    /// - `board` - The locked cells of the playfield, stored in any `BoardOps` implementation
+    /// - `piece` - The piece controlled by the player
+    /// - `queue` - The upcoming pieces
+    /// - `hold` - The hold slot
+    /// - `score` - The points, lines and level
+    /// - `gravity` - The gravity and lock delay timers
+    /// - `garbage` - The incoming garbage from the opponent
+    /// - `gap` - The column of the gap of the incoming garbage rows
+    /// - `cheese` - The rows of garbage the cheese race started with, left to clear
+    /// - `settings` - The tunables the game was created with
+    /// - `rng` - The random number generator shared by all random decisions, holding the seed of the game
+    /// - `state` - The top-level state of the game
+    /// - `high_scores` - The best marathon scores, kept when restarting
+    /// - `sprint_times` - The best sprint times, kept when restarting
+    /// - `resume` - The state to return to when unpausing, or after entering a name
+    /// - `countdown_timer` - The number of ticks spent in the current countdown step
+    /// - `shift` - The auto-repeat of the held direction
+    /// - `history` - The recent actions of the player, for the input display
+    /// - `log` - The recent happenings of the game, for the `log` of the console
+    /// - `clock` - The time spent playing, excluding pauses and countdowns
+    /// - `frames` - The number of ticks in any state, driving the animated effects, wrapping after 2^32 ticks
+    /// - `events` - The events of the current tick
+    /// - `idle` - The number of ticks on the menu without input
+    /// - `attract` - The player of the attract mode, while the game demos itself
+    /// - `inactivity` - The ticks without input, dimming the panel and putting the game to sleep
+    /// - `sleep_resume` - The state to return to when waking up
+    /// - `menu` - The splash and the menu, shown in the `GameState::Menu` state
+    /// - `raw_input` - The input of the last tick of `.apply_raw()`, to tell presses from held actions
+    /// - `layout` - Where the playfield and the HUD are drawn on the panel
+    /// - `finesse` - The inputs of the active piece and the placements that took more inputs than needed
+    /// - `lifetime` - The totals of every game played, kept when restarting
+    /// - `recorder` - The recent inputs of the games in progress and the last frozen replay, kept when restarting,
+    ///   recording nothing until enabled
+    /// - `reset_chord` - The chord abandoning the game for the menu, kept when restarting
+    board: B,
+    piece: ActivePiece,
+    queue: PieceQueue,
+    hold: HoldSlot,
+    score: Score,
+    gravity: GravityEngine,
+    garbage: GarbageQueue,
+    gap: GarbageGap,
+    cheese: CheeseRows,
+    settings: Settings,
+    rng: GameRng,
+    state: GameState,
+    high_scores: HighScores,
+    sprint_times: HighScores,
+    resume: GameState,
+    countdown_timer: u32,
+    shift: HorizontalRepeat,
+    history: InputHistory,
+    log: GameLog<LOG_ENTRIES>,
+    clock: GameClock,
+    frames: u32,
+    events: GameEvents,
+    idle: u32,
+    attract: Option<Autopilot>,
+    inactivity: Inactivity,
+    sleep_resume: GameState,
+    menu: MenuState,
+    raw_input: InputState,
+    layout: Layout,
+    finesse: Finesse,
+    lifetime: LifetimeStats,
+    recorder: ReplayRecorder,
+    reset_chord: Chord,
+}
+
+/// Draw a number centered in a region, e.g. the cleared lines in the score strip of the layout.
+/// # Arguments
+/// - `frame` - A muteable reference to the `FrameBuffer` to draw into
+/// - `value` - The number to draw, capped at the largest number whose digits fit the width of the region
+/// - `region` - The region to draw in, at least `GLYPH_ROWS` high
+/// - `color` - The color of the digits
+fn _draw_number<const N: usize>(
+    frame: &mut FrameBuffer<N>,
+    value: u32,
+    region: Region,
+    color: Rgb,
+) {
+    let width = region.bottom_right.col + 1 - region.top_left.col;
+    let fits = ((width + 1) / (GLYPH_COLS + 1)).clamp(1, MAX_DIGITS);
+    let largest = 10u32
+        .checked_pow(fits as u32)
+        .map_or(u32::MAX, |power| power - 1);
+    let mut digits = [0; MAX_DIGITS];
+    let count = to_digits(value.min(largest), &mut digits);
+    let drawn = count * (GLYPH_COLS + 1) - 1;
+    let left = region.top_left.col + width.saturating_sub(drawn) / 2;
+    for (index, digit) in digits[MAX_DIGITS - count..].iter().enumerate() {
+        let origin = Coordinate::from_array([region.top_left.row, left + index * (GLYPH_COLS + 1)]);
+        draw_digit(frame, origin, *digit, color);
+    }
+}
+
+impl Game {
+    /// Create a new game on a heap-allocated board of `Settings::rows` by `Settings::cols`, and spawn its first piece.
+    /// # Arguments
+    /// - `settings` - The `Settings` to play with
+    /// - `seed` - The seed for the random number generator, the same seed deals the same pieces
+    /// # Returns
+    /// - `Game` - The game in the `GameState::Menu` state, waiting for `Action::Start`
+    pub fn new(settings: Settings, seed: u64) -> Self {
+        let board = Board::new(
+            Coordinate::from_array([settings.rows, settings.cols]),
+            Cell::Empty,
+        );
+        Game::with_board(board, settings, seed)
+    }
+
+    /// Re-simulate a recorded game from its seed and inputs.
+    /// # Arguments
+    /// - `replay` - A reference to the `Replay` of the game
+    /// # Returns
+    /// - `ReplayResult` - The state of the game after the last recorded tick
+    pub fn play_replay(replay: &Replay) -> ReplayResult {
+        let mut game = Game::new(*replay.get_settings(), replay.get_seed());
+        for input in replay.inputs() {
+            game.tick(&input);
+        }
+        ReplayResult {
+            ticks: replay.get_ticks(),
+            board_hash: game.board_hash(),
+            points: game.score.get_points(),
+            lines: game.score.get_lines(),
+        }
+    }
+
+    /// Re-simulate a recorded game up to a tick, starting from its last keyframe before that tick.
+    ///
+    /// The keyframes do not keep the high scores and the menu, so the ticks after a keyframe only match the recorded
+    /// game until it returns to the menu or enters a name after another game of the same replay.
+    /// # Arguments
+    /// - `replay` - A reference to the `Replay` of the game
+    /// - `tick` - The number of recorded ticks to play, at most `Replay::get_ticks()`
+    /// # Returns
+    /// - `Result<Game, TetrisError>` - The game after the tick, or an error if the keyframe does not restore
+    pub fn seek_replay(replay: &Replay, tick: u32) -> Result<Game, TetrisError> {
+        let keyframe = replay
+            .get_keyframes()
+            .iter()
+            .rev()
+            .find(|keyframe| keyframe.get_ticks() <= tick);
+        let (mut game, start) = match keyframe {
+            Some(keyframe) => (
+                Game::restore_state(&keyframe.to_save_state()?, None)?,
+                keyframe.get_ticks(),
+            ),
+            None => (Game::new(*replay.get_settings(), replay.get_seed()), 0),
+        };
+        for input in replay
+            .inputs()
+            .skip(start as usize)
+            .take((tick - start) as usize)
+        {
+            game.tick(&input);
+        }
+        Ok(game)
+    }
+
+    /// Restore a game in progress from a save state on a heap-allocated board of the saved size.
+    /// # Arguments
+    /// - `buf` - The save state written by `.save_state_into()`
+    /// - `settings_override` - The `Settings` to continue with instead of the saved ones, of the same board size
+    /// # Returns
+    /// - `Result<Game, TetrisError>` - The game, whose ticks continue exactly like the saved game would have, or a
+    ///   `SaveError` if the bytes are no valid save state of this version
+    pub fn restore_state(
+        buf: &[u8],
+        settings_override: Option<Settings>,
+    ) -> Result<Game, TetrisError> {
+        let board = Board::new(save_state_shape(buf)?, Cell::Empty);
+        Game::restore_state_on(board, buf, settings_override)
+    }
+}
+
+impl<B: BoardOps<Cell> + Clone> Game<B> {
+    /// Create a new game on a given board and spawn its first piece.
+    /// # Arguments
+    /// - `board` - The board to play on, emptied first, its shape overrides `Settings::rows` and `Settings::cols`
+    /// - `settings` - The `Settings` to play with
+    /// - `seed` - The seed for the random number generator, the same seed deals the same pieces
+    /// # Returns
+    /// - `Game` - The game in the `GameState::Menu` state, waiting for `Action::Start`
+    pub fn with_board(mut board: B, mut settings: Settings, seed: u64) -> Self {
+        preload_masks();
+        board.clear();
+        settings.rows = board.get_shape().row;
+        settings.cols = board.get_shape().col;
+        let mut rng = GameRng::new(seed);
+        let mut queue = PieceQueue::new(&mut rng);
+        let shape = queue.pop(&mut rng);
+        let mut game = Game {
+            board,
+            piece: ActivePiece::new(Tetromino::from(shape), Coordinate::from_array([0, 0])),
+            queue,
+            hold: HoldSlot::default(),
+            score: Score::new(settings.start_level, settings.mode == GameMode::Marathon),
+            gravity: GravityEngine::new(settings.lock_delay, settings.max_lock_resets),
+            garbage: GarbageQueue::new(),
+            gap: GarbageGap::new(),
+            cheese: CheeseRows::new(),
+            settings,
+            rng,
+            state: GameState::Menu,
+            high_scores: HighScores::default(),
+            sprint_times: HighScores::new(Ranking::Time),
+            resume: GameState::Menu,
+            countdown_timer: 0,
+            shift: HorizontalRepeat::new(settings.das, settings.arr),
+            history: InputHistory::new(),
+            log: GameLog::new(),
+            clock: GameClock::new(),
+            frames: 0,
+            events: GameEvents::new(),
+            idle: 0,
+            attract: None,
+            inactivity: Inactivity::new(),
+            sleep_resume: GameState::Menu,
+            menu: MenuState::new(settings),
+            raw_input: InputState::default(),
+            layout: Layout::top_left(
+                Coordinate::from_array([settings.rows, settings.cols]),
+                settings.previews,
+            ),
+            finesse: Finesse::new(),
+            lifetime: LifetimeStats::new(),
+            recorder: ReplayRecorder::default(),
+            reset_chord: Chord::new(&RESET_CHORD, RESET_TICKS),
+        };
+        game.menu.set_randomizer_stats(*game.queue.get_stats());
+        game._fill_cheese();
+        game._spawn(shape);
+        game
+    }
+
+    /// Start over with the same settings and seed, back in the `GameState::Menu` state without the splash.
+    pub fn restart(&mut self) {
+        let (high_scores, sprint_times) = (self.high_scores, self.sprint_times);
+        // A game abandoned halfway does not count towards the lifetime totals.
+        let mut lifetime = self.lifetime;
+        lifetime.discard_game();
+        // The log spans the games, so its ticks keep counting up, and the layout stays fitted to the panel.
+        let (log, frames, layout) = (core::mem::take(&mut self.log), self.frames, self.layout);
+        // So do the statistics of the randomizer, counting the first piece of the new game on top.
+        let mut stats = *self.queue.get_stats();
+        let (recorder, reset_chord) = (core::mem::take(&mut self.recorder), self.reset_chord);
+        *self = Game::with_board(self.board.clone(), self.settings, self.seed());
+        self.set_high_scores(high_scores);
+        self.sprint_times = sprint_times;
+        self.set_lifetime_stats(lifetime);
+        (self.recorder, self.reset_chord) = (recorder, reset_chord);
+        (self.log, self.frames, self.layout) = (log, frames, layout);
+        stats.record(self.piece.get_tetromino().shape);
+        self.queue.set_stats(stats);
+        self.menu.set_randomizer_stats(stats);
+        self.menu.skip_splash();
+    }
+
+    /// Serialize the game in progress into a save state, e.g. to resume it after a power cycle.
+    ///
+    /// The layout is fixed for a board size, see the `savestate` module, and fits a single flash record for the
+    /// standard board. The high scores are not part of it, those have their own record.
+    /// # Arguments
+    /// - `buf` - The buffer to write into, at least `save_state_bytes()` of the board long
+    /// # Returns
+    /// - `Result<usize, TetrisError>` - The number of bytes written, or a `SaveError` if the buffer is too small or
+    ///   the game demos itself in the attract mode
+    pub fn save_state_into(&self, buf: &mut [u8]) -> Result<usize, TetrisError> {
+        if self.attract.is_some() {
+            return Err(SaveError::Attract.into());
+        }
+        let shape = self.board.get_shape();
+        let len = save_state_bytes(shape.row, shape.col);
+        let mut out = Writer::new(buf.get_mut(..len).ok_or(SaveError::BufferTooSmall(len))?);
+        out.bytes(&MAGIC);
+        out.u8(SAVE_VERSION);
+        out.index(shape.row);
+        out.index(shape.col);
+        out.bytes(&self.settings.to_bytes());
+        let start = out.get_len();
+        self.rng.save(&mut out);
+        for state in [self.state, self.resume, self.sleep_resume] {
+            state.save(&mut out);
+        }
+        self.piece.save(&mut out);
+        self.queue.save(&mut out);
+        self.hold.save(&mut out);
+        self.score.save(&mut out);
+        self.gravity.save(&mut out);
+        self.garbage.save(&mut out);
+        self.gap.save(&mut out);
+        self.inactivity.save(&mut out);
+        self.shift.save(&mut out);
+        out.u32(self.countdown_timer);
+        self.clock.save(&mut out);
+        out.u32(self.frames);
+        out.u32(self.idle);
+        self.cheese.save(&mut out);
+        out.pad_to(start, STATE_BYTES);
+        for (_, cell) in self.board.cells() {
+            out.u8(cell.to_u8());
+        }
+        let crc = _crc32(out.get_written());
+        out.u32(crc);
+        Ok(out.get_len())
+    }
+
+    /// Restore a game in progress from a save state on a given board.
+    /// # Arguments
+    /// - `board` - The board to play on, of the size of the saved board
+    /// - `buf` - The save state written by `.save_state_into()`
+    /// - `settings_override` - The `Settings` to continue with instead of the saved ones, of the same board size
+    /// # Returns
+    /// - `Result<Game, TetrisError>` - The game, whose ticks continue exactly like the saved game would have, or a
+    ///   `SaveError` if the bytes are no valid save state of this version and board size
+    pub fn restore_state_on(
+        board: B,
+        buf: &[u8],
+        settings_override: Option<Settings>,
+    ) -> Result<Self, TetrisError> {
+        let Coordinate {
+            row: rows,
+            col: cols,
+        } = board.get_shape();
+        let mut input = open_save_state(buf, board.get_shape())?;
+        let settings = match settings_override {
+            Some(settings) => {
+                input.bytes::<SETTINGS_BYTES>()?;
+                settings
+            }
+            None => Settings::from_bytes(&input.bytes()?)?,
+        };
+        if [settings.rows, settings.cols] != [rows, cols] {
+            return Err(SaveError::BoardSize { rows, cols }.into());
+        }
+        let mut game = Game::with_board(board, settings, 0);
+        let start = input.get_offset();
+        game.rng.restore(&mut input)?;
+        for state in [&mut game.state, &mut game.resume, &mut game.sleep_resume] {
+            state.restore(&mut input)?;
+        }
+        game.piece.restore(&mut input)?;
+        game.queue.restore(&mut input)?;
+        game.hold.restore(&mut input)?;
+        game.score.restore(&mut input)?;
+        game.gravity.restore(&mut input)?;
+        game.garbage.restore(&mut input)?;
+        game.gap.restore(&mut input)?;
+        game.inactivity.restore(&mut input)?;
+        game.shift.restore(&mut input)?;
+        game.countdown_timer = input.u32()?;
+        game.clock.restore(&mut input)?;
+        game.frames = input.u32()?;
+        game.idle = input.u32()?;
+        game.cheese.restore(&mut input)?;
+        input.skip_to(start, STATE_BYTES)?;
+        for row in 0..rows {
+            for col in 0..cols {
+                let cell = cell_from_u8(input.u8()?)?;
+                game.board.set(Coordinate { row, col }, cell);
+            }
+        }
+        if game.validate().is_err() {
+            return Err(SaveError::Invalid.into());
+        }
+        if game.state == GameState::Menu {
+            game.menu.skip_splash();
+        }
+        Ok(game)
+    }
+
+    /// Check the invariants of the game, see the `invariants` module. Runs at the end of every tick in debug builds.
+    ///
+    /// The board is valid, the active piece is on the board and does not overlap the stack while it is in play, the
+    /// queue is stocked, the hold slot is only locked while a piece is in play and the score is consistent.
+    /// # Returns
+    /// - `Result<(), InvariantViolation>` - The first invariant that does not hold, if any
+    pub fn validate(&self) -> Result<(), InvariantViolation> {
+        self.board.validate()?;
+        let corner = self.piece.get_coord() + self.piece.get_tetromino().get_shape();
+        let shape = self.board.get_shape();
+        if corner.row > shape.row || corner.col > shape.col {
+            return Err(InvariantViolation::PieceOutOfBounds);
+        }
+        // The locked piece is part of the stack while its rows clear, and overlaps it after a block out.
+        let phase = self._phase();
+        let in_play = matches!(
+            phase,
+            GameState::Menu | GameState::Countdown(_) | GameState::Playing
+        );
+        if in_play && !self.piece.fits(&self.board) {
+            return Err(InvariantViolation::PieceOverlapsStack);
+        }
+        if !self.queue.is_stocked() {
+            return Err(InvariantViolation::QueueNotStocked);
+        }
+        let waiting = matches!(phase, GameState::Menu | GameState::Countdown(_));
+        if !self.hold.can_hold() && (waiting || !self.settings.hold) {
+            return Err(InvariantViolation::HoldLocked);
+        }
+        self.score.validate()
+    }
+
+    /// Get the seed of the game, to show it or share it with an opponent racing the same pieces.
+    pub fn seed(&self) -> u64 {
+        self.rng.get_seed()
+    }
+
+    /// Get a reference to the locked cells of the playfield.
+    pub fn get_board(&self) -> &B {
+        &self.board
+    }
+
+    /// Get a reference to the piece controlled by the player.
+    pub fn get_piece(&self) -> &ActivePiece {
+        &self.piece
+    }
+
+    /// Get a reference to the upcoming pieces.
+    pub fn get_queue(&self) -> &PieceQueue {
+        &self.queue
+    }
+
+    /// Get a reference to the hold slot.
+    pub fn get_hold(&self) -> &HoldSlot {
+        &self.hold
+    }
+
+    /// Get a reference to the score.
+    pub fn get_score(&self) -> &Score {
+        &self.score
+    }
+
+    /// Get a reference to the settings.
+    pub fn get_settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    /// Get the palette to draw with.
+    /// # Returns
+    /// - `&'static Palette` - The palette of the color scheme, at the current level for `ColorScheme::Levels`
+    pub fn get_palette(&self) -> &'static Palette {
+        match self.settings.colors {
+            ColorScheme::Guideline => &GUIDELINE,
+            ColorScheme::Levels => Palette::for_level(self.score.get_level()),
+            ColorScheme::Accessible => &ACCESSIBLE,
+        }
+    }
+
+    /// Get a reference to the high-score table.
+    pub fn get_high_scores(&self) -> &HighScores {
+        &self.high_scores
+    }
+
+    /// Replace the high-score table, e.g. with one loaded from flash.
+    /// # Arguments
+    /// - `high_scores` - The `HighScores` to keep
+    pub fn set_high_scores(&mut self, high_scores: HighScores) {
+        self.high_scores = high_scores;
+        self.menu.set_high_scores(high_scores);
+    }
+
+    /// Get a reference to the table of the best sprint times.
+    pub fn get_sprint_times(&self) -> &HighScores {
+        &self.sprint_times
+    }
+
+    /// Replace the table of the best sprint times, e.g. with one loaded from flash.
+    /// # Arguments
+    /// - `sprint_times` - The `HighScores` to keep, ranked by `Ranking::Time`
+    pub fn set_sprint_times(&mut self, sprint_times: HighScores) {
+        self.sprint_times = sprint_times;
+    }
+
+    /// Get a reference to the totals of every game played.
+    pub fn get_lifetime_stats(&self) -> &LifetimeStats {
+        &self.lifetime
+    }
+
+    /// Replace the totals of every game played, e.g. with the totals loaded from flash.
+    /// # Arguments
+    /// - `lifetime` - The `LifetimeStats` to add the next games to
+    pub fn set_lifetime_stats(&mut self, lifetime: LifetimeStats) {
+        self.lifetime = lifetime;
+        self.menu.set_lifetime_stats(lifetime);
+    }
+
+    /// Get a reference to the recent inputs and the last frozen replay, e.g. to dump it for a bug report.
+    pub fn get_recorder(&self) -> &ReplayRecorder {
+        &self.recorder
+    }
+
+    /// Enable or disable recording the recent inputs, see `ReplayRecorder`. Enabling allocates the buffers of the
+    /// recorder, so the ticks do not, and disabling drops them with the frozen replay.
+    /// # Arguments
+    /// - `recording` - Whether (`true`) or not (`false`) to record
+    pub fn set_recording(&mut self, recording: bool) {
+        let shape = self.board.get_shape();
+        match (recording, self.recorder.is_recording()) {
+            (true, false) => self.recorder = ReplayRecorder::new(shape.row, shape.col),
+            (false, true) => self.recorder = ReplayRecorder::default(),
+            _ => {}
+        }
+    }
+
+    /// Replace the chord abandoning the game for the menu, `RESET_CHORD` held for `RESET_TICKS` by default, see
+    /// `GameEvent::Aborted`.
+    /// # Arguments
+    /// - `chord` - The `Chord`, or `Chord::default()` to never abandon a game
+    pub fn set_reset_chord(&mut self, chord: Chord) {
+        self.reset_chord = chord;
+    }
+
+    /// Replace the last frozen replay, e.g. with the one kept in flash before a power cycle.
+    /// # Arguments
+    /// - `replay` - The `Replay`, starting from a keyframe at tick 0
+    pub fn set_frozen_replay(&mut self, replay: Replay) {
+        self.recorder.set_frozen(replay);
+    }
+
+    /// Get a reference to the splash and the menu.
+    pub fn get_menu(&self) -> &MenuState {
+        &self.menu
+    }
+
+    /// Get a reference to the finesse of the game, e.g. for the number of faults.
+    pub fn get_finesse(&self) -> &Finesse {
+        &self.finesse
+    }
+
+    /// Get a reference to where the playfield and the HUD are drawn.
+    pub fn get_layout(&self) -> &Layout {
+        &self.layout
+    }
+
+    /// Replace where the playfield and the HUD are drawn, e.g. with `Layout::auto()` for a larger panel. The layout
+    /// is kept when restarting.
+    /// # Arguments
+    /// - `layout` - The `Layout`, fitted to the shape of the board
+    pub fn set_layout(&mut self, layout: Layout) {
+        self.layout = layout;
+    }
+
+    /// Get a reference to the rows of garbage the cheese race started with that are still on the board.
+    pub fn get_cheese(&self) -> &CheeseRows {
+        &self.cheese
+    }
+
+    /// Get a reference to the recent actions of the player.
+    pub fn get_history(&self) -> &InputHistory {
+        &self.history
+    }
+
+    /// Get a reference to the log of the recent ticks.
+    pub fn get_log(&self) -> &GameLog<LOG_ENTRIES> {
+        &self.log
+    }
+
+    /// Get the number of ticks in any state, the timestamps of the log.
+    /// # Returns
+    /// - `u32` - The number of ticks, wrapping after 2^32 ticks
+    pub fn get_frames(&self) -> u32 {
+        self.frames
+    }
+
+    /// Get a reference to the incoming garbage.
+    pub fn get_garbage(&self) -> &GarbageQueue {
+        &self.garbage
+    }
+
+    /// Queue garbage rows sent by the opponent, applied when a piece locks without clearing.
+    /// # Arguments
+    /// - `lines` - The number of incoming rows
+    pub fn receive_garbage(&mut self, lines: u32) {
+        self.garbage.receive(lines);
+    }
+
+    /// Replace the active piece with a new piece spawned at the top, e.g. to set up a situation while debugging.
+    /// # Arguments
+    /// - `shape` - The `TetrominoShape` of the new piece, ending the game if it does not fit
+    pub fn spawn(&mut self, shape: TetrominoShape) {
+        self._spawn(shape);
+    }
+
+    /// Jump to a level, changing the gravity and the points per clear, e.g. to test the speed of a level.
+    /// # Arguments
+    /// - `level` - The level to continue at, see `Score::set_level()`
+    pub fn set_level(&mut self, level: u8) {
+        self.score.set_level(level);
+    }
+
+    /// Replace the locked cells, to start a test in the middle of a game, see `testing::GameFixture`.
+    #[cfg(test)]
+    pub(crate) fn set_board(&mut self, board: B) {
+        self.board = board;
+    }
+
+    /// Replace the active piece without spawning it, starting its gravity and finesse like a spawned piece.
+    #[cfg(test)]
+    pub(crate) fn set_piece(&mut self, piece: ActivePiece) {
+        self.piece = piece;
+        self.gravity.reset();
+        self.finesse.start_piece();
+    }
+
+    /// Replace the upcoming pieces, see `PieceQueue::set_next()`.
+    #[cfg(test)]
+    pub(crate) fn set_next(&mut self, shapes: &[TetrominoShape]) {
+        self.queue.set_next(shapes, &mut self.rng);
+    }
+
+    /// Replace the hold slot.
+    #[cfg(test)]
+    pub(crate) fn set_hold(&mut self, hold: HoldSlot) {
+        self.hold = hold;
+    }
+
+    /// Overwrite the totals of the score, see `Score::set_totals()`.
+    #[cfg(test)]
+    pub(crate) fn set_totals(&mut self, points: u32, lines: u32) {
+        self.score.set_totals(points, lines);
+    }
+
+    /// Replace the state, returning to `GameState::Playing` from a pause, sleep or name entry.
+    #[cfg(test)]
+    pub(crate) fn set_state(&mut self, state: GameState) {
+        self.state = state;
+        self.resume = GameState::Playing;
+        self.sleep_resume = GameState::Playing;
+    }
+
+    /// Replace the debug overlays of this game and of the games started from the menu.
+    /// # Arguments
+    /// - `debug` - The `DebugOverlay` to draw
+    pub fn set_debug_overlay(&mut self, debug: DebugOverlay) {
+        self.settings.debug = debug;
+        self.menu.set_debug_overlay(debug);
+    }
+
+    /// Replace the colors of the stack of this game and of the games started from the menu, recoloring the cells
+    /// locked so far on the next frame.
+    /// # Arguments
+    /// - `stack_colors` - The `StackColorMode` to draw the locked cells with
+    pub fn set_stack_colors(&mut self, stack_colors: StackColorMode) {
+        self.settings.stack_colors = stack_colors;
+        self.menu.set_stack_colors(stack_colors);
+    }
+
+    /// Get how far the delayed auto-shift of a direction is charged.
+    /// # Arguments
+    /// - `direction` - `Action::Left` or `Action::Right`
+    /// # Returns
+    /// - `u8` - 0 while the direction is not held, up to 255 once the piece shifts repeatedly
+    pub fn das_progress(&self, direction: Action) -> u8 {
+        self.shift.get_charge(direction)
+    }
+
+    /// Get how far the lock delay of the active piece has passed.
+    /// # Returns
+    /// - `u8` - 0 while the piece is falling, up to 255 when it locks
+    pub fn lock_delay_progress(&self) -> u8 {
+        self.gravity.get_lock_progress()
+    }
+
+    /// Get the number of times moving the grounded active piece restarted its lock delay.
+    /// # Returns
+    /// - `u8` - The restarts used, at most `Settings::max_lock_resets`
+    pub fn resets_used(&self) -> u8 {
+        self.gravity.get_resets()
+    }
+
+    /// Get the top-level state of the game.
+    pub fn get_state(&self) -> GameState {
+        self.state
+    }
+
+    /// Check if the game is demoing itself in the attract mode.
+    pub fn is_attract(&self) -> bool {
+        self.attract.is_some()
+    }
+
+    /// Get a reference to the ticks without input and the brightness they ramp the rendered frame to.
+    pub fn get_inactivity(&self) -> &Inactivity {
+        &self.inactivity
+    }
+
+    /// Check if the game is over.
+    pub fn is_game_over(&self) -> bool {
+        matches!(self.state, GameState::GameOver(_))
+    }
+
+    /// Get the number of ticks spent playing, which does not advance while paused or counting down.
+    pub fn get_ticks(&self) -> u64 {
+        self.clock.get_ticks_elapsed()
+    }
+
+    /// Get a reference to the time spent playing.
+    pub fn get_clock(&self) -> &GameClock {
+        &self.clock
+    }
+
+    /// Hash the locked cells of the playfield, to compare games cheaply.
+    /// # Returns
+    /// - `u64` - The 64 bit FNV-1a hash of the cells in row major order
+    pub fn board_hash(&self) -> u64 {
+        self.board
+            .cells()
+            .fold(0xCBF2_9CE4_8422_2325, |hash, (_, cell)| {
+                (hash ^ cell.to_u8() as u64).wrapping_mul(0x0100_0000_01B3)
+            })
+    }
+
+    /// Advance the game by a single tick.
+    ///
+    /// `Action::Start` starts a game from the menu or after a game over, `Action::Pause` toggles the pause.
+    /// While paused, nothing but unpausing has an effect and the inputs are discarded.
+    /// After `ATTRACT_TICKS` ticks on the menu without input, the attract mode plays instead of the player until
+    /// any action is held, which returns to the menu.
+    /// After `Settings::sleep_ticks` ticks without input in any state, the game sleeps until any action is held,
+    /// which resumes the previous state without acting on the input.
+    /// Rotating, hard dropping and holding act on presses, shifting and soft dropping act while held.
+    /// Holding the reset chord abandons the game for the menu, holding the `recorder::CHORD` freezes the replay window,
+    /// and the actions of either pressed while all of them are held act on nothing.
+    /// # Arguments
+    /// - `input` - A reference to the `InputState` of this tick
+    /// # Returns
+    /// - `TickOutput` - What happened during the tick
+    pub fn tick(&mut self, input: &InputState) -> TickOutput {
+        self.events.clear();
+        self.frames = self.frames.wrapping_add(1);
+        self._record_input(input);
+        let reset = self.reset_chord.update(input);
+        let input = &mask_chord(&self.reset_chord.mask(input));
+        let (power, input) = self._power(input);
+        let input = &self._attract_input(&input);
+        let mut output = TickOutput::default();
+        match self.state {
+            // The menu is where the chord returns to, so there is nothing to abandon in it.
+            _ if reset && self.state != GameState::Menu => self._abort(),
+            GameState::Menu => {
+                self.idle = match input.get_held_bits() {
+                    0 => self.idle.saturating_add(1),
+                    _ => 0,
+                };
+                if self.idle >= ATTRACT_TICKS {
+                    self._attract();
+                } else if let MenuOutput::Start(settings) = self.menu.tick(input) {
+                    self._start(settings);
+                }
+            }
+            GameState::GameOver(_) | GameState::Finished(_) => {
+                if input.is_pressed(Action::Start) {
+                    self.restart();
+                    self.state = GameState::Countdown(COUNTDOWN_STEPS);
+                }
+            }
+            GameState::NameEntry(entry) => self._enter_name(entry, input),
+            GameState::Sweep { cause, ticks } => {
+                self.state = match ticks + 1 >= self.settings.sweep_ticks {
+                    true => GameState::GameOver(cause),
+                    false => GameState::Sweep {
+                        cause,
+                        ticks: ticks + 1,
+                    },
+                };
+                if self.is_game_over() {
+                    self._end(self.state);
+                }
+            }
+            GameState::Paused => {
+                if input.is_pressed(Action::Pause) || input.is_pressed(Action::Start) {
+                    self.state = self.resume;
+                }
+            }
+            GameState::Sleep => (),
+            GameState::Countdown(steps) => match input.is_pressed(Action::Pause) {
+                true => self._pause(),
+                false => {
+                    // Ignore the input, but precharge the auto-repeat of a held direction.
+                    self.shift.update(input);
+                    self.countdown_timer += 1;
+                    if self.countdown_timer >= self.settings.countdown_ticks {
+                        self.countdown_timer = 0;
+                        self.state = match steps > 1 {
+                            true => GameState::Countdown(steps - 1),
+                            false => GameState::Playing,
+                        };
+                    }
+                }
+            },
+            GameState::Playing => match input.is_pressed(Action::Pause) {
+                true => self._pause(),
+                false => {
+                    self.clock.tick();
+                    self.history.tick();
+                    self.garbage.tick();
+                    output = self._play(input);
+                    output.finished = self._check_finished();
+                }
+            },
+            GameState::Clearing {
+                rows_mask,
+                frames_left,
+            } => match input.is_pressed(Action::Pause) {
+                true => self._pause(),
+                false => {
+                    // Keep charging the auto-repeat, so a held direction carries over to the next piece.
+                    self.shift.update(input);
+                    self.clock.tick();
+                    self.history.tick();
+                    self.garbage.tick();
+                    self.state = GameState::Clearing {
+                        rows_mask,
+                        frames_left: frames_left - 1,
+                    };
+                    if frames_left == 1 {
+                        self.state = GameState::Playing;
+                        self._clear();
+                        output.finished = self._check_finished();
+                    }
+                }
+            },
+        }
+        // The demo starts over once it ends, without entering a name.
+        if self.attract.is_some()
+            && matches!(
+                self.state,
+                GameState::GameOver(_) | GameState::Finished(_) | GameState::NameEntry(_)
+            )
+        {
+            self.restart();
+            self._attract();
+        }
+        let coord = self.piece.get_coord();
+        let grounded = (self.state == GameState::Playing && self.piece.is_grounded(&self.board))
+            .then_some([coord.row as u8, coord.col as u8]);
+        self.log.observe(
+            self.frames,
+            &self.state,
+            grounded,
+            self.gravity.get_resets(),
+        );
+        output.state = self.state;
+        output.dim = self.state == GameState::Paused;
+        output.power = power;
+        output.overlay = self._overlay();
+        output.events = core::mem::take(&mut self.events);
+        #[cfg(debug_assertions)]
+        if let Err(violation) = self.validate() {
+            panic!(
+                "invariant violated after {} ticks: {}",
+                self.frames, violation
+            );
+        }
+        output
+    }
+
+    /// Advance the game by a single tick with the input decoded from a single byte, e.g. of a fuzzer.
+    ///
+    /// Any sequence of bytes is a valid game, see `InputState::next_raw()` for the encoding. The game never panics on
+    /// any sequence, and its state stays valid, see `.validate()`.
+    /// # Arguments
+    /// - `input_byte` - The byte encoding the actions held this tick
+    /// # Returns
+    /// - `TickOutput` - The outcome of the tick, see `.tick()`
+    pub fn apply_raw(&mut self, input_byte: u8) -> TickOutput {
+        let input = self.raw_input.next_raw(input_byte);
+        let output = self.tick(&input);
+        self.raw_input = input;
+        output
+    }
+
+    /// Get the state the game continues in once it is unpaused or wakes up.
+    fn _phase(&self) -> GameState {
+        let state = match self.state {
+            GameState::Sleep => self.sleep_resume,
+            state => state,
+        };
+        match state {
+            GameState::Paused => self.resume,
+            state => state,
+        }
+    }
+
+    /// Count the ticks without input, falling asleep after `Settings::sleep_ticks` and waking on any input.
+    /// # Arguments
+    /// - `input` - A reference to the `InputState` of the player this tick
+    /// # Returns
+    /// - `(Power, InputState)` - The power after the tick, and the input to play, swallowed when waking up
+    fn _power(&mut self, input: &InputState) -> (Power, InputState) {
+        let active = input.get_held_bits() != 0;
+        let power = self.inactivity.tick(active, &self.settings);
+        match (self.state, power) {
+            (GameState::Sleep, Power::Awake) => {
+                self.state = self.sleep_resume;
+                return (power, InputState::default());
+            }
+            (GameState::Sleep, _) => (),
+            (state, Power::Asleep) => {
+                self.sleep_resume = state;
+                self.state = GameState::Sleep;
+            }
+            _ => (),
+        }
+        (power, *input)
+    }
+
+    /// Replace the input of the player with the input of the attract mode, returning to the menu on any input.
+    /// # Arguments
+    /// - `input` - A reference to the `InputState` of the player this tick
+    /// # Returns
+    /// - `InputState` - The input to play this tick
+    fn _attract_input(&mut self, input: &InputState) -> InputState {
+        let lookahead = Lookahead::of(self);
+        match self.attract {
+            Some(_) if input.get_held_bits() != 0 => {
+                // Swallow the input, so it does not start a game right away.
+                self.restart();
+                InputState::default()
+            }
+            Some(ref mut autopilot) => match self.state {
+                GameState::Playing => autopilot.next_input(&self.board, &self.piece, &lookahead),
+                _ => InputState::default(),
+            },
+            None => *input,
+        }
+    }
+
+    /// Start a game from the menu, starting over first if other settings were chosen.
+    /// # Arguments
+    /// - `settings` - The `Settings` chosen in the menu
+    fn _start(&mut self, settings: Settings) {
+        if settings != self.settings {
+            let (high_scores, sprint_times) = (self.high_scores, self.sprint_times);
+            let lifetime = self.lifetime;
+            let (recorder, reset_chord) = (core::mem::take(&mut self.recorder), self.reset_chord);
+            *self = Game::with_board(self.board.clone(), settings, self.seed());
+            self.set_high_scores(high_scores);
+            self.sprint_times = sprint_times;
+            self.set_lifetime_stats(lifetime);
+            (self.recorder, self.reset_chord) = (recorder, reset_chord);
+        }
+        self._record(Action::Start);
+        self.state = GameState::Countdown(COUNTDOWN_STEPS);
+    }
+
+    /// Start the attract mode, skipping the countdown.
+    fn _attract(&mut self) {
+        self.idle = 0;
+        self.attract = Some(Autopilot::new(self.settings.attract));
+        self.state = GameState::Playing;
+    }
+
+    /// Advance the game by a single tick while playing.
+    fn _play(&mut self, input: &InputState) -> TickOutput {
+        if input.is_pressed(Action::Hold) && self.settings.hold && self.hold.can_hold() {
+            let shape = self.piece.get_tetromino().shape;
+            let next = match self.hold.swap(shape) {
+                Some(held) => held,
+                None => self.queue.pop(&mut self.rng),
+            };
+            self._emit(GameEvent::PieceHeld);
+            self._record(Action::Hold);
+            self._spawn(next);
+            // The piece from the hold slot or the queue blocked out, it must not move or lock anymore.
+            if self.state != GameState::Playing {
+                return TickOutput::default();
+            }
+        }
+        self.finesse.count(input);
+        let mut moved = false;
+        for rotation in [Action::RotateCw, Action::RotateCcw] {
+            if input.is_pressed(rotation) {
+                moved |= self._rotate(rotation);
+            }
+        }
+        moved |= self._shift(input);
+        if moved {
+            self.gravity.on_move(&self.piece, &self.board);
+        }
+        let lock = match input.is_pressed(Action::HardDrop) {
+            true => {
+                self._record(Action::HardDrop);
+                while self.piece.try_drop(&self.board) {}
+                true
+            }
+            false => {
+                if input.is_pressed(Action::SoftDrop) {
+                    self._record(Action::SoftDrop);
+                }
+                let mut ticks = ticks_per_row(self.score.get_level());
+                if input.is_held(Action::SoftDrop) {
+                    ticks = (ticks / self.settings.soft_drop_factor.max(1)).max(1);
+                }
+                self.gravity.step(&mut self.piece, &self.board, ticks)
+            }
+        };
+        match lock {
+            true => self._lock(),
+            false => TickOutput::default(),
+        }
+    }
+
+    /// Rotate the active piece with wall kicks, reporting whether it kicked or failed.
+    /// # Arguments
+    /// - `rotation` - `Action::RotateCw` or `Action::RotateCcw`
+    /// # Returns
+    /// - `bool` - Whether (`true`) or not (`false`) the piece rotated
+    fn _rotate(&mut self, rotation: Action) -> bool {
+        let result = match rotation {
+            Action::RotateCw => self.piece.try_rotate_cw_with_kicks(&self.board),
+            _ => self.piece.try_rotate_ccw_with_kicks(&self.board),
+        };
+        match result {
+            RotationResult::Rotated { .. } => {
+                self._record(rotation);
+                self._emit(GameEvent::Rotated {
+                    kicked: result.is_kicked(),
+                });
+                true
+            }
+            RotationResult::Failed => {
+                self._emit(GameEvent::RotationFailed);
+                false
+            }
+        }
+    }
+
+    /// Shift the active piece in the held direction, with delayed auto-shift (DAS).
+    /// # Arguments
+    /// - `input` - A reference to the `InputState` of this tick
+    /// # Returns
+    /// - `bool` - Whether (`true`) or not (`false`) the piece moved
+    fn _shift(&mut self, input: &InputState) -> bool {
+        let (direction, repeats) = match self.shift.update(input) {
+            Some(shift) => shift,
+            None => return false,
+        };
+        let mut moved = false;
+        for _ in 0..repeats {
+            let shifted = match direction {
+                Action::Left => self.piece.try_shift_left(&self.board),
+                _ => self.piece.try_shift_right(&self.board),
+            };
+            if !shifted {
+                break;
+            }
+            moved = true;
+        }
+        if moved {
+            self._record(direction);
+        }
+        moved
+    }
+
+    /// Draw the playfield, the ghost piece, the active piece and the upcoming pieces, or the menu before a game.
+    ///
+    /// The pieces are layered over the locked board in the frame, the active piece over its ghost, and never written
+    /// into the board, which only holds the locked stack.
+    /// The board, the previews and the held piece are placed by the `Layout`, by default the board at the top-left of
+    /// the panel and the previews in the column next to its border, followed by the held piece, grayed out until it
+    /// can be held again. The board is bordered where the panel has room, the cleared lines are drawn in the score
+    /// strip of the layout, if any, and the rest of the panel is filled with its letterbox color. The incoming garbage
+    /// stacks up in red over the right border, flashing once it is about to be applied. With
+    /// `Settings::input_display`, the recent actions of the player are drawn below the bottom border or the score
+    /// strip, and the gauges of `Settings::debug` around the board.
+    /// While clearing, the completed rows alternate between white and their colors and no piece is drawn.
+    /// The countdown digit and the game-over sweep are drawn instead of the piece.
+    /// The animated effects are drawn on top: a pulsing pause indicator and a sparkling board while
+    /// entering a new high score.
+    /// # Arguments
+    /// - `frame` - A muteable reference to the `FrameBuffer` to draw into
+    pub fn render_into<const N: usize>(&self, frame: &mut FrameBuffer<N>) {
+        match self._shown_state() {
+            GameState::Menu => self.menu.render(frame, self.get_palette()),
+            _ => {
+                self._draw(frame);
+                self._draw_effects(frame);
+            }
+        }
+        self._dim(frame);
+    }
+
+    /// Scale the frame by the brightness of the inactivity, fading it while dimming and falling asleep.
+    fn _dim<const N: usize>(&self, frame: &mut FrameBuffer<N>) {
+        let level = self.inactivity.get_level();
+        if level == u8::MAX {
+            return;
+        }
+        let shape = frame.get_shape();
+        for index in 0..shape.inner_product() {
+            let coord = Coordinate::from_row_major(index, shape).expect(IN_FRAME);
+            let color = frame.get_pixel(coord).expect(IN_FRAME);
+            frame.set_pixel(coord, color.scaled(level));
+        }
+    }
+
+    /// Get the state to draw, the state the game fell asleep in while sleeping.
+    fn _shown_state(&self) -> GameState {
+        match self.state {
+            GameState::Sleep => self.sleep_resume,
+            state => state,
+        }
+    }
+
+    /// Draw the state of the game, see `.render_into()`.
+    fn _draw<const N: usize>(&self, frame: &mut FrameBuffer<N>) {
+        let layout = &self.layout;
+        let config = layout.get_config();
+        let panel = Region {
+            top_left: Coordinate::from_array([0, 0]),
+            bottom_right: frame.get_shape() - [1, 1],
+        };
+        for coord in panel.coords() {
+            frame.set_pixel(coord, layout.letterbox);
+        }
+        let hud = [Some(layout.hold), Some(layout.queue), layout.score];
+        for coord in hud.iter().flatten().flat_map(|region| region.coords()) {
+            frame.set_pixel(coord, Rgb::BLACK);
+        }
+        let palette = self.get_palette();
+        let (rows_mask, elapsed) = match self._clearing() {
+            Some((rows_mask, frames_left)) => (rows_mask, self.settings.clear_frames - frames_left),
+            None => (0, 0),
+        };
+        for (coord, cell) in self.board.cells() {
+            let (color, pattern) = match cell {
+                Cell::Empty => {
+                    let checkered = self.settings.grid && (coord.row + coord.col) % 2 == 1;
+                    let color = match checkered {
+                        true => palette.grid,
+                        false => palette.background,
+                    };
+                    frame.draw_cell(coord, &config, color, CellPattern::Solid);
+                    continue;
+                }
+                Cell::Filled { shape, lum } => (
+                    self._stack_color(palette, shape, coord.row)
+                        .scaled(lum)
+                        .scaled(self._fog(coord.row)),
+                    palette.get_pattern(shape),
+                ),
+                Cell::Garbage => (palette.garbage, CellPattern::Solid),
+            };
+            match rows_mask & (1 << coord.row) != 0 {
+                true => frame.draw_cell(
+                    coord,
+                    &config,
+                    flash(FLASH_COLOR, color, 2 * FLASH_FRAMES as u16, elapsed as u32),
+                    CellPattern::Solid,
+                ),
+                false => frame.draw_cell(coord, &config, color, pattern),
+            }
+        }
+        self._draw_border(frame);
+        let pending = self.garbage.pending_lines();
+        if pending > 0 {
+            let color = match self.garbage.is_imminent() {
+                true => flash(
+                    GARBAGE_COLOR,
+                    palette.border,
+                    GARBAGE_FLASH_TICKS,
+                    self.frames,
+                ),
+                false => GARBAGE_COLOR,
+            };
+            frame.draw_garbage_meter(layout.garbage, pending, color);
+        }
+        let queue = layout.queue;
+        let fits = (queue.bottom_right.row + 1 - queue.top_left.row) / PREVIEW_ROWS;
+        let previews = self.settings.previews.min(fits.max(1));
+        for (index, shape) in self.queue.peek(previews).enumerate() {
+            let preview = Tetromino::from(*shape);
+            let coord = queue.top_left + [index * PREVIEW_ROWS, 0];
+            frame.set_patterned_mask(
+                preview.get_mask(),
+                coord,
+                palette.get_color(preview.shape),
+                palette.get_pattern(preview.shape),
+            );
+        }
+        if let Some(shape) = self.hold.get_shape() {
+            frame.draw_piece_thumbnail(
+                shape,
+                layout.hold.top_left,
+                palette.get_color(shape),
+                !self.hold.can_hold(),
+            );
+        }
+        if let Some(score) = layout.score {
+            let color = palette.get_color(TetrominoShape::I);
+            _draw_number(frame, self.score.get_lines(), score, color);
+        }
+        if self.settings.input_display {
+            draw_input_history(frame, &self.history, layout.get_input_origin(), Rgb::WHITE);
+        }
+        match self._overlay() {
+            Overlay::Countdown(steps) => {
+                let glyph = Coordinate::from_array([GLYPH_ROWS, GLYPH_COLS]);
+                draw_digit(frame, layout.centered(glyph), steps, FLASH_COLOR);
+                return;
+            }
+            Overlay::Sweep(rows) => {
+                for r in self.settings.rows - rows.min(self.settings.rows)..self.settings.rows {
+                    for c in 0..self.settings.cols {
+                        let coord = Coordinate { row: r, col: c };
+                        frame.draw_cell(coord, &config, SWEEP_COLOR, CellPattern::Solid);
+                    }
+                }
+                return;
+            }
+            Overlay::None => (),
+        }
+        if self._clearing().is_some() {
+            return;
+        }
+        self._draw_gauges(frame);
+        frame.draw_piece(
+            &self.piece,
+            &self.board,
+            &config,
+            palette,
+            self.settings.ghost,
+        );
+        let max_resets = self.gravity.get_max_resets();
+        if self.settings.debug.contains(DebugOverlay::FINAL_RESET)
+            && max_resets > 0
+            && self.resets_used() == max_resets
+        {
+            for coord in self.piece.get_cells() {
+                for pixel in layout.cell(coord).coords() {
+                    if let Some(color) = frame.get_pixel(pixel) {
+                        frame.set_pixel(pixel, color.lerp(Rgb::WHITE, FINAL_RESET_TINT));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Draw the border around the field of the layout, on the sides where the panel has room.
+    fn _draw_border<const N: usize>(&self, frame: &mut FrameBuffer<N>) {
+        let color = self.get_palette().border;
+        let field = self.layout.field;
+        let (top, left) = (
+            field.top_left.row.saturating_sub(1),
+            field.top_left.col.saturating_sub(1),
+        );
+        let bottom_right = field.bottom_right + [1, 1];
+        for row in top..=bottom_right.row {
+            frame.set_pixel(Coordinate::from_array([row, bottom_right.col]), color);
+            if field.top_left.col > 0 {
+                frame.set_pixel(Coordinate::from_array([row, left]), color);
+            }
+        }
+        for col in left..=bottom_right.col {
+            frame.set_pixel(Coordinate::from_array([bottom_right.row, col]), color);
+            if field.top_left.row > 0 {
+                frame.set_pixel(Coordinate::from_array([top, col]), color);
+            }
+        }
+    }
+
+    /// Draw the debug gauges of `Settings::debug` under the active piece: the auto-shift charge of each direction
+    /// in the outer columns of the field, left in its first column and right over the border, and the lock delay
+    /// in the row below the bottom border.
+    fn _draw_gauges<const N: usize>(&self, frame: &mut FrameBuffer<N>) {
+        let field = self.layout.field;
+        let (top_left, bottom_right) = (field.top_left, field.bottom_right);
+        let (rows, cols) = (
+            bottom_right.row + 1 - top_left.row,
+            bottom_right.col + 1 - top_left.col,
+        );
+        if self.settings.debug.contains(DebugOverlay::DAS) {
+            let sides = [
+                (Action::Left, top_left.col),
+                (Action::Right, bottom_right.col + 1),
+            ];
+            for (direction, col) in sides {
+                let bottom = Coordinate::from_array([bottom_right.row, col]);
+                let level = self.das_progress(direction);
+                frame.draw_column_gauge(bottom, rows, level, GAUGE_COLOR);
+            }
+        }
+        if self.settings.debug.contains(DebugOverlay::LOCK_DELAY) {
+            let left = Coordinate::from_array([bottom_right.row + 2, top_left.col]);
+            frame.draw_row_gauge(left, cols, self.lock_delay_progress(), GAUGE_COLOR);
+        }
+    }
+
+    /// Get the color of a locked cell by the `StackColorMode`, before its luminance and the depth fog.
+    /// # Arguments
+    /// - `palette` - A reference to the `Palette` the game is drawn with
+    /// - `shape` - The `TetrominoShape` the cell was locked from
+    /// - `row` - The row of the cell
+    /// # Returns
+    /// - `Rgb` - The color of the cell
+    fn _stack_color(&self, palette: &Palette, shape: TetrominoShape, row: usize) -> Rgb {
+        match self.settings.stack_colors {
+            StackColorMode::PerPiece => palette.get_color(shape),
+            StackColorMode::Monochrome(color) => color,
+            StackColorMode::ByRow { top, bottom } => {
+                let t = row * u8::MAX as usize / (self.settings.rows - 1);
+                palette
+                    .get_color(top)
+                    .lerp(palette.get_color(bottom), t as u8)
+            }
+        }
+    }
+
+    /// Get the brightness of a row of the board, darkening the bottom rows with `depth_fog`.
+    /// # Arguments
+    /// - `row` - The index of the row
+    /// # Returns
+    /// - `u8` - The brightness of the row, 255 for the unchanged rows
+    fn _fog(&self, row: usize) -> u8 {
+        match self.settings.depth_fog {
+            true => FOG
+                .get(self.settings.rows - 1 - row)
+                .copied()
+                .unwrap_or(u8::MAX),
+            false => u8::MAX,
+        }
+    }
+
+    /// Draw the translucent UI layer, to composite over the frame drawn by `.render_into()`.
+    ///
+    /// While paused or entering a name, the panel is dimmed. The name entry shows the rank the score
+    /// gets in the high-score table on top.
+    /// # Arguments
+    /// - `overlay` - A muteable reference to the `display::overlay::Overlay` to draw into
+    pub fn render_overlay(&self, overlay: &mut UiOverlay) {
+        overlay.clear();
+        match self._shown_state() {
+            GameState::Paused => overlay.dim_all(DIM_ALPHA),
+            GameState::NameEntry(_) => {
+                overlay.dim_all(DIM_ALPHA);
+                if let Some(rank) = self
+                    ._ranked()
+                    .and_then(|(table, score)| table.qualifies(score))
+                {
+                    let glyph = Coordinate::from_array([GLYPH_ROWS, GLYPH_COLS]);
+                    let coord = self.layout.centered(glyph);
+                    overlay.draw_digit(coord, rank as u8 + 1, FLASH_COLOR, u8::MAX);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Draw the animated effects over the state of the game.
+    fn _draw_effects<const N: usize>(&self, frame: &mut FrameBuffer<N>) {
+        match self._shown_state() {
+            GameState::Paused => {
+                // Two vertical bars in the middle of the field.
+                let color = pulse(Rgb::WHITE, PAUSE_PULSE_TICKS, self.frames);
+                let glyph = Coordinate::from_array([GLYPH_ROWS, GLYPH_COLS]);
+                let top_left = self.layout.centered(glyph);
+                for r in 0..GLYPH_ROWS {
+                    frame.set_pixel(top_left + [r, 0], color);
+                    frame.set_pixel(top_left + [r, 2], color);
+                }
+            }
+            GameState::NameEntry(_) => {
+                // Seed with the frame, so the same frame always sparkles the same cells.
+                let mut rng = GameRng::new(self.frames as u64);
+                for coord in self.layout.field.coords() {
+                    let base = frame.get_pixel(coord).expect(IN_FRAME);
+                    frame.set_pixel(coord, sparkle(base, &mut rng, SPARKLE_DENSITY));
+                }
+            }
+            GameState::GameOver(_) | GameState::Finished(_) if self.settings.finesse => {
+                // The finesse faults of the game, in the middle of the field.
+                let field = self.layout.field;
+                let top = self
+                    .layout
+                    .centered(Coordinate::from_array([GLYPH_ROWS, 0]))
+                    .row;
+                let band = Region {
+                    top_left: Coordinate::from_array([top, field.top_left.col]),
+                    bottom_right: Coordinate::from_array([
+                        top + GLYPH_ROWS - 1,
+                        field.bottom_right.col,
+                    ]),
+                };
+                let color = self.get_palette().get_color(TetrominoShape::Z);
+                _draw_number(frame, self.finesse.get_faults(), band, color);
+            }
+            _ => (),
+        }
+    }
+
+    /// Get the completed rows and the remaining frames while clearing, also when paused during a clear.
+    fn _clearing(&self) -> Option<(u64, u8)> {
+        let state = match self._shown_state() {
+            GameState::Paused => self.resume,
+            state => state,
+        };
+        match state {
+            GameState::Clearing {
+                rows_mask,
+                frames_left,
+            } => Some((rows_mask, frames_left)),
+            _ => None,
+        }
+    }
+
+    /// Finish the game if the goal of its mode was reached.
+    fn _check_finished(&mut self) -> Option<Outcome> {
+        let finished = match self.settings.mode {
+            GameMode::Marathon => false,
+            GameMode::Sprint { lines } => self.score.get_lines() >= lines,
+            GameMode::Ultra { ticks } => self.clock.get_ticks_elapsed() >= ticks as u64,
+            GameMode::Cheese { .. } => self.cheese.get_remaining() == 0,
+        };
+        if !finished || self.state != GameState::Playing {
+            return None;
+        }
+        let outcome = Outcome {
+            ticks: self.clock.get_ticks_elapsed(),
+            points: self.score.get_points(),
+            lines: self.score.get_lines(),
+        };
+        self._emit(GameEvent::Finished {
+            outcome,
+            centis: self.clock.elapsed_centis(TICK_HZ),
+        });
+        self._end(GameState::Finished(outcome));
+        Some(outcome)
+    }
+
+    /// Start the game-over sweep, or end the game at once without sweep.
+    /// # Arguments
+    /// - `cause` - Why the game ended
+    fn _top_out(&mut self, cause: Cause) {
+        self._emit(GameEvent::GameOver {
+            cause,
+            centis: self.clock.elapsed_centis(TICK_HZ),
+        });
+        if let Some(ticks) = self.recorder.freeze() {
+            self._emit(GameEvent::ReplayFrozen { ticks });
+        }
+        match self.settings.sweep_ticks {
+            0 => self._end(GameState::GameOver(cause)),
+            _ => self.state = GameState::Sweep { cause, ticks: 0 },
+        }
+    }
+
+    /// Get what the renderer should draw on top of the playfield in the current state.
+    fn _overlay(&self) -> Overlay {
+        match self._shown_state() {
+            GameState::Countdown(steps) => Overlay::Countdown(steps),
+            GameState::Sweep { ticks, .. } => Overlay::Sweep(
+                (ticks as usize + 1) * self.settings.rows / self.settings.sweep_ticks as usize,
+            ),
+            _ => Overlay::None,
+        }
+    }
+
+    /// Get the high-score table of the mode of the game and the score to rank in it.
+    /// # Returns
+    /// - `Option<(&HighScores, u32)>` - The points in the marathon table, the time of a finished sprint in the table
+    ///   of sprint times, or `None` if the game is not ranked
+    fn _ranked(&self) -> Option<(&HighScores, u32)> {
+        let ended = match self.state {
+            GameState::NameEntry(_) => self.resume,
+            state => state,
+        };
+        match (self.settings.mode, ended) {
+            (GameMode::Marathon, _) => Some((&self.high_scores, self.score.get_points())),
+            (GameMode::Sprint { .. }, GameState::Finished(_)) => {
+                Some((&self.sprint_times, self.clock.elapsed_centis(TICK_HZ)))
+            }
+            _ => None,
+        }
+    }
+
+    /// End the game, entering a name first if a marathon score or a sprint time made it into its high-score table.
+    /// # Arguments
+    /// - `state` - The `GameState::GameOver` or `GameState::Finished` state to end in
+    fn _end(&mut self, state: GameState) {
+        self.state = state;
+        let qualifies = self
+            ._ranked()
+            .is_some_and(|(table, score)| table.qualifies(score).is_some());
+        self.state = match qualifies {
+            true => {
+                self.resume = state;
+                GameState::NameEntry(NameEntry {
+                    initials: *b"AAA",
+                    cursor: 0,
+                })
+            }
+            false => state,
+        };
+    }
+
+    /// Abandon the game in any state for the menu, on the mode select of the mode played.
+    ///
+    /// The lifetime totals drop the counts of a game in progress, see `LifetimeStats::fold()`, while a game entering
+    /// a name already ended, so its high-score entry is inserted with the initials entered so far.
+    fn _abort(&mut self) {
+        if let GameState::NameEntry(entry) = self.state {
+            self._insert_entry(entry.initials);
+        }
+        let centis = self.clock.elapsed_centis(TICK_HZ);
+        self.restart();
+        self.menu.show_mode_select();
+        self._emit(GameEvent::Aborted { centis });
+    }
+
+    /// Edit the initials of the high-score entry, inserting it once all initials are accepted.
+    fn _enter_name(&mut self, mut entry: NameEntry, input: &InputState) {
+        let letter = &mut entry.initials[entry.cursor];
+        if input.is_pressed(Action::Left) {
+            *letter = b'A' + (*letter - b'A' + 25) % 26;
+        }
+        if input.is_pressed(Action::Right) {
+            *letter = b'A' + (*letter - b'A' + 1) % 26;
+        }
+        if input.is_pressed(Action::RotateCcw) {
+            entry.cursor = entry.cursor.saturating_sub(1);
+        }
+        if input.is_pressed(Action::RotateCw) {
+            entry.cursor += 1;
+        }
+        self.state = match entry.cursor < entry.initials.len() {
+            true => GameState::NameEntry(entry),
+            false => {
+                self._insert_entry(entry.initials);
+                self.resume
+            }
+        };
+    }
+
+    /// Insert the score of the ended game into its high-score table.
+    /// # Arguments
+    /// - `initials` - The initials of the entry
+    fn _insert_entry(&mut self, initials: [u8; 3]) {
+        let (lines, level) = (self.score.get_lines(), self.score.get_level());
+        match self.settings.mode {
+            GameMode::Sprint { .. } => {
+                let centis = self.clock.elapsed_centis(TICK_HZ);
+                self.sprint_times.insert(initials, centis, lines, level);
+            }
+            _ => self
+                .high_scores
+                .insert(initials, self.score.get_points(), lines, level),
+        }
+    }
+
+    /// Freeze the game until it is unpaused.
+    fn _pause(&mut self) {
+        self._record(Action::Pause);
+        self.resume = self.state;
+        self.state = GameState::Paused;
+    }
+
+    /// Lock the active piece, update the score and attack the opponent.
+    ///
+    /// The completed rows are flashed for `Settings::clear_frames` frames before they are removed
+    /// and the next piece spawns. Without completed rows, the ready incoming garbage is applied
+    /// and the next piece spawns immediately.
+    fn _lock(&mut self) -> TickOutput {
+        let shape = self.piece.get_tetromino().shape;
+        let cells = self
+            .piece
+            .get_cells()
+            .map(|coord| [coord.row as u8, coord.col as u8]);
+        let t_spin = self.piece.place(&mut self.board, Cell::locked(shape));
+        let rows_mask = self._full_rows_mask();
+        let event = ClearEvent {
+            lines: rows_mask.count_ones() as usize,
+            t_spin,
+        };
+        let back_to_back = self.score.get_back_to_back() && is_difficult(&event);
+        let filled = self
+            .board
+            .cells()
+            .filter(|(_, cell)| *cell != Cell::Empty)
+            .count();
+        let perfect_clear = filled == event.lines * self.settings.cols;
+        let sent = self
+            .garbage
+            .cancel(attack(&event, back_to_back, perfect_clear));
+        let level = self.score.get_level();
+        let points = self.score.apply(&event);
+        self.history.end_piece();
+        let coord = self.piece.get_coord();
+        self.log.push(
+            self.frames,
+            LogEntry::Locked {
+                row: coord.row as u8,
+                col: coord.col as u8,
+                resets: self.gravity.get_resets(),
+            },
+        );
+        self._emit(GameEvent::PieceLocked { shape, cells });
+        // Only the player is judged, on the board the table is made for.
+        if self.settings.cols == FINESSE_COLS && self.attract.is_none() {
+            if let Some(excess) = self.finesse.judge(&self.piece) {
+                self._emit(GameEvent::FinesseFault { excess });
+            }
+        }
+        if event.lines > 0 {
+            self._emit(GameEvent::LinesCleared {
+                n: event.lines,
+                t_spin,
+                b2b: back_to_back,
+            });
+            self._emit(GameEvent::RowsCleared {
+                rows_mask,
+                cols: self.settings.cols as u8,
+            });
+        }
+        if self.score.get_level() > level {
+            self._emit(GameEvent::LevelUp {
+                level: self.score.get_level(),
+            });
+        }
+        if rows_mask == 0 && self._apply_garbage() {
+            self._top_out(Cause::TopOut);
+            return TickOutput {
+                locked: Some(event),
+                points,
+                sent,
+                ..Default::default()
+            };
+        }
+        match rows_mask == 0 || self.settings.clear_frames == 0 {
+            true => self._clear(),
+            false => {
+                self.state = GameState::Clearing {
+                    rows_mask,
+                    frames_left: self.settings.clear_frames,
+                }
+            }
+        }
+        TickOutput {
+            locked: Some(event),
+            points,
+            sent,
+            ..Default::default()
+        }
+    }
+
+    /// Push the ready incoming garbage rows in from the bottom of the board.
+    /// # Returns
+    /// - `bool` - Whether (`true`) or not (`false`) the stack was pushed out at the top
+    fn _apply_garbage(&mut self) -> bool {
+        let lines = self.garbage.take_ready();
+        if lines > 0 {
+            self._emit(GameEvent::GarbageReceived { lines });
+        }
+        // A row at a time, so the gaps need no buffer, keeping the first row on top.
+        let mut topped_out = false;
+        for _ in 0..lines {
+            let gap = self.gap.next(&mut self.rng, self.settings.cols);
+            topped_out |= self.board.insert_garbage_rows(&[gap], Cell::Garbage);
+            self.cheese.push(self.settings.rows, false);
+        }
+        topped_out
+    }
+
+    /// Pre-fill the board with the garbage rows of a cheese race, at most half of the board so pieces still spawn.
+    fn _fill_cheese(&mut self) {
+        if let GameMode::Cheese { rows, .. } = self.settings.mode {
+            for _ in 0..(rows as usize).min(self.settings.rows / 2) {
+                self._push_cheese(true);
+            }
+        }
+    }
+
+    /// Push a single garbage row of a cheese race in from the bottom of the board.
+    /// # Arguments
+    /// - `counted` - Whether (`true`) or not (`false`) the row is starting garbage that has to be cleared
+    fn _push_cheese(&mut self, counted: bool) {
+        let gap = self.gap.next_cheese(&mut self.rng, self.settings.cols);
+        // Only pushed onto an empty board or in place of cleared rows, so nothing is pushed out at the top.
+        self.board.insert_garbage_rows(&[gap], Cell::Garbage);
+        self.cheese.push(self.settings.rows, counted);
+    }
+
+    /// Get the completed rows of the board.
+    /// # Returns
+    /// - `u64` - A bit per completed row, bit 0 is the top row
+    fn _full_rows_mask(&self) -> u64 {
+        self.board
+            .full_rows()
+            .fold(0, |mask: u64, row| mask | (1 << row))
+    }
+
+    /// Record an event of the current tick, dropping it if the tick already has `MAX_EVENTS` events.
+    /// The games of the attract mode do not count towards the lifetime totals.
+    fn _emit(&mut self, event: GameEvent) {
+        if self.attract.is_none() {
+            self.lifetime.fold(&event, self.settings.mode);
+        }
+        if let Some(entry) = LogEntry::from_event(&event) {
+            self.log.push(self.frames, entry);
+        }
+        self.events.push(event).ok();
+    }
+
+    /// Record the input of a tick before it takes effect, while a game is in progress, see `ReplayRecorder`.
+    fn _record_input(&mut self, input: &InputState) {
+        let recording = self.attract.is_none()
+            && matches!(
+                self._shown_state(),
+                GameState::Countdown(_)
+                    | GameState::Playing
+                    | GameState::Clearing { .. }
+                    | GameState::Paused
+            );
+        let mut recorder = core::mem::take(&mut self.recorder);
+        let frozen = recorder
+            .record(self, input, recording)
+            .expect("a game outside of the attract mode saves");
+        self.recorder = recorder;
+        if let Some(ticks) = frozen {
+            self._emit(GameEvent::ReplayFrozen { ticks });
+        }
+    }
+
+    /// Record an action that took effect this tick, in the history and in the log.
+    fn _record(&mut self, action: Action) {
+        self.history.record(action);
+        self.log.push(self.frames, LogEntry::Action(action));
+    }
+
+    /// Remove the completed rows, regenerate the cleared cheese and spawn the next piece.
+    fn _clear(&mut self) {
+        let dug = self.cheese.clear(self._full_rows_mask());
+        self.board.clear_full_rows();
+        if let GameMode::Cheese {
+            regenerate: true, ..
+        } = self.settings.mode
+        {
+            for _ in 0..dug {
+                self._push_cheese(false);
+            }
+        }
+        self.hold.unlock();
+        let next = self.queue.pop(&mut self.rng);
+        self._spawn(next);
+    }
+
+    /// Spawn a piece centered at the top of the board, ending the game if it does not fit.
+    fn _spawn(&mut self, shape: TetrominoShape) {
+        let tetromino = Tetromino::from(shape);
+        let col = self.settings.cols.saturating_sub(tetromino.get_shape().col) / 2;
+        self.piece = ActivePiece::new(tetromino, Coordinate::from_array([0, col]));
+        self.gravity.reset();
+        self.finesse.start_piece();
+        if !self.piece.fits(&self.board) {
+            self._top_out(Cause::BlockOut);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Cause, Game, GameState, NameEntry, Outcome, Overlay, TickOutput, ATTRACT_TICKS,
+        COUNTDOWN_STEPS, DIM_ALPHA, FINAL_RESET_TINT, GARBAGE_COLOR, GAUGE_COLOR, RESET_CHORD,
+        RESET_TICKS,
+    };
+    use crate::ai::{Autopilot, Lookahead};
+    use crate::board::{Board, BoardOps, FixedBoard};
+    use crate::cell::{Cell, LOCKED_LUM};
+    use crate::clock::{ticks_to_centis, GameClock, TICK_HZ};
+    use crate::color::{Color, Rgb};
+    use crate::coordinate::Coordinate;
+    use crate::display::overlay::Overlay as UiOverlay;
+    use crate::events::{EventSink, GameEvent};
+    use crate::framebuffer::FrameBuffer;
+    use crate::garbage::{attack, GARBAGE_DELAY, GARBAGE_WARNING_TICKS};
+    use crate::grid::Array2D;
+    use crate::highscores::HighScores;
+    use crate::input::buttons::ButtonInput;
+    use crate::input::keymap::KeyMap;
+    use crate::input::{Action, InputState};
+    use crate::invariants::InvariantViolation;
+    use crate::layout::Layout;
+    use crate::lifetime::LifetimeStats;
+    use crate::menu::Screen;
+    use crate::palette::Palette;
+    use crate::piece::{ActivePiece, Movement};
+    use crate::power::Power;
+    use crate::replay::{Replay, ReplayResult, MAX_REPLAY_WORDS};
+    use crate::rng::{GameRng, RngSource};
+    use crate::rotation::kicks::RotationState;
+    use crate::savestate::{save_state_bytes, SaveError, SAVE_VERSION};
+    use crate::scoring::{ClearEvent, TSpin};
+    use crate::settings::{ColorScheme, DebugOverlay, GameMode, Settings, StackColorMode};
+    use crate::testing::GameFixture;
+    use crate::tetrominoes::{Tetromino, TetrominoShape};
+    use test_case::test_case;
+
+    /// Score a board for the scripted player: lower stacks with fewer holes are better.
+    fn evaluate(board: &Board<Cell>) -> i64 {
+        let shape = board.get_shape();
+        let mut heights = vec![0; shape.col];
+        let mut holes = 0;
+        for (c, height) in heights.iter_mut().enumerate() {
+            let top = (0..shape.row).find(|&r| board.is_filled(Coordinate { row: r, col: c }));
+            if let Some(top) = top {
+                *height = (shape.row - top) as i64;
+                holes += (top..shape.row)
+                    .filter(|&r| !board.is_filled(Coordinate { row: r, col: c }))
+                    .count() as i64;
+            }
+        }
+        let bumpiness: i64 = heights.windows(2).map(|w| (w[0] - w[1]).abs()).sum();
+        -(heights.iter().sum::<i64>() * 5 + holes * 35 + bumpiness * 2)
+    }
+
+    /// Plan the inputs to place the active piece at the best spot, one action per tick.
+    fn plan(game: &Game) -> Vec<InputState> {
+        let shape = game.get_piece().get_tetromino().shape;
+        let spawn = game.get_piece().get_coord();
+        let mut best = (i64::MIN, Vec::new());
+        for rotations in 0..4 {
+            for shift in -5..=5_isize {
+                let mut board = Board::from_array(game.get_board().get_array(), Cell::Empty);
+                let mut piece = ActivePiece::new(Tetromino::from(shape), spawn);
+                let mut actions = Vec::new();
+                for _ in 0..rotations {
+                    piece.try_rotate_cw_with_kicks(&board);
+                    actions.push(Action::RotateCw);
+                }
+                for _ in 0..shift.abs() {
+                    match shift < 0 {
+                        true => piece.try_shift_left(&board),
+                        false => piece.try_shift_right(&board),
+                    };
+                    actions.push(if shift < 0 {
+                        Action::Left
+                    } else {
+                        Action::Right
+                    });
+                }
+                while piece.try_drop(&board) {}
+                piece.lock(&mut board, Cell::locked(shape));
+                actions.push(Action::HardDrop);
+                let value = evaluate(&board);
+                if value > best.0 {
+                    best = (value, actions);
+                }
+            }
+        }
+        best.1.into_iter().map(InputState::from_action).collect()
+    }
+
+    /// Start a game and tick through the countdown.
+    fn start(game: &mut Game) {
+        game.tick(&InputState::from_action(Action::Start));
+        settle(game);
+    }
+
+    /// Tick without input until the game is playing, e.g. after the completed rows were flashed.
+    fn settle(game: &mut Game) {
+        while game.get_state() != GameState::Playing {
+            game.tick(&InputState::default());
+        }
+    }
+
+    /// Fill the empty cells of a row with garbage and hard drop an O piece, which never fills a single gap.
+    fn dig(game: &mut Game, row: usize) -> TickOutput {
+        for col in 0..game.get_settings().cols {
+            let coord = Coordinate { row, col };
+            if !game.board.is_filled(coord) {
+                game.board.set(coord, Cell::Garbage);
+            }
+        }
+        game.spawn(TetrominoShape::O);
+        game.tick(&InputState::from_action(Action::HardDrop))
+    }
+
+    #[test]
+    fn test_spawn() {
+        // Pieces spawn centered on the top row.
+        let game = Game::new(Settings::default(), 7);
+        let piece = game.get_piece();
+        let width = piece.get_tetromino().get_shape().col;
+        assert_eq!(
+            piece.get_coord(),
+            Coordinate::from_array([0, (10 - width) / 2])
+        );
+        assert_eq!(game.get_queue().peek(3).count(), 3);
+    }
+
+    #[test_case(12, 6; "narrow")]
+    #[test_case(24, 16; "wide")]
+    fn test_board_sizes(rows: usize, cols: usize) {
+        // The autopilot clears lines on other board sizes, then stacking in place tops out, all pieces centered.
+        let settings = Settings {
+            rows,
+            cols,
+            clear_frames: 0,
+            ..Default::default()
+        };
+        assert_eq!(settings.validate(), Ok(()));
+        let mut game = Game::new(settings, 3);
+        start(&mut game);
+        let centered = |game: &Game| {
+            let piece = game.get_piece();
+            let width = piece.get_tetromino().get_shape().col;
+            piece.get_coord() == Coordinate::from_array([0, (cols - width) / 2])
+        };
+        assert!(centered(&game));
+        let mut autopilot = Autopilot::default();
+        for _ in 0..20_000 {
+            let input =
+                autopilot.next_input(game.get_board(), game.get_piece(), &Lookahead::default());
+            if game.tick(&input).locked.is_some() {
+                assert!(centered(&game));
+            }
+            if game.get_score().get_lines() >= 4 || game.is_game_over() {
+                break;
+            }
+        }
+        assert!(game.get_score().get_lines() >= 4);
+        game.tick(&InputState::default());
+        for _ in 0..rows * cols {
+            let output = game.tick(&InputState::from_action(Action::HardDrop));
+            game.tick(&InputState::default());
+            assert!(output.locked.is_some());
+            if game.get_state() != GameState::Playing {
+                break;
+            }
+            assert!(centered(&game));
+        }
+        // The stack is swept off the board before the game is over.
+        assert!(matches!(
+            game.get_state(),
+            GameState::Sweep {
+                cause: Cause::BlockOut,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_hold() {
+        // Holding swaps in the next piece, and is only allowed once per piece.
+        let mut game = Game::new(Settings::default(), 7);
+        start(&mut game);
+        let first = game.get_piece().get_tetromino().shape;
+        let second = *game.get_queue().peek(1).next().unwrap();
+        game.tick(&InputState::from_action(Action::Hold));
+        assert_eq!(game.get_hold().get_shape(), Some(first));
+        assert_eq!(game.get_piece().get_tetromino().shape, second);
+        game.tick(&InputState::from_action(Action::Hold));
+        assert_eq!(game.get_piece().get_tetromino().shape, second);
+        let output = game.tick(&InputState::from_action(Action::HardDrop));
+        assert!(output.locked.is_some());
+        assert!(game.get_hold().can_hold());
+    }
+
+    #[test]
+    fn test_render() {
+        // The active piece is drawn at its position, its ghost dimmed on the bottom row.
+        let mut game = Game::new(Settings::default(), 7);
+        start(&mut game);
+        let mut frame = FrameBuffer::<320>::new(Coordinate::from_array([20, 16]));
+        game.render_into(&mut frame);
+        let piece = game.get_piece();
+        let tetromino = piece.get_tetromino();
+        let color = Rgb::from(Color::from(tetromino.shape));
+        let ghost = piece.ghost_coord(game.get_board());
+        assert_eq!(ghost.row + tetromino.get_shape().row, 20);
+        for r in 0..tetromino.get_shape().row {
+            for c in 0..tetromino.get_shape().col {
+                if tetromino.get_mask()[(r, c)] {
+                    assert_eq!(frame.get_pixel(piece.get_coord() + [r, c]), Some(color));
+                    assert_ne!(frame.get_pixel(ghost + [r, c]), Some(color));
+                }
+            }
+        }
+        // With level palettes, the piece takes the color of the palette of its level.
+        let settings = Settings {
+            colors: ColorScheme::Levels,
+            ..Default::default()
+        };
+        let mut game = Game::new(settings, 7);
+        start(&mut game);
+        game.render_into(&mut frame);
+        let palette = Palette::for_level(settings.start_level);
+        assert_eq!(game.get_palette(), palette);
+        let color = palette.get_color(tetromino.shape);
+        for r in 0..tetromino.get_shape().row {
+            for c in 0..tetromino.get_shape().col {
+                if tetromino.get_mask()[(r, c)] {
+                    assert_eq!(frame.get_pixel(piece.get_coord() + [r, c]), Some(color));
+                    assert_eq!(frame.get_pixel(ghost + [r, c]), palette.ghost);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_render_hold() {
+        // The held piece is drawn below the previews, grayed out until the next piece.
+        let mut game = Game::new(Settings::default(), 7);
+        start(&mut game);
+        let held = game.get_piece().get_tetromino().shape;
+        game.tick(&InputState::from_action(Action::Hold));
+        let slot = |game: &Game| {
+            let mut frame = FrameBuffer::<320>::new(Coordinate::from_array([20, 16]));
+            game.render_into(&mut frame);
+            let mut colors = Vec::new();
+            for row in 10..14 {
+                for col in 11..15 {
+                    let color = frame.get_pixel(Coordinate { row, col }).unwrap();
+                    if color != Rgb::BLACK {
+                        colors.push(color);
+                    }
+                }
+            }
+            colors
+        };
+        assert_eq!(slot(&game), vec![Rgb::GRAY; 4]);
+        game.tick(&InputState::from_action(Action::HardDrop));
+        assert_eq!(slot(&game), vec![game.get_palette().get_color(held); 4]);
+    }
+
+    #[test]
+    fn test_render_layout() {
+        // A centered field on a tall panel, letterboxed around the field and the HUD.
+        let mut game = Game::new(Settings::default(), 7);
+        let panel = Coordinate::from_array([64, 32]);
+        let mut layout = Layout::auto(panel, Coordinate::from_array([20, 10]), 1).unwrap();
+        layout.letterbox = Rgb::from_array(&[0, 0, 64]);
+        game.set_layout(layout);
+        start(&mut game);
+        let mut frame = FrameBuffer::<{ 64 * 32 }>::new(panel);
+        game.render_into(&mut frame);
+        let piece = game.get_piece();
+        let tetromino = piece.get_tetromino();
+        let color = game.get_palette().get_color(tetromino.shape);
+        for r in 0..tetromino.get_shape().row {
+            for c in 0..tetromino.get_shape().col {
+                if tetromino.get_mask()[(r, c)] {
+                    let coord = layout.field.top_left + piece.get_coord() + [r, c];
+                    assert_eq!(frame.get_pixel(coord), Some(color));
+                }
+            }
+        }
+        // The first preview at the top of the queue, the border on all four sides of the field.
+        let preview = Tetromino::from(*game.queue.peek(1).next().unwrap());
+        let colors: Vec<Rgb> = layout
+            .queue
+            .coords()
+            .filter_map(|coord| frame.get_pixel(coord))
+            .filter(|color| *color != Rgb::BLACK)
+            .collect();
+        assert_eq!(colors[0], game.get_palette().get_color(preview.shape));
+        let border = game.get_palette().border;
+        for corner in [
+            layout.field.top_left - [1, 1],
+            layout.field.bottom_right + [1, 1],
+        ] {
+            assert_eq!(frame.get_pixel(corner), Some(border));
+        }
+        assert_eq!(
+            frame.get_pixel(Coordinate::from_array([0, 0])),
+            Some(layout.letterbox)
+        );
+        assert_eq!(frame.get_pixel(layout.hold.top_left), Some(Rgb::BLACK));
+        let score = layout.score.unwrap();
+        assert_eq!(frame.get_pixel(score.top_left), Some(Rgb::BLACK));
+    }
+
+    #[test]
+    fn test_stack_colors() {
+        // The same stack under every mode, switched mid-game by drawing alone.
+        let mut game = GameFixture::new(Settings::default())
+            .board_str(
+                "
+                I.........
+                T........O
+                ",
+            )
+            .build()
+            .unwrap();
+        let cells: Vec<(Coordinate, Cell)> = game.get_board().cells().collect();
+        let palette = game.get_palette();
+        let [top, bottom] = [[18, 0], [19, 0]].map(Coordinate::from_array);
+        let colors = |game: &Game| {
+            let mut frame = FrameBuffer::<320>::new(Coordinate::from_array([20, 16]));
+            game.render_into(&mut frame);
+            [top, bottom, Coordinate::from_array([19, 9])]
+                .map(|coord| frame.get_pixel(coord).unwrap())
+        };
+        let piece = |shape| palette.get_color(shape).scaled(LOCKED_LUM);
+        assert_eq!(
+            colors(&game),
+            [TetrominoShape::I, TetrominoShape::T, TetrominoShape::O].map(piece)
+        );
+        game.set_stack_colors(StackColorMode::MONOCHROME);
+        assert_eq!(colors(&game), [Rgb::GRAY.scaled(LOCKED_LUM); 3]);
+        // The gradient runs from the I color on the top row to the T color on the bottom row.
+        game.set_stack_colors(StackColorMode::GRADIENT);
+        let [i, t] = [TetrominoShape::I, TetrominoShape::T].map(|shape| palette.get_color(shape));
+        let row_18 = i.lerp(t, (18 * 255 / 19) as u8).scaled(LOCKED_LUM);
+        assert_eq!(
+            colors(&game),
+            [row_18, piece(TetrominoShape::T), piece(TetrominoShape::T)]
+        );
+        // The locked cells are untouched, and the next game keeps the mode.
+        assert_eq!(game.get_board().cells().collect::<Vec<_>>(), cells);
+        game.restart();
+        assert_eq!(game.get_settings().stack_colors, StackColorMode::GRADIENT);
+    }
+
+    #[test]
+    fn test_render_layers() {
+        // A landed piece is drawn over its ghost, without either touching the cells of the board.
+        let mut game = Game::new(Settings::default(), 7);
+        start(&mut game);
+        while game.get_piece().get_coord() != game.get_piece().ghost_coord(game.get_board()) {
+            game.tick(&InputState::default());
+        }
+        let mut frame = FrameBuffer::<320>::new(Coordinate::from_array([20, 16]));
+        game.render_into(&mut frame);
+        let piece = game.get_piece();
+        let tetromino = piece.get_tetromino();
+        let palette = game.get_palette();
+        for r in 0..tetromino.get_shape().row {
+            for c in 0..tetromino.get_shape().col {
+                if tetromino.get_mask()[(r, c)] {
+                    assert_eq!(
+                        frame.get_pixel(piece.get_coord() + [r, c]),
+                        Some(palette.get_color(tetromino.shape))
+                    );
+                }
+            }
+        }
+        assert!(game
+            .get_board()
+            .cells()
+            .all(|(_, cell)| cell == Cell::Empty));
+    }
+
+    #[test]
+    fn test_render_grid() {
+        // The empty cells form a checkerboard, bordered on the right and at the bottom.
+        // ....|
+        // ....|
+        // .#.#|
+        // #.#.|
+        // .#.#|
+        // -----
+        let settings = Settings {
+            rows: 5,
+            cols: 4,
+            previews: 0,
+            ghost: false,
+            grid: true,
+            ..Default::default()
+        };
+        let mut game = Game::new(settings, 7);
+        start(&mut game);
+        let mut frame = FrameBuffer::<48>::new(Coordinate::from_array([6, 8]));
+        game.render_into(&mut frame);
+        let palette = game.get_palette();
+        let (bg, grid) = (palette.background, palette.grid);
+        assert_eq!(frame.get_row(2).unwrap()[..4], [bg, grid, bg, grid]);
+        assert_eq!(frame.get_row(3).unwrap()[..4], [grid, bg, grid, bg]);
+        assert_eq!(frame.get_row(4).unwrap()[..4], [bg, grid, bg, grid]);
+        assert!(
+            (0..6).all(|r| frame.get_pixel(Coordinate { row: r, col: 4 }) == Some(palette.border))
+        );
+        assert_eq!(frame.get_row(5).unwrap()[..5], [palette.border; 5]);
+        assert_eq!(frame.get_row(5).unwrap()[5..], [Rgb::BLACK; 3]);
+    }
+
+    #[test]
+    fn test_render_locked() {
+        // Locked pieces are dimmer than the active piece, and darker still on the fogged bottom row.
+        let settings = Settings {
+            depth_fog: true,
+            ..Default::default()
+        };
+        let mut game = Game::new(settings, 7);
+        start(&mut game);
+        let shape = game.get_piece().get_tetromino().shape;
+        game.tick(&InputState::from_action(Action::HardDrop));
+        let locked: Vec<(usize, Cell)> = game
+            .get_board()
+            .get_array()
+            .elements_row_major_iter()
+            .enumerate()
+            .filter(|(_, cell)| **cell != Cell::Empty)
+            .map(|(index, cell)| (index, *cell))
+            .collect();
+        assert_eq!(locked.len(), 4);
+        assert!(locked.iter().all(|(_, cell)| *cell == Cell::locked(shape)));
+        let mut frame = FrameBuffer::<320>::new(Coordinate::from_array([20, 16]));
+        game.render_into(&mut frame);
+        let color = Rgb::from(Color::from(shape));
+        for (index, _) in locked {
+            let coord = Coordinate::from_row_major(index, game.get_board().get_shape()).unwrap();
+            let fog = match coord.row {
+                19 => 128,
+                _ => 160,
+            };
+            assert_eq!(
+                frame.get_pixel(coord),
+                Some(color.scaled(LOCKED_LUM).scaled(fog))
+            );
+        }
+    }
+
+    #[test]
+    fn test_scripted_game() {
+        // Play 50 pieces on a seeded game with a simple scripted player.
+        let mut game = Game::new(Settings::default(), 2023);
+        start(&mut game);
+        let mut pieces = 0;
+        while pieces < 50 {
+            for input in plan(&game) {
+                let output = game.tick(&input);
+                assert!(matches!(
+                    output.state,
+                    GameState::Playing | GameState::Clearing { .. }
+                ));
+                if output.locked.is_some() {
+                    pieces += 1;
+                }
+            }
+            settle(&mut game);
+        }
+        assert_eq!(game.get_score().get_lines(), 14);
+        assert_eq!(game.get_score().get_points(), 2250);
+        assert_eq!(game.get_score().get_level(), 2);
+        assert_eq!(game.board_hash(), 1057633137937934626);
+    }
+
+    #[test]
+    fn test_countdown() {
+        // Start counts down 3 seconds before playing, without moving the piece.
+        let mut game = Game::new(Settings::default(), 7);
+        let coord = game.get_piece().get_coord();
+        assert_eq!(game.tick(&InputState::default()).state, GameState::Menu);
+        assert_eq!(
+            game.tick(&InputState::from_action(Action::Start)).state,
+            GameState::Countdown(3)
+        );
+        let states: Vec<GameState> = (0..180)
+            .map(|_| game.tick(&InputState::from_action(Action::HardDrop)).state)
+            .collect();
+        assert_eq!(states[58], GameState::Countdown(3));
+        assert_eq!(states[59], GameState::Countdown(2));
+        assert_eq!(states[119], GameState::Countdown(1));
+        assert_eq!(states[179], GameState::Playing);
+        assert_eq!(game.get_piece().get_coord(), coord);
+        assert_eq!(game.get_ticks(), 0);
+    }
+
+    #[test]
+    fn test_menu() {
+        // The game starts with the settings chosen in the menu, keeping the seed and the high scores.
+        let mut game = Game::new(Settings::default(), 7);
+        let mut high_scores = HighScores::default();
+        high_scores.insert(*b"ABC", 1000, 10, 2);
+        game.set_high_scores(high_scores);
+        assert!(matches!(game.get_menu().get_screen(), Screen::Splash(_)));
+        for action in [
+            Action::Left,
+            Action::SoftDrop,
+            Action::RotateCw,
+            Action::SoftDrop,
+            Action::SoftDrop,
+            Action::RotateCw,
+            Action::Start,
+        ] {
+            game.tick(&InputState::from_action(action));
+            game.tick(&InputState::default());
+        }
+        assert!(matches!(game.get_state(), GameState::Countdown(_)));
+        assert_eq!(game.get_settings().mode, GameMode::ULTRA);
+        assert_eq!(game.seed(), 7);
+        assert_eq!(*game.get_high_scores(), high_scores);
+        // Starting over returns to the menu without the splash, with the chosen settings.
+        game.restart();
+        assert_eq!(game.get_state(), GameState::Menu);
+        assert!(matches!(game.get_menu().get_screen(), Screen::Main(0)));
+        assert_eq!(game.get_menu().get_settings().mode, GameMode::ULTRA);
+    }
+
+    #[test]
+    fn test_attract() {
+        // After a minute without input on the menu the game demos itself, until any action is held.
+        let mut game = Game::new(Settings::default(), 157);
+        for _ in 1..ATTRACT_TICKS {
+            assert_eq!(game.tick(&InputState::default()).state, GameState::Menu);
+        }
+        assert!(!game.is_attract());
+        assert_eq!(game.tick(&InputState::default()).state, GameState::Playing);
+        assert!(game.is_attract());
+        let mut pieces = 0;
+        for _ in 0..2000 {
+            pieces += game.tick(&InputState::default()).locked.is_some() as u32;
+        }
+        assert!(pieces > 10);
+        assert!(game.is_attract());
+        let mut input = InputState::default();
+        input.hold(Action::Left);
+        assert_eq!(game.tick(&input).state, GameState::Menu);
+        assert!(!game.is_attract());
+        assert_eq!(game.get_score().get_points(), 0);
+        // Holding an action on the menu postpones the demo.
+        for _ in 1..ATTRACT_TICKS {
+            game.tick(&InputState::default());
+        }
+        game.tick(&input);
+        assert_eq!(game.tick(&InputState::default()).state, GameState::Menu);
+    }
+
+    #[test]
+    fn test_sleep() {
+        // Without input the panel dims, then fades to black while the game sleeps, and any input wakes it up.
+        let settings = Settings {
+            dim_ticks: 100,
+            dim_level: 64,
+            sleep_ticks: 300,
+            ..Default::default()
+        };
+        let mut game = Game::new(settings, 160);
+        start(&mut game);
+        game.tick(&InputState::from_action(Action::Pause));
+        // The border and the previews right of the board, which are not animated while paused.
+        let render = |game: &Game| {
+            let mut frame = FrameBuffer::<320>::new(Coordinate::from_array([20, 16]));
+            game.render_into(&mut frame);
+            (0..20)
+                .flat_map(|row| (10..16).map(move |col| Coordinate { row, col }))
+                .map(|coord| frame.get_pixel(coord).unwrap())
+                .collect::<Vec<Rgb>>()
+        };
+        let awake = render(&game);
+        let piece = game.get_piece().get_coord();
+        let mut ticks = 1;
+        while game.tick(&InputState::default()).power == Power::Awake {
+            ticks += 1;
+        }
+        assert_eq!(ticks, 100);
+        while game.get_inactivity().get_level() > 64 {
+            assert_eq!(game.tick(&InputState::default()).state, GameState::Paused);
+        }
+        let dimmed: Vec<Rgb> = awake.iter().map(|color| color.scaled(64)).collect();
+        assert_eq!(render(&game), dimmed);
+        while game.get_state() != GameState::Sleep {
+            game.tick(&InputState::default());
+        }
+        assert_eq!(game.get_inactivity().get_idle(), 300);
+        for _ in 0..120 {
+            let output = game.tick(&InputState::default());
+            assert_eq!(
+                (output.state, output.power),
+                (GameState::Sleep, Power::Asleep)
+            );
+        }
+        assert!(render(&game).iter().all(|color| *color == Rgb::BLACK));
+        // Waking restores the paused game at full brightness, without acting on the input.
+        let output = game.tick(&InputState::from_action(Action::Pause));
+        assert_eq!(
+            (output.state, output.power),
+            (GameState::Paused, Power::Awake)
+        );
+        assert_eq!(game.get_inactivity().get_level(), 255);
+        assert_eq!(render(&game), awake);
+        // The piece did not fall while asleep, and unpausing plays on.
+        assert_eq!(game.get_piece().get_coord(), piece);
+        game.tick(&InputState::default());
+        assert_eq!(
+            game.tick(&InputState::from_action(Action::Pause)).state,
+            GameState::Playing
+        );
+    }
+
+    #[test]
+    fn test_pause_lock_delay() {
+        // Pausing while grounded continues the lock delay where it left off.
+        let grounded = || {
+            GameFixture::new(Settings::default())
+                .active(
+                    TetrominoShape::O,
+                    RotationState::Spawn,
+                    Coordinate::from_array([18, 4]),
+                )
+                .build()
+                .unwrap()
+        };
+        let ticks_to_lock = |game: &mut Game| {
+            let mut ticks = 0;
+            while game.tick(&InputState::default()).locked.is_none() {
+                ticks += 1;
+            }
+            ticks
+        };
+        let mut reference = grounded();
+        assert!(reference.get_piece().is_grounded(reference.get_board()));
+        let total = ticks_to_lock(&mut reference);
+
+        let mut game = grounded();
+        for _ in 0..10 {
+            game.tick(&InputState::default());
+        }
+        let output = game.tick(&InputState::from_action(Action::Pause));
+        assert_eq!(output.state, GameState::Paused);
+        assert!(output.dim);
+        let ticks = game.get_ticks();
+        for _ in 0..100 {
+            assert!(game.tick(&InputState::default()).locked.is_none());
+        }
+        assert_eq!(game.get_ticks(), ticks);
+        game.tick(&InputState::from_action(Action::Pause));
+        assert_eq!(game.get_state(), GameState::Playing);
+        assert_eq!(10 + ticks_to_lock(&mut game), total);
+    }
+
+    #[test]
+    fn test_pause_discards_input() {
+        // Inputs pressed while paused have no effect after resuming.
+        let mut game = Game::new(Settings::default(), 7);
+        start(&mut game);
+        game.tick(&InputState::from_action(Action::Pause));
+        let coord = game.get_piece().get_coord();
+        for action in [
+            Action::Left,
+            Action::RotateCw,
+            Action::HardDrop,
+            Action::Hold,
+        ] {
+            game.tick(&InputState::from_action(action));
+        }
+        game.tick(&InputState::from_action(Action::Start));
+        assert_eq!(game.get_state(), GameState::Playing);
+        assert_eq!(game.get_piece().get_coord(), coord);
+        assert_eq!(game.get_hold().get_shape(), None);
+        assert_eq!(
+            game.board_hash(),
+            Game::new(Settings::default(), 7).board_hash()
+        );
+    }
+
+    #[test]
+    fn test_pause_pulse() {
+        // The pause indicator fades in and out, peaking halfway every pulse.
+        let mut game = Game::new(Settings::default(), 7);
+        start(&mut game);
+        game.tick(&InputState::from_action(Action::Pause));
+        let mut frame = FrameBuffer::<320>::new(Coordinate::from_array([20, 16]));
+        let bar = Coordinate::from_array([7, 3]);
+        let mut colors = Vec::new();
+        for _ in 0..120 {
+            game.tick(&InputState::default());
+            game.render_into(&mut frame);
+            colors.push(frame.get_pixel(bar).unwrap());
+        }
+        assert!(colors.contains(&Rgb::WHITE));
+        assert!(colors.contains(&Rgb::BLACK));
+        assert_eq!(colors[..60], colors[60..]);
+        assert_eq!(game.get_state(), GameState::Paused);
+    }
+
+    #[test]
+    fn test_render_overlay() {
+        // The panel is dimmed while paused, and transparent again after resuming.
+        let mut game = Game::new(Settings::default(), 7);
+        start(&mut game);
+        let mut overlay = UiOverlay::new(Coordinate::from_array([20, 16]));
+        game.render_overlay(&mut overlay);
+        assert_eq!(overlay.get_cell(Coordinate::from_array([10, 5])), None);
+        game.tick(&InputState::from_action(Action::Pause));
+        game.render_overlay(&mut overlay);
+        assert_eq!(
+            overlay.get_cell(Coordinate::from_array([10, 5])),
+            Some((Rgb::BLACK, DIM_ALPHA))
+        );
+        game.tick(&InputState::from_action(Action::Pause));
+        game.render_overlay(&mut overlay);
+        assert_eq!(overlay.get_cell(Coordinate::from_array([10, 5])), None);
+    }
+
+    #[test]
+    fn test_sprint() {
+        // A scripted sprint finishes on the tick that clears the 40th line, and its time enters the sprint table.
+        let settings = Settings {
+            mode: GameMode::SPRINT,
+            clear_frames: 0,
+            ..Default::default()
+        };
+        let mut game = Game::new(settings, 2023);
+        start(&mut game);
+        // The clock stands still while paused.
+        game.tick(&InputState::from_action(Action::Pause));
+        for _ in 0..100 {
+            game.tick(&InputState::default());
+        }
+        game.tick(&InputState::from_action(Action::Pause));
+        assert_eq!(game.get_clock().elapsed_centis(TICK_HZ), 0);
+        let mut finished = None;
+        while finished.is_none() {
+            for input in plan(&game) {
+                let lines = game.get_score().get_lines();
+                let output = game.tick(&input);
+                if let Some(outcome) = output.finished {
+                    assert!(lines < 40);
+                    assert!(game.get_score().get_lines() >= 40);
+                    assert_eq!(outcome.ticks, game.get_ticks());
+                    assert!(output.events.contains(&GameEvent::Finished {
+                        outcome,
+                        centis: ticks_to_centis(outcome.ticks, TICK_HZ),
+                    }));
+                    // The first time always makes it into the table, so a name is entered first.
+                    assert!(matches!(output.state, GameState::NameEntry(_)));
+                    finished = Some(outcome);
+                    break;
+                }
+                assert_eq!(output.state, GameState::Playing);
+            }
+        }
+        let outcome = finished.unwrap();
+        for _ in 0..3 {
+            game.tick(&InputState::default());
+            game.tick(&InputState::from_action(Action::RotateCw));
+        }
+        assert_eq!(game.get_state(), GameState::Finished(outcome));
+        let entry = game.get_sprint_times().iter().next().unwrap();
+        assert_eq!(entry.score, ticks_to_centis(outcome.ticks, TICK_HZ));
+        assert_eq!(game.get_high_scores().iter().count(), 0);
+        // The finished game no longer advances.
+        let ticks = game.get_ticks();
+        game.tick(&InputState::from_action(Action::HardDrop));
+        assert_eq!(game.get_ticks(), ticks);
+        assert_eq!(game.get_score().get_level(), 1);
+    }
+
+    #[test]
+    fn test_ultra() {
+        // An ultra finishes after exactly its tick budget, even with a piece mid-air.
+        let settings = Settings {
+            mode: GameMode::Ultra { ticks: 100 },
+            ..Default::default()
+        };
+        let mut game = Game::new(settings, 7);
+        start(&mut game);
+        for _ in 0..99 {
+            assert_eq!(game.tick(&InputState::default()).finished, None);
+        }
+        assert!(!game.get_piece().is_grounded(game.get_board()));
+        let output = game.tick(&InputState::default());
+        assert_eq!(
+            output.finished,
+            Some(Outcome {
+                ticks: 100,
+                points: 0,
+                lines: 0
+            })
+        );
+    }
+
+    #[test]
+    fn test_ultra_overflow() {
+        // The tick counter carries on past `u32::MAX` instead of wrapping, so the longest ultra still finishes.
+        let settings = Settings {
+            mode: GameMode::Ultra { ticks: u32::MAX },
+            ..Default::default()
+        };
+        let mut game = Game::new(settings, 7);
+        start(&mut game);
+        game.clock = GameClock::from_ticks(u32::MAX as u64 - 1);
+        assert_eq!(
+            game.tick(&InputState::default())
+                .finished
+                .map(|outcome| outcome.ticks),
+            Some(u32::MAX as u64)
+        );
+        let mut game = Game::new(Settings::default(), 7);
+        start(&mut game);
+        game.clock = GameClock::from_ticks(u32::MAX as u64);
+        game.tick(&InputState::default());
+        assert_eq!(game.get_ticks(), u32::MAX as u64 + 1);
+    }
+
+    #[test]
+    fn test_clearing() {
+        // Complete the bottom row with a flat I piece, which flashes for 8 frames before it is removed.
+        // ..........
+        // ...IIII...
+        // XXX....XXX
+        let settings = Settings {
+            clear_frames: 8,
+            ..Default::default()
+        };
+        let mut game = Game::new(settings, 7);
+        start(&mut game);
+        for (col, width) in [(0, 3), (7, 3)] {
+            game.board.fill_mask(
+                &Array2D::filled_with(true, 1, width),
+                Coordinate { row: 19, col },
+                Cell::locked(TetrominoShape::Z),
+            );
+        }
+        game._spawn(TetrominoShape::I);
+        let output = game.tick(&InputState::from_action(Action::HardDrop));
+        assert_eq!(output.locked.map(|event| event.lines), Some(1));
+        assert_eq!(game.get_score().get_lines(), 1);
+        let hash = game.board_hash();
+        let ticks = game.get_ticks();
+        let mut frame = FrameBuffer::<320>::new(Coordinate::from_array([20, 16]));
+        let white = Some(Rgb::WHITE);
+        let colored = Some(Rgb::from(Color::from(TetrominoShape::Z)).scaled(LOCKED_LUM));
+        for frames_left in (1..=8).rev() {
+            assert_eq!(
+                game.get_state(),
+                GameState::Clearing {
+                    rows_mask: 1 << 19,
+                    frames_left
+                }
+            );
+            // The rows alternate every 4 frames, starting white.
+            game.render_into(&mut frame);
+            let expected = match frames_left > 4 {
+                true => white,
+                false => colored,
+            };
+            assert_eq!(frame.get_pixel(Coordinate { row: 19, col: 0 }), expected);
+            assert_eq!(game.board_hash(), hash);
+            // Input other than pausing is ignored.
+            game.tick(&InputState::from_action(Action::Hold));
+        }
+        assert_eq!(game.get_state(), GameState::Playing);
+        assert_eq!(game.get_ticks(), ticks + 8);
+        assert_eq!(game.get_hold().get_shape(), None);
+        assert_ne!(game.board_hash(), hash);
+        assert!(!game.get_board().is_filled(Coordinate { row: 19, col: 0 }));
+    }
+
+    #[test]
+    fn test_das() {
+        // Holding right shifts once, waits for the DAS and then repeats every ARR ticks until the wall.
+        let mut game = Game::new(Settings::default(), 7);
+        start(&mut game);
+        let col = game.get_piece().get_coord().col;
+        let mut input = InputState::default();
+        let mut cols = Vec::new();
+        for _ in 0..16 {
+            input = input.next(&[Action::Right]);
+            game.tick(&input);
+            cols.push(game.get_piece().get_coord().col - col);
+        }
+        let wall =
+            game.get_settings().cols - game.get_piece().get_tetromino().get_shape().col - col;
+        let expected: Vec<usize> = [1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 3, 3, 4, 4]
+            .into_iter()
+            .map(|shift| shift.min(wall))
+            .collect();
+        assert_eq!(cols, expected);
+        // Releasing and pressing again shifts back once.
+        input = input.next(&[]);
+        game.tick(&input);
+        input = input.next(&[Action::Left]);
+        game.tick(&input);
+        assert_eq!(game.get_piece().get_coord().col - col, expected[15] - 1);
+    }
+
+    #[test]
+    fn test_press_edges() {
+        // Holding rotate or hold acts only once, holding hard drop drops a single piece.
+        let mut game = Game::new(Settings::default(), 7);
+        start(&mut game);
+        let mut input = InputState::default();
+        let shape = game.get_piece().get_tetromino().shape;
+        for _ in 0..5 {
+            input = input.next(&[Action::Hold]);
+            game.tick(&input);
+        }
+        assert_eq!(game.get_hold().get_shape(), Some(shape));
+        assert_ne!(game.get_piece().get_tetromino().shape, TetrominoShape::O);
+        for _ in 0..5 {
+            input = input.next(&[Action::RotateCw]);
+            game.tick(&input);
+        }
+        assert_eq!(
+            game.get_piece().get_tetromino().get_rotation(),
+            RotationState::Right
+        );
+        let mut locked = 0;
+        for _ in 0..5 {
+            input = input.next(&[Action::HardDrop]);
+            locked += game.tick(&input).locked.iter().count();
+        }
+        assert_eq!(locked, 1);
+    }
+
+    #[test]
+    fn test_das_across_spawn() {
+        // A direction held while the completed rows flash is charged when the next piece spawns.
+        // ..........
+        // ...IIII...
+        // XXX....XXX
+        let mut game = Game::new(Settings::default(), 7);
+        start(&mut game);
+        for (col, width) in [(0, 3), (7, 3)] {
+            game.board.fill_mask(
+                &Array2D::filled_with(true, 1, width),
+                Coordinate { row: 19, col },
+                Cell::locked(TetrominoShape::Z),
+            );
+        }
+        game._spawn(TetrominoShape::I);
+        let mut input = InputState::from_action(Action::HardDrop);
+        game.tick(&input);
+        while game.get_state() != GameState::Playing {
+            input = input.next(&[Action::Right]);
+            game.tick(&input);
+        }
+        let col = game.get_piece().get_coord().col;
+        input = input.next(&[Action::Right]);
+        game.tick(&input);
+        input = input.next(&[Action::Right]);
+        game.tick(&input);
+        // The 16 frames of flashing exceed the DAS, so the piece repeats every other tick straight away.
+        assert_eq!(game.get_piece().get_coord().col, col + 1);
+    }
+
+    #[test]
+    fn test_name_entry() {
+        // A marathon game that tops out with points enters the initials "BZ" + "A" for the high-score table.
+        let mut game = Game::new(Settings::default(), 2023);
+        start(&mut game);
+        while game.get_score().get_points() == 0 {
+            for input in plan(&game) {
+                game.tick(&input);
+            }
+            settle(&mut game);
+        }
+        while !matches!(game.get_state(), GameState::NameEntry(_)) {
+            game.tick(&InputState::from_action(Action::HardDrop));
+        }
+        assert_eq!(
+            game.get_state(),
+            GameState::NameEntry(NameEntry {
+                initials: *b"AAA",
+                cursor: 0
+            })
+        );
+        let mut input = InputState::default();
+        for held in [
+            Action::Right,
+            Action::RotateCw,
+            Action::Left,
+            Action::RotateCw,
+            Action::RotateCcw,
+            Action::RotateCw,
+            Action::RotateCw,
+        ] {
+            input = input.next(&[]);
+            game.tick(&input);
+            input = input.next(&[held]);
+            game.tick(&input);
+        }
+        assert_eq!(game.get_state(), GameState::GameOver(Cause::BlockOut));
+        let entry = game.get_high_scores().iter().next().unwrap();
+        assert_eq!(entry.initials, *b"BZA");
+        assert_eq!(entry.score, game.get_score().get_points());
+        // The table survives a restart.
+        game.tick(&InputState::from_action(Action::Start));
+        assert_eq!(game.get_high_scores().iter().count(), 1);
+    }
+
+    #[test]
+    fn test_lifetime() {
+        // A topped out game adds its lines and time to the lifetime totals, which survive a restart.
+        let mut game = Game::new(Settings::default(), 2023);
+        start(&mut game);
+        while game.get_score().get_lines() == 0 {
+            for input in plan(&game) {
+                game.tick(&input);
+            }
+            settle(&mut game);
+        }
+        let lines = game.get_score().get_lines();
+        assert_eq!(game.get_lifetime_stats().get_lines(), 0);
+        while !matches!(
+            game.get_state(),
+            GameState::GameOver(_) | GameState::NameEntry(_)
+        ) {
+            game.tick(&InputState::from_action(Action::HardDrop));
+        }
+        let lifetime = *game.get_lifetime_stats();
+        assert_eq!((lifetime.get_games(), lifetime.get_lines()), (1, lines));
+        assert!(lifetime.get_centis() > 0);
+        game.restart();
+        assert_eq!(*game.get_lifetime_stats(), lifetime);
+        // A game abandoned halfway does not count.
+        start(&mut game);
+        while game.get_score().get_lines() == 0 {
+            for input in plan(&game) {
+                game.tick(&input);
+            }
+            settle(&mut game);
+        }
+        game.restart();
+        assert_eq!(*game.get_lifetime_stats(), lifetime);
+    }
+
+    #[test]
+    fn test_replay() {
+        // Record 1000 ticks of random but scripted input and replay them to the same board.
+        let settings = Settings::default();
+        let mut game = Game::new(settings, 42);
+        let mut replay = Replay::new(settings, 42, MAX_REPLAY_WORDS);
+        let mut script = GameRng::new(1);
+        let actions = [
+            Action::Left,
+            Action::Right,
+            Action::RotateCw,
+            Action::RotateCcw,
+            Action::SoftDrop,
+            Action::HardDrop,
+            Action::Hold,
+        ];
+        let mut input = InputState::default().next(&[Action::Start]);
+        for _ in 0..1000 {
+            assert!(replay.record(&input));
+            game.tick(&input);
+            let held = match script.below(16) as usize {
+                index if index < actions.len() => vec![actions[index]],
+                _ => vec![],
+            };
+            input = input.next(&held);
+        }
+        assert_ne!(game.board_hash(), Game::new(settings, 42).board_hash());
+        assert!(replay.get_size() < 2000);
+        assert_eq!(
+            Game::play_replay(&replay),
+            ReplayResult {
+                ticks: 1000,
+                board_hash: game.board_hash(),
+                points: game.get_score().get_points(),
+                lines: game.get_score().get_lines(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_save_state() {
+        // Save halfway through scripted input, restore, and feed the rest to both games.
+        let settings = Settings::default();
+        let mut game = Game::new(settings, 42);
+        let mut script = GameRng::new(7);
+        let actions = [
+            Action::Left,
+            Action::Right,
+            Action::RotateCw,
+            Action::RotateCcw,
+            Action::SoftDrop,
+            Action::HardDrop,
+            Action::Hold,
+        ];
+        let mut inputs = vec![InputState::default().next(&[Action::Start])];
+        for _ in 1..2000 {
+            let held = match script.below(16) as usize {
+                index if index < actions.len() => vec![actions[index]],
+                _ => vec![],
+            };
+            inputs.push(inputs[inputs.len() - 1].next(&held));
+        }
+        for input in &inputs[..1000] {
+            game.tick(input);
+        }
+        let mut buffer = [0; 1024];
+        let len = game.save_state_into(&mut buffer).unwrap();
+        assert_eq!(len, save_state_bytes(settings.rows, settings.cols));
+        assert!(len < 1024);
+        let mut restored = Game::restore_state(&buffer[..len], None).unwrap();
+        assert_eq!(restored.board_hash(), game.board_hash());
+        assert_eq!(restored.get_state(), game.get_state());
+        for input in &inputs[1000..] {
+            game.tick(input);
+            restored.tick(input);
+        }
+        assert_ne!(game.board_hash(), Game::new(settings, 42).board_hash());
+        assert_eq!(restored.board_hash(), game.board_hash());
+        assert_eq!(
+            restored.get_score().get_points(),
+            game.get_score().get_points()
+        );
+        assert_eq!(
+            restored.get_score().get_lines(),
+            game.get_score().get_lines()
+        );
+        assert_eq!(restored.get_state(), game.get_state());
+        // Saving the restored game gives the same bytes as saving the original.
+        let mut again = [0; 1024];
+        game.save_state_into(&mut buffer).unwrap();
+        restored.save_state_into(&mut again).unwrap();
+        assert_eq!(buffer, again);
+        // The same state restores on a fixed board of the same size.
+        let fixed =
+            Game::restore_state_on(FixedBoard::<Cell, 20, 10>::new(Cell::Empty), &buffer, None);
+        assert_eq!(fixed.unwrap().board_hash(), game.board_hash());
+    }
+
+    #[test]
+    fn test_save_state_errors() {
+        let settings = Settings::default();
+        let mut game = Game::new(settings, 42);
+        game.tick(&InputState::from_action(Action::Start));
+        let mut buffer = [0; 1024];
+        let len = save_state_bytes(settings.rows, settings.cols);
+        assert_eq!(
+            game.save_state_into(&mut buffer[..len - 1]),
+            Err(SaveError::BufferTooSmall(len).into())
+        );
+        game.save_state_into(&mut buffer).unwrap();
+        assert!(Game::restore_state(&buffer, None).is_ok());
+        let override_settings = Settings { das: 5, ..settings };
+        assert!(Game::restore_state(&buffer, Some(override_settings)).is_ok());
+        assert_eq!(
+            Game::restore_state(&buffer[..len - 1], None).err(),
+            Some(SaveError::Truncated(len - 1).into())
+        );
+        let wider = Settings {
+            cols: 12,
+            ..settings
+        };
+        assert_eq!(
+            Game::restore_state(&buffer, Some(wider)).err(),
+            Some(SaveError::BoardSize { rows: 20, cols: 10 }.into())
+        );
+        assert_eq!(
+            Game::restore_state_on(FixedBoard::<Cell, 22, 10>::new(Cell::Empty), &buffer, None)
+                .err(),
+            Some(SaveError::BoardSize { rows: 20, cols: 10 }.into())
+        );
+        let mut corrupt = buffer;
+        corrupt[100] ^= 1;
+        assert_eq!(
+            Game::restore_state(&corrupt, None).err(),
+            Some(SaveError::Crc.into())
+        );
+        let mut newer = buffer;
+        newer[4] += 1;
+        assert_eq!(
+            Game::restore_state(&newer, None).err(),
+            Some(SaveError::Version(SAVE_VERSION + 1).into())
+        );
+        assert_eq!(
+            Game::restore_state(&[0; 8], None).err(),
+            Some(SaveError::Magic.into())
+        );
+        // The attract mode is no game to resume.
+        let mut demo = Game::new(settings, 42);
+        while demo.attract.is_none() {
+            demo.tick(&InputState::default());
+        }
+        assert_eq!(
+            demo.save_state_into(&mut buffer),
+            Err(SaveError::Attract.into())
+        );
+    }
+
+    #[test]
+    fn test_save_state_level() {
+        // A level set from the console restores with the level it started from, and advances alike after it.
+        let settings = Settings::default();
+        let mut game = Game::new(settings, 42);
+        game.tick(&InputState::from_action(Action::Start));
+        game.set_level(5);
+        let mut buffer = [0; 1024];
+        let len = game.save_state_into(&mut buffer).unwrap();
+        let mut restored = Game::restore_state(&buffer[..len], None).unwrap();
+        assert_eq!(restored.get_score(), game.get_score());
+        while !game.is_game_over() {
+            let input = InputState::from_action(Action::HardDrop);
+            game.tick(&input);
+            restored.tick(&input);
+            game.tick(&InputState::default());
+            restored.tick(&InputState::default());
+        }
+        assert_eq!(restored.get_score(), game.get_score());
+    }
+
+    #[test]
+    fn test_apply_raw() {
+        // Arbitrary bytes, like the fuzz target feeds them, keep every game valid, see `fuzz/`.
+        for seed in 0..20 {
+            let mut game = Game::new(Settings::default(), seed);
+            let mut bytes = GameRng::new(seed + 100);
+            let mut locked = 0;
+            for tick in 0..3000 {
+                let byte = match bytes.below(16) {
+                    0 => 0x82,
+                    1 => bytes.below(256) as u8,
+                    _ => bytes.below(128) as u8,
+                };
+                locked += game.apply_raw(byte).locked.is_some() as usize;
+                assert_eq!(game.validate(), Ok(()), "seed {} tick {}", seed, tick);
+            }
+            assert!(locked > 10);
+        }
+        // A held byte presses its actions only once.
+        let mut game = Game::new(Settings::default(), 42);
+        game.apply_raw(0x82);
+        assert!(matches!(game.get_state(), GameState::Countdown(_)));
+        game.apply_raw(0x81);
+        assert_eq!(game.get_state(), GameState::Paused);
+        game.apply_raw(0x81);
+        assert_eq!(game.get_state(), GameState::Paused);
+    }
+
+    #[test]
+    fn test_hold_block_out() {
+        // Holding spawns a piece into a full spawn area, the game ends without moving or locking it.
+        let mut game = Game::new(Settings::default(), 42);
+        start(&mut game);
+        game.piece = ActivePiece::new(
+            Tetromino::from(TetrominoShape::I),
+            Coordinate::from_array([10, 3]),
+        );
+        for row in 0..3 {
+            for col in 0..10 {
+                game.board.set(Coordinate { row, col }, Cell::Garbage);
+            }
+        }
+        let hash = game.board_hash();
+        let mut input = InputState::from_action(Action::Hold);
+        input.press(Action::HardDrop);
+        let output = game.tick(&input);
+        assert_eq!(
+            game.get_state(),
+            GameState::Sweep {
+                cause: Cause::BlockOut,
+                ticks: 0
+            }
+        );
+        assert_eq!(output.locked, None);
+        assert_eq!(game.board_hash(), hash);
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut game = Game::new(Settings::default(), 42);
+        assert_eq!(game.validate(), Ok(()));
+        // Holding is only possible while a piece is in play.
+        game.hold.swap(TetrominoShape::T);
+        assert_eq!(game.validate(), Err(InvariantViolation::HoldLocked));
+        let mut game = Game::new(Settings::default(), 42);
+        start(&mut game);
+        assert_eq!(game.validate(), Ok(()));
+        // A cell of the stack under the falling piece, also while paused.
+        let coord = game.get_piece().get_coord() + [1, 1];
+        game.board.set(coord, Cell::Garbage);
+        assert!(!game.piece.fits(&game.board));
+        assert_eq!(game.validate(), Err(InvariantViolation::PieceOverlapsStack));
+        game.state = GameState::Paused;
+        game.resume = GameState::Playing;
+        assert_eq!(game.validate(), Err(InvariantViolation::PieceOverlapsStack));
+        // But not after a block out, where the piece spawned into the stack.
+        game.state = GameState::GameOver(Cause::BlockOut);
+        assert_eq!(game.validate(), Ok(()));
+        game.piece = ActivePiece::new(
+            Tetromino::from(TetrominoShape::I),
+            Coordinate::from_array([18, 8]),
+        );
+        assert_eq!(game.validate(), Err(InvariantViolation::PieceOutOfBounds));
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "the active piece overlaps the stack")]
+    fn test_validate_tick() {
+        let mut game = Game::new(Settings::default(), 42);
+        start(&mut game);
+        let coord = game.get_piece().get_coord() + [1, 1];
+        game.board.set(coord, Cell::Garbage);
+        game.tick(&InputState::default());
+    }
+
+    #[test]
+    fn test_same_seed() {
+        // Two games with the same seed and inputs deal the same pieces and build the same boards.
+        let mut games = [
+            Game::new(Settings::default(), 1234),
+            Game::new(Settings::default(), 1234),
+        ];
+        assert_eq!(games[0].seed(), 1234);
+        let mut script = GameRng::new(5);
+        let mut input = InputState::default().next(&[Action::Start]);
+        let mut shapes = Vec::new();
+        for _ in 0..10_000 {
+            for game in games.iter_mut() {
+                game.tick(&input);
+            }
+            let [first, second] = &games;
+            assert_eq!(first.board_hash(), second.board_hash());
+            assert_eq!(
+                first.get_piece().get_tetromino().shape,
+                second.get_piece().get_tetromino().shape
+            );
+            assert!(first.get_queue().peek(7).eq(second.get_queue().peek(7)));
+            shapes.push(first.get_piece().get_tetromino().shape);
+            input = match script.below(8) {
+                0 => input.next(&[Action::HardDrop]),
+                1 => input.next(&[Action::Start]),
+                2 => input.next(&[Action::Left]),
+                3 => input.next(&[Action::RotateCw]),
+                _ => input.next(&[]),
+            };
+        }
+        shapes.dedup();
+        assert!(shapes.len() > 100);
+    }
+
+    #[test]
+    fn test_fixed_board() {
+        // A game on a fixed-size board plays exactly like one on a heap-allocated board.
+        let mut game = Game::new(Settings::default(), 2023);
+        let mut fixed = Game::with_board(
+            FixedBoard::<Cell, 20, 10>::new(Cell::Empty),
+            Settings::default(),
+            2023,
+        );
+        let mut inputs = vec![InputState::from_action(Action::Start)];
+        while game.get_score().get_lines() < 10 {
+            for input in inputs.drain(..) {
+                assert_eq!(game.tick(&input), fixed.tick(&input));
+                assert_eq!(game.board_hash(), fixed.board_hash());
+            }
+            inputs = match game.get_state() {
+                GameState::Playing => plan(&game),
+                _ => vec![InputState::default()],
+            };
+        }
+        assert_eq!(
+            game.get_score().get_points(),
+            fixed.get_score().get_points()
+        );
+    }
+
+    #[test]
+    fn test_render_garbage() {
+        // The meter over the right border shows the rows left after cancelling, steady until the warning starts.
+        let mut game = Game::new(Settings::default(), 7);
+        start(&mut game);
+        game.receive_garbage(5);
+        assert_eq!(game.garbage.cancel(2), 0);
+        let meter = |game: &Game| {
+            let mut frame = FrameBuffer::<320>::new(Coordinate::from_array([20, 16]));
+            game.render_into(&mut frame);
+            (0..20)
+                .map(|row| frame.get_pixel(Coordinate { row, col: 10 }).unwrap())
+                .collect::<Vec<Rgb>>()
+        };
+        let border = game.get_palette().border;
+        let mut expected = vec![border; 17];
+        expected.extend([GARBAGE_COLOR; 3]);
+        assert_eq!(meter(&game), expected);
+        for _ in 0..GARBAGE_DELAY - GARBAGE_WARNING_TICKS - 1 {
+            game.tick(&InputState::default());
+            assert_eq!(meter(&game), expected);
+        }
+        assert_eq!(
+            game.get_garbage().ticks_until_application(),
+            Some(GARBAGE_WARNING_TICKS + 1)
+        );
+        // From the threshold on, the meter flashes between red and the border until a piece locks.
+        let mut colors = Vec::new();
+        for _ in 0..GARBAGE_WARNING_TICKS + 1 {
+            game.tick(&InputState::default());
+            assert!(game.get_garbage().is_imminent());
+            colors.push(meter(&game)[19]);
+        }
+        assert!(colors.contains(&GARBAGE_COLOR) && colors.contains(&border));
+        game.tick(&InputState::from_action(Action::HardDrop));
+        assert_eq!(meter(&game), vec![border; 20]);
+    }
+
+    #[test]
+    fn test_finesse() {
+        // Tapping right and back left before dropping in the spawn column wastes both taps.
+        let settings = Settings {
+            finesse: true,
+            ..Default::default()
+        };
+        let mut game = Game::new(settings, 7);
+        start(&mut game);
+        for action in [Action::Right, Action::Left] {
+            game.tick(&InputState::from_action(action));
+            game.tick(&InputState::default());
+        }
+        assert_eq!(game.get_finesse().get_inputs(), 2);
+        let output = game.tick(&InputState::from_action(Action::HardDrop));
+        assert!(output
+            .events
+            .contains(&GameEvent::FinesseFault { excess: 2 }));
+        // Dropping the next piece right away is as few inputs as it gets.
+        settle(&mut game);
+        let output = game.tick(&InputState::from_action(Action::HardDrop));
+        assert!(output.locked.is_some());
+        assert!(!output
+            .events
+            .iter()
+            .any(|event| matches!(event, GameEvent::FinesseFault { .. })));
+        assert_eq!(game.get_finesse().get_faults(), 1);
+        // After the game, the fault shows in the middle of the field as a 1, lit in its middle column.
+        game.state = GameState::GameOver(Cause::BlockOut);
+        let mut frame = FrameBuffer::<320>::new(Coordinate::from_array([20, 16]));
+        game.render_into(&mut frame);
+        let color = game.get_palette().get_color(TetrominoShape::Z);
+        assert_eq!(frame.get_pixel(Coordinate::from_array([7, 4])), Some(color));
+        game.settings.finesse = false;
+        game.render_into(&mut frame);
+        assert_ne!(frame.get_pixel(Coordinate::from_array([7, 4])), Some(color));
+    }
+
+    #[test]
+    fn test_garbage() {
+        // Incoming garbage waits for its delay and is only applied when a piece locks without clearing.
+        let mut game = GameFixture::new(Settings::default())
+            .board_str(
+                "
+                IIII..IIII
+                ",
+            )
+            .active(
+                TetrominoShape::O,
+                RotationState::Spawn,
+                Coordinate::from_array([0, 4]),
+            )
+            .queue(&[TetrominoShape::T])
+            .build()
+            .unwrap();
+        game.receive_garbage(3);
+        for _ in 0..GARBAGE_DELAY - 1 {
+            game.tick(&InputState::default());
+        }
+        let hash = game.board_hash();
+        game.tick(&InputState::default());
+        assert_eq!(game.board_hash(), hash);
+        assert_eq!(game.get_garbage().pending_lines(), 3);
+        // The O clears the bottom row, which keeps the garbage waiting for the next lock.
+        let output = game.tick(&InputState::from_action(Action::HardDrop));
+        assert_eq!(output.sent, 0);
+        assert_eq!(game.get_garbage().pending_lines(), 3);
+        settle(&mut game);
+        game.tick(&InputState::from_action(Action::HardDrop));
+        assert_eq!(game.get_garbage().pending_lines(), 0);
+        let garbage = game
+            .get_board()
+            .get_array()
+            .elements_row_major_iter()
+            .filter(|cell| **cell == Cell::Garbage)
+            .count();
+        assert_eq!(garbage, 3 * 9);
+        // A tetris cancels the single queued row and sends the other 3.
+        game.receive_garbage(1);
+        let event = ClearEvent {
+            lines: 4,
+            t_spin: TSpin::None,
+        };
+        assert_eq!(game.garbage.cancel(attack(&event, false, false)), 3);
+    }
+
+    #[test]
+    fn test_cheese() {
+        // The cheese rows keep counting after other clears and incoming garbage moved them.
+        let settings = Settings {
+            mode: GameMode::Cheese {
+                rows: 2,
+                regenerate: false,
+            },
+            clear_frames: 0,
+            ..Default::default()
+        };
+        let mut game = Game::new(settings, 11);
+        assert_eq!(game.get_cheese().get_remaining(), 2);
+        let garbage = game
+            .board
+            .cells()
+            .filter(|(_, cell)| *cell == Cell::Garbage)
+            .count();
+        assert_eq!(garbage, 2 * 9);
+        start(&mut game);
+        // An opponent row pushes the cheese up to rows 17 and 18.
+        game.receive_garbage(1);
+        for _ in 0..GARBAGE_DELAY {
+            game.tick(&InputState::default());
+        }
+        game.spawn(TetrominoShape::O);
+        game.tick(&InputState::from_action(Action::HardDrop));
+        assert_eq!(game.get_cheese().get_remaining(), 2);
+        // Clearing the opponent row drops the cheese back down, without counting.
+        let output = dig(&mut game, 19);
+        assert_eq!(output.locked.map(|event| event.lines), Some(1));
+        assert_eq!(game.get_cheese().get_remaining(), 2);
+        // The shifted cheese row still counts.
+        let output = dig(&mut game, 19);
+        assert_eq!(game.get_cheese().get_remaining(), 1);
+        assert_eq!(output.finished, None);
+        // Clearing the last cheese row finishes the race.
+        let output = dig(&mut game, 19);
+        assert_eq!(game.get_cheese().get_remaining(), 0);
+        let outcome = output.finished.unwrap();
+        assert!(output.events.contains(&GameEvent::Finished {
+            outcome,
+            centis: ticks_to_centis(outcome.ticks, TICK_HZ),
+        }));
+        assert_eq!(game.get_state(), GameState::Finished(outcome));
+    }
+
+    #[test]
+    fn test_cheese_regenerate() {
+        // Every cleared cheese row is replaced by a garbage row that does not count.
+        let settings = Settings {
+            mode: GameMode::Cheese {
+                rows: 2,
+                regenerate: true,
+            },
+            clear_frames: 0,
+            ..Default::default()
+        };
+        let mut game = Game::new(settings, 11);
+        start(&mut game);
+        let garbage_rows = |game: &Game| {
+            (0..20)
+                .filter(|&row| {
+                    (0..10)
+                        .any(|col| game.board.get(Coordinate { row, col }) == Some(Cell::Garbage))
+                })
+                .count()
+        };
+        dig(&mut game, 19);
+        assert_eq!(game.get_cheese().get_remaining(), 1);
+        assert_eq!(garbage_rows(&game), 2);
+        // The remaining cheese row moved up above the regenerated row.
+        let output = dig(&mut game, 18);
+        assert!(output.finished.is_some());
+        assert_eq!(garbage_rows(&game), 2);
+    }
+
+    #[test]
+    fn test_debug_timers() {
+        // Holding left charges its auto-shift, then the landed piece counts down its lock delay and its resets.
+        let settings = Settings {
+            max_lock_resets: 1,
+            debug: DebugOverlay::ALL,
+            ..Default::default()
+        };
+        let mut game = Game::new(settings, 7);
+        start(&mut game);
+        let mut input = InputState::default();
+        for tick in 0..=settings.das {
+            input = input.next(&[Action::Left]);
+            game.tick(&input);
+            let charge = (tick * 255 / settings.das) as u8;
+            assert_eq!(game.das_progress(Action::Left), charge);
+            assert_eq!(game.das_progress(Action::Right), 0);
+        }
+        assert_eq!(game.das_progress(Action::Left), 255);
+        input = input.next(&[]);
+        game.tick(&input);
+        assert_eq!(game.das_progress(Action::Left), 0);
+        while game.get_piece().get_coord() != game.get_piece().ghost_coord(game.get_board()) {
+            assert_eq!(game.lock_delay_progress(), 0);
+            game.tick(&input);
+        }
+        for tick in 1..=5 {
+            game.tick(&input);
+            let progress = (tick * 255 / settings.lock_delay) as u8;
+            assert_eq!(game.lock_delay_progress(), progress);
+        }
+        // The gauges fill the left column and the row under the border.
+        let mut frame = FrameBuffer::<640>::new(Coordinate::from_array([32, 20]));
+        game.render_into(&mut frame);
+        let gauge = Coordinate::from_array([settings.rows + 1, 0]);
+        assert_eq!(frame.get_pixel(gauge), Some(GAUGE_COLOR));
+        assert_eq!(frame.get_pixel(gauge + [0, 1]), Some(Rgb::BLACK));
+        // Shifting the grounded piece restarts the lock delay, using the only reset, a tick later it counts again.
+        assert_eq!(game.resets_used(), 0);
+        game.tick(&input.next(&[Action::Right]));
+        assert_eq!(game.resets_used(), 1);
+        let progress = (255 / settings.lock_delay) as u8;
+        assert_eq!(game.lock_delay_progress(), progress);
+        // The piece is tinted after its last reset.
+        game.render_into(&mut frame);
+        let shape = game.get_piece().get_tetromino().shape;
+        let color = game.get_palette().get_color(shape);
+        for coord in game.get_piece().get_cells() {
+            assert_eq!(
+                frame.get_pixel(coord),
+                Some(color.lerp(Rgb::WHITE, FINAL_RESET_TINT))
+            );
+        }
+    }
+
+    #[test]
+    fn test_input_history() {
+        // The history only shows the actions that took effect, a shift into the wall is not recorded.
+        let settings = Settings {
+            input_display: true,
+            ..Default::default()
+        };
+        let mut game = Game::new(settings, 7);
+        start(&mut game);
+        game.tick(&InputState::from_action(Action::RotateCw));
+        let mut input = InputState::default();
+        for _ in 0..10 {
+            input = input.next(&[Action::Left]);
+            game.tick(&input);
+        }
+        let actions: Vec<Action> = game
+            .get_history()
+            .iter()
+            .map(|(action, _)| action)
+            .collect();
+        assert_eq!(actions[0], Action::Left);
+        assert_eq!(
+            actions[actions.len() - 2..],
+            [Action::RotateCw, Action::Start]
+        );
+        let shifts = game.get_history().get_piece().shifts as usize;
+        assert_eq!(actions.len(), 2 + shifts);
+        assert!(shifts < 10);
+        game.tick(&InputState::from_action(Action::HardDrop));
+        assert_eq!(game.get_history().get_last_piece().rotations, 1);
+        assert_eq!(
+            game.get_history().iter().next(),
+            Some((Action::HardDrop, 0))
+        );
+        // The hard drop is drawn below the bottom border.
+        let mut frame = FrameBuffer::<{ 32 * 64 }>::new(Coordinate::from_array([64, 32]));
+        game.render_into(&mut frame);
+        let pixel = frame.get_pixel(Coordinate::from_array([22, 0])).unwrap();
+        assert_eq!(pixel, Rgb::WHITE);
+    }
+
+    #[test]
+    fn test_countdown_das() {
+        // The countdown takes exactly 3 configured steps, ignores input but precharges the auto-repeat.
+        let settings = Settings {
+            countdown_ticks: 20,
+            ..Default::default()
+        };
+        let mut game = Game::new(settings, 7);
+        let coord = game.get_piece().get_coord();
+        let mut input = InputState::default().next(&[Action::Start]);
+        assert_eq!(game.tick(&input).overlay, Overlay::Countdown(3));
+        let mut overlays = Vec::new();
+        for _ in 0..60 {
+            input = input.next(&[Action::Right, Action::RotateCw]);
+            overlays.push(game.tick(&input).overlay);
+        }
+        assert_eq!(overlays[18], Overlay::Countdown(3));
+        assert_eq!(overlays[19], Overlay::Countdown(2));
+        assert_eq!(overlays[39], Overlay::Countdown(1));
+        assert_eq!(overlays[59], Overlay::None);
+        assert_eq!(game.get_state(), GameState::Playing);
+        assert_eq!(game.get_piece().get_coord(), coord);
+        // Held through the countdown, the first playing tick already repeats.
+        input = input.next(&[Action::Right]);
+        game.tick(&input);
+        assert_eq!(game.get_piece().get_coord().col, coord.col + 1);
+        assert_eq!(
+            game.get_piece().get_tetromino().get_rotation(),
+            RotationState::Spawn
+        );
+    }
+
+    #[test]
+    fn test_sweep() {
+        // Topping out fills the board from the bottom up for exactly the configured ticks.
+        let mut game = Game::new(Settings::default(), 7);
+        start(&mut game);
+        while matches!(
+            game.get_state(),
+            GameState::Playing | GameState::Clearing { .. }
+        ) {
+            game.tick(&InputState::from_action(Action::HardDrop));
+        }
+        assert_eq!(
+            game.get_state(),
+            GameState::Sweep {
+                cause: Cause::BlockOut,
+                ticks: 0
+            }
+        );
+        let outputs: Vec<TickOutput> = (0..60)
+            .map(|_| game.tick(&InputState::from_action(Action::Start)))
+            .collect();
+        assert_eq!(outputs[0].overlay, Overlay::Sweep(0));
+        assert_eq!(outputs[29].overlay, Overlay::Sweep(10));
+        assert!(matches!(outputs[58].state, GameState::Sweep { .. }));
+        assert_eq!(outputs[59].state, GameState::GameOver(Cause::BlockOut));
+        let mut frame = FrameBuffer::<320>::new(Coordinate::from_array([20, 16]));
+        game.tick(&InputState::default());
+        game.render_into(&mut frame);
+        assert_ne!(
+            frame.get_pixel(Coordinate { row: 0, col: 0 }),
+            Some(Rgb::from_array(&[96, 96, 96]))
+        );
+    }
+
+    /// Collect the dispatched events, like the sound layer of the firmware would consume them.
+    #[derive(Default)]
+    struct Recorder {
+        events: Vec<GameEvent>,
+    }
+
+    impl EventSink for Recorder {
+        fn on_event(&mut self, event: &GameEvent) {
+            self.events.push(*event);
+        }
+    }
+
+    #[test]
+    fn test_events() {
+        // Three tetrises in a well: the second and third are back-to-back, the third crosses level 2.
+        let settings = Settings {
+            clear_frames: 0,
+            ..Default::default()
+        };
+        let mut game = Game::new(settings, 7);
+        start(&mut game);
+        let mut recorder = Recorder::default();
+        for tetris in 0..3 {
+            game._spawn(TetrominoShape::I);
+            game.tick(&InputState::from_action(Action::RotateCw));
+            let well = game.get_piece().get_coord().col;
+            for col in (0..10).filter(|&col| col != well) {
+                game.board.fill_mask(
+                    &Array2D::filled_with(true, 4, 1),
+                    Coordinate { row: 16, col },
+                    Cell::locked(TetrominoShape::O),
+                );
+            }
+            let output = game.tick(&InputState::from_action(Action::HardDrop));
+            output.dispatch(&mut recorder);
+            let mut expected = vec![
+                GameEvent::PieceLocked {
+                    shape: TetrominoShape::I,
+                    cells: [16, 17, 18, 19].map(|row| [row, well as u8]),
+                },
+                GameEvent::LinesCleared {
+                    n: 4,
+                    t_spin: TSpin::None,
+                    b2b: tetris > 0,
+                },
+                GameEvent::RowsCleared {
+                    rows_mask: 0b1111 << 16,
+                    cols: 10,
+                },
+            ];
+            if tetris == 2 {
+                expected.push(GameEvent::LevelUp { level: 2 });
+            }
+            assert_eq!(output.events.to_vec(), expected);
+        }
+        assert_eq!(recorder.events.len(), 10);
+        // Holding reports the swap.
+        let output = game.tick(&InputState::from_action(Action::Hold));
+        assert_eq!(output.events.to_vec(), vec![GameEvent::PieceHeld]);
+    }
+
+    #[test]
+    fn test_keymap() {
+        // With left and right swapped in the settings, the first button moves the piece right.
+        let mut keymap = KeyMap::default();
+        keymap.set(0, Action::Right);
+        let settings = Settings {
+            keymap,
+            ..Settings::default()
+        };
+        let mut game = Game::new(settings, 7);
+        let mut buttons = ButtonInput::new(game.get_menu().get_settings().keymap, 1);
+        let mut press = |game: &mut Game, sample: u16| {
+            buttons.sample(sample);
+            game.tick(&buttons.next_input());
+            buttons.sample(0);
+            game.tick(&buttons.next_input());
+        };
+        press(&mut game, 1 << 8);
+        settle(&mut game);
+        game._spawn(TetrominoShape::T);
+        let col = |game: &Game| {
+            game.get_piece()
+                .get_cells()
+                .map(|cell| cell.col)
+                .iter()
+                .min()
+                .copied()
+        };
+        let spawned = col(&game);
+        press(&mut game, 1 << 0);
+        assert_eq!(col(&game), spawned.map(|col| col + 1));
+        press(&mut game, 1 << 1);
+        press(&mut game, 1 << 1);
+        assert_eq!(col(&game), spawned.map(|col| col - 1));
+    }
+
+    #[test]
+    fn test_t_spin_double() {
+        // The T in its right state rotates clockwise into the slot and locks, clearing the bottom 2 rows.
+        let settings = Settings {
+            clear_frames: 0,
+            ..Default::default()
+        };
+        let mut game = GameFixture::new(settings)
+            .board_str(
+                "
+                ..........
+                OO........
+                O...OOOOOO
+                OO.OOOOOOO
+                ",
+            )
+            .active(
+                TetrominoShape::T,
+                RotationState::Right,
+                Coordinate::from_array([17, 2]),
+            )
+            .build()
+            .unwrap();
+        let output = game.tick(&InputState::from_action(Action::RotateCw));
+        assert_eq!(
+            output.events.to_vec(),
+            vec![GameEvent::Rotated { kicked: false }]
+        );
+        let piece = game.get_piece();
+        assert_eq!(piece.get_coord(), Coordinate::from_array([18, 1]));
+        assert_eq!(
+            (piece.get_last_movement(), piece.get_kick_index()),
+            (Movement::Rotate, Some(0))
+        );
+        let output = game.tick(&InputState::from_action(Action::HardDrop));
+        assert!(output.events.contains(&GameEvent::LinesCleared {
+            n: 2,
+            t_spin: TSpin::Full,
+            b2b: false
+        }));
+        assert_eq!(game.get_score().get_lines(), 2);
+    }
+
+    #[test]
+    fn test_rotation_events() {
+        let mut game = Game::new(Settings::default(), 7);
+        start(&mut game);
+        // Rotating in place on an empty board.
+        game._spawn(TetrominoShape::T);
+        let output = game.tick(&InputState::from_action(Action::RotateCw));
+        assert_eq!(
+            output.events.to_vec(),
+            vec![GameEvent::Rotated { kicked: false }]
+        );
+        // Against the left wall, rotating back to spawn needs a kick.
+        while game.piece.try_shift_left(&game.board) {}
+        let output = game.tick(&InputState::from_action(Action::RotateCcw));
+        assert_eq!(
+            output.events.to_vec(),
+            vec![GameEvent::Rotated { kicked: true }]
+        );
+        // Enclosed by the stack, every kick fails.
+        game._spawn(TetrominoShape::T);
+        let cells = game.get_piece().get_cells();
+        let shape = game.board.get_shape();
+        for row in 0..shape.row {
+            for col in 0..shape.col {
+                let coord = Coordinate { row, col };
+                if !cells.contains(&coord) {
+                    game.board.set(coord, Cell::locked(TetrominoShape::O));
+                }
+            }
+        }
+        let output = game.tick(&InputState::from_action(Action::RotateCw));
+        assert_eq!(output.events.to_vec(), vec![GameEvent::RotationFailed]);
+    }
+
+    /// Hold the reset chord for twice as long as it takes, returning the ticks it abandoned the game on.
+    /// # Arguments
+    /// - `game` - A muteable reference to the `Game`
+    /// - `before` - The state the game is expected in on the tick before the chord fires, with nothing held
+    fn hold_reset(game: &mut Game, before: GameState) -> Vec<u32> {
+        let mut input = InputState::default();
+        let mut aborted = Vec::new();
+        let hold = game.get_hold().get_shape();
+        for tick in 0..2 * RESET_TICKS {
+            if tick == RESET_TICKS - 1 {
+                assert_eq!(game.get_state(), before);
+                assert_eq!(game.get_hold().get_shape(), hold);
+            }
+            input = input.next(&RESET_CHORD);
+            let output = game.tick(&input);
+            if output
+                .events
+                .iter()
+                .any(|event| matches!(event, GameEvent::Aborted { .. }))
+            {
+                aborted.push(tick);
+            }
+        }
+        aborted
+    }
+
+    #[test_case(GameState::Countdown(2), GameState::Countdown(2); "countdown")]
+    #[test_case(GameState::Playing, GameState::Playing; "playing")]
+    #[test_case(GameState::Paused, GameState::Paused; "paused")]
+    #[test_case(GameState::Clearing { rows_mask: 1 << 19, frames_left: 8 }, GameState::Playing; "clearing")]
+    #[test_case(GameState::Sleep, GameState::Playing; "sleep")]
+    #[test_case(GameState::GameOver(Cause::TopOut), GameState::GameOver(Cause::TopOut); "game over")]
+    #[test_case(GameState::NameEntry(NameEntry { initials: *b"BEN", cursor: 1 }), GameState::NameEntry(NameEntry { initials: *b"BEN", cursor: 1 }); "name entry")]
+    #[test_case(GameState::Menu, GameState::Menu; "menu")]
+    fn test_reset_chord(phase: GameState, before: GameState) {
+        // Holding the chord abandons the game once from any phase, back on the mode select of the mode played.
+        // Until then, pressing it neither starts, unpauses, restarts nor holds.
+        let settings = Settings {
+            mode: GameMode::ULTRA,
+            ..Settings::default()
+        };
+        let mut game = GameFixture::new(settings)
+            .board_str("IIII..IIII")
+            .score(1200, 8, 1)
+            .phase(phase)
+            .build()
+            .unwrap();
+        if phase == GameState::Menu {
+            // The menu is where the chord returns to, holding it there neither starts a game nor leaves the screen.
+            game.menu.skip_splash();
+            let mut input = InputState::default();
+            for _ in 0..2 * RESET_TICKS {
+                input = input.next(&RESET_CHORD);
+                assert!(game.tick(&input).events.is_empty());
+            }
+            assert_eq!(game.get_state(), before);
+            assert!(matches!(game.get_menu().get_screen(), Screen::Main(0)));
+            // Start on its own still starts one.
+            game.tick(&InputState::default().next(&[Action::Start]));
+            assert_eq!(game.get_state(), GameState::Countdown(COUNTDOWN_STEPS));
+            return;
+        }
+        assert_eq!(hold_reset(&mut game, before), [RESET_TICKS - 1]);
+        assert_eq!(game.get_state(), GameState::Menu);
+        assert!(matches!(
+            game.get_menu().get_screen(),
+            Screen::ModeSelect(2)
+        ));
+        assert_eq!(game.get_settings().mode, GameMode::ULTRA);
+        assert!(game
+            .get_board()
+            .cells()
+            .all(|(_, cell)| cell == Cell::Empty));
+        assert_eq!(game.get_lifetime_stats(), &LifetimeStats::new());
+        // A game entering a name already ended, so it keeps its entry.
+        let entered = game
+            .get_high_scores()
+            .iter()
+            .any(|entry| entry.initials == *b"BEN");
+        assert_eq!(entered, matches!(phase, GameState::NameEntry(_)));
+    }
+
+    #[test]
+    fn test_reset_lifetime() {
+        // The lines of an abandoned game count neither towards the totals nor towards the next game.
+        let mut totals = LifetimeStats::new();
+        totals.fold(
+            &GameEvent::GameOver {
+                cause: Cause::BlockOut,
+                centis: 6000,
+            },
+            GameMode::Marathon,
+        );
+        let mut game = GameFixture::new(Settings::default())
+            .board_str("IIII..IIII")
+            .active(
+                TetrominoShape::O,
+                RotationState::Spawn,
+                Coordinate::from_array([0, 4]),
+            )
+            .build()
+            .unwrap();
+        game.set_lifetime_stats(totals);
+        let output = game.tick(&InputState::from_action(Action::HardDrop));
+        assert!(output
+            .events
+            .iter()
+            .any(|event| matches!(event, GameEvent::LinesCleared { n: 1, .. })));
+        while game.get_state() != GameState::Playing {
+            game.tick(&InputState::default());
+        }
+        assert_eq!(hold_reset(&mut game, GameState::Playing), [RESET_TICKS - 1]);
+        assert_eq!(game.get_lifetime_stats(), &totals);
+        // The next game only counts itself.
+        game.tick(&InputState::from_action(Action::Start));
+        while !game.is_game_over() {
+            game.tick(&InputState::from_action(Action::HardDrop));
+            game.tick(&InputState::default());
+        }
+        let stats = game.get_lifetime_stats();
+        assert_eq!((stats.get_games(), stats.get_lines()), (2, 0));
+    }
+}
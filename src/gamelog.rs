@@ -0,0 +1,370 @@
+//! A log of what happened during the recent ticks, to explain a reported glitch after the fact, e.g. "it locked
+//! early".
+//!
+//! The game appends a `LogRecord` whenever something happened: an action that took effect, an event, a change of
+//! the state, the active piece landing, restarting its lock delay or locking. Quiet ticks add nothing. Every record
+//! takes 8 bytes, so the `LOG_ENTRIES` of a game take 4 KiB, and the oldest record is dropped for a new one. The
+//! console prints the last records with `log`, and `GameLog::explain_lock()` reconstructs the last lock from them.
+use crate::events::GameEvent;
+use crate::game::GameState;
+use crate::input::Action;
+use crate::scoring::TSpin;
+use core::fmt;
+
+/// The number of records kept by the log of a game.
+pub const LOG_ENTRIES: usize = 512;
+
+/// The state of the game without its data, see `GameState`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StateKind {
+    Menu,
+    Countdown,
+    Playing,
+    Clearing,
+    Paused,
+    Sleep,
+    Sweep,
+    GameOver,
+    Finished,
+    NameEntry,
+}
+
+impl StateKind {
+    /// Get the kind of a state.
+    /// # Arguments
+    /// - `state` - A reference to the `GameState`
+    /// # Returns
+    /// - `StateKind` - The kind of the state
+    pub fn of(state: &GameState) -> Self {
+        match state {
+            GameState::Menu => StateKind::Menu,
+            GameState::Countdown(_) => StateKind::Countdown,
+            GameState::Playing => StateKind::Playing,
+            GameState::Clearing { .. } => StateKind::Clearing,
+            GameState::Paused => StateKind::Paused,
+            GameState::Sleep => StateKind::Sleep,
+            GameState::Sweep { .. } => StateKind::Sweep,
+            GameState::GameOver(_) => StateKind::GameOver,
+            GameState::Finished(_) => StateKind::Finished,
+            GameState::NameEntry(_) => StateKind::NameEntry,
+        }
+    }
+}
+
+/// Something that happened during a tick, packed into 4 bytes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LogEntry {
+    /// An action of the player took effect.
+    Action(Action),
+    /// The game entered a state of another kind.
+    State(StateKind),
+    /// The active piece touched down, with the top-left element of its mask at [row, col], starting its lock delay.
+    Landed { row: u8, col: u8 },
+    /// Moving the grounded piece restarted its lock delay, `used` times for this piece.
+    Reset { used: u8 },
+    /// The active piece locked with the top-left element of its mask at [row, col], after `resets` restarts.
+    Locked { row: u8, col: u8, resets: u8 },
+    /// The locked piece cleared `lines` rows.
+    Cleared { lines: u8, t_spin: TSpin, b2b: bool },
+    /// The level advanced.
+    LevelUp(u8),
+    /// Incoming garbage rows were pushed onto the board, saturating at `u16::MAX`.
+    Garbage(u16),
+}
+
+impl LogEntry {
+    /// Get the entry of an event, for the events not logged otherwise.
+    /// # Arguments
+    /// - `event` - A reference to the `GameEvent`
+    /// # Returns
+    /// - `Option<LogEntry>` - The entry, or `None` for the events logged as actions, locks or states
+    pub fn from_event(event: &GameEvent) -> Option<Self> {
+        match *event {
+            GameEvent::LinesCleared { n, t_spin, b2b } => Some(LogEntry::Cleared {
+                lines: n as u8,
+                t_spin,
+                b2b,
+            }),
+            GameEvent::LevelUp { level } => Some(LogEntry::LevelUp(level)),
+            GameEvent::GarbageReceived { lines } => {
+                Some(LogEntry::Garbage(lines.min(u16::MAX as u32) as u16))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogEntry::Action(action) => write!(f, "action {:?}", action),
+            LogEntry::State(kind) => write!(f, "state {:?}", kind),
+            LogEntry::Landed { row, col } => write!(f, "landed at {},{}", row, col),
+            LogEntry::Reset { used } => write!(f, "lock reset {}", used),
+            LogEntry::Locked { row, col, resets } => {
+                write!(f, "locked at {},{} after {} resets", row, col, resets)
+            }
+            LogEntry::Cleared { lines, t_spin, b2b } => {
+                write!(f, "cleared {} lines, t-spin {:?}", lines, t_spin)?;
+                match b2b {
+                    true => f.write_str(", back-to-back"),
+                    false => Ok(()),
+                }
+            }
+            LogEntry::LevelUp(level) => write!(f, "level {}", level),
+            LogEntry::Garbage(lines) => write!(f, "garbage {} rows", lines),
+        }
+    }
+}
+
+/// A `LogEntry` with the tick it happened on.
+/// # Attributes
+/// - `tick` - The tick of the game, see `Game::get_frames()`
+/// - `entry` - What happened
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LogRecord {
+    pub tick: u32,
+    pub entry: LogEntry,
+}
+
+/// The last lock reconstructed from the log.
+/// # Attributes
+/// - `landed` - The tick the piece last touched down, `None` if it was hard dropped from the air
+/// - `resets` - The number of times moving the grounded piece restarted its lock delay
+/// - `last_reset` - The tick of the last restart, if any
+/// - `locked` - The tick the piece locked on
+/// - `hard_drop` - Whether (`true`) or not (`false`) a hard drop locked the piece, instead of its lock delay
+///   expiring
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LockTrace {
+    pub landed: Option<u32>,
+    pub resets: u8,
+    pub last_reset: Option<u32>,
+    pub locked: u32,
+    pub hard_drop: bool,
+}
+
+/// The recent records of a game, dropping the oldest record for a new one once `N` records are kept.
+/// # Attributes
+/// - `records` - The records, oldest first
+/// - `state` - The kind of the state at the end of the last observed tick
+/// - `grounded` - Whether the active piece was grounded at the end of the last observed tick
+/// - `resets` - The lock delay restarts of the active piece at the end of the last observed tick
+#[derive(Debug)]
+pub struct GameLog<const N: usize> {
+    records: heapless::Deque<LogRecord, N>,
+    state: Option<StateKind>,
+    grounded: bool,
+    resets: u8,
+}
+
+impl<const N: usize> GameLog<N> {
+    /// Create an empty log.
+    /// # Returns
+    /// - `GameLog<N>` - The log without any records
+    pub fn new() -> Self {
+        GameLog {
+            records: heapless::Deque::new(),
+            state: None,
+            grounded: false,
+            resets: 0,
+        }
+    }
+
+    /// Append a record, dropping the oldest record if the log is full.
+    /// # Arguments
+    /// - `tick` - The tick the entry happened on
+    /// - `entry` - The `LogEntry`
+    pub fn push(&mut self, tick: u32, entry: LogEntry) {
+        if self.records.is_full() {
+            self.records.pop_front();
+        }
+        self.records
+            .push_back(LogRecord { tick, entry })
+            .expect("the oldest record was dropped to make room");
+    }
+
+    /// Log what changed since the end of the last observed tick: the kind of the state, the active piece landing
+    /// and the restarts of its lock delay.
+    /// # Arguments
+    /// - `tick` - The tick that just ended
+    /// - `state` - A reference to the `GameState` at the end of the tick
+    /// - `grounded` - The [row, col] of the active piece if it rests on the stack while playing, `None` otherwise
+    /// - `resets` - The number of lock delay restarts of the active piece
+    pub fn observe(&mut self, tick: u32, state: &GameState, grounded: Option<[u8; 2]>, resets: u8) {
+        let kind = StateKind::of(state);
+        if self.state != Some(kind) {
+            self.push(tick, LogEntry::State(kind));
+            self.state = Some(kind);
+        }
+        if let (Some([row, col]), false) = (grounded, self.grounded) {
+            self.push(tick, LogEntry::Landed { row, col });
+        }
+        self.grounded = grounded.is_some();
+        // The restarts only count down when the next piece spawns, which the lock already logged.
+        if resets > self.resets {
+            self.push(tick, LogEntry::Reset { used: resets });
+        }
+        self.resets = resets;
+    }
+
+    /// Get the number of records.
+    /// # Returns
+    /// - `usize` - The number of kept records, at most `N`
+    pub fn get_len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Iterate over the records.
+    /// # Returns
+    /// - `impl Iterator<Item = &LogRecord>` - The records, oldest first
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &LogRecord> {
+        self.records.iter()
+    }
+
+    /// Reconstruct the last lock: when the piece landed, how often its lock delay restarted and when it locked.
+    /// # Returns
+    /// - `Option<LockTrace>` - The last lock, or `None` if no lock is logged
+    pub fn explain_lock(&self) -> Option<LockTrace> {
+        let mut records = self.records.iter().rev();
+        let (locked, resets) = records.find_map(|record| match record.entry {
+            LogEntry::Locked { resets, .. } => Some((record.tick, resets)),
+            _ => None,
+        })?;
+        let mut trace = LockTrace {
+            landed: None,
+            resets,
+            last_reset: None,
+            locked,
+            hard_drop: false,
+        };
+        // Walk back from the lock to the lock of the previous piece.
+        for record in self.records.iter().rev() {
+            if record.tick > locked {
+                continue;
+            }
+            match record.entry {
+                LogEntry::Locked { .. } if record.tick < locked => break,
+                LogEntry::Action(Action::HardDrop) if record.tick == locked => {
+                    trace.hard_drop = true
+                }
+                LogEntry::Reset { .. } if trace.last_reset.is_none() => {
+                    trace.last_reset = Some(record.tick)
+                }
+                LogEntry::Landed { .. } if trace.landed.is_none() => {
+                    trace.landed = Some(record.tick)
+                }
+                _ => (),
+            }
+        }
+        Some(trace)
+    }
+
+    /// Write the last records, a line per record separated by `\n` without a trailing line ending.
+    /// # Arguments
+    /// - `out` - A muteable reference to the writer
+    /// - `count` - The number of records to write, the newest ones
+    /// # Returns
+    /// - `fmt::Result` - The error of the writer, if any
+    pub fn report_into(&self, out: &mut dyn fmt::Write, count: usize) -> fmt::Result {
+        let skip = self.records.len().saturating_sub(count);
+        for (index, record) in self.records.iter().skip(skip).enumerate() {
+            if index > 0 {
+                out.write_char('\n')?;
+            }
+            write!(out, "{:>8} {}", record.tick, record.entry)?;
+        }
+        Ok(())
+    }
+}
+
+impl<const N: usize> Default for GameLog<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GameLog, LogEntry, LogRecord, StateKind};
+    use crate::game::{Game, GameState};
+    use crate::input::{Action, InputState};
+    use crate::settings::Settings;
+
+    #[test]
+    fn test_ring() {
+        assert_eq!(core::mem::size_of::<LogRecord>(), 8);
+        let mut log = GameLog::<4>::new();
+        assert_eq!(log.explain_lock(), None);
+        for level in 1..=6 {
+            log.push(level as u32 * 10, LogEntry::LevelUp(level));
+        }
+        // The two oldest records were dropped for the newest ones.
+        assert_eq!(log.get_len(), 4);
+        let ticks: Vec<u32> = log.iter().map(|record| record.tick).collect();
+        assert_eq!(ticks, [30, 40, 50, 60]);
+        let mut report = String::new();
+        log.report_into(&mut report, 2).unwrap();
+        assert_eq!(report, "      50 level 5\n      60 level 6");
+        report.clear();
+        log.report_into(&mut report, 10).unwrap();
+        assert_eq!(report.lines().count(), 4);
+        // Only changes of the state are logged.
+        log.observe(61, &GameState::Playing, None, 0);
+        log.observe(62, &GameState::Playing, None, 0);
+        assert_eq!(
+            log.iter().last(),
+            Some(&LogRecord {
+                tick: 61,
+                entry: LogEntry::State(StateKind::Playing)
+            })
+        );
+    }
+
+    #[test]
+    fn test_early_lock() {
+        // Shifting the grounded piece every 10 ticks would keep it alive forever, but only 2 shifts restart its
+        // lock delay, so it locks 30 ticks after the second one.
+        let settings = Settings {
+            lock_delay: 30,
+            max_lock_resets: 2,
+            ..Settings::default()
+        };
+        let mut game = Game::new(settings, 5);
+        game.tick(&InputState::from_action(Action::Start));
+        while game.get_state() != GameState::Playing {
+            game.tick(&InputState::default());
+        }
+        let mut ticks = 0;
+        while game.get_log().explain_lock().is_none() {
+            let action = match (ticks % 10, ticks / 10 % 2) {
+                (0, 0) if ticks > 0 => Action::Left,
+                (0, _) if ticks > 0 => Action::Right,
+                _ => Action::SoftDrop,
+            };
+            game.tick(&InputState::from_action(action));
+            ticks += 1;
+        }
+        let trace = game.get_log().explain_lock().unwrap();
+        let (landed, last_reset) = (trace.landed.unwrap(), trace.last_reset.unwrap());
+        assert_eq!(trace.resets, 2);
+        assert!(!trace.hard_drop);
+        assert!(landed < last_reset);
+        assert_eq!(trace.locked, game.get_frames());
+        assert_eq!(trace.locked - last_reset, 29);
+        // The shifts after the second restart did not restart the lock delay.
+        let shifts = game
+            .get_log()
+            .iter()
+            .filter(|record| record.tick > last_reset)
+            .filter(|record| matches!(record.entry, LogEntry::Action(Action::Left | Action::Right)))
+            .count();
+        assert_eq!(shifts, 2);
+        let resets = game
+            .get_log()
+            .iter()
+            .filter(|record| matches!(record.entry, LogEntry::Reset { .. }))
+            .count();
+        assert_eq!(resets, 2);
+    }
+}
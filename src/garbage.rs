@@ -0,0 +1,430 @@
+use crate::error::TetrisError;
+use crate::invariants::MAX_ROWS;
+use crate::rng::{GameRng, RngSource};
+use crate::savestate::{Persist, Reader, SaveError, Writer};
+use crate::scoring::{is_difficult, ClearEvent, TSpin};
+
+/// The number of ticks incoming garbage waits before it can be applied, half a second at 60 ticks per second.
+pub const GARBAGE_DELAY: u32 = 30;
+
+/// The number of ticks before incoming garbage can be applied from which its meter flashes as a warning.
+pub const GARBAGE_WARNING_TICKS: u32 = 12;
+
+/// The maximum number of batches of incoming garbage waiting at once, more rows join the newest batch.
+pub const MAX_BATCHES: usize = 16;
+
+/// The chance in percent that the gap moves to another column for the next garbage row.
+const GAP_REROLL_PERCENT: u32 = 30;
+
+/// The number of garbage rows sent for a clear.
+///
+/// Singles send nothing, doubles 1, triples 2 and tetrises 4. T-spins send twice their lines,
+/// mini T-spins their lines minus one. A back-to-back clear sends 1 more, a perfect clear always sends 10.
+/// # Arguments
+/// - `event` - A reference to the `ClearEvent` of the locked piece
+/// - `back_to_back` - Whether (`true`) or not (`false`) the clear continued a back-to-back chain
+/// - `perfect_clear` - Whether (`true`) or not (`false`) the clear left the board empty
+/// # Returns
+/// - `u32` - The number of garbage rows to send
+pub fn attack(event: &ClearEvent, back_to_back: bool, perfect_clear: bool) -> u32 {
+    let lines = event.lines as u32;
+    if lines == 0 {
+        return 0;
+    }
+    if perfect_clear {
+        return 10;
+    }
+    let base = match event.t_spin {
+        TSpin::None => match lines {
+            1 => 0,
+            2 => 1,
+            3 => 2,
+            _ => 4,
+        },
+        TSpin::Mini => lines - 1,
+        TSpin::Full => 2 * lines,
+    };
+    match back_to_back && is_difficult(event) {
+        true => base + 1,
+        false => base,
+    }
+}
+
+#[derive(Debug)]
+pub struct GarbageQueue {
+    /// The incoming garbage of a player, waiting to be applied when a piece locks without clearing.
+    /// # Attributes
+    /// - `pending` - The batches of incoming rows and the ticks left until they can be applied, oldest first
+    pending: heapless::Deque<(u32, u32), MAX_BATCHES>,
+}
+
+impl GarbageQueue {
+    /// Create an empty queue.
+    pub fn new() -> Self {
+        GarbageQueue {
+            pending: heapless::Deque::new(),
+        }
+    }
+
+    /// Get the total number of queued rows, ready or not.
+    pub fn pending_lines(&self) -> u32 {
+        self.pending.iter().map(|(lines, _)| lines).sum()
+    }
+
+    /// Get the number of ticks until the oldest queued rows can be applied.
+    /// # Returns
+    /// - `Option<u32>` - The ticks left, 0 once the rows wait for a piece to lock without clearing, or `None` if
+    ///   nothing is queued
+    pub fn ticks_until_application(&self) -> Option<u32> {
+        self.pending.front().map(|(_, delay)| *delay)
+    }
+
+    /// Get whether the oldest queued rows are within `GARBAGE_WARNING_TICKS` of being applied.
+    /// # Returns
+    /// - `bool` - Whether (`true`) or not (`false`) the meter of the incoming garbage should flash
+    pub fn is_imminent(&self) -> bool {
+        self.ticks_until_application()
+            .is_some_and(|ticks| ticks <= GARBAGE_WARNING_TICKS)
+    }
+
+    /// Queue incoming rows, which can be applied after `GARBAGE_DELAY` ticks.
+    ///
+    /// With `MAX_BATCHES` batches queued, the rows join the newest batch instead.
+    /// # Arguments
+    /// - `lines` - The number of incoming rows
+    pub fn receive(&mut self, lines: u32) {
+        if lines > 0 {
+            if let Err((lines, _)) = self.pending.push_back((lines, GARBAGE_DELAY)) {
+                if let Some((newest, _)) = self.pending.back_mut() {
+                    *newest = newest.saturating_add(lines);
+                }
+            }
+        }
+    }
+
+    /// Advance the delay of all queued rows by a single tick.
+    pub fn tick(&mut self) {
+        for (_, delay) in self.pending.iter_mut() {
+            *delay = delay.saturating_sub(1);
+        }
+    }
+
+    /// Offset outgoing rows against the queued incoming rows, oldest first.
+    /// # Arguments
+    /// - `outgoing` - The number of rows about to be sent
+    /// # Returns
+    /// - `u32` - The number of rows left to send after cancelling
+    pub fn cancel(&mut self, mut outgoing: u32) -> u32 {
+        while let Some((lines, _)) = self.pending.front_mut() {
+            if outgoing == 0 {
+                break;
+            }
+            let cancelled = outgoing.min(*lines);
+            *lines -= cancelled;
+            outgoing -= cancelled;
+            if *lines == 0 {
+                self.pending.pop_front();
+            }
+        }
+        outgoing
+    }
+
+    /// Remove the rows whose delay has passed from the queue.
+    /// # Returns
+    /// - `u32` - The number of rows to apply
+    pub fn take_ready(&mut self) -> u32 {
+        let mut ready = 0;
+        while let Some((lines, 0)) = self.pending.front() {
+            ready += lines;
+            self.pending.pop_front();
+        }
+        ready
+    }
+}
+
+impl Default for GarbageQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug)]
+pub struct GarbageGap {
+    /// The column of the gap of garbage rows, which sticks for consecutive rows with an occasional reroll.
+    /// # Attributes
+    /// - `column` - The column of the gap of the last row, `None` before the first row
+    column: Option<usize>,
+}
+
+impl GarbageGap {
+    /// Create a gap that picks its first column at the first row.
+    pub fn new() -> Self {
+        GarbageGap { column: None }
+    }
+
+    /// Get the gap of the next garbage row.
+    /// # Arguments
+    /// - `rng` - A muteable reference to the `GameRng` of the game
+    /// - `cols` - The number of columns of the board
+    /// # Returns
+    /// - `usize` - The column of the gap
+    pub fn next(&mut self, rng: &mut GameRng, cols: usize) -> usize {
+        let column = match self.column {
+            Some(column) if rng.below(100) >= GAP_REROLL_PERCENT => column,
+            _ => self._reroll(rng, cols),
+        };
+        self.column = Some(column);
+        column
+    }
+
+    /// Get the gap of the next row of a cheese race, which always moves to another column.
+    /// # Arguments
+    /// - `rng` - A muteable reference to the `GameRng` of the game
+    /// - `cols` - The number of columns of the board
+    /// # Returns
+    /// - `usize` - The column of the gap
+    pub fn next_cheese(&mut self, rng: &mut GameRng, cols: usize) -> usize {
+        let column = self._reroll(rng, cols);
+        self.column = Some(column);
+        column
+    }
+
+    /// Pick a random column for the gap, any other column than the gap of the last row.
+    fn _reroll(&self, rng: &mut GameRng, cols: usize) -> usize {
+        match self.column {
+            Some(column) => (column + 1 + rng.below(cols as u32 - 1) as usize) % cols,
+            None => rng.below(cols as u32) as usize,
+        }
+    }
+}
+
+impl Default for GarbageGap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CheeseRows {
+    /// The garbage rows a cheese race started with, followed through clears and garbage pushed in below them.
+    /// # Attributes
+    /// - `mask` - A bit per row of the board, set for the rows still holding starting garbage, bit 0 is the top row
+    mask: u64,
+}
+
+impl CheeseRows {
+    /// Create a tracker without any garbage rows.
+    pub fn new() -> Self {
+        CheeseRows { mask: 0 }
+    }
+
+    /// Follow a garbage row pushed in from the bottom, moving the tracked rows up by one.
+    /// # Arguments
+    /// - `rows` - The number of rows of the board
+    /// - `counted` - Whether (`true`) or not (`false`) the row is starting garbage that has to be cleared
+    pub fn push(&mut self, rows: usize, counted: bool) {
+        self.mask >>= 1;
+        if counted {
+            self.mask |= 1 << (rows - 1);
+        }
+    }
+
+    /// Forget the cleared rows and move the tracked rows above them down, like `BoardOps::clear_full_rows()`.
+    /// # Arguments
+    /// - `rows_mask` - A bit per cleared row of the board
+    /// # Returns
+    /// - `u32` - The number of cleared rows of starting garbage
+    pub fn clear(&mut self, rows_mask: u64) -> u32 {
+        let cleared = (self.mask & rows_mask).count_ones();
+        let mut mask = 0;
+        let mut below = 0;
+        // Walk up from the bottom, moving every kept row down by the number of cleared rows below it.
+        for row in (0..MAX_ROWS).rev() {
+            match rows_mask & (1 << row) != 0 {
+                true => below += 1,
+                false => mask |= (self.mask >> row & 1) << (row + below),
+            }
+        }
+        self.mask = mask;
+        cleared
+    }
+
+    /// Get the number of rows of starting garbage still on the board.
+    /// # Returns
+    /// - `u32` - The number of rows left to clear
+    pub fn get_remaining(&self) -> u32 {
+        self.mask.count_ones()
+    }
+}
+
+impl Persist for GarbageQueue {
+    fn save(&self, out: &mut Writer) {
+        let start = out.get_len();
+        out.index(self.pending.len());
+        for (lines, delay) in self.pending.iter() {
+            out.u32(*lines);
+            out.u32(*delay);
+        }
+        out.pad_to(start, 1 + 8 * MAX_BATCHES);
+    }
+
+    fn restore(&mut self, input: &mut Reader) -> Result<(), TetrisError> {
+        let start = input.get_offset();
+        let len = input.index()?;
+        if len > MAX_BATCHES {
+            return Err(SaveError::Invalid.into());
+        }
+        self.pending.clear();
+        for _ in 0..len {
+            let batch = (input.u32()?, input.u32()?);
+            self.pending
+                .push_back(batch)
+                .expect("the length is checked against the capacity");
+        }
+        input.skip_to(start, 1 + 8 * MAX_BATCHES)
+    }
+}
+
+impl Persist for GarbageGap {
+    fn save(&self, out: &mut Writer) {
+        out.option_u8(self.column.map(|column| column as u8));
+    }
+
+    fn restore(&mut self, input: &mut Reader) -> Result<(), TetrisError> {
+        self.column = input.option_u8()?.map(usize::from);
+        Ok(())
+    }
+}
+
+impl Persist for CheeseRows {
+    fn save(&self, out: &mut Writer) {
+        out.u64(self.mask);
+    }
+
+    fn restore(&mut self, input: &mut Reader) -> Result<(), TetrisError> {
+        self.mask = input.u64()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        attack, CheeseRows, GarbageGap, GarbageQueue, GARBAGE_DELAY, GARBAGE_WARNING_TICKS,
+    };
+    use crate::rng::GameRng;
+    use crate::scoring::{ClearEvent, TSpin};
+    use test_case::test_case;
+
+    #[test_case(1, TSpin::None, false, false => 0; "single")]
+    #[test_case(2, TSpin::None, false, false => 1; "double")]
+    #[test_case(3, TSpin::None, false, false => 2; "triple")]
+    #[test_case(4, TSpin::None, false, false => 4; "tetris")]
+    #[test_case(4, TSpin::None, true, false => 5; "back-to-back tetris")]
+    #[test_case(2, TSpin::None, true, false => 1; "double breaks back-to-back")]
+    #[test_case(2, TSpin::Full, false, false => 4; "t-spin double")]
+    #[test_case(3, TSpin::Full, true, false => 7; "back-to-back t-spin triple")]
+    #[test_case(1, TSpin::Mini, false, false => 0; "mini t-spin single")]
+    #[test_case(2, TSpin::None, false, true => 10; "perfect clear")]
+    #[test_case(0, TSpin::Full, true, false => 0; "t-spin without lines")]
+    fn test_attack(lines: usize, t_spin: TSpin, back_to_back: bool, perfect_clear: bool) -> u32 {
+        attack(&ClearEvent { lines, t_spin }, back_to_back, perfect_clear)
+    }
+
+    #[test]
+    fn test_cancel() {
+        let mut queue = GarbageQueue::new();
+        queue.receive(2);
+        queue.receive(3);
+        // Sending 4 cancels the first batch and 2 of the second.
+        assert_eq!(queue.cancel(4), 0);
+        assert_eq!(queue.pending_lines(), 1);
+        // Sending more than queued sends the remainder.
+        assert_eq!(queue.cancel(5), 4);
+        assert_eq!(queue.pending_lines(), 0);
+        assert_eq!(queue.ticks_until_application(), None);
+    }
+
+    #[test]
+    fn test_delay() {
+        let mut queue = GarbageQueue::new();
+        queue.receive(2);
+        for _ in 0..GARBAGE_DELAY - 1 {
+            queue.tick();
+        }
+        queue.receive(1);
+        assert_eq!(queue.ticks_until_application(), Some(1));
+        assert_eq!(queue.take_ready(), 0);
+        queue.tick();
+        assert_eq!(queue.ticks_until_application(), Some(0));
+        assert_eq!(queue.take_ready(), 2);
+        assert_eq!(queue.pending_lines(), 1);
+        assert_eq!(queue.ticks_until_application(), Some(GARBAGE_DELAY - 1));
+    }
+
+    #[test]
+    fn test_imminent() {
+        // The warning starts exactly at the threshold and lasts until the rows are applied.
+        let mut queue = GarbageQueue::new();
+        assert!(!queue.is_imminent());
+        queue.receive(2);
+        for _ in 0..GARBAGE_DELAY - GARBAGE_WARNING_TICKS - 1 {
+            queue.tick();
+        }
+        assert_eq!(
+            queue.ticks_until_application(),
+            Some(GARBAGE_WARNING_TICKS + 1)
+        );
+        assert!(!queue.is_imminent());
+        queue.tick();
+        assert!(queue.is_imminent());
+        for _ in 0..GARBAGE_DELAY {
+            queue.tick();
+        }
+        assert!(queue.is_imminent());
+        assert_eq!(queue.take_ready(), 2);
+        assert!(!queue.is_imminent());
+    }
+
+    #[test]
+    fn test_sticky_gap() {
+        // The gap mostly sticks to its column, and a reroll always moves it.
+        let mut rng = GameRng::new(2023);
+        let mut gap = GarbageGap::new();
+        let gaps: Vec<usize> = (0..100).map(|_| gap.next(&mut rng, 10)).collect();
+        let moves = gaps.windows(2).filter(|w| w[0] != w[1]).count();
+        assert!((15..45).contains(&moves));
+        assert!(gaps.iter().all(|&gap| gap < 10));
+        let mut again = GarbageGap::new();
+        let mut rng = GameRng::new(2023);
+        assert!(gaps.iter().all(|&gap| gap == again.next(&mut rng, 10)));
+    }
+
+    #[test]
+    fn test_cheese_gap() {
+        // A cheese row never has its gap in the column of the row above.
+        let mut rng = GameRng::new(7);
+        let mut gap = GarbageGap::new();
+        let gaps: Vec<usize> = (0..100).map(|_| gap.next_cheese(&mut rng, 10)).collect();
+        assert!(gaps.windows(2).all(|w| w[0] != w[1]));
+        assert!(gaps.iter().all(|&gap| gap < 10));
+    }
+
+    #[test]
+    fn test_cheese_rows() {
+        // Two cheese rows at the bottom of a 6 row board, with an opponent row pushed in below them.
+        let mut cheese = CheeseRows::new();
+        cheese.push(6, true);
+        cheese.push(6, true);
+        cheese.push(6, false);
+        assert_eq!(cheese.get_remaining(), 2);
+        // Clearing the bottom row and the row above the cheese shifts the cheese down by one.
+        assert_eq!(cheese.clear(0b100010), 0);
+        assert_eq!(cheese.get_remaining(), 2);
+        // The cheese moved from rows 3 and 4 to rows 4 and 5, the upper row drops to row 5 once the lower is cleared.
+        assert_eq!(cheese.clear(0b100000), 1);
+        assert_eq!(cheese.clear(0b010000), 0);
+        assert_eq!(cheese.clear(0b100000), 1);
+        assert_eq!(cheese.get_remaining(), 0);
+    }
+}
@@ -1,10 +1,132 @@
-#![allow(dead_code)]
-use std::iter::Iterator;
+use core::iter::Iterator;
 
-use crate::board::Board;
+use crate::board::{Board, BoardOps};
 use crate::coordinate::Coordinate;
+use crate::error::TetrisError;
+use crate::piece::{ActivePiece, PieceError};
+use crate::savestate::{Persist, Reader, Writer};
 use crate::tetrominoes::Tetromino;
 
+/// The number of ticks it takes a piece to fall one row at levels 1 to 15, at 60 ticks per second.
+/// Follows the guideline curve of (0.8 - (level - 1) * 0.007)^(level - 1) seconds per row.
+const GRAVITY_TICKS: [u32; 15] = [60, 48, 37, 28, 21, 16, 11, 8, 6, 4, 3, 2, 1, 1, 1];
+
+/// Get the number of ticks it takes a piece to fall one row.
+/// # Arguments
+/// - `level` - The current level, clamped to the gravity table
+/// # Returns
+/// - `u32` - The number of ticks per row, at least 1
+pub fn ticks_per_row(level: u8) -> u32 {
+    let index = (level as usize)
+        .saturating_sub(1)
+        .min(GRAVITY_TICKS.len() - 1);
+    GRAVITY_TICKS[index]
+}
+
+#[derive(Debug)]
+pub struct GravityEngine {
+    /// Drops the active piece over time and decides when it locks.
+    /// # Attributes
+    /// - `counter` - The number of ticks since the piece last fell a row
+    /// - `lock_timer` - The number of ticks the piece has been grounded
+    /// - `resets` - The number of times moving the grounded piece restarted the lock delay
+    /// - `lock_delay` - The number of grounded ticks before the piece locks
+    /// - `max_resets` - The number of lock delay restarts allowed per piece
+    counter: u32,
+    lock_timer: u32,
+    resets: u8,
+    lock_delay: u32,
+    max_resets: u8,
+}
+
+impl GravityEngine {
+    /// Create a new `GravityEngine`.
+    /// # Arguments
+    /// - `lock_delay` - The number of grounded ticks before the piece locks
+    /// - `max_resets` - The number of lock delay restarts allowed per piece
+    /// # Returns
+    /// - `GravityEngine` - The gravity engine, ready for a new piece
+    pub fn new(lock_delay: u32, max_resets: u8) -> Self {
+        GravityEngine {
+            counter: 0,
+            lock_timer: 0,
+            resets: 0,
+            lock_delay,
+            max_resets,
+        }
+    }
+
+    /// Reset the timers for a newly spawned piece.
+    pub fn reset(&mut self) {
+        self.counter = 0;
+        self.lock_timer = 0;
+        self.resets = 0;
+    }
+
+    /// Restart the lock delay after the player moved or rotated a grounded piece, if resets are left.
+    /// # Arguments
+    /// - `piece` - A reference to the `ActivePiece` that moved
+    /// - `board` - A reference to the `Board` object
+    pub fn on_move<T: Copy + PartialEq, B: BoardOps<T>>(&mut self, piece: &ActivePiece, board: &B) {
+        if piece.is_grounded(board) && self.lock_timer > 0 && self.resets < self.max_resets {
+            self.lock_timer = 0;
+            self.resets += 1;
+        }
+    }
+
+    /// Get how far the lock delay of the grounded piece has passed.
+    /// # Returns
+    /// - `u8` - 0 while the piece is falling, up to 255 when it locks
+    pub fn get_lock_progress(&self) -> u8 {
+        match (self.lock_timer, self.lock_delay) {
+            (0, _) => 0,
+            (_, 0) => u8::MAX,
+            (timer, delay) => (timer.min(delay) as u64 * u8::MAX as u64 / delay as u64) as u8,
+        }
+    }
+
+    /// Get the number of times the lock delay of the piece restarted.
+    /// # Returns
+    /// - `u8` - The restarts used, at most `max_resets`
+    pub fn get_resets(&self) -> u8 {
+        self.resets
+    }
+
+    /// Get the number of lock delay restarts allowed per piece.
+    /// # Returns
+    /// - `u8` - The restarts allowed
+    pub fn get_max_resets(&self) -> u8 {
+        self.max_resets
+    }
+
+    /// Advance gravity by a single tick.
+    /// # Arguments
+    /// - `piece` - A muteable reference to the `ActivePiece` to drop
+    /// - `board` - A reference to the `Board` object
+    /// - `ticks_per_row` - The number of ticks it takes the piece to fall one row
+    /// # Returns
+    /// - `bool` - Whether (`true`) or not (`false`) the piece should lock
+    pub fn step<T: Copy + PartialEq, B: BoardOps<T>>(
+        &mut self,
+        piece: &mut ActivePiece,
+        board: &B,
+        ticks_per_row: u32,
+    ) -> bool {
+        if piece.is_grounded(board) {
+            self.counter = 0;
+            self.lock_timer += 1;
+            return self.lock_timer >= self.lock_delay;
+        }
+        self.counter += 1;
+        if self.counter >= ticks_per_row {
+            self.counter = 0;
+            self.lock_timer = 0;
+            piece.try_drop(board);
+        }
+        false
+    }
+}
+
 /// Check if a tetromino is within the bounds of the board at a certain coordinate.
 /// # Arguments
 /// - `coord` - The position of the top-left element of the tetromino mask on the board
@@ -20,9 +142,9 @@ pub fn tetromino_is_in_bounds<T>(
 where
     T: Copy
         + Clone
-        + std::ops::BitAnd<T, Output = T>
-        + std::ops::BitOr<T, Output = T>
-        + std::ops::BitXor<T, Output = T>,
+        + core::ops::BitAnd<T, Output = T>
+        + core::ops::BitOr<T, Output = T>
+        + core::ops::BitXor<T, Output = T>,
 {
     (coord + tetromino.get_shape())
         .is_within_bounds(Coordinate::from_array([0, 0]), board.get_shape())
@@ -43,9 +165,9 @@ pub fn tetromino_reached_bottom<T>(
 where
     T: Copy
         + Clone
-        + std::ops::BitAnd<T, Output = T>
-        + std::ops::BitOr<T, Output = T>
-        + std::ops::BitXor<T, Output = T>,
+        + core::ops::BitAnd<T, Output = T>
+        + core::ops::BitOr<T, Output = T>
+        + core::ops::BitXor<T, Output = T>,
 {
     // TODO: check if > or >=. Ideally some mobility until trying to sink out of view.
     (coord + tetromino.get_shape()).row >= board.get_shape().row
@@ -57,72 +179,74 @@ where
 /// - `board` - A muteable reference the `Board` object
 /// - `tetromino` - A reference to the `Tetromino` object
 /// # Returns
-/// - `bool` - Whether (`true`) or not (`false`) the tetromino hit another block
-pub fn tetromino_hit<T>(coord: Coordinate, board: &Board<T>, tetromino: &Tetromino<T>) -> bool
+/// - `Result<bool, TetrisError>` - Whether (`true`) or not (`false`) the tetromino hit another block, or a
+///   `PieceError::OutOfBounds` if the tetromino exceeds the board
+pub fn tetromino_hit<T>(
+    coord: Coordinate,
+    board: &Board<T>,
+    tetromino: &Tetromino<T>,
+) -> Result<bool, TetrisError>
 where
     T: Copy
         + Clone
-        + std::cmp::PartialEq<bool>
-        + std::ops::BitAnd<T, Output = T>
-        + std::ops::BitOr<T, Output = T>
-        + std::ops::BitXor<T, Output = T>,
+        + core::cmp::PartialEq<bool>
+        + core::ops::BitAnd<T, Output = T>
+        + core::ops::BitOr<T, Output = T>
+        + core::ops::BitXor<T, Output = T>,
 {
-    let slice_ = board.slice(coord, coord + tetromino.get_shape());
-    let mut slice = slice_.unwrap();
-    slice.set_mask_and(tetromino.get_mask(), Coordinate::from_array([0, 0]));
-    // let arr = slice.get_array();
-    // let mut row_major = arr.as_row_major();
-    // let mut iter = row_major.iter_mut();
-    // let any = iter.any(|el| *el == true);
-    // any
+    let mut slice = board
+        .slice(coord, coord + tetromino.get_shape())
+        .map_err(|_| PieceError::OutOfBounds)?;
+    // The slice has the shape of the tetromino, so its mask always fits.
+    slice.set_mask_and(tetromino.get_mask(), Coordinate::from_array([0, 0]))?;
     let any = slice
         .get_array()
         .elements_row_major_iter()
         .any(|&el| el == true);
-    any
+    Ok(any)
 }
 
-/// Set the array of a `Tetromino` on the interal board state of the `Board`.
-/// # Arguments
-/// - `coord` - The position of the top-left element of the tetromino mask on the board
-/// - `value` - The value to set the tetromino mask to in the board, as the same generic in `Tetromino`
-/// - `board` - A muteable reference the `Board` object
-/// - `tetromino` - A reference to the `Tetromino` object
-/// # Returns
-/// - `Board<T>` - The internal board state after correctly setting the mask
-/// - `Error::IndicesOutOfBounds` - Raises an error when the tetromino mask cannot be set at that coordinate
+// /// Set the array of a `Tetromino` on the interal board state of the `Board`.
+// /// # Arguments
+// /// - `coord` - The position of the top-left element of the tetromino mask on the board
+// /// - `value` - The value to set the tetromino mask to in the board, as the same generic in `Tetromino`
+// /// - `board` - A muteable reference the `Board` object
+// /// - `tetromino` - A reference to the `Tetromino` object
+// /// # Returns
+// /// - `Board<T>` - The internal board state after correctly setting the mask
+// /// - `Error::IndicesOutOfBounds` - Raises an error when the tetromino mask cannot be set at that coordinate
 // pub fn set_tetromino<T>(
 //     coord: Coordinate,
-//     board: &Board<T>,
+//     board: &B,
 //     tetromino: &Tetromino<T>,
 // ) -> Result<Board<T>, Error>
 // where
 //     T: Copy
 //         + Clone
-//         + std::ops::BitXor<T, Output = T>
-//         + std::ops::BitAnd<T, Output = T>
-//         + std::ops::BitOr<T, Output = T>,
+//         + core::ops::BitXor<T, Output = T>
+//         + core::ops::BitAnd<T, Output = T>
+//         + core::ops::BitOr<T, Output = T>,
 // {
 //     let mut new = Board::from_array(board.get_array(), board.get_negative());
 //     new.set_mask(tetromino.get_mask(), coord);
 //     board.or(new.get_array())
 // }
 
-/// Drop a tetromino to the next row.
-/// # Arguments
-/// - `coord` - The position of the top-left element of the tetromino mask on the board
-/// - `board` - A muteable reference the `Board` object with some lifetime `'a`
-/// - `tetromino` - A reference to the `Tetromino` object
-/// # Returns
-/// - `Result<&`a mut Board<T>, Error> - A muteable reference to the updated board state with the same lifetime `'a` or
-/// an `array2d::Error::IndicesOutOfBounds` error.
-// pub fn drop_tetromino<T>(coord: Coordinate, board: &mut Board<T>, tetromino: &Tetromino<T>)
+// /// Drop a tetromino to the next row.
+// /// # Arguments
+// /// - `coord` - The position of the top-left element of the tetromino mask on the board
+// /// - `board` - A muteable reference the `Board` object with some lifetime `'a`
+// /// - `tetromino` - A reference to the `Tetromino` object
+// /// # Returns
+// /// - `Result<&`a mut Board<T>, Error> - A muteable reference to the updated board state with the same lifetime `'a` or
+// /// an `array2d::Error::IndicesOutOfBounds` error.
+// pub fn drop_tetromino<T>(coord: Coordinate, board: &mut B, tetromino: &Tetromino<T>)
 // where
 //     T: Copy
 //         + Clone
-//         + std::ops::BitAnd<T, Output = T>
-//         + std::ops::BitOr<T, Output = T>
-//         + std::ops::BitXor<T, Output = T>,
+//         + core::ops::BitAnd<T, Output = T>
+//         + core::ops::BitOr<T, Output = T>
+//         + core::ops::BitXor<T, Output = T>,
 // {
 //     let mut row_major = Vec::with_capacity(tetromino.get_mask().num_elements());
 //     for _ in 0..tetromino.get_shape().col {
@@ -140,18 +264,34 @@ where
 //     board.set_mask(&mask_zero_row, coord);
 // }
 
-#[cfg(test)]
+impl Persist for GravityEngine {
+    fn save(&self, out: &mut Writer) {
+        out.u32(self.counter);
+        out.u32(self.lock_timer);
+        out.u8(self.resets);
+    }
+
+    fn restore(&mut self, input: &mut Reader) -> Result<(), TetrisError> {
+        self.counter = input.u32()?;
+        self.lock_timer = input.u32()?;
+        self.resets = input.u8()?;
+        Ok(())
+    }
+}
 
+#[cfg(test)]
 mod tests {
 
-    use super::{tetromino_hit, tetromino_reached_bottom};
+    use super::{tetromino_hit, tetromino_reached_bottom, ticks_per_row, GravityEngine};
+    use crate::grid::Array2D;
     use crate::{
         board::Board,
         coordinate::Coordinate,
+        fixtures::fill_mid_game,
         // gravity::drop_tetromino,
+        piece::{ActivePiece, PieceError},
         tetrominoes::{Tetromino, TetrominoShape},
     };
-    use array2d::Array2D;
     use test_case::test_case;
 
     // TODO: define macro to expand into different test_case
@@ -203,12 +343,67 @@ mod tests {
         for rot in 0..5 {
             tetromino.rotate_cw();
             match rot % 2 == 0 {
-                true => assert!(tetromino_hit(coord, &board, &tetromino)),
-                false => assert!(!tetromino_hit(coord, &board, &tetromino)),
+                true => assert_eq!(tetromino_hit(coord, &board, &tetromino), Ok(true)),
+                false => assert_eq!(tetromino_hit(coord, &board, &tetromino), Ok(false)),
             }
         }
     }
 
+    #[test_case(TetrominoShape::I)]
+    #[test_case(TetrominoShape::O)]
+    #[test_case(TetrominoShape::T)]
+    fn test_hit_mid_game(shape: TetrominoShape) {
+        // Drop a tetromino on a half-full board: it only hits once it reaches the stack in the bottom 10 rows.
+        let mut board = Board::new(Coordinate::from_array([20, 10]), false);
+        fill_mid_game(&mut board, 10, &[true], 5);
+        let tetromino = Tetromino::from(shape);
+        let rows = tetromino.get_shape().row;
+        let hit = (0..=20 - rows)
+            .find(|row| {
+                tetromino_hit(Coordinate::from_array([*row, 3]), &board, &tetromino).unwrap()
+            })
+            .unwrap();
+        assert!(hit + rows > 10);
+        // Past the bottom, the tetromino exceeds the board.
+        assert_eq!(
+            tetromino_hit(Coordinate::from_array([20, 3]), &board, &tetromino),
+            Err(PieceError::OutOfBounds.into())
+        );
+    }
+
+    #[test]
+    fn test_gravity_engine() {
+        // Drop an O piece on a 4 row board at 2 ticks per row and a lock delay of 3 ticks.
+        let board = Board::new(Coordinate::from_array([4, 4]), false);
+        let mut piece = ActivePiece::new(
+            Tetromino::from(TetrominoShape::O),
+            Coordinate::from_array([0, 0]),
+        );
+        let mut gravity = GravityEngine::new(3, 1);
+        let locks: Vec<bool> = (0..7)
+            .map(|_| gravity.step(&mut piece, &board, 2))
+            .collect();
+        assert_eq!(piece.get_coord(), Coordinate::from_array([2, 0]));
+        assert_eq!(locks, vec![false, false, false, false, false, false, true]);
+        // Moving the grounded piece restarts the lock delay only once.
+        let mut gravity = GravityEngine::new(3, 1);
+        let mut piece = ActivePiece::new(
+            Tetromino::from(TetrominoShape::O),
+            Coordinate::from_array([2, 0]),
+        );
+        assert!(!gravity.step(&mut piece, &board, 2));
+        assert!(!gravity.step(&mut piece, &board, 2));
+        piece.try_shift_right(&board);
+        gravity.on_move(&piece, &board);
+        assert!(!gravity.step(&mut piece, &board, 2));
+        assert!(!gravity.step(&mut piece, &board, 2));
+        piece.try_shift_right(&board);
+        gravity.on_move(&piece, &board);
+        assert!(gravity.step(&mut piece, &board, 2));
+        assert_eq!(ticks_per_row(0), ticks_per_row(1));
+        assert_eq!(ticks_per_row(99), 1);
+    }
+
     // #[test_case(TetrominoShape::I)]
     // #[test_case(TetrominoShape::J)]
     // #[test_case(TetrominoShape::L)]
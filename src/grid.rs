@@ -0,0 +1,393 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::{Index, IndexMut};
+
+/// The errors of creating or writing an `Array2D`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The [row, col] is outside of the grid.
+    IndicesOutOfBounds(usize, usize),
+    /// The row major index is outside of the grid.
+    IndexOutOfBounds(usize),
+    /// The number of elements does not match the requested dimensions.
+    DimensionMismatch,
+    /// The iterator ran out before the grid was full.
+    NotEnoughElements,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Array2D<T> {
+    /// A fixed-size 2D grid stored in row major order, needing only `alloc` so it builds without `std`.
+    ///
+    /// Mirrors the API of the `array2d` crate used before, so the game logic reads the same on the
+    /// host and on the target.
+    /// # Attributes
+    /// - `array` - The elements, row after row
+    /// - `num_rows` - The number of rows
+    /// - `num_columns` - The number of columns
+    array: Vec<T>,
+    num_rows: usize,
+    num_columns: usize,
+}
+
+impl<T> Array2D<T> {
+    /// Create a grid from its rows.
+    /// # Arguments
+    /// - `elements` - The rows, all of the same length
+    /// # Returns
+    /// - `Result<Array2D<T>, Error>` - The grid or an `Error::DimensionMismatch` for ragged rows
+    pub fn from_rows(elements: &[Vec<T>]) -> Result<Self, Error>
+    where
+        T: Clone,
+    {
+        let num_columns = elements.first().map(Vec::len).unwrap_or(0);
+        if elements.iter().any(|row| row.len() != num_columns) {
+            return Err(Error::DimensionMismatch);
+        }
+        Ok(Array2D {
+            array: elements.concat(),
+            num_rows: elements.len(),
+            num_columns,
+        })
+    }
+
+    /// Create a grid from its columns.
+    /// # Arguments
+    /// - `elements` - The columns, all of the same length
+    /// # Returns
+    /// - `Result<Array2D<T>, Error>` - The grid or an `Error::DimensionMismatch` for ragged columns
+    pub fn from_columns(elements: &[Vec<T>]) -> Result<Self, Error>
+    where
+        T: Clone,
+    {
+        let num_rows = elements.first().map(Vec::len).unwrap_or(0);
+        if elements.iter().any(|column| column.len() != num_rows) {
+            return Err(Error::DimensionMismatch);
+        }
+        let array = (0..num_rows)
+            .flat_map(|r| elements.iter().map(move |column| column[r].clone()))
+            .collect();
+        Ok(Array2D {
+            array,
+            num_rows,
+            num_columns: elements.len(),
+        })
+    }
+
+    /// Create a grid from a flat slice in row major order.
+    /// # Arguments
+    /// - `elements` - The elements, row after row
+    /// - `num_rows` - The number of rows
+    /// - `num_columns` - The number of columns
+    /// # Returns
+    /// - `Result<Array2D<T>, Error>` - The grid or an `Error::DimensionMismatch` if the slice does not fill it exactly
+    pub fn from_row_major(
+        elements: &[T],
+        num_rows: usize,
+        num_columns: usize,
+    ) -> Result<Self, Error>
+    where
+        T: Clone,
+    {
+        if elements.len() != num_rows * num_columns {
+            return Err(Error::DimensionMismatch);
+        }
+        Ok(Array2D {
+            array: elements.to_vec(),
+            num_rows,
+            num_columns,
+        })
+    }
+
+    /// Create a grid from a flat slice in column major order.
+    /// # Arguments
+    /// - `elements` - The elements, column after column
+    /// - `num_rows` - The number of rows
+    /// - `num_columns` - The number of columns
+    /// # Returns
+    /// - `Result<Array2D<T>, Error>` - The grid or an `Error::DimensionMismatch` if the slice does not fill it exactly
+    pub fn from_column_major(
+        elements: &[T],
+        num_rows: usize,
+        num_columns: usize,
+    ) -> Result<Self, Error>
+    where
+        T: Clone,
+    {
+        if elements.len() != num_rows * num_columns {
+            return Err(Error::DimensionMismatch);
+        }
+        let array = (0..num_rows)
+            .flat_map(|r| (0..num_columns).map(move |c| elements[c * num_rows + r].clone()))
+            .collect();
+        Ok(Array2D {
+            array,
+            num_rows,
+            num_columns,
+        })
+    }
+
+    /// Create a grid with every element set to the same value.
+    /// # Arguments
+    /// - `element` - The value of every element
+    /// - `num_rows` - The number of rows
+    /// - `num_columns` - The number of columns
+    /// # Returns
+    /// - `Array2D<T>` - The filled grid
+    pub fn filled_with(element: T, num_rows: usize, num_columns: usize) -> Self
+    where
+        T: Clone,
+    {
+        Array2D {
+            array: vec![element; num_rows * num_columns],
+            num_rows,
+            num_columns,
+        }
+    }
+
+    /// Create a grid from an iterator in row major order, ignoring any surplus elements.
+    /// # Arguments
+    /// - `iterator` - The elements, row after row
+    /// - `num_rows` - The number of rows
+    /// - `num_columns` - The number of columns
+    /// # Returns
+    /// - `Result<Array2D<T>, Error>` - The grid or an `Error::NotEnoughElements` if the iterator runs out
+    pub fn from_iter_row_major<I: Iterator<Item = T>>(
+        iterator: I,
+        num_rows: usize,
+        num_columns: usize,
+    ) -> Result<Self, Error> {
+        let array: Vec<T> = iterator.take(num_rows * num_columns).collect();
+        if array.len() < num_rows * num_columns {
+            return Err(Error::NotEnoughElements);
+        }
+        Ok(Array2D {
+            array,
+            num_rows,
+            num_columns,
+        })
+    }
+
+    /// Create a grid from an iterator in column major order, ignoring any surplus elements.
+    /// # Arguments
+    /// - `iterator` - The elements, column after column
+    /// - `num_rows` - The number of rows
+    /// - `num_columns` - The number of columns
+    /// # Returns
+    /// - `Result<Array2D<T>, Error>` - The grid or an `Error::NotEnoughElements` if the iterator runs out
+    pub fn from_iter_column_major<I: Iterator<Item = T>>(
+        iterator: I,
+        num_rows: usize,
+        num_columns: usize,
+    ) -> Result<Self, Error>
+    where
+        T: Clone,
+    {
+        let elements: Vec<T> = iterator.take(num_rows * num_columns).collect();
+        if elements.len() < num_rows * num_columns {
+            return Err(Error::NotEnoughElements);
+        }
+        Self::from_column_major(&elements, num_rows, num_columns)
+    }
+
+    /// Get the number of rows.
+    pub fn num_rows(&self) -> usize {
+        self.num_rows
+    }
+
+    /// Get the number of columns.
+    pub fn num_columns(&self) -> usize {
+        self.num_columns
+    }
+
+    /// Get the number of elements.
+    pub fn num_elements(&self) -> usize {
+        self.array.len()
+    }
+
+    /// Get an element.
+    /// # Arguments
+    /// - `row` - The row of the element
+    /// - `column` - The column of the element
+    /// # Returns
+    /// - `Option<&T>` - A reference to the element or `None` if it is outside of the grid
+    pub fn get(&self, row: usize, column: usize) -> Option<&T> {
+        match row < self.num_rows && column < self.num_columns {
+            true => self.array.get(row * self.num_columns + column),
+            false => None,
+        }
+    }
+
+    /// Get a mutable element.
+    /// # Arguments
+    /// - `row` - The row of the element
+    /// - `column` - The column of the element
+    /// # Returns
+    /// - `Option<&mut T>` - A mutable reference to the element or `None` if it is outside of the grid
+    pub fn get_mut(&mut self, row: usize, column: usize) -> Option<&mut T> {
+        match row < self.num_rows && column < self.num_columns {
+            true => self.array.get_mut(row * self.num_columns + column),
+            false => None,
+        }
+    }
+
+    /// Get an element by its index in row major order.
+    /// # Arguments
+    /// - `index` - The index of the element, counting row after row
+    /// # Returns
+    /// - `Option<&T>` - A reference to the element or `None` if it is outside of the grid
+    pub fn get_row_major(&self, index: usize) -> Option<&T> {
+        self.array.get(index)
+    }
+
+    /// Get an element by its index in column major order.
+    /// # Arguments
+    /// - `index` - The index of the element, counting column after column
+    /// # Returns
+    /// - `Option<&T>` - A reference to the element or `None` if it is outside of the grid
+    pub fn get_column_major(&self, index: usize) -> Option<&T> {
+        match self.num_rows {
+            0 => None,
+            rows => self.get(index % rows, index / rows),
+        }
+    }
+
+    /// Overwrite an element.
+    /// # Arguments
+    /// - `row` - The row of the element
+    /// - `column` - The column of the element
+    /// - `element` - The new value
+    /// # Returns
+    /// - `Result<(), Error>` - An `Error::IndicesOutOfBounds` if the element is outside of the grid
+    pub fn set(&mut self, row: usize, column: usize, element: T) -> Result<(), Error> {
+        match self.get_mut(row, column) {
+            Some(location) => {
+                *location = element;
+                Ok(())
+            }
+            None => Err(Error::IndicesOutOfBounds(row, column)),
+        }
+    }
+
+    /// Iterate over the elements in row major order.
+    pub fn elements_row_major_iter(&self) -> impl DoubleEndedIterator<Item = &T> + Clone {
+        self.array.iter()
+    }
+
+    /// Iterate over the rows as slices, from the top. A grid without columns has no rows to iterate.
+    pub fn rows_iter(&self) -> impl Iterator<Item = &[T]> {
+        self.array.chunks_exact(self.num_columns.max(1))
+    }
+
+    /// Iterate over the rows as mutable slices, from the top. A grid without columns has no rows to iterate.
+    pub fn rows_iter_mut(&mut self) -> impl Iterator<Item = &mut [T]> {
+        self.array.chunks_exact_mut(self.num_columns.max(1))
+    }
+
+    /// Iterate over the elements in column major order.
+    pub fn elements_column_major_iter(&self) -> impl Iterator<Item = &T> {
+        (0..self.num_columns).flat_map(move |c| {
+            (0..self.num_rows).map(move |r| &self.array[r * self.num_columns + c])
+        })
+    }
+
+    /// Copy the rows into nested vectors.
+    pub fn as_rows(&self) -> Vec<Vec<T>>
+    where
+        T: Clone,
+    {
+        match self.num_columns {
+            0 => vec![Vec::new(); self.num_rows],
+            columns => self.array.chunks(columns).map(<[T]>::to_vec).collect(),
+        }
+    }
+
+    /// Copy the columns into nested vectors.
+    pub fn as_columns(&self) -> Vec<Vec<T>>
+    where
+        T: Clone,
+    {
+        (0..self.num_columns)
+            .map(|c| {
+                (0..self.num_rows)
+                    .map(|r| self.array[r * self.num_columns + c].clone())
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Copy the elements into a flat vector in row major order.
+    pub fn as_row_major(&self) -> Vec<T>
+    where
+        T: Clone,
+    {
+        self.array.clone()
+    }
+
+    /// Copy the elements into a flat vector in column major order.
+    pub fn as_column_major(&self) -> Vec<T>
+    where
+        T: Clone,
+    {
+        self.elements_column_major_iter().cloned().collect()
+    }
+}
+
+impl<T> Index<(usize, usize)> for Array2D<T> {
+    type Output = T;
+
+    fn index(&self, (row, column): (usize, usize)) -> &Self::Output {
+        self.get(row, column)
+            .unwrap_or_else(|| panic!("Indices ({}, {}) out of bounds", row, column))
+    }
+}
+
+impl<T> IndexMut<(usize, usize)> for Array2D<T> {
+    fn index_mut(&mut self, (row, column): (usize, usize)) -> &mut Self::Output {
+        self.get_mut(row, column)
+            .unwrap_or_else(|| panic!("Indices ({}, {}) out of bounds", row, column))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Array2D, Error};
+
+    #[test]
+    fn test_layouts() {
+        let rows = Array2D::from_rows(&[vec![1, 2, 3], vec![4, 5, 6]]).unwrap();
+        assert_eq!(
+            Array2D::from_columns(&[vec![1, 4], vec![2, 5], vec![3, 6]]),
+            Ok(rows.clone())
+        );
+        assert_eq!(
+            Array2D::from_column_major(&[1, 4, 2, 5, 3, 6], 2, 3),
+            Ok(rows.clone())
+        );
+        assert_eq!(rows.as_row_major(), vec![1, 2, 3, 4, 5, 6]);
+        assert_eq!(rows.as_column_major(), vec![1, 4, 2, 5, 3, 6]);
+        assert_eq!(rows.as_columns(), vec![vec![1, 4], vec![2, 5], vec![3, 6]]);
+        assert_eq!(rows.get_column_major(1), Some(&4));
+        assert_eq!(rows[(1, 2)], 6);
+        assert_eq!(
+            rows.rows_iter().collect::<Vec<_>>(),
+            vec![&[1, 2, 3][..], &[4, 5, 6][..]]
+        );
+        assert_eq!(Array2D::<u8>::filled_with(0, 2, 0).rows_iter().count(), 0);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            Array2D::from_rows(&[vec![1, 2], vec![3]]),
+            Err(Error::DimensionMismatch)
+        );
+        assert_eq!(
+            Array2D::from_iter_row_major(0..5, 2, 3),
+            Err(Error::NotEnoughElements)
+        );
+        let mut grid = Array2D::filled_with(0, 2, 2);
+        assert_eq!(grid.set(2, 0, 1), Err(Error::IndicesOutOfBounds(2, 0)));
+        assert_eq!(grid.get(0, 2), None);
+    }
+}
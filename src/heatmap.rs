@@ -0,0 +1,247 @@
+//! Where the pieces locked, counted per cell of the board for a review after the game.
+//!
+//! A `PlacementHeatmap` is an `EventSink`, counting the cells of every `GameEvent::PieceLocked`. Keep one per game,
+//! reset when a new game starts, and merge it into an all-time heatmap stored with `RecordStore::store_heatmap()`.
+//! The counts are drawn as a gradient from blue for the rarely used cells to red for the most used cell.
+use crate::color::Rgb;
+use crate::coordinate::Coordinate;
+use crate::events::{EventSink, GameEvent};
+use crate::framebuffer::FrameBuffer;
+use alloc::vec::Vec;
+
+/// The color of the least used cells that were used at all.
+const COLD: Rgb = Rgb::from_array(&[0, 0, 255]);
+
+/// The color of the most used cell.
+const HOT: Rgb = Rgb::from_array(&[255, 0, 0]);
+
+/// Get the number of bytes of a serialized heatmap.
+/// # Arguments
+/// - `rows` - The number of rows of the heatmap
+/// - `cols` - The number of columns of the heatmap
+/// # Returns
+/// - `usize` - The number of bytes, the shape followed by a little endian `u16` per cell
+pub const fn heatmap_bytes(rows: usize, cols: usize) -> usize {
+    2 + 2 * rows * cols
+}
+
+/// Get the color of a normalized count.
+/// # Arguments
+/// - `level` - The count relative to the largest count, from 0 (unused) to 255 (the most used)
+/// # Returns
+/// - `Rgb` - The color, from blue to red
+pub fn heat_color(level: u8) -> Rgb {
+    COLD.lerp(HOT, level)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PlacementHeatmap<const ROWS: usize, const COLS: usize> {
+    /// The number of locked pieces that covered every cell of the board.
+    /// # Attributes
+    /// - `counts` - The count of every cell, row after row, saturating at `u16::MAX`
+    counts: [[u16; COLS]; ROWS],
+}
+
+impl<const ROWS: usize, const COLS: usize> PlacementHeatmap<ROWS, COLS> {
+    /// Create a heatmap without any locked pieces.
+    /// # Returns
+    /// - `PlacementHeatmap` - The heatmap with all counts at 0
+    pub fn new() -> Self {
+        PlacementHeatmap {
+            counts: [[0; COLS]; ROWS],
+        }
+    }
+
+    /// Set all counts back to 0, e.g. when a new game starts.
+    pub fn reset(&mut self) {
+        self.counts = [[0; COLS]; ROWS];
+    }
+
+    /// Count a locked piece covering a cell, ignoring cells outside of the heatmap.
+    /// # Arguments
+    /// - `coord` - The [row, col] of the cell
+    pub fn add(&mut self, coord: Coordinate) {
+        if let Some(count) = self
+            .counts
+            .get_mut(coord.row)
+            .and_then(|row| row.get_mut(coord.col))
+        {
+            *count = count.saturating_add(1);
+        }
+    }
+
+    /// Add the counts of another heatmap, e.g. to add a finished game to the all-time heatmap.
+    /// # Arguments
+    /// - `other` - A reference to the heatmap to add
+    pub fn merge(&mut self, other: &Self) {
+        for (row, other) in self.counts.iter_mut().zip(other.counts.iter()) {
+            for (count, other) in row.iter_mut().zip(other.iter()) {
+                *count = count.saturating_add(*other);
+            }
+        }
+    }
+
+    /// Get the count of a cell.
+    /// # Arguments
+    /// - `coord` - The [row, col] of the cell
+    /// # Returns
+    /// - `u16` - The number of locked pieces that covered the cell, 0 outside of the heatmap
+    pub fn get(&self, coord: Coordinate) -> u16 {
+        self.counts
+            .get(coord.row)
+            .and_then(|row| row.get(coord.col))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Get the largest count of any cell.
+    /// # Returns
+    /// - `u16` - The count of the most used cell, 0 without any locked pieces
+    pub fn max(&self) -> u16 {
+        self.counts.iter().flatten().copied().max().unwrap_or(0)
+    }
+
+    /// Get the counts relative to the largest count.
+    /// # Returns
+    /// - `impl Iterator<Item = (Coordinate, u8)>` - Every cell, row after row, with its count scaled to 0 to 255, all
+    ///   0 without any locked pieces
+    pub fn normalized(&self) -> impl Iterator<Item = (Coordinate, u8)> + '_ {
+        let max = self.max().max(1) as u32;
+        self.counts
+            .iter()
+            .enumerate()
+            .flat_map(move |(row, counts)| {
+                counts.iter().enumerate().map(move |(col, count)| {
+                    let level = *count as u32 * u8::MAX as u32 / max;
+                    (Coordinate { row, col }, level as u8)
+                })
+            })
+    }
+
+    /// Draw the heatmap, leaving the unused cells untouched.
+    /// # Arguments
+    /// - `frame` - A muteable reference to the `FrameBuffer` to draw into
+    /// - `origin` - The position of the top-left cell
+    pub fn draw_into<const N: usize>(&self, frame: &mut FrameBuffer<N>, origin: Coordinate) {
+        for (coord, level) in self.normalized() {
+            if self.get(coord) > 0 {
+                frame.set_pixel(origin + [coord.row, coord.col], heat_color(level));
+            }
+        }
+    }
+
+    /// Serialize the heatmap for persistent storage.
+    /// # Returns
+    /// - `Vec<u8>` - The `heatmap_bytes()` bytes: the number of rows and columns, and the little endian counts
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(heatmap_bytes(ROWS, COLS));
+        bytes.extend_from_slice(&[ROWS as u8, COLS as u8]);
+        for count in self.counts.iter().flatten() {
+            bytes.extend_from_slice(&count.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Deserialize a heatmap written by `.to_bytes()`.
+    /// # Arguments
+    /// - `bytes` - The serialized heatmap
+    /// # Returns
+    /// - `Option<PlacementHeatmap>` - The heatmap, or `None` if the bytes are of another shape
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != heatmap_bytes(ROWS, COLS) || bytes[..2] != [ROWS as u8, COLS as u8] {
+            return None;
+        }
+        let mut heatmap = PlacementHeatmap::new();
+        for (count, pair) in heatmap
+            .counts
+            .iter_mut()
+            .flatten()
+            .zip(bytes[2..].chunks_exact(2))
+        {
+            *count = u16::from_le_bytes([pair[0], pair[1]]);
+        }
+        Some(heatmap)
+    }
+}
+
+impl<const ROWS: usize, const COLS: usize> Default for PlacementHeatmap<ROWS, COLS> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const ROWS: usize, const COLS: usize> EventSink for PlacementHeatmap<ROWS, COLS> {
+    fn on_event(&mut self, event: &GameEvent) {
+        if let GameEvent::PieceLocked { cells, .. } = event {
+            for [row, col] in cells {
+                self.add(Coordinate::from_array([*row as usize, *col as usize]));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{heat_color, PlacementHeatmap, COLD, HOT};
+    use crate::coordinate::Coordinate;
+    use crate::events::{EventSink, GameEvent};
+    use crate::game::{Game, GameState};
+    use crate::input::{Action, InputState};
+    use crate::settings::Settings;
+    use crate::tetrominoes::TetrominoShape;
+
+    #[test]
+    fn test_locked_piece() {
+        // Hard dropping the first piece counts exactly its four cells.
+        let mut game = Game::new(Settings::default(), 7);
+        game.tick(&InputState::from_action(Action::Start));
+        while game.get_state() != GameState::Playing {
+            game.tick(&InputState::default());
+        }
+        let mut heatmap = PlacementHeatmap::<20, 10>::new();
+        let mut landed = game.get_piece().clone();
+        while landed.try_drop(game.get_board()) {}
+        let output = game.tick(&InputState::from_action(Action::HardDrop));
+        output.dispatch(&mut heatmap);
+        let cells = landed.get_cells();
+        for row in 0..20 {
+            for col in 0..10 {
+                let coord = Coordinate { row, col };
+                assert_eq!(heatmap.get(coord), cells.contains(&coord) as u16);
+            }
+        }
+        assert_eq!(heatmap.max(), 1);
+    }
+
+    #[test]
+    fn test_normalized() {
+        // An empty heatmap normalizes to all 0 without dividing by 0.
+        let mut heatmap = PlacementHeatmap::<4, 3>::new();
+        assert_eq!(heatmap.max(), 0);
+        assert!(heatmap.normalized().all(|(_, level)| level == 0));
+        assert_eq!(heatmap.normalized().count(), 12);
+        heatmap.on_event(&GameEvent::PieceLocked {
+            shape: TetrominoShape::T,
+            cells: [[2, 1], [3, 0], [3, 1], [3, 2]],
+        });
+        heatmap.add(Coordinate::from_array([3, 1]));
+        let levels: Vec<u8> = heatmap.normalized().map(|(_, level)| level).collect();
+        assert_eq!(levels[7], 127);
+        assert_eq!(levels[10], 255);
+        assert_eq!(heat_color(0), COLD);
+        assert_eq!(heat_color(255), HOT);
+    }
+
+    #[test]
+    fn test_bytes() {
+        let mut heatmap = PlacementHeatmap::<3, 2>::new();
+        for _ in 0..300 {
+            heatmap.add(Coordinate::from_array([2, 1]));
+        }
+        heatmap.add(Coordinate::from_array([0, 0]));
+        let bytes = heatmap.to_bytes();
+        assert_eq!(PlacementHeatmap::from_bytes(&bytes), Some(heatmap));
+        // A heatmap of another shape is rejected.
+        assert_eq!(PlacementHeatmap::<2, 3>::from_bytes(&bytes), None);
+    }
+}
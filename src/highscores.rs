@@ -0,0 +1,235 @@
+/// The number of entries in the high-score table.
+pub const HIGH_SCORES: usize = 5;
+
+/// The number of bytes of a single serialized entry: initials, score, lines and level.
+const ENTRY_BYTES: usize = 3 + 4 + 4 + 1;
+
+/// The number of bytes of a serialized table: the ranking, the number of entries, the entries and a checksum.
+pub const HIGH_SCORES_BYTES: usize = 2 + HIGH_SCORES * ENTRY_BYTES + 4;
+
+/// The panic message of a field outside of its entry, a bug in the layout of the table.
+const FIELD_FITS: &str = "the field is within the serialized table";
+
+/// How the entries of a high-score table are ranked, the tag of the mode the table is kept for.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Ranking {
+    /// The most points first, for the marathon mode.
+    #[default]
+    Points = 0,
+    /// The shortest time first, for the sprint mode.
+    Time = 1,
+}
+
+/// A single entry of the high-score table.
+/// # Attributes
+/// - `initials` - The 3 ASCII initials of the player
+/// - `score` - The number of points, or the time in hundredths of a second in a table ranked by `Ranking::Time`
+/// - `lines` - The number of cleared lines
+/// - `level` - The level reached
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HighScore {
+    pub initials: [u8; 3],
+    pub score: u32,
+    pub lines: u32,
+    pub level: u8,
+}
+
+/// The best scores, ordered from best to worst.
+/// # Attributes
+/// - `ranking` - How the scores are ranked
+/// - `entries` - The entries in use, ordered from best to worst score, followed by the unused entries
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct HighScores {
+    ranking: Ranking,
+    entries: [Option<HighScore>; HIGH_SCORES],
+}
+
+impl HighScores {
+    /// Create an empty table.
+    /// # Arguments
+    /// - `ranking` - How the scores are ranked
+    /// # Returns
+    /// - `HighScores` - The table without entries
+    pub fn new(ranking: Ranking) -> Self {
+        HighScores {
+            ranking,
+            entries: [None; HIGH_SCORES],
+        }
+    }
+
+    /// Get how the scores are ranked.
+    pub fn get_ranking(&self) -> Ranking {
+        self.ranking
+    }
+
+    /// Check if a score makes it into the table, more points or a shorter time than an entry.
+    /// # Arguments
+    /// - `score` - The number of points, or the time in hundredths of a second
+    /// # Returns
+    /// - `Option<usize>` - The rank the score would get, or `None` if it does not qualify
+    pub fn qualifies(&self, score: u32) -> Option<usize> {
+        if score == 0 {
+            return None;
+        }
+        self.entries.iter().position(|entry| match entry {
+            Some(entry) => match self.ranking {
+                Ranking::Points => score > entry.score,
+                Ranking::Time => score < entry.score,
+            },
+            None => true,
+        })
+    }
+
+    /// Insert a score in the table, evicting the worst entry if the table is full.
+    /// A score that does not qualify is ignored.
+    /// # Arguments
+    /// - `initials` - The 3 ASCII initials of the player
+    /// - `score` - The number of points, or the time in hundredths of a second
+    /// - `lines` - The number of cleared lines
+    /// - `level` - The level reached
+    pub fn insert(&mut self, initials: [u8; 3], score: u32, lines: u32, level: u8) {
+        if let Some(rank) = self.qualifies(score) {
+            self.entries[rank..].rotate_right(1);
+            self.entries[rank] = Some(HighScore {
+                initials,
+                score,
+                lines,
+                level,
+            });
+        }
+    }
+
+    /// Iterate over the entries, from best to worst score.
+    pub fn iter(&self) -> impl Iterator<Item = &HighScore> {
+        self.entries.iter().flatten()
+    }
+
+    /// Serialize the table for persistent storage.
+    /// # Returns
+    /// - `[u8; HIGH_SCORES_BYTES]` - The ranking, the number of entries, the little endian entries and their FNV-1a
+    ///   checksum
+    pub fn to_bytes(self) -> [u8; HIGH_SCORES_BYTES] {
+        let mut bytes = [0; HIGH_SCORES_BYTES];
+        bytes[0] = self.ranking as u8;
+        bytes[1] = self.iter().count() as u8;
+        for (index, entry) in self.iter().enumerate() {
+            let chunk = &mut bytes[2 + index * ENTRY_BYTES..2 + (index + 1) * ENTRY_BYTES];
+            chunk[0..3].copy_from_slice(&entry.initials);
+            chunk[3..7].copy_from_slice(&entry.score.to_le_bytes());
+            chunk[7..11].copy_from_slice(&entry.lines.to_le_bytes());
+            chunk[11] = entry.level;
+        }
+        let checksum = _checksum(&bytes[..HIGH_SCORES_BYTES - 4]);
+        bytes[HIGH_SCORES_BYTES - 4..].copy_from_slice(&checksum.to_le_bytes());
+        bytes
+    }
+
+    /// Deserialize a table written by `.to_bytes()`.
+    /// # Arguments
+    /// - `bytes` - A reference to the serialized table
+    /// # Returns
+    /// - `Option<HighScores>` - The table, or `None` if the bytes are corrupted
+    pub fn from_bytes(bytes: &[u8; HIGH_SCORES_BYTES]) -> Option<Self> {
+        let checksum =
+            u32::from_le_bytes(bytes[HIGH_SCORES_BYTES - 4..].try_into().expect(FIELD_FITS));
+        let ranking = match bytes[0] {
+            0 => Ranking::Points,
+            1 => Ranking::Time,
+            _ => return None,
+        };
+        let len = bytes[1] as usize;
+        if checksum != _checksum(&bytes[..HIGH_SCORES_BYTES - 4]) || len > HIGH_SCORES {
+            return None;
+        }
+        let mut high_scores = HighScores::new(ranking);
+        for index in 0..len {
+            let chunk = &bytes[2 + index * ENTRY_BYTES..2 + (index + 1) * ENTRY_BYTES];
+            high_scores.entries[index] = Some(HighScore {
+                initials: chunk[0..3].try_into().expect(FIELD_FITS),
+                score: u32::from_le_bytes(chunk[3..7].try_into().expect(FIELD_FITS)),
+                lines: u32::from_le_bytes(chunk[7..11].try_into().expect(FIELD_FITS)),
+                level: chunk[11],
+            });
+        }
+        Some(high_scores)
+    }
+}
+
+/// The 32 bit FNV-1a hash of some bytes.
+fn _checksum(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811C_9DC5, |hash, byte| {
+        (hash ^ *byte as u32).wrapping_mul(0x0100_0193)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HighScores, Ranking, HIGH_SCORES};
+
+    fn scores(high_scores: &HighScores) -> Vec<u32> {
+        high_scores.iter().map(|entry| entry.score).collect()
+    }
+
+    #[test]
+    fn test_insert() {
+        let mut high_scores = HighScores::default();
+        assert_eq!(high_scores.qualifies(0), None);
+        for score in [300, 100, 500, 200, 400] {
+            assert!(high_scores.qualifies(score).is_some());
+            high_scores.insert(*b"ABC", score, 0, 1);
+        }
+        assert_eq!(scores(&high_scores), vec![500, 400, 300, 200, 100]);
+        // A full table evicts the lowest entry, or rejects a lower score.
+        assert_eq!(high_scores.qualifies(100), None);
+        assert_eq!(high_scores.qualifies(350), Some(2));
+        high_scores.insert(*b"XYZ", 350, 12, 2);
+        assert_eq!(scores(&high_scores), vec![500, 400, 350, 300, 200]);
+        assert_eq!(high_scores.iter().nth(2).unwrap().initials, *b"XYZ");
+        high_scores.insert(*b"XYZ", 50, 0, 1);
+        assert_eq!(high_scores.iter().count(), HIGH_SCORES);
+        assert_eq!(scores(&high_scores), vec![500, 400, 350, 300, 200]);
+    }
+
+    #[test]
+    fn test_insert_time() {
+        // A table of sprint times keeps the fastest times first.
+        let mut high_scores = HighScores::new(Ranking::Time);
+        assert_eq!(high_scores.qualifies(0), None);
+        for centis in [9_000, 6_000, 12_000, 7_500, 10_000] {
+            high_scores.insert(*b"ABC", centis, 40, 1);
+        }
+        assert_eq!(
+            scores(&high_scores),
+            vec![6_000, 7_500, 9_000, 10_000, 12_000]
+        );
+        assert_eq!(high_scores.qualifies(12_500), None);
+        assert_eq!(high_scores.qualifies(7_000), Some(1));
+        high_scores.insert(*b"XYZ", 7_000, 40, 1);
+        assert_eq!(
+            scores(&high_scores),
+            vec![6_000, 7_000, 7_500, 9_000, 10_000]
+        );
+    }
+
+    #[test]
+    fn test_bytes() {
+        let mut high_scores = HighScores::default();
+        assert_eq!(
+            HighScores::from_bytes(&high_scores.to_bytes()),
+            Some(high_scores)
+        );
+        high_scores.insert(*b"BEN", 123_456, 78, 9);
+        high_scores.insert(*b"DJ ", 1_000, 4, 1);
+        let mut bytes = high_scores.to_bytes();
+        assert_eq!(HighScores::from_bytes(&bytes), Some(high_scores));
+        // Flipping a single bit is detected.
+        bytes[5] ^= 0x10;
+        assert_eq!(HighScores::from_bytes(&bytes), None);
+        // The ranking is kept.
+        let mut times = HighScores::new(Ranking::Time);
+        times.insert(*b"BEN", 6_543, 40, 1);
+        let restored = HighScores::from_bytes(&times.to_bytes()).unwrap();
+        assert_eq!(restored.get_ranking(), Ranking::Time);
+        assert_eq!(restored, times);
+    }
+}
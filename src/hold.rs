@@ -0,0 +1,55 @@
+use crate::error::TetrisError;
+use crate::savestate::{shape_from_u8, Persist, Reader, Writer};
+use crate::tetrominoes::TetrominoShape;
+
+#[derive(Debug, Default)]
+pub struct HoldSlot {
+    /// The slot the player can put the active piece in, once per piece.
+    /// # Attributes
+    /// - `shape` - The held shape, if any
+    /// - `locked` - Whether the slot was already used for the current piece
+    shape: Option<TetrominoShape>,
+    locked: bool,
+}
+
+impl HoldSlot {
+    /// Get the held shape.
+    pub fn get_shape(&self) -> Option<TetrominoShape> {
+        self.shape
+    }
+
+    /// Check if the active piece can be held.
+    /// # Returns
+    /// - `bool` - Whether (`true`) or not (`false`) the slot was not used yet for the current piece
+    pub fn can_hold(&self) -> bool {
+        !self.locked
+    }
+
+    /// Put a shape in the slot, locking it until the next piece.
+    /// # Arguments
+    /// - `shape` - The shape of the active piece
+    /// # Returns
+    /// - `Option<TetrominoShape>` - The previously held shape, if any
+    pub fn swap(&mut self, shape: TetrominoShape) -> Option<TetrominoShape> {
+        self.locked = true;
+        self.shape.replace(shape)
+    }
+
+    /// Allow holding again, after the active piece locked.
+    pub fn unlock(&mut self) {
+        self.locked = false;
+    }
+}
+
+impl Persist for HoldSlot {
+    fn save(&self, out: &mut Writer) {
+        out.option_u8(self.shape.map(|shape| shape as u8));
+        out.bool(self.locked);
+    }
+
+    fn restore(&mut self, input: &mut Reader) -> Result<(), TetrisError> {
+        self.shape = input.option_u8()?.map(shape_from_u8).transpose()?;
+        self.locked = input.bool()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,360 @@
+//! The output stage for HUB75 RGB matrix panels, scanning the bitplanes of a `FrameBuffer` out row by row.
+//!
+//! # Scanning
+//! A HUB75 panel only lights two rows at once, the row at the address on the A to D lines and the row half a panel
+//! below it, e.g. a 1/16 scan panel of 32 rows lights rows 0 and 16, then 1 and 17 and so on. Every pixel of both
+//! rows is shifted in on the R1, G1, B1 and R2, G2, B2 lines, latched with LAT and shown while OE is low.
+//!
+//! # Bitplanes
+//! The LEDs are either on or off, so every channel is shown with binary-coded modulation: the top `COLOR_DEPTH`
+//! bits of a channel are split into bitplanes, and every bitplane is shown twice as long as the one before it, see
+//! `plane_ticks()`. `FrameBuffer::encode_bitplanes()` writes a byte per pixel pair, scan line by scan line and
+//! bitplane by bitplane from the least significant one, so every step of the `ScanScheduler` clocks out a single
+//! contiguous run of `cols` bytes.
+//!
+//! # Timing
+//! A scan line of `BITPLANES` bitplanes is shown for `15 * base_ticks` ticks, so a 1/16 scan panel with a
+//! `TICK_NANOS` tick and a base of a single tick shows a frame every 120 µs, plus the time to shift the pixels in.
+use crate::color::Rgb;
+use core::ops::Range;
+
+/// The number of most significant bits of every channel shown on the panel.
+pub const COLOR_DEPTH: u8 = 4;
+
+/// The number of bitplanes of every scan line, one per bit of color depth.
+pub const BITPLANES: usize = COLOR_DEPTH as usize;
+
+/// The bit of the red line of the upper row in the byte of a pixel pair, in the order of the connector.
+pub const R1: u8 = 1 << 0;
+
+/// The bit of the green line of the upper row.
+pub const G1: u8 = 1 << 1;
+
+/// The bit of the blue line of the upper row.
+pub const B1: u8 = 1 << 2;
+
+/// The bit of the red line of the lower row.
+pub const R2: u8 = 1 << 3;
+
+/// The bit of the green line of the lower row.
+pub const G2: u8 = 1 << 4;
+
+/// The bit of the blue line of the lower row.
+pub const B2: u8 = 1 << 5;
+
+/// The number of nanoseconds of a single tick of the `ScanScheduler` on the RP2040.
+pub const TICK_NANOS: u32 = 500;
+
+/// The number of bytes of the bitplanes of a panel, see `FrameBuffer::encode_bitplanes()`.
+/// # Arguments
+/// - `rows` - The number of rows of the panel, an even number
+/// - `cols` - The number of columns of the panel
+/// # Returns
+/// - `usize` - The number of bytes, a byte per pixel pair for every bitplane
+pub fn plane_bytes(rows: usize, cols: usize) -> usize {
+    rows / 2 * BITPLANES * cols
+}
+
+/// Pack the bits of a bitplane of the pixels of two rows lit at once into the byte clocked out for them.
+/// # Arguments
+/// - `upper` - The color of the pixel in the upper half of the panel
+/// - `lower` - The color of the pixel half a panel below it
+/// - `plane` - The bitplane, 0 for the least significant bit of the color depth
+/// # Returns
+/// - `u8` - The `R1` to `B2` bits lit in the bitplane
+pub fn pixel_bits(upper: Rgb, lower: Rgb, plane: usize) -> u8 {
+    let bit = |channel: u8, mask: u8| match channel >> (8 - COLOR_DEPTH as usize + plane) & 1 {
+        1 => mask,
+        _ => 0,
+    };
+    bit(upper.r, R1)
+        | bit(upper.g, G1)
+        | bit(upper.b, B1)
+        | bit(lower.r, R2)
+        | bit(lower.g, G2)
+        | bit(lower.b, B2)
+}
+
+/// Get the number of ticks a bitplane is shown, doubling with every bit.
+/// # Arguments
+/// - `plane` - The bitplane, 0 for the least significant bit of the color depth
+/// - `base_ticks` - The number of ticks of the least significant bitplane
+/// # Returns
+/// - `u32` - The number of ticks
+pub fn plane_ticks(plane: usize, base_ticks: u32) -> u32 {
+    base_ticks << plane
+}
+
+/// A single step of scanning a panel, showing one bitplane of one scan line.
+/// # Attributes
+/// - `row` - The scan line, the address of the upper row
+/// - `plane` - The bitplane, 0 for the least significant bit of the color depth
+/// - `ticks` - The number of ticks to show the bitplane for
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScanStep {
+    pub row: usize,
+    pub plane: usize,
+    pub ticks: u32,
+}
+
+impl ScanStep {
+    /// Get the bytes of the step in the bitplanes of `FrameBuffer::encode_bitplanes()`.
+    /// # Arguments
+    /// - `cols` - The number of columns of the panel
+    /// # Returns
+    /// - `Range<usize>` - The range of the `cols` bytes to clock out
+    pub fn get_range(&self, cols: usize) -> Range<usize> {
+        let start = (self.row * BITPLANES + self.plane) * cols;
+        start..start + cols
+    }
+}
+
+/// Schedule the scanning of a panel, every bitplane of a scan line in turn, and then the next scan line.
+/// # Attributes
+/// - `scan_lines` - The number of scan lines, half the rows of the panel
+/// - `base_ticks` - The number of ticks of the least significant bitplane, scaling the brightness and refresh rate
+/// - `row` - The scan line of the next step
+/// - `plane` - The bitplane of the next step
+#[derive(Debug)]
+pub struct ScanScheduler {
+    scan_lines: usize,
+    base_ticks: u32,
+    row: usize,
+    plane: usize,
+}
+
+impl ScanScheduler {
+    /// Create a scheduler starting at the least significant bitplane of the first scan line.
+    /// # Arguments
+    /// - `scan_lines` - The number of scan lines, half the rows of the panel
+    /// - `base_ticks` - The number of ticks of the least significant bitplane, at least 1
+    /// # Returns
+    /// - `ScanScheduler` - The scheduler
+    pub fn new(scan_lines: usize, base_ticks: u32) -> Self {
+        ScanScheduler {
+            scan_lines,
+            base_ticks,
+            row: 0,
+            plane: 0,
+        }
+    }
+
+    /// Get the next step to clock out, wrapping to the first scan line after the last one.
+    /// # Returns
+    /// - `ScanStep` - The scan line and bitplane, and how long to show them
+    pub fn next_step(&mut self) -> ScanStep {
+        let step = ScanStep {
+            row: self.row,
+            plane: self.plane,
+            ticks: plane_ticks(self.plane, self.base_ticks),
+        };
+        self.plane += 1;
+        if self.plane == BITPLANES {
+            self.plane = 0;
+            self.row = (self.row + 1) % self.scan_lines.max(1);
+        }
+        step
+    }
+
+    /// Get the number of steps of a full frame.
+    /// # Returns
+    /// - `usize` - Every bitplane of every scan line
+    pub fn get_steps(&self) -> usize {
+        self.scan_lines * BITPLANES
+    }
+
+    /// Get the number of ticks the panel is lit during a full frame, excluding the time to shift the pixels in.
+    /// # Returns
+    /// - `u32` - The sum of the ticks of every step
+    pub fn frame_ticks(&self) -> u32 {
+        let row_ticks: u32 = (0..BITPLANES)
+            .map(|plane| plane_ticks(plane, self.base_ticks))
+            .sum();
+        self.scan_lines as u32 * row_ticks
+    }
+}
+
+#[cfg(feature = "rp2040")]
+pub use driver::{ControlPin, Hub75};
+
+#[cfg(feature = "rp2040")]
+mod driver {
+    use super::{ScanScheduler, TICK_NANOS};
+    use embedded_hal::digital::OutputPin;
+    use rp2040_hal::clocks::{Clock, ClocksManager};
+    use rp2040_hal::gpio::{DynPinId, FunctionSioOutput, Pin, PullDown};
+    use rp2040_hal::pio::{
+        PIOBuilder, PIOExt, PinDir, Running, ShiftDirection, StateMachine, StateMachineIndex, Tx,
+        UninitStateMachine, PIO,
+    };
+
+    /// The rate the pixels are shifted into the panel, well within the 25 MHz most panels accept.
+    const PIXEL_CLOCK_HZ: u32 = 10_000_000;
+
+    /// The number of PIO cycles of every pixel, setting the data lines and then raising the clock.
+    const CYCLES_PER_PIXEL: u32 = 2;
+
+    /// A control line of the panel, the address lines, LAT and OE.
+    pub type ControlPin = Pin<DynPinId, FunctionSioOutput, PullDown>;
+
+    /// A HUB75 panel, its pixels shifted in by a PIO state machine and its rows scanned by the CPU.
+    /// # Attributes
+    /// - `tx` - The TX FIFO of the state machine, a word per pixel pair
+    /// - `address` - The address lines, A first
+    /// - `latch` - The LAT line, latching the shifted pixels on a rising edge
+    /// - `enable` - The OE line, lighting the latched row while low
+    /// - `cols` - The number of columns of the panel
+    /// - `pixel_cycles` - The number of system clock cycles of a single pixel
+    /// - `tick_cycles` - The number of system clock cycles of a tick of the `ScanScheduler`
+    /// - `_sm` - The running state machine, kept so it is not reconfigured elsewhere
+    pub struct Hub75<P: PIOExt, SM: StateMachineIndex, const A: usize> {
+        tx: Tx<(P, SM)>,
+        address: [ControlPin; A],
+        latch: ControlPin,
+        enable: ControlPin,
+        cols: usize,
+        pixel_cycles: u32,
+        tick_cycles: u32,
+        _sm: StateMachine<(P, SM), Running>,
+    }
+
+    impl<P: PIOExt, SM: StateMachineIndex, const A: usize> Hub75<P, SM, A> {
+        /// Install the shifting program and start a state machine driving the data and clock lines.
+        /// # Arguments
+        /// - `pio` - A muteable reference to the PIO block to install the program in
+        /// - `sm` - The state machine to run the program on
+        /// - `data_base` - The GPIO of R1, followed by G1, B1, R2, G2 and B2, already set to the function of the PIO
+        ///   block
+        /// - `clock` - The GPIO of the clock line, already set to the function of the PIO block
+        /// - `address` - The address lines, A first, 4 for a 1/16 scan panel
+        /// - `latch` - The LAT line
+        /// - `enable` - The OE line
+        /// - `cols` - The number of columns of the panel
+        /// - `clocks` - A reference to the initialized clocks, deriving the PIO clock from the system clock
+        /// # Returns
+        /// - `Hub75<P, SM, A>` - The driver, with the panel dark until the first frame
+        #[allow(clippy::too_many_arguments)]
+        pub fn new(
+            pio: &mut PIO<P>,
+            sm: UninitStateMachine<(P, SM)>,
+            data_base: u8,
+            clock: u8,
+            address: [ControlPin; A],
+            latch: ControlPin,
+            mut enable: ControlPin,
+            cols: usize,
+            clocks: &ClocksManager,
+        ) -> Self {
+            enable.set_high().ok();
+            let program = pio::pio_asm!(
+                ".side_set 1",
+                ".wrap_target",
+                "    out pins, 6     side 0",
+                "    out null, 26    side 1",
+                ".wrap",
+            );
+            let installed = pio
+                .install(&program.program)
+                .expect("the HUB75 program fits in the instruction memory of the PIO");
+            // The clock divisor in 16.8 fixed point, e.g. 6 + 64 / 256 at a system clock of 125 MHz.
+            let sys_hz = clocks.system_clock.freq().to_Hz();
+            let divisor = sys_hz as u64 * 256 / (PIXEL_CLOCK_HZ * CYCLES_PER_PIXEL) as u64;
+            let (mut sm, _, tx) = PIOBuilder::from_installed_program(installed)
+                .out_pins(data_base, 6)
+                .side_set_pin_base(clock)
+                .out_shift_direction(ShiftDirection::Right)
+                .autopull(true)
+                // A threshold of 0 pulls after all 32 bits, a word per pixel pair.
+                .pull_threshold(0)
+                .clock_divisor_fixed_point((divisor >> 8) as u16, divisor as u8)
+                .build(sm);
+            sm.set_pindirs(
+                (data_base..data_base + 6)
+                    .chain([clock])
+                    .map(|id| (id, PinDir::Output)),
+            );
+            Hub75 {
+                tx,
+                address,
+                latch,
+                enable,
+                cols,
+                pixel_cycles: sys_hz / PIXEL_CLOCK_HZ,
+                tick_cycles: sys_hz / 1_000_000 * TICK_NANOS / 1_000,
+                _sm: sm.start(),
+            }
+        }
+
+        /// Show every bitplane of every scan line once, blocking until the last one was shown.
+        /// # Arguments
+        /// - `planes` - The bitplanes of the frame, see `FrameBuffer::encode_bitplanes()`
+        /// - `scheduler` - A muteable reference to the `ScanScheduler` of the panel
+        pub fn write(&mut self, planes: &[u8], scheduler: &mut ScanScheduler) {
+            for _ in 0..scheduler.get_steps() {
+                let step = scheduler.next_step();
+                for pixel in planes.get(step.get_range(self.cols)).unwrap_or(&[]) {
+                    while !self.tx.write(*pixel as u32) {}
+                }
+                // The last pixel may still be shifting out once the TX FIFO is empty.
+                while !self.tx.is_empty() {}
+                cortex_m::asm::delay(self.pixel_cycles);
+                self.enable.set_high().ok();
+                for (bit, pin) in self.address.iter_mut().enumerate() {
+                    match step.row >> bit & 1 {
+                        1 => pin.set_high().ok(),
+                        _ => pin.set_low().ok(),
+                    };
+                }
+                self.latch.set_high().ok();
+                self.latch.set_low().ok();
+                self.enable.set_low().ok();
+                cortex_m::asm::delay(step.ticks * self.tick_cycles);
+            }
+            self.enable.set_high().ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        pixel_bits, plane_bytes, plane_ticks, ScanScheduler, ScanStep, B1, B2, BITPLANES, G1, G2,
+        R1, R2,
+    };
+    use crate::color::Rgb;
+
+    #[test]
+    fn test_pixel_bits() {
+        // Only the top 4 bits of every channel are shown, 0xA0 lighting the second and fourth bitplane.
+        let upper = Rgb::from_array(&[0xF0, 0x0F, 0xA0]);
+        let lower = Rgb::from_array(&[0x30, 0x50, 0x00]);
+        assert_eq!(pixel_bits(upper, lower, 0), R1 | R2 | G2);
+        assert_eq!(pixel_bits(upper, lower, 1), R1 | B1 | R2);
+        assert_eq!(pixel_bits(upper, lower, 2), R1 | G2);
+        assert_eq!(pixel_bits(upper, lower, 3), R1 | B1);
+        assert_eq!(
+            pixel_bits(Rgb::WHITE, Rgb::WHITE, 3),
+            R1 | G1 | B1 | R2 | G2 | B2
+        );
+    }
+
+    #[test]
+    fn test_scheduler() {
+        // The 2 scan lines of a 4 by 4 panel, every bitplane twice as long as the one before.
+        let mut scheduler = ScanScheduler::new(2, 1);
+        assert_eq!(scheduler.get_steps(), 8);
+        assert_eq!(scheduler.frame_ticks(), 30);
+        let steps: Vec<_> = (0..9).map(|_| scheduler.next_step()).collect();
+        let expected: Vec<_> = [(0, 0, 1), (0, 1, 2), (0, 2, 4), (0, 3, 8)]
+            .into_iter()
+            .chain([(1, 0, 1), (1, 1, 2), (1, 2, 4), (1, 3, 8), (0, 0, 1)])
+            .map(|(row, plane, ticks)| ScanStep { row, plane, ticks })
+            .collect();
+        assert_eq!(steps, expected);
+        assert_eq!(steps[5].get_range(4), 20..24);
+        // A longer base tick brightens the panel at a lower refresh rate.
+        assert_eq!(plane_ticks(3, 5), 40);
+        assert_eq!(ScanScheduler::new(16, 5).frame_ticks(), 16 * 75);
+        assert_eq!(plane_bytes(32, 64), 16 * BITPLANES * 64);
+    }
+}
@@ -0,0 +1,390 @@
+//! The heads-up display on a 128x32 SSD1306 OLED, so the LED panel only shows the playfield.
+//!
+//! The HUD is rendered into a 1 bit per pixel buffer in the layout of the display memory of the SSD1306: 4 pages
+//! of 8 rows, every byte a column of a page with the top row in the least significant bit. From left to right it
+//! shows the hold piece, the score, level and lines below each other, and the next three pieces. In the sprint and
+//! ultra modes the time replaces the level, the time played in a sprint and the time left in an ultra. A cheese race
+//! shows the number of its garbage rows left to clear instead.
+//!
+//! The numbers are drawn with the digit font of `display::digits` at twice its size, the pieces as thumbnails of
+//! their masks on a 4x4 glyph, every cell a block of `CELL_PIXELS` pixels.
+use crate::board::BoardOps;
+use crate::cell::Cell;
+use crate::clock::{format_time, ticks_to_centis, TICK_HZ, TIME_BYTES};
+use crate::coordinate::Coordinate;
+use crate::display::digits::{digit_mask, to_digits, GLYPH_COLS, GLYPH_ROWS};
+use crate::game::Game;
+use crate::settings::GameMode;
+use crate::tetrominoes::{Tetromino, TetrominoShape};
+
+/// The number of columns of the display.
+pub const HUD_COLS: usize = 128;
+
+/// The number of pages of 8 rows of the display.
+pub const HUD_PAGES: usize = 4;
+
+/// The number of bytes of the display memory.
+pub const HUD_BYTES: usize = HUD_COLS * HUD_PAGES;
+
+/// The number of cells of the glyph of a piece, in both directions.
+pub const THUMBNAIL_CELLS: usize = 4;
+
+/// The number of pixels of a cell of a piece, in both directions.
+pub const CELL_PIXELS: usize = 4;
+
+/// The number of pixels of a pixel of the digit font, in both directions.
+const DIGIT_SCALE: usize = 2;
+
+/// The number of digits of the score, enough for any score short of 100 million.
+const SCORE_DIGITS: usize = 8;
+
+/// The number of upcoming pieces shown.
+pub const HUD_PREVIEWS: usize = 3;
+
+/// The top-left pixel of the hold piece.
+const HOLD_ORIGIN: Coordinate = Coordinate { row: 8, col: 0 };
+
+/// The column right of the numbers, which are right-aligned.
+const NUMBERS_RIGHT: usize = 76;
+
+/// The top-left pixel of the first upcoming piece, the others follow to the right.
+const NEXT_ORIGIN: Coordinate = Coordinate { row: 8, col: 80 };
+
+/// Light a pixel of the buffer, ignoring pixels outside of the display.
+/// # Arguments
+/// - `buffer` - A muteable reference to the buffer
+/// - `coord` - The [row, col] of the pixel
+pub fn set_pixel(buffer: &mut [u8; HUD_BYTES], coord: Coordinate) {
+    if coord.row < HUD_PAGES * 8 && coord.col < HUD_COLS {
+        buffer[coord.row / 8 * HUD_COLS + coord.col] |= 1 << (coord.row % 8);
+    }
+}
+
+/// Light a square block of pixels.
+fn _fill_block(buffer: &mut [u8; HUD_BYTES], origin: Coordinate, size: usize) {
+    for r in 0..size {
+        for c in 0..size {
+            set_pixel(buffer, origin + [r, c]);
+        }
+    }
+}
+
+/// Draw the thumbnail of a piece, its mask centered on a glyph of `THUMBNAIL_CELLS` cells.
+/// # Arguments
+/// - `buffer` - A muteable reference to the buffer
+/// - `shape` - The `TetrominoShape` to draw, in its spawn orientation
+/// - `origin` - The top-left pixel of the glyph
+pub fn draw_thumbnail(buffer: &mut [u8; HUD_BYTES], shape: TetrominoShape, origin: Coordinate) {
+    let tetromino = Tetromino::from(shape);
+    let dims = tetromino.get_shape();
+    let offset = Coordinate {
+        row: (THUMBNAIL_CELLS - dims.row) / 2,
+        col: (THUMBNAIL_CELLS - dims.col) / 2,
+    };
+    for r in 0..dims.row {
+        for c in 0..dims.col {
+            if tetromino.get_mask()[(r, c)] {
+                let cell = Coordinate {
+                    row: (offset.row + r) * CELL_PIXELS,
+                    col: (offset.col + c) * CELL_PIXELS,
+                };
+                _fill_block(buffer, origin + cell, CELL_PIXELS);
+            }
+        }
+    }
+}
+
+/// Draw a number right-aligned at twice the size of the digit font, with a blank pixel between the digits.
+/// # Arguments
+/// - `buffer` - A muteable reference to the buffer
+/// - `value` - The number to draw, only its lowest `digits` digits if it is wider
+/// - `top` - The top row of the number
+/// - `right` - The column right of the last digit
+/// - `digits` - The largest number of digits to draw
+pub fn draw_number(
+    buffer: &mut [u8; HUD_BYTES],
+    value: u32,
+    top: usize,
+    right: usize,
+    digits: usize,
+) {
+    let mut decimals = [0; SCORE_DIGITS];
+    let digits = digits.min(SCORE_DIGITS);
+    let count = to_digits(value, &mut decimals[SCORE_DIGITS - digits..]).min(digits);
+    let width = (GLYPH_COLS + 1) * DIGIT_SCALE;
+    for (index, digit) in decimals[SCORE_DIGITS - count..].iter().enumerate() {
+        let left = right - (count - index) * width + DIGIT_SCALE;
+        _draw_digit(
+            buffer,
+            *digit,
+            Coordinate {
+                row: top,
+                col: left,
+            },
+        );
+    }
+}
+
+/// Draw a time as `M:SS.CC` right-aligned like `draw_number()`, the separators a single scaled pixel wide.
+/// # Arguments
+/// - `buffer` - A muteable reference to the buffer
+/// - `centis` - The time in hundredths of a second, only its lowest digits of minutes if it does not fit
+/// - `top` - The top row of the time
+/// - `right` - The column right of the last digit
+pub fn draw_time(buffer: &mut [u8; HUD_BYTES], centis: u32, top: usize, right: usize) {
+    let mut text = [0; TIME_BYTES];
+    let len = format_time(centis, &mut text);
+    let mut left = right;
+    for byte in text[..len].iter().rev() {
+        let (width, dots): (usize, &[usize]) = match byte {
+            b':' => (2 * DIGIT_SCALE, &[1, 3]),
+            b'.' => (2 * DIGIT_SCALE, &[GLYPH_ROWS - 1]),
+            _ => ((GLYPH_COLS + 1) * DIGIT_SCALE, &[]),
+        };
+        let Some(next) = left.checked_sub(width) else {
+            break;
+        };
+        left = next;
+        let origin = Coordinate {
+            row: top,
+            col: left + DIGIT_SCALE,
+        };
+        match byte.is_ascii_digit() {
+            true => _draw_digit(buffer, byte - b'0', origin),
+            false => {
+                for r in dots {
+                    _fill_block(buffer, origin + [r * DIGIT_SCALE, 0], DIGIT_SCALE);
+                }
+            }
+        }
+    }
+}
+
+/// Draw a digit of the digit font at twice its size, with its top-left pixel at an origin.
+fn _draw_digit(buffer: &mut [u8; HUD_BYTES], digit: u8, origin: Coordinate) {
+    for (r, row) in digit_mask(digit).iter().enumerate() {
+        for (c, lit) in row.iter().enumerate() {
+            if *lit {
+                _fill_block(
+                    buffer,
+                    origin + [r * DIGIT_SCALE, c * DIGIT_SCALE],
+                    DIGIT_SCALE,
+                );
+            }
+        }
+    }
+}
+
+/// Render the HUD of a game into the display memory of the SSD1306.
+/// # Arguments
+/// - `game` - A reference to the `Game` to show
+/// - `buffer` - A muteable reference to the buffer, cleared first
+pub fn render_hud<B: BoardOps<Cell> + Clone>(game: &Game<B>, buffer: &mut [u8; HUD_BYTES]) {
+    buffer.fill(0);
+    if let Some(shape) = game.get_hold().get_shape() {
+        draw_thumbnail(buffer, shape, HOLD_ORIGIN);
+    }
+    let score = game.get_score();
+    let height = GLYPH_ROWS * DIGIT_SCALE + 1;
+    draw_number(buffer, score.get_points(), 0, NUMBERS_RIGHT, SCORE_DIGITS);
+    let elapsed = game.get_clock().get_ticks_elapsed();
+    match game.get_settings().mode {
+        GameMode::Marathon => {
+            draw_number(buffer, score.get_level() as u32, height, NUMBERS_RIGHT, 3)
+        }
+        GameMode::Sprint { .. } => {
+            let centis = game.get_clock().elapsed_centis(TICK_HZ);
+            draw_time(buffer, centis, height, NUMBERS_RIGHT);
+        }
+        GameMode::Ultra { ticks } => {
+            let centis = ticks_to_centis((ticks as u64).saturating_sub(elapsed), TICK_HZ);
+            draw_time(buffer, centis, height, NUMBERS_RIGHT);
+        }
+        GameMode::Cheese { .. } => {
+            let remaining = game.get_cheese().get_remaining();
+            draw_number(buffer, remaining, height, NUMBERS_RIGHT, 3)
+        }
+    }
+    draw_number(buffer, score.get_lines(), 2 * height, NUMBERS_RIGHT, 4);
+    let size = THUMBNAIL_CELLS * CELL_PIXELS;
+    for (index, shape) in game.get_queue().peek(HUD_PREVIEWS).enumerate() {
+        draw_thumbnail(buffer, *shape, NEXT_ORIGIN + [0, index * size]);
+    }
+}
+
+#[cfg(feature = "rp2040")]
+pub use i2c::Ssd1306;
+
+#[cfg(feature = "rp2040")]
+mod i2c {
+    use super::{HUD_BYTES, HUD_COLS, HUD_PAGES};
+    use embedded_hal::i2c::I2c;
+
+    /// The commands setting up a 128x32 display, with horizontal addressing and the charge pump on.
+    const INIT: [u8; 26] = [
+        0xAE, // Display off
+        0xD5, 0x80, // Clock divide
+        0xA8, 0x1F, // Multiplex of 32 rows
+        0xD3, 0x00, // No display offset
+        0x40, // Start line 0
+        0x8D, 0x14, // Charge pump on
+        0x20, 0x00, // Horizontal addressing
+        0xA1, // Segment remap
+        0xC8, // Scan from the last row
+        0xDA, 0x02, // Sequential COM pins
+        0x81, 0x8F, // Contrast
+        0xD9, 0xF1, // Pre-charge period
+        0xDB, 0x40, // VCOMH deselect level
+        0xA4, // Show the display memory
+        0xA6, // Not inverted
+        0x2E, // No scrolling
+        0xAF, // Display on
+    ];
+
+    /// The number of bytes of display memory sent per I2C transaction.
+    const CHUNK: usize = 16;
+
+    /// An SSD1306 OLED on I2C.
+    /// # Attributes
+    /// - `i2c` - The I2C bus
+    /// - `address` - The address of the display, usually 0x3C
+    pub struct Ssd1306<I> {
+        i2c: I,
+        address: u8,
+    }
+
+    impl<I: I2c> Ssd1306<I> {
+        /// Set up the display.
+        /// # Arguments
+        /// - `i2c` - The I2C bus
+        /// - `address` - The address of the display, usually 0x3C
+        /// # Returns
+        /// - `Result<Ssd1306<I>, I::Error>` - The display, or the error of the bus
+        pub fn new(mut i2c: I, address: u8) -> Result<Self, I::Error> {
+            let mut commands = [0; INIT.len() + 1];
+            commands[1..].copy_from_slice(&INIT);
+            i2c.write(address, &commands)?;
+            Ok(Ssd1306 { i2c, address })
+        }
+
+        /// Send a rendered HUD to the display memory.
+        /// # Arguments
+        /// - `buffer` - A reference to the buffer, see `render_hud()`
+        /// # Returns
+        /// - `Result<(), I::Error>` - The error of the bus, if any
+        pub fn flush(&mut self, buffer: &[u8; HUD_BYTES]) -> Result<(), I::Error> {
+            // Address the whole display, with a control byte of 0x00 for commands.
+            let window = [
+                0x00,
+                0x21,
+                0,
+                (HUD_COLS - 1) as u8,
+                0x22,
+                0,
+                (HUD_PAGES - 1) as u8,
+            ];
+            self.i2c.write(self.address, &window)?;
+            // A control byte of 0x40 for display memory.
+            let mut data = [0x40; CHUNK + 1];
+            for chunk in buffer.chunks(CHUNK) {
+                data[1..].copy_from_slice(chunk);
+                self.i2c.write(self.address, &data)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{draw_number, draw_time, render_hud, HUD_BYTES, HUD_COLS};
+    use crate::game::Game;
+    use crate::input::{Action, InputState};
+    use crate::settings::Settings;
+    use crate::tetrominoes::TetrominoShape;
+
+    #[test]
+    fn test_draw_number() {
+        // A 1 is 6 pixels wide right of its blank gap, and 10 pixels tall.
+        let mut buffer = [0; HUD_BYTES];
+        draw_number(&mut buffer, 1, 0, 8, 3);
+        // The stem of the 1 spans all 10 rows, over the first two pages.
+        for col in [4, 5] {
+            assert_eq!(buffer[col], 0xFF);
+            assert_eq!(buffer[HUD_COLS + col], 0x03);
+        }
+        // The foot spans the bottom 2 rows, the flag the second and third row.
+        assert_eq!(buffer[2], 0x0C);
+        assert_eq!(buffer[HUD_COLS + 2], 0x03);
+        assert_eq!(buffer[6], 0x00);
+        assert_eq!(buffer[HUD_COLS + 6], 0x03);
+        assert_eq!(buffer[0..2], [0, 0]);
+        // A number wider than its digits is cut to its lowest digits.
+        let mut wide = [0; HUD_BYTES];
+        draw_number(&mut wide, 21, 0, 8, 1);
+        assert_eq!(wide, buffer);
+    }
+
+    #[test]
+    fn test_draw_time() {
+        // A time of 1:02.03 is its digits drawn like numbers, with a dot and a colon between them.
+        let mut buffer = [0; HUD_BYTES];
+        draw_time(&mut buffer, 6_203, 0, 80);
+        let mut expected = [0; HUD_BYTES];
+        draw_number(&mut expected, 3, 0, 80, 1);
+        draw_number(&mut expected, 0, 0, 72, 1);
+        // The dot lights the bottom 2 of the 10 rows.
+        expected[HUD_COLS + 62] = 0x03;
+        expected[HUD_COLS + 63] = 0x03;
+        draw_number(&mut expected, 2, 0, 60, 1);
+        draw_number(&mut expected, 0, 0, 52, 1);
+        // The colon lights the second and fourth row of the font.
+        expected[42] = 0xCC;
+        expected[43] = 0xCC;
+        draw_number(&mut expected, 1, 0, 40, 1);
+        assert_eq!(buffer, expected);
+        // The minutes are cut off at the left edge instead of wrapping around.
+        let mut cut = [0; HUD_BYTES];
+        draw_time(&mut cut, 6_203, 0, 39);
+        assert!(cut[..HUD_COLS].iter().any(|byte| *byte != 0));
+    }
+
+    #[test]
+    fn test_render_hud() {
+        // Hold an L piece, from the first seed spawning one.
+        let mut game = (0..)
+            .map(|seed| Game::new(Settings::default(), seed))
+            .find(|game| game.get_piece().get_tetromino().shape == TetrominoShape::L)
+            .unwrap();
+        game.tick(&InputState::from_action(Action::Start));
+        while game.get_hold().get_shape().is_none() {
+            game.tick(&InputState::from_action(Action::Hold));
+        }
+        assert_eq!(game.get_hold().get_shape(), Some(TetrominoShape::L));
+        let mut buffer = [0xAA; HUD_BYTES];
+        render_hud(&game, &mut buffer);
+        // The L fills rows 12 to 15 of page 1 in its third column of cells, and rows 16 to 19 of page 2 in all three.
+        for col in 0..16 {
+            let (top, bottom) = match col {
+                0..=7 => (0x00, 0x0F),
+                8..=11 => (0xF0, 0x0F),
+                _ => (0x00, 0x00),
+            };
+            assert_eq!(buffer[HUD_COLS + col], top, "column {col}");
+            assert_eq!(buffer[2 * HUD_COLS + col], bottom, "column {col}");
+        }
+        assert!(buffer[..16].iter().all(|byte| *byte == 0));
+        // The score of 0 is right-aligned at column 76, the middle of its 0 only lit on its top and bottom rows.
+        assert_eq!(buffer[70], 0xFF);
+        assert_eq!(buffer[72], 0x03);
+        assert_eq!(buffer[76], 0x00);
+        // The next three pieces are drawn on the middle two pages.
+        for index in 0..3 {
+            let slot = 80 + index * 16;
+            assert!((slot..slot + 16)
+                .any(|col| buffer[HUD_COLS + col] != 0 || buffer[2 * HUD_COLS + col] != 0));
+        }
+        assert!(buffer[80..HUD_COLS].iter().all(|byte| *byte == 0));
+        assert!(buffer[3 * HUD_COLS + 80..].iter().all(|byte| *byte == 0));
+    }
+}
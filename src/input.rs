@@ -0,0 +1,532 @@
+pub mod buttons;
+pub mod encoder;
+pub mod history;
+pub mod keymap;
+pub mod queue;
+pub mod tilt;
+
+use crate::error::TetrisError;
+use crate::savestate::{Persist, Reader, SaveError, Writer};
+
+/// The actions a player can take, independent of the input hardware.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Left,
+    Right,
+    RotateCw,
+    RotateCcw,
+    SoftDrop,
+    HardDrop,
+    Hold,
+    Pause,
+    Start,
+}
+
+/// All actions in the order of their discriminants, e.g. to decode an action from its bit.
+pub const ACTIONS: [Action; 9] = [
+    Action::Left,
+    Action::Right,
+    Action::RotateCw,
+    Action::RotateCcw,
+    Action::SoftDrop,
+    Action::HardDrop,
+    Action::Hold,
+    Action::Pause,
+    Action::Start,
+];
+
+/// The bit of a byte decoded by `InputState::next_raw()` selecting `Action::Pause` and `Action::Start`.
+const RAW_SYSTEM: u8 = 1 << 7;
+
+/// The actions held during a single tick, and which of them were pressed this tick, stored as bit sets.
+///
+/// Any input backend, GPIO buttons or a desktop keyboard, reports the held actions every tick
+/// and derives the press edges from the previous tick with `.next()`.
+/// # Attributes
+/// - `held` - The actions held down this tick
+/// - `pressed` - The actions that went down this tick, a subset of `held`
+/// - `raw` - The physical inputs that went down this tick, before the `KeyMap`, for the screens reading the buttons
+///   themselves, see `.with_raw_pressed()`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct InputState {
+    held: u16,
+    pressed: u16,
+    raw: u16,
+}
+
+impl InputState {
+    /// Create an input state with a single action pressed this tick.
+    /// # Arguments
+    /// - `action` - The pressed `Action`
+    /// # Returns
+    /// - `InputState` - The input state
+    pub fn from_action(action: Action) -> Self {
+        let mut input = InputState::default();
+        input.press(action);
+        input
+    }
+
+    /// Create the input state of the next tick from the actions held during that tick.
+    /// # Arguments
+    /// - `held` - The actions held during the next tick
+    /// # Returns
+    /// - `InputState` - The input state, with the actions that were not held this tick as pressed
+    pub fn next(&self, held: &[Action]) -> Self {
+        self.next_bits(
+            held.iter()
+                .fold(0, |bits, action| bits | 1 << *action as u16),
+        )
+    }
+
+    /// Create the input state of the next tick from the bit set of actions held during that tick.
+    /// # Arguments
+    /// - `held` - The bit set of actions held during the next tick, as returned by `.get_held_bits()`
+    /// # Returns
+    /// - `InputState` - The input state, with the actions that were not held this tick as pressed
+    pub fn next_bits(&self, held: u16) -> Self {
+        InputState {
+            held,
+            pressed: held & !self.held,
+            raw: 0,
+        }
+    }
+
+    /// Create the input state of the next tick from a single byte of untrusted input, e.g. of a fuzzer.
+    ///
+    /// Without bit 7, bits 0 to 6 hold the actions `Action::Left` to `Action::Hold`. With bit 7, bit 0 holds
+    /// `Action::Pause` and bit 1 `Action::Start`, the other bits are ignored. Every byte decodes to a valid input.
+    /// # Arguments
+    /// - `byte` - The byte encoding the actions held during the next tick
+    /// # Returns
+    /// - `InputState` - The input state, with the actions that were not held this tick as pressed
+    pub fn next_raw(&self, byte: u8) -> Self {
+        let held = match byte & RAW_SYSTEM {
+            0 => byte as u16,
+            _ => ((byte & 0b11) as u16) << Action::Pause as u16,
+        };
+        self.next_bits(held)
+    }
+
+    /// Add the physical inputs that went down this tick, e.g. for the menu to remap them, see `keymap::KeyMap`.
+    /// # Arguments
+    /// - `raw` - The bit set of inputs that went down this tick, bit `n` for input `n`
+    /// # Returns
+    /// - `InputState` - The input state, with the same actions
+    pub fn with_raw_pressed(self, raw: u16) -> Self {
+        InputState { raw, ..self }
+    }
+
+    /// Get the bit set of physical inputs that went down this tick, 0 for an input backend without them.
+    pub fn get_raw_pressed(&self) -> u16 {
+        self.raw
+    }
+
+    /// Get the bit set of held actions, with bit `n` set for the action with discriminant `n`.
+    pub fn get_held_bits(&self) -> u16 {
+        self.held
+    }
+
+    /// Mark an action as pressed this tick, which also holds it.
+    /// # Arguments
+    /// - `action` - The pressed `Action`
+    pub fn press(&mut self, action: Action) {
+        self.held |= 1 << action as u16;
+        self.pressed |= 1 << action as u16;
+    }
+
+    /// Mark an action as held, without pressing it this tick.
+    /// # Arguments
+    /// - `action` - The held `Action`
+    pub fn hold(&mut self, action: Action) {
+        self.held |= 1 << action as u16;
+    }
+
+    /// Check if an action was pressed this tick.
+    /// # Arguments
+    /// - `action` - The `Action` to check
+    /// # Returns
+    /// - `bool` - Whether (`true`) or not (`false`) the action went down this tick
+    pub fn is_pressed(&self, action: Action) -> bool {
+        self.pressed & (1 << action as u16) != 0
+    }
+
+    /// Check if an action is held, either pressed this tick or held since an earlier tick.
+    /// # Arguments
+    /// - `action` - The `Action` to check
+    /// # Returns
+    /// - `bool` - Whether (`true`) or not (`false`) the action is held
+    pub fn is_held(&self, action: Action) -> bool {
+        self.held & (1 << action as u16) != 0
+    }
+}
+
+/// A combination of actions held together for a number of ticks, e.g. to return to the menu without a power cycle.
+/// # Attributes
+/// - `bits` - The bit set of the actions, see `InputState::get_held_bits()`, 0 for a chord that never fires
+/// - `ticks` - The number of ticks to hold the actions for
+/// - `held` - The number of ticks the actions have been held together
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Chord {
+    bits: u16,
+    ticks: u32,
+    held: u32,
+}
+
+impl Chord {
+    /// Create a chord that is not held.
+    /// # Arguments
+    /// - `actions` - The actions to hold together, other actions may be held as well
+    /// - `ticks` - The number of ticks to hold them for, at least 1
+    /// # Returns
+    /// - `Chord` - The chord
+    pub fn new(actions: &[Action], ticks: u32) -> Self {
+        Chord {
+            bits: actions
+                .iter()
+                .fold(0, |bits, action| bits | 1 << *action as u16),
+            ticks,
+            held: 0,
+        }
+    }
+
+    /// Get the number of ticks to hold the actions for.
+    pub fn get_ticks(&self) -> u32 {
+        self.ticks
+    }
+
+    /// Forget how long the actions have been held, they have to be held for the full ticks again.
+    pub fn reset(&mut self) {
+        self.held = 0;
+    }
+
+    /// Advance the chord by a single tick.
+    /// # Arguments
+    /// - `input` - A reference to the `InputState` of this tick
+    /// # Returns
+    /// - `bool` - Whether (`true`) or not (`false`) the actions were held for long enough this tick, once per hold
+    pub fn update(&mut self, input: &InputState) -> bool {
+        self.held = match self.bits != 0 && input.get_held_bits() & self.bits == self.bits {
+            true => self.held.saturating_add(1),
+            false => 0,
+        };
+        self.held != 0 && self.held == self.ticks
+    }
+
+    /// Swallow the presses of the actions while all of them are held, so pressing the chord does not act on them.
+    /// # Arguments
+    /// - `input` - A reference to the `InputState` of this tick
+    /// # Returns
+    /// - `InputState` - The input state, without the actions as pressed if all of them are held
+    pub fn mask(&self, input: &InputState) -> InputState {
+        match self.bits != 0 && input.held & self.bits == self.bits {
+            true => InputState {
+                pressed: input.pressed & !self.bits,
+                ..*input
+            },
+            false => *input,
+        }
+    }
+}
+
+/// The delayed auto-shift (DAS) of a single held action.
+/// # Attributes
+/// - `action` - The action that repeats while held
+/// - `das` - The number of ticks the action is held before it starts repeating
+/// - `arr` - The number of ticks between repeats, 0 repeats as often as possible
+/// - `held` - The number of ticks the action has been held, if held
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AutoRepeat {
+    action: Action,
+    das: u32,
+    arr: u32,
+    held: Option<u32>,
+}
+
+impl AutoRepeat {
+    /// Create an auto-repeat for an action that is not held.
+    /// # Arguments
+    /// - `action` - The `Action` that repeats while held
+    /// - `das` - The delay in ticks before repeating
+    /// - `arr` - The number of ticks between repeats, 0 to repeat as often as possible
+    /// # Returns
+    /// - `AutoRepeat` - The auto-repeat
+    pub fn new(action: Action, das: u32, arr: u32) -> Self {
+        AutoRepeat {
+            action,
+            das,
+            arr,
+            held: None,
+        }
+    }
+
+    /// Forget how long the action has been held, the next tick it is held counts as a press.
+    pub fn reset(&mut self) {
+        self.held = None;
+    }
+
+    /// Get how far the delay before repeating has passed.
+    /// # Returns
+    /// - `u8` - 0 while not held, up to 255 once the action repeats
+    pub fn get_charge(&self) -> u8 {
+        match (self.held, self.das) {
+            (None, _) => 0,
+            (Some(_), 0) => u8::MAX,
+            (Some(held), das) => (held.min(das) as u64 * u8::MAX as u64 / das as u64) as u8,
+        }
+    }
+
+    /// Advance the auto-repeat by a single tick.
+    /// # Arguments
+    /// - `input` - A reference to the `InputState` of this tick
+    /// # Returns
+    /// - `usize` - The number of times to act this tick, `usize::MAX` to act as often as possible
+    pub fn update(&mut self, input: &InputState) -> usize {
+        if !input.is_held(self.action) {
+            self.held = None;
+            return 0;
+        }
+        let held = match self.held {
+            Some(held) if !input.is_pressed(self.action) => held.saturating_add(1),
+            _ => 0,
+        };
+        self.held = Some(held);
+        match held {
+            0 => 1,
+            held if held < self.das => 0,
+            _ if self.arr == 0 => usize::MAX,
+            held => match (held - self.das) % self.arr {
+                0 => 1,
+                _ => 0,
+            },
+        }
+    }
+}
+
+/// The auto-repeat of the left and right actions, where the most recently pressed direction takes priority.
+/// # Attributes
+/// - `left` - The auto-repeat of `Action::Left`
+/// - `right` - The auto-repeat of `Action::Right`
+/// - `active` - The direction with priority, if any is held
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HorizontalRepeat {
+    left: AutoRepeat,
+    right: AutoRepeat,
+    active: Option<Action>,
+}
+
+impl HorizontalRepeat {
+    /// Create the horizontal auto-repeat without any direction held.
+    /// # Arguments
+    /// - `das` - The delay in ticks before repeating
+    /// - `arr` - The number of ticks between repeats, 0 to repeat as often as possible
+    /// # Returns
+    /// - `HorizontalRepeat` - The horizontal auto-repeat
+    pub fn new(das: u32, arr: u32) -> Self {
+        HorizontalRepeat {
+            left: AutoRepeat::new(Action::Left, das, arr),
+            right: AutoRepeat::new(Action::Right, das, arr),
+            active: None,
+        }
+    }
+
+    /// Get how far the delay before repeating a direction has passed, see `AutoRepeat::get_charge()`.
+    /// # Arguments
+    /// - `direction` - `Action::Left` or `Action::Right`, any other action for right
+    /// # Returns
+    /// - `u8` - 0 while the direction is not held or overridden, up to 255 once it repeats
+    pub fn get_charge(&self, direction: Action) -> u8 {
+        match direction {
+            Action::Left => self.left.get_charge(),
+            _ => self.right.get_charge(),
+        }
+    }
+
+    /// Advance the auto-repeat by a single tick.
+    ///
+    /// Pressing the opposite direction takes over and resets the overridden direction,
+    /// which is picked up as a fresh press when the override is released while it is still held.
+    /// # Arguments
+    /// - `input` - A reference to the `InputState` of this tick
+    /// # Returns
+    /// - `Option<(Action, usize)>` - The direction and the number of shifts this tick, see `AutoRepeat::update()`
+    pub fn update(&mut self, input: &InputState) -> Option<(Action, usize)> {
+        for direction in [Action::Left, Action::Right] {
+            if input.is_pressed(direction) {
+                self.active = Some(direction);
+            }
+        }
+        let held = |direction: Action| input.is_held(direction).then_some(direction);
+        self.active = match self.active {
+            Some(Action::Left) => held(Action::Left).or(held(Action::Right)),
+            Some(_) => held(Action::Right).or(held(Action::Left)),
+            None => held(Action::Left).or(held(Action::Right)),
+        };
+        let (active, other) = match self.active {
+            Some(Action::Left) => (&mut self.left, &mut self.right),
+            Some(_) => (&mut self.right, &mut self.left),
+            None => {
+                // Neither direction is held, so neither keeps its charge.
+                self.left.reset();
+                self.right.reset();
+                return None;
+            }
+        };
+        other.reset();
+        Some((active.action, active.update(input)))
+    }
+}
+
+impl Persist for HorizontalRepeat {
+    fn save(&self, out: &mut Writer) {
+        out.option_u32(self.left.held);
+        out.option_u32(self.right.held);
+        out.u8(match self.active {
+            None => 0,
+            Some(Action::Left) => 1,
+            Some(_) => 2,
+        });
+    }
+
+    fn restore(&mut self, input: &mut Reader) -> Result<(), TetrisError> {
+        self.left.held = input.option_u32()?;
+        self.right.held = input.option_u32()?;
+        self.active = match input.u8()? {
+            0 => None,
+            1 => Some(Action::Left),
+            2 => Some(Action::Right),
+            _ => return Err(SaveError::Invalid.into()),
+        };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Action, AutoRepeat, Chord, HorizontalRepeat, InputState};
+
+    /// Feed the held actions of every tick and count the shifts per direction.
+    fn count(repeat: &mut HorizontalRepeat, ticks: &[&[Action]]) -> (usize, usize) {
+        let mut input = InputState::default();
+        let mut moves = (0, 0);
+        for held in ticks {
+            input = input.next(held);
+            match repeat.update(&input) {
+                Some((Action::Left, count)) => moves.0 += count,
+                Some((_, count)) => moves.1 += count,
+                None => (),
+            }
+        }
+        moves
+    }
+
+    #[test]
+    fn test_next() {
+        let first = InputState::default().next(&[Action::Left, Action::RotateCw]);
+        assert!(first.is_pressed(Action::Left) && first.is_held(Action::Left));
+        assert!(first.is_pressed(Action::RotateCw));
+        let second = first.next(&[Action::Left, Action::HardDrop]);
+        assert!(!second.is_pressed(Action::Left) && second.is_held(Action::Left));
+        assert!(!second.is_held(Action::RotateCw));
+        assert!(second.is_pressed(Action::HardDrop));
+        let third = second.next(&[Action::RotateCw]);
+        assert!(!third.is_held(Action::Left));
+        assert!(third.is_pressed(Action::RotateCw));
+    }
+
+    #[test]
+    fn test_next_raw() {
+        let first = InputState::default().next_raw(0b0010_0001);
+        assert_eq!(
+            first,
+            InputState::default().next(&[Action::Left, Action::HardDrop])
+        );
+        let second = first.next_raw(0b0000_0001);
+        assert!(second.is_held(Action::Left) && !second.is_pressed(Action::Left));
+        // Bit 7 selects pause and start, ignoring the other bits.
+        let third = second.next_raw(0b1111_1110);
+        assert_eq!(third, second.next(&[Action::Start]));
+        assert_eq!(
+            InputState::default().next_raw(0b1000_0100),
+            InputState::default()
+        );
+    }
+
+    #[test]
+    fn test_hold() {
+        // Holding for 20 ticks with a DAS of 10 and an ARR of 2 moves at ticks 0, 10, 12, 14, 16 and 18.
+        let mut repeat = HorizontalRepeat::new(10, 2);
+        assert_eq!(
+            count(&mut repeat, &[&[Action::Right] as &[Action]; 20]),
+            (0, 6)
+        );
+        // An ARR of 0 moves as often as possible once charged.
+        let mut repeat = AutoRepeat::new(Action::Left, 3, 0);
+        let input = InputState::from_action(Action::Left);
+        assert_eq!(repeat.update(&input), 1);
+        let input = input.next(&[Action::Left]);
+        let moves: Vec<usize> = (0..3).map(|_| repeat.update(&input)).collect();
+        assert_eq!(moves, vec![0, 0, usize::MAX]);
+    }
+
+    #[test]
+    fn test_tap() {
+        // Tap, release and tap again moves exactly twice.
+        let mut repeat = HorizontalRepeat::new(10, 2);
+        let ticks: [&[Action]; 3] = [&[Action::Left], &[], &[Action::Left]];
+        assert_eq!(count(&mut repeat, &ticks), (2, 0));
+    }
+
+    #[test]
+    fn test_chord() {
+        // Held together for 3 ticks fires once, in any order and with other actions, until released.
+        let mut chord = Chord::new(&[Action::Start, Action::Hold], 3);
+        let ticks: [&[Action]; 8] = [
+            &[Action::Start],
+            &[Action::Start, Action::Hold],
+            &[Action::Start, Action::Hold, Action::Left],
+            &[Action::Start, Action::Hold],
+            &[Action::Start, Action::Hold],
+            &[Action::Hold],
+            &[Action::Start, Action::Hold],
+            &[Action::Start, Action::Hold],
+        ];
+        let mut input = InputState::default();
+        let fired: Vec<bool> = ticks
+            .iter()
+            .map(|held| {
+                input = input.next(held);
+                chord.update(&input)
+            })
+            .collect();
+        assert_eq!(
+            fired,
+            [false, false, false, true, false, false, false, false]
+        );
+        // Its actions are only pressed when not all of them are held.
+        let start = InputState::from_action(Action::Start);
+        assert_eq!(chord.mask(&start), start);
+        let both = InputState::default().next(&[Action::Start, Action::Hold, Action::Left]);
+        assert_eq!(
+            chord.mask(&both),
+            InputState {
+                pressed: 1 << Action::Left as u16,
+                ..both
+            }
+        );
+        // A chord without actions never fires.
+        let mut none = Chord::default();
+        assert!(!(0..3).any(|_| none.update(&input)));
+        assert_eq!(none.mask(&both), both);
+    }
+
+    #[test]
+    fn test_override() {
+        // Pressing right while holding left takes over, releasing it picks up left as a fresh press.
+        let mut repeat = HorizontalRepeat::new(10, 2);
+        let mut ticks: Vec<&[Action]> = vec![&[Action::Left]; 12];
+        ticks.extend([&[Action::Left, Action::Right] as &[Action]; 5]);
+        ticks.push(&[Action::Left]);
+        // Left moves at ticks 0, 10, right once at its press, left once at its pick-up.
+        assert_eq!(count(&mut repeat, &ticks), (3, 1));
+    }
+}
@@ -0,0 +1,269 @@
+use crate::input::keymap::KeyMap;
+use crate::input::InputState;
+
+/// The maximum number of buttons, one per bit of a sample.
+pub const MAX_BUTTONS: usize = 16;
+
+/// An integrator debouncer of up to `MAX_BUTTONS` buttons, sampled together as a bit set.
+///
+/// Every sample a button is down counts its integrator up, and every sample it is up counts it down.
+/// The button only goes down once the integrator reaches the threshold, and only goes up again once
+/// it drops back to 0, so bounces and glitches shorter than the threshold are rejected.
+/// # Attributes
+/// - `threshold` - The number of consecutive samples a button has to be down to go down, or up to go up
+/// - `counters` - The integrator of every button, in the range [0, `threshold`]
+/// - `state` - The bit set of debounced buttons that are down
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Debouncer {
+    threshold: u8,
+    counters: [u8; MAX_BUTTONS],
+    state: u16,
+}
+
+impl Debouncer {
+    /// Create a debouncer with all buttons up.
+    /// # Arguments
+    /// - `threshold` - The number of samples to debounce, at least 1 where 1 does not debounce at all
+    /// # Returns
+    /// - `Debouncer` - The debouncer
+    pub fn new(threshold: u8) -> Self {
+        Debouncer {
+            threshold: threshold.max(1),
+            counters: [0; MAX_BUTTONS],
+            state: 0,
+        }
+    }
+
+    /// Feed a sample of all buttons to the integrators.
+    /// # Arguments
+    /// - `sample` - The bit set of buttons that are down in this sample, bit `n` for button `n`
+    /// # Returns
+    /// - `u16` - The bit set of debounced buttons that are down, see `.get_state()`
+    pub fn update(&mut self, sample: u16) -> u16 {
+        for (button, counter) in self.counters.iter_mut().enumerate() {
+            let bit = 1 << button;
+            *counter = match sample & bit != 0 {
+                true => (*counter + 1).min(self.threshold),
+                false => counter.saturating_sub(1),
+            };
+            if *counter == self.threshold {
+                self.state |= bit;
+            } else if *counter == 0 {
+                self.state &= !bit;
+            }
+        }
+        self.state
+    }
+
+    /// Get the bit set of debounced buttons that are down, bit `n` for button `n`.
+    pub fn get_state(&self) -> u16 {
+        self.state
+    }
+}
+
+/// The input state of a set of buttons, debounced and mapped to their actions.
+/// # Attributes
+/// - `keymap` - The `KeyMap` from the buttons to their actions
+/// - `debouncer` - The `Debouncer` of the buttons
+/// - `buttons` - The bit set of debounced buttons that were down at the latest tick
+/// - `input` - The `InputState` of the latest tick
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ButtonInput {
+    keymap: KeyMap,
+    debouncer: Debouncer,
+    buttons: u16,
+    input: InputState,
+}
+
+impl ButtonInput {
+    /// Create the input of a set of buttons that are all up.
+    /// # Arguments
+    /// - `keymap` - The `KeyMap` from the buttons to their actions
+    /// - `threshold` - The number of samples to debounce, see `Debouncer::new()`
+    /// # Returns
+    /// - `ButtonInput` - The button input
+    pub fn new(keymap: KeyMap, threshold: u8) -> Self {
+        ButtonInput {
+            keymap,
+            debouncer: Debouncer::new(threshold),
+            buttons: 0,
+            input: InputState::default(),
+        }
+    }
+
+    /// Replace the key map, e.g. after remapping the buttons in the menu.
+    /// # Arguments
+    /// - `keymap` - The `KeyMap` from the buttons to their actions
+    pub fn set_keymap(&mut self, keymap: KeyMap) {
+        self.keymap = keymap;
+    }
+
+    /// Feed a sample of all buttons to the debouncer.
+    /// # Arguments
+    /// - `sample` - The bit set of buttons that are down in this sample, bit `n` for button `n`
+    pub fn sample(&mut self, sample: u16) {
+        self.debouncer.update(sample);
+    }
+
+    /// Create the input state of the next tick from the debounced buttons.
+    ///
+    /// A button that went down since the previous tick is pressed, one that stayed down is only held. The buttons
+    /// that went down are also reported before the key map, see `InputState::get_raw_pressed()`.
+    /// # Returns
+    /// - `InputState` - The input state to pass to `Game::tick()`
+    pub fn next_input(&mut self) -> InputState {
+        let buttons = self.debouncer.get_state();
+        let held = self.keymap.to_held_bits(buttons);
+        self.input = self
+            .input
+            .next_bits(held)
+            .with_raw_pressed(buttons & !self.buttons);
+        self.buttons = buttons;
+        self.input
+    }
+}
+
+#[cfg(feature = "rp2040")]
+pub use gpio::{Button, Buttons};
+
+#[cfg(feature = "rp2040")]
+mod gpio {
+    use super::{ButtonInput, MAX_BUTTONS};
+    use crate::input::keymap::KeyMap;
+    use crate::input::InputState;
+    use embedded_hal::digital::InputPin;
+    use rp2040_hal::gpio::{DynPinId, FunctionSioInput, Pin, PullUp};
+
+    /// A button wired between a GPIO pin and ground, reading low while down.
+    pub type Button = Pin<DynPinId, FunctionSioInput, PullUp>;
+
+    /// A set of buttons on GPIO pins, each mapped to an `Action`.
+    /// # Attributes
+    /// - `pins` - The pin of every button
+    /// - `input` - The debounced `ButtonInput` of the pins
+    pub struct Buttons<const N: usize> {
+        pins: [Button; N],
+        input: ButtonInput,
+    }
+
+    impl<const N: usize> Buttons<N> {
+        /// Create the buttons from their pins and the key map.
+        /// # Arguments
+        /// - `pins` - The pin of every button, already set to pull-up inputs, at most `MAX_BUTTONS`
+        /// - `keymap` - The `KeyMap` from the buttons to their actions, by their index in `pins`
+        /// - `threshold` - The number of samples to debounce, see `Debouncer::new()`
+        /// # Returns
+        /// - `Buttons<N>` - The buttons
+        pub fn new(pins: [Button; N], keymap: KeyMap, threshold: u8) -> Self {
+            assert!(
+                N <= MAX_BUTTONS,
+                "at most {MAX_BUTTONS} buttons are supported"
+            );
+            Buttons {
+                pins,
+                input: ButtonInput::new(keymap, threshold),
+            }
+        }
+
+        /// Replace the key map, e.g. after remapping the buttons in the menu.
+        /// # Arguments
+        /// - `keymap` - The `KeyMap` from the buttons to their actions
+        pub fn set_keymap(&mut self, keymap: KeyMap) {
+            self.input.set_keymap(keymap);
+        }
+
+        /// Sample all pins once and feed them to the debouncer.
+        pub fn sample(&mut self) {
+            let sample = self
+                .pins
+                .iter_mut()
+                .enumerate()
+                .fold(0, |bits: u16, (button, pin)| {
+                    match pin.is_low().unwrap_or(false) {
+                        true => bits | 1 << button,
+                        false => bits,
+                    }
+                });
+            self.input.sample(sample);
+        }
+
+        /// Create the input state of the next tick, see `ButtonInput::next_input()`.
+        pub fn next_input(&mut self) -> InputState {
+            self.input.next_input()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ButtonInput, Debouncer};
+    use crate::input::keymap::KeyMap;
+    use crate::input::Action;
+
+    /// Feed the samples of a single button and collect the debounced state after each of them.
+    fn debounce(threshold: u8, samples: &[u8]) -> Vec<u8> {
+        let mut debouncer = Debouncer::new(threshold);
+        samples
+            .iter()
+            .map(|sample| debouncer.update(*sample as u16) as u8)
+            .collect()
+    }
+
+    #[test]
+    fn test_debouncer() {
+        // A bouncy press goes down once the integrator reaches the threshold, and stays down.
+        assert_eq!(
+            debounce(3, &[1, 0, 1, 1, 0, 1, 1, 1, 1]),
+            [0, 0, 0, 0, 0, 0, 1, 1, 1],
+        );
+        // A glitch shorter than the threshold is rejected.
+        assert_eq!(debounce(3, &[0, 1, 1, 0, 0, 0]), [0; 6]);
+        // A release only goes up once the integrator is back at 0.
+        assert_eq!(debounce(2, &[1, 1, 0, 1, 0, 0, 0]), [0, 1, 1, 1, 1, 0, 0],);
+        // A threshold of 1 follows the samples.
+        assert_eq!(debounce(1, &[1, 0, 1]), [1, 0, 1]);
+        // The buttons are debounced independently.
+        let mut debouncer = Debouncer::new(2);
+        debouncer.update(0b01);
+        assert_eq!(debouncer.update(0b11), 0b01);
+        assert_eq!(debouncer.update(0b10), 0b11);
+    }
+
+    #[test]
+    fn test_button_input() {
+        let mut keymap = KeyMap::unmapped();
+        for (button, action) in [Action::Left, Action::HardDrop, Action::Start]
+            .into_iter()
+            .enumerate()
+        {
+            keymap.set(button, action);
+        }
+        assert_eq!(
+            keymap.to_held_bits(0b101),
+            1 << Action::Left as u16 | 1 << Action::Start as u16,
+        );
+        // A long hold is pressed on the first tick it is debounced, and held after that, and so is its button.
+        let mut buttons = ButtonInput::new(keymap, 2);
+        let mut ticks = Vec::new();
+        for sample in [0b010, 0b010, 0b010, 0b010, 0b000, 0b000] {
+            buttons.sample(sample);
+            let input = buttons.next_input();
+            ticks.push((
+                input.is_pressed(Action::HardDrop),
+                input.is_held(Action::HardDrop),
+                input.get_raw_pressed(),
+            ));
+        }
+        assert_eq!(
+            ticks,
+            [
+                (false, false, 0),
+                (true, true, 0b010),
+                (false, true, 0),
+                (false, true, 0),
+                (false, true, 0),
+                (false, false, 0),
+            ],
+        );
+    }
+}
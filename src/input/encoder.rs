@@ -0,0 +1,346 @@
+//! A rotary encoder with a push button, as an input backend instead of a d-pad.
+//!
+//! The two contacts of the encoder are decoded with a full-state machine, which only reports a detent once the
+//! contacts went through all four states of a step and came back to rest. Contact bounce moves it back and forth
+//! between neighboring states without reporting anything, so no separate debouncing is needed.
+//!
+//! Every detent becomes a single press of `Action::Left` or `Action::Right`, which shifts the piece while playing
+//! and cycles the letter while entering a name. The push button rotates the piece, or starts, resumes and selects
+//! depending on the state of the game.
+use crate::game::GameState;
+use crate::input::buttons::Debouncer;
+use crate::input::{Action, InputState};
+
+/// The maximum number of detents waiting to be played, so a fast spin does not keep shifting long after.
+pub const MAX_PENDING: i8 = 8;
+
+/// The direction of a detent.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    /// Clockwise, contact A closing and opening before contact B.
+    Cw,
+    /// Counter-clockwise, contact B closing and opening before contact A.
+    Ccw,
+}
+
+// The states of the decoder, between two detents at rest with both contacts open.
+const START: u8 = 0;
+const CW_FINAL: u8 = 1;
+const CW_BEGIN: u8 = 2;
+const CW_NEXT: u8 = 3;
+const CCW_BEGIN: u8 = 4;
+const CCW_FINAL: u8 = 5;
+const CCW_NEXT: u8 = 6;
+
+/// The flags of the transitions completing a detent.
+const EMIT_CW: u8 = 0x10;
+const EMIT_CCW: u8 = 0x20;
+
+/// The next state of the decoder, by the current state and the levels `a << 1 | b` of the contacts.
+const TRANSITIONS: [[u8; 4]; 7] = [
+    // START
+    [START, CW_BEGIN, CCW_BEGIN, START],
+    // CW_FINAL
+    [CW_NEXT, START, CW_FINAL, START | EMIT_CW],
+    // CW_BEGIN
+    [CW_NEXT, CW_BEGIN, START, START],
+    // CW_NEXT
+    [CW_NEXT, CW_BEGIN, CW_FINAL, START],
+    // CCW_BEGIN
+    [CCW_NEXT, START, CCW_BEGIN, START],
+    // CCW_FINAL
+    [CCW_NEXT, CCW_FINAL, START, START | EMIT_CCW],
+    // CCW_NEXT
+    [CCW_NEXT, CCW_FINAL, CCW_BEGIN, START],
+];
+
+/// A full-state decoder of the two contacts of a quadrature encoder with one step per detent.
+/// # Attributes
+/// - `state` - The progress through the current step, one of the states of `TRANSITIONS`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct QuadratureDecoder {
+    state: u8,
+}
+
+impl QuadratureDecoder {
+    /// Create a decoder at rest.
+    /// # Returns
+    /// - `QuadratureDecoder` - The decoder, expecting both contacts open
+    pub fn new() -> Self {
+        QuadratureDecoder { state: START }
+    }
+
+    /// Feed a sample of the contacts.
+    /// # Arguments
+    /// - `a` - The level of contact A, `true` while open, i.e. high with a pull-up
+    /// - `b` - The level of contact B, `true` while open
+    /// # Returns
+    /// - `Option<Direction>` - The direction of the detent completed by the sample, if any
+    pub fn feed(&mut self, a: bool, b: bool) -> Option<Direction> {
+        let next = TRANSITIONS[self.state as usize][(a as usize) << 1 | b as usize];
+        self.state = next & 0x0F;
+        match next & (EMIT_CW | EMIT_CCW) {
+            EMIT_CW => Some(Direction::Cw),
+            EMIT_CCW => Some(Direction::Ccw),
+            _ => None,
+        }
+    }
+}
+
+/// Get the action of the push button in a state of the game.
+/// # Arguments
+/// - `state` - The `GameState` when the button goes down
+/// # Returns
+/// - `Action` - `Action::Start` on the menu and after a game, `Action::Pause` to resume, `Action::RotateCw` otherwise,
+///   which also accepts a letter of the name entry
+pub fn button_action(state: GameState) -> Action {
+    match state {
+        GameState::Menu | GameState::GameOver(_) | GameState::Finished(_) => Action::Start,
+        GameState::Paused => Action::Pause,
+        _ => Action::RotateCw,
+    }
+}
+
+/// The input state of an encoder with a push button.
+/// # Attributes
+/// - `decoder` - The `QuadratureDecoder` of the contacts
+/// - `debouncer` - The `Debouncer` of the push button
+/// - `pending` - The detents not played yet, positive clockwise, at most `MAX_PENDING` either way
+/// - `pulse` - Whether (`true`) or not (`false`) a detent was played the previous tick, so it is released first
+/// - `button` - The action of the push button while it is down, chosen when it went down
+/// - `input` - The `InputState` of the latest tick
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EncoderInput {
+    decoder: QuadratureDecoder,
+    debouncer: Debouncer,
+    pending: i8,
+    pulse: bool,
+    button: Option<Action>,
+    input: InputState,
+}
+
+impl EncoderInput {
+    /// Create the input of an encoder at rest with the button up.
+    /// # Arguments
+    /// - `threshold` - The number of samples to debounce the push button, see `Debouncer::new()`
+    /// # Returns
+    /// - `EncoderInput` - The encoder input
+    pub fn new(threshold: u8) -> Self {
+        EncoderInput {
+            decoder: QuadratureDecoder::new(),
+            debouncer: Debouncer::new(threshold),
+            pending: 0,
+            pulse: false,
+            button: None,
+            input: InputState::default(),
+        }
+    }
+
+    /// Feed a sample of the contacts and the push button, as often as possible to catch every step.
+    /// # Arguments
+    /// - `a` - The level of contact A, see `QuadratureDecoder::feed()`
+    /// - `b` - The level of contact B
+    /// - `down` - Whether (`true`) or not (`false`) the push button is down
+    pub fn sample(&mut self, a: bool, b: bool, down: bool) {
+        self.pending = match self.decoder.feed(a, b) {
+            Some(Direction::Cw) => (self.pending + 1).min(MAX_PENDING),
+            Some(Direction::Ccw) => (self.pending - 1).max(-MAX_PENDING),
+            None => self.pending,
+        };
+        self.debouncer.update(down as u16);
+    }
+
+    /// Create the input state of the next tick, playing at most one detent.
+    ///
+    /// A detent presses its action for a single tick and releases it the next, so every detent is a separate press.
+    /// # Arguments
+    /// - `state` - The `GameState` before the tick, choosing the action of the push button
+    /// # Returns
+    /// - `InputState` - The input state to pass to `Game::tick()`
+    pub fn next_input(&mut self, state: GameState) -> InputState {
+        let mut held = 0;
+        match (self.pulse, self.pending.signum()) {
+            (false, 1) => held |= 1 << Action::Right as u16,
+            (false, -1) => held |= 1 << Action::Left as u16,
+            _ => (),
+        }
+        self.pulse = held != 0;
+        self.pending -= self.pulse as i8 * self.pending.signum();
+        self.button = match self.debouncer.get_state() & 1 {
+            0 => None,
+            _ => Some(self.button.unwrap_or_else(|| button_action(state))),
+        };
+        if let Some(action) = self.button {
+            held |= 1 << action as u16;
+        }
+        self.input = self.input.next_bits(held);
+        self.input
+    }
+}
+
+#[cfg(feature = "rp2040")]
+pub use gpio::Encoder;
+
+#[cfg(feature = "rp2040")]
+mod gpio {
+    use super::EncoderInput;
+    use crate::game::GameState;
+    use crate::input::buttons::Button;
+    use crate::input::InputState;
+    use embedded_hal::digital::InputPin;
+
+    /// A rotary encoder with a push button, its contacts and button wired between GPIO pins and ground.
+    /// # Attributes
+    /// - `a` - The pin of contact A
+    /// - `b` - The pin of contact B
+    /// - `button` - The pin of the push button
+    /// - `input` - The decoded `EncoderInput` of the pins
+    pub struct Encoder {
+        a: Button,
+        b: Button,
+        button: Button,
+        input: EncoderInput,
+    }
+
+    impl Encoder {
+        /// Create the encoder from its pins.
+        /// # Arguments
+        /// - `a` - The pin of contact A, already set to a pull-up input
+        /// - `b` - The pin of contact B, already set to a pull-up input
+        /// - `button` - The pin of the push button, already set to a pull-up input
+        /// - `threshold` - The number of samples to debounce the push button, see `Debouncer::new()`
+        /// # Returns
+        /// - `Encoder` - The encoder
+        pub fn new(a: Button, b: Button, button: Button, threshold: u8) -> Self {
+            Encoder {
+                a,
+                b,
+                button,
+                input: EncoderInput::new(threshold),
+            }
+        }
+
+        /// Sample all pins once, on every iteration of the main loop rather than once per tick.
+        pub fn sample(&mut self) {
+            let a = self.a.is_high().unwrap_or(true);
+            let b = self.b.is_high().unwrap_or(true);
+            let down = self.button.is_low().unwrap_or(false);
+            self.input.sample(a, b, down);
+        }
+
+        /// Create the input state of the next tick, see `EncoderInput::next_input()`.
+        pub fn next_input(&mut self, state: GameState) -> InputState {
+            self.input.next_input(state)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{button_action, Direction, EncoderInput, QuadratureDecoder, MAX_PENDING};
+    use crate::game::{Cause, GameState};
+    use crate::input::Action;
+    use std::vec::Vec;
+
+    /// Decode a capture of the contacts, written as the levels `ab` of every sample, and collect the detents.
+    fn decode(capture: &str) -> Vec<Direction> {
+        let mut decoder = QuadratureDecoder::new();
+        capture
+            .split_whitespace()
+            .filter_map(|sample| {
+                let mut levels = sample.chars().map(|level| level == '1');
+                decoder.feed(levels.next().unwrap(), levels.next().unwrap())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_decoder() {
+        use Direction::{Ccw, Cw};
+        // Clean steps in both directions.
+        assert_eq!(decode("11 01 00 10 11"), [Cw]);
+        assert_eq!(decode("11 10 00 01 11 10 00 01 11"), [Ccw, Ccw]);
+        // Captured from a cheap encoder sampled at 10 kHz: contact A bounces when it closes and when it opens.
+        assert_eq!(
+            decode("11 11 01 11 01 01 11 01 00 00 10 00 10 10 11 11"),
+            [Cw]
+        );
+        // A turned back halfway through a step reports nothing, then the completed step back.
+        assert_eq!(decode("11 01 00 01 11 10 00 01 11"), [Ccw]);
+        // A glitch on both contacts at once, impossible for a real step, is ignored.
+        assert_eq!(decode("11 00 11 01 00 10 11"), [Cw]);
+        // Spinning fast with bounce on every edge still counts every detent.
+        let spin = "11 01 11 01 00 01 00 10 00 10 11 10 11 ".repeat(12);
+        assert_eq!(decode(&spin).len(), 12);
+        assert!(decode(&spin).iter().all(|direction| *direction == Cw));
+    }
+
+    /// Turn the encoder by a number of clean detents.
+    fn turn(encoder: &mut EncoderInput, detents: i32) {
+        let step: [(bool, bool); 4] = match detents > 0 {
+            true => [(false, true), (false, false), (true, false), (true, true)],
+            false => [(true, false), (false, false), (false, true), (true, true)],
+        };
+        for _ in 0..detents.abs() {
+            for (a, b) in step {
+                encoder.sample(a, b, false);
+            }
+        }
+    }
+
+    #[test]
+    fn test_encoder_input() {
+        let mut encoder = EncoderInput::new(2);
+        // Every detent is a separate press, with a release in between.
+        turn(&mut encoder, 2);
+        let presses: Vec<(bool, bool)> = (0..5)
+            .map(|_| {
+                let input = encoder.next_input(GameState::Playing);
+                (
+                    input.is_pressed(Action::Right),
+                    input.is_held(Action::Right),
+                )
+            })
+            .collect();
+        assert_eq!(
+            presses,
+            [
+                (true, true),
+                (false, false),
+                (true, true),
+                (false, false),
+                (false, false)
+            ]
+        );
+        // Turning back cancels pending detents, and a fast spin is capped.
+        turn(&mut encoder, 3);
+        turn(&mut encoder, -1);
+        turn(&mut encoder, -20);
+        let mut lefts = 0;
+        for _ in 0..40 {
+            let input = encoder.next_input(GameState::Playing);
+            assert!(!input.is_held(Action::Right));
+            lefts += input.is_pressed(Action::Left) as i8;
+        }
+        assert_eq!(lefts, MAX_PENDING);
+        // The push button keeps the action it went down with.
+        encoder.sample(true, true, true);
+        encoder.sample(true, true, true);
+        let input = encoder.next_input(GameState::Menu);
+        assert!(input.is_pressed(Action::Start));
+        let input = encoder.next_input(GameState::Countdown(3));
+        assert!(input.is_held(Action::Start));
+        assert!(!input.is_held(Action::RotateCw));
+    }
+
+    #[test]
+    fn test_button_action() {
+        assert_eq!(button_action(GameState::Menu), Action::Start);
+        assert_eq!(
+            button_action(GameState::GameOver(Cause::BlockOut)),
+            Action::Start
+        );
+        assert_eq!(button_action(GameState::Paused), Action::Pause);
+        assert_eq!(button_action(GameState::Playing), Action::RotateCw);
+    }
+}
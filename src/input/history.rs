@@ -0,0 +1,164 @@
+//! The most recent actions of the player, for an on-screen input display while streaming or recording.
+//!
+//! The game records the actions as they resolve, after the auto-repeat and only when they took effect, so the
+//! history shows the moves that actually happened: a shift for every tick the piece moved sideways, a rotation only
+//! when it found a spot. Every entry ages by a tick for every tick of play, see `display::actions` for the widget
+//! fading them out.
+use crate::input::Action;
+
+/// The number of actions kept in the history, the oldest action is dropped for a new one.
+pub const HISTORY_LEN: usize = 8;
+
+/// The inputs spent on a single piece.
+/// # Attributes
+/// - `rotations` - The number of rotations of the piece, in either direction
+/// - `shifts` - The number of ticks the piece moved sideways
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PieceInputs {
+    pub rotations: u32,
+    pub shifts: u32,
+}
+
+#[derive(Debug)]
+pub struct InputHistory {
+    /// The recent actions of the player and the inputs spent on the pieces.
+    /// # Attributes
+    /// - `entries` - The actions and the tick they were taken at, oldest first
+    /// - `now` - The number of ticks recorded, wrapping after 2^32 ticks
+    /// - `piece` - The inputs spent on the active piece
+    /// - `last_piece` - The inputs spent on the last locked piece
+    entries: heapless::Deque<(Action, u32), HISTORY_LEN>,
+    now: u32,
+    piece: PieceInputs,
+    last_piece: PieceInputs,
+}
+
+impl InputHistory {
+    /// Create an empty history.
+    /// # Returns
+    /// - `InputHistory` - The history without any actions
+    pub fn new() -> Self {
+        InputHistory {
+            entries: heapless::Deque::new(),
+            now: 0,
+            piece: PieceInputs::default(),
+            last_piece: PieceInputs::default(),
+        }
+    }
+
+    /// Record an action that took effect this tick, dropping the oldest action if the history is full.
+    /// # Arguments
+    /// - `action` - The `Action` that took effect
+    pub fn record(&mut self, action: Action) {
+        if self.entries.is_full() {
+            self.entries.pop_front();
+        }
+        self.entries
+            .push_back((action, self.now))
+            .expect("the oldest entry was dropped to make room");
+        match action {
+            Action::RotateCw | Action::RotateCcw => self.piece.rotations += 1,
+            Action::Left | Action::Right => self.piece.shifts += 1,
+            _ => (),
+        }
+    }
+
+    /// Age all recorded actions by a single tick.
+    pub fn tick(&mut self) {
+        self.now = self.now.wrapping_add(1);
+    }
+
+    /// Finish the inputs of the active piece once it locks, starting to count for the next piece.
+    pub fn end_piece(&mut self) {
+        self.last_piece = core::mem::take(&mut self.piece);
+    }
+
+    /// Get the recorded actions with their ages.
+    /// # Returns
+    /// - `impl Iterator<Item = (Action, u32)>` - The actions and the number of ticks since they were taken, newest
+    ///   first
+    pub fn iter(&self) -> impl Iterator<Item = (Action, u32)> + '_ {
+        self.entries
+            .iter()
+            .rev()
+            .map(|(action, tick)| (*action, self.now.wrapping_sub(*tick)))
+    }
+
+    /// Get the inputs spent on the active piece so far.
+    /// # Returns
+    /// - `PieceInputs` - The rotations and shifts of the active piece
+    pub fn get_piece(&self) -> PieceInputs {
+        self.piece
+    }
+
+    /// Get the inputs spent on the last locked piece.
+    /// # Returns
+    /// - `PieceInputs` - The rotations and shifts of the last piece, all 0 before the first piece locks
+    pub fn get_last_piece(&self) -> PieceInputs {
+        self.last_piece
+    }
+}
+
+impl Default for InputHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{InputHistory, PieceInputs, HISTORY_LEN};
+    use crate::input::{Action, ACTIONS};
+
+    #[test]
+    fn test_evicts_oldest() {
+        let mut history = InputHistory::new();
+        for index in 0..HISTORY_LEN + 2 {
+            history.record(ACTIONS[index % ACTIONS.len()]);
+        }
+        let actions: Vec<Action> = history.iter().map(|(action, _)| action).collect();
+        assert_eq!(actions.len(), HISTORY_LEN);
+        // The newest action comes first, the first two actions were dropped.
+        assert_eq!(actions[0], ACTIONS[(HISTORY_LEN + 1) % ACTIONS.len()]);
+        assert_eq!(actions[HISTORY_LEN - 1], ACTIONS[2]);
+    }
+
+    #[test]
+    fn test_ages() {
+        let mut history = InputHistory::new();
+        history.record(Action::Left);
+        history.tick();
+        history.tick();
+        history.record(Action::RotateCw);
+        history.tick();
+        let ages: Vec<(Action, u32)> = history.iter().collect();
+        assert_eq!(ages, [(Action::RotateCw, 1), (Action::Left, 3)]);
+        // The ages keep counting across the wrap of the tick counter.
+        history.now = u32::MAX;
+        history.record(Action::Hold);
+        history.tick();
+        assert_eq!(history.iter().next(), Some((Action::Hold, 1)));
+    }
+
+    #[test]
+    fn test_piece_inputs() {
+        let mut history = InputHistory::new();
+        for action in [
+            Action::RotateCw,
+            Action::Left,
+            Action::Left,
+            Action::RotateCcw,
+        ] {
+            history.record(action);
+        }
+        history.record(Action::HardDrop);
+        let spent = PieceInputs {
+            rotations: 2,
+            shifts: 2,
+        };
+        assert_eq!(history.get_piece(), spent);
+        history.end_piece();
+        assert_eq!(history.get_piece(), PieceInputs::default());
+        assert_eq!(history.get_last_piece(), spent);
+    }
+}
@@ -0,0 +1,204 @@
+//! The map from the physical inputs of a build, e.g. its GPIO buttons or the keys of the simulator, to the actions of
+//! the game.
+//!
+//! The map is part of the `Settings`, so every build can wire its buttons in its own order, and the player can remap
+//! them from the controls screen of the menu. The input backends report their inputs by index, bit `n` of a sample for
+//! input `n`, and resolve them through the map before producing an `InputState`.
+use crate::input::buttons::MAX_BUTTONS;
+use crate::input::{Action, ACTIONS};
+use crate::settings::SettingsError;
+
+/// The number of physical inputs of a `KeyMap`, one per bit of a sample of the buttons.
+pub const KEYMAP_INPUTS: usize = MAX_BUTTONS;
+
+/// The byte of an input without an action in the serialized map.
+const UNMAPPED: u8 = u8::MAX;
+
+/// The `Action` of every physical input.
+///
+/// A valid map, see `.validate()`, maps every action of `ACTIONS` to exactly one input, and leaves the other inputs
+/// without an action.
+/// # Attributes
+/// - `actions` - The action of every input, if any
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct KeyMap {
+    actions: [Option<Action>; KEYMAP_INPUTS],
+}
+
+impl Default for KeyMap {
+    /// Map the first inputs to the actions in the order of `ACTIONS`, the order of the buttons on GPIO 2 to 10 of the
+    /// firmware.
+    fn default() -> Self {
+        let mut keymap = KeyMap::unmapped();
+        for (input, action) in ACTIONS.iter().enumerate() {
+            keymap.actions[input] = Some(*action);
+        }
+        keymap
+    }
+}
+
+impl KeyMap {
+    /// Create a map without any actions, e.g. to fill one input at a time.
+    /// # Returns
+    /// - `KeyMap` - The map, invalid until every action is mapped
+    pub fn unmapped() -> Self {
+        KeyMap {
+            actions: [None; KEYMAP_INPUTS],
+        }
+    }
+
+    /// Get the action of an input.
+    /// # Arguments
+    /// - `input` - The index of the physical input
+    /// # Returns
+    /// - `Option<Action>` - The action, or `None` if the input is unmapped or out of range
+    pub fn get_action(&self, input: usize) -> Option<Action> {
+        self.actions.get(input).copied().flatten()
+    }
+
+    /// Get the first input mapped to an action.
+    /// # Arguments
+    /// - `action` - The `Action` to look up
+    /// # Returns
+    /// - `Option<usize>` - The index of the physical input, or `None` if the action is unmapped
+    pub fn get_input(&self, action: Action) -> Option<usize> {
+        self.actions
+            .iter()
+            .position(|mapped| *mapped == Some(action))
+    }
+
+    /// Map an input to an action, handing the previous action of the input to the inputs of the action, so two
+    /// inputs swap their actions and a valid map stays valid.
+    /// # Arguments
+    /// - `input` - The index of the physical input, ignored if out of range
+    /// - `action` - The `Action` of the input
+    pub fn set(&mut self, input: usize, action: Action) {
+        let Some(previous) = self.actions.get(input).copied() else {
+            return;
+        };
+        for mapped in self.actions.iter_mut() {
+            if *mapped == Some(action) {
+                *mapped = previous;
+            }
+        }
+        self.actions[input] = Some(action);
+    }
+
+    /// Map a bit set of inputs to the bit set of their actions, as consumed by `InputState::next_bits()`.
+    /// # Arguments
+    /// - `inputs` - The bit set of inputs that are down, bit `n` for input `n`
+    /// # Returns
+    /// - `u16` - The bit set of held actions, see `InputState::get_held_bits()`
+    pub fn to_held_bits(&self, inputs: u16) -> u16 {
+        self.actions
+            .iter()
+            .enumerate()
+            .filter(|(input, _)| inputs & (1 << input) != 0)
+            .filter_map(|(_, action)| *action)
+            .fold(0, |bits, action| bits | 1 << action as u16)
+    }
+
+    /// Check that every action is mapped to exactly one input.
+    /// # Returns
+    /// - `Result<(), SettingsError>` - Nothing, or a `SettingsError::KeyMap` with the first action that is not
+    pub fn validate(&self) -> Result<(), SettingsError> {
+        for action in ACTIONS {
+            let inputs = self
+                .actions
+                .iter()
+                .filter(|mapped| **mapped == Some(action))
+                .count();
+            if inputs != 1 {
+                return Err(SettingsError::KeyMap(action));
+            }
+        }
+        Ok(())
+    }
+
+    /// Serialize the map, a byte per input.
+    /// # Returns
+    /// - `[u8; KEYMAP_INPUTS]` - The discriminant of the action of every input, or 255 for an unmapped input
+    pub fn to_bytes(&self) -> [u8; KEYMAP_INPUTS] {
+        self.actions
+            .map(|action| action.map_or(UNMAPPED, |action| action as u8))
+    }
+
+    /// Deserialize a map written by `.to_bytes()`, without validating it.
+    /// # Arguments
+    /// - `bytes` - A reference to the serialized map
+    /// # Returns
+    /// - `Result<KeyMap, SettingsError>` - The map, or a `SettingsError::Action` with the first unknown action
+    pub fn from_bytes(bytes: &[u8; KEYMAP_INPUTS]) -> Result<Self, SettingsError> {
+        let mut keymap = KeyMap::unmapped();
+        for (mapped, byte) in keymap.actions.iter_mut().zip(bytes) {
+            *mapped = match *byte {
+                UNMAPPED => None,
+                byte => Some(
+                    *ACTIONS
+                        .get(byte as usize)
+                        .ok_or(SettingsError::Action(byte))?,
+                ),
+            };
+        }
+        Ok(keymap)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{KeyMap, KEYMAP_INPUTS};
+    use crate::input::Action;
+    use crate::settings::SettingsError;
+
+    #[test]
+    fn test_set() {
+        // Mapping an input to an action swaps it with the input of that action.
+        let mut keymap = KeyMap::default();
+        assert_eq!(keymap.validate(), Ok(()));
+        keymap.set(3, Action::RotateCw);
+        assert_eq!(keymap.get_action(3), Some(Action::RotateCw));
+        assert_eq!(keymap.get_action(2), Some(Action::RotateCcw));
+        assert_eq!(keymap.validate(), Ok(()));
+        assert_eq!(
+            keymap.to_held_bits(0b1001),
+            1 << Action::Left as u16 | 1 << Action::RotateCw as u16
+        );
+        // An unmapped input takes over the action, leaving its old input unmapped.
+        keymap.set(12, Action::Hold);
+        assert_eq!(keymap.get_input(Action::Hold), Some(12));
+        assert_eq!(keymap.get_action(6), None);
+        assert_eq!(keymap.to_held_bits(1 << 6), 0);
+        keymap.set(KEYMAP_INPUTS, Action::Left);
+        assert_eq!(keymap.get_input(Action::Left), Some(0));
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut keymap = KeyMap::default();
+        keymap.actions[5] = None;
+        assert_eq!(
+            keymap.validate(),
+            Err(SettingsError::KeyMap(Action::HardDrop))
+        );
+        keymap.actions[5] = Some(Action::Left);
+        assert_eq!(keymap.validate(), Err(SettingsError::KeyMap(Action::Left)));
+        assert_eq!(
+            KeyMap::unmapped().validate(),
+            Err(SettingsError::KeyMap(Action::Left))
+        );
+    }
+
+    #[test]
+    fn test_bytes() {
+        let mut keymap = KeyMap::default();
+        keymap.set(15, Action::Start);
+        let bytes = keymap.to_bytes();
+        assert_eq!(bytes[..3], [0, 1, 2]);
+        assert_eq!(bytes[8], u8::MAX);
+        assert_eq!(bytes[15], Action::Start as u8);
+        assert_eq!(KeyMap::from_bytes(&bytes), Ok(keymap));
+        let mut unknown = bytes;
+        unknown[4] = 9;
+        assert_eq!(KeyMap::from_bytes(&unknown), Err(SettingsError::Action(9)));
+    }
+}
@@ -0,0 +1,258 @@
+//! A fixed-capacity queue of input events, handing button edges from GPIO interrupts to the main loop.
+//!
+//! A single producer, e.g. an interrupt handler, pushes `(Action, Edge)` events and a single consumer, the game
+//! loop, drains them once per tick into the `InputState`. Both sides own one of the two indices and only read the
+//! other one, so no compare-and-swap is needed and only atomic loads and stores are used, which the Cortex-M0+ of
+//! the RP2040 supports. Every slot is an atomic byte, so the queue is sound without any `unsafe`.
+use super::{Action, InputState, ACTIONS};
+use core::sync::atomic::{AtomicU16, AtomicU32, AtomicU8, Ordering};
+
+/// The transition of a button.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Edge {
+    /// The button went down.
+    Down,
+    /// The button went up.
+    Up,
+}
+
+/// Encode an event into the byte of a slot.
+fn _encode(action: Action, edge: Edge) -> u8 {
+    (action as u8) << 1 | edge as u8
+}
+
+/// Decode the byte of a slot into an event.
+fn _decode(byte: u8) -> (Action, Edge) {
+    let edge = match byte & 1 {
+        0 => Edge::Down,
+        _ => Edge::Up,
+    };
+    (ACTIONS[(byte >> 1) as usize], edge)
+}
+
+/// A single-producer single-consumer ring buffer of `N` input events.
+///
+/// Only a single thread or interrupt may push and only a single one may pop or drain at a time. The indices count
+/// every push and pop and wrap around, so all `N` slots are used. `N` should be a power of two to keep the slots in
+/// order when the indices wrap, after 2^32 events.
+/// # Attributes
+/// - `slots` - The encoded events
+/// - `head` - The number of popped events, only stored by the consumer
+/// - `tail` - The number of pushed events, only stored by the producer
+/// - `overflows` - The number of events dropped because the queue was full, only stored by the producer
+/// - `releases` - The bit set of actions released in the tick they were pressed, only used by the consumer
+#[derive(Debug)]
+pub struct EventQueue<const N: usize> {
+    slots: [AtomicU8; N],
+    head: AtomicU32,
+    tail: AtomicU32,
+    overflows: AtomicU32,
+    releases: AtomicU16,
+}
+
+impl<const N: usize> EventQueue<N> {
+    /// Create an empty queue, e.g. in a `static`.
+    /// # Returns
+    /// - `EventQueue<N>` - The empty queue
+    pub const fn new() -> Self {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const EMPTY: AtomicU8 = AtomicU8::new(0);
+        EventQueue {
+            slots: [EMPTY; N],
+            head: AtomicU32::new(0),
+            tail: AtomicU32::new(0),
+            overflows: AtomicU32::new(0),
+            releases: AtomicU16::new(0),
+        }
+    }
+
+    /// Get the number of queued events.
+    pub fn get_len(&self) -> usize {
+        let tail = self.tail.load(Ordering::Acquire);
+        tail.wrapping_sub(self.head.load(Ordering::Acquire)) as usize
+    }
+
+    /// Get the number of events dropped because the queue was full.
+    pub fn get_overflows(&self) -> u32 {
+        self.overflows.load(Ordering::Relaxed)
+    }
+
+    /// Queue an event, on the producer side.
+    /// # Arguments
+    /// - `action` - The `Action` of the button
+    /// - `edge` - The `Edge` of the button
+    /// # Returns
+    /// - `bool` - Whether (`true`) or not (`false`) the event was queued, `false` when it was dropped on overflow
+    pub fn push(&self, action: Action, edge: Edge) -> bool {
+        let tail = self.tail.load(Ordering::Relaxed);
+        if tail.wrapping_sub(self.head.load(Ordering::Acquire)) as usize >= N {
+            let overflows = self.overflows.load(Ordering::Relaxed);
+            self.overflows
+                .store(overflows.wrapping_add(1), Ordering::Relaxed);
+            return false;
+        }
+        self.slots[tail as usize % N].store(_encode(action, edge), Ordering::Relaxed);
+        // Publish the slot before the index.
+        self.tail.store(tail.wrapping_add(1), Ordering::Release);
+        true
+    }
+
+    /// Take the oldest event, on the consumer side.
+    /// # Returns
+    /// - `Option<(Action, Edge)>` - The oldest event, or `None` if the queue is empty
+    pub fn pop(&self) -> Option<(Action, Edge)> {
+        let head = self.head.load(Ordering::Relaxed);
+        if self.tail.load(Ordering::Acquire) == head {
+            return None;
+        }
+        let event = _decode(self.slots[head as usize % N].load(Ordering::Relaxed));
+        // Free the slot only after it was read.
+        self.head.store(head.wrapping_add(1), Ordering::Release);
+        Some(event)
+    }
+
+    /// Take all queued events and fold them into the input state of the next tick, on the consumer side.
+    ///
+    /// An action that goes down and up again within a single tick is still pressed and held for that tick, and
+    /// released at the next drain, so short taps are never lost.
+    /// # Arguments
+    /// - `previous` - A reference to the `InputState` of the previous tick
+    /// # Returns
+    /// - `InputState` - The input state of the next tick
+    pub fn drain(&self, previous: &InputState) -> InputState {
+        let mut held = previous.held & !self.releases.load(Ordering::Relaxed);
+        let mut pressed = 0;
+        let mut releases = 0;
+        while let Some((action, edge)) = self.pop() {
+            let bit = 1 << action as u16;
+            match edge {
+                Edge::Down => {
+                    pressed |= bit & !held;
+                    held |= bit;
+                    releases &= !bit;
+                }
+                Edge::Up => match pressed & bit {
+                    0 => held &= !bit,
+                    _ => releases |= bit,
+                },
+            }
+        }
+        self.releases.store(releases, Ordering::Relaxed);
+        InputState {
+            held,
+            pressed,
+            raw: 0,
+        }
+    }
+}
+
+impl<const N: usize> Default for EventQueue<N> {
+    fn default() -> Self {
+        EventQueue::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Edge, EventQueue};
+    use crate::input::{Action, InputState, ACTIONS};
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn test_push_pop() {
+        let queue = EventQueue::<4>::new();
+        assert_eq!(queue.pop(), None);
+        for (action, edge) in [
+            (Action::Left, Edge::Down),
+            (Action::Start, Edge::Up),
+            (Action::Left, Edge::Up),
+        ] {
+            assert!(queue.push(action, edge));
+        }
+        assert_eq!(queue.pop(), Some((Action::Left, Edge::Down)));
+        // The indices wrap around the slots.
+        assert!(queue.push(Action::Hold, Edge::Down));
+        assert!(queue.push(Action::Pause, Edge::Down));
+        assert_eq!(queue.get_len(), 4);
+        // A full queue drops the event and counts the overflow.
+        assert!(!queue.push(Action::HardDrop, Edge::Down));
+        assert_eq!(queue.get_overflows(), 1);
+        assert_eq!(queue.pop(), Some((Action::Start, Edge::Up)));
+        assert_eq!(queue.pop(), Some((Action::Left, Edge::Up)));
+        assert_eq!(queue.pop(), Some((Action::Hold, Edge::Down)));
+        assert_eq!(queue.pop(), Some((Action::Pause, Edge::Down)));
+        assert_eq!(queue.pop(), None);
+        // Every action survives the encoding.
+        for action in ACTIONS {
+            assert!(queue.push(action, Edge::Up));
+            assert_eq!(queue.pop(), Some((action, Edge::Up)));
+        }
+    }
+
+    #[test]
+    fn test_drain() {
+        let queue = EventQueue::<8>::new();
+        queue.push(Action::Left, Edge::Down);
+        queue.push(Action::HardDrop, Edge::Down);
+        let first = queue.drain(&InputState::default());
+        assert!(first.is_pressed(Action::Left) && first.is_held(Action::Left));
+        assert!(first.is_pressed(Action::HardDrop));
+        // Without events the actions stay held.
+        queue.push(Action::HardDrop, Edge::Up);
+        let second = queue.drain(&first);
+        assert!(!second.is_pressed(Action::Left) && second.is_held(Action::Left));
+        assert!(!second.is_held(Action::HardDrop));
+        // A tap within a single tick is pressed for that tick and released at the next one.
+        queue.push(Action::RotateCw, Edge::Down);
+        queue.push(Action::RotateCw, Edge::Up);
+        let third = queue.drain(&second);
+        assert!(third.is_pressed(Action::RotateCw));
+        let fourth = queue.drain(&third);
+        assert!(!fourth.is_held(Action::RotateCw));
+        assert!(fourth.is_held(Action::Left));
+        // A release and a press of a held action within a tick press it again.
+        queue.push(Action::Left, Edge::Up);
+        queue.push(Action::Left, Edge::Down);
+        let fifth = queue.drain(&fourth);
+        assert!(fifth.is_held(Action::Left) && fifth.is_pressed(Action::Left));
+    }
+
+    #[test]
+    fn test_threads() {
+        // A producer thread pushes a known sequence, retrying on overflow, while the consumer pops it in order.
+        const EVENTS: usize = 100_000;
+        let event = |index: usize| {
+            let edge = match index & 1 {
+                0 => Edge::Down,
+                _ => Edge::Up,
+            };
+            (ACTIONS[index / 2 % ACTIONS.len()], edge)
+        };
+        let queue = Arc::new(EventQueue::<16>::new());
+        let producer = {
+            let queue = queue.clone();
+            thread::spawn(move || {
+                for index in 0..EVENTS {
+                    let (action, edge) = event(index);
+                    while !queue.push(action, edge) {
+                        thread::yield_now();
+                    }
+                }
+            })
+        };
+        let mut index = 0;
+        while index < EVENTS {
+            match queue.pop() {
+                Some(popped) => {
+                    assert_eq!(popped, event(index));
+                    index += 1;
+                }
+                None => thread::yield_now(),
+            }
+        }
+        producer.join().unwrap();
+        assert_eq!(queue.pop(), None);
+    }
+}
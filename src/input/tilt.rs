@@ -0,0 +1,369 @@
+//! Tilt controls, steering the pieces by tilting the whole device with an I2C accelerometer.
+//!
+//! Every reading of the accelerometer goes through a pipeline of integer math: the axes are remapped to the
+//! mounting of the board, low-pass filtered to smooth out jerks and noise, cut off below a deadzone and compared
+//! against a pair of thresholds. Tilting sideways past `TiltConfig::press` holds `Action::Left` or `Action::Right`
+//! until the tilt falls back below `TiltConfig::release`, tilting the top of the panel away holds
+//! `Action::SoftDrop`. The gap between both thresholds keeps a tilt hovering around one of them from chattering.
+//!
+//! The held actions are a bit set as for `InputState::next_bits()`, so tilting feeds the same auto-repeat as the
+//! buttons, and both can be combined by OR-ing their bits.
+use crate::input::Action;
+
+/// The reading of 1 g, for 16 bit readings at a range of ±2 g.
+pub const ONE_G: i32 = 16384;
+
+/// The number of fractional bits of the filter state.
+const FRACTION: u32 = 4;
+
+/// How the accelerometer is mounted, as the rotation of its x axis from the right edge of the panel, clockwise
+/// when looking at the panel, with its z axis pointing out of the panel.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Mounting {
+    /// The x axis points right, the y axis to the top of the panel.
+    #[default]
+    Rot0,
+    /// The x axis points down.
+    Rot90,
+    /// The x axis points left.
+    Rot180,
+    /// The x axis points up.
+    Rot270,
+}
+
+impl Mounting {
+    /// Remap a reading of the accelerometer to the axes of the panel.
+    /// # Arguments
+    /// - `raw` - The [x, y, z] reading of the accelerometer
+    /// # Returns
+    /// - `[i32; 2]` - The [sideways, forward] acceleration, positive when tilting right and away respectively
+    pub fn remap(&self, raw: [i16; 3]) -> [i32; 2] {
+        let [x, y] = [raw[0] as i32, raw[1] as i32];
+        // At rest an accelerometer reads 1 g upward, so an axis tilting down reads negative.
+        match self {
+            Mounting::Rot0 => [-x, -y],
+            Mounting::Rot90 => [-y, x],
+            Mounting::Rot180 => [x, y],
+            Mounting::Rot270 => [y, -x],
+        }
+    }
+}
+
+/// An exponential low-pass filter in fixed point.
+/// # Attributes
+/// - `state` - The filtered value, with `FRACTION` fractional bits
+/// - `shift` - The smoothing, every sample moves the value by `1 / 2^shift` of the difference
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LowPass {
+    state: i32,
+    shift: u32,
+}
+
+impl LowPass {
+    /// Create a filter at rest.
+    /// # Arguments
+    /// - `shift` - The smoothing, 0 to pass the samples through and higher to smooth more
+    /// # Returns
+    /// - `LowPass` - The filter with a value of 0
+    pub fn new(shift: u32) -> Self {
+        LowPass { state: 0, shift }
+    }
+
+    /// Get the filtered value.
+    pub fn get_value(&self) -> i32 {
+        self.state >> FRACTION
+    }
+
+    /// Feed a sample.
+    /// # Arguments
+    /// - `sample` - The next sample
+    /// # Returns
+    /// - `i32` - The filtered value
+    pub fn update(&mut self, sample: i32) -> i32 {
+        self.state += ((sample << FRACTION) - self.state) >> self.shift;
+        self.get_value()
+    }
+}
+
+/// The tuning of the tilt controls, in readings of the accelerometer where `ONE_G` is a tilt of 90 degrees.
+/// # Attributes
+/// - `mounting` - How the accelerometer is mounted
+/// - `shift` - The smoothing of the low-pass filter, see `LowPass::new()`
+/// - `deadzone` - The tilt treated as level
+/// - `press` - The tilt holding an action
+/// - `release` - The tilt releasing a held action, below `press`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TiltConfig {
+    pub mounting: Mounting,
+    pub shift: u32,
+    pub deadzone: i32,
+    pub press: i32,
+    pub release: i32,
+}
+
+impl Default for TiltConfig {
+    /// About 3 degrees of deadzone, a press at 20 degrees and a release at 12 degrees.
+    fn default() -> Self {
+        TiltConfig {
+            mounting: Mounting::Rot0,
+            shift: 2,
+            deadzone: ONE_G / 20,
+            press: ONE_G * 11 / 32,
+            release: ONE_G * 7 / 34,
+        }
+    }
+}
+
+/// A pair of thresholds with hysteresis.
+/// # Arguments
+/// - `active` - Whether (`true`) or not (`false`) the action is held
+/// - `tilt` - The tilt toward the action
+/// - `config` - A reference to the `TiltConfig` holding the thresholds
+/// # Returns
+/// - `bool` - Whether (`true`) or not (`false`) the action is held after the tilt
+fn _hysteresis(active: bool, tilt: i32, config: &TiltConfig) -> bool {
+    match active {
+        true => tilt >= config.release,
+        false => tilt >= config.press,
+    }
+}
+
+/// The tilt controls, turning readings of the accelerometer into held actions.
+/// # Attributes
+/// - `config` - The tuning
+/// - `filters` - The low-pass filters of the [sideways, forward] tilt
+/// - `held` - The bit set of held actions
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tilt {
+    config: TiltConfig,
+    filters: [LowPass; 2],
+    held: u16,
+}
+
+impl Tilt {
+    /// Create the tilt controls, level and without any held actions.
+    /// # Arguments
+    /// - `config` - The tuning
+    /// # Returns
+    /// - `Tilt` - The tilt controls
+    pub fn new(config: TiltConfig) -> Self {
+        Tilt {
+            config,
+            filters: [LowPass::new(config.shift); 2],
+            held: 0,
+        }
+    }
+
+    /// Get the filtered [sideways, forward] tilt, without the deadzone.
+    pub fn get_tilt(&self) -> [i32; 2] {
+        [self.filters[0].get_value(), self.filters[1].get_value()]
+    }
+
+    /// Get the bit set of held actions, as for `InputState::next_bits()`.
+    pub fn get_held_bits(&self) -> u16 {
+        self.held
+    }
+
+    /// Feed a reading of the accelerometer.
+    /// # Arguments
+    /// - `raw` - The [x, y, z] reading of the accelerometer
+    /// # Returns
+    /// - `u16` - The bit set of held actions after the reading
+    pub fn update(&mut self, raw: [i16; 3]) -> u16 {
+        let remapped = self.config.mounting.remap(raw);
+        let [sideways, forward] = [0, 1].map(|axis| {
+            let tilt = self.filters[axis].update(remapped[axis]);
+            match tilt.abs() < self.config.deadzone {
+                true => 0,
+                false => tilt,
+            }
+        });
+        let is_held = |action: Action| self.held & 1 << action as u16 != 0;
+        let left = _hysteresis(is_held(Action::Left), -sideways, &self.config);
+        let right = _hysteresis(is_held(Action::Right), sideways, &self.config);
+        let down = _hysteresis(is_held(Action::SoftDrop), forward, &self.config);
+        self.held = [
+            (left, Action::Left),
+            (right, Action::Right),
+            (down, Action::SoftDrop),
+        ]
+        .iter()
+        .fold(0, |bits, (active, action)| match active {
+            true => bits | 1 << *action as u16,
+            false => bits,
+        });
+        self.held
+    }
+}
+
+#[cfg(feature = "rp2040")]
+pub use i2c::Accelerometer;
+
+#[cfg(feature = "rp2040")]
+mod i2c {
+    use embedded_hal::i2c::I2c;
+
+    /// The supported accelerometers, at their default addresses and a range of ±2 g.
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub enum Accelerometer {
+        /// The InvenSense MPU-6050, at address 0x68.
+        Mpu6050,
+        /// The ST LIS3DH, at address 0x18.
+        Lis3dh,
+    }
+
+    impl Accelerometer {
+        /// Get the I2C address.
+        pub fn get_address(&self) -> u8 {
+            match self {
+                Accelerometer::Mpu6050 => 0x68,
+                Accelerometer::Lis3dh => 0x18,
+            }
+        }
+
+        /// Wake the accelerometer and start measuring.
+        /// # Arguments
+        /// - `i2c` - A muteable reference to the I2C bus
+        /// # Returns
+        /// - `Result<(), I::Error>` - The error of the bus, if any
+        pub fn init<I: I2c>(&self, i2c: &mut I) -> Result<(), I::Error> {
+            let address = self.get_address();
+            match self {
+                // Clear the sleep bit of PWR_MGMT_1, the range defaults to ±2 g.
+                Accelerometer::Mpu6050 => i2c.write(address, &[0x6B, 0x00]),
+                Accelerometer::Lis3dh => {
+                    // 100 Hz with all axes in CTRL_REG1, high resolution at ±2 g in CTRL_REG4.
+                    i2c.write(address, &[0x20, 0x57])?;
+                    i2c.write(address, &[0x23, 0x08])
+                }
+            }
+        }
+
+        /// Read the acceleration.
+        /// # Arguments
+        /// - `i2c` - A muteable reference to the I2C bus
+        /// # Returns
+        /// - `Result<[i16; 3], I::Error>` - The [x, y, z] reading, where `ONE_G` is 1 g, or the error of the bus
+        pub fn read<I: I2c>(&self, i2c: &mut I) -> Result<[i16; 3], I::Error> {
+            let mut bytes = [0; 6];
+            match self {
+                // ACCEL_XOUT_H and on, big endian.
+                Accelerometer::Mpu6050 => {
+                    i2c.write_read(self.get_address(), &[0x3B], &mut bytes)?;
+                    Ok([0, 2, 4].map(|i| i16::from_be_bytes([bytes[i], bytes[i + 1]])))
+                }
+                // OUT_X_L and on with the auto-increment bit, little endian and left aligned.
+                Accelerometer::Lis3dh => {
+                    i2c.write_read(self.get_address(), &[0x28 | 0x80], &mut bytes)?;
+                    Ok([0, 2, 4].map(|i| i16::from_le_bytes([bytes[i], bytes[i + 1]])))
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LowPass, Mounting, Tilt, TiltConfig, ONE_G};
+    use crate::input::Action;
+    use std::vec::Vec;
+
+    /// Feed a trace of [sideways, forward] tilts, for `Mounting::Rot0`, and collect the held actions after each of them.
+    fn run(trace: &[[i32; 2]]) -> Vec<u16> {
+        let mut tilt = Tilt::new(TiltConfig::default());
+        trace
+            .iter()
+            .map(|[x, y]| tilt.update([-*x as i16, -*y as i16, ONE_G as i16]))
+            .collect()
+    }
+
+    /// Count the number of times an action went down.
+    fn presses(held: &[u16], action: Action) -> usize {
+        let bit = 1 << action as u16;
+        held.windows(2)
+            .filter(|pair| pair[0] & bit == 0 && pair[1] & bit != 0)
+            .count()
+    }
+
+    #[test]
+    fn test_remap() {
+        let raw = [100, 200, 16384];
+        assert_eq!(Mounting::Rot0.remap(raw), [-100, -200]);
+        assert_eq!(Mounting::Rot90.remap(raw), [-200, 100]);
+        assert_eq!(Mounting::Rot180.remap(raw), [100, 200]);
+        assert_eq!(Mounting::Rot270.remap(raw), [200, -100]);
+    }
+
+    #[test]
+    fn test_low_pass() {
+        let mut filter = LowPass::new(2);
+        // A step converges within a few dozen samples.
+        let values: Vec<i32> = (0..40).map(|_| filter.update(1000)).collect();
+        assert_eq!(values[0], 250);
+        assert!(values.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert!(values[39] >= 995);
+        // Negative samples converge as well.
+        for _ in 0..80 {
+            filter.update(-1000);
+        }
+        assert!(filter.get_value() <= -995);
+        // Without smoothing the samples pass through.
+        assert_eq!(LowPass::new(0).update(-1234), -1234);
+    }
+
+    #[test]
+    fn test_slow_tilt() {
+        // Tilting left to 30 degrees over a second holds left once, and releases it when coming back level.
+        let ramp = |i: i32| -ONE_G / 2 * i / 60;
+        let trace: Vec<[i32; 2]> = (0..=60)
+            .map(|i| [ramp(i), 0])
+            .chain((0..30).map(|_| [-ONE_G / 2, 0]))
+            .chain((0..=60).rev().map(|i| [ramp(i), 0]))
+            .collect();
+        let held = run(&trace);
+        assert_eq!(presses(&held, Action::Left), 1);
+        assert_eq!(held[60 + 29], 1 << Action::Left as u16);
+        assert_eq!(*held.last().unwrap(), 0);
+        assert!(held
+            .iter()
+            .all(|bits| bits & (1 << Action::Right as u16) == 0));
+        // Tilting the top away holds the soft drop.
+        let held = run(&[[0, ONE_G / 2]; 20]);
+        assert_eq!(held[19], 1 << Action::SoftDrop as u16);
+    }
+
+    #[test]
+    fn test_jerk() {
+        // A single reading of a quick jerk is smoothed out by the filter.
+        let mut trace = [[0, 0]; 20];
+        trace[10] = [ONE_G, ONE_G];
+        assert!(run(&trace).iter().all(|bits| *bits == 0));
+        // As is noise within the deadzone.
+        let trace: Vec<[i32; 2]> = (0..100)
+            .map(|i| match i & 1 {
+                0 => [ONE_G / 25, -ONE_G / 25],
+                _ => [-ONE_G / 25, ONE_G / 25],
+            })
+            .collect();
+        assert!(run(&trace).iter().all(|bits| *bits == 0));
+    }
+
+    #[test]
+    fn test_hysteresis() {
+        // Noise around the press threshold presses once, without chattering.
+        let config = TiltConfig::default();
+        let noise = [0, 3, -2, 5, -4, 1, -5, 2, 4, -3];
+        let trace: Vec<[i32; 2]> = (0..200)
+            .map(|i| [config.press + noise[i % noise.len()] * ONE_G / 100, 0])
+            .collect();
+        let held = run(&trace);
+        assert_eq!(presses(&held, Action::Right), 1);
+        assert_eq!(held[199], 1 << Action::Right as u16);
+        // As does noise around the release threshold after a press.
+        let trace: Vec<[i32; 2]> = (0..20)
+            .map(|_| [config.press * 2, 0])
+            .chain((0..200).map(|i| [config.release + noise[i % noise.len()] * ONE_G / 100, 0]))
+            .collect();
+        assert_eq!(presses(&run(&trace), Action::Right), 1);
+    }
+}
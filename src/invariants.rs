@@ -0,0 +1,87 @@
+//! Internal consistency checks of a board and a game, see `BoardOps::validate()` and `Game::validate()`.
+//!
+//! The game checks itself at the end of every tick in debug builds, so a feature breaking the state of the game fails
+//! at the tick that broke it, naming the invariant, instead of many ticks later at a confusing symptom.
+use crate::coordinate::Coordinate;
+use core::fmt;
+
+/// The most rows a board can have, as `GameState::Clearing` marks the completed rows in a `u64`.
+pub const MAX_ROWS: usize = 64;
+
+/// An invariant of a board or a game that does not hold.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InvariantViolation {
+    /// The board stores another number of cells than its rows times its columns.
+    CellCount { expected: usize, found: usize },
+    /// The board has more than `MAX_ROWS` rows, or no rows or columns at all.
+    BoardShape { rows: usize, cols: usize },
+    /// The mask of the active piece exceeds the board.
+    PieceOutOfBounds,
+    /// The active piece overlaps the stack while it is in play.
+    PieceOverlapsStack,
+    /// The queue holds less than a full bag of upcoming pieces.
+    QueueNotStocked,
+    /// The hold slot is locked while no piece is in play, or while holding is disabled.
+    HoldLocked,
+    /// The level is below the start level, or above it by more than the cleared lines allow.
+    Level { level: u8, start_level: u8 },
+    /// The combo counts more clearing pieces than there are cleared lines.
+    Combo { combo: u32, lines: u32 },
+    /// The back-to-back chain is set without any cleared lines.
+    BackToBack,
+}
+
+impl fmt::Display for InvariantViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvariantViolation::CellCount { expected, found } => {
+                write!(f, "the board stores {} cells, expected {}", found, expected)
+            }
+            InvariantViolation::BoardShape { rows, cols } => {
+                write!(
+                    f,
+                    "a {} by {} board, expected 1 to {} rows",
+                    rows, cols, MAX_ROWS
+                )
+            }
+            InvariantViolation::PieceOutOfBounds => {
+                f.write_str("the active piece exceeds the board")
+            }
+            InvariantViolation::PieceOverlapsStack => {
+                f.write_str("the active piece overlaps the stack")
+            }
+            InvariantViolation::QueueNotStocked => {
+                f.write_str("the queue holds less than a full bag")
+            }
+            InvariantViolation::HoldLocked => {
+                f.write_str("the hold slot is locked without a piece in play")
+            }
+            InvariantViolation::Level { level, start_level } => write!(
+                f,
+                "level {} does not follow from start level {}",
+                level, start_level
+            ),
+            InvariantViolation::Combo { combo, lines } => {
+                write!(f, "a combo of {} after {} lines", combo, lines)
+            }
+            InvariantViolation::BackToBack => {
+                f.write_str("a back-to-back chain without cleared lines")
+            }
+        }
+    }
+}
+
+/// Check that a board has 1 to `MAX_ROWS` rows and at least one column.
+/// # Arguments
+/// - `shape` - The number of [rows, cols] of the board
+/// # Returns
+/// - `Result<(), InvariantViolation>` - An `InvariantViolation::BoardShape` if the shape is out of range
+pub(crate) fn validate_shape(shape: Coordinate) -> Result<(), InvariantViolation> {
+    match (1..=MAX_ROWS).contains(&shape.row) && shape.col > 0 {
+        true => Ok(()),
+        false => Err(InvariantViolation::BoardShape {
+            rows: shape.row,
+            cols: shape.col,
+        }),
+    }
+}
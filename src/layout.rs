@@ -0,0 +1,435 @@
+//! Where the playfield and the regions of the HUD go on the LED panel.
+//!
+//! By default the playfield sits in the top-left corner, with the previews and the held piece in the columns right of
+//! its border, see `Layout::top_left()`. On a larger panel `Layout::auto()` centers the field between the held piece
+//! on the left and the previews on the right, or keeps it left of a single column holding both where the panel is too
+//! narrow for that, and adds a score strip below the field where the panel has room. The meter of the incoming
+//! garbage takes the border column right of the field in both. Every pixel outside of the
+//! field, its border and the regions of the HUD is filled with the letterbox color.
+use crate::color::Rgb;
+use crate::coordinate::{Coordinate, Region};
+use crate::display::digits::GLYPH_ROWS;
+use crate::framebuffer::{DrawConfig, PIECE_SLOT};
+use core::fmt;
+
+/// The number of panel rows reserved per piece in the queue region.
+pub const PREVIEW_ROWS: usize = 3;
+
+/// The number of upcoming pieces the queue region of `Layout::auto()` has room for.
+pub const AUTO_PREVIEWS: usize = 3;
+
+/// The number of panel rows between the bottom border of the field and the score strip, left to the lock delay
+/// gauge of `DebugOverlay::LOCK_DELAY`.
+const STRIP_GAP: usize = 1;
+
+/// Why a layout does not fit on a panel.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LayoutError {
+    /// A scale of 0, drawing no cells at all.
+    Scale,
+    /// The panel has fewer rows than the field and the HUD need.
+    Rows { needed: usize, panel: usize },
+    /// The panel has fewer columns than the field and the HUD need.
+    Cols { needed: usize, panel: usize },
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LayoutError::Scale => f.write_str("a scale of 0, expected at least 1"),
+            LayoutError::Rows { needed, panel } => write!(
+                f,
+                "{} rows needed for the field and the HUD, the panel has {}",
+                needed, panel
+            ),
+            LayoutError::Cols { needed, panel } => write!(
+                f,
+                "{} columns needed for the field and the HUD, the panel has {}",
+                needed, panel
+            ),
+        }
+    }
+}
+
+/// Where to draw the playfield and the HUD on the panel, in pixels.
+/// # Attributes
+/// - `field` - The pixels of the cells of the board, its top-left pixel a multiple of `scale`. The border is drawn
+///   around it where the panel has room
+/// - `scale` - The number of rows and columns of pixels every cell is drawn as
+/// - `hold` - The slot of the held piece, `PIECE_SLOT` pixels square
+/// - `queue` - The upcoming pieces, `PREVIEW_ROWS` rows per piece
+/// - `score` - The strip showing the cleared lines, or `None` without room for it
+/// - `garbage` - The column of the meter of the incoming garbage, drawn over the right border of the field
+/// - `letterbox` - The color of the pixels outside of the field, its border and the regions of the HUD
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Layout {
+    pub field: Region,
+    pub scale: u8,
+    pub hold: Region,
+    pub queue: Region,
+    pub score: Option<Region>,
+    pub garbage: Region,
+    pub letterbox: Rgb,
+}
+
+/// Get a region from its top-left pixel and its number of [rows, cols].
+fn _region(top_left: Coordinate, dims: Coordinate) -> Region {
+    Region {
+        top_left,
+        bottom_right: top_left + dims - [1, 1],
+    }
+}
+
+/// Get the border column right of a field, as high as the field.
+fn _right_border(field: Region) -> Region {
+    Region {
+        top_left: Coordinate::from_array([field.top_left.row, field.bottom_right.col + 1]),
+        bottom_right: field.bottom_right + [0, 1],
+    }
+}
+
+/// Center a span in a space, keeping room ahead and behind it and starting at a multiple of the scale.
+/// # Arguments
+/// - `space` - The length of the space
+/// - `len` - The length of the span
+/// - `scale` - The multiple the span starts at
+/// - `before` - The room to keep ahead of the span
+/// - `after` - The room to keep behind the span
+/// # Returns
+/// - `Option<usize>` - The start of the span, or `None` if it does not fit
+fn _center(space: usize, len: usize, scale: usize, before: usize, after: usize) -> Option<usize> {
+    let slack = space.checked_sub(before + len + after)?;
+    let start = before + slack / 2;
+    let down = start - start % scale;
+    let up = match start % scale {
+        0 => down,
+        _ => down + scale,
+    };
+    match (up + len + after <= space, down >= before) {
+        (true, _) => Some(up),
+        (false, true) => Some(down),
+        (false, false) => None,
+    }
+}
+
+impl Layout {
+    /// Get the layout of a board in the top-left corner of the panel, a cell per pixel, with the previews right of
+    /// its border and the held piece below them.
+    /// # Arguments
+    /// - `board` - The number of [rows, cols] of the board
+    /// - `previews` - The number of upcoming pieces to show
+    /// # Returns
+    /// - `Layout` - The layout without a score strip, letterboxed in black
+    pub fn top_left(board: Coordinate, previews: usize) -> Self {
+        let hud = Coordinate::from_array([0, board.col + 1]);
+        let field = _region(Coordinate::from_array([0, 0]), board);
+        Layout {
+            field,
+            scale: 1,
+            hold: _region(
+                hud + [previews * PREVIEW_ROWS + 1, 0],
+                Coordinate::from_array([PIECE_SLOT; 2]),
+            ),
+            queue: _region(
+                hud,
+                Coordinate::from_array([(previews * PREVIEW_ROWS).max(1), PIECE_SLOT]),
+            ),
+            score: None,
+            garbage: _right_border(field),
+            letterbox: Rgb::BLACK,
+        }
+    }
+
+    /// Fit a board on a panel, centering the field and giving the remaining columns to the HUD.
+    ///
+    /// Where the panel has room on both sides of the centered field, the held piece goes left of the field and the
+    /// previews right of it, otherwise the field moves to the left edge with the previews and the held piece below
+    /// them right of it. The field is centered vertically, together with the score strip below it if that fits.
+    /// # Arguments
+    /// - `panel` - The number of [rows, cols] of the panel
+    /// - `board` - The number of [rows, cols] of the board
+    /// - `scale` - The number of rows and columns of pixels every cell is drawn as
+    /// # Returns
+    /// - `Result<Layout, LayoutError>` - The layout letterboxed in black, or the error saying what does not fit
+    pub fn auto(panel: Coordinate, board: Coordinate, scale: u8) -> Result<Self, LayoutError> {
+        if scale == 0 {
+            return Err(LayoutError::Scale);
+        }
+        let scale = scale as usize;
+        let field = Coordinate::from_array([board.row * scale, board.col * scale]);
+        let queue_rows = AUTO_PREVIEWS * PREVIEW_ROWS;
+        // A HUD column is a piece slot next to the border.
+        let side = 1 + PIECE_SLOT;
+        let centered = _center(panel.col, field.col, scale, side, side);
+        let col = match centered {
+            Some(col) => col,
+            None if field.col + side <= panel.col => 0,
+            None => {
+                return Err(LayoutError::Cols {
+                    needed: field.col + side,
+                    panel: panel.col,
+                })
+            }
+        };
+        let hud_rows = match centered {
+            Some(_) => queue_rows.max(PIECE_SLOT),
+            None => queue_rows + 1 + PIECE_SLOT,
+        };
+        // The border above and below the field, the gap and the score strip below, or only the bottom border.
+        let strip = 1 + STRIP_GAP + GLYPH_ROWS;
+        let (row, score) = match _center(panel.row, field.row, scale, 1, strip) {
+            Some(row) if row + hud_rows <= panel.row => (row, true),
+            _ => match _center(panel.row, field.row, scale, 0, 1) {
+                Some(row) if row + hud_rows <= panel.row => (row, false),
+                _ => {
+                    return Err(LayoutError::Rows {
+                        needed: field.row.max(hud_rows) + 1,
+                        panel: panel.row,
+                    })
+                }
+            },
+        };
+        let slot = Coordinate::from_array([PIECE_SLOT; 2]);
+        let queue = Coordinate::from_array([queue_rows, PIECE_SLOT]);
+        let right = Coordinate::from_array([row, col + field.col + 1]);
+        let (hold, queue) = match centered {
+            Some(_) => (
+                _region(Coordinate::from_array([row, col - side]), slot),
+                _region(right, queue),
+            ),
+            None => (
+                _region(right + [queue_rows + 1, 0], slot),
+                _region(right, queue),
+            ),
+        };
+        let score = score.then(|| Region {
+            top_left: Coordinate::from_array([
+                row + field.row + 1 + STRIP_GAP,
+                hold.top_left.col.min(col),
+            ]),
+            bottom_right: Coordinate::from_array([
+                row + field.row + STRIP_GAP + GLYPH_ROWS,
+                queue.bottom_right.col,
+            ]),
+        });
+        let field = _region(Coordinate::from_array([row, col]), field);
+        Ok(Layout {
+            field,
+            scale: scale as u8,
+            hold,
+            queue,
+            score,
+            garbage: _right_border(field),
+            letterbox: Rgb::BLACK,
+        })
+    }
+
+    /// Get the configuration to draw the board and the pieces with.
+    /// # Returns
+    /// - `DrawConfig` - The configuration placing the board on the field, at the scale of the layout
+    pub fn get_config(&self) -> DrawConfig {
+        let scale = self.scale.max(1) as usize;
+        DrawConfig {
+            origin: Coordinate::from_array([
+                self.field.top_left.row / scale,
+                self.field.top_left.col / scale,
+            ]),
+            scale: self.scale,
+            ..DrawConfig::default()
+        }
+    }
+
+    /// Get the pixels of a cell of the board.
+    /// # Arguments
+    /// - `coord` - The [row, col] of the cell
+    /// # Returns
+    /// - `Region` - The block of `scale` by `scale` pixels the cell is drawn as
+    pub fn cell(&self, coord: Coordinate) -> Region {
+        let scale = self.scale.max(1) as usize;
+        _region(
+            self.field.top_left + [coord.row * scale, coord.col * scale],
+            Coordinate::from_array([scale; 2]),
+        )
+    }
+
+    /// Get where to draw a glyph in the middle of the field, e.g. the countdown.
+    /// # Arguments
+    /// - `dims` - The number of [rows, cols] of the glyph
+    /// # Returns
+    /// - `Coordinate` - The top-left pixel of the glyph, rounded to the top-left
+    pub fn centered(&self, dims: Coordinate) -> Coordinate {
+        let field = self.field.bottom_right - self.field.top_left + [1, 1];
+        self.field.top_left
+            + [
+                field.row.saturating_sub(dims.row) / 2,
+                field.col.saturating_sub(dims.col) / 2,
+            ]
+    }
+
+    /// Get where to draw the recent actions of the player, below the score strip or else below the border of the
+    /// field, leaving a row for the lock delay gauge.
+    /// # Returns
+    /// - `Coordinate` - The top-left pixel of the newest action
+    pub fn get_input_origin(&self) -> Coordinate {
+        let below = match self.score {
+            Some(score) => score.bottom_right.row + 2,
+            None => self.field.bottom_right.row + 3,
+        };
+        Coordinate::from_array([below, self.field.top_left.col])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Layout, LayoutError, AUTO_PREVIEWS, PREVIEW_ROWS};
+    use crate::coordinate::{Coordinate, Region};
+    use crate::framebuffer::PIECE_SLOT;
+    use crate::settings::MAX_COLS;
+    use std::string::ToString;
+
+    const BOARD: Coordinate = Coordinate { row: 20, col: 10 };
+
+    /// Check that the regions of a layout lie on the panel and apart, the field with its border.
+    fn check(layout: &Layout, panel: Coordinate) {
+        let field = layout.field;
+        let bordered = Region {
+            top_left: field.top_left - [field.top_left.row.min(1), field.top_left.col.min(1)],
+            bottom_right: field.bottom_right + [1, 1],
+        };
+        let regions = [
+            Some(bordered),
+            Some(layout.hold),
+            Some(layout.queue),
+            layout.score,
+        ];
+        let regions: Vec<Region> = regions.iter().flatten().copied().collect();
+        for (index, region) in regions.iter().enumerate() {
+            assert!(region.bottom_right.row < panel.row && region.bottom_right.col < panel.col);
+            for other in &regions[index + 1..] {
+                assert!(!region.overlaps(other), "{:?} overlaps {:?}", region, other);
+            }
+        }
+        let dims = |region: Region| region.bottom_right - region.top_left + [1, 1];
+        let scale = layout.scale as usize;
+        assert_eq!(
+            dims(field),
+            Coordinate::from_array([20 * scale, 10 * scale])
+        );
+        assert_eq!(dims(layout.hold), Coordinate::from_array([PIECE_SLOT; 2]));
+        assert_eq!(
+            dims(layout.queue),
+            Coordinate::from_array([AUTO_PREVIEWS * PREVIEW_ROWS, PIECE_SLOT])
+        );
+        assert_eq!(field.top_left.row % scale + field.top_left.col % scale, 0);
+        // The garbage meter runs along the right border, clear of the field and the HUD.
+        let garbage = layout.garbage;
+        assert_eq!(dims(garbage), Coordinate::from_array([dims(field).row, 1]));
+        assert_eq!(garbage.bottom_right, field.bottom_right + [0, 1]);
+        for region in regions.iter().skip(1).chain([field].iter()) {
+            assert!(
+                !garbage.overlaps(region),
+                "{:?} overlaps {:?}",
+                garbage,
+                region
+            );
+        }
+    }
+
+    #[test]
+    fn test_narrow() {
+        // 16 columns leave no room left of a centered field, so the field keeps to the left edge with the HUD right.
+        let panel = Coordinate::from_array([32, 16]);
+        let layout = Layout::auto(panel, BOARD, 1).unwrap();
+        check(&layout, panel);
+        assert_eq!(layout.field.top_left, Coordinate::from_array([3, 0]));
+        assert_eq!(layout.queue.top_left, Coordinate::from_array([3, 11]));
+        assert_eq!(layout.hold.top_left, Coordinate::from_array([13, 11]));
+        let score = layout.score.unwrap();
+        assert_eq!(score.top_left, Coordinate::from_array([25, 0]));
+        assert_eq!(score.bottom_right, Coordinate::from_array([29, 14]));
+        assert_eq!(layout.get_config().origin, Coordinate::from_array([3, 0]));
+    }
+
+    #[test]
+    fn test_centered() {
+        // The held piece left of the field and the previews right of it, 5 columns apart on both sides.
+        let panel = Coordinate::from_array([64, 32]);
+        let layout = Layout::auto(panel, BOARD, 1).unwrap();
+        check(&layout, panel);
+        assert_eq!(layout.field.top_left, Coordinate::from_array([19, 11]));
+        assert_eq!(layout.hold.top_left, Coordinate::from_array([19, 6]));
+        assert_eq!(layout.queue.top_left, Coordinate::from_array([19, 22]));
+        let score = layout.score.unwrap();
+        assert_eq!(score.top_left, Coordinate::from_array([41, 6]));
+        assert_eq!(score.bottom_right, Coordinate::from_array([45, 25]));
+        assert_eq!(layout.get_input_origin(), Coordinate::from_array([47, 11]));
+        // At twice the size the field starts on whole cells.
+        let layout = Layout::auto(panel, BOARD, 2).unwrap();
+        check(&layout, panel);
+        assert_eq!(layout.field.top_left, Coordinate::from_array([10, 6]));
+        assert_eq!(layout.get_config().origin, Coordinate::from_array([5, 3]));
+        assert_eq!(
+            layout.cell(Coordinate::from_array([1, 2])).top_left,
+            Coordinate::from_array([12, 10])
+        );
+        // Without room below the field, the strip is left out.
+        let panel = Coordinate::from_array([22, 32]);
+        let layout = Layout::auto(panel, BOARD, 1).unwrap();
+        check(&layout, panel);
+        assert_eq!(layout.score, None);
+        assert_eq!(layout.field.top_left, Coordinate::from_array([0, 11]));
+    }
+
+    #[test]
+    fn test_too_small() {
+        let error = Layout::auto(Coordinate::from_array([32, 12]), BOARD, 1).unwrap_err();
+        assert_eq!(
+            error,
+            LayoutError::Cols {
+                needed: 15,
+                panel: 12
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "15 columns needed for the field and the HUD, the panel has 12"
+        );
+        assert_eq!(
+            Layout::auto(Coordinate::from_array([64, 32]), BOARD, 4),
+            Err(LayoutError::Cols {
+                needed: 45,
+                panel: 32
+            })
+        );
+        assert_eq!(
+            Layout::auto(Coordinate::from_array([16, 32]), BOARD, 1),
+            Err(LayoutError::Rows {
+                needed: 21,
+                panel: 16
+            })
+        );
+        assert_eq!(
+            Layout::auto(Coordinate::from_array([64, 32]), BOARD, 0),
+            Err(LayoutError::Scale)
+        );
+    }
+
+    #[test]
+    fn test_top_left() {
+        // The default layout of the renderer, the field in the corner and the HUD right of its border.
+        let layout = Layout::top_left(BOARD, 3);
+        check(&layout, Coordinate::from_array([64, 32]));
+        assert_eq!(layout.field.top_left, Coordinate::from_array([0, 0]));
+        assert_eq!(layout.queue.top_left, Coordinate::from_array([0, 11]));
+        assert_eq!(layout.hold.top_left, Coordinate::from_array([10, 11]));
+        assert_eq!(layout.get_input_origin(), Coordinate::from_array([22, 0]));
+        assert_eq!(
+            layout.centered(Coordinate::from_array([5, 3])),
+            Coordinate::from_array([7, 3])
+        );
+        // The widest board still leaves room for the previews and the held piece.
+        let widest = Layout::top_left(Coordinate::from_array([20, MAX_COLS]), 3);
+        assert_eq!(widest.queue.bottom_right.col, 31);
+        assert_eq!(widest.hold.bottom_right.col, 31);
+    }
+}
@@ -0,0 +1,68 @@
+//! The game logic, free of `std` so it runs on the RP2040 with only an allocator.
+//!
+//! The terminal simulator in `src/bin/simulator.rs` enables the `sim` feature, which implies `std`.
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
+// Invariants of the crate panic with an `expect()` message, anything else returns a `Result`, see `error`.
+#![cfg_attr(not(test), deny(clippy::unwrap_used))]
+
+extern crate alloc;
+
+pub mod ai;
+#[cfg(all(test, feature = "alloc-counter"))]
+mod alloc_counter;
+pub mod base64;
+pub mod board;
+pub mod cell;
+pub mod clock;
+pub mod color;
+pub mod console;
+pub mod coordinate;
+pub mod crash;
+pub mod display;
+pub mod effects;
+pub mod error;
+pub mod events;
+pub mod finesse;
+pub mod fixtures;
+pub mod framebuffer;
+pub mod game;
+pub mod gamelog;
+pub mod garbage;
+pub mod gravity;
+pub mod grid;
+pub mod heatmap;
+pub mod highscores;
+pub mod hold;
+pub mod hub75;
+pub mod hud;
+pub mod input;
+pub mod invariants;
+pub mod layout;
+pub mod lifetime;
+pub mod link;
+pub mod loop_timing;
+pub mod menu;
+pub mod palette;
+pub mod piece;
+pub mod power;
+pub mod profiler;
+pub mod queue;
+pub mod randomizer;
+pub mod recorder;
+pub mod render;
+pub mod replay;
+pub mod rng;
+pub mod rotation;
+pub mod savestate;
+pub mod scoring;
+pub mod seqlock;
+pub mod settings;
+pub mod sim;
+pub mod sound;
+pub mod storage;
+#[cfg(test)]
+mod testing;
+pub mod tetrominoes;
+pub mod ws2812;
+
+pub use color::{Color, Rgb};
@@ -0,0 +1,328 @@
+//! The totals of every game played on the device, kept in flash across power cycles.
+//!
+//! The game folds its own events into its `LifetimeStats`: the cleared lines, tetrises and T-spins of the game in
+//! progress are counted on the side and only added to the totals once the game ends, together with the time played
+//! and a finished sprint. A game abandoned halfway, e.g. by a power loss or the reset chord, does not count, while a
+//! game aborted after it ended, e.g. while entering a name, counted once when it ended. Every counter saturates
+//! instead of wrapping, and the totals are serialized into a fixed number of bytes for their own `RecordKind`.
+use crate::events::GameEvent;
+use crate::scoring::TSpin;
+use crate::settings::GameMode;
+
+/// The number of bytes of the serialized totals: the games, lines, tetrises, T-spins, time played and best sprint.
+pub const LIFETIME_BYTES: usize = 6 * 4;
+
+/// The value of every byte of an erased flash sector.
+const ERASED: u8 = 0xFF;
+
+/// The serialized best sprint time without a finished sprint, the value of an erased field.
+const NO_SPRINT: u32 = u32::MAX;
+
+/// The panic message of a field outside of the serialized totals, a bug in their layout.
+const FIELD_FITS: &str = "the field is within the serialized totals";
+
+/// The counts of the game in progress, added to the totals once it ends.
+/// # Attributes
+/// - `lines` - The number of cleared lines
+/// - `tetrises` - The number of clears of 4 lines
+/// - `t_spins` - The number of T-spins, mini or full, with or without lines
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct GameTally {
+    lines: u32,
+    tetrises: u32,
+    t_spins: u32,
+}
+
+/// The totals over every finished or topped out game.
+/// # Attributes
+/// - `games` - The number of games played to the end
+/// - `lines` - The number of cleared lines
+/// - `tetrises` - The number of clears of 4 lines
+/// - `t_spins` - The number of T-spins, mini or full, with or without lines
+/// - `centis` - The time played in hundredths of a second, excluding pauses and countdowns
+/// - `best_sprint` - The shortest finished sprint in hundredths of a second, or `None` before the first one
+/// - `game` - The counts of the game in progress, not serialized
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LifetimeStats {
+    games: u32,
+    lines: u32,
+    tetrises: u32,
+    t_spins: u32,
+    centis: u32,
+    best_sprint: Option<u32>,
+    game: GameTally,
+}
+
+impl LifetimeStats {
+    /// Create the totals of a device without any games.
+    /// # Returns
+    /// - `LifetimeStats` - The totals with all counters at 0
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fold an event of a game into the totals.
+    /// # Arguments
+    /// - `event` - A reference to the `GameEvent`
+    /// - `mode` - The `GameMode` of the game, to tell a finished sprint
+    pub fn fold(&mut self, event: &GameEvent, mode: GameMode) {
+        match *event {
+            GameEvent::LinesCleared { n, t_spin, .. } => {
+                self.game.lines = self.game.lines.saturating_add(n as u32);
+                if n == 4 {
+                    self.game.tetrises = self.game.tetrises.saturating_add(1);
+                }
+                if t_spin != TSpin::None {
+                    self.game.t_spins = self.game.t_spins.saturating_add(1);
+                }
+            }
+            GameEvent::GameOver { centis, .. } => self._end_game(centis, None),
+            GameEvent::Finished { centis, .. } => {
+                let sprint = matches!(mode, GameMode::Sprint { .. }).then_some(centis);
+                self._end_game(centis, sprint);
+            }
+            GameEvent::Aborted { .. } => self.discard_game(),
+            _ => (),
+        }
+    }
+
+    /// Add the totals of another device or session, e.g. the totals loaded from flash.
+    /// # Arguments
+    /// - `other` - A reference to the `LifetimeStats` to add, without the game in progress
+    pub fn merge(&mut self, other: &LifetimeStats) {
+        self.games = self.games.saturating_add(other.games);
+        self.lines = self.lines.saturating_add(other.lines);
+        self.tetrises = self.tetrises.saturating_add(other.tetrises);
+        self.t_spins = self.t_spins.saturating_add(other.t_spins);
+        self.centis = self.centis.saturating_add(other.centis);
+        self.best_sprint = match (self.best_sprint, other.best_sprint) {
+            (Some(best), Some(other)) => Some(best.min(other)),
+            (best, other) => best.or(other),
+        };
+    }
+
+    /// Forget the counts of the game in progress, e.g. when it is abandoned by starting over.
+    pub fn discard_game(&mut self) {
+        self.game = GameTally::default();
+    }
+
+    /// Get the number of games played to the end.
+    pub fn get_games(&self) -> u32 {
+        self.games
+    }
+
+    /// Get the number of cleared lines.
+    pub fn get_lines(&self) -> u32 {
+        self.lines
+    }
+
+    /// Get the number of clears of 4 lines.
+    pub fn get_tetrises(&self) -> u32 {
+        self.tetrises
+    }
+
+    /// Get the number of T-spins.
+    pub fn get_t_spins(&self) -> u32 {
+        self.t_spins
+    }
+
+    /// Get the time played in hundredths of a second.
+    pub fn get_centis(&self) -> u32 {
+        self.centis
+    }
+
+    /// Get the shortest finished sprint in hundredths of a second, or `None` before the first one.
+    pub fn get_best_sprint(&self) -> Option<u32> {
+        self.best_sprint
+    }
+
+    /// Serialize the totals for persistent storage, without the game in progress.
+    /// # Returns
+    /// - `[u8; LIFETIME_BYTES]` - The little endian counters, the best sprint `u32::MAX` without a finished sprint
+    pub fn to_bytes(self) -> [u8; LIFETIME_BYTES] {
+        let mut bytes = [0; LIFETIME_BYTES];
+        let fields = [
+            self.games,
+            self.lines,
+            self.tetrises,
+            self.t_spins,
+            self.centis,
+            self.best_sprint.unwrap_or(NO_SPRINT),
+        ];
+        for (chunk, field) in bytes.chunks_exact_mut(4).zip(fields) {
+            chunk.copy_from_slice(&field.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Deserialize the totals written by `.to_bytes()`.
+    /// # Arguments
+    /// - `bytes` - A reference to the serialized totals
+    /// # Returns
+    /// - `LifetimeStats` - The totals, all 0 for the erased bytes of a freshly formatted flash
+    pub fn from_bytes(bytes: &[u8; LIFETIME_BYTES]) -> Self {
+        if bytes.iter().all(|byte| *byte == ERASED) {
+            return Self::new();
+        }
+        let field = |index: usize| {
+            u32::from_le_bytes(
+                bytes[index * 4..(index + 1) * 4]
+                    .try_into()
+                    .expect(FIELD_FITS),
+            )
+        };
+        LifetimeStats {
+            games: field(0),
+            lines: field(1),
+            tetrises: field(2),
+            t_spins: field(3),
+            centis: field(4),
+            best_sprint: match field(5) {
+                NO_SPRINT => None,
+                centis => Some(centis),
+            },
+            game: GameTally::default(),
+        }
+    }
+
+    /// Add the game that just ended to the totals and start counting the next one.
+    /// # Arguments
+    /// - `centis` - The time the game was played in hundredths of a second
+    /// - `sprint` - The time of a finished sprint, or `None` for any other game
+    fn _end_game(&mut self, centis: u32, sprint: Option<u32>) {
+        let game = core::mem::take(&mut self.game);
+        self.merge(&LifetimeStats {
+            games: 1,
+            lines: game.lines,
+            tetrises: game.tetrises,
+            t_spins: game.t_spins,
+            centis,
+            best_sprint: sprint,
+            game: GameTally::default(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LifetimeStats, LIFETIME_BYTES};
+    use crate::events::GameEvent;
+    use crate::game::{Cause, Outcome};
+    use crate::scoring::TSpin;
+    use crate::settings::GameMode;
+
+    /// A clear of `n` lines without bonus.
+    fn clear(n: usize, t_spin: TSpin) -> GameEvent {
+        GameEvent::LinesCleared {
+            n,
+            t_spin,
+            b2b: false,
+        }
+    }
+
+    #[test]
+    fn test_fold() {
+        let mut stats = LifetimeStats::new();
+        // A marathon topping out after a tetris and a T-spin double.
+        for event in [
+            clear(4, TSpin::None),
+            GameEvent::PieceHeld,
+            clear(2, TSpin::Full),
+        ] {
+            stats.fold(&event, GameMode::Marathon);
+        }
+        assert_eq!(stats.get_games(), 0);
+        assert_eq!(stats.get_lines(), 0);
+        let game_over = GameEvent::GameOver {
+            cause: Cause::BlockOut,
+            centis: 6000,
+        };
+        stats.fold(&game_over, GameMode::Marathon);
+        assert_eq!((stats.get_games(), stats.get_lines()), (1, 6));
+        assert_eq!((stats.get_tetrises(), stats.get_t_spins()), (1, 1));
+        assert_eq!(stats.get_best_sprint(), None);
+        // A sprint of 3 lines, finished twice as fast as the marathon lasted.
+        for event in [clear(1, TSpin::Mini), clear(2, TSpin::None)] {
+            stats.fold(&event, GameMode::Sprint { lines: 3 });
+        }
+        let finished = GameEvent::Finished {
+            outcome: Outcome {
+                ticks: 180,
+                points: 400,
+                lines: 3,
+            },
+            centis: 3000,
+        };
+        stats.fold(&finished, GameMode::Sprint { lines: 3 });
+        assert_eq!((stats.get_games(), stats.get_lines()), (2, 9));
+        assert_eq!((stats.get_tetrises(), stats.get_t_spins()), (1, 2));
+        assert_eq!(
+            (stats.get_centis(), stats.get_best_sprint()),
+            (9000, Some(3000))
+        );
+        // A slower sprint keeps the best time, and the counters saturate.
+        let mut full = LifetimeStats {
+            games: u32::MAX,
+            centis: u32::MAX,
+            best_sprint: Some(2500),
+            ..LifetimeStats::new()
+        };
+        full.fold(&finished, GameMode::SPRINT);
+        assert_eq!((full.get_games(), full.get_centis()), (u32::MAX, u32::MAX));
+        assert_eq!(full.get_best_sprint(), Some(2500));
+        stats.merge(&full);
+        assert_eq!(
+            (stats.get_games(), stats.get_best_sprint()),
+            (u32::MAX, Some(2500))
+        );
+    }
+
+    #[test]
+    fn test_aborted() {
+        // An aborted game does not count, and neither do its lines towards the next game.
+        let mut stats = LifetimeStats::new();
+        stats.fold(&clear(4, TSpin::None), GameMode::Marathon);
+        stats.fold(&GameEvent::Aborted { centis: 1500 }, GameMode::Marathon);
+        assert_eq!(stats, LifetimeStats::new());
+        stats.fold(&clear(1, TSpin::None), GameMode::Marathon);
+        stats.fold(
+            &GameEvent::GameOver {
+                cause: Cause::TopOut,
+                centis: 900,
+            },
+            GameMode::Marathon,
+        );
+        assert_eq!((stats.get_games(), stats.get_lines()), (1, 1));
+        assert_eq!(stats.get_centis(), 900);
+    }
+
+    #[test]
+    fn test_bytes() {
+        let mut stats = LifetimeStats::new();
+        assert_eq!(LifetimeStats::from_bytes(&stats.to_bytes()), stats);
+        stats.fold(&clear(4, TSpin::None), GameMode::SPRINT);
+        stats.fold(
+            &GameEvent::Finished {
+                outcome: Outcome {
+                    ticks: 3600,
+                    points: 800,
+                    lines: 40,
+                },
+                centis: 6000,
+            },
+            GameMode::SPRINT,
+        );
+        assert_eq!(LifetimeStats::from_bytes(&stats.to_bytes()), stats);
+        // The game in progress is not stored.
+        stats.fold(&clear(1, TSpin::None), GameMode::SPRINT);
+        assert_eq!(LifetimeStats::from_bytes(&stats.to_bytes()).get_lines(), 4);
+    }
+
+    #[test]
+    fn test_blank_flash() {
+        // A freshly formatted flash starts from zeros, without a best sprint.
+        let stats = LifetimeStats::from_bytes(&[0xFF; LIFETIME_BYTES]);
+        assert_eq!(stats, LifetimeStats::new());
+        assert_eq!((stats.get_games(), stats.get_best_sprint()), (0, None));
+    }
+}
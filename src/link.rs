@@ -0,0 +1,832 @@
+//! The wire protocol of head-to-head play between two devices over a 3-wire UART link.
+//!
+//! # Framing
+//! Every message is a frame of its type byte, its little-endian payload and the CRC-16/CCITT of both, delimited by
+//! `FLAG` bytes like HDLC. A `FLAG` or `ESCAPE` byte inside the frame is sent as `ESCAPE` followed by the byte XOR
+//! 0x20, so a `FLAG` always delimits a frame and the receiver resynchronizes on it after noise or a lost byte.
+//!
+//! # Session
+//! Both devices keep sending `Message::Hello` until they hear the other, and play the sum of both seeds. A
+//! connected device answers another handshake at most once every `PING_MICROS`, in case its first answer got
+//! lost. While connected, an idle device sends a `Message::Ping` every `PING_MICROS` and the link times out
+//! after `TIMEOUT_MICROS` without any valid frame. Devices running the same game in lockstep exchange board digests
+//! every few ticks to detect a desync.
+use crate::error::TetrisError;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// The version of the protocol, both devices must speak the same.
+pub const PROTOCOL_VERSION: u8 = 1;
+
+/// The byte delimiting frames.
+pub const FLAG: u8 = 0x7E;
+
+/// The byte escaping a `FLAG` or `ESCAPE` byte inside a frame.
+pub const ESCAPE: u8 = 0x7D;
+
+/// The maximum number of bytes of an unescaped frame: the type, the largest payload and the CRC.
+const MAX_BODY_BYTES: usize = 1 + 12 + 2;
+
+/// The panic message of a body exceeding `MAX_BODY_BYTES`, a bug in the encoding of a message.
+const BODY_FITS: &str = "the body of every message fits in MAX_BODY_BYTES";
+
+/// The maximum number of bytes of an encoded frame, with every byte escaped and both flags.
+pub const MAX_FRAME_BYTES: usize = 2 * MAX_BODY_BYTES + 2;
+
+/// The number of microseconds between handshakes or keepalives, while nothing else is sent.
+pub const PING_MICROS: u64 = 250_000;
+
+/// The number of microseconds without a valid frame before a connected link times out.
+pub const TIMEOUT_MICROS: u64 = 1_000_000;
+
+/// The number of board digests of either device kept while waiting for the digest of the same tick.
+pub const DIGEST_HISTORY: usize = 8;
+
+/// Why a frame could not be encoded or decoded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ProtocolError {
+    /// The buffer is shorter than the encoded frame, with the number of bytes of the frame.
+    BufferTooSmall(usize),
+    /// The frame is too short for a type and a CRC, with its number of bytes.
+    Truncated(usize),
+    /// The CRC of the frame is wrong.
+    Crc,
+    /// The type is unknown or its payload has the wrong number of bytes.
+    Payload { kind: u8, length: usize },
+    /// The frame grew beyond the largest frame before its closing `FLAG`.
+    Overflow,
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtocolError::BufferTooSmall(length) => {
+                write!(
+                    f,
+                    "the buffer is shorter than the frame of {} bytes",
+                    length
+                )
+            }
+            ProtocolError::Truncated(length) => {
+                write!(f, "the frame of {} bytes is truncated", length)
+            }
+            ProtocolError::Crc => f.write_str("the CRC of the frame is wrong"),
+            ProtocolError::Payload { kind, length } => {
+                write!(
+                    f,
+                    "unknown type {} with a payload of {} bytes",
+                    kind, length
+                )
+            }
+            ProtocolError::Overflow => f.write_str("the frame exceeds the largest frame"),
+        }
+    }
+}
+
+/// A message between the two devices.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Message {
+    /// The handshake, with the seed of the sender and its `PROTOCOL_VERSION`.
+    Hello { seed: u64, version: u8 },
+    /// Garbage rows sent to the opponent, with the column of their gap.
+    Garbage { lines: u8, gap: u8 },
+    /// The hash of the board at a tick, see `Game::board_hash()`.
+    BoardDigest { tick: u32, hash: u64 },
+    /// The sender topped out, so the receiver won.
+    Topped,
+    /// A keepalive, answered with `Message::Pong`.
+    Ping,
+    /// The answer to a `Message::Ping`.
+    Pong,
+}
+
+impl Message {
+    /// Encode the message into a frame.
+    /// # Arguments
+    /// - `buffer` - A muteable reference to the buffer to write the frame into, `MAX_FRAME_BYTES` always suffice
+    /// # Returns
+    /// - `Result<usize, TetrisError>` - The number of bytes written, or a `ProtocolError::BufferTooSmall` if the
+    ///   frame does not fit in the buffer, leaving it unchanged
+    pub fn encode_into(&self, buffer: &mut [u8]) -> Result<usize, TetrisError> {
+        let mut body = heapless::Vec::<u8, MAX_BODY_BYTES>::new();
+        let (kind, payload) = self._to_payload();
+        body.push(kind).expect(BODY_FITS);
+        body.extend_from_slice(&payload).expect(BODY_FITS);
+        let crc = _crc16(&body);
+        body.extend_from_slice(&crc.to_le_bytes()).expect(BODY_FITS);
+        let mut frame = [0; MAX_FRAME_BYTES];
+        frame[0] = FLAG;
+        let mut length = 1;
+        for byte in body {
+            match byte {
+                FLAG | ESCAPE => {
+                    frame[length] = ESCAPE;
+                    frame[length + 1] = byte ^ 0x20;
+                    length += 2;
+                }
+                _ => {
+                    frame[length] = byte;
+                    length += 1;
+                }
+            }
+        }
+        frame[length] = FLAG;
+        length += 1;
+        buffer
+            .get_mut(..length)
+            .ok_or(ProtocolError::BufferTooSmall(length))?
+            .copy_from_slice(&frame[..length]);
+        Ok(length)
+    }
+
+    /// Decode a message from the unescaped body of a frame.
+    /// # Arguments
+    /// - `body` - The type, payload and CRC of the frame
+    /// # Returns
+    /// - `Result<Message, ProtocolError>` - The message, or an error if the CRC, the type or the payload length is
+    ///   wrong
+    fn _from_body(body: &[u8]) -> Result<Message, ProtocolError> {
+        let truncated = ProtocolError::Truncated(body.len());
+        let (data, crc) = body.split_at(body.len().checked_sub(2).ok_or(truncated)?);
+        let (kind, payload) = data.split_first().ok_or(truncated)?;
+        if _crc16(data).to_le_bytes() != crc {
+            return Err(ProtocolError::Crc);
+        }
+        match (kind, payload.len()) {
+            (1, 9) => Ok(Message::Hello {
+                seed: u64::from_le_bytes(_bytes_at(payload, 0)),
+                version: payload[8],
+            }),
+            (2, 2) => Ok(Message::Garbage {
+                lines: payload[0],
+                gap: payload[1],
+            }),
+            (3, 12) => Ok(Message::BoardDigest {
+                tick: u32::from_le_bytes(_bytes_at(payload, 0)),
+                hash: u64::from_le_bytes(_bytes_at(payload, 4)),
+            }),
+            (4, 0) => Ok(Message::Topped),
+            (5, 0) => Ok(Message::Ping),
+            (6, 0) => Ok(Message::Pong),
+            (kind, length) => Err(ProtocolError::Payload {
+                kind: *kind,
+                length,
+            }),
+        }
+    }
+
+    /// Get the type byte and the payload of the message.
+    fn _to_payload(&self) -> (u8, heapless::Vec<u8, 12>) {
+        let mut payload = heapless::Vec::new();
+        let kind = match *self {
+            Message::Hello { seed, version } => {
+                payload
+                    .extend_from_slice(&seed.to_le_bytes())
+                    .expect(BODY_FITS);
+                payload.push(version).expect(BODY_FITS);
+                1
+            }
+            Message::Garbage { lines, gap } => {
+                payload.extend_from_slice(&[lines, gap]).expect(BODY_FITS);
+                2
+            }
+            Message::BoardDigest { tick, hash } => {
+                payload
+                    .extend_from_slice(&tick.to_le_bytes())
+                    .expect(BODY_FITS);
+                payload
+                    .extend_from_slice(&hash.to_le_bytes())
+                    .expect(BODY_FITS);
+                3
+            }
+            Message::Topped => 4,
+            Message::Ping => 5,
+            Message::Pong => 6,
+        };
+        (kind, payload)
+    }
+}
+
+/// A streaming decoder of frames, fed a byte at a time as they arrive.
+/// # Attributes
+/// - `body` - The unescaped bytes received since the last `FLAG`
+/// - `escaped` - Whether the previous byte was an `ESCAPE`
+/// - `overflow` - Whether the current frame exceeded the largest frame, so it is dropped at the next `FLAG`
+/// - `errors` - The number of frames dropped for a wrong CRC, type or length
+#[derive(Clone, Debug, Default)]
+pub struct Decoder {
+    body: heapless::Vec<u8, MAX_BODY_BYTES>,
+    escaped: bool,
+    overflow: bool,
+    errors: u32,
+}
+
+impl Decoder {
+    /// Create a decoder waiting for the first `FLAG`.
+    pub fn new() -> Self {
+        Decoder::default()
+    }
+
+    /// Feed a received byte to the decoder.
+    /// # Arguments
+    /// - `byte` - The received byte
+    /// # Returns
+    /// - `Result<Option<Message>, TetrisError>` - The message of the frame the byte completed, if any, or the
+    ///   `ProtocolError` of the completed frame if it is broken
+    pub fn decode(&mut self, byte: u8) -> Result<Option<Message>, TetrisError> {
+        match byte {
+            FLAG => {
+                let message = match (self.overflow, self.body.is_empty()) {
+                    (true, _) => Err(ProtocolError::Overflow),
+                    // Back to back flags delimit an empty frame, which is not an error.
+                    (false, true) => Ok(None),
+                    (false, false) => Message::_from_body(&self.body).map(Some),
+                };
+                if message.is_err() {
+                    self.errors += 1;
+                }
+                self.body.clear();
+                self.escaped = false;
+                self.overflow = false;
+                Ok(message?)
+            }
+            ESCAPE => {
+                self.escaped = true;
+                Ok(None)
+            }
+            _ => {
+                let byte = match self.escaped {
+                    true => byte ^ 0x20,
+                    false => byte,
+                };
+                self.escaped = false;
+                if self.body.push(byte).is_err() {
+                    self.overflow = true;
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    /// Get the number of frames dropped for a wrong CRC, type or length since the decoder was created.
+    pub fn get_errors(&self) -> u32 {
+        self.errors
+    }
+}
+
+/// The state of a `LinkSession`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum LinkState {
+    /// Sending `Message::Hello` until the other device answers.
+    Connecting,
+    /// Both devices agreed on the seed.
+    Connected,
+    /// No valid frame arrived for `TIMEOUT_MICROS`, reconnecting on the next `Message::Hello`.
+    TimedOut,
+    /// The other device speaks another `PROTOCOL_VERSION`.
+    VersionMismatch { version: u8 },
+    /// The board digests of a tick differ, so the games in lockstep diverged.
+    Desynced { tick: u32 },
+}
+
+/// Something the game has to react to, reported by a `LinkSession`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum LinkEvent {
+    /// The handshake completed, start a game with the seed both devices agreed on.
+    Connected { seed: u64 },
+    /// The opponent sent garbage rows, see `Game::receive_garbage()`.
+    Garbage { lines: u8, gap: u8 },
+    /// The opponent topped out.
+    OpponentTopped,
+    /// The link entered a failed state, see `LinkState`.
+    Failed { state: LinkState },
+}
+
+/// One end of the link, managing the handshake, the keepalives and the desync detection.
+///
+/// The session only deals in bytes, so the UART driver feeds it the received bytes and sends what it queued.
+/// # Attributes
+/// - `seed` - The seed of this device, sent in the handshake
+/// - `state` - The `LinkState`
+/// - `decoder` - The `Decoder` of the received bytes
+/// - `output` - The encoded frames waiting to be sent
+/// - `last_received` - The time of the last valid frame in microseconds
+/// - `last_sent` - The time of the last queued frame in microseconds
+/// - `last_hello` - The time of the last queued handshake in microseconds
+/// - `local_digests` - The digests of this device not yet matched with one of the other device, by tick
+/// - `remote_digests` - The digests of the other device not yet matched with one of this device, by tick
+#[derive(Clone, Debug)]
+pub struct LinkSession {
+    seed: u64,
+    state: LinkState,
+    decoder: Decoder,
+    output: Vec<u8>,
+    last_received: u64,
+    last_sent: u64,
+    last_hello: u64,
+    local_digests: heapless::Deque<(u32, u64), DIGEST_HISTORY>,
+    remote_digests: heapless::Deque<(u32, u64), DIGEST_HISTORY>,
+}
+
+impl LinkSession {
+    /// Create a session and queue its first `Message::Hello`.
+    /// # Arguments
+    /// - `seed` - The seed of this device, e.g. from the ring oscillator
+    /// - `now` - The current time in microseconds
+    /// # Returns
+    /// - `LinkSession` - The session in the `LinkState::Connecting` state
+    pub fn new(seed: u64, now: u64) -> Self {
+        let mut session = LinkSession {
+            seed,
+            state: LinkState::Connecting,
+            decoder: Decoder::new(),
+            output: Vec::new(),
+            last_received: now,
+            last_sent: now,
+            last_hello: now,
+            local_digests: heapless::Deque::new(),
+            remote_digests: heapless::Deque::new(),
+        };
+        session._hello(now);
+        session
+    }
+
+    /// Get the state of the session.
+    pub fn get_state(&self) -> LinkState {
+        self.state
+    }
+
+    /// Get a reference to the decoder, e.g. to log its errors.
+    pub fn get_decoder(&self) -> &Decoder {
+        &self.decoder
+    }
+
+    /// Take the bytes queued for sending.
+    /// # Returns
+    /// - `Vec<u8>` - The encoded frames, in the order they have to be sent
+    pub fn take_output(&mut self) -> Vec<u8> {
+        core::mem::take(&mut self.output)
+    }
+
+    /// Feed a received byte to the session.
+    /// # Arguments
+    /// - `byte` - The received byte
+    /// - `now` - The current time in microseconds
+    /// # Returns
+    /// - `Option<LinkEvent>` - What the game has to react to, if the byte completed a message
+    pub fn receive(&mut self, byte: u8, now: u64) -> Option<LinkEvent> {
+        // Broken frames are counted by the decoder and dropped, the link resynchronizes on the next flag.
+        let message = self.decoder.decode(byte).ok().flatten()?;
+        self.last_received = now;
+        match (self.state, message) {
+            (_, Message::Hello { seed, version }) => self._on_hello(seed, version, now),
+            (LinkState::Connected, Message::Garbage { lines, gap }) => {
+                Some(LinkEvent::Garbage { lines, gap })
+            }
+            (LinkState::Connected, Message::Topped) => Some(LinkEvent::OpponentTopped),
+            (LinkState::Connected, Message::BoardDigest { tick, hash }) => {
+                let (remote, local) = (&mut self.remote_digests, &self.local_digests);
+                let desynced = _match_digest(remote, local, tick, hash);
+                self._check_desync(desynced)
+            }
+            (LinkState::Connected, Message::Ping) => {
+                self._send(Message::Pong, now);
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// Advance the timers of the session, to call every iteration of the main loop.
+    ///
+    /// Resends the handshake while not connected, sends a keepalive when idle and times out a silent link.
+    /// # Arguments
+    /// - `now` - The current time in microseconds
+    /// # Returns
+    /// - `Option<LinkEvent>` - `LinkEvent::Failed` if the link timed out
+    pub fn update(&mut self, now: u64) -> Option<LinkEvent> {
+        let idle = now.saturating_sub(self.last_sent) >= PING_MICROS;
+        match self.state {
+            LinkState::Connecting | LinkState::TimedOut if idle => self._hello(now),
+            LinkState::Connected if now.saturating_sub(self.last_received) >= TIMEOUT_MICROS => {
+                self.state = LinkState::TimedOut;
+                return Some(LinkEvent::Failed { state: self.state });
+            }
+            LinkState::Connected if idle => self._send(Message::Ping, now),
+            _ => {}
+        }
+        None
+    }
+
+    /// Send garbage rows to the opponent.
+    /// # Arguments
+    /// - `lines` - The number of rows
+    /// - `gap` - The column of the gap of the rows
+    /// - `now` - The current time in microseconds
+    pub fn send_garbage(&mut self, lines: u8, gap: u8, now: u64) {
+        self._send(Message::Garbage { lines, gap }, now);
+    }
+
+    /// Tell the opponent this device topped out.
+    /// # Arguments
+    /// - `now` - The current time in microseconds
+    pub fn send_topped(&mut self, now: u64) {
+        self._send(Message::Topped, now);
+    }
+
+    /// Send the digest of the board at a tick and compare it with the digest of the other device, if it arrived.
+    /// # Arguments
+    /// - `tick` - The tick of the game the digest was taken at
+    /// - `hash` - The hash of the board, see `Game::board_hash()`
+    /// - `now` - The current time in microseconds
+    /// # Returns
+    /// - `Option<LinkEvent>` - `LinkEvent::Failed` if the digest of the other device for the tick differs
+    pub fn send_digest(&mut self, tick: u32, hash: u64, now: u64) -> Option<LinkEvent> {
+        if self.state != LinkState::Connected {
+            return None;
+        }
+        self._send(Message::BoardDigest { tick, hash }, now);
+        let (local, remote) = (&mut self.local_digests, &self.remote_digests);
+        let desynced = _match_digest(local, remote, tick, hash);
+        self._check_desync(desynced)
+    }
+
+    /// Answer a handshake, connecting if the versions match.
+    fn _on_hello(&mut self, seed: u64, version: u8, now: u64) -> Option<LinkEvent> {
+        match self.state {
+            LinkState::VersionMismatch { .. } | LinkState::Desynced { .. } => None,
+            _ if version != PROTOCOL_VERSION => {
+                self.state = LinkState::VersionMismatch { version };
+                Some(LinkEvent::Failed { state: self.state })
+            }
+            // The other device did not hear the answer yet, or this is its answer.
+            LinkState::Connected => {
+                if now.saturating_sub(self.last_hello) >= PING_MICROS {
+                    self._hello(now);
+                }
+                None
+            }
+            LinkState::Connecting | LinkState::TimedOut => {
+                self._hello(now);
+                self.state = LinkState::Connected;
+                self.local_digests.clear();
+                self.remote_digests.clear();
+                Some(LinkEvent::Connected {
+                    seed: self.seed.wrapping_add(seed),
+                })
+            }
+        }
+    }
+
+    /// Fail the session if a digest did not match.
+    fn _check_desync(&mut self, desynced: Option<u32>) -> Option<LinkEvent> {
+        let tick = desynced?;
+        self.state = LinkState::Desynced { tick };
+        Some(LinkEvent::Failed { state: self.state })
+    }
+
+    /// Queue the handshake.
+    fn _hello(&mut self, now: u64) {
+        let hello = Message::Hello {
+            seed: self.seed,
+            version: PROTOCOL_VERSION,
+        };
+        self._send(hello, now);
+        self.last_hello = now;
+    }
+
+    /// Queue a message.
+    fn _send(&mut self, message: Message, now: u64) {
+        let mut frame = [0; MAX_FRAME_BYTES];
+        let length = message
+            .encode_into(&mut frame)
+            .expect("every frame fits in MAX_FRAME_BYTES");
+        self.output.extend_from_slice(&frame[..length]);
+        self.last_sent = now;
+    }
+}
+
+/// Match a digest with the digest of the same tick of the other device, or keep it until that one arrives.
+/// # Arguments
+/// - `own` - A muteable reference to the unmatched digests of the device the digest belongs to
+/// - `other` - A reference to the unmatched digests of the other device
+/// - `tick` - The tick of the digest
+/// - `hash` - The hash of the digest
+/// # Returns
+/// - `Option<u32>` - The tick if the digests of the tick differ
+fn _match_digest(
+    own: &mut heapless::Deque<(u32, u64), DIGEST_HISTORY>,
+    other: &heapless::Deque<(u32, u64), DIGEST_HISTORY>,
+    tick: u32,
+    hash: u64,
+) -> Option<u32> {
+    match other.iter().find(|(other_tick, _)| *other_tick == tick) {
+        Some((_, other_hash)) => (*other_hash != hash).then_some(tick),
+        None => {
+            if own.is_full() {
+                own.pop_front();
+            }
+            own.push_back((tick, hash)).ok();
+            None
+        }
+    }
+}
+
+/// Compute the CRC-16/CCITT-FALSE of some bytes.
+fn _crc16(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0xFFFF, |crc: u16, byte| {
+        (0..8).fold(crc ^ (*byte as u16) << 8, |crc, _| match crc & 0x8000 {
+            0 => crc << 1,
+            _ => (crc << 1) ^ 0x1021,
+        })
+    })
+}
+
+/// Copy `N` bytes of a payload from an offset, the caller checked the length of the payload.
+fn _bytes_at<const N: usize>(payload: &[u8], offset: usize) -> [u8; N] {
+    let mut bytes = [0; N];
+    bytes.copy_from_slice(&payload[offset..offset + N]);
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Decoder, LinkEvent, LinkSession, LinkState, Message, ProtocolError, ESCAPE, FLAG,
+        MAX_FRAME_BYTES, PING_MICROS, PROTOCOL_VERSION, TIMEOUT_MICROS,
+    };
+    use crate::game::Game;
+    use crate::input::{Action, InputState};
+    use crate::settings::Settings;
+
+    const MESSAGES: [Message; 6] = [
+        Message::Hello {
+            seed: 0x7E7D_0102_0304_0506,
+            version: PROTOCOL_VERSION,
+        },
+        Message::Garbage { lines: 4, gap: 7 },
+        Message::BoardDigest {
+            tick: 600,
+            hash: u64::MAX,
+        },
+        Message::Topped,
+        Message::Ping,
+        Message::Pong,
+    ];
+
+    /// Encode a message into a frame.
+    fn encode(message: &Message) -> Vec<u8> {
+        let mut frame = [0; MAX_FRAME_BYTES];
+        let length = message.encode_into(&mut frame).unwrap();
+        frame[..length].to_vec()
+    }
+
+    /// Feed bytes to a decoder and collect the messages.
+    fn decode_all(decoder: &mut Decoder, bytes: &[u8]) -> Vec<Message> {
+        bytes
+            .iter()
+            .filter_map(|byte| decoder.decode(*byte).unwrap_or(None))
+            .collect()
+    }
+
+    /// Deliver the queued bytes of one session to the other, collecting the events.
+    fn deliver(from: &mut LinkSession, to: &mut LinkSession, now: u64) -> Vec<LinkEvent> {
+        from.take_output()
+            .iter()
+            .filter_map(|byte| to.receive(*byte, now))
+            .collect()
+    }
+
+    #[test]
+    fn test_crc() {
+        assert_eq!(super::_crc16(b"123456789"), 0x29B1);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut decoder = Decoder::new();
+        for message in MESSAGES {
+            let frame = encode(&message);
+            // The flags only delimit the frame, the ones in the seed are escaped.
+            assert_eq!(frame.iter().filter(|byte| **byte == FLAG).count(), 2);
+            assert_eq!(decode_all(&mut decoder, &frame), [message]);
+        }
+        assert!(encode(&MESSAGES[0]).contains(&ESCAPE));
+        assert_eq!(decoder.get_errors(), 0);
+    }
+
+    #[test]
+    fn test_split_delivery() {
+        // Frames split at every possible point and delivered back to back still decode in order.
+        let stream: Vec<u8> = MESSAGES.iter().flat_map(encode).collect();
+        for split in 0..stream.len() {
+            let mut decoder = Decoder::new();
+            let mut messages = decode_all(&mut decoder, &stream[..split]);
+            messages.extend(decode_all(&mut decoder, &stream[split..]));
+            assert_eq!(messages, MESSAGES);
+        }
+    }
+
+    #[test]
+    fn test_corrupted_frames() {
+        let garbage = encode(&Message::Garbage { lines: 2, gap: 3 });
+        let mut decoder = Decoder::new();
+        // Noise before the first flag is dropped as a broken frame.
+        let mut stream = vec![0x13, 0x37];
+        // A flipped bit fails the CRC.
+        let mut flipped = garbage.clone();
+        flipped[2] ^= 0x01;
+        stream.extend(&flipped);
+        // A lost byte fails the length or the CRC.
+        let mut lost = garbage.clone();
+        lost.remove(3);
+        stream.extend(&lost);
+        // A frame cut short by the start of the next one is dropped, the next one survives.
+        stream.extend(&garbage[..3]);
+        stream.extend(&garbage);
+        // Endless noise without a flag overflows and is dropped.
+        stream.extend([0x55; 2 * MAX_FRAME_BYTES]);
+        stream.extend(&garbage);
+        assert_eq!(
+            decode_all(&mut decoder, &stream),
+            [
+                Message::Garbage { lines: 2, gap: 3 },
+                Message::Garbage { lines: 2, gap: 3 }
+            ],
+        );
+        assert_eq!(decoder.get_errors(), 5);
+    }
+
+    #[test]
+    fn test_protocol_errors() {
+        // A buffer too short for the frame is left alone.
+        let mut short = [0; 4];
+        assert_eq!(
+            Message::Ping.encode_into(&mut short),
+            Err(ProtocolError::BufferTooSmall(5).into())
+        );
+        assert_eq!(short, [0; 4]);
+        // Every broken frame reports why at its closing flag.
+        let mut flipped = encode(&Message::Garbage { lines: 2, gap: 3 });
+        flipped[2] ^= 0x01;
+        let unknown = [9, 0];
+        let crc = super::_crc16(&unknown).to_le_bytes();
+        let frames: [(Vec<u8>, ProtocolError); 4] = [
+            (flipped[1..].to_vec(), ProtocolError::Crc),
+            (vec![0x01, FLAG], ProtocolError::Truncated(1)),
+            (
+                vec![unknown[0], unknown[1], crc[0], crc[1], FLAG],
+                ProtocolError::Payload { kind: 9, length: 1 },
+            ),
+            (
+                [0x55; MAX_FRAME_BYTES]
+                    .iter()
+                    .chain(&[FLAG])
+                    .copied()
+                    .collect(),
+                ProtocolError::Overflow,
+            ),
+        ];
+        let mut decoder = Decoder::new();
+        decoder.decode(FLAG).unwrap();
+        for (frame, error) in frames {
+            let (last, bytes) = frame.split_last().unwrap();
+            assert!(bytes.iter().all(|byte| decoder.decode(*byte) == Ok(None)));
+            assert_eq!(decoder.decode(*last), Err(error.into()));
+        }
+        // Back to back flags are no error.
+        assert_eq!(decoder.decode(FLAG), Ok(None));
+        assert_eq!(decoder.get_errors(), 4);
+    }
+
+    #[test]
+    fn test_handshake() {
+        let mut a = LinkSession::new(1000, 0);
+        let mut b = LinkSession::new(24, 0);
+        // The first handshake of a is lost, b is not plugged in yet.
+        a.take_output();
+        assert_eq!(a.update(PING_MICROS - 1), None);
+        assert!(a.take_output().is_empty());
+        a.update(PING_MICROS);
+        assert_eq!(
+            deliver(&mut a, &mut b, PING_MICROS),
+            [LinkEvent::Connected { seed: 1024 }]
+        );
+        // The first handshake of b still reaches a, followed by its answer.
+        assert_eq!(
+            deliver(&mut b, &mut a, PING_MICROS),
+            [LinkEvent::Connected { seed: 1024 }]
+        );
+        assert_eq!(a.get_state(), LinkState::Connected);
+        assert_eq!(b.get_state(), LinkState::Connected);
+        // The extra answer of a does not reconnect b.
+        assert_eq!(deliver(&mut a, &mut b, PING_MICROS), []);
+        // Another version fails the handshake.
+        let mut c = LinkSession::new(1, 0);
+        let mut hello = vec![0; MAX_FRAME_BYTES];
+        let length = Message::Hello {
+            seed: 2,
+            version: PROTOCOL_VERSION + 1,
+        }
+        .encode_into(&mut hello)
+        .unwrap();
+        let events: Vec<LinkEvent> = hello[..length]
+            .iter()
+            .filter_map(|byte| c.receive(*byte, 0))
+            .collect();
+        let state = LinkState::VersionMismatch {
+            version: PROTOCOL_VERSION + 1,
+        };
+        assert_eq!(events, [LinkEvent::Failed { state }]);
+    }
+
+    #[test]
+    fn test_keepalive() {
+        let mut a = LinkSession::new(1, 0);
+        let mut b = LinkSession::new(2, 0);
+        deliver(&mut a, &mut b, 0);
+        deliver(&mut b, &mut a, 0);
+        deliver(&mut a, &mut b, 0);
+        // An idle link pings and pongs, so neither side times out.
+        let mut now = 0;
+        while now < 4 * TIMEOUT_MICROS {
+            now += 10_000;
+            assert_eq!(a.update(now), None);
+            assert_eq!(b.update(now), None);
+            deliver(&mut a, &mut b, now);
+            deliver(&mut b, &mut a, now);
+        }
+        // Messages arrive as events.
+        a.send_garbage(3, 5, now);
+        a.send_topped(now);
+        assert_eq!(
+            deliver(&mut a, &mut b, now),
+            [
+                LinkEvent::Garbage { lines: 3, gap: 5 },
+                LinkEvent::OpponentTopped
+            ],
+        );
+        // Once b goes silent, a times out and reconnects on the next handshake.
+        a.update(now + TIMEOUT_MICROS - 1);
+        let state = LinkState::TimedOut;
+        assert_eq!(
+            a.update(now + TIMEOUT_MICROS),
+            Some(LinkEvent::Failed { state })
+        );
+        let mut b = LinkSession::new(2, now + TIMEOUT_MICROS);
+        assert_eq!(
+            deliver(&mut b, &mut a, now + TIMEOUT_MICROS),
+            [LinkEvent::Connected { seed: 3 }]
+        );
+    }
+
+    #[test]
+    fn test_desync() {
+        // Two devices race the same game in lockstep, exchanging digests every 10 ticks.
+        let mut a = LinkSession::new(7, 0);
+        let mut b = LinkSession::new(8, 0);
+        deliver(&mut a, &mut b, 0);
+        deliver(&mut b, &mut a, 0);
+        deliver(&mut a, &mut b, 0);
+        let mut game_a = Game::new(Settings::default(), 15);
+        let mut game_b = Game::new(Settings::default(), 15);
+        let mut events = Vec::new();
+        for tick in 0..300u32 {
+            let input = match tick % 40 {
+                0 => InputState::from_action(Action::Start),
+                20 => InputState::from_action(Action::HardDrop),
+                _ => InputState::default(),
+            };
+            game_a.tick(&input);
+            // At tick 200 the input to b is corrupted, so b drops its piece in another column.
+            game_b.tick(&match tick == 199 {
+                true => InputState::from_action(Action::Left),
+                false => input,
+            });
+            if tick % 10 == 0 {
+                events.extend(a.send_digest(tick, game_a.board_hash(), tick as u64));
+                // Either device may be the first to send the digest of a tick.
+                events.extend(deliver(&mut a, &mut b, tick as u64));
+                events.extend(b.send_digest(tick, game_b.board_hash(), tick as u64));
+                events.extend(deliver(&mut b, &mut a, tick as u64));
+            }
+        }
+        assert_eq!(game_a.get_ticks(), game_b.get_ticks());
+        let desync = events
+            .iter()
+            .find_map(|event| match event {
+                LinkEvent::Failed {
+                    state: LinkState::Desynced { tick },
+                } => Some(*tick),
+                _ => None,
+            })
+            .unwrap();
+        assert!((200..=220).contains(&desync));
+        assert!(matches!(a.get_state(), LinkState::Desynced { .. }));
+        assert!(matches!(b.get_state(), LinkState::Desynced { .. }));
+    }
+}
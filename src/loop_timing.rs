@@ -0,0 +1,225 @@
+/// The default maximum number of ticks to catch up on in a single iteration of the main loop.
+pub const MAX_CATCH_UP: u32 = 8;
+
+/// The number of ticks without a tick run before the watchdog reboots the board, well beyond a catch-up.
+pub const WATCHDOG_TICKS: u32 = 4 * MAX_CATCH_UP;
+
+/// The longest timeout of the watchdog of the RP2040, which counts down twice per microsecond.
+const MAX_WATCHDOG_MICROS: u32 = 0x7F_FFFF;
+
+/// The number of microseconds in a second.
+const MICROS_PER_SECOND: u64 = 1_000_000;
+
+/// A monotonic clock counting microseconds, e.g. the timer of the RP2040 or `std::time::Instant`.
+pub trait Clock {
+    /// Get the number of microseconds since an arbitrary, fixed point in time.
+    fn now_micros(&mut self) -> u64;
+}
+
+impl<F: FnMut() -> u64> Clock for F {
+    fn now_micros(&mut self) -> u64 {
+        self()
+    }
+}
+
+/// A fixed timestep, telling the main loop how many game ticks are due however long an iteration takes.
+///
+/// Tick `n` is due `n / tick_hz` seconds after the first poll, computed from the start so the rounding
+/// of the tick duration to whole microseconds never accumulates. When the loop falls behind by more than
+/// `max_catch_up` ticks, e.g. after a long LED flush, the excess ticks are dropped instead of run, so the
+/// game slows down rather than spending every iteration catching up.
+/// # Attributes
+/// - `tick_hz` - The number of ticks per second
+/// - `max_catch_up` - The maximum number of ticks to run in a single iteration
+/// - `start` - The time of the first poll in microseconds, if polled yet
+/// - `ticks` - The number of ticks run or dropped since the start
+/// - `dropped` - The number of ticks dropped since the start
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FixedTimestep {
+    tick_hz: u32,
+    max_catch_up: u32,
+    start: Option<u64>,
+    ticks: u64,
+    dropped: u64,
+}
+
+impl FixedTimestep {
+    /// Create a fixed timestep catching up on at most `MAX_CATCH_UP` ticks per iteration.
+    /// # Arguments
+    /// - `tick_hz` - The number of ticks per second, larger than 0
+    /// # Returns
+    /// - `FixedTimestep` - The fixed timestep, starting at the first poll
+    pub fn new(tick_hz: u32) -> Self {
+        FixedTimestep::with_max_catch_up(tick_hz, MAX_CATCH_UP)
+    }
+
+    /// Create a fixed timestep with a custom catch-up limit.
+    /// # Arguments
+    /// - `tick_hz` - The number of ticks per second, larger than 0
+    /// - `max_catch_up` - The maximum number of ticks to run in a single iteration, at least 1
+    /// # Returns
+    /// - `FixedTimestep` - The fixed timestep, starting at the first poll
+    pub fn with_max_catch_up(tick_hz: u32, max_catch_up: u32) -> Self {
+        assert!(tick_hz > 0, "the tick rate must be positive");
+        FixedTimestep {
+            tick_hz,
+            max_catch_up: max_catch_up.max(1),
+            start: None,
+            ticks: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Read the clock and get the number of ticks to run this iteration, see `.update()`.
+    /// # Arguments
+    /// - `clock` - A muteable reference to the `Clock`
+    /// # Returns
+    /// - `u32` - The number of ticks to run, at most `max_catch_up`
+    pub fn poll(&mut self, clock: &mut impl Clock) -> u32 {
+        self.update(clock.now_micros())
+    }
+
+    /// Get the number of ticks to run this iteration, dropping the ticks beyond `max_catch_up`.
+    /// # Arguments
+    /// - `now` - The current time in microseconds, never earlier than in a previous update
+    /// # Returns
+    /// - `u32` - The number of ticks to run, at most `max_catch_up`
+    pub fn update(&mut self, now: u64) -> u32 {
+        let start = *self.start.get_or_insert(now);
+        let due = (now - start) * self.tick_hz as u64 / MICROS_PER_SECOND + 1;
+        let behind = due.saturating_sub(self.ticks);
+        let run = behind.min(self.max_catch_up as u64);
+        self.dropped += behind - run;
+        self.ticks += behind;
+        run as u32
+    }
+
+    /// Get the time the next tick is due in microseconds, e.g. to sleep until then.
+    pub fn get_next_deadline(&self) -> u64 {
+        // Round up to the first whole microsecond the tick is due at.
+        let micros = self.ticks * MICROS_PER_SECOND;
+        let tick_hz = self.tick_hz as u64;
+        self.start.unwrap_or(0) + micros / tick_hz + (micros % tick_hz).min(1)
+    }
+
+    /// Get the number of ticks run or dropped since the first poll.
+    pub fn get_ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    /// Get the number of ticks dropped since the first poll, because the loop fell too far behind.
+    pub fn get_dropped(&self) -> u64 {
+        self.dropped
+    }
+}
+
+/// Get the timeout of a watchdog fed on every tick, so it only bites when the loop stops running ticks.
+/// # Arguments
+/// - `tick_hz` - The number of ticks per second, larger than 0
+/// - `ticks` - The number of ticks without a tick run to tolerate, e.g. `WATCHDOG_TICKS`
+/// # Returns
+/// - `u32` - The timeout in microseconds, at most the longest timeout of the RP2040
+pub fn watchdog_timeout_micros(tick_hz: u32, ticks: u32) -> u32 {
+    let micros = ticks as u64 * MICROS_PER_SECOND / tick_hz as u64;
+    micros.min(MAX_WATCHDOG_MICROS as u64) as u32
+}
+
+#[cfg(feature = "rp2040")]
+pub use watchdog::TickWatchdog;
+
+#[cfg(feature = "rp2040")]
+mod watchdog {
+    use super::{watchdog_timeout_micros, WATCHDOG_TICKS};
+    use crate::crash;
+    use rp2040_hal::fugit::ExtU32;
+    use rp2040_hal::Watchdog;
+
+    /// The watchdog of the RP2040, fed by the fixed-timestep loop on every tick it runs.
+    ///
+    /// Once a core panicked it is no longer fed, so the crash screen is followed by a reboot.
+    /// # Attributes
+    /// - `watchdog` - The watchdog
+    pub struct TickWatchdog {
+        watchdog: Watchdog,
+    }
+
+    impl TickWatchdog {
+        /// Arm the watchdog with a timeout of `WATCHDOG_TICKS` ticks, paused while a debugger halts the cores.
+        /// # Arguments
+        /// - `watchdog` - The watchdog, after it generated the tick for the clocks, see `init_clocks_and_plls()`
+        /// - `tick_hz` - The number of ticks per second of the loop
+        /// # Returns
+        /// - `TickWatchdog` - The armed watchdog
+        pub fn arm(mut watchdog: Watchdog, tick_hz: u32) -> Self {
+            watchdog.pause_on_debug(true);
+            watchdog.start(watchdog_timeout_micros(tick_hz, WATCHDOG_TICKS).micros());
+            TickWatchdog { watchdog }
+        }
+
+        /// Feed the watchdog if the loop ran any ticks and no core panicked.
+        /// # Arguments
+        /// - `ticks` - The number of ticks run this iteration, see `FixedTimestep::poll()`
+        pub fn feed(&self, ticks: u32) {
+            if ticks > 0 && !crash::has_panicked() {
+                self.watchdog.feed();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{watchdog_timeout_micros, Clock, FixedTimestep, WATCHDOG_TICKS};
+    use crate::rng::{GameRng, RngSource};
+
+    #[test]
+    fn test_jittery_clock() {
+        // Iterations take between 1 and 40 ms, averaging about a tick of 16.67 ms.
+        let mut rng = GameRng::new(148);
+        let mut now = 5_000;
+        let mut clock = || {
+            now += 1_000 + rng.below(39_000) as u64;
+            now
+        };
+        let mut timestep = FixedTimestep::new(60);
+        let first = clock.now_micros();
+        let mut run = timestep.update(first) as u64;
+        let mut last = first;
+        while last - first < 60_000_000 {
+            last = clock.now_micros();
+            run += timestep.poll(&mut || last) as u64;
+        }
+        // Exactly one tick is due at the start and every 1 / 60 s after it, without any drift.
+        assert_eq!(timestep.get_dropped(), 0);
+        assert_eq!(run, (last - first) * 60 / 1_000_000 + 1);
+        assert!(timestep.get_next_deadline() > last);
+        assert!(timestep.get_next_deadline() - last <= 16_667);
+    }
+
+    #[test]
+    fn test_catch_up() {
+        let mut timestep = FixedTimestep::with_max_catch_up(60, 4);
+        assert_eq!(timestep.update(1_000), 1);
+        assert_eq!(timestep.update(1_000), 0);
+        assert_eq!(timestep.get_next_deadline(), 17_667);
+        assert_eq!(timestep.update(17_666), 0);
+        assert_eq!(timestep.update(17_667), 1);
+        // A stall of a second runs 4 ticks and drops the other 56.
+        assert_eq!(timestep.update(1_017_667), 4);
+        assert_eq!(timestep.get_dropped(), 56);
+        assert_eq!(timestep.get_ticks(), 62);
+        // After the stall the timestep keeps its pace.
+        assert_eq!(timestep.get_next_deadline(), 1_034_334);
+        assert_eq!(timestep.update(1_034_333), 0);
+        assert_eq!(timestep.update(1_034_334), 1);
+    }
+
+    #[test]
+    fn test_watchdog_timeout() {
+        // 32 ticks at 60 Hz, about half a second.
+        assert_eq!(watchdog_timeout_micros(60, WATCHDOG_TICKS), 533_333);
+        assert_eq!(watchdog_timeout_micros(1, 1), 1_000_000);
+        // The RP2040 cannot wait longer than about 8.4 s.
+        assert_eq!(watchdog_timeout_micros(1, 60), 0x7F_FFFF);
+    }
+}
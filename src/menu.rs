@@ -0,0 +1,1076 @@
+//! The boot splash and the menu, shown in the `GameState::Menu` state before a game starts.
+//!
+//! After a power-up, a few pieces fall on a scratch board and stack into a big T, after which the main menu shows.
+//! Any input skips the splash. The menu is navigated with the actions of the game: `Action::HardDrop` and
+//! `Action::SoftDrop` move the cursor up and down, `Action::Left` and `Action::Right` change a value,
+//! `Action::RotateCw` selects and `Action::RotateCcw` goes back. `Action::Start` starts a game with the chosen
+//! settings from any screen.
+//!
+//! The controls option remaps the buttons: the menu asks for every action in the order of `ACTIONS`, showing its
+//! number, and maps it to the next button pressed that has no action yet, read before the key map so the old map
+//! does not get in the way. The new map replaces the old one once every action has a button, and without a press for
+//! `REMAP_TIMEOUT_TICKS` the old map is kept.
+//!
+//! The panel has no font but the digits, so every item is drawn as an icon or a number in the colors of the pieces,
+//! with a white cursor left of the selected item. The statistics screen draws how often every shape was dealt as a
+//! bar in the color of the shape, see `RandomizerStats`. The lifetime screen draws the totals of `LifetimeStats` from
+//! top to bottom, every one in the color of the next shape of `SHAPES`: the games, lines, tetrises, T-spins, minutes
+//! played and the best sprint in hundredths of a second, left out before the first finished sprint.
+use crate::board::{Board, BoardOps};
+use crate::cell::Cell;
+use crate::color::Rgb;
+use crate::coordinate::Coordinate;
+use crate::display::digits::{draw_digit, to_digits, GLYPH_COLS, GLYPH_ROWS, MAX_DIGITS};
+use crate::framebuffer::FrameBuffer;
+use crate::gravity::GravityEngine;
+use crate::highscores::HighScores;
+use crate::input::keymap::{KeyMap, KEYMAP_INPUTS};
+use crate::input::{Action, InputState, ACTIONS};
+use crate::lifetime::LifetimeStats;
+use crate::palette::Palette;
+use crate::piece::ActivePiece;
+use crate::randomizer::RandomizerStats;
+use crate::settings::{ColorScheme, DebugOverlay, GameMode, Settings, StackColorMode};
+use crate::tetrominoes::{Tetromino, TetrominoShape, SHAPES};
+use alloc::boxed::Box;
+
+/// The number of rows of the scratch board of the splash.
+pub const SPLASH_ROWS: usize = 24;
+
+/// The number of columns of the scratch board of the splash.
+pub const SPLASH_COLS: usize = 32;
+
+/// The pieces of the splash and the column they fall in, stacking into a T: a stem of O pieces and a bar of I pieces.
+const SPLASH_PIECES: [(TetrominoShape, usize); 8] = [
+    (TetrominoShape::O, 15),
+    (TetrominoShape::O, 15),
+    (TetrominoShape::O, 15),
+    (TetrominoShape::O, 15),
+    (TetrominoShape::I, 12),
+    (TetrominoShape::I, 16),
+    (TetrominoShape::I, 12),
+    (TetrominoShape::I, 16),
+];
+
+/// The number of ticks a piece of the splash takes to fall a row.
+const SPLASH_TICKS_PER_ROW: u32 = 1;
+
+/// The number of ticks a piece of the splash rests before it locks.
+const SPLASH_LOCK_DELAY: u32 = 8;
+
+/// The number of ticks the finished splash is shown before the main menu.
+const SPLASH_HOLD_TICKS: u32 = 60;
+
+/// The highest level a game can start at, the fastest gravity.
+pub const MAX_START_LEVEL: u8 = 15;
+
+/// The number of panel rows per item of a menu.
+const ITEM_ROWS: usize = GLYPH_ROWS + 2;
+
+/// The number of panel columns left of the items, holding the cursor.
+const ITEM_COL: usize = 3;
+
+/// The color of the cursor.
+const CURSOR_COLOR: Rgb = Rgb::WHITE;
+
+/// The brightness of an option that is off, out of 255.
+const OFF_LUM: u8 = 64;
+
+/// The number of panel columns of a bar of the statistics screen.
+const BAR_COLS: usize = 3;
+
+/// The number of ticks the controls screen waits for a button before keeping the old key map, 10 seconds at 60 ticks
+/// per second.
+pub const REMAP_TIMEOUT_TICKS: u32 = 10 * 60;
+
+/// The items of the main menu, from top to bottom.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MainItem {
+    /// Start a game, drawn as a T piece.
+    Play,
+    /// Choose the `GameMode`, drawn as its number.
+    Mode,
+    /// Choose the start level, drawn as its number.
+    Level,
+    /// Toggle the options, drawn as an O piece.
+    Options,
+    /// Show the high scores, drawn as the best score.
+    Scores,
+    /// Show the statistics of the randomizer, drawn as the number of dealt pieces.
+    Stats,
+    /// Show the lifetime totals, drawn as the number of games played.
+    Lifetime,
+}
+
+/// The items of the main menu, in the order they are drawn.
+pub const MAIN_ITEMS: [MainItem; 7] = [
+    MainItem::Play,
+    MainItem::Mode,
+    MainItem::Level,
+    MainItem::Options,
+    MainItem::Scores,
+    MainItem::Stats,
+    MainItem::Lifetime,
+];
+
+/// The modes to choose from, drawn as the numbers 1 to 4 followed by their goal.
+pub const MODES: [GameMode; 4] = [
+    GameMode::Marathon,
+    GameMode::SPRINT,
+    GameMode::ULTRA,
+    GameMode::CHEESE,
+];
+
+/// The items of the options screen, every one toggled by selecting it or pressing left or right.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OptionItem {
+    /// `Settings::ghost`
+    Ghost,
+    /// `Settings::hold`
+    Hold,
+    /// `Settings::grid`
+    Grid,
+    /// `Settings::depth_fog`
+    DepthFog,
+    /// `Settings::input_display`
+    InputDisplay,
+    /// `Settings::finesse`
+    Finesse,
+    /// `Settings::colors`, cycling through the color schemes.
+    Colors,
+    /// `Settings::keymap`, remapped on the controls screen, drawn as a J piece.
+    Controls,
+}
+
+/// The items of the options screen, in the order they are drawn.
+pub const OPTION_ITEMS: [OptionItem; 8] = [
+    OptionItem::Ghost,
+    OptionItem::Hold,
+    OptionItem::Grid,
+    OptionItem::DepthFog,
+    OptionItem::InputDisplay,
+    OptionItem::Finesse,
+    OptionItem::Colors,
+    OptionItem::Controls,
+];
+
+/// The pieces falling on a scratch board at boot.
+/// # Attributes
+/// - `board` - The scratch board the pieces lock on
+/// - `piece` - The falling piece, if any are left
+/// - `gravity` - The gravity and lock delay of the falling piece
+/// - `next` - The index in `SPLASH_PIECES` of the next piece to spawn
+/// - `ticks` - The number of ticks since the last piece locked
+#[derive(Debug)]
+pub struct Splash {
+    board: Board<Cell>,
+    piece: Option<ActivePiece>,
+    gravity: GravityEngine,
+    next: usize,
+    ticks: u32,
+}
+
+impl Splash {
+    /// Create the splash with its first piece at the top of an empty board.
+    /// # Returns
+    /// - `Splash` - The splash
+    pub fn new() -> Self {
+        let mut splash = Splash {
+            board: Board::new(
+                Coordinate::from_array([SPLASH_ROWS, SPLASH_COLS]),
+                Cell::Empty,
+            ),
+            piece: None,
+            gravity: GravityEngine::new(SPLASH_LOCK_DELAY, 0),
+            next: 0,
+            ticks: 0,
+        };
+        splash._spawn();
+        splash
+    }
+
+    /// Get a reference to the locked pieces of the splash.
+    pub fn get_board(&self) -> &Board<Cell> {
+        &self.board
+    }
+
+    /// Check whether all pieces fell and the result was shown long enough.
+    pub fn is_done(&self) -> bool {
+        self.piece.is_none() && self.ticks >= SPLASH_HOLD_TICKS
+    }
+
+    /// Spawn the next piece at the top of the board, if any are left.
+    fn _spawn(&mut self) {
+        if let Some((shape, col)) = SPLASH_PIECES.get(self.next) {
+            let coord = Coordinate { row: 0, col: *col };
+            self.piece = Some(ActivePiece::new(Tetromino::from(*shape), coord));
+            self.gravity.reset();
+            self.next += 1;
+        }
+    }
+
+    /// Let the falling piece fall or lock, or count the ticks once all pieces locked.
+    pub fn tick(&mut self) {
+        let lock = match &mut self.piece {
+            Some(piece) => self.gravity.step(piece, &self.board, SPLASH_TICKS_PER_ROW),
+            None => {
+                self.ticks += 1;
+                false
+            }
+        };
+        if lock {
+            if let Some(piece) = self.piece.take() {
+                let shape = piece.get_tetromino().shape;
+                piece.place(&mut self.board, Cell::locked(shape));
+            }
+            self._spawn();
+        }
+    }
+
+    /// Draw the splash centered on the panel.
+    /// # Arguments
+    /// - `frame` - A muteable reference to the `FrameBuffer` to draw into
+    /// - `palette` - A reference to the `Palette` to draw the pieces with
+    pub fn render<const N: usize>(&self, frame: &mut FrameBuffer<N>, palette: &Palette) {
+        let dims = frame.get_shape();
+        let origin = Coordinate {
+            row: dims.row.saturating_sub(SPLASH_ROWS) / 2,
+            col: dims.col.saturating_sub(SPLASH_COLS) / 2,
+        };
+        for (coord, cell) in self.board.cells() {
+            if let Cell::Filled { shape, lum } = cell {
+                frame.set_pixel(origin + coord, palette.get_color(shape).scaled(lum));
+            }
+        }
+        if let Some(piece) = &self.piece {
+            let tetromino = piece.get_tetromino();
+            frame.set_mask(
+                tetromino.get_mask(),
+                origin + piece.get_coord(),
+                palette.get_color(tetromino.shape),
+            );
+        }
+    }
+}
+
+impl Default for Splash {
+    fn default() -> Self {
+        Splash::new()
+    }
+}
+
+/// A screen of the menu, with the state it needs.
+#[derive(Debug)]
+pub enum Screen {
+    /// The pieces falling at boot, boxed so the scratch board is freed once the menu shows.
+    Splash(Box<Splash>),
+    /// The main menu, with the index of the selected item of `MAIN_ITEMS`.
+    Main(usize),
+    /// Choosing the mode, with the index of the selected mode of `MODES`.
+    ModeSelect(usize),
+    /// Choosing the start level, with the shown level.
+    LevelSelect(u8),
+    /// Toggling the options, with the index of the selected item of `OPTION_ITEMS`.
+    Options(usize),
+    /// Remapping the buttons, with the index in `ACTIONS` of the action waiting for a button, the new key map so far
+    /// and the number of ticks since the last button.
+    Remap {
+        action: usize,
+        keymap: KeyMap,
+        idle_ticks: u32,
+    },
+    /// The high-score table.
+    HighScores,
+    /// How often every shape was dealt, as bars.
+    Stats,
+    /// The totals of every game played, as numbers.
+    Lifetime,
+}
+
+/// What the menu did during a single tick.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum MenuOutput {
+    /// Still in the menu.
+    #[default]
+    None,
+    /// Start a game with the chosen settings.
+    Start(Settings),
+}
+
+/// The splash and the menu, as a state machine of screens.
+/// # Attributes
+/// - `screen` - The current screen
+/// - `settings` - The settings chosen so far, handed to the game when it starts
+/// - `high_scores` - The high scores to show
+/// - `stats` - The statistics of the randomizer to show
+/// - `lifetime` - The totals of every game played to show
+#[derive(Debug)]
+pub struct MenuState {
+    screen: Screen,
+    settings: Settings,
+    high_scores: HighScores,
+    stats: RandomizerStats,
+    lifetime: LifetimeStats,
+}
+
+/// Move a cursor up or down a list, wrapping around at both ends.
+/// # Arguments
+/// - `cursor` - The index of the selected item
+/// - `len` - The number of items
+/// - `input` - A reference to the `InputState` of this tick
+/// # Returns
+/// - `usize` - The index of the selected item after the tick
+fn _move_cursor(cursor: usize, len: usize, input: &InputState) -> usize {
+    match (
+        input.is_pressed(Action::HardDrop),
+        input.is_pressed(Action::SoftDrop),
+    ) {
+        (true, false) => (cursor + len - 1) % len,
+        (false, true) => (cursor + 1) % len,
+        _ => cursor,
+    }
+}
+
+/// Get the index of a mode in `MODES`, the standard mode for a custom goal.
+fn _mode_index(mode: GameMode) -> usize {
+    match mode {
+        GameMode::Marathon => 0,
+        GameMode::Sprint { .. } => 1,
+        GameMode::Ultra { .. } => 2,
+        GameMode::Cheese { .. } => 3,
+    }
+}
+
+/// Get the index of the main menu item of a screen, to return to it.
+fn _main_index(item: MainItem) -> usize {
+    MAIN_ITEMS
+        .iter()
+        .position(|main| *main == item)
+        .expect("every screen returns to an item of the main menu")
+}
+
+/// Toggle an option, or cycle the color scheme.
+/// # Arguments
+/// - `settings` - A muteable reference to the `Settings` to change
+/// - `item` - The `OptionItem` to change
+fn _toggle(settings: &mut Settings, item: OptionItem) {
+    match item {
+        OptionItem::Ghost => settings.ghost = !settings.ghost,
+        OptionItem::Hold => settings.hold = !settings.hold,
+        OptionItem::Grid => settings.grid = !settings.grid,
+        OptionItem::DepthFog => settings.depth_fog = !settings.depth_fog,
+        OptionItem::InputDisplay => settings.input_display = !settings.input_display,
+        OptionItem::Finesse => settings.finesse = !settings.finesse,
+        OptionItem::Colors => {
+            settings.colors = match settings.colors {
+                ColorScheme::Guideline => ColorScheme::Levels,
+                ColorScheme::Levels => ColorScheme::Accessible,
+                ColorScheme::Accessible => ColorScheme::Guideline,
+            }
+        }
+        // Opens its own screen instead.
+        OptionItem::Controls => (),
+    }
+}
+
+/// Get the index of an item of the options screen, to return to it.
+fn _option_index(item: OptionItem) -> usize {
+    OPTION_ITEMS
+        .iter()
+        .position(|option| *option == item)
+        .expect("every option is an item of the options screen")
+}
+
+/// Draw a number left-aligned with the digit font.
+/// # Arguments
+/// - `frame` - A muteable reference to the `FrameBuffer` to draw into
+/// - `origin` - The top-left pixel of the first digit
+/// - `value` - The number to draw
+/// - `color` - The color of the digits
+fn _draw_number<const N: usize>(
+    frame: &mut FrameBuffer<N>,
+    origin: Coordinate,
+    value: u32,
+    color: Rgb,
+) {
+    let mut digits = [0; MAX_DIGITS];
+    let count = to_digits(value, &mut digits);
+    for (index, digit) in digits[MAX_DIGITS - count..].iter().enumerate() {
+        draw_digit(frame, origin + [0, index * (GLYPH_COLS + 1)], *digit, color);
+    }
+}
+
+impl MenuState {
+    /// Create the menu, starting with the splash.
+    /// # Arguments
+    /// - `settings` - The `Settings` to start choosing from
+    /// # Returns
+    /// - `MenuState` - The menu showing the splash
+    pub fn new(settings: Settings) -> Self {
+        MenuState {
+            screen: Screen::Splash(Box::default()),
+            settings,
+            high_scores: HighScores::default(),
+            stats: RandomizerStats::new(),
+            lifetime: LifetimeStats::new(),
+        }
+    }
+
+    /// Get a reference to the current screen.
+    pub fn get_screen(&self) -> &Screen {
+        &self.screen
+    }
+
+    /// Get a reference to the settings chosen so far.
+    pub fn get_settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    /// Replace the high scores to show.
+    /// # Arguments
+    /// - `high_scores` - The `HighScores` to show
+    pub fn set_high_scores(&mut self, high_scores: HighScores) {
+        self.high_scores = high_scores;
+    }
+
+    /// Replace the statistics of the randomizer to show.
+    /// # Arguments
+    /// - `stats` - The `RandomizerStats` of the session
+    pub fn set_randomizer_stats(&mut self, stats: RandomizerStats) {
+        self.stats = stats;
+    }
+
+    /// Replace the lifetime totals to show.
+    /// # Arguments
+    /// - `lifetime` - The `LifetimeStats` of every game played
+    pub fn set_lifetime_stats(&mut self, lifetime: LifetimeStats) {
+        self.lifetime = lifetime;
+    }
+
+    /// Replace the debug overlays, e.g. toggled from the console, so the next game keeps them.
+    /// # Arguments
+    /// - `debug` - The `DebugOverlay` to draw
+    pub fn set_debug_overlay(&mut self, debug: DebugOverlay) {
+        self.settings.debug = debug;
+    }
+
+    /// Replace the colors of the stack, e.g. switched from the console, so the next game keeps them.
+    /// # Arguments
+    /// - `stack_colors` - The `StackColorMode` to draw the locked cells with
+    pub fn set_stack_colors(&mut self, stack_colors: StackColorMode) {
+        self.settings.stack_colors = stack_colors;
+    }
+
+    /// Skip the splash, e.g. when returning to the menu after a game.
+    pub fn skip_splash(&mut self) {
+        if let Screen::Splash(_) = self.screen {
+            self.screen = Screen::Main(0);
+        }
+    }
+
+    /// Show the mode select on the mode of the settings, e.g. when returning to the menu after abandoning a game.
+    pub fn show_mode_select(&mut self) {
+        self.screen = Screen::ModeSelect(_mode_index(self.settings.mode));
+    }
+
+    /// Advance the menu by a single tick.
+    /// # Arguments
+    /// - `input` - A reference to the `InputState` of this tick
+    /// # Returns
+    /// - `MenuOutput` - Whether to start a game, and with which settings
+    pub fn tick(&mut self, input: &InputState) -> MenuOutput {
+        // While remapping, the buttons are read before the old key map, so none of them starts a game.
+        let remapping = matches!(self.screen, Screen::Remap { .. });
+        if input.is_pressed(Action::Start) && !remapping {
+            return MenuOutput::Start(self.settings);
+        }
+        let select = input.is_pressed(Action::RotateCw);
+        let back = input.is_pressed(Action::RotateCcw);
+        let change = input.is_pressed(Action::Left) || input.is_pressed(Action::Right);
+        let next = match &mut self.screen {
+            Screen::Splash(splash) => {
+                splash.tick();
+                (splash.is_done() || input.get_held_bits() != 0).then_some(Screen::Main(0))
+            }
+            Screen::Main(cursor) => match (select, MAIN_ITEMS[*cursor]) {
+                (true, MainItem::Play) => return MenuOutput::Start(self.settings),
+                (true, MainItem::Mode) => Some(Screen::ModeSelect(_mode_index(self.settings.mode))),
+                (true, MainItem::Level) => Some(Screen::LevelSelect(self.settings.start_level)),
+                (true, MainItem::Options) => Some(Screen::Options(0)),
+                (true, MainItem::Scores) => Some(Screen::HighScores),
+                (true, MainItem::Stats) => Some(Screen::Stats),
+                (true, MainItem::Lifetime) => Some(Screen::Lifetime),
+                (false, _) => Some(Screen::Main(_move_cursor(*cursor, MAIN_ITEMS.len(), input))),
+            },
+            Screen::ModeSelect(cursor) => match (select, back) {
+                (true, _) => {
+                    self.settings.mode = MODES[*cursor];
+                    Some(Screen::Main(_main_index(MainItem::Mode)))
+                }
+                (false, true) => Some(Screen::Main(_main_index(MainItem::Mode))),
+                (false, false) => Some(Screen::ModeSelect(_move_cursor(
+                    *cursor,
+                    MODES.len(),
+                    input,
+                ))),
+            },
+            Screen::LevelSelect(level) => match (select, back) {
+                (true, _) => {
+                    self.settings.start_level = *level;
+                    Some(Screen::Main(_main_index(MainItem::Level)))
+                }
+                (false, true) => Some(Screen::Main(_main_index(MainItem::Level))),
+                (false, false) => {
+                    if input.is_pressed(Action::Left) {
+                        *level = level.saturating_sub(1).max(1);
+                    }
+                    if input.is_pressed(Action::Right) {
+                        *level = (*level + 1).min(MAX_START_LEVEL);
+                    }
+                    None
+                }
+            },
+            Screen::Options(cursor) => match (select || change, back) {
+                (_, true) => Some(Screen::Main(_main_index(MainItem::Options))),
+                (true, false) => match OPTION_ITEMS[*cursor] {
+                    OptionItem::Controls => select.then_some(Screen::Remap {
+                        action: 0,
+                        keymap: KeyMap::unmapped(),
+                        idle_ticks: 0,
+                    }),
+                    option => {
+                        _toggle(&mut self.settings, option);
+                        None
+                    }
+                },
+                (false, false) => Some(Screen::Options(_move_cursor(
+                    *cursor,
+                    OPTION_ITEMS.len(),
+                    input,
+                ))),
+            },
+            Screen::Remap {
+                action,
+                keymap,
+                idle_ticks,
+            } => {
+                // The lowest button that went down, if it has no action yet.
+                let button = input.get_raw_pressed().trailing_zeros() as usize;
+                let done = Screen::Options(_option_index(OptionItem::Controls));
+                match button < KEYMAP_INPUTS && keymap.get_action(button).is_none() {
+                    true => {
+                        keymap.set(button, ACTIONS[*action]);
+                        *action += 1;
+                        *idle_ticks = 0;
+                        if *action == ACTIONS.len() {
+                            self.settings.keymap = *keymap;
+                        }
+                        (*action == ACTIONS.len()).then_some(done)
+                    }
+                    false => {
+                        *idle_ticks += 1;
+                        (*idle_ticks >= REMAP_TIMEOUT_TICKS).then_some(done)
+                    }
+                }
+            }
+            Screen::HighScores => {
+                (select || back).then_some(Screen::Main(_main_index(MainItem::Scores)))
+            }
+            Screen::Stats => (select || back).then_some(Screen::Main(_main_index(MainItem::Stats))),
+            Screen::Lifetime => {
+                (select || back).then_some(Screen::Main(_main_index(MainItem::Lifetime)))
+            }
+        };
+        if let Some(screen) = next {
+            self.screen = screen;
+        }
+        MenuOutput::None
+    }
+
+    /// Draw the current screen, replacing the whole frame.
+    /// # Arguments
+    /// - `frame` - A muteable reference to the `FrameBuffer` to draw into
+    /// - `palette` - A reference to the `Palette` to draw the icons with
+    pub fn render<const N: usize>(&self, frame: &mut FrameBuffer<N>, palette: &Palette) {
+        frame.clear();
+        let item = |index: usize| Coordinate {
+            row: 1 + index * ITEM_ROWS,
+            col: ITEM_COL,
+        };
+        let cursor = match self.screen {
+            Screen::Splash(ref splash) => {
+                splash.render(frame, palette);
+                return;
+            }
+            Screen::Main(cursor) => {
+                for (index, main) in MAIN_ITEMS.iter().enumerate() {
+                    self._draw_main_item(frame, item(index), *main, palette);
+                }
+                Some(cursor)
+            }
+            Screen::ModeSelect(cursor) => {
+                for (index, mode) in MODES.iter().enumerate() {
+                    let color = palette.get_color(TetrominoShape::I);
+                    draw_digit(frame, item(index), index as u8 + 1, color);
+                    let goal = match mode {
+                        GameMode::Marathon => continue,
+                        GameMode::Sprint { lines } => *lines,
+                        GameMode::Ultra { ticks } => ticks / 60,
+                        GameMode::Cheese { rows, .. } => *rows as u32,
+                    };
+                    _draw_number(frame, item(index) + [0, GLYPH_COLS + 2], goal, Rgb::WHITE);
+                }
+                Some(cursor)
+            }
+            Screen::LevelSelect(level) => {
+                let color = palette.get_color(TetrominoShape::L);
+                _draw_number(frame, item(0), level as u32, color);
+                None
+            }
+            Screen::Options(cursor) => {
+                for (index, option) in OPTION_ITEMS.iter().enumerate() {
+                    self._draw_option(frame, item(index), *option, palette);
+                }
+                Some(cursor)
+            }
+            Screen::Remap { action, .. } => {
+                let color = palette.get_color(TetrominoShape::J);
+                _draw_number(frame, item(0), action as u32 + 1, color);
+                None
+            }
+            Screen::HighScores => {
+                for (index, entry) in self.high_scores.iter().enumerate() {
+                    _draw_number(frame, item(index), entry.score, Rgb::WHITE);
+                }
+                None
+            }
+            Screen::Stats => {
+                self._draw_stats(frame, palette);
+                None
+            }
+            Screen::Lifetime => {
+                let lifetime = &self.lifetime;
+                let totals = [
+                    Some(lifetime.get_games()),
+                    Some(lifetime.get_lines()),
+                    Some(lifetime.get_tetrises()),
+                    Some(lifetime.get_t_spins()),
+                    Some(lifetime.get_centis() / (60 * 100)),
+                    lifetime.get_best_sprint(),
+                ];
+                for (index, (total, shape)) in totals.into_iter().zip(SHAPES).enumerate() {
+                    if let Some(total) = total {
+                        _draw_number(frame, item(index), total, palette.get_color(shape));
+                    }
+                }
+                None
+            }
+        };
+        if let Some(cursor) = cursor {
+            for r in 0..GLYPH_ROWS {
+                for col in 0..ITEM_COL - 1 {
+                    let row = item(cursor).row + r;
+                    frame.set_pixel(Coordinate { row, col }, CURSOR_COLOR);
+                }
+            }
+        }
+    }
+
+    /// Draw an item of the main menu as its icon or value.
+    fn _draw_main_item<const N: usize>(
+        &self,
+        frame: &mut FrameBuffer<N>,
+        origin: Coordinate,
+        main: MainItem,
+        palette: &Palette,
+    ) {
+        let icon = |shape: TetrominoShape| (Tetromino::from(shape), palette.get_color(shape));
+        match main {
+            MainItem::Play | MainItem::Options => {
+                let (tetromino, color) = match main {
+                    MainItem::Play => icon(TetrominoShape::T),
+                    _ => icon(TetrominoShape::O),
+                };
+                frame.set_mask(tetromino.get_mask(), origin + [1, 0], color);
+            }
+            MainItem::Mode => {
+                let color = palette.get_color(TetrominoShape::I);
+                draw_digit(
+                    frame,
+                    origin,
+                    _mode_index(self.settings.mode) as u8 + 1,
+                    color,
+                );
+            }
+            MainItem::Level => {
+                let color = palette.get_color(TetrominoShape::L);
+                _draw_number(frame, origin, self.settings.start_level as u32, color);
+            }
+            MainItem::Scores => {
+                let best = self
+                    .high_scores
+                    .iter()
+                    .next()
+                    .map_or(0, |entry| entry.score);
+                _draw_number(frame, origin, best, palette.get_color(TetrominoShape::S));
+            }
+            MainItem::Stats => {
+                let color = palette.get_color(TetrominoShape::Z);
+                _draw_number(frame, origin, self.stats.get_dealt(), color);
+            }
+            MainItem::Lifetime => {
+                let color = palette.get_color(TetrominoShape::J);
+                _draw_number(frame, origin, self.lifetime.get_games(), color);
+            }
+        }
+    }
+
+    /// Draw the count of every shape as a bar standing on the bottom of the panel, in the order of `SHAPES`.
+    fn _draw_stats<const N: usize>(&self, frame: &mut FrameBuffer<N>, palette: &Palette) {
+        // Leave a row free above the tallest bar and below all bars.
+        let bottom = frame.get_shape().row.saturating_sub(2);
+        let heights = self.stats.bar_heights(bottom);
+        for (index, (shape, height)) in SHAPES.iter().zip(heights).enumerate() {
+            let col = ITEM_COL + index * (BAR_COLS + 1);
+            for row in bottom + 1 - height..=bottom {
+                for c in 0..BAR_COLS {
+                    frame.set_pixel(Coordinate { row, col: col + c }, palette.get_color(*shape));
+                }
+            }
+        }
+    }
+
+    /// Draw an option as a block, lit while on, or the colors of the pieces for the color scheme.
+    fn _draw_option<const N: usize>(
+        &self,
+        frame: &mut FrameBuffer<N>,
+        origin: Coordinate,
+        option: OptionItem,
+        palette: &Palette,
+    ) {
+        let on = match option {
+            OptionItem::Ghost => self.settings.ghost,
+            OptionItem::Hold => self.settings.hold,
+            OptionItem::Grid => self.settings.grid,
+            OptionItem::DepthFog => self.settings.depth_fog,
+            OptionItem::InputDisplay => self.settings.input_display,
+            OptionItem::Finesse => self.settings.finesse,
+            OptionItem::Colors => {
+                let scheme = self.settings.colors as u8 + 1;
+                draw_digit(frame, origin, scheme, palette.get_color(TetrominoShape::T));
+                return;
+            }
+            OptionItem::Controls => {
+                let tetromino = Tetromino::from(TetrominoShape::J);
+                let color = palette.get_color(TetrominoShape::J);
+                frame.set_mask(tetromino.get_mask(), origin + [1, 0], color);
+                return;
+            }
+        };
+        let color = match on {
+            true => palette.get_color(TetrominoShape::S),
+            false => palette.get_color(TetrominoShape::Z).scaled(OFF_LUM),
+        };
+        for r in 0..GLYPH_ROWS {
+            for c in 0..GLYPH_COLS {
+                frame.set_pixel(origin + [r, c], color);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        MenuOutput, MenuState, Screen, Splash, MAX_START_LEVEL, REMAP_TIMEOUT_TICKS, SPLASH_ROWS,
+    };
+    use crate::board::BoardOps;
+    use crate::cell::Cell;
+    use crate::color::Rgb;
+    use crate::coordinate::Coordinate;
+    use crate::events::GameEvent;
+    use crate::framebuffer::FrameBuffer;
+    use crate::game::Cause;
+    use crate::input::{Action, InputState, ACTIONS};
+    use crate::lifetime::LifetimeStats;
+    use crate::palette::GUIDELINE;
+    use crate::randomizer::RandomizerStats;
+    use crate::settings::{ColorScheme, GameMode, Settings};
+    use crate::tetrominoes::{TetrominoShape, SHAPES};
+
+    /// Press a sequence of actions on consecutive ticks, with a tick without input in between.
+    fn press(menu: &mut MenuState, actions: &[Action]) -> MenuOutput {
+        let mut output = MenuOutput::None;
+        for action in actions {
+            output = menu.tick(&InputState::from_action(*action));
+            if output != MenuOutput::None {
+                return output;
+            }
+            menu.tick(&InputState::default());
+        }
+        output
+    }
+
+    #[test]
+    fn test_splash() {
+        // The pieces stack into a T, a bar of 8 columns on a stem of 2 centered below it.
+        let mut splash = Splash::new();
+        let mut ticks = 0;
+        while !splash.is_done() {
+            splash.tick();
+            ticks += 1;
+        }
+        assert!(ticks < 5 * 60);
+        let filled =
+            |row: usize, col: usize| splash.get_board().get_array()[(row, col)] != Cell::Empty;
+        for col in 12..20 {
+            assert!(filled(SPLASH_ROWS - 10, col) && filled(SPLASH_ROWS - 9, col));
+        }
+        for row in SPLASH_ROWS - 8..SPLASH_ROWS {
+            assert!(filled(row, 15) && filled(row, 16));
+            assert!(!filled(row, 14) && !filled(row, 17));
+        }
+        assert_eq!(
+            splash
+                .get_board()
+                .cells()
+                .filter(|(_, cell)| *cell != Cell::Empty)
+                .count(),
+            32
+        );
+    }
+
+    #[test]
+    fn test_skip_splash() {
+        // Any input skips the splash, and Start starts right away.
+        let mut menu = MenuState::new(Settings::default());
+        assert_eq!(menu.tick(&InputState::default()), MenuOutput::None);
+        assert!(matches!(menu.get_screen(), Screen::Splash(_)));
+        press(&mut menu, &[Action::Left]);
+        assert!(matches!(menu.get_screen(), Screen::Main(0)));
+        let mut menu = MenuState::new(Settings::default());
+        assert_eq!(
+            press(&mut menu, &[Action::Start]),
+            MenuOutput::Start(Settings::default())
+        );
+    }
+
+    #[test]
+    fn test_navigate() {
+        let mut menu = MenuState::new(Settings::default());
+        menu.skip_splash();
+        // Choose the sprint from the mode screen.
+        press(
+            &mut menu,
+            &[
+                Action::SoftDrop,
+                Action::RotateCw,
+                Action::SoftDrop,
+                Action::RotateCw,
+            ],
+        );
+        assert_eq!(menu.get_settings().mode, GameMode::SPRINT);
+        assert!(matches!(menu.get_screen(), Screen::Main(1)));
+        // Start at level 4, clamped at both ends, after backing out of a first try.
+        press(
+            &mut menu,
+            &[
+                Action::SoftDrop,
+                Action::RotateCw,
+                Action::Right,
+                Action::RotateCcw,
+            ],
+        );
+        assert_eq!(menu.get_settings().start_level, 1);
+        press(&mut menu, &[Action::RotateCw, Action::Left, Action::Left]);
+        press(
+            &mut menu,
+            &[
+                Action::Right,
+                Action::Right,
+                Action::Right,
+                Action::RotateCw,
+            ],
+        );
+        assert_eq!(menu.get_settings().start_level, 4);
+        // Turn off the ghost and cycle to the accessible colors, wrapping up from the first option past the controls.
+        press(
+            &mut menu,
+            &[Action::SoftDrop, Action::RotateCw, Action::Right],
+        );
+        press(
+            &mut menu,
+            &[
+                Action::HardDrop,
+                Action::HardDrop,
+                Action::RotateCw,
+                Action::Left,
+                Action::RotateCcw,
+            ],
+        );
+        assert!(!menu.get_settings().ghost);
+        assert_eq!(menu.get_settings().colors, ColorScheme::Accessible);
+        // Peek at the high scores and the statistics, wrapping down past the lifetime totals to the play item and
+        // starting from there.
+        press(
+            &mut menu,
+            &[Action::SoftDrop, Action::RotateCw, Action::RotateCcw],
+        );
+        assert!(matches!(menu.get_screen(), Screen::Main(4)));
+        press(&mut menu, &[Action::SoftDrop, Action::RotateCw]);
+        assert!(matches!(menu.get_screen(), Screen::Stats));
+        press(&mut menu, &[Action::RotateCcw]);
+        assert!(matches!(menu.get_screen(), Screen::Main(5)));
+        let output = press(
+            &mut menu,
+            &[Action::SoftDrop, Action::SoftDrop, Action::RotateCw],
+        );
+        assert_eq!(
+            output,
+            MenuOutput::Start(Settings {
+                mode: GameMode::SPRINT,
+                start_level: 4,
+                ghost: false,
+                colors: ColorScheme::Accessible,
+                ..Settings::default()
+            })
+        );
+        // The level never leaves the range of the gravity table.
+        let mut menu = MenuState::new(Settings::default());
+        menu.skip_splash();
+        press(
+            &mut menu,
+            &[Action::SoftDrop, Action::SoftDrop, Action::RotateCw],
+        );
+        press(&mut menu, &[Action::Right; 20]);
+        press(&mut menu, &[Action::RotateCw]);
+        assert_eq!(menu.get_settings().start_level, MAX_START_LEVEL);
+    }
+
+    #[test]
+    fn test_remap() {
+        // Swap left and right, the first two buttons, from the last option.
+        let mut menu = MenuState::new(Settings::default());
+        menu.skip_splash();
+        press(
+            &mut menu,
+            &[Action::SoftDrop, Action::SoftDrop, Action::SoftDrop],
+        );
+        press(&mut menu, &[Action::RotateCw, Action::HardDrop]);
+        press(&mut menu, &[Action::Left, Action::RotateCw]);
+        assert!(matches!(menu.get_screen(), Screen::Remap { action: 0, .. }));
+        let raw = |input: usize| InputState::default().with_raw_pressed(1 << input);
+        let mut order = [1, 0, 2, 3, 4, 5, 6, 7, 8];
+        for (index, input) in order.iter().enumerate() {
+            assert_eq!(menu.tick(&raw(*input)), MenuOutput::None);
+            // A button that already has an action, or none at all, is ignored.
+            menu.tick(&raw(1));
+            menu.tick(&InputState::default());
+            if index + 1 < ACTIONS.len() {
+                assert!(matches!(
+                    menu.get_screen(),
+                    Screen::Remap { action, .. } if *action == index + 1
+                ));
+            }
+        }
+        // The start button of the old map is taken as a button, not as a start.
+        assert!(matches!(menu.get_screen(), Screen::Options(7)));
+        let keymap = menu.get_settings().keymap;
+        assert_eq!(keymap.get_action(0), Some(Action::Right));
+        assert_eq!(keymap.get_action(1), Some(Action::Left));
+        assert_eq!(keymap.get_action(8), Some(Action::Start));
+        assert_eq!(keymap.validate(), Ok(()));
+        // Without a button for too long the new map is dropped and the old one kept.
+        order.swap(0, 1);
+        press(&mut menu, &[Action::RotateCw]);
+        menu.tick(&raw(order[0]));
+        for _ in 0..REMAP_TIMEOUT_TICKS {
+            menu.tick(&InputState::default());
+        }
+        assert!(matches!(menu.get_screen(), Screen::Options(7)));
+        assert_eq!(menu.get_settings().keymap, keymap);
+    }
+
+    #[test]
+    fn test_render() {
+        // The cursor is drawn left of the selected item, the T piece of the play item right of it.
+        let mut menu = MenuState::new(Settings::default());
+        menu.skip_splash();
+        press(&mut menu, &[Action::SoftDrop]);
+        let mut frame = FrameBuffer::<2048>::new(Coordinate::from_array([64, 32]));
+        menu.render(&mut frame, &GUIDELINE);
+        let at = |frame: &FrameBuffer<2048>, row: usize, col: usize| {
+            frame.get_pixel(Coordinate { row, col }).unwrap()
+        };
+        assert_eq!(at(&frame, 8, 0), Rgb::WHITE);
+        assert_eq!(at(&frame, 1, 0), Rgb::BLACK);
+        assert_eq!(at(&frame, 3, 4), GUIDELINE.get_color(TetrominoShape::T));
+        // The bars of the statistics stand on the bottom, the most dealt shape reaching up to the second row.
+        let mut stats = RandomizerStats::new();
+        for shape in [TetrominoShape::I, TetrominoShape::T, TetrominoShape::I] {
+            stats.record(shape);
+        }
+        menu.set_randomizer_stats(stats);
+        press(
+            &mut menu,
+            &[
+                Action::HardDrop,
+                Action::HardDrop,
+                Action::HardDrop,
+                Action::RotateCw,
+            ],
+        );
+        assert!(matches!(menu.get_screen(), Screen::Stats));
+        menu.render(&mut frame, &GUIDELINE);
+        let (i, t) = (
+            GUIDELINE.get_color(TetrominoShape::I),
+            GUIDELINE.get_color(TetrominoShape::T),
+        );
+        assert_eq!(
+            (at(&frame, 0, 3), at(&frame, 1, 3), at(&frame, 62, 5)),
+            (Rgb::BLACK, i, i)
+        );
+        assert_eq!(
+            (at(&frame, 31, 23), at(&frame, 32, 23), at(&frame, 63, 23)),
+            (Rgb::BLACK, t, Rgb::BLACK)
+        );
+        assert_eq!(at(&frame, 62, 7), Rgb::BLACK);
+        // The lifetime totals are numbers in the colors of the shapes, without a best sprint before the first one.
+        let mut lifetime = LifetimeStats::new();
+        lifetime.fold(
+            &GameEvent::GameOver {
+                cause: Cause::BlockOut,
+                centis: 6000,
+            },
+            GameMode::Marathon,
+        );
+        menu.set_lifetime_stats(lifetime);
+        press(
+            &mut menu,
+            &[Action::RotateCcw, Action::SoftDrop, Action::RotateCw],
+        );
+        assert!(matches!(menu.get_screen(), Screen::Lifetime));
+        menu.render(&mut frame, &GUIDELINE);
+        let lit = |frame: &FrameBuffer<2048>, index: usize, color: Rgb| {
+            let rows = 1 + index * 7..1 + index * 7 + 5;
+            rows.flat_map(|row| (0..32).map(move |col| (row, col)))
+                .filter(|(row, col)| at(frame, *row, *col) == color)
+                .count()
+        };
+        assert!(lit(&frame, 0, GUIDELINE.get_color(SHAPES[0])) > 0);
+        assert!(lit(&frame, 4, GUIDELINE.get_color(SHAPES[4])) > 0);
+        assert_eq!(lit(&frame, 5, Rgb::BLACK), 5 * 32);
+        // The splash is drawn centered on the panel.
+        let mut menu = MenuState::new(Settings::default());
+        for _ in 0..400 {
+            menu.tick(&InputState::default());
+            if !matches!(menu.get_screen(), Screen::Splash(_)) {
+                break;
+            }
+            menu.render(&mut frame, &GUIDELINE);
+        }
+        let bottom = (64 - SPLASH_ROWS) / 2 + SPLASH_ROWS - 1;
+        assert_ne!(at(&frame, bottom, 15), Rgb::BLACK);
+        assert_eq!(at(&frame, bottom, 14), Rgb::BLACK);
+    }
+}
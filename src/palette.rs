@@ -0,0 +1,256 @@
+use crate::color::Rgb;
+use crate::tetrominoes::TetrominoShape;
+
+/// The number of built-in level palettes, cycled through as the level advances.
+pub const LEVEL_PALETTES: usize = 10;
+
+/// The sub-pixel pattern of a cell on a display showing every cell as a 2x2 block or larger.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum CellPattern {
+    /// All sub-pixels lit.
+    #[default]
+    Solid,
+    /// The top-left and bottom-right sub-pixels lit.
+    Checker,
+    /// The top sub-pixels lit.
+    HorizontalStripe,
+    /// The left sub-pixels lit.
+    VerticalStripe,
+}
+
+impl CellPattern {
+    /// Check if a sub-pixel of the pattern is lit, tiling the 2x2 pattern over larger blocks.
+    /// # Arguments
+    /// - `row` - The row of the sub-pixel within the block
+    /// - `col` - The column of the sub-pixel within the block
+    /// # Returns
+    /// - `bool` - Whether the sub-pixel shows the color (`true`) or stays off (`false`)
+    pub fn is_lit(self, row: usize, col: usize) -> bool {
+        match self {
+            CellPattern::Solid => true,
+            CellPattern::Checker => row & 1 == col & 1,
+            CellPattern::HorizontalStripe => row & 1 == 0,
+            CellPattern::VerticalStripe => col & 1 == 0,
+        }
+    }
+}
+
+/// The colors used to draw a game.
+/// # Attributes
+/// - `shapes` - The color of every shape, in the order of `TetrominoShape`
+/// - `patterns` - The pattern of every shape on upscaled displays, in the order of `TetrominoShape`
+/// - `background` - The color of empty cells
+/// - `grid` - The color of every other empty cell, drawing a faint checkerboard when the grid is enabled
+/// - `border` - The color of the border around the board
+/// - `ghost` - The color of the ghost piece, or `None` to draw it as the dimmed piece color
+/// - `garbage` - The color of garbage cells
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Palette {
+    pub shapes: [Rgb; 7],
+    pub patterns: [CellPattern; 7],
+    pub background: Rgb,
+    pub grid: Rgb,
+    pub border: Rgb,
+    pub ghost: Option<Rgb>,
+    pub garbage: Rgb,
+}
+
+/// The guideline colors, matching the color stored in every `Tetromino`.
+pub const GUIDELINE: Palette = Palette {
+    shapes: [
+        _rgb(0, 255, 255),
+        _rgb(0, 0, 255),
+        _rgb(255, 127, 0),
+        _rgb(255, 255, 0),
+        _rgb(0, 255, 0),
+        _rgb(255, 0, 255),
+        _rgb(255, 0, 0),
+    ],
+    patterns: [CellPattern::Solid; 7],
+    background: Rgb::BLACK,
+    grid: _rgb(16, 16, 16),
+    border: _rgb(32, 32, 32),
+    ghost: None,
+    garbage: Rgb::GRAY,
+};
+
+/// The high-contrast colorblind-safe colors of Okabe and Ito, with a pattern for the shapes that are
+/// easily confused by color alone.
+pub const ACCESSIBLE: Palette = Palette {
+    shapes: [
+        _rgb(86, 180, 233),
+        _rgb(0, 114, 178),
+        _rgb(230, 159, 0),
+        _rgb(240, 228, 66),
+        _rgb(0, 158, 115),
+        _rgb(204, 121, 167),
+        _rgb(213, 94, 0),
+    ],
+    patterns: [
+        CellPattern::Solid,
+        CellPattern::HorizontalStripe,
+        CellPattern::VerticalStripe,
+        CellPattern::Solid,
+        CellPattern::Checker,
+        CellPattern::Solid,
+        CellPattern::HorizontalStripe,
+    ],
+    background: Rgb::BLACK,
+    grid: _rgb(16, 16, 16),
+    border: _rgb(32, 32, 32),
+    ghost: Some(_rgb(32, 32, 32)),
+    garbage: Rgb::GRAY,
+};
+
+/// The level palettes, each derived from the 2 colors of a level of classic NES Tetris.
+const LEVELS: [Palette; LEVEL_PALETTES] = [
+    _level(_rgb(0, 88, 248), _rgb(60, 188, 252)),
+    _level(_rgb(0, 168, 0), _rgb(184, 248, 24)),
+    _level(_rgb(216, 0, 204), _rgb(248, 120, 248)),
+    _level(_rgb(0, 88, 248), _rgb(88, 216, 84)),
+    _level(_rgb(228, 0, 88), _rgb(88, 248, 152)),
+    _level(_rgb(88, 248, 152), _rgb(104, 136, 252)),
+    _level(_rgb(248, 56, 0), _rgb(124, 124, 124)),
+    _level(_rgb(104, 68, 252), _rgb(168, 0, 32)),
+    _level(_rgb(0, 88, 248), _rgb(248, 56, 0)),
+    _level(_rgb(248, 56, 0), _rgb(252, 160, 68)),
+];
+
+impl Palette {
+    /// Get the level palette of a level, cycling through the built-in palettes.
+    /// # Arguments
+    /// - `level` - The level
+    /// # Returns
+    /// - `&'static Palette` - A reference to the palette at index `level % LEVEL_PALETTES`
+    pub fn for_level(level: u8) -> &'static Palette {
+        &LEVELS[level as usize % LEVEL_PALETTES]
+    }
+
+    /// Get the color of a shape.
+    /// # Arguments
+    /// - `shape` - The `TetrominoShape`
+    /// # Returns
+    /// - `Rgb` - The color to draw the shape with
+    pub fn get_color(&self, shape: TetrominoShape) -> Rgb {
+        self.shapes[shape as usize]
+    }
+
+    /// Get the pattern of a shape.
+    /// # Arguments
+    /// - `shape` - The `TetrominoShape`
+    /// # Returns
+    /// - `CellPattern` - The pattern to draw the shape with on upscaled displays
+    pub fn get_pattern(&self, shape: TetrominoShape) -> CellPattern {
+        self.patterns[shape as usize]
+    }
+}
+
+/// Build a color in a constant.
+const fn _rgb(r: u8, g: u8, b: u8) -> Rgb {
+    Rgb { r, g, b }
+}
+
+/// Average 2 colors, rounding down.
+const fn _mix(a: Rgb, b: Rgb) -> Rgb {
+    _rgb(
+        ((a.r as u16 + b.r as u16) / 2) as u8,
+        ((a.g as u16 + b.g as u16) / 2) as u8,
+        ((a.b as u16 + b.b as u16) / 2) as u8,
+    )
+}
+
+/// Halve the brightness of a color.
+const fn _half(color: Rgb) -> Rgb {
+    _rgb(color.r / 2, color.g / 2, color.b / 2)
+}
+
+/// Derive a level palette with 7 distinct shape colors from a primary and a secondary color.
+///
+/// J and L use the primary and secondary color, S and Z their shades and I, O and T their tints,
+/// so no 2 shapes share a color.
+const fn _level(primary: Rgb, secondary: Rgb) -> Palette {
+    Palette {
+        shapes: [
+            _mix(primary, Rgb::WHITE),
+            primary,
+            secondary,
+            _mix(secondary, Rgb::WHITE),
+            _half(primary),
+            _mix(primary, secondary),
+            _half(secondary),
+        ],
+        patterns: [CellPattern::Solid; 7],
+        background: Rgb::BLACK,
+        grid: _rgb(16, 16, 16),
+        border: _half(_half(primary)),
+        ghost: Some(_half(Rgb::GRAY)),
+        garbage: Rgb::GRAY,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CellPattern, Palette, ACCESSIBLE, GUIDELINE, LEVEL_PALETTES};
+    use crate::color::{Color, Rgb};
+    use crate::tetrominoes::TetrominoShape;
+
+    const SHAPES: [TetrominoShape; 7] = [
+        TetrominoShape::I,
+        TetrominoShape::J,
+        TetrominoShape::L,
+        TetrominoShape::O,
+        TetrominoShape::S,
+        TetrominoShape::T,
+        TetrominoShape::Z,
+    ];
+
+    #[test]
+    fn test_cycling() {
+        assert_eq!(
+            Palette::for_level(0),
+            Palette::for_level(LEVEL_PALETTES as u8)
+        );
+        assert_eq!(Palette::for_level(3), Palette::for_level(23));
+        assert_ne!(Palette::for_level(1), Palette::for_level(2));
+    }
+
+    #[test]
+    fn test_distinct() {
+        for palette in (0..LEVEL_PALETTES as u8)
+            .map(Palette::for_level)
+            .chain([&GUIDELINE, &ACCESSIBLE])
+        {
+            for (index, shape) in SHAPES.iter().enumerate() {
+                let color = palette.get_color(*shape);
+                assert_ne!(color, palette.background);
+                assert!(SHAPES[..index]
+                    .iter()
+                    .all(|other| palette.get_color(*other) != color));
+            }
+        }
+    }
+
+    #[test]
+    fn test_accessible() {
+        // Every shape is recognizable by its pair of color and pattern.
+        for (index, shape) in SHAPES.iter().enumerate() {
+            let pair = (ACCESSIBLE.get_color(*shape), ACCESSIBLE.get_pattern(*shape));
+            assert!(SHAPES[..index]
+                .iter()
+                .all(
+                    |other| (ACCESSIBLE.get_color(*other), ACCESSIBLE.get_pattern(*other)) != pair
+                ));
+        }
+        assert_eq!(
+            [(0, 0), (0, 1), (1, 0), (1, 1)].map(|(r, c)| CellPattern::Checker.is_lit(r, c)),
+            [true, false, false, true]
+        );
+    }
+
+    #[test]
+    fn test_guideline() {
+        for shape in SHAPES {
+            assert_eq!(GUIDELINE.get_color(shape), Rgb::from(Color::from(shape)));
+        }
+    }
+}
@@ -0,0 +1,438 @@
+use crate::board::BoardOps;
+use crate::coordinate::Coordinate;
+use crate::error::TetrisError;
+use crate::rotation::kicks::{apply_kick, kicks, mask_offset, RotationState};
+use crate::savestate::{shape_from_u8, Persist, Reader, SaveError, Writer};
+use crate::scoring::{ClearEvent, TSpin};
+use crate::tetrominoes::{Tetromino, TetrominoShape};
+use core::fmt;
+
+/// The kick index that upgrades a mini T-spin to a full T-spin, i.e. the last kick of the table.
+const T_SPIN_UPGRADE_KICK: usize = 4;
+
+/// The errors of placing a piece on a board.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PieceError {
+    /// The piece, placed at the coordinate, exceeds the board.
+    OutOfBounds,
+}
+
+impl fmt::Display for PieceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PieceError::OutOfBounds => f.write_str("the piece exceeds the board"),
+        }
+    }
+}
+
+/// The outcome of rotating an `ActivePiece` with wall kicks, e.g. for different feedback on a kick.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RotationResult {
+    /// The piece rotated, moved by the offset at `kick_index` in the kick table, 0 for a rotation in place.
+    Rotated { kick_index: usize },
+    /// No kick fits, the piece kept its rotation and position.
+    Failed,
+}
+
+impl RotationResult {
+    /// Check if the piece rotated.
+    /// # Returns
+    /// - `bool` - Whether (`true`) or not (`false`) any kick fit
+    pub fn is_rotated(self) -> bool {
+        matches!(self, RotationResult::Rotated { .. })
+    }
+
+    /// Check if the piece needed a wall kick to rotate.
+    /// # Returns
+    /// - `bool` - Whether (`true`) or not (`false`) the piece rotated with another kick than the first
+    pub fn is_kicked(self) -> bool {
+        matches!(self, RotationResult::Rotated { kick_index } if kick_index > 0)
+    }
+}
+
+/// The last successful movement of an `ActivePiece`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Movement {
+    Spawn,
+    Shift,
+    Drop,
+    Rotate,
+}
+
+#[derive(Clone, Debug)]
+pub struct ActivePiece {
+    /// The tetromino currently controlled by the player.
+    /// # Attributes
+    /// - `tetromino` - The `Tetromino` with its current rotation
+    /// - `coord` - The position of the top-left element of the tetromino mask on the board
+    /// - `last_movement` - The last movement that succeeded, needed for T-spin detection
+    /// - `kick_index` - The index in the kick table used by the last successful rotation
+    tetromino: Tetromino<bool>,
+    coord: Coordinate,
+    last_movement: Movement,
+    kick_index: Option<usize>,
+}
+
+impl ActivePiece {
+    /// Create a new `ActivePiece` at some position.
+    /// # Arguments
+    /// - `tetromino` - The `Tetromino` to control
+    /// - `coord` - The position of the top-left element of the tetromino mask on the board
+    /// # Returns
+    /// - `ActivePiece` - The active piece, with `Movement::Spawn` as its last movement
+    pub fn new(tetromino: Tetromino<bool>, coord: Coordinate) -> Self {
+        ActivePiece {
+            tetromino,
+            coord,
+            last_movement: Movement::Spawn,
+            kick_index: None,
+        }
+    }
+
+    /// Get a reference to the controlled tetromino.
+    /// # Returns
+    /// - `&Tetromino<bool>` - A reference to the tetromino
+    pub fn get_tetromino(&self) -> &Tetromino<bool> {
+        &self.tetromino
+    }
+
+    /// Get the position of the piece.
+    /// # Returns
+    /// - `Coordinate` - The position of the top-left element of the tetromino mask on the board
+    pub fn get_coord(&self) -> Coordinate {
+        self.coord
+    }
+
+    /// Get the cells covered by the piece.
+    /// # Returns
+    /// - `[Coordinate; 4]` - The positions of the cells on the board, row after row
+    pub fn get_cells(&self) -> [Coordinate; 4] {
+        let mask = self.tetromino.get_mask();
+        let covered = mask
+            .elements_row_major_iter()
+            .enumerate()
+            .filter(|(_, lit)| **lit);
+        let mut cells = [self.coord; 4];
+        for (cell, (index, _)) in cells.iter_mut().zip(covered) {
+            *cell = self.coord + [index / mask.num_columns(), index % mask.num_columns()];
+        }
+        cells
+    }
+
+    /// Get the last movement that succeeded.
+    /// # Returns
+    /// - `Movement` - The last successful movement
+    pub fn get_last_movement(&self) -> Movement {
+        self.last_movement
+    }
+
+    /// Get the kick used by the last successful rotation.
+    /// # Returns
+    /// - `Option<usize>` - The index in the kick table, or `None` if the piece was never rotated
+    pub fn get_kick_index(&self) -> Option<usize> {
+        self.kick_index
+    }
+
+    /// Check if the piece fits on the board at its current position.
+    /// # Arguments
+    /// - `board` - A reference to the `Board` object
+    /// # Returns
+    /// - `bool` - Whether (`true`) or not (`false`) the piece is within bounds and overlaps no filled cell
+    pub fn fits<T: Copy + PartialEq, B: BoardOps<T>>(&self, board: &B) -> bool {
+        board.fits(self.tetromino.get_mask(), self.coord)
+    }
+
+    /// Check if the piece rests on the stack or the bottom of the board, i.e. cannot drop another row.
+    /// # Arguments
+    /// - `board` - A reference to the `Board` object
+    /// # Returns
+    /// - `bool` - Whether (`true`) or not (`false`) the piece is grounded
+    pub fn is_grounded<T: Copy + PartialEq, B: BoardOps<T>>(&self, board: &B) -> bool {
+        !board.fits(self.tetromino.get_mask(), self.coord + [1, 0])
+    }
+
+    /// Get the position the piece would land at when hard dropped, used to draw the ghost piece.
+    /// # Arguments
+    /// - `board` - A reference to the `Board` object
+    /// # Returns
+    /// - `Coordinate` - The position of the top-left element of the tetromino mask after dropping
+    pub fn ghost_coord<T: Copy + PartialEq, B: BoardOps<T>>(&self, board: &B) -> Coordinate {
+        let mut coord = self.coord;
+        while board.fits(self.tetromino.get_mask(), coord + [1, 0]) {
+            coord = coord + [1, 0];
+        }
+        coord
+    }
+
+    /// Move the piece one column to the left if it fits there.
+    /// # Arguments
+    /// - `board` - A reference to the `Board` object
+    /// # Returns
+    /// - `bool` - Whether (`true`) or not (`false`) the piece moved
+    pub fn try_shift_left<T: Copy + PartialEq, B: BoardOps<T>>(&mut self, board: &B) -> bool {
+        self._try_move(board, [0, -1], Movement::Shift)
+    }
+
+    /// Move the piece one column to the right if it fits there.
+    /// # Arguments
+    /// - `board` - A reference to the `Board` object
+    /// # Returns
+    /// - `bool` - Whether (`true`) or not (`false`) the piece moved
+    pub fn try_shift_right<T: Copy + PartialEq, B: BoardOps<T>>(&mut self, board: &B) -> bool {
+        self._try_move(board, [0, 1], Movement::Shift)
+    }
+
+    /// Move the piece one row down if it fits there.
+    /// # Arguments
+    /// - `board` - A reference to the `Board` object
+    /// # Returns
+    /// - `bool` - Whether (`true`) or not (`false`) the piece moved
+    pub fn try_drop<T: Copy + PartialEq, B: BoardOps<T>>(&mut self, board: &B) -> bool {
+        self._try_move(board, [1, 0], Movement::Drop)
+    }
+
+    /// Rotate the piece 90 degrees clockwise, trying the wall kicks in order until one fits.
+    /// # Arguments
+    /// - `board` - A reference to the `Board` object
+    /// # Returns
+    /// - `RotationResult` - The index of the kick that succeeded, or `RotationResult::Failed`
+    pub fn try_rotate_cw_with_kicks<T: Copy + PartialEq, B: BoardOps<T>>(
+        &mut self,
+        board: &B,
+    ) -> RotationResult {
+        self._try_rotate_with_kicks(board, true)
+    }
+
+    /// Rotate the piece 90 degrees counterclockwise, trying the wall kicks in order until one fits.
+    /// # Arguments
+    /// - `board` - A reference to the `Board` object
+    /// # Returns
+    /// - `RotationResult` - The index of the kick that succeeded, or `RotationResult::Failed`
+    pub fn try_rotate_ccw_with_kicks<T: Copy + PartialEq, B: BoardOps<T>>(
+        &mut self,
+        board: &B,
+    ) -> RotationResult {
+        self._try_rotate_with_kicks(board, false)
+    }
+
+    /// Detect whether the piece would lock as a T-spin, using the 3-corner rule.
+    ///
+    /// A T piece whose last movement was a rotation is a T-spin when at least three of the four diagonal
+    /// corners of its 3x3 rotation box are filled or out of bounds. It is a mini T-spin unless both corners
+    /// next to the point of the T are filled, or the rotation used the last kick of the table.
+    /// # Arguments
+    /// - `board` - A reference to the `Board` object
+    /// # Returns
+    /// - `TSpin` - The kind of T-spin
+    pub fn t_spin<T: Copy + PartialEq, B: BoardOps<T>>(&self, board: &B) -> TSpin {
+        if self.tetromino.shape != TetrominoShape::T || self.last_movement != Movement::Rotate {
+            return TSpin::None;
+        }
+        let state = self.tetromino.get_rotation();
+        let center = self.coord + [1, 1] - mask_offset(&self.tetromino.shape, state);
+        let origin = Coordinate::from_array([0, 0]);
+        let is_occupied = |offset: [isize; 2]| match apply_kick(center, offset) {
+            Some(coord) if coord.is_within_bounds(origin, board.get_coords()) => {
+                board.is_filled(coord)
+            }
+            _ => true,
+        };
+        // Corners as [top-left, top-right, bottom-right, bottom-left].
+        let corners = [[-1, -1], [-1, 1], [1, 1], [1, -1]].map(is_occupied);
+        if corners.iter().filter(|&&c| c).count() < 3 {
+            return TSpin::None;
+        }
+        let front = match state {
+            RotationState::Spawn => [corners[0], corners[1]],
+            RotationState::Right => [corners[1], corners[2]],
+            RotationState::Two => [corners[2], corners[3]],
+            RotationState::Left => [corners[3], corners[0]],
+        };
+        match (front[0] && front[1]) || self.kick_index == Some(T_SPIN_UPGRADE_KICK) {
+            true => TSpin::Full,
+            false => TSpin::Mini,
+        }
+    }
+
+    /// Lock the piece on the board and clear the rows it completed.
+    /// # Arguments
+    /// - `board` - A muteable reference to the `Board` object
+    /// - `value` - The value to write in the cells covered by the piece
+    /// # Returns
+    /// - `ClearEvent` - The number of cleared rows and the kind of T-spin
+    pub fn lock<T: Copy + PartialEq, B: BoardOps<T>>(&self, board: &mut B, value: T) -> ClearEvent {
+        let t_spin = self.place(board, value);
+        ClearEvent {
+            lines: board.clear_full_rows(),
+            t_spin,
+        }
+    }
+
+    /// Write the piece into the board, leaving the rows it completed in place.
+    /// # Arguments
+    /// - `board` - A muteable reference to the `Board` object
+    /// - `value` - The value to write in the cells covered by the piece
+    /// # Returns
+    /// - `TSpin` - The kind of T-spin, detected before the piece was written
+    pub fn place<T: Copy + PartialEq, B: BoardOps<T>>(&self, board: &mut B, value: T) -> TSpin {
+        let t_spin = self.t_spin(board);
+        board.fill_mask(self.tetromino.get_mask(), self.coord, value);
+        t_spin
+    }
+
+    /// Backend for the `.try_shift_*()` and `.try_drop()` convenience methods.
+    fn _try_move<T: Copy + PartialEq, B: BoardOps<T>>(
+        &mut self,
+        board: &B,
+        offset: [isize; 2],
+        movement: Movement,
+    ) -> bool {
+        match apply_kick(self.coord, offset) {
+            Some(coord) if board.fits(self.tetromino.get_mask(), coord) => {
+                self.coord = coord;
+                self.last_movement = movement;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Backend for the `.try_rotate_cw_with_kicks()` and `.try_rotate_ccw_with_kicks()` convenience methods.
+    fn _try_rotate_with_kicks<T: Copy + PartialEq, B: BoardOps<T>>(
+        &mut self,
+        board: &B,
+        clockwise: bool,
+    ) -> RotationResult {
+        let from = self.tetromino.get_rotation();
+        match clockwise {
+            true => self.tetromino.rotate_cw(),
+            false => self.tetromino.rotate_ccw(),
+        }
+        let to = self.tetromino.get_rotation();
+        // Keep the rotation box in place while swapping the trimmed masks.
+        let from_offset = mask_offset(&self.tetromino.shape, from);
+        let to_offset = mask_offset(&self.tetromino.shape, to);
+        let base = [
+            to_offset[0] as isize - from_offset[0] as isize,
+            to_offset[1] as isize - from_offset[1] as isize,
+        ];
+        for (index, kick) in kicks(&self.tetromino.shape, from, to).iter().enumerate() {
+            if let Some(coord) = apply_kick(self.coord, [base[0] + kick[0], base[1] + kick[1]]) {
+                if board.fits(self.tetromino.get_mask(), coord) {
+                    self.coord = coord;
+                    self.last_movement = Movement::Rotate;
+                    // The T-spin detection reads the kick of the same result the caller gets.
+                    self.kick_index = Some(index);
+                    return RotationResult::Rotated { kick_index: index };
+                }
+            }
+        }
+        // No kick fits, undo the rotation.
+        match clockwise {
+            true => self.tetromino.rotate_ccw(),
+            false => self.tetromino.rotate_cw(),
+        }
+        RotationResult::Failed
+    }
+}
+
+impl Persist for ActivePiece {
+    fn save(&self, out: &mut Writer) {
+        out.u8(self.tetromino.shape as u8);
+        out.u8(self.tetromino.get_rotation() as u8);
+        out.index(self.coord.row);
+        out.index(self.coord.col);
+        out.u8(self.last_movement as u8);
+        out.option_u8(self.kick_index.map(|index| index as u8));
+    }
+
+    fn restore(&mut self, input: &mut Reader) -> Result<(), TetrisError> {
+        let mut tetromino = Tetromino::from(shape_from_u8(input.u8()?)?);
+        for _ in 0..input.u8()?.min(3) {
+            tetromino.rotate_cw();
+        }
+        self.tetromino = tetromino;
+        self.coord = Coordinate {
+            row: input.index()?,
+            col: input.index()?,
+        };
+        self.last_movement = match input.u8()? {
+            0 => Movement::Spawn,
+            1 => Movement::Shift,
+            2 => Movement::Drop,
+            3 => Movement::Rotate,
+            _ => return Err(SaveError::Invalid.into()),
+        };
+        self.kick_index = input.option_u8()?.map(usize::from);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ActivePiece, RotationResult};
+    use crate::board::Board;
+    use crate::coordinate::Coordinate;
+    use crate::scoring::{ClearEvent, TSpin};
+    use crate::tetrominoes::{Tetromino, TetrominoShape};
+
+    #[test]
+    fn test_t_spin_gravity() {
+        // Create board:
+        //   0 1 2 3
+        // 0 o . . .
+        // 1 o o . .
+        // 2 o . . .
+        // 3 . . . .
+        // 4 . x x x
+        // Drop the T in its right state into the corner, which is not a T-spin.
+        let mut board = Board::new(Coordinate::from_array([5, 4]), false);
+        board
+            .set_value(
+                true,
+                Coordinate::from_array([4, 1]),
+                Coordinate::from_array([1, 3]),
+            )
+            .unwrap();
+        let mut tetromino = Tetromino::from(TetrominoShape::T);
+        tetromino.rotate_cw();
+        let mut piece = ActivePiece::new(tetromino, Coordinate::from_array([0, 0]));
+        while piece.try_drop(&board) {}
+        assert_eq!(piece.get_coord(), Coordinate::from_array([2, 0]));
+        assert_eq!(piece.t_spin(&board), TSpin::None);
+        assert_eq!(
+            piece.lock(&mut board, true),
+            ClearEvent {
+                lines: 1,
+                t_spin: TSpin::None
+            }
+        );
+    }
+
+    #[test]
+    fn test_t_spin_mini() {
+        // Create board with a T in its spawn state:
+        //   0 1 2 3
+        // 0 . . . .
+        // 1 . . . .
+        // 2 . o . .
+        // 3 o o o .
+        // 4 . x x x
+        // Rotate clockwise, which kicks one column to the left into the corner as a mini T-spin.
+        let mut board = Board::new(Coordinate::from_array([5, 4]), false);
+        board
+            .set_value(
+                true,
+                Coordinate::from_array([4, 1]),
+                Coordinate::from_array([1, 3]),
+            )
+            .unwrap();
+        let tetromino = Tetromino::from(TetrominoShape::T);
+        let mut piece = ActivePiece::new(tetromino, Coordinate::from_array([2, 0]));
+        let result = piece.try_rotate_cw_with_kicks(&board);
+        assert_eq!(result, RotationResult::Rotated { kick_index: 1 });
+        assert!(result.is_kicked());
+        assert_eq!(piece.get_coord(), Coordinate::from_array([2, 0]));
+        assert_eq!(piece.t_spin(&board), TSpin::Mini);
+    }
+}
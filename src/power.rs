@@ -0,0 +1,252 @@
+//! Power saving, dimming the panel and then putting the game to sleep after a while without input.
+//!
+//! The `Inactivity` tracker counts the ticks since the last input. After `Settings::dim_ticks` it ramps the
+//! brightness down to `Settings::dim_level`, after `Settings::sleep_ticks` down to black while the game sleeps.
+//! Any input wakes it at once at full brightness. The ramps take `RAMP_TICKS` ticks from full brightness to black,
+//! about a second, and are applied to the rendered frame with `Rgb::scaled()`.
+use crate::error::TetrisError;
+use crate::savestate::{Persist, Reader, Writer};
+use crate::settings::Settings;
+
+/// The number of ticks a ramp takes from full brightness to black.
+pub const RAMP_TICKS: u32 = 60;
+
+/// The change of the brightness level per tick of a ramp, large enough that a full ramp takes at most `RAMP_TICKS`.
+const RAMP_STEP: u8 = (u8::MAX as u32 / RAMP_TICKS + 1) as u8;
+
+/// How much power the panel is allowed to draw.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Power {
+    /// At the brightness of the settings.
+    #[default]
+    Awake,
+    /// Dimmed to `Settings::dim_level`.
+    Dimmed,
+    /// Black, with the game asleep.
+    Asleep,
+}
+
+/// Move a brightness level toward a target by at most a step.
+/// # Arguments
+/// - `level` - The current level
+/// - `target` - The level to ramp to
+/// - `step` - The largest change of the level
+/// # Returns
+/// - `u8` - The next level of the ramp, the target once it is within a step
+pub fn ramp_toward(level: u8, target: u8, step: u8) -> u8 {
+    match level < target {
+        true => level.saturating_add(step).min(target),
+        false => level.saturating_sub(step).max(target),
+    }
+}
+
+/// The ticks since the last input, and the brightness level ramping with them.
+/// # Attributes
+/// - `idle` - The number of ticks since the last input
+/// - `level` - The brightness of the rendered frame, from 0 (black) to 255 (the brightness of the settings)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Inactivity {
+    idle: u32,
+    level: u8,
+}
+
+impl Inactivity {
+    /// Create a tracker that just saw an input.
+    /// # Returns
+    /// - `Inactivity` - The tracker at full brightness
+    pub fn new() -> Self {
+        Inactivity {
+            idle: 0,
+            level: u8::MAX,
+        }
+    }
+
+    /// Get the number of ticks since the last input.
+    pub fn get_idle(&self) -> u32 {
+        self.idle
+    }
+
+    /// Get the brightness to scale the rendered frame with, see `Rgb::scaled()`.
+    pub fn get_level(&self) -> u8 {
+        self.level
+    }
+
+    /// Get the power the panel is allowed to draw after the ticks without input.
+    /// # Arguments
+    /// - `settings` - A reference to the `Settings` holding the thresholds, 0 disabling a threshold
+    /// # Returns
+    /// - `Power` - The power after the current number of idle ticks
+    pub fn get_power(&self, settings: &Settings) -> Power {
+        let after = |ticks: u32| ticks > 0 && self.idle >= ticks;
+        match (after(settings.dim_ticks), after(settings.sleep_ticks)) {
+            (_, true) => Power::Asleep,
+            (true, false) => Power::Dimmed,
+            (false, false) => Power::Awake,
+        }
+    }
+
+    /// Count a tick, waking on any input and ramping the brightness toward the power otherwise.
+    /// # Arguments
+    /// - `active` - Whether (`true`) or not (`false`) any input was held this tick
+    /// - `settings` - A reference to the `Settings` holding the thresholds and the dim level
+    /// # Returns
+    /// - `Power` - The power after the tick
+    pub fn tick(&mut self, active: bool, settings: &Settings) -> Power {
+        if active {
+            *self = Inactivity::new();
+            return Power::Awake;
+        }
+        self.idle = self.idle.saturating_add(1);
+        let power = self.get_power(settings);
+        let target = match power {
+            Power::Awake => u8::MAX,
+            Power::Dimmed => settings.dim_level,
+            Power::Asleep => 0,
+        };
+        self.level = ramp_toward(self.level, target, RAMP_STEP);
+        power
+    }
+}
+
+impl Default for Inactivity {
+    fn default() -> Self {
+        Inactivity::new()
+    }
+}
+
+#[cfg(feature = "rp2040")]
+pub use doze::Doze;
+
+#[cfg(feature = "rp2040")]
+mod doze {
+    use rp2040_hal::fugit::ExtU32;
+    use rp2040_hal::pac::{Interrupt, NVIC};
+    use rp2040_hal::timer::{Alarm, Alarm0};
+    use rp2040_hal::Timer;
+
+    /// Waiting for the next tick with the core halted, instead of spinning, while the game sleeps.
+    ///
+    /// The alarm raises its interrupt without it being enabled in the NVIC. With `SEVONPEND` the pending interrupt
+    /// still wakes the core from `WFE`, so no interrupt handler is needed.
+    /// # Attributes
+    /// - `alarm` - The timer alarm waking the core
+    pub struct Doze {
+        alarm: Alarm0,
+    }
+
+    impl Doze {
+        /// Take an alarm to wake the core with.
+        /// # Arguments
+        /// - `alarm` - The `Alarm0` of the timer
+        /// # Returns
+        /// - `Doze` - The doze, waking on pending interrupts
+        pub fn new(mut alarm: Alarm0) -> Self {
+            alarm.enable_interrupt();
+            // SAFETY: only sets the SEVONPEND bit of the system control register of this core.
+            unsafe {
+                (*cortex_m::peripheral::SCB::PTR)
+                    .scr
+                    .modify(|scr| scr | 1 << 4)
+            };
+            Doze { alarm }
+        }
+
+        /// Halt the core until a deadline, e.g. the next tick of the `FixedTimestep`.
+        /// # Arguments
+        /// - `timer` - A reference to the `Timer` the deadline is measured with
+        /// - `deadline` - The time to wake at in microseconds, returning at once if it passed
+        pub fn until(&mut self, timer: &Timer, deadline: u64) {
+            let now = timer.get_counter().ticks();
+            if deadline <= now {
+                return;
+            }
+            let micros = (deadline - now).min(u32::MAX as u64) as u32;
+            if self.alarm.schedule(micros.micros()).is_err() {
+                return;
+            }
+            while !self.alarm.finished() {
+                cortex_m::asm::wfe();
+            }
+            self.alarm.clear_interrupt();
+            NVIC::unpend(Interrupt::TIMER_IRQ_0);
+        }
+    }
+}
+
+impl Persist for Inactivity {
+    fn save(&self, out: &mut Writer) {
+        out.u32(self.idle);
+        out.u8(self.level);
+    }
+
+    fn restore(&mut self, input: &mut Reader) -> Result<(), TetrisError> {
+        self.idle = input.u32()?;
+        self.level = input.u8()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ramp_toward, Inactivity, Power, RAMP_TICKS};
+    use crate::settings::Settings;
+
+    #[test]
+    fn test_ramp_toward() {
+        assert_eq!(ramp_toward(255, 64, 5), 250);
+        assert_eq!(ramp_toward(66, 64, 5), 64);
+        assert_eq!(ramp_toward(62, 64, 5), 64);
+        assert_eq!(ramp_toward(3, 0, 5), 0);
+        assert_eq!(ramp_toward(253, 255, 5), 255);
+        assert_eq!(ramp_toward(64, 64, 5), 64);
+    }
+
+    #[test]
+    fn test_inactivity() {
+        let settings = Settings {
+            dim_ticks: 100,
+            dim_level: 64,
+            sleep_ticks: 300,
+            ..Default::default()
+        };
+        let mut inactivity = Inactivity::new();
+        for _ in 0..99 {
+            assert_eq!(inactivity.tick(false, &settings), Power::Awake);
+        }
+        assert_eq!(inactivity.get_level(), 255);
+        // The brightness ramps down to the dim level within a second.
+        assert_eq!(inactivity.tick(false, &settings), Power::Dimmed);
+        assert!(inactivity.get_level() < 255);
+        for _ in 0..RAMP_TICKS {
+            inactivity.tick(false, &settings);
+        }
+        assert_eq!(inactivity.get_level(), 64);
+        // Then down to black once asleep.
+        while inactivity.get_idle() < 299 {
+            assert_eq!(inactivity.tick(false, &settings), Power::Dimmed);
+        }
+        assert_eq!(inactivity.tick(false, &settings), Power::Asleep);
+        for _ in 0..RAMP_TICKS {
+            inactivity.tick(false, &settings);
+        }
+        assert_eq!(inactivity.get_level(), 0);
+        // Any input wakes at full brightness.
+        assert_eq!(inactivity.tick(true, &settings), Power::Awake);
+        assert_eq!(inactivity, Inactivity::new());
+        // A threshold of 0 never triggers.
+        let never = Settings {
+            dim_ticks: 0,
+            sleep_ticks: 0,
+            ..settings
+        };
+        assert_eq!(
+            Inactivity {
+                idle: u32::MAX,
+                level: 255
+            }
+            .get_power(&never),
+            Power::Awake
+        );
+    }
+}
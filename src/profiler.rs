@@ -0,0 +1,348 @@
+//! A lightweight profiler of the main loop, measuring how much of the tick budget every phase of a frame takes.
+//!
+//! The main loop brackets every phase with `Profiler::enter()` and `Profiler::exit()`, and calls
+//! `Profiler::end_frame()` once per iteration. The time of every phase is summed per frame, a phase may run several
+//! times when catching up on ticks, and kept over the last `WINDOW` frames for the maximum and the average. The
+//! debug console prints them with its `prof` command.
+//!
+//! The time is read from any `Clock`, the timer on the RP2040, which has no cycle counter, or `std::time::Instant`
+//! on the host. Without the `profiling` feature, the `Profiler` is empty and all its methods do nothing, so the calls
+//! can stay in the main loop of release builds without costing anything.
+use crate::loop_timing::Clock;
+use core::fmt;
+
+/// The number of frames the statistics are kept over.
+pub const WINDOW: usize = 64;
+
+/// A phase of a frame of the main loop.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Phase {
+    /// Reading the buttons.
+    Input,
+    /// Ticking the game.
+    Logic,
+    /// Rendering the frame.
+    Render,
+    /// Handing the frame to the LEDs.
+    Flush,
+}
+
+/// The phases in the order they are reported.
+pub const PHASES: [Phase; 4] = [Phase::Input, Phase::Logic, Phase::Render, Phase::Flush];
+
+impl Phase {
+    /// Get the name of the phase, as printed in the report.
+    pub fn name(self) -> &'static str {
+        match self {
+            Phase::Input => "input",
+            Phase::Logic => "logic",
+            Phase::Render => "render",
+            Phase::Flush => "flush",
+        }
+    }
+}
+
+/// The durations of the last `WINDOW` frames of a phase, in microseconds.
+/// # Attributes
+/// - `samples` - The durations, in a ring
+/// - `len` - The number of durations in use, up to `WINDOW`
+/// - `next` - The index of the next duration to overwrite
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rolling {
+    samples: [u32; WINDOW],
+    len: usize,
+    next: usize,
+}
+
+impl Rolling {
+    /// Create a window without any durations.
+    pub const fn new() -> Self {
+        Rolling {
+            samples: [0; WINDOW],
+            len: 0,
+            next: 0,
+        }
+    }
+
+    /// Add the duration of a frame, replacing the oldest one once the window is full.
+    /// # Arguments
+    /// - `micros` - The duration in microseconds
+    pub fn push(&mut self, micros: u32) {
+        self.samples[self.next] = micros;
+        self.next = (self.next + 1) % WINDOW;
+        self.len = (self.len + 1).min(WINDOW);
+    }
+
+    /// Get the number of frames in the window.
+    pub fn get_len(&self) -> usize {
+        self.len
+    }
+
+    /// Get the longest duration in the window, 0 without any.
+    pub fn get_max(&self) -> u32 {
+        self.samples[..self.len].iter().copied().max().unwrap_or(0)
+    }
+
+    /// Get the average duration in the window rounded down, 0 without any.
+    pub fn get_avg(&self) -> u32 {
+        match self.len {
+            0 => 0,
+            len => {
+                (self.samples[..len]
+                    .iter()
+                    .map(|micros| *micros as u64)
+                    .sum::<u64>()
+                    / len as u64) as u32
+            }
+        }
+    }
+}
+
+impl Default for Rolling {
+    fn default() -> Self {
+        Rolling::new()
+    }
+}
+
+/// Anything that can describe where the time of a frame goes, e.g. for the debug console.
+pub trait Report {
+    /// Write the report, a line per phase separated by `\n` without a trailing line ending.
+    /// # Arguments
+    /// - `out` - A muteable reference to the writer
+    /// # Returns
+    /// - `fmt::Result` - The error of the writer, if any
+    fn report_into(&self, out: &mut dyn fmt::Write) -> fmt::Result;
+}
+
+#[cfg(feature = "profiling")]
+pub use enabled::Profiler;
+
+#[cfg(not(feature = "profiling"))]
+pub use disabled::Profiler;
+
+#[cfg(feature = "profiling")]
+mod enabled {
+    use super::{Clock, Phase, Report, Rolling, PHASES};
+    use core::fmt;
+
+    /// The profiler of the main loop.
+    /// # Attributes
+    /// - `clock` - The clock measuring the phases
+    /// - `entered` - The time every phase was entered at, while it runs
+    /// - `frame` - The time spent in every phase during the current frame
+    /// - `stats` - The time spent in every phase during the last frames
+    #[derive(Debug)]
+    pub struct Profiler<C> {
+        clock: C,
+        entered: [Option<u64>; PHASES.len()],
+        frame: [u32; PHASES.len()],
+        stats: [Rolling; PHASES.len()],
+    }
+
+    impl<C: Clock> Profiler<C> {
+        /// Create a profiler without any measured frames.
+        /// # Arguments
+        /// - `clock` - The `Clock` to measure the phases with
+        /// # Returns
+        /// - `Profiler<C>` - The profiler
+        pub fn new(clock: C) -> Self {
+            Profiler {
+                clock,
+                entered: [None; PHASES.len()],
+                frame: [0; PHASES.len()],
+                stats: [Rolling::new(); PHASES.len()],
+            }
+        }
+
+        /// Start measuring a phase.
+        /// # Arguments
+        /// - `phase` - The `Phase` the main loop enters
+        pub fn enter(&mut self, phase: Phase) {
+            self.entered[phase as usize] = Some(self.clock.now_micros());
+        }
+
+        /// Stop measuring a phase, adding its time to the current frame. Without a matching enter, nothing is added.
+        /// The time of a phase per frame saturates at `u32::MAX` microseconds, over an hour.
+        /// # Arguments
+        /// - `phase` - The `Phase` the main loop exits
+        pub fn exit(&mut self, phase: Phase) {
+            if let Some(start) = self.entered[phase as usize].take() {
+                let micros = self.clock.now_micros().saturating_sub(start);
+                let total = &mut self.frame[phase as usize];
+                *total = total.saturating_add(micros.min(u32::MAX as u64) as u32);
+            }
+        }
+
+        /// Finish the current frame, adding the time of every phase to the statistics.
+        pub fn end_frame(&mut self) {
+            for (stats, micros) in self.stats.iter_mut().zip(self.frame.iter_mut()) {
+                stats.push(*micros);
+                *micros = 0;
+            }
+        }
+
+        /// Get the statistics of a phase.
+        /// # Arguments
+        /// - `phase` - The `Phase`
+        /// # Returns
+        /// - `&Rolling` - A reference to the time spent in the phase during the last frames
+        pub fn get_stats(&self, phase: Phase) -> &Rolling {
+            &self.stats[phase as usize]
+        }
+    }
+
+    impl<C: Clock> Report for Profiler<C> {
+        fn report_into(&self, out: &mut dyn fmt::Write) -> fmt::Result {
+            for (index, phase) in PHASES.iter().enumerate() {
+                if index > 0 {
+                    out.write_char('\n')?;
+                }
+                let stats = self.get_stats(*phase);
+                write!(
+                    out,
+                    "{} avg {} max {} us over {} frames",
+                    phase.name(),
+                    stats.get_avg(),
+                    stats.get_max(),
+                    stats.get_len()
+                )?;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(not(feature = "profiling"))]
+mod disabled {
+    use super::{Clock, Phase, Report};
+    use core::fmt;
+    use core::marker::PhantomData;
+
+    /// The profiler of the main loop, empty without the `profiling` feature.
+    #[derive(Debug)]
+    pub struct Profiler<C> {
+        clock: PhantomData<C>,
+    }
+
+    impl<C: Clock> Profiler<C> {
+        /// Create a profiler that measures nothing, dropping the clock.
+        /// # Arguments
+        /// - `clock` - The `Clock` the enabled profiler would measure the phases with
+        /// # Returns
+        /// - `Profiler<C>` - The empty profiler
+        pub fn new(clock: C) -> Self {
+            drop(clock);
+            Profiler { clock: PhantomData }
+        }
+
+        /// Do nothing, see the enabled `Profiler::enter()`.
+        #[inline(always)]
+        pub fn enter(&mut self, _phase: Phase) {}
+
+        /// Do nothing, see the enabled `Profiler::exit()`.
+        #[inline(always)]
+        pub fn exit(&mut self, _phase: Phase) {}
+
+        /// Do nothing, see the enabled `Profiler::end_frame()`.
+        #[inline(always)]
+        pub fn end_frame(&mut self) {}
+    }
+
+    impl<C: Clock> Report for Profiler<C> {
+        fn report_into(&self, out: &mut dyn fmt::Write) -> fmt::Result {
+            out.write_str("profiling disabled, build with the profiling feature")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Phase, Profiler, Report, Rolling, WINDOW};
+    use std::cell::Cell;
+    use std::string::String;
+
+    #[test]
+    fn test_rolling() {
+        let mut rolling = Rolling::new();
+        assert_eq!((rolling.get_avg(), rolling.get_max()), (0, 0));
+        for micros in [10, 30, 20] {
+            rolling.push(micros);
+        }
+        assert_eq!(
+            (rolling.get_avg(), rolling.get_max(), rolling.get_len()),
+            (20, 30, 3)
+        );
+        // Once full, the oldest frames drop out, including the maximum.
+        for _ in 0..WINDOW - 1 {
+            rolling.push(4);
+        }
+        assert_eq!(rolling.get_len(), WINDOW);
+        assert_eq!(rolling.get_max(), 20);
+        rolling.push(4);
+        assert_eq!((rolling.get_avg(), rolling.get_max()), (4, 4));
+    }
+
+    #[cfg(feature = "profiling")]
+    #[test]
+    fn test_profiler() {
+        // A fake clock advancing by a fixed step on every read.
+        let now = Cell::new(0);
+        let step = Cell::new(100);
+        let clock = || {
+            now.set(now.get() + step.get());
+            now.get()
+        };
+        let mut profiler = Profiler::new(clock);
+        // Logic runs twice in the first frame, catching up, and its time is summed.
+        profiler.enter(Phase::Logic);
+        profiler.exit(Phase::Logic);
+        profiler.enter(Phase::Logic);
+        profiler.exit(Phase::Logic);
+        profiler.enter(Phase::Render);
+        profiler.exit(Phase::Render);
+        profiler.end_frame();
+        step.set(300);
+        profiler.enter(Phase::Logic);
+        profiler.exit(Phase::Logic);
+        // An exit without an enter adds nothing.
+        profiler.exit(Phase::Flush);
+        profiler.end_frame();
+        let logic = profiler.get_stats(Phase::Logic);
+        assert_eq!(
+            (logic.get_avg(), logic.get_max(), logic.get_len()),
+            (250, 300, 2)
+        );
+        let render = profiler.get_stats(Phase::Render);
+        assert_eq!((render.get_avg(), render.get_max()), (50, 100));
+        assert_eq!(profiler.get_stats(Phase::Flush).get_max(), 0);
+        let mut report = String::new();
+        profiler.report_into(&mut report).unwrap();
+        assert_eq!(
+            report,
+            "input avg 0 max 0 us over 2 frames\n\
+             logic avg 250 max 300 us over 2 frames\n\
+             render avg 50 max 100 us over 2 frames\n\
+             flush avg 0 max 0 us over 2 frames"
+        );
+    }
+
+    #[cfg(not(feature = "profiling"))]
+    #[test]
+    fn test_disabled() {
+        // The disabled profiler takes no space and never reads its clock.
+        let reads = Cell::new(0);
+        let clock = || {
+            reads.set(reads.get() + 1);
+            0
+        };
+        let mut profiler = Profiler::new(clock);
+        assert_eq!(core::mem::size_of_val(&profiler), 0);
+        profiler.enter(Phase::Logic);
+        profiler.exit(Phase::Logic);
+        profiler.end_frame();
+        assert_eq!(reads.get(), 0);
+        let mut report = String::new();
+        profiler.report_into(&mut report).unwrap();
+        assert!(report.starts_with("profiling disabled"));
+    }
+}
@@ -0,0 +1,155 @@
+use crate::error::TetrisError;
+use crate::randomizer::RandomizerStats;
+use crate::rng::{GameRng, RngSource};
+use crate::savestate::{shape_from_u8, Persist, Reader, SaveError, Writer};
+use crate::tetrominoes::{TetrominoShape, SHAPES};
+
+/// All seven shapes, forming a single bag.
+const BAG: [TetrominoShape; 7] = SHAPES;
+
+/// The capacity of the queue, two full bags.
+const CAPACITY: usize = 2 * BAG.len();
+
+#[derive(Debug)]
+pub struct PieceQueue {
+    /// The upcoming pieces, dealt from shuffled bags of all seven shapes.
+    /// # Attributes
+    /// - `pieces` - The upcoming pieces, always holding at least one full bag
+    /// - `stats` - The statistics of the dealt pieces, not part of the save state
+    pieces: heapless::Deque<TetrominoShape, CAPACITY>,
+    stats: RandomizerStats,
+}
+
+impl PieceQueue {
+    /// Create a new queue holding two shuffled bags.
+    /// # Arguments
+    /// - `rng` - A muteable reference to the `GameRng` used for shuffling
+    /// # Returns
+    /// - `PieceQueue` - The filled queue
+    pub fn new(rng: &mut GameRng) -> Self {
+        let mut queue = PieceQueue {
+            pieces: heapless::Deque::new(),
+            stats: RandomizerStats::new(),
+        };
+        queue._refill(rng);
+        queue._refill(rng);
+        queue
+    }
+
+    /// Take the next piece from the queue, shuffling in a new bag when running low.
+    /// # Arguments
+    /// - `rng` - A muteable reference to the `GameRng` used for shuffling
+    /// # Returns
+    /// - `TetrominoShape` - The next piece
+    pub fn pop(&mut self, rng: &mut GameRng) -> TetrominoShape {
+        if self.pieces.len() <= BAG.len() {
+            self._refill(rng);
+        }
+        let shape = self
+            .pieces
+            .pop_front()
+            .expect("the queue holds a bag after refilling");
+        self.stats.record(shape);
+        shape
+    }
+
+    /// Peek at the upcoming pieces without taking them.
+    /// # Arguments
+    /// - `count` - The number of pieces to peek at, at most 7
+    /// # Returns
+    /// - `impl Iterator<Item = &TetrominoShape>` - The upcoming pieces, in order
+    pub fn peek(&self, count: usize) -> impl Iterator<Item = &TetrominoShape> {
+        self.pieces.iter().take(count)
+    }
+
+    /// Check if the queue holds at least a full bag, as it does between any two pops.
+    /// # Returns
+    /// - `bool` - Whether (`true`) or not (`false`) at least 7 pieces are queued
+    pub fn is_stocked(&self) -> bool {
+        self.pieces.len() >= BAG.len()
+    }
+
+    /// Get a reference to the statistics of the dealt pieces.
+    pub fn get_stats(&self) -> &RandomizerStats {
+        &self.stats
+    }
+
+    /// Replace the statistics of the dealt pieces, e.g. to keep counting over the games of a session.
+    /// # Arguments
+    /// - `stats` - The `RandomizerStats` to continue from
+    pub fn set_stats(&mut self, stats: RandomizerStats) {
+        self.stats = stats;
+    }
+
+    /// Replace the upcoming pieces, followed by a shuffled bag, to start a test with known pieces.
+    /// # Arguments
+    /// - `shapes` - The upcoming shapes, at most 7
+    /// - `rng` - A muteable reference to the `GameRng` used for shuffling
+    #[cfg(test)]
+    pub(crate) fn set_next(&mut self, shapes: &[TetrominoShape], rng: &mut GameRng) {
+        self.pieces.clear();
+        for shape in shapes {
+            self.pieces
+                .push_back(*shape)
+                .expect("at most a bag of shapes is set");
+        }
+        self._refill(rng);
+    }
+
+    /// Append a shuffled bag to the queue, using a Fisher-Yates shuffle.
+    fn _refill(&mut self, rng: &mut GameRng) {
+        let mut bag = BAG;
+        for i in (1..bag.len()).rev() {
+            bag.swap(i, rng.below(i as u32 + 1) as usize);
+        }
+        for shape in bag {
+            // Only refilled with at most a bag left, so the second bag always fits.
+            self.pieces.push_back(shape).ok();
+        }
+    }
+}
+
+impl Persist for PieceQueue {
+    fn save(&self, out: &mut Writer) {
+        let start = out.get_len();
+        out.index(self.pieces.len());
+        for shape in self.pieces.iter() {
+            out.u8(*shape as u8);
+        }
+        out.pad_to(start, 1 + CAPACITY);
+    }
+
+    fn restore(&mut self, input: &mut Reader) -> Result<(), TetrisError> {
+        let start = input.get_offset();
+        let len = input.index()?;
+        if len > CAPACITY {
+            return Err(SaveError::Invalid.into());
+        }
+        self.pieces.clear();
+        for _ in 0..len {
+            let shape = shape_from_u8(input.u8()?)?;
+            self.pieces
+                .push_back(shape)
+                .expect("the length is checked against the capacity");
+        }
+        input.skip_to(start, 1 + CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PieceQueue, BAG};
+    use crate::rng::GameRng;
+
+    #[test]
+    fn test_bags() {
+        // Every consecutive group of 7 pieces holds each shape exactly once.
+        let mut rng = GameRng::new(42);
+        let mut queue = PieceQueue::new(&mut rng);
+        for _ in 0..10 {
+            let mut bag: Vec<_> = (0..7).map(|_| queue.pop(&mut rng)).collect();
+            bag.sort_by_key(|shape| *shape as u8);
+            assert_eq!(bag, BAG);
+        }
+    }
+}
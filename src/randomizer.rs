@@ -0,0 +1,218 @@
+//! Statistics of the dealt pieces, to check a claim that the randomizer is unfair.
+//!
+//! The `PieceQueue` records every piece it deals in its `RandomizerStats`: how often every shape was dealt, the gap
+//! since it was last dealt and its longest drought. The chi-square statistic compares the counts with a uniform
+//! distribution, and like every other number it is computed with integers only. The game keeps the statistics over
+//! the games of a session, the menu draws the counts as bars and the console prints them with `stats`.
+use crate::tetrominoes::{TetrominoShape, SHAPES};
+use core::fmt;
+
+/// The scale of the chi-square statistic, in hundredths.
+pub const CHI_SQUARE_SCALE: u64 = 100;
+
+/// The chi-square statistic above which the counts are unlikely to be uniform, 12.59 for the 6 degrees of freedom of
+/// the seven shapes at a significance of 5%, scaled by `CHI_SQUARE_SCALE`.
+pub const CHI_SQUARE_CRITICAL: u32 = 1259;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RandomizerStats {
+    /// The statistics of the dealt pieces, indexed by the discriminant of the shape.
+    /// # Attributes
+    /// - `counts` - The number of times every shape was dealt
+    /// - `gaps` - The number of pieces dealt since every shape was last dealt, or since the start
+    /// - `max_gaps` - The largest number of pieces dealt between two pieces of every shape, counting the pieces
+    ///   before the first one
+    /// - `dealt` - The number of dealt pieces
+    counts: [u32; 7],
+    gaps: [u32; 7],
+    max_gaps: [u32; 7],
+    dealt: u32,
+}
+
+impl RandomizerStats {
+    /// Create the statistics without any dealt pieces.
+    /// # Returns
+    /// - `RandomizerStats` - The statistics with all counts at 0
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a dealt piece.
+    /// # Arguments
+    /// - `shape` - The `TetrominoShape` of the piece
+    pub fn record(&mut self, shape: TetrominoShape) {
+        let dealt = shape as usize;
+        for (index, gap) in self.gaps.iter_mut().enumerate() {
+            match index == dealt {
+                true => {
+                    self.max_gaps[index] = self.max_gaps[index].max(*gap);
+                    *gap = 0;
+                }
+                false => *gap = gap.saturating_add(1),
+            }
+        }
+        self.counts[dealt] = self.counts[dealt].saturating_add(1);
+        self.dealt = self.dealt.saturating_add(1);
+    }
+
+    /// Get the number of dealt pieces.
+    pub fn get_dealt(&self) -> u32 {
+        self.dealt
+    }
+
+    /// Get the number of times a shape was dealt.
+    /// # Arguments
+    /// - `shape` - The `TetrominoShape`
+    /// # Returns
+    /// - `u32` - The count of the shape
+    pub fn get_count(&self, shape: TetrominoShape) -> u32 {
+        self.counts[shape as usize]
+    }
+
+    /// Get the number of pieces dealt since a shape was last dealt.
+    /// # Arguments
+    /// - `shape` - The `TetrominoShape`
+    /// # Returns
+    /// - `u32` - The current gap of the shape, the number of dealt pieces if it was never dealt
+    pub fn get_gap(&self, shape: TetrominoShape) -> u32 {
+        self.gaps[shape as usize]
+    }
+
+    /// Get the largest number of pieces dealt between two pieces of a shape.
+    /// # Arguments
+    /// - `shape` - The `TetrominoShape`
+    /// # Returns
+    /// - `u32` - The largest finished gap of the shape, not counting the current one
+    pub fn get_max_gap(&self, shape: TetrominoShape) -> u32 {
+        self.max_gaps[shape as usize]
+    }
+
+    /// Get the longest drought of any shape, including the droughts still going on.
+    /// # Returns
+    /// - `u32` - The largest gap of any shape, finished or current
+    pub fn get_max_drought(&self) -> u32 {
+        self.max_gaps
+            .iter()
+            .chain(self.gaps.iter())
+            .copied()
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Get the chi-square statistic of the counts against a uniform distribution over the seven shapes.
+    ///
+    /// With `n` dealt pieces every shape is expected `n / 7` times, so the statistic is `7 * sum(count^2) / n - n`,
+    /// computed in a single division to stay exact.
+    /// # Returns
+    /// - `u32` - The statistic scaled by `CHI_SQUARE_SCALE` and rounded down, 0 without any dealt pieces, compare
+    ///   it with `CHI_SQUARE_CRITICAL`
+    pub fn get_chi_square(&self) -> u32 {
+        let dealt = self.dealt as u64;
+        if dealt == 0 {
+            return 0;
+        }
+        let squares: u64 = self
+            .counts
+            .iter()
+            .map(|count| *count as u64 * *count as u64)
+            .sum();
+        let scaled = (SHAPES.len() as u64 * CHI_SQUARE_SCALE)
+            .saturating_mul(squares)
+            .saturating_sub(CHI_SQUARE_SCALE.saturating_mul(dealt * dealt));
+        (scaled / dealt).min(u32::MAX as u64) as u32
+    }
+
+    /// Get the heights of bars showing the counts, the most dealt shape at the full height.
+    /// # Arguments
+    /// - `height` - The height of the tallest bar
+    /// # Returns
+    /// - `[usize; 7]` - The height of the bar of every shape, in the order of `SHAPES`, rounded down
+    pub fn bar_heights(&self, height: usize) -> [usize; 7] {
+        let max = self.counts.iter().copied().max().unwrap_or(0).max(1) as u64;
+        self.counts
+            .map(|count| (count as u64 * height as u64 / max) as usize)
+    }
+
+    /// Write the statistics, a line per shape with its count, current gap and longest gap, followed by a line with
+    /// the dealt pieces, the longest drought and the chi-square statistic, without a trailing line ending.
+    /// # Arguments
+    /// - `out` - A muteable reference to the writer
+    /// # Returns
+    /// - `fmt::Result` - The error of the writer, if any
+    pub fn report_into(&self, out: &mut dyn fmt::Write) -> fmt::Result {
+        for shape in SHAPES {
+            writeln!(
+                out,
+                "{} count {:>5} gap {:>3} max {:>3}",
+                char::from(shape),
+                self.get_count(shape),
+                self.get_gap(shape),
+                self.get_max_gap(shape)
+            )?;
+        }
+        let chi_square = self.get_chi_square();
+        write!(
+            out,
+            "dealt {}, drought {}, chi-square {}.{:02}",
+            self.dealt,
+            self.get_max_drought(),
+            chi_square / CHI_SQUARE_SCALE as u32,
+            chi_square % CHI_SQUARE_SCALE as u32
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RandomizerStats, CHI_SQUARE_CRITICAL};
+    use crate::queue::PieceQueue;
+    use crate::rng::GameRng;
+    use crate::tetrominoes::TetrominoShape::{I, J, O, T};
+    use crate::tetrominoes::SHAPES;
+
+    #[test]
+    fn test_biased() {
+        // Alternating I and O pieces followed by a single T, never dealing the other four shapes.
+        let mut stats = RandomizerStats::new();
+        for shape in [I, O, I, O, I, O, I, O, I, O, T] {
+            stats.record(shape);
+        }
+        assert_eq!(stats.get_dealt(), 11);
+        assert_eq!((stats.get_count(I), stats.get_count(O)), (5, 5));
+        assert_eq!((stats.get_max_gap(I), stats.get_max_gap(O)), (1, 1));
+        assert_eq!((stats.get_gap(I), stats.get_gap(O)), (2, 1));
+        // The T waited for the 10 pieces before it, the J is still waiting for all 11.
+        assert_eq!((stats.get_max_gap(T), stats.get_gap(T)), (10, 0));
+        assert_eq!((stats.get_max_gap(J), stats.get_gap(J)), (0, 11));
+        assert_eq!(stats.get_max_drought(), 11);
+        // 7 * (25 + 25 + 1) / 11 - 11 = 21.45
+        assert_eq!(stats.get_chi_square(), 2145);
+        assert!(stats.get_chi_square() > CHI_SQUARE_CRITICAL);
+        assert_eq!(stats.bar_heights(20), [20, 0, 0, 20, 0, 4, 0]);
+        let mut report = String::new();
+        stats.report_into(&mut report).unwrap();
+        assert!(report.starts_with("I count     5 gap   2 max   1\n"));
+        assert!(report.ends_with("\ndealt 11, drought 11, chi-square 21.45"));
+    }
+
+    #[test]
+    fn test_bags() {
+        // Two pieces of a shape are at most 12 pieces apart, the first piece of a bag and the last of the next.
+        let mut rng = GameRng::new(3);
+        let mut queue = PieceQueue::new(&mut rng);
+        for _ in 0..7 * 100 {
+            queue.pop(&mut rng);
+        }
+        let stats = queue.get_stats();
+        assert_eq!(stats.get_dealt(), 700);
+        assert!(stats.get_max_drought() <= 12);
+        assert!(SHAPES.iter().any(|shape| stats.get_max_gap(*shape) >= 10));
+        // Whole bags deal every shape equally often.
+        let counts: Vec<u32> = SHAPES.iter().map(|shape| stats.get_count(*shape)).collect();
+        assert_eq!(counts, [100; 7]);
+        assert_eq!(stats.get_chi_square(), 0);
+        let empty = RandomizerStats::new();
+        assert_eq!((empty.get_chi_square(), empty.get_max_drought()), (0, 0));
+        assert_eq!(empty.bar_heights(20), [0; 7]);
+    }
+}
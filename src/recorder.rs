@@ -0,0 +1,503 @@
+//! A flight recorder of the recent inputs, to attach a replay to a bug report after something weird happened.
+//!
+//! Players rarely record the game that glitched, so a game with recording enabled, see `Game::set_recording()`,
+//! records itself into a window of segments of `SEGMENT_TICKS` ticks, each starting from a save state of the game,
+//! dropping the oldest segment for a new one once `WINDOW_SEGMENTS` are kept. The ticks of all segments share a ring
+//! of `WINDOW_WORDS` words encoded like a `Replay`, so a busy window also drops its oldest segments once the ring is
+//! full. The window thus holds up to the last 2.5 minutes of play. A game over, or holding the `CHORD` for
+//! `CHORD_TICKS`, freezes the window into a replay starting from the save state of its oldest segment, to play with
+//! `Game::seek_replay()`. The console prints the frozen replay as base64 with `dump`, and the firmware keeps the most
+//! recent one in flash.
+//!
+//! A tick never allocates, so the buffers of the window and of the frozen copy are allocated with the recorder and
+//! reused, and the frozen copy only becomes a `Replay` when it is read.
+//!
+//! The presses of the first tick of a replay are derived as if nothing was held before, see `Replay::inputs()`, so a
+//! segment only starts on a tick after a tick without held actions.
+use crate::board::BoardOps;
+use crate::cell::Cell;
+use crate::clock::TICK_HZ;
+use crate::error::TetrisError;
+use crate::game::Game;
+use crate::input::{Action, Chord, InputState};
+use crate::replay::{encode_tick, Keyframe, Replay};
+use crate::savestate::save_state_bytes;
+use crate::settings::Settings;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// The number of ticks of a segment, 30 seconds at `TICK_HZ`.
+pub const SEGMENT_TICKS: u32 = 30 * TICK_HZ;
+
+/// The number of segments of the window, the oldest dropped for a new one.
+pub const WINDOW_SEGMENTS: usize = 5;
+
+/// The number of words of the ring shared by the segments, 4 KiB. A tick adds at most a word, mostly far less as a
+/// word holds a run of idle ticks.
+pub const WINDOW_WORDS: usize = 2048;
+
+/// The actions to hold together to freeze the window without a game over.
+pub const CHORD: [Action; 2] = [Action::Hold, Action::Pause];
+
+/// The number of ticks to hold the `CHORD` for, 2 seconds at `TICK_HZ`.
+pub const CHORD_TICKS: u32 = 2 * TICK_HZ;
+
+/// Swallow the presses of the `CHORD` while all of its actions are held, see `Chord::mask()`, so freezing the window
+/// neither pauses nor holds. Recording or not, so a replay of the ticks plays alike.
+/// # Arguments
+/// - `input` - A reference to the `InputState` of this tick
+/// # Returns
+/// - `InputState` - The input state, without the actions of the `CHORD` as pressed if all of them are held
+pub fn mask_chord(input: &InputState) -> InputState {
+    Chord::new(&CHORD, CHORD_TICKS).mask(input)
+}
+
+/// A segment of a window, starting from a save state.
+/// # Attributes
+/// - `word` - The index of its first word, counting every word ever written to the ring
+/// - `ticks` - The number of recorded ticks
+/// - `seed` - The seed of the recorded game
+/// - `settings` - The `Settings` of the recorded game
+/// - `state` - The save state of the game before its first tick, see `Game::save_state_into()`
+#[derive(Debug, Default)]
+struct Segment {
+    word: usize,
+    ticks: u32,
+    seed: u64,
+    settings: Settings,
+    state: Vec<u8>,
+}
+
+/// The segments of recorded ticks and the ring of their words.
+/// # Attributes
+/// - `words` - The ring of words, at the index of a word modulo its length
+/// - `start` - The index of the oldest word of the segments
+/// - `end` - The index of the next word to write
+/// - `segments` - The ring of segments, at the index of a segment modulo `WINDOW_SEGMENTS`
+/// - `first` - The index of the oldest segment
+/// - `count` - The number of segments, recording into the newest one
+#[derive(Debug, Default)]
+struct Window {
+    words: Vec<u16>,
+    start: usize,
+    end: usize,
+    segments: Vec<Segment>,
+    first: usize,
+    count: usize,
+}
+
+impl Window {
+    /// Create an empty window with its buffers for a board size.
+    /// # Arguments
+    /// - `rows` - The number of rows of the board
+    /// - `cols` - The number of columns of the board
+    /// # Returns
+    /// - `Window` - The window without any segments
+    fn new(rows: usize, cols: usize) -> Self {
+        Window {
+            words: vec![0; WINDOW_WORDS],
+            segments: (0..WINDOW_SEGMENTS)
+                .map(|_| Segment {
+                    state: vec![0; save_state_bytes(rows, cols)],
+                    ..Segment::default()
+                })
+                .collect(),
+            ..Window::default()
+        }
+    }
+
+    /// Drop all segments.
+    fn clear(&mut self) {
+        self.start = self.end;
+        self.count = 0;
+    }
+
+    /// Get a segment by its age, 0 for the oldest one.
+    fn _segment(&self, index: usize) -> &Segment {
+        &self.segments[(self.first + index) % WINDOW_SEGMENTS]
+    }
+
+    /// Drop the oldest segment and its words.
+    fn _drop_oldest(&mut self) {
+        self.first += 1;
+        self.count -= 1;
+        self.start = match self.count {
+            0 => self.end,
+            _ => self._segment(0).word,
+        };
+    }
+
+    /// Start a new segment from the current state of a game, dropping the oldest one if the window is full.
+    /// # Arguments
+    /// - `game` - A reference to the recorded `Game`
+    /// # Returns
+    /// - `Result<(), TetrisError>` - Nothing, or a `SaveError` if the game cannot be saved
+    fn start_segment<B: BoardOps<Cell> + Clone>(
+        &mut self,
+        game: &Game<B>,
+    ) -> Result<(), TetrisError> {
+        if self.count == WINDOW_SEGMENTS {
+            self._drop_oldest();
+        }
+        let segment = &mut self.segments[(self.first + self.count) % WINDOW_SEGMENTS];
+        game.save_state_into(&mut segment.state)?;
+        (segment.word, segment.ticks) = (self.end, 0);
+        (segment.seed, segment.settings) = (game.seed(), *game.get_settings());
+        self.count += 1;
+        Ok(())
+    }
+
+    /// Record the held actions of a tick into the newest segment, dropping the oldest segments while the ring is
+    /// full, or all of them if the newest one filled the ring by itself.
+    /// # Arguments
+    /// - `held` - The held actions of the tick, see `InputState::get_held_bits()`
+    fn record(&mut self, held: u16) {
+        let newest = (self.first + self.count - 1) % WINDOW_SEGMENTS;
+        let len = self.words.len();
+        let last = match self.end > self.segments[newest].word {
+            true => Some(&mut self.words[(self.end - 1) % len]),
+            false => None,
+        };
+        if let Some(word) = encode_tick(last, held) {
+            if self.end - self.start == len {
+                self._drop_oldest();
+                if self.count == 0 {
+                    return;
+                }
+            }
+            self.words[self.end % len] = word;
+            self.end += 1;
+        }
+        self.segments[newest].ticks += 1;
+    }
+
+    /// Get the number of recorded ticks.
+    fn get_ticks(&self) -> u32 {
+        (0..self.count)
+            .map(|index| self._segment(index).ticks)
+            .sum()
+    }
+
+    /// Copy another window of the same board size into the buffers of this one.
+    /// # Arguments
+    /// - `other` - A reference to the `Window` to copy
+    fn copy_from(&mut self, other: &Window) {
+        self.words.copy_from_slice(&other.words);
+        (self.start, self.end) = (other.start, other.end);
+        (self.first, self.count) = (other.first, other.count);
+        for (segment, copied) in self.segments.iter_mut().zip(&other.segments) {
+            segment.state.copy_from_slice(&copied.state);
+            (segment.word, segment.ticks) = (copied.word, copied.ticks);
+            (segment.seed, segment.settings) = (copied.seed, copied.settings);
+        }
+    }
+
+    /// Build the replay of the segments from one of them on.
+    /// # Arguments
+    /// - `index` - The age of the first segment of the replay, 0 for the oldest one
+    /// # Returns
+    /// - `Replay` - The replay, starting from a keyframe of the segment at tick 0
+    fn to_replay(&self, index: usize) -> Replay {
+        let segment = self._segment(index);
+        let words = (segment.word..self.end)
+            .map(|word| self.words[word % self.words.len()])
+            .collect();
+        let keyframe = Keyframe::from_save_state(&segment.state, 0)
+            .expect("a segment starts from a save state");
+        Replay::from_keyframe(segment.settings, segment.seed, words, keyframe)
+    }
+}
+
+/// The recent inputs of a game in a window of segments, and the last frozen window.
+/// # Attributes
+/// - `window` - The window recording the ticks
+/// - `frozen` - The copy of the window made by the last freeze
+/// - `stored` - The frozen replay set from elsewhere, e.g. flash, until the next freeze
+/// - `last_held` - The held actions of the previous tick, recorded or not
+/// - `chord` - The `CHORD` held for `CHORD_TICKS` to freeze the window
+#[derive(Debug, Default)]
+pub struct ReplayRecorder {
+    window: Window,
+    frozen: Window,
+    stored: Option<Replay>,
+    last_held: u16,
+    chord: Chord,
+}
+
+impl ReplayRecorder {
+    /// Create a recorder without any ticks or a frozen replay, allocating its buffers. The default recorder has none
+    /// and records nothing.
+    /// # Arguments
+    /// - `rows` - The number of rows of the board of the recorded games
+    /// - `cols` - The number of columns of the board of the recorded games
+    /// # Returns
+    /// - `ReplayRecorder` - The recorder, about 14 KiB on the heap for the standard board
+    pub fn new(rows: usize, cols: usize) -> Self {
+        ReplayRecorder {
+            window: Window::new(rows, cols),
+            frozen: Window::new(rows, cols),
+            chord: Chord::new(&CHORD, CHORD_TICKS),
+            ..ReplayRecorder::default()
+        }
+    }
+
+    /// Whether (`true`) or not (`false`) the recorder has buffers to record into, see `ReplayRecorder::new()`.
+    pub fn is_recording(&self) -> bool {
+        !self.window.words.is_empty()
+    }
+
+    /// Record the input of the next tick of a game, before the game ticks.
+    ///
+    /// A tick outside of a game in progress breaks the continuity of the window, so it drops the window.
+    /// # Arguments
+    /// - `game` - A reference to the recorded `Game`, to save the state of a new segment
+    /// - `input` - A reference to the `InputState` of the tick
+    /// - `recording` - Whether (`true`) or not (`false`) the tick is part of a game in progress
+    /// # Returns
+    /// - `Result<Option<u32>, TetrisError>` - The number of ticks of the window if the `CHORD` froze it this tick,
+    ///   or a `SaveError` if the state of a new segment cannot be saved, e.g. of a game on another board
+    pub fn record<B: BoardOps<Cell> + Clone>(
+        &mut self,
+        game: &Game<B>,
+        input: &InputState,
+        recording: bool,
+    ) -> Result<Option<u32>, TetrisError> {
+        let after_idle = self.last_held == 0;
+        self.last_held = input.get_held_bits();
+        if !recording || !self.is_recording() {
+            self.window.clear();
+            self.chord.reset();
+            return Ok(None);
+        }
+        let full = match self.window.count {
+            0 => true,
+            count => self.window._segment(count - 1).ticks >= SEGMENT_TICKS,
+        };
+        if full && after_idle {
+            self.window.start_segment(game)?;
+        }
+        if self.window.count > 0 {
+            self.window.record(self.last_held);
+        }
+        match self.chord.update(input) {
+            true => Ok(self.freeze()),
+            false => Ok(None),
+        }
+    }
+
+    /// Freeze the window, replacing the frozen replay, and keep recording.
+    /// # Returns
+    /// - `Option<u32>` - The number of ticks of the window, or `None` without any recorded ticks
+    pub fn freeze(&mut self) -> Option<u32> {
+        let ticks = self.get_ticks();
+        if ticks == 0 {
+            return None;
+        }
+        self.frozen.copy_from(&self.window);
+        self.stored = None;
+        Some(ticks)
+    }
+
+    /// Get the number of recorded ticks of the window.
+    pub fn get_ticks(&self) -> u32 {
+        self.window.get_ticks()
+    }
+
+    /// Get the frozen window as a single replay.
+    /// # Returns
+    /// - `Option<Replay>` - The replay, starting from a keyframe at tick 0, or `None` before the first freeze
+    pub fn get_frozen(&self) -> Option<Replay> {
+        self.get_frozen_within(usize::MAX)
+    }
+
+    /// Get the most recent part of the frozen window fitting a number of bytes, dropping its oldest segments.
+    /// # Arguments
+    /// - `max_bytes` - The maximum number of bytes of the replay, see `Replay::get_serialized_size()`
+    /// # Returns
+    /// - `Option<Replay>` - The replay, starting from a keyframe at tick 0, or `None` if not even its last segment
+    ///   fits or before the first freeze
+    pub fn get_frozen_within(&self, max_bytes: usize) -> Option<Replay> {
+        if let Some(stored) = &self.stored {
+            return (stored.get_serialized_size() <= max_bytes).then(|| stored.clone());
+        }
+        (0..self.frozen.count)
+            .map(|index| self.frozen.to_replay(index))
+            .find(|replay| replay.get_serialized_size() <= max_bytes)
+    }
+
+    /// Replace the frozen replay until the next freeze, e.g. with the one kept in flash before a power cycle.
+    /// # Arguments
+    /// - `replay` - The `Replay`, starting from a keyframe at tick 0
+    pub fn set_frozen(&mut self, replay: Replay) {
+        self.frozen.clear();
+        self.stored = Some(replay);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ReplayRecorder, CHORD, CHORD_TICKS, SEGMENT_TICKS, WINDOW_SEGMENTS, WINDOW_WORDS};
+    use crate::ai::{Autopilot, Lookahead};
+    use crate::events::GameEvent;
+    use crate::game::{Game, GameState};
+    use crate::input::{Action, InputState};
+    use crate::replay::Replay;
+    use crate::rng::{GameRng, RngSource};
+    use crate::settings::Settings;
+
+    /// A recording game started and counted down until playing.
+    fn playing(seed: u64) -> Game {
+        let mut game = Game::new(Settings::default(), seed);
+        game.set_recording(true);
+        game.tick(&InputState::from_action(Action::Start));
+        while game.get_state() != GameState::Playing {
+            game.tick(&InputState::default());
+        }
+        game
+    }
+
+    #[test]
+    fn test_window() {
+        // A paused game fed random moves for longer than the window keeps the most recent ticks.
+        let mut game = playing(3);
+        game.tick(&InputState::from_action(Action::Pause));
+        let shape = game.get_board().get_shape();
+        let mut recorder = ReplayRecorder::new(shape.row, shape.col);
+        let mut script = GameRng::new(11);
+        let mut input = InputState::default();
+        let mut held = Vec::new();
+        for _ in 0..(WINDOW_SEGMENTS as u32 + 2) * SEGMENT_TICKS {
+            input = match script.below(40) {
+                0 => input.next(&[Action::Left]),
+                1 => input.next(&[Action::RotateCw, Action::SoftDrop]),
+                _ => input.next(&[]),
+            };
+            assert_eq!(recorder.record(&game, &input, true), Ok(None));
+            game.tick(&input);
+            held.push(input.get_held_bits());
+        }
+        assert_eq!(game.get_state(), GameState::Paused);
+        let ticks = recorder.freeze().unwrap();
+        assert!(ticks >= (WINDOW_SEGMENTS as u32 - 1) * SEGMENT_TICKS);
+        assert!(ticks < WINDOW_SEGMENTS as u32 * SEGMENT_TICKS + 60);
+        let replay = recorder.get_frozen().unwrap();
+        assert!(replay.get_size() <= 2 * WINDOW_WORDS);
+        assert_eq!(replay.get_ticks(), ticks);
+        assert_eq!(replay.get_keyframes()[0].get_ticks(), 0);
+        assert!(replay
+            .inputs()
+            .map(|input| input.get_held_bits())
+            .eq(held[held.len() - ticks as usize..].iter().copied()));
+        let sought = Game::seek_replay(&replay, ticks).unwrap();
+        assert_eq!(sought.board_hash(), game.board_hash());
+        // The newest segments fitting fewer bytes, and nothing fitting too few.
+        let part = recorder.get_frozen_within(replay.get_serialized_size() - 1);
+        assert!(part.unwrap().get_ticks() < ticks);
+        assert!(recorder.get_frozen_within(0).is_none());
+        // A tick outside of a game drops the window, but keeps the frozen replay.
+        recorder.record(&game, &input, false).unwrap();
+        assert_eq!((recorder.get_ticks(), recorder.freeze()), (0, None));
+        assert_eq!(recorder.get_frozen().unwrap().get_ticks(), ticks);
+    }
+
+    #[test]
+    fn test_busy() {
+        // A press every other tick fills the ring of words before the segments, dropping the oldest ones early.
+        let mut game = playing(4);
+        game.tick(&InputState::from_action(Action::Pause));
+        let shape = game.get_board().get_shape();
+        let mut recorder = ReplayRecorder::new(shape.row, shape.col);
+        let mut held = Vec::new();
+        for tick in 0..3 * SEGMENT_TICKS {
+            let input = match tick % 2 {
+                0 => InputState::from_action(Action::Left),
+                _ => InputState::default(),
+            };
+            recorder.record(&game, &input, true).unwrap();
+            game.tick(&input);
+            held.push(input.get_held_bits());
+        }
+        let ticks = recorder.get_ticks();
+        assert!(ticks <= WINDOW_WORDS as u32);
+        assert!(ticks >= WINDOW_WORDS as u32 - SEGMENT_TICKS);
+        recorder.freeze();
+        let replay = recorder.get_frozen().unwrap();
+        assert!(replay
+            .inputs()
+            .map(|input| input.get_held_bits())
+            .eq(held[held.len() - ticks as usize..].iter().copied()));
+    }
+
+    #[test]
+    fn test_game_over() {
+        // The autopilot plays for longer than the window, then hard drops until the stack tops out.
+        let mut game = playing(5);
+        let mut autopilot = Autopilot::default();
+        let mut ticks = 0;
+        let frozen = loop {
+            let input = match ticks > WINDOW_SEGMENTS as u32 * SEGMENT_TICKS {
+                true if ticks % 2 == 0 => InputState::from_action(Action::HardDrop),
+                true => InputState::default(),
+                false => {
+                    autopilot.next_input(game.get_board(), game.get_piece(), &Lookahead::default())
+                }
+            };
+            let output = game.tick(&input);
+            ticks += 1;
+            let frozen = output.events.iter().find_map(|event| match event {
+                GameEvent::ReplayFrozen { ticks } => Some(*ticks),
+                _ => None,
+            });
+            if let Some(frozen) = frozen {
+                assert!(output
+                    .events
+                    .iter()
+                    .any(|event| matches!(event, GameEvent::GameOver { .. })));
+                break frozen;
+            }
+        };
+        assert!(frozen < ticks);
+        // The dump pasted back re-simulates to the board of the game over.
+        let replay = game.get_recorder().get_frozen().unwrap();
+        assert_eq!(replay.get_ticks(), frozen);
+        let mut text = vec![0; replay.get_base64_size()];
+        replay.to_base64_into(&mut text).unwrap();
+        let mut buf = vec![0; text.len()];
+        let pasted = Replay::from_base64(&text, &mut buf).unwrap();
+        let sought = Game::seek_replay(&pasted, pasted.get_ticks()).unwrap();
+        assert_eq!(sought.board_hash(), game.board_hash());
+        assert_eq!(
+            sought.get_score().get_points(),
+            game.get_score().get_points()
+        );
+        // The window ends with the game, keeping the frozen replay.
+        game.tick(&InputState::default());
+        assert_eq!(game.get_recorder().get_ticks(), 0);
+        assert!(game.get_recorder().get_frozen().is_some());
+    }
+
+    #[test]
+    fn test_chord() {
+        // Holding the chord freezes the window once, without ending, pausing or holding in the game.
+        let mut game = playing(9);
+        for _ in 0..100 {
+            game.tick(&InputState::default());
+        }
+        let recorded = game.get_recorder().get_ticks();
+        let (state, hold) = (game.get_state(), game.get_hold().get_shape());
+        let mut input = InputState::default();
+        let mut frozen = Vec::new();
+        for tick in 0..2 * CHORD_TICKS {
+            input = input.next(&CHORD);
+            for event in game.tick(&input).events {
+                if let GameEvent::ReplayFrozen { ticks } = event {
+                    frozen.push((tick, ticks));
+                }
+            }
+        }
+        assert_eq!(frozen, [(CHORD_TICKS - 1, recorded + CHORD_TICKS)]);
+        assert_eq!(game.get_state(), state);
+        assert_eq!(game.get_hold().get_shape(), hold);
+        assert_eq!(game.get_recorder().get_ticks(), recorded + 2 * CHORD_TICKS);
+    }
+}
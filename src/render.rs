@@ -0,0 +1,201 @@
+//! The handoff of frames from the game on one core to the LEDs on the other.
+//!
+//! Rendering a frame takes a fraction of a tick, but sending it to the LEDs takes longer than a tick. So the game
+//! renders on core 0 and publishes the frame, and core 1 copies the latest frame and sends it to the LEDs at its own
+//! pace, skipping frames it was too slow for. The game never waits for the LEDs.
+use crate::board::BoardOps;
+use crate::cell::Cell;
+use crate::color::Rgb;
+use crate::coordinate::Coordinate;
+use crate::framebuffer::{FrameBuffer, IN_FRAME};
+use crate::game::Game;
+use crate::seqlock::SeqLock;
+
+/// The latest frame, shared between the core running the game and the core driving the LEDs, e.g. in a `static`.
+///
+/// Only the colors are shared, the patterns of the pixels are dropped.
+/// # Attributes
+/// - `dims` - The number of [rows, cols] of the panel
+/// - `pixels` - The colors of the pixels in row major order, packed by `Rgb::to_u32_rgb()`
+#[derive(Debug)]
+pub struct Shared<const N: usize> {
+    dims: Coordinate,
+    pixels: SeqLock<N>,
+}
+
+impl<const N: usize> Shared<N> {
+    /// Create a shared black frame.
+    /// # Arguments
+    /// - `dims` - The number of [rows, cols] of the panel as a `Coordinate`, covering exactly `N` pixels
+    /// # Returns
+    /// - `Shared<N>` - The shared frame without any published frames
+    pub const fn new(dims: Coordinate) -> Self {
+        assert!(dims.row * dims.col == N, "the panel must have N pixels");
+        Shared {
+            dims,
+            pixels: SeqLock::new(),
+        }
+    }
+
+    /// Get the number of published frames, to check cheaply for a new frame before copying it.
+    pub fn get_frames(&self) -> u32 {
+        self.pixels.get_writes()
+    }
+
+    /// Render the game and publish the frame.
+    /// # Arguments
+    /// - `game` - A reference to the `Game` to render, see `Game::render_into()`
+    pub fn publish<B: BoardOps<Cell> + Clone>(&self, game: &Game<B>) {
+        let mut frame = FrameBuffer::<N>::new(self.dims);
+        game.render_into(&mut frame);
+        self.publish_frame(&frame);
+    }
+
+    /// Publish a rendered frame.
+    /// # Arguments
+    /// - `frame` - A reference to the `FrameBuffer` to publish, of the same shape
+    pub fn publish_frame(&self, frame: &FrameBuffer<N>) {
+        let mut words = [0; N];
+        for (index, word) in words.iter_mut().enumerate() {
+            let coord = Coordinate::from_row_major(index, self.dims).expect(IN_FRAME);
+            *word = frame.get_pixel(coord).unwrap_or(Rgb::BLACK).to_u32_rgb();
+        }
+        self.pixels.write(&words);
+    }
+
+    /// Copy the latest frame, waiting only while it is being published.
+    /// # Returns
+    /// - `FrameSnapshot<N>` - The copy of the frame
+    pub fn latest(&self) -> FrameSnapshot<N> {
+        let mut words = [0; N];
+        let frame = self.pixels.read(&mut words);
+        FrameSnapshot {
+            frame,
+            dims: self.dims,
+            pixels: words.map(Rgb::from_u32_rgb),
+        }
+    }
+}
+
+/// A copy of a published frame.
+/// # Attributes
+/// - `frame` - The number of frames published up to and including this one
+/// - `dims` - The number of [rows, cols] of the panel
+/// - `pixels` - The colors of the pixels in row major order
+#[derive(Clone, Debug, PartialEq)]
+pub struct FrameSnapshot<const N: usize> {
+    pub frame: u32,
+    dims: Coordinate,
+    pixels: [Rgb; N],
+}
+
+impl<const N: usize> FrameSnapshot<N> {
+    /// Get the color of a pixel.
+    /// # Arguments
+    /// - `coord` - The [row, col] of the pixel
+    /// # Returns
+    /// - `Option<Rgb>` - The color, or `None` if the pixel is outside of the panel
+    pub fn get_pixel(&self, coord: Coordinate) -> Option<Rgb> {
+        match coord.row < self.dims.row && coord.col < self.dims.col {
+            true => Some(self.pixels[coord.row * self.dims.col + coord.col]),
+            false => None,
+        }
+    }
+
+    /// Draw the frame into a frame buffer, marking only the changed pixels dirty.
+    /// # Arguments
+    /// - `frame` - A muteable reference to the `FrameBuffer` to draw into, of the same shape
+    pub fn draw_into(&self, frame: &mut FrameBuffer<N>) {
+        for (index, color) in self.pixels.iter().enumerate() {
+            frame.set_pixel(
+                Coordinate::from_row_major(index, self.dims).expect(IN_FRAME),
+                *color,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Shared;
+    use crate::coordinate::Coordinate;
+    use crate::framebuffer::FrameBuffer;
+    use crate::game::Game;
+    use crate::input::{Action, InputState};
+    use crate::settings::Settings;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use std::vec::Vec;
+
+    const DIMS: Coordinate = Coordinate { row: 20, col: 16 };
+
+    /// Render the frames of the first ticks of a game.
+    fn _frames(ticks: usize) -> Vec<FrameBuffer<320>> {
+        let mut game = Game::new(Settings::default(), 155);
+        game.tick(&InputState::from_action(Action::Start));
+        (0..ticks)
+            .map(|_| {
+                game.tick(&InputState::from_action(Action::SoftDrop));
+                let mut frame = FrameBuffer::<320>::new(DIMS);
+                game.render_into(&mut frame);
+                frame
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_publish() {
+        let shared = Shared::<320>::new(DIMS);
+        assert_eq!(shared.latest().frame, 0);
+        let game = Game::new(Settings::default(), 155);
+        shared.publish(&game);
+        let mut expected = FrameBuffer::<320>::new(DIMS);
+        game.render_into(&mut expected);
+        let snapshot = shared.latest();
+        assert_eq!(snapshot.frame, 1);
+        assert_eq!(shared.get_frames(), 1);
+        let mut frame = FrameBuffer::<320>::new(DIMS);
+        snapshot.draw_into(&mut frame);
+        for index in 0..320 {
+            let coord = Coordinate::from_row_major(index, DIMS).unwrap();
+            assert_eq!(frame.get_pixel(coord), expected.get_pixel(coord));
+            assert_eq!(snapshot.get_pixel(coord), expected.get_pixel(coord));
+        }
+        assert_eq!(snapshot.get_pixel(DIMS), None);
+        // Drawing the same snapshot again changes nothing.
+        frame.write_dirty(&Settings::default(), |_| {});
+        snapshot.draw_into(&mut frame);
+        assert_eq!(frame.get_dirty(), None);
+    }
+
+    #[test]
+    fn test_publish_across_threads() {
+        // Core 1 must only ever see whole frames of the game on core 0, never a mix of two.
+        let frames = Arc::new(_frames(200));
+        let shared = Arc::new(Shared::<320>::new(DIMS));
+        let done = Arc::new(AtomicBool::new(false));
+        let reader = {
+            let (frames, shared, done) = (frames.clone(), shared.clone(), done.clone());
+            thread::spawn(move || loop {
+                let finished = done.load(Ordering::Acquire);
+                let snapshot = shared.latest();
+                if snapshot.frame > 0 {
+                    let expected = &frames[snapshot.frame as usize - 1];
+                    for index in 0..320 {
+                        let coord = Coordinate::from_row_major(index, DIMS).unwrap();
+                        assert_eq!(snapshot.get_pixel(coord), expected.get_pixel(coord));
+                    }
+                }
+                if finished {
+                    return snapshot.frame;
+                }
+            })
+        };
+        for frame in frames.iter() {
+            shared.publish_frame(frame);
+        }
+        done.store(true, Ordering::Release);
+        assert_eq!(reader.join().unwrap(), 200);
+    }
+}
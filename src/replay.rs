@@ -0,0 +1,660 @@
+//! Replays, recording the inputs of a game to play it again tick for tick.
+//!
+//! Every tick is stored as a 16 bit word, so a replay only plays from the start. To start playback mid-game, a replay
+//! optionally keeps a `Keyframe` every few locked pieces: the fixed-size part of a save state and the board, its
+//! filled cells run-length encoded by `Board::encode_rle_into()` followed by their 4 bit codes, see `Cell::to_u8()`.
+//! `Game::seek_replay()` restores the last keyframe before a tick and plays the remaining ticks from there.
+//!
+//! A replay with a keyframe at tick 0 starts mid-game from that keyframe instead of from its seed, e.g. the window of
+//! a `recorder::ReplayRecorder`, and only plays with `Game::seek_replay()`. To paste a replay into text, it is also
+//! serialized as base64, see `base64`.
+use crate::base64::{self, Base64Error, Encoder};
+use crate::board::{rle_max_bytes, Board, BoardError, BoardOps};
+use crate::cell::Cell;
+use crate::error::TetrisError;
+use crate::game::Game;
+use crate::grid::Array2D;
+use crate::input::InputState;
+use crate::savestate::{
+    save_state_bytes, save_state_shape, SaveError, HEADER_BYTES as SAVE_HEADER_BYTES, STATE_BYTES,
+};
+use crate::settings::{Settings, SettingsError, SETTINGS_BYTES};
+use crate::storage::_crc32;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// The default maximum number of words of a replay, 32 KiB of the 264 KiB of RAM of the RP2040.
+pub const MAX_REPLAY_WORDS: usize = 16 * 1024;
+
+/// The default number of locked pieces between keyframes, see `Replay::set_keyframe_pieces()`.
+pub const KEYFRAME_PIECES: u32 = 10;
+
+/// The bytes starting a serialized replay.
+const MAGIC: [u8; 4] = *b"TRPL";
+
+/// The number of bytes before the words of a serialized replay: the magic, the seed, the settings and the number of
+/// words.
+const HEADER_BYTES: usize = MAGIC.len() + 8 + SETTINGS_BYTES + 4;
+
+/// The number of bytes of the save state kept by a keyframe, everything before the board.
+const KEYFRAME_STATE_BYTES: usize = SAVE_HEADER_BYTES + STATE_BYTES;
+
+/// The panic message of a field outside of the checked header, a bug in the layout of the header.
+const HEADER_FITS: &str = "the field is within the header";
+
+/// The flag marking a word as a run of idle ticks, with the number of ticks in the other bits.
+const RUN_FLAG: u16 = 1 << 15;
+
+/// The longest run of idle ticks a single word can hold.
+const MAX_RUN: u16 = !RUN_FLAG;
+
+/// Encode the held actions of a tick, extending a run of idle ticks in the last word if possible.
+/// # Arguments
+/// - `last` - A muteable reference to the last word of the replay, `None` without any words
+/// - `held` - The held actions of the tick, see `InputState::get_held_bits()`
+/// # Returns
+/// - `Option<u16>` - The word to append, or `None` if the tick extended the last word
+pub(crate) fn encode_tick(last: Option<&mut u16>, held: u16) -> Option<u16> {
+    match last {
+        Some(last) if held == 0 && *last & RUN_FLAG != 0 && *last != RUN_FLAG | MAX_RUN => {
+            *last += 1;
+            None
+        }
+        _ => Some(match held {
+            0 => RUN_FLAG | 1,
+            _ => held,
+        }),
+    }
+}
+
+/// Count the ticks encoded by words, see `encode_tick()`.
+/// # Arguments
+/// - `words` - The words of the replay
+/// # Returns
+/// - `u32` - The number of ticks
+pub(crate) fn count_ticks(words: &[u16]) -> u32 {
+    words
+        .iter()
+        .map(|word| match word & RUN_FLAG {
+            0 => 1,
+            _ => (word & MAX_RUN) as u32,
+        })
+        .sum()
+}
+
+/// The state of a recorded game after a tick, to start playback from.
+/// # Attributes
+/// - `ticks` - The number of recorded ticks before the keyframe
+/// - `state` - The save state up to the board, see `Game::save_state_into()`
+/// - `board` - The filled cells of the board, see `Board::encode_rle_into()`, followed by their codes, 2 per byte
+///   starting at the low nibble
+#[derive(Clone, Debug, PartialEq)]
+pub struct Keyframe {
+    ticks: u32,
+    state: Vec<u8>,
+    board: Vec<u8>,
+}
+
+impl Keyframe {
+    /// Capture the state of a game.
+    /// # Arguments
+    /// - `game` - A reference to the `Game`, not in the attract mode
+    /// - `ticks` - The number of recorded ticks before the keyframe
+    /// # Returns
+    /// - `Result<Keyframe, TetrisError>` - The keyframe, or a `SaveError` if the game cannot be saved
+    pub fn capture<B: BoardOps<Cell> + Clone>(
+        game: &Game<B>,
+        ticks: u32,
+    ) -> Result<Self, TetrisError> {
+        let shape = game.get_board().get_shape();
+        let mut buf = vec![0; save_state_bytes(shape.row, shape.col)];
+        game.save_state_into(&mut buf)?;
+        Self::from_save_state(&buf, ticks)
+    }
+
+    /// Create a keyframe from a save state, e.g. one saved into a buffer ahead of time.
+    /// # Arguments
+    /// - `buf` - The save state written by `Game::save_state_into()`
+    /// - `ticks` - The number of recorded ticks before the keyframe
+    /// # Returns
+    /// - `Result<Keyframe, TetrisError>` - The keyframe, or a `SaveError` if the bytes are no save state
+    pub fn from_save_state(buf: &[u8], ticks: u32) -> Result<Self, TetrisError> {
+        let shape = save_state_shape(buf)?;
+        let len = save_state_bytes(shape.row, shape.col);
+        if buf.len() < len {
+            return Err(SaveError::Truncated(buf.len()).into());
+        }
+        let buf = &buf[..len];
+        let cells = &buf[KEYFRAME_STATE_BYTES..buf.len() - 4];
+        let array = Array2D::from_row_major(cells, shape.row, shape.col)
+            .expect("a save state holds a byte per cell");
+        let mut board = vec![0; rle_max_bytes(shape.row, shape.col)];
+        let len = Board::from_array(&array, 0).encode_rle_into(&mut board)?;
+        board.truncate(len);
+        for pair in cells
+            .iter()
+            .filter(|cell| **cell != 0)
+            .collect::<Vec<_>>()
+            .chunks(2)
+        {
+            board.push(pair.iter().rev().fold(0, |byte, code| byte << 4 | **code));
+        }
+        Ok(Keyframe {
+            ticks,
+            state: buf[..KEYFRAME_STATE_BYTES].to_vec(),
+            board,
+        })
+    }
+
+    /// Get the number of recorded ticks before the keyframe.
+    pub fn get_ticks(&self) -> u32 {
+        self.ticks
+    }
+
+    /// Get the number of bytes of the encoded board.
+    pub fn get_board_size(&self) -> usize {
+        self.board.len()
+    }
+
+    /// Rebuild the full save state of the keyframe.
+    /// # Returns
+    /// - `Result<Vec<u8>, TetrisError>` - The save state, see `Game::restore_state()`, or a `BoardError::Encoding` if
+    ///   the board does not decode
+    pub fn to_save_state(&self) -> Result<Vec<u8>, TetrisError> {
+        let shape = save_state_shape(&self.state)?;
+        let mut occupancy = Board::new(shape, false);
+        let len = occupancy.decode_rle(&self.board, true)?;
+        let codes = &self.board[len..];
+        let filled = occupancy.cells().filter(|(_, filled)| *filled).count();
+        if codes.len() != filled / 2 + (filled & 1) {
+            return Err(BoardError::Encoding.into());
+        }
+        let mut buf = self.state.clone();
+        let mut code = (0..filled).map(|index| codes[index / 2] >> (index % 2 * 4) & 0x0F);
+        for (_, filled) in occupancy.cells() {
+            buf.push(match filled {
+                true => code.next().expect("every filled cell has a code"),
+                false => 0,
+            });
+        }
+        let crc = _crc32(&buf);
+        buf.extend_from_slice(&crc.to_le_bytes());
+        Ok(buf)
+    }
+}
+
+/// The recorded inputs of a game, to replay it tick for tick.
+///
+/// Every tick is stored as a 16 bit word holding the bit set of held actions,
+/// the presses are derived from the previous tick like `InputState::next()` does.
+/// Consecutive ticks without any held action are stored as a single run length word.
+/// # Attributes
+/// - `seed` - The seed the recorded game was created with
+/// - `settings` - The settings the recorded game was created with
+/// - `words` - The encoded ticks
+/// - `max_words` - The maximum number of words, recording stops once reached
+/// - `ticks` - The number of recorded ticks
+/// - `keyframe_pieces` - The number of locked pieces between keyframes, 0 for none
+/// - `pieces` - The number of recorded locked pieces since the last keyframe
+/// - `keyframes` - The keyframes, by their ticks
+#[derive(Clone, Debug)]
+pub struct Replay {
+    seed: u64,
+    settings: Settings,
+    words: Vec<u16>,
+    max_words: usize,
+    ticks: u32,
+    keyframe_pieces: u32,
+    pieces: u32,
+    keyframes: Vec<Keyframe>,
+}
+
+/// Why a serialized replay could not be read.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReplayError {
+    /// The bytes do not start with the magic of a replay.
+    Magic,
+    /// The bytes end within the header, a word or a keyframe, with the number of bytes.
+    Truncated(usize),
+    /// The bytes go on after the last keyframe, with the number of bytes.
+    Trailing(usize),
+    /// The settings of the recorded game are invalid.
+    Settings(SettingsError),
+    /// The base64 text is invalid or does not fit the buffer.
+    Base64(Base64Error),
+}
+
+/// The state of a game at the end of a replay, to verify it against the recorded game.
+/// # Attributes
+/// - `ticks` - The number of replayed ticks
+/// - `board_hash` - The hash of the board, see `Game::board_hash()`
+/// - `points` - The number of points
+/// - `lines` - The number of cleared lines
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ReplayResult {
+    pub ticks: u32,
+    pub board_hash: u64,
+    pub points: u32,
+    pub lines: u32,
+}
+
+impl Replay {
+    /// Create an empty replay of a game.
+    /// # Arguments
+    /// - `settings` - The `Settings` of the recorded game
+    /// - `seed` - The seed of the recorded game
+    /// - `max_words` - The maximum number of 16 bit words to store, e.g. `MAX_REPLAY_WORDS`
+    /// # Returns
+    /// - `Replay` - The replay without any ticks
+    pub fn new(settings: Settings, seed: u64, max_words: usize) -> Self {
+        Replay {
+            seed,
+            settings,
+            words: Vec::new(),
+            max_words,
+            ticks: 0,
+            keyframe_pieces: 0,
+            pieces: 0,
+            keyframes: Vec::new(),
+        }
+    }
+
+    /// Create a replay starting mid-game from a keyframe at tick 0, e.g. the window of a `ReplayRecorder`.
+    /// # Arguments
+    /// - `settings` - The `Settings` of the recorded game
+    /// - `seed` - The seed of the recorded game
+    /// - `words` - The recorded ticks, encoded by `encode_tick()`
+    /// - `keyframe` - The `Keyframe` of the game before the first tick, its ticks set to 0
+    /// # Returns
+    /// - `Replay` - The full replay of the words
+    pub(crate) fn from_keyframe(
+        settings: Settings,
+        seed: u64,
+        words: Vec<u16>,
+        keyframe: Keyframe,
+    ) -> Self {
+        Replay {
+            seed,
+            settings,
+            max_words: words.len(),
+            ticks: count_ticks(&words),
+            words,
+            keyframe_pieces: 0,
+            pieces: 0,
+            keyframes: vec![keyframe],
+        }
+    }
+
+    /// Keep a keyframe every few locked pieces, see `.record_lock()`.
+    /// # Arguments
+    /// - `pieces` - The number of locked pieces between keyframes, 0 for none
+    pub fn set_keyframe_pieces(&mut self, pieces: u32) {
+        self.keyframe_pieces = pieces;
+    }
+
+    /// Get the keyframes, by their ticks.
+    pub fn get_keyframes(&self) -> &[Keyframe] {
+        &self.keyframes
+    }
+
+    /// Get the seed of the recorded game.
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Get the settings of the recorded game.
+    pub fn get_settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    /// Get the number of recorded ticks.
+    pub fn get_ticks(&self) -> u32 {
+        self.ticks
+    }
+
+    /// Get the number of bytes used by the recorded ticks, without the keyframes.
+    pub fn get_size(&self) -> usize {
+        self.words.len() * 2
+    }
+
+    /// Record the input of the next tick.
+    /// # Arguments
+    /// - `input` - A reference to the `InputState` of the tick
+    /// # Returns
+    /// - `bool` - Whether (`true`) or not (`false`) the tick was recorded, `false` once the replay is full
+    pub fn record(&mut self, input: &InputState) -> bool {
+        if let Some(word) = encode_tick(self.words.last_mut(), input.get_held_bits()) {
+            if self.words.len() >= self.max_words {
+                return false;
+            }
+            self.words.push(word);
+        }
+        self.ticks += 1;
+        true
+    }
+
+    /// Count a locked piece of the recorded game, after recording its tick, keeping a keyframe of the game after
+    /// every `keyframe_pieces` pieces.
+    /// # Arguments
+    /// - `game` - A reference to the recorded `Game`, after the tick the piece locked on
+    /// # Returns
+    /// - `Result<bool, TetrisError>` - Whether (`true`) or not (`false`) a keyframe was kept, or a `SaveError` if the
+    ///   game cannot be saved, e.g. in the attract mode
+    pub fn record_lock<B: BoardOps<Cell> + Clone>(
+        &mut self,
+        game: &Game<B>,
+    ) -> Result<bool, TetrisError> {
+        self.pieces += 1;
+        if self.keyframe_pieces == 0 || self.pieces < self.keyframe_pieces {
+            return Ok(false);
+        }
+        self.pieces = 0;
+        self.keyframes.push(Keyframe::capture(game, self.ticks)?);
+        Ok(true)
+    }
+
+    /// Serialize the replay, e.g. to save it to a file.
+    /// # Returns
+    /// - `Vec<u8>` - The magic, the little endian seed, the settings, the number of words and the words, then the
+    ///   number of keyframes and every keyframe as its ticks, the number of bytes of its board, its state and its board
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.get_serialized_size());
+        self._serialize(&mut |field| bytes.extend_from_slice(field));
+        bytes
+    }
+
+    /// Get the number of characters of the replay as base64, see `.to_base64_into()`.
+    pub fn get_base64_size(&self) -> usize {
+        base64::encoded_len(self.get_serialized_size())
+    }
+
+    /// Serialize the replay as base64 without allocating, e.g. to print it over a serial port.
+    /// # Arguments
+    /// - `out` - The buffer to write the characters into, at least `.get_base64_size()` long
+    /// # Returns
+    /// - `Result<usize, ReplayError>` - The number of characters written, the bytes of `.to_bytes()` as base64, or
+    ///   `Base64Error::BufferTooSmall` with the number of characters
+    pub fn to_base64_into(&self, out: &mut [u8]) -> Result<usize, ReplayError> {
+        let mut encoder = Encoder::new(out);
+        self._serialize(&mut |field| encoder.write(field));
+        encoder.finish().map_err(ReplayError::Base64)
+    }
+
+    /// Deserialize a replay written by `.to_base64_into()`, e.g. pasted from an issue.
+    /// # Arguments
+    /// - `text` - The base64 characters, whitespace like line endings is skipped
+    /// - `buf` - The buffer to decode the bytes into, at least 3 bytes per 4 characters long
+    /// # Returns
+    /// - `Result<Replay, ReplayError>` - The replay, or an error if the text is no base64 of a valid replay
+    pub fn from_base64(text: &[u8], buf: &mut [u8]) -> Result<Self, ReplayError> {
+        let len = base64::decode_into(text, buf).map_err(ReplayError::Base64)?;
+        Replay::from_bytes(&buf[..len])
+    }
+
+    /// Deserialize a replay written by `.to_bytes()`, full so it records no further ticks.
+    /// # Arguments
+    /// - `bytes` - The serialized replay
+    /// # Returns
+    /// - `Result<Replay, ReplayError>` - The replay, or an error if the bytes are not a valid replay
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ReplayError> {
+        if bytes.len() < HEADER_BYTES {
+            return match bytes.starts_with(&MAGIC) {
+                true => Err(ReplayError::Truncated(bytes.len())),
+                false => Err(ReplayError::Magic),
+            };
+        }
+        if bytes[..MAGIC.len()] != MAGIC {
+            return Err(ReplayError::Magic);
+        }
+        let seed = u64::from_le_bytes(bytes[4..12].try_into().expect(HEADER_FITS));
+        let settings = Settings::from_bytes(
+            bytes[12..12 + SETTINGS_BYTES]
+                .try_into()
+                .expect(HEADER_FITS),
+        )
+        .map_err(ReplayError::Settings)?;
+        let truncated = ReplayError::Truncated(bytes.len());
+        let mut offset = HEADER_BYTES - 4;
+        let mut take = |len: usize| {
+            let end = offset.checked_add(len).ok_or(truncated)?;
+            let field = bytes.get(offset..end).ok_or(truncated)?;
+            offset = end;
+            Ok(field)
+        };
+        let u32_at = |field: &[u8]| u32::from_le_bytes(field.try_into().expect(HEADER_FITS));
+        let word_count = u32_at(take(4)?) as usize;
+        let words: Vec<u16> = take(word_count.saturating_mul(2))?
+            .chunks(2)
+            .map(|chunk| u16::from_le_bytes([chunk[0], chunk[1]]))
+            .collect();
+        let mut keyframes = Vec::new();
+        for _ in 0..u32_at(take(4)?) {
+            let ticks = u32_at(take(4)?);
+            let board_len = u16::from_le_bytes(take(2)?.try_into().expect(HEADER_FITS));
+            keyframes.push(Keyframe {
+                ticks,
+                state: take(KEYFRAME_STATE_BYTES)?.to_vec(),
+                board: take(board_len as usize)?.to_vec(),
+            });
+        }
+        if offset < bytes.len() {
+            return Err(ReplayError::Trailing(bytes.len() - offset));
+        }
+        Ok(Replay {
+            seed,
+            settings,
+            max_words: words.len(),
+            ticks: count_ticks(&words),
+            words,
+            keyframe_pieces: 0,
+            pieces: 0,
+            keyframes,
+        })
+    }
+
+    /// Get the number of bytes of the serialized replay, see `.to_bytes()`.
+    pub fn get_serialized_size(&self) -> usize {
+        let keyframes: usize = self
+            .keyframes
+            .iter()
+            .map(|keyframe| 4 + 2 + keyframe.state.len() + keyframe.board.len())
+            .sum();
+        HEADER_BYTES + self.get_size() + 4 + keyframes
+    }
+
+    /// Serialize the replay field by field, see `.to_bytes()`.
+    /// # Arguments
+    /// - `sink` - A muteable reference to the closure taking every field
+    fn _serialize(&self, sink: &mut dyn FnMut(&[u8])) {
+        sink(&MAGIC);
+        sink(&self.seed.to_le_bytes());
+        sink(&self.settings.to_bytes());
+        sink(&(self.words.len() as u32).to_le_bytes());
+        for word in &self.words {
+            sink(&word.to_le_bytes());
+        }
+        sink(&(self.keyframes.len() as u32).to_le_bytes());
+        for keyframe in &self.keyframes {
+            sink(&keyframe.ticks.to_le_bytes());
+            sink(&(keyframe.board.len() as u16).to_le_bytes());
+            sink(&keyframe.state);
+            sink(&keyframe.board);
+        }
+    }
+
+    /// Iterate over the recorded inputs, one per tick.
+    pub fn inputs(&self) -> impl Iterator<Item = InputState> + '_ {
+        self.words
+            .iter()
+            .flat_map(|word| {
+                let (held, count) = match word & RUN_FLAG {
+                    0 => (*word, 1),
+                    _ => (0, word & MAX_RUN),
+                };
+                (0..count).map(move |_| held)
+            })
+            .scan(InputState::default(), |previous, held| {
+                *previous = previous.next_bits(held);
+                Some(*previous)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Replay, ReplayError, HEADER_BYTES};
+    use crate::ai::{Autopilot, Lookahead};
+    use crate::base64::{encoded_len, Base64Error};
+    use crate::game::{Game, GameState};
+    use crate::input::{Action, InputState};
+    use crate::settings::{Settings, SettingsError};
+
+    #[test]
+    fn test_encoding() {
+        // Idle ticks collapse into a single word, and recording stops once the replay is full.
+        let mut replay = Replay::new(Settings::default(), 0, 4);
+        let held: [&[Action]; 6] = [&[Action::Left], &[], &[], &[], &[Action::Left], &[]];
+        let mut input = InputState::default();
+        let mut recorded = Vec::new();
+        for actions in held {
+            input = input.next(actions);
+            recorded.push(input);
+            assert!(replay.record(&input));
+        }
+        assert_eq!(replay.get_size(), 8);
+        assert_eq!(replay.get_ticks(), 6);
+        assert_eq!(replay.inputs().collect::<Vec<InputState>>(), recorded);
+        assert!(!replay.record(&InputState::from_action(Action::Right)));
+        assert!(replay.record(&InputState::default()));
+        assert_eq!(replay.get_ticks(), 7);
+    }
+
+    #[test]
+    fn test_bytes() {
+        // A replay survives a round trip through its bytes, ticks and all.
+        let settings = Settings {
+            das: 8,
+            ..Default::default()
+        };
+        let mut replay = Replay::new(settings, 158, 16);
+        let mut input = InputState::default();
+        for held in [&[Action::Right][..], &[], &[], &[Action::HardDrop]] {
+            input = input.next(held);
+            replay.record(&input);
+        }
+        let bytes = replay.to_bytes();
+        assert_eq!(bytes.len(), HEADER_BYTES + 6 + 4);
+        let read = Replay::from_bytes(&bytes).unwrap();
+        assert_eq!(read.get_seed(), 158);
+        assert_eq!(read.get_settings(), &settings);
+        assert_eq!(read.get_ticks(), 4);
+        assert!(read.inputs().eq(replay.inputs()));
+        // Invalid bytes are rejected.
+        assert_eq!(
+            Replay::from_bytes(b"TRPL").unwrap_err(),
+            ReplayError::Truncated(4)
+        );
+        assert_eq!(
+            Replay::from_bytes(&bytes[..bytes.len() - 1]).unwrap_err(),
+            ReplayError::Truncated(bytes.len() - 1)
+        );
+        let mut other = bytes.clone();
+        other.push(0);
+        assert_eq!(
+            Replay::from_bytes(&other).unwrap_err(),
+            ReplayError::Trailing(1)
+        );
+        other = bytes.clone();
+        other[0] = b'X';
+        assert_eq!(Replay::from_bytes(&other).unwrap_err(), ReplayError::Magic);
+        other = bytes.clone();
+        other[12] = 0;
+        assert_eq!(
+            Replay::from_bytes(&other).unwrap_err(),
+            ReplayError::Settings(SettingsError::Version(0))
+        );
+    }
+
+    #[test]
+    fn test_base64() {
+        // A replay survives a round trip through base64 broken into lines, with the bytes of `.to_bytes()`.
+        let mut replay = Replay::new(Settings::default(), 7, 16);
+        let mut input = InputState::default();
+        for held in [&[Action::Left][..], &[], &[Action::Left, Action::RotateCw]] {
+            input = input.next(held);
+            replay.record(&input);
+        }
+        let mut text = vec![0; replay.get_base64_size()];
+        assert_eq!(replay.to_base64_into(&mut text), Ok(text.len()));
+        assert_eq!(text.len(), encoded_len(replay.to_bytes().len()));
+        let lines: Vec<u8> = text
+            .chunks(76)
+            .flat_map(|line| line.iter().chain(b"\n"))
+            .copied()
+            .collect();
+        let mut buf = vec![0; text.len() / 4 * 3];
+        let read = Replay::from_base64(&lines, &mut buf).unwrap();
+        assert_eq!(read.to_bytes(), replay.to_bytes());
+        assert!(read.inputs().eq(replay.inputs()));
+        // Short buffers and stray characters are rejected.
+        let mut short = vec![0; text.len() - 1];
+        assert_eq!(
+            replay.to_base64_into(&mut short),
+            Err(ReplayError::Base64(Base64Error::BufferTooSmall(text.len())))
+        );
+        text[10] = b'*';
+        assert_eq!(
+            Replay::from_base64(&text, &mut buf).unwrap_err(),
+            ReplayError::Base64(Base64Error::Character(10))
+        );
+    }
+
+    #[test]
+    fn test_keyframes() {
+        // Record a game of the autopilot with a keyframe every 5 pieces.
+        let mut game = Game::new(Settings::default(), 193);
+        let mut replay = Replay::new(Settings::default(), 193, 1 << 16);
+        replay.set_keyframe_pieces(5);
+        let mut autopilot = Autopilot::default();
+        let mut input = InputState::from_action(Action::Start);
+        for _ in 0..2000 {
+            replay.record(&input);
+            if game.tick(&input).locked.is_some() {
+                replay.record_lock(&game).unwrap();
+            }
+            input = match game.get_state() {
+                GameState::Playing => {
+                    autopilot.next_input(game.get_board(), game.get_piece(), &Lookahead::default())
+                }
+                _ => InputState::default(),
+            };
+        }
+        let keyframes = replay.get_keyframes();
+        assert!(keyframes.len() >= 4);
+        // The keyframes of the sparse board stay far below a byte per cell.
+        assert!(keyframes
+            .iter()
+            .all(|keyframe| keyframe.get_board_size() < 100));
+        // Seeking to the end starts from the last keyframe and matches the full playback.
+        let full = Game::play_replay(&replay);
+        let last = keyframes.last().unwrap().get_ticks();
+        assert!(last > 0 && last <= replay.get_ticks());
+        let sought = Game::seek_replay(&replay, replay.get_ticks()).unwrap();
+        assert_eq!(sought.board_hash(), full.board_hash);
+        assert_eq!(sought.get_score().get_points(), full.points);
+        // Between two keyframes, and after a round trip through the bytes.
+        let read = Replay::from_bytes(&replay.to_bytes()).unwrap();
+        assert_eq!(read.get_keyframes(), keyframes);
+        let tick = (keyframes[1].get_ticks() + keyframes[2].get_ticks()) / 2;
+        let mut expected = Game::new(Settings::default(), 193);
+        for input in replay.inputs().take(tick as usize) {
+            expected.tick(&input);
+        }
+        let sought = Game::seek_replay(&read, tick).unwrap();
+        assert_eq!(sought.board_hash(), expected.board_hash());
+        assert_eq!(
+            sought.get_piece().get_cells(),
+            expected.get_piece().get_cells()
+        );
+    }
+}
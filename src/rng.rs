@@ -0,0 +1,125 @@
+pub mod rosc;
+
+use crate::error::TetrisError;
+use crate::savestate::{Persist, Reader, Writer};
+
+/// The multiplier of the underlying 64 bit linear congruential generator.
+const PCG_MULTIPLIER: u64 = 6_364_136_223_846_793_005;
+
+/// The stream of the generator, the one of the demo of the reference implementation so its output serves as test vector.
+const PCG_STREAM: u64 = 54;
+
+/// The increment of the underlying linear congruential generator, derived from the stream.
+const PCG_INCREMENT: u64 = (PCG_STREAM << 1) | 1;
+
+/// A source of random numbers, so the game logic does not depend on a particular generator.
+pub trait RngSource {
+    /// Get the next random `u32`.
+    fn next_u32(&mut self) -> u32;
+
+    /// Get a random number in the range [0, bound), without bias.
+    /// # Arguments
+    /// - `bound` - The exclusive upper bound, larger than 0
+    /// # Returns
+    /// - `u32` - The random number
+    fn below(&mut self, bound: u32) -> u32 {
+        // Reject the lowest 2^32 % bound numbers, so every remainder is equally likely.
+        let threshold = bound.wrapping_neg() % bound;
+        loop {
+            let number = self.next_u32();
+            if number >= threshold {
+                return number % bound;
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct GameRng {
+    /// The single source of randomness of a game, the 32 bit PCG generator PCG-XSH-RR.
+    ///
+    /// The algorithm is fixed and free of platform dependent behavior, so two devices seeded
+    /// with the same `u64` draw the same numbers, e.g. to race the same piece sequence in versus.
+    /// Seeding follows `pcg32_srandom_r(seed, 54)` of the reference implementation at <https://www.pcg-random.org>.
+    /// # Attributes
+    /// - `seed` - The seed the generator was created with
+    /// - `state` - The 64 bit state of the linear congruential generator
+    seed: u64,
+    state: u64,
+}
+
+impl GameRng {
+    /// Create a new generator from a seed.
+    /// # Arguments
+    /// - `seed` - Any `u64`, including 0
+    /// # Returns
+    /// - `GameRng` - The seeded generator
+    pub fn new(seed: u64) -> Self {
+        let mut rng = GameRng { seed, state: 0 };
+        rng._step();
+        rng.state = rng.state.wrapping_add(seed);
+        rng._step();
+        rng
+    }
+
+    /// Get the seed the generator was created with.
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Advance the linear congruential generator.
+    fn _step(&mut self) {
+        self.state = self
+            .state
+            .wrapping_mul(PCG_MULTIPLIER)
+            .wrapping_add(PCG_INCREMENT);
+    }
+}
+
+impl RngSource for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        let state = self.state;
+        self._step();
+        let xorshifted = (((state >> 18) ^ state) >> 27) as u32;
+        xorshifted.rotate_right((state >> 59) as u32)
+    }
+}
+
+impl Persist for GameRng {
+    fn save(&self, out: &mut Writer) {
+        out.u64(self.seed);
+        out.u64(self.state);
+    }
+
+    fn restore(&mut self, input: &mut Reader) -> Result<(), TetrisError> {
+        self.seed = input.u64()?;
+        self.state = input.u64()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GameRng, RngSource};
+
+    #[test]
+    fn test_reference() {
+        // The first outputs of `pcg32_srandom_r(&rng, 42, 54)` in the demo of the reference implementation.
+        let mut rng = GameRng::new(42);
+        let outputs: Vec<u32> = (0..6).map(|_| rng.next_u32()).collect();
+        assert_eq!(
+            outputs,
+            vec![0xa15c02b7, 0x7b47f409, 0xba1d3330, 0x83d2f293, 0xbfa4784b, 0xcbed606e]
+        );
+    }
+
+    #[test]
+    fn test_below() {
+        let mut rng = GameRng::new(7);
+        let mut counts = [0; 7];
+        for _ in 0..7000 {
+            counts[rng.below(7) as usize] += 1;
+        }
+        assert!(counts.iter().all(|&count| (900..1100).contains(&count)));
+    }
+}
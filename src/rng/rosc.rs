@@ -0,0 +1,133 @@
+/// The number of raw bits sampled from the ring oscillator for a seed.
+///
+/// Von Neumann debiasing keeps at most a quarter of them, so even a heavily biased oscillator yields
+/// well over the 64 bits of the seed.
+pub const ROSC_SAMPLES: usize = 4096;
+
+/// Remove the bias of a source of independent bits with the von Neumann extractor.
+///
+/// The raw bits are taken in pairs, where 01 yields a 0, 10 yields a 1 and 00 or 11 are dropped,
+/// so both outputs are equally likely whatever the probability of a raw 1.
+/// # Arguments
+/// - `bits` - The raw bits
+/// # Returns
+/// - `impl Iterator<Item = bool>` - The unbiased bits, fewer than half of the raw bits
+pub fn debias(bits: impl IntoIterator<Item = bool>) -> impl Iterator<Item = bool> {
+    let mut bits = bits.into_iter();
+    core::iter::from_fn(move || loop {
+        let first = bits.next()?;
+        if first != bits.next()? {
+            return Some(first);
+        }
+    })
+}
+
+/// Accumulate the debiased raw bits into a seed, mixing in a fallback value.
+///
+/// Every debiased bit is folded into the seed, so the bits beyond the first 64 still add entropy.
+/// The fallback, e.g. the timer at boot, keeps the seed varying when the source is stuck and yields
+/// no bits at all.
+/// # Arguments
+/// - `bits` - The raw bits, see `debias()`
+/// - `fallback` - The value to mix into the seed
+/// # Returns
+/// - `u64` - The seed for `GameRng::new()`
+pub fn seed_from_bits(bits: impl IntoIterator<Item = bool>, fallback: u64) -> u64 {
+    let seed = debias(bits).fold(0, |seed: u64, bit| seed.rotate_left(1) ^ bit as u64);
+    seed ^ _mix(fallback)
+}
+
+/// Scramble a value with the finalizer of SplitMix64, so close fallback values give unrelated seeds.
+/// # Arguments
+/// - `value` - The value to scramble
+/// # Returns
+/// - `u64` - The scrambled value
+fn _mix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(feature = "rp2040")]
+pub use hardware::{seed_from_rosc, seed_from_rosc_and_timer, Rosc};
+
+#[cfg(feature = "rp2040")]
+mod hardware {
+    use super::{seed_from_bits, ROSC_SAMPLES};
+    use rp2040_hal::rosc::{Enabled, RingOscillator};
+    use rp2040_hal::Timer;
+
+    /// The running ring oscillator of the RP2040.
+    pub type Rosc = RingOscillator<Enabled>;
+
+    /// Create a seed from the jitter of the ring oscillator.
+    /// # Arguments
+    /// - `rosc` - A muteable reference to the running `Rosc`
+    /// # Returns
+    /// - `u64` - The seed for `GameRng::new()`
+    pub fn seed_from_rosc(rosc: &mut Rosc) -> u64 {
+        _sample(rosc, 0)
+    }
+
+    /// Create a seed from the jitter of the ring oscillator, mixed with the time since boot.
+    /// # Arguments
+    /// - `rosc` - A muteable reference to the running `Rosc`
+    /// - `timer` - A reference to the `Timer`, read after sampling so it includes the time sampling took
+    /// # Returns
+    /// - `u64` - The seed for `GameRng::new()`
+    pub fn seed_from_rosc_and_timer(rosc: &mut Rosc, timer: &Timer) -> u64 {
+        _sample(rosc, timer.get_counter().ticks())
+    }
+
+    /// Sample `ROSC_SAMPLES` raw bits of the ring oscillator into a seed.
+    fn _sample(rosc: &mut Rosc, fallback: u64) -> u64 {
+        let bits = (0..ROSC_SAMPLES).map(|_| rosc.get_random_bit());
+        seed_from_bits(bits, fallback)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{debias, seed_from_bits, ROSC_SAMPLES};
+    use crate::rng::{GameRng, RngSource};
+
+    /// Check that the number of ones is within 3 standard deviations of half the bits.
+    fn monobit(bits: &[bool]) -> bool {
+        let ones = bits.iter().filter(|bit| **bit).count() as i64;
+        let total = bits.len() as i64;
+        (2 * ones - total).pow(2) < 9 * total
+    }
+
+    /// A synthetic source of independent bits that are 1 three times out of four.
+    fn biased(rng: &mut GameRng) -> impl Iterator<Item = bool> + '_ {
+        core::iter::repeat_with(move || rng.below(4) != 0)
+    }
+
+    #[test]
+    fn test_debias() {
+        assert_eq!(
+            debias([false, true, true, false, true, true, false, false, true]).collect::<Vec<_>>(),
+            [false, true],
+        );
+        let mut rng = GameRng::new(147);
+        let raw: Vec<bool> = biased(&mut rng).take(8000).collect();
+        assert!(!monobit(&raw));
+        let debiased: Vec<bool> = debias(raw).collect();
+        assert!(debiased.len() > 1000);
+        assert!(monobit(&debiased));
+    }
+
+    #[test]
+    fn test_seed_from_bits() {
+        let mut rng = GameRng::new(147);
+        let bits: Vec<bool> = (0..32)
+            .map(|_| seed_from_bits(biased(&mut rng).take(ROSC_SAMPLES), 0))
+            .flat_map(|seed| (0..64).map(move |bit| seed & (1 << bit) != 0))
+            .collect();
+        assert!(monobit(&bits));
+        // A stuck source still gives different seeds for different fallbacks.
+        let stuck = || [true; ROSC_SAMPLES];
+        assert_ne!(seed_from_bits(stuck(), 1), seed_from_bits(stuck(), 2));
+    }
+}
@@ -1,18 +1,21 @@
-use array2d::Array2D;
-use std::clone::Clone;
+pub mod kicks;
+
+use crate::grid::Array2D;
+use alloc::vec::Vec;
+use core::clone::Clone;
 
 /// Transpose a matrix by turning rows into columns or vise versa.
 /// # Arguments
 /// - `matrix` - A reference to an Array2D of a generic which can be cloned
 fn transpose<T: Clone>(matrix: &Array2D<T>) -> Array2D<T> {
-    Array2D::from_columns(&matrix.as_rows()).unwrap()
+    Array2D::from_columns(&matrix.as_rows()).expect("the rows of a grid have the same length")
 }
 /// Rotate a matrix 90 degrees clockwise by transposing and reversing the column order.
 /// # Arguments
 /// - `matrix` - A reference to an Array2D of a generic which can be cloned
 pub fn rotate_cw<T: Clone>(matrix: &Array2D<T>) -> Array2D<T> {
     let columns: Vec<Vec<T>> = transpose(matrix).as_columns().into_iter().rev().collect();
-    Array2D::from_columns(&columns).unwrap()
+    Array2D::from_columns(&columns).expect("the columns of a grid have the same length")
 }
 
 /// Rotate a matrix 90 degrees counterclockwise by transposing and reversing the row order.
@@ -20,7 +23,7 @@ pub fn rotate_cw<T: Clone>(matrix: &Array2D<T>) -> Array2D<T> {
 /// - `matrix` - A reference to an Array2D of a generic which can be cloned
 pub fn rotate_ccw<T: Clone>(matrix: &Array2D<T>) -> Array2D<T> {
     let rows: Vec<Vec<T>> = transpose(matrix).as_rows().into_iter().rev().collect();
-    Array2D::from_rows(&rows).unwrap()
+    Array2D::from_rows(&rows).expect("the rows of a grid have the same length")
 }
 
 /// Generate all 4 matrices resulting from 90 degrees clockwise rotation in order.
@@ -35,7 +38,7 @@ pub fn generate_matrices<T: Clone>(matrix: Array2D<T>) -> [Array2D<T>; 4] {
 
 #[cfg(test)]
 mod tests {
-    use array2d::Array2D;
+    use crate::grid::Array2D;
 
     use crate::rotation::{rotate_ccw, rotate_cw, transpose};
 
@@ -0,0 +1,431 @@
+//! The wall kicks of the Super Rotation System, and a check of the stored tables against the published guideline data.
+//!
+//! The guideline publishes the kicks as (x, y) offsets with y pointing up, while the engine stores and applies them as
+//! [row, col] offsets with the row pointing down. `verify_table()` converts the published offsets and compares every
+//! stored one, so a sign error in either convention shows up in its report.
+
+use crate::coordinate::Coordinate;
+use crate::tetrominoes::{TetrominoShape, SHAPES};
+use alloc::vec::Vec;
+
+/// The four rotation states of a tetromino, in clockwise order starting from the spawn state.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RotationState {
+    Spawn,
+    Right,
+    Two,
+    Left,
+}
+
+impl RotationState {
+    /// Get the rotation state matching an index into the rotated masks of a `Tetromino`.
+    /// # Arguments
+    /// - `index` - The mask index, wrapping every 4 rotations
+    /// # Returns
+    /// - `RotationState` - The matching rotation state
+    pub fn from_index(index: usize) -> Self {
+        match index % 4 {
+            0 => RotationState::Spawn,
+            1 => RotationState::Right,
+            2 => RotationState::Two,
+            _ => RotationState::Left,
+        }
+    }
+}
+
+// The kick tables of the Super Rotation System. The guideline publishes these as (x, y) with y pointing up,
+// they are stored here as [row, col] offsets with the row pointing down to match `Coordinate`.
+const KICKS_NONE: [[isize; 2]; 1] = [[0, 0]];
+
+const KICKS_JLSTZ_0_R: [[isize; 2]; 5] = [[0, 0], [0, -1], [-1, -1], [2, 0], [2, -1]];
+const KICKS_JLSTZ_R_0: [[isize; 2]; 5] = [[0, 0], [0, 1], [1, 1], [-2, 0], [-2, 1]];
+const KICKS_JLSTZ_R_2: [[isize; 2]; 5] = [[0, 0], [0, 1], [1, 1], [-2, 0], [-2, 1]];
+const KICKS_JLSTZ_2_R: [[isize; 2]; 5] = [[0, 0], [0, -1], [-1, -1], [2, 0], [2, -1]];
+const KICKS_JLSTZ_2_L: [[isize; 2]; 5] = [[0, 0], [0, 1], [-1, 1], [2, 0], [2, 1]];
+const KICKS_JLSTZ_L_2: [[isize; 2]; 5] = [[0, 0], [0, -1], [1, -1], [-2, 0], [-2, -1]];
+const KICKS_JLSTZ_L_0: [[isize; 2]; 5] = [[0, 0], [0, -1], [1, -1], [-2, 0], [-2, -1]];
+const KICKS_JLSTZ_0_L: [[isize; 2]; 5] = [[0, 0], [0, 1], [-1, 1], [2, 0], [2, 1]];
+
+const KICKS_I_0_R: [[isize; 2]; 5] = [[0, 0], [0, -2], [0, 1], [1, -2], [-2, 1]];
+const KICKS_I_R_0: [[isize; 2]; 5] = [[0, 0], [0, 2], [0, -1], [-1, 2], [2, -1]];
+const KICKS_I_R_2: [[isize; 2]; 5] = [[0, 0], [0, -1], [0, 2], [-2, -1], [1, 2]];
+const KICKS_I_2_R: [[isize; 2]; 5] = [[0, 0], [0, 1], [0, -2], [2, 1], [-1, -2]];
+const KICKS_I_2_L: [[isize; 2]; 5] = [[0, 0], [0, 2], [0, -1], [-1, 2], [2, -1]];
+const KICKS_I_L_2: [[isize; 2]; 5] = [[0, 0], [0, -2], [0, 1], [1, -2], [-2, 1]];
+const KICKS_I_L_0: [[isize; 2]; 5] = [[0, 0], [0, 1], [0, -2], [2, 1], [-1, -2]];
+const KICKS_I_0_L: [[isize; 2]; 5] = [[0, 0], [0, -1], [0, 2], [-2, -1], [1, 2]];
+
+/// Get the wall kick offsets to try, in order, when rotating between two adjacent rotation states.
+/// # Arguments
+/// - `shape` - A reference to the `TetrominoShape` being rotated
+/// - `from` - The current rotation state
+/// - `to` - The targeted rotation state, one step clockwise or counterclockwise from `from`
+/// # Returns
+/// - `&'static [[isize; 2]]` - The [row, col] offsets to try, starting with the unkicked rotation
+pub fn kicks(
+    shape: &TetrominoShape,
+    from: RotationState,
+    to: RotationState,
+) -> &'static [[isize; 2]] {
+    use RotationState::*;
+    match shape {
+        TetrominoShape::O => &KICKS_NONE,
+        TetrominoShape::I => match (from, to) {
+            (Spawn, Right) => &KICKS_I_0_R,
+            (Right, Spawn) => &KICKS_I_R_0,
+            (Right, Two) => &KICKS_I_R_2,
+            (Two, Right) => &KICKS_I_2_R,
+            (Two, Left) => &KICKS_I_2_L,
+            (Left, Two) => &KICKS_I_L_2,
+            (Left, Spawn) => &KICKS_I_L_0,
+            (Spawn, Left) => &KICKS_I_0_L,
+            _ => &KICKS_NONE,
+        },
+        _ => match (from, to) {
+            (Spawn, Right) => &KICKS_JLSTZ_0_R,
+            (Right, Spawn) => &KICKS_JLSTZ_R_0,
+            (Right, Two) => &KICKS_JLSTZ_R_2,
+            (Two, Right) => &KICKS_JLSTZ_2_R,
+            (Two, Left) => &KICKS_JLSTZ_2_L,
+            (Left, Two) => &KICKS_JLSTZ_L_2,
+            (Left, Spawn) => &KICKS_JLSTZ_L_0,
+            (Spawn, Left) => &KICKS_JLSTZ_0_L,
+            _ => &KICKS_NONE,
+        },
+    }
+}
+
+/// Apply a kick, or any other signed offset, to the position of a piece.
+/// # Arguments
+/// - `coord` - The `Coordinate` to offset
+/// - `offset` - The signed [row, col] offset, with the row pointing down as returned by `kicks()`
+/// # Returns
+/// - `Option<Coordinate>` - The offset coordinate, or `None` if it would become negative
+pub fn apply_kick(coord: Coordinate, offset: [isize; 2]) -> Option<Coordinate> {
+    Some(Coordinate {
+        row: coord.row.checked_add_signed(offset[0])?,
+        col: coord.col.checked_add_signed(offset[1])?,
+    })
+}
+
+/// The eight rotations between adjacent states, in the order of the published tables.
+pub const TRANSITIONS: [(RotationState, RotationState); 8] = [
+    (RotationState::Spawn, RotationState::Right),
+    (RotationState::Right, RotationState::Spawn),
+    (RotationState::Right, RotationState::Two),
+    (RotationState::Two, RotationState::Right),
+    (RotationState::Two, RotationState::Left),
+    (RotationState::Left, RotationState::Two),
+    (RotationState::Left, RotationState::Spawn),
+    (RotationState::Spawn, RotationState::Left),
+];
+
+// The published guideline tables as (x, y) with y pointing up, copied as written and only used to verify the tables
+// above, in the order of `TRANSITIONS`.
+const GUIDELINE_JLSTZ: [[[isize; 2]; 5]; 8] = [
+    [[0, 0], [-1, 0], [-1, 1], [0, -2], [-1, -2]],
+    [[0, 0], [1, 0], [1, -1], [0, 2], [1, 2]],
+    [[0, 0], [1, 0], [1, -1], [0, 2], [1, 2]],
+    [[0, 0], [-1, 0], [-1, 1], [0, -2], [-1, -2]],
+    [[0, 0], [1, 0], [1, 1], [0, -2], [1, -2]],
+    [[0, 0], [-1, 0], [-1, -1], [0, 2], [-1, 2]],
+    [[0, 0], [-1, 0], [-1, -1], [0, 2], [-1, 2]],
+    [[0, 0], [1, 0], [1, 1], [0, -2], [1, -2]],
+];
+
+const GUIDELINE_I: [[[isize; 2]; 5]; 8] = [
+    [[0, 0], [-2, 0], [1, 0], [-2, -1], [1, 2]],
+    [[0, 0], [2, 0], [-1, 0], [2, 1], [-1, -2]],
+    [[0, 0], [-1, 0], [2, 0], [-1, 2], [2, -1]],
+    [[0, 0], [1, 0], [-2, 0], [1, -2], [-2, 1]],
+    [[0, 0], [2, 0], [-1, 0], [2, 1], [-1, -2]],
+    [[0, 0], [-2, 0], [1, 0], [-2, -1], [1, 2]],
+    [[0, 0], [1, 0], [-2, 0], [1, -2], [-2, 1]],
+    [[0, 0], [-1, 0], [2, 0], [-1, 2], [2, -1]],
+];
+
+/// A single kick of the stored tables.
+/// # Attributes
+/// - `shape` - The `TetrominoShape` being rotated
+/// - `from` - The rotation state before the rotation
+/// - `to` - The rotation state after the rotation
+/// - `index` - The position of the kick in its table, 0 for the unkicked rotation
+/// - `offset` - The stored [row, col] offset, with the row pointing down
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct KickEntry {
+    pub shape: TetrominoShape,
+    pub from: RotationState,
+    pub to: RotationState,
+    pub index: usize,
+    pub offset: [isize; 2],
+}
+
+/// The outcome of `verify_table()`.
+/// # Attributes
+/// - `entries` - Every kick of the stored tables, by shape, transition and index
+/// - `mismatches` - The kicks that differ from the published table, or are missing from or extra to it
+#[derive(Clone, Debug, PartialEq)]
+pub struct KickReport {
+    pub entries: Vec<KickEntry>,
+    pub mismatches: Vec<KickEntry>,
+}
+
+impl KickReport {
+    /// Check whether the stored tables match the published guideline data.
+    /// # Returns
+    /// - `bool` - Whether (`true`) or not (`false`) every kick matches
+    pub fn is_valid(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+/// Get a kick of the published guideline tables, converted to a [row, col] offset.
+/// # Arguments
+/// - `shape` - A reference to the `TetrominoShape` being rotated
+/// - `transition` - The index of the rotation in `TRANSITIONS`
+/// - `index` - The position of the kick in its table
+/// # Returns
+/// - `Option<[isize; 2]>` - The offset, or `None` if the table has no such kick
+fn _guideline_kick(shape: &TetrominoShape, transition: usize, index: usize) -> Option<[isize; 2]> {
+    let [x, y] = match shape {
+        TetrominoShape::O => *[[0, 0]].get(index)?,
+        TetrominoShape::I => *GUIDELINE_I[transition].get(index)?,
+        _ => *GUIDELINE_JLSTZ[transition].get(index)?,
+    };
+    // Right stays right, while up becomes a smaller row.
+    Some([-y, x])
+}
+
+/// Compare every stored kick of every shape with the published guideline tables.
+/// # Returns
+/// - `KickReport` - All stored kicks, and the ones that do not match
+pub fn verify_table() -> KickReport {
+    let mut report = KickReport {
+        entries: Vec::new(),
+        mismatches: Vec::new(),
+    };
+    for shape in SHAPES {
+        for (transition, (from, to)) in TRANSITIONS.iter().enumerate() {
+            let stored = kicks(&shape, *from, *to);
+            // Walk the longer of both tables, so a kick missing from either side is reported.
+            let published =
+                (0..).take_while(|index| _guideline_kick(&shape, transition, *index).is_some());
+            for index in 0..stored.len().max(published.count()) {
+                let entry = KickEntry {
+                    shape,
+                    from: *from,
+                    to: *to,
+                    index,
+                    offset: stored.get(index).copied().unwrap_or([0, 0]),
+                };
+                let expected = _guideline_kick(&shape, transition, index);
+                if index < stored.len() {
+                    report.entries.push(entry);
+                }
+                if index >= stored.len() || expected != Some(entry.offset) {
+                    report.mismatches.push(entry);
+                }
+            }
+        }
+    }
+    report
+}
+
+/// Get the position of the trimmed tetromino mask within its rotation box.
+///
+/// The masks of a `Tetromino` are trimmed to the filled cells, while the rotation system rotates pieces
+/// around the center of a 3x3 (or 4x4 for the I piece) box. This offset converts between both.
+/// # Arguments
+/// - `shape` - A reference to the `TetrominoShape`
+/// - `state` - The rotation state of the mask
+/// # Returns
+/// - `[usize; 2]` - The [row, col] of the top-left element of the mask within the rotation box
+pub fn mask_offset(shape: &TetrominoShape, state: RotationState) -> [usize; 2] {
+    match shape {
+        TetrominoShape::O => [0, 0],
+        TetrominoShape::I => match state {
+            RotationState::Spawn => [1, 0],
+            RotationState::Right => [0, 2],
+            RotationState::Two => [2, 0],
+            RotationState::Left => [0, 1],
+        },
+        _ => match state {
+            RotationState::Spawn => [0, 0],
+            RotationState::Right => [0, 1],
+            RotationState::Two => [1, 0],
+            RotationState::Left => [0, 0],
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply_kick, kicks, verify_table, RotationState, TRANSITIONS};
+    use crate::board::Board;
+    use crate::coordinate::Coordinate;
+    use crate::grid::Array2D;
+    use crate::piece::{ActivePiece, RotationResult};
+    use crate::tetrominoes::{Tetromino, TetrominoShape, SHAPES};
+
+    /// A rotation on a fixture board and the cells the piece should end up on.
+    struct KickCase {
+        shape: TetrominoShape,
+        from: RotationState,
+        clockwise: bool,
+        coord: [usize; 2],
+        kick_index: usize,
+        rows: &'static [&'static str],
+    }
+
+    /// Parse a fixture, with `x` for a filled cell, `o` for a cell of the rotated piece and `.` for an empty cell.
+    fn parse(rows: &[&str]) -> (Board<bool>, Vec<Coordinate>) {
+        let mut filled = Vec::new();
+        let mut piece = Vec::new();
+        for (row, line) in rows.iter().enumerate() {
+            for (col, char) in line.chars().enumerate() {
+                filled.push(char == 'x');
+                if char == 'o' {
+                    piece.push(Coordinate { row, col });
+                }
+            }
+        }
+        let array = Array2D::from_row_major(&filled, rows.len(), rows[0].len()).unwrap();
+        (Board::from_array(&array, false), piece)
+    }
+
+    const CASES: [KickCase; 5] = [
+        // The T-spin triple: the T rests on the overhang and rotates clockwise down into the slot with the last kick,
+        // one column left and two rows down.
+        KickCase {
+            shape: TetrominoShape::T,
+            from: RotationState::Spawn,
+            clockwise: true,
+            coord: [1, 1],
+            kick_index: 4,
+            rows: &[
+                "......", //
+                "xx....", //
+                "x.....", //
+                "xoxxxx", //
+                "xooxxx", //
+                "xoxxxx", //
+            ],
+        },
+        // An upright I against the left wall lies down two columns to the right.
+        KickCase {
+            shape: TetrominoShape::I,
+            from: RotationState::Right,
+            clockwise: false,
+            coord: [1, 0],
+            kick_index: 1,
+            rows: &[
+                "......", //
+                "......", //
+                "oooo..", //
+                "......", //
+                "......", //
+            ],
+        },
+        KickCase {
+            shape: TetrominoShape::I,
+            from: RotationState::Right,
+            clockwise: true,
+            coord: [1, 0],
+            kick_index: 2,
+            rows: &[
+                "......", //
+                "......", //
+                "......", //
+                "oooo..", //
+                "......", //
+            ],
+        },
+        // An upright I against the right wall lies down two columns to the left.
+        KickCase {
+            shape: TetrominoShape::I,
+            from: RotationState::Left,
+            clockwise: true,
+            coord: [1, 5],
+            kick_index: 2,
+            rows: &[
+                "......", //
+                "......", //
+                "..oooo", //
+                "......", //
+                "......", //
+            ],
+        },
+        KickCase {
+            shape: TetrominoShape::I,
+            from: RotationState::Left,
+            clockwise: false,
+            coord: [1, 5],
+            kick_index: 1,
+            rows: &[
+                "......", //
+                "......", //
+                "......", //
+                "..oooo", //
+                "......", //
+            ],
+        },
+    ];
+
+    #[test]
+    fn test_verify_table() {
+        let report = verify_table();
+        assert!(report.is_valid(), "{:?}", report.mismatches);
+        // The O piece never kicks, every other shape has 5 kicks per rotation.
+        assert_eq!(report.entries.len(), 8 + 6 * 8 * 5);
+        for shape in SHAPES {
+            for (from, to) in TRANSITIONS {
+                assert_eq!(kicks(&shape, from, to)[0], [0, 0]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_apply_kick() {
+        // A negative row is up and a negative col is left.
+        let coord = Coordinate::from_array([5, 5]);
+        assert_eq!(
+            apply_kick(coord, [-2, 1]),
+            Some(Coordinate::from_array([3, 6]))
+        );
+        assert_eq!(
+            apply_kick(coord, [1, -2]),
+            Some(Coordinate::from_array([6, 3]))
+        );
+        assert_eq!(apply_kick(coord, [0, -6]), None);
+    }
+
+    #[test]
+    fn test_cases() {
+        for (number, case) in CASES.iter().enumerate() {
+            let (board, mut expected) = parse(case.rows);
+            let mut tetromino = Tetromino::from(case.shape);
+            while RotationState::from_index(tetromino.get_rotation() as usize) != case.from {
+                tetromino.rotate_cw();
+            }
+            let mut piece = ActivePiece::new(tetromino, Coordinate::from_array(case.coord));
+            assert!(piece.fits(&board), "case {}", number);
+            let result = match case.clockwise {
+                true => piece.try_rotate_cw_with_kicks(&board),
+                false => piece.try_rotate_ccw_with_kicks(&board),
+            };
+            assert_eq!(
+                result,
+                RotationResult::Rotated {
+                    kick_index: case.kick_index
+                },
+                "case {}",
+                number
+            );
+            let mut cells = piece.get_cells().to_vec();
+            cells.sort_by_key(|cell| (cell.row, cell.col));
+            expected.sort_by_key(|cell| (cell.row, cell.col));
+            assert_eq!(cells, expected, "case {}", number);
+        }
+    }
+}
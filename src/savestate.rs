@@ -0,0 +1,574 @@
+//! Save states, serializing a game in progress to resume it after a power cycle.
+//!
+//! # Layout
+//! A save state has a fixed layout for a board size, all numbers little-endian:
+//! - `MAGIC`, `SAVE_VERSION`, the number of rows and columns of the board and the serialized `Settings`
+//! - `STATE_BYTES` bytes of every component of the game, see `Game::save_state_into()`
+//! - a byte per cell of the board, see `Cell::to_u8()`
+//! - the CRC-32 of everything before it
+//!
+//! Every component writes its own fields through the `Persist` trait, the parts derived from the settings, e.g. the
+//! lock delay or the auto-repeat rates, are rebuilt from the settings the game is restored with.
+//!
+//! The board follows the fixed-size state, so `read_board()` decodes it without restoring the game, e.g. to draw a
+//! thumbnail of every stored save state to pick from.
+use crate::board::BoardOps;
+use crate::cell::Cell;
+use crate::coordinate::Coordinate;
+use crate::error::TetrisError;
+use crate::game::{Cause, GameState, NameEntry, Outcome};
+use crate::settings::SETTINGS_BYTES;
+use crate::storage::_crc32;
+use crate::tetrominoes::{TetrominoShape, SHAPES};
+use core::fmt;
+
+/// The bytes every save state starts with.
+pub const MAGIC: [u8; 4] = *b"TSAV";
+
+/// The version of the layout, save states of other versions are rejected.
+pub const SAVE_VERSION: u8 = 8;
+
+/// The number of bytes before the state of the components: the magic, the version, the shape and the settings.
+pub(crate) const HEADER_BYTES: usize = MAGIC.len() + 3 + SETTINGS_BYTES;
+
+/// The number of bytes of the state of the components, independent of the size of the board.
+///
+/// The components take 290 bytes, the rest is zeroed and leaves room for new fields without moving the board.
+pub const STATE_BYTES: usize = 296;
+
+/// The number of bytes of a `GameState`, its kind and the largest payload, of `GameState::Finished`.
+pub const GAME_STATE_BYTES: usize = 1 + 16;
+
+/// The byte standing for `None` in an optional byte.
+const NONE: u8 = u8::MAX;
+
+/// Get the number of bytes of a save state of a board.
+/// # Arguments
+/// - `rows` - The number of rows of the board
+/// - `cols` - The number of columns of the board
+/// # Returns
+/// - `usize` - The number of bytes, e.g. 607 for the standard 20 by 10 board
+pub const fn save_state_bytes(rows: usize, cols: usize) -> usize {
+    HEADER_BYTES + STATE_BYTES + rows * cols + 4
+}
+
+/// Why a save state could not be written or read.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SaveError {
+    /// The buffer is shorter than the save state, with the number of bytes of the save state.
+    BufferTooSmall(usize),
+    /// The bytes end before the save state does, with the number of bytes.
+    Truncated(usize),
+    /// The bytes do not start with the magic of a save state.
+    Magic,
+    /// The save state was written by an unsupported version.
+    Version(u8),
+    /// The save state is of a board of another size, with its number of rows and columns.
+    BoardSize { rows: usize, cols: usize },
+    /// The CRC of the save state is wrong.
+    Crc,
+    /// A field holds a value no game can be in.
+    Invalid,
+    /// The game demos itself in the attract mode, which is no game to resume.
+    Attract,
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::BufferTooSmall(length) => {
+                write!(
+                    f,
+                    "the buffer is shorter than the save state of {} bytes",
+                    length
+                )
+            }
+            SaveError::Truncated(length) => {
+                write!(f, "the save state of {} bytes is truncated", length)
+            }
+            SaveError::Magic => f.write_str("not a save state"),
+            SaveError::Version(version) => write!(f, "unsupported version {}", version),
+            SaveError::BoardSize { rows, cols } => {
+                write!(f, "a save state of a {} by {} board", rows, cols)
+            }
+            SaveError::Crc => f.write_str("the CRC of the save state is wrong"),
+            SaveError::Invalid => f.write_str("a field holds an impossible value"),
+            SaveError::Attract => f.write_str("the attract mode cannot be saved"),
+        }
+    }
+}
+
+/// A cursor writing the fields of a save state into a buffer checked to be large enough beforehand.
+/// # Attributes
+/// - `buffer` - The buffer to write into
+/// - `len` - The number of bytes written
+pub(crate) struct Writer<'a> {
+    buffer: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> Writer<'a> {
+    /// Create a writer at the start of a buffer.
+    pub(crate) fn new(buffer: &'a mut [u8]) -> Self {
+        Writer { buffer, len: 0 }
+    }
+
+    /// Get the number of bytes written.
+    pub(crate) fn get_len(&self) -> usize {
+        self.len
+    }
+
+    /// Get the bytes written so far.
+    pub(crate) fn get_written(&self) -> &[u8] {
+        &self.buffer[..self.len]
+    }
+
+    /// Write some bytes, panicking if the buffer was not checked to be large enough.
+    pub(crate) fn bytes(&mut self, bytes: &[u8]) {
+        self.buffer[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+        self.len += bytes.len();
+    }
+
+    pub(crate) fn u8(&mut self, value: u8) {
+        self.bytes(&[value]);
+    }
+
+    pub(crate) fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    pub(crate) fn u32(&mut self, value: u32) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    pub(crate) fn u64(&mut self, value: u64) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    /// Write an optional byte, `None` as `NONE`.
+    pub(crate) fn option_u8(&mut self, value: Option<u8>) {
+        self.u8(value.unwrap_or(NONE));
+    }
+
+    /// Write an optional number as a flag and the number, 0 for `None`.
+    pub(crate) fn option_u32(&mut self, value: Option<u32>) {
+        self.bool(value.is_some());
+        self.u32(value.unwrap_or(0));
+    }
+
+    /// Write a number of a board, at most 255 as the board has at most 64 rows and 27 columns.
+    pub(crate) fn index(&mut self, value: usize) {
+        self.u8(value.min(u8::MAX as usize) as u8);
+    }
+
+    /// Write zeroes up to a fixed number of bytes since an offset, keeping the layout fixed.
+    pub(crate) fn pad_to(&mut self, start: usize, bytes: usize) {
+        while self.len < start + bytes {
+            self.u8(0);
+        }
+    }
+}
+
+/// A cursor reading the fields of a save state.
+/// # Attributes
+/// - `bytes` - The save state
+/// - `offset` - The number of bytes read
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    /// Create a reader at the start of a save state.
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Reader { bytes, offset: 0 }
+    }
+
+    /// Get the number of bytes read.
+    pub(crate) fn get_offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Read some bytes.
+    pub(crate) fn bytes<const N: usize>(&mut self) -> Result<[u8; N], TetrisError> {
+        let bytes = self
+            .bytes
+            .get(self.offset..self.offset + N)
+            .ok_or(SaveError::Truncated(self.bytes.len()))?;
+        self.offset += N;
+        let mut array = [0; N];
+        array.copy_from_slice(bytes);
+        Ok(array)
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, TetrisError> {
+        Ok(self.bytes::<1>()?[0])
+    }
+
+    pub(crate) fn bool(&mut self) -> Result<bool, TetrisError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(SaveError::Invalid.into()),
+        }
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, TetrisError> {
+        Ok(u32::from_le_bytes(self.bytes()?))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, TetrisError> {
+        Ok(u64::from_le_bytes(self.bytes()?))
+    }
+
+    /// Read an optional byte written by `Writer::option_u8()`.
+    pub(crate) fn option_u8(&mut self) -> Result<Option<u8>, TetrisError> {
+        Ok(Some(self.u8()?).filter(|value| *value != NONE))
+    }
+
+    /// Read an optional number written by `Writer::option_u32()`.
+    pub(crate) fn option_u32(&mut self) -> Result<Option<u32>, TetrisError> {
+        let some = self.bool()?;
+        let value = self.u32()?;
+        Ok(some.then_some(value))
+    }
+
+    /// Read a number of a board written by `Writer::index()`.
+    pub(crate) fn index(&mut self) -> Result<usize, TetrisError> {
+        Ok(self.u8()? as usize)
+    }
+
+    /// Skip the padding up to a fixed number of bytes since an offset.
+    pub(crate) fn skip_to(&mut self, start: usize, bytes: usize) -> Result<(), TetrisError> {
+        while self.offset < start + bytes {
+            self.u8()?;
+        }
+        Ok(())
+    }
+}
+
+/// Get the size of the board of a save state from its header, e.g. to allocate a board to restore it on.
+/// # Arguments
+/// - `buf` - The save state written by `Game::save_state_into()`
+/// # Returns
+/// - `Result<Coordinate, TetrisError>` - The number of [rows, cols] of the board, or a `SaveError` if the bytes are
+///   no save state of this version, the rest of the bytes are only checked once read
+pub fn save_state_shape(buf: &[u8]) -> Result<Coordinate, TetrisError> {
+    let mut input = Reader::new(buf);
+    if input.bytes()? != MAGIC {
+        return Err(SaveError::Magic.into());
+    }
+    let version = input.u8()?;
+    if version != SAVE_VERSION {
+        return Err(SaveError::Version(version).into());
+    }
+    Ok(Coordinate::from_array([input.index()?, input.index()?]))
+}
+
+/// Check the header and the CRC of a save state for a board size.
+/// # Arguments
+/// - `buf` - The save state written by `Game::save_state_into()`
+/// - `shape` - The number of [rows, cols] of the board to read the save state onto
+/// # Returns
+/// - `Result<Reader, TetrisError>` - A reader at the settings, or a `SaveError` if the bytes are no valid save state
+///   of this version and board size
+pub(crate) fn open_save_state(buf: &[u8], shape: Coordinate) -> Result<Reader<'_>, TetrisError> {
+    let saved = save_state_shape(buf)?;
+    if saved != shape {
+        return Err(SaveError::BoardSize {
+            rows: saved.row,
+            cols: saved.col,
+        }
+        .into());
+    }
+    let len = save_state_bytes(shape.row, shape.col);
+    let (body, crc) = buf
+        .get(..len)
+        .ok_or(SaveError::Truncated(buf.len()))?
+        .split_at(len - 4);
+    if crc != _crc32(body).to_le_bytes() {
+        return Err(SaveError::Crc.into());
+    }
+    let mut input = Reader::new(body);
+    input.skip_to(0, MAGIC.len() + 3)?;
+    Ok(input)
+}
+
+/// Read the board of a save state without restoring the game.
+/// # Arguments
+/// - `board` - A muteable reference to the board to read into, of the size of the saved board
+/// - `buf` - The save state written by `Game::save_state_into()`
+/// # Returns
+/// - `Result<(), TetrisError>` - A `SaveError` if the bytes are no valid save state of this version and board size
+pub fn read_board<B: BoardOps<Cell>>(board: &mut B, buf: &[u8]) -> Result<(), TetrisError> {
+    let shape = board.get_shape();
+    let mut input = open_save_state(buf, shape)?;
+    input.skip_to(0, HEADER_BYTES + STATE_BYTES)?;
+    for row in 0..shape.row {
+        for col in 0..shape.col {
+            board.set(Coordinate { row, col }, cell_from_u8(input.u8()?)?);
+        }
+    }
+    Ok(())
+}
+
+/// The part of the state of a game a component keeps, written into and read from a save state.
+pub(crate) trait Persist {
+    /// Write the state.
+    /// # Arguments
+    /// - `out` - A muteable reference to the `Writer`
+    fn save(&self, out: &mut Writer);
+
+    /// Read the state written by `.save()` into a component created with the settings of the restored game.
+    /// # Arguments
+    /// - `input` - A muteable reference to the `Reader`
+    /// # Returns
+    /// - `Result<(), TetrisError>` - A `SaveError` if the bytes hold no valid state
+    fn restore(&mut self, input: &mut Reader) -> Result<(), TetrisError>;
+}
+
+/// Get the shape of its index in `SHAPES`.
+/// # Arguments
+/// - `index` - The index, as written for `shape as u8`
+/// # Returns
+/// - `Result<TetrominoShape, TetrisError>` - The shape, or a `SaveError::Invalid` for an index beyond the shapes
+pub(crate) fn shape_from_u8(index: u8) -> Result<TetrominoShape, TetrisError> {
+    SHAPES
+        .get(index as usize)
+        .copied()
+        .ok_or(SaveError::Invalid.into())
+}
+
+/// Get the cell of a byte written by `Cell::to_u8()`, filled cells at the brightness of locked pieces.
+/// # Arguments
+/// - `byte` - The byte
+/// # Returns
+/// - `Result<Cell, TetrisError>` - The cell, or a `SaveError::Invalid` for a byte no cell is written as
+pub(crate) fn cell_from_u8(byte: u8) -> Result<Cell, TetrisError> {
+    match byte {
+        0 => Ok(Cell::Empty),
+        8 => Ok(Cell::Garbage),
+        shape => Ok(Cell::locked(shape_from_u8(shape - 1)?)),
+    }
+}
+
+impl Persist for Cause {
+    fn save(&self, out: &mut Writer) {
+        out.u8(*self as u8);
+    }
+
+    fn restore(&mut self, input: &mut Reader) -> Result<(), TetrisError> {
+        *self = match input.u8()? {
+            0 => Cause::BlockOut,
+            1 => Cause::TopOut,
+            _ => return Err(SaveError::Invalid.into()),
+        };
+        Ok(())
+    }
+}
+
+impl Persist for GameState {
+    fn save(&self, out: &mut Writer) {
+        let start = out.get_len();
+        match *self {
+            GameState::Menu => out.u8(0),
+            GameState::Countdown(steps) => {
+                out.u8(1);
+                out.u8(steps);
+            }
+            GameState::Playing => out.u8(2),
+            GameState::Clearing {
+                rows_mask,
+                frames_left,
+            } => {
+                out.u8(3);
+                out.u64(rows_mask);
+                out.u8(frames_left);
+            }
+            GameState::Paused => out.u8(4),
+            GameState::Sleep => out.u8(5),
+            GameState::Sweep { cause, ticks } => {
+                out.u8(6);
+                cause.save(out);
+                out.u32(ticks);
+            }
+            GameState::GameOver(cause) => {
+                out.u8(7);
+                cause.save(out);
+            }
+            GameState::Finished(outcome) => {
+                out.u8(8);
+                out.u64(outcome.ticks);
+                out.u32(outcome.points);
+                out.u32(outcome.lines);
+            }
+            GameState::NameEntry(entry) => {
+                out.u8(9);
+                out.bytes(&entry.initials);
+                out.index(entry.cursor);
+            }
+        }
+        out.pad_to(start, GAME_STATE_BYTES);
+    }
+
+    fn restore(&mut self, input: &mut Reader) -> Result<(), TetrisError> {
+        let start = input.get_offset();
+        let mut cause = Cause::BlockOut;
+        *self = match input.u8()? {
+            0 => GameState::Menu,
+            1 => GameState::Countdown(input.u8()?),
+            2 => GameState::Playing,
+            // A clear without frames left would never end.
+            3 => match (input.u64()?, input.u8()?) {
+                (_, 0) => return Err(SaveError::Invalid.into()),
+                (rows_mask, frames_left) => GameState::Clearing {
+                    rows_mask,
+                    frames_left,
+                },
+            },
+            4 => GameState::Paused,
+            5 => GameState::Sleep,
+            6 => {
+                cause.restore(input)?;
+                GameState::Sweep {
+                    cause,
+                    ticks: input.u32()?,
+                }
+            }
+            7 => {
+                cause.restore(input)?;
+                GameState::GameOver(cause)
+            }
+            8 => GameState::Finished(Outcome {
+                ticks: input.u64()?,
+                points: input.u32()?,
+                lines: input.u32()?,
+            }),
+            9 => GameState::NameEntry(NameEntry {
+                initials: input.bytes()?,
+                cursor: input.index()?,
+            }),
+            _ => return Err(SaveError::Invalid.into()),
+        };
+        input.skip_to(start, GAME_STATE_BYTES)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        cell_from_u8, read_board, save_state_shape, Persist, Reader, SaveError, Writer,
+        GAME_STATE_BYTES,
+    };
+    use crate::board::{BoardOps, FixedBoard};
+    use crate::cell::Cell;
+    use crate::coordinate::Coordinate;
+    use crate::game::{Cause, Game, GameState, NameEntry, Outcome};
+    use crate::input::{Action, InputState};
+    use crate::settings::Settings;
+    use crate::tetrominoes::SHAPES;
+
+    #[test]
+    fn test_game_state() {
+        let states = [
+            GameState::Menu,
+            GameState::Countdown(2),
+            GameState::Clearing {
+                rows_mask: 1 << 40,
+                frames_left: 3,
+            },
+            GameState::Sweep {
+                cause: Cause::TopOut,
+                ticks: 7,
+            },
+            GameState::GameOver(Cause::BlockOut),
+            GameState::Finished(Outcome {
+                ticks: u64::MAX,
+                points: 1,
+                lines: 2,
+            }),
+            GameState::NameEntry(NameEntry {
+                initials: *b"ABC",
+                cursor: 2,
+            }),
+        ];
+        let mut buffer = [0; GAME_STATE_BYTES * 7];
+        let mut out = Writer::new(&mut buffer);
+        for state in states {
+            state.save(&mut out);
+        }
+        // Every state takes the same number of bytes.
+        assert_eq!(out.get_len(), buffer.len());
+        let mut input = Reader::new(&buffer);
+        for state in states {
+            let mut restored = GameState::Playing;
+            restored.restore(&mut input).unwrap();
+            assert_eq!(restored, state);
+        }
+        let mut restored = GameState::Menu;
+        assert_eq!(
+            restored.restore(&mut Reader::new(&[10; GAME_STATE_BYTES])),
+            Err(SaveError::Invalid.into())
+        );
+        let mut buffer = [0; GAME_STATE_BYTES];
+        GameState::Clearing {
+            rows_mask: 1,
+            frames_left: 0,
+        }
+        .save(&mut Writer::new(&mut buffer));
+        assert_eq!(
+            restored.restore(&mut Reader::new(&buffer)),
+            Err(SaveError::Invalid.into())
+        );
+        assert_eq!(
+            restored.restore(&mut Reader::new(&[2; 4])),
+            Err(SaveError::Truncated(4).into())
+        );
+    }
+
+    #[test]
+    fn test_cells() {
+        for cell in SHAPES
+            .iter()
+            .map(|shape| Cell::locked(*shape))
+            .chain([Cell::Empty, Cell::Garbage])
+        {
+            assert_eq!(cell_from_u8(cell.to_u8()), Ok(cell));
+        }
+        assert_eq!(cell_from_u8(9), Err(SaveError::Invalid.into()));
+    }
+
+    #[test]
+    fn test_read_board() {
+        // The board of a save state reads without restoring the game, only onto a board of its size.
+        let mut game = Game::new(Settings::default(), 42);
+        game.tick(&InputState::from_action(Action::Start));
+        for tick in 0..600 {
+            let action = match tick % 3 {
+                0 => Action::HardDrop,
+                _ => Action::Left,
+            };
+            game.tick(&InputState::from_action(action));
+            game.tick(&InputState::default());
+        }
+        let mut buffer = [0; 1024];
+        game.save_state_into(&mut buffer).unwrap();
+        assert_eq!(
+            save_state_shape(&buffer),
+            Ok(Coordinate::from_array([20, 10]))
+        );
+        let mut board = FixedBoard::<Cell, 20, 10>::new(Cell::Empty);
+        read_board(&mut board, &buffer).unwrap();
+        assert!(board.cells().any(|(_, cell)| cell != Cell::Empty));
+        for (coord, cell) in board.cells() {
+            assert_eq!(game.get_board().get(coord), Some(cell));
+        }
+        let mut wider = FixedBoard::<Cell, 20, 12>::new(Cell::Empty);
+        assert_eq!(
+            read_board(&mut wider, &buffer),
+            Err(SaveError::BoardSize { rows: 20, cols: 10 }.into())
+        );
+        buffer[100] ^= 1;
+        assert_eq!(read_board(&mut board, &buffer), Err(SaveError::Crc.into()));
+    }
+}
@@ -0,0 +1,373 @@
+use crate::error::TetrisError;
+use crate::invariants::InvariantViolation;
+use crate::savestate::{Persist, Reader, Writer};
+
+/// The kind of T-spin performed by the last locked piece.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TSpin {
+    #[default]
+    None,
+    Mini,
+    Full,
+}
+
+/// The outcome of locking a piece on the board.
+/// # Attributes
+/// - `lines` - The number of rows cleared by the lock
+/// - `t_spin` - The kind of T-spin the lock was
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ClearEvent {
+    pub lines: usize,
+    pub t_spin: TSpin,
+}
+
+/// The number of cleared lines needed to advance one level.
+const LINES_PER_LEVEL: u32 = 10;
+
+/// The base points of a clear, before multiplying with the level.
+/// # Arguments
+/// - `event` - A reference to the `ClearEvent`
+/// # Returns
+/// - `u32` - The base points awarded for the clear
+fn base_points(event: &ClearEvent) -> u32 {
+    match (event.t_spin, event.lines) {
+        (TSpin::None, 0) => 0,
+        (TSpin::None, 1) => 100,
+        (TSpin::None, 2) => 300,
+        (TSpin::None, 3) => 500,
+        (TSpin::None, _) => 800,
+        (TSpin::Mini, 0) => 100,
+        (TSpin::Mini, 1) => 200,
+        (TSpin::Mini, _) => 400,
+        (TSpin::Full, 0) => 400,
+        (TSpin::Full, 1) => 800,
+        (TSpin::Full, 2) => 1200,
+        (TSpin::Full, _) => 1600,
+    }
+}
+
+/// Check if a clear is difficult, i.e. a tetris or a T-spin that cleared lines.
+/// # Arguments
+/// - `event` - A reference to the `ClearEvent`
+/// # Returns
+/// - `bool` - Whether (`true`) or not (`false`) the clear continues a back-to-back chain
+pub fn is_difficult(event: &ClearEvent) -> bool {
+    event.lines >= 4 || (event.lines > 0 && event.t_spin != TSpin::None)
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Score {
+    /// The score of a game, fed by the `ClearEvent` of every locked piece.
+    /// # Attributes
+    /// - `points` - The total number of points, saturating at `u32::MAX`
+    /// - `lines` - The total number of cleared lines, saturating at `u32::MAX`
+    /// - `level` - The current level, advancing every 10 lines and saturating at `u8::MAX`
+    /// - `start_level` - The level the game started at
+    /// - `progression` - Whether the level advances with the cleared lines
+    /// - `combo` - The number of consecutive clearing pieces after the first, or `None` without a chain
+    /// - `back_to_back` - Whether the last clear was difficult, so the next difficult clear gets a bonus
+    points: u32,
+    lines: u32,
+    level: u8,
+    start_level: u8,
+    progression: bool,
+    combo: Option<u32>,
+    back_to_back: bool,
+}
+
+impl Score {
+    /// Create an empty score at some level.
+    /// # Arguments
+    /// - `start_level` - The level to start at
+    /// - `progression` - Whether the level advances every 10 lines, or stays at `start_level`
+    /// # Returns
+    /// - `Score` - A score without points, lines, combo or back-to-back chain
+    pub fn new(start_level: u8, progression: bool) -> Self {
+        Score {
+            points: 0,
+            lines: 0,
+            level: start_level,
+            start_level,
+            progression,
+            combo: None,
+            back_to_back: false,
+        }
+    }
+
+    /// Get the total number of points.
+    pub fn get_points(&self) -> u32 {
+        self.points
+    }
+
+    /// Get the total number of cleared lines.
+    pub fn get_lines(&self) -> u32 {
+        self.lines
+    }
+
+    /// Get the current level.
+    pub fn get_level(&self) -> u8 {
+        self.level
+    }
+
+    /// Set the current level, which keeps advancing from there every 10 lines with progression.
+    /// # Arguments
+    /// - `level` - The level to continue at, at least the number of levels already advanced
+    pub fn set_level(&mut self, level: u8) {
+        self.level = level;
+        let advanced = u8::try_from(self.lines / LINES_PER_LEVEL).unwrap_or(u8::MAX);
+        self.start_level = level.saturating_sub(advanced);
+    }
+
+    /// Get the current combo.
+    /// # Returns
+    /// - `u32` - The number of consecutive clearing pieces after the first, 0 without a chain
+    pub fn get_combo(&self) -> u32 {
+        self.combo.unwrap_or(0)
+    }
+
+    /// Get the back-to-back state.
+    /// # Returns
+    /// - `bool` - Whether (`true`) or not (`false`) the next difficult clear gets the back-to-back bonus
+    pub fn get_back_to_back(&self) -> bool {
+        self.back_to_back
+    }
+
+    /// Update the score with the outcome of a locked piece.
+    ///
+    /// The clear is worth its base points times the level, times 1.5 when it is a difficult clear
+    /// following another difficult clear. Every consecutive clear adds a combo bonus of 50 times
+    /// the combo times the level. Locks without clearing reset the combo, but keep the back-to-back chain.
+    /// All totals saturate instead of wrapping, the points and lines at `u32::MAX` and the level at `u8::MAX`.
+    /// # Arguments
+    /// - `event` - A reference to the `ClearEvent` of the locked piece
+    /// # Returns
+    /// - `u32` - The number of points awarded
+    pub fn apply(&mut self, event: &ClearEvent) -> u32 {
+        let level = self.level as u32;
+        let mut points = base_points(event).saturating_mul(level);
+        if event.lines == 0 {
+            self.combo = None;
+        } else {
+            let difficult = is_difficult(event);
+            if difficult && self.back_to_back {
+                points = points.saturating_add(points / 2);
+            }
+            self.back_to_back = difficult;
+            let combo = self.combo.map_or(0, |combo| combo.saturating_add(1));
+            points = points.saturating_add(combo.saturating_mul(50).saturating_mul(level));
+            self.combo = Some(combo);
+        }
+        self.points = self.points.saturating_add(points);
+        self.lines = self.lines.saturating_add(event.lines as u32);
+        if self.progression {
+            let advanced = u8::try_from(self.lines / LINES_PER_LEVEL).unwrap_or(u8::MAX);
+            self.level = self.start_level.saturating_add(advanced);
+        }
+        points
+    }
+
+    /// Check the invariants of the score, see the `invariants` module.
+    ///
+    /// The level is at least the start level and with progression at most the levels advanced by the cleared lines
+    /// above it, the combo and the back-to-back chain need at least as many cleared lines as clearing pieces.
+    /// # Returns
+    /// - `Result<(), InvariantViolation>` - The first invariant that does not hold, if any
+    pub fn validate(&self) -> Result<(), InvariantViolation> {
+        let advanced = u8::try_from(self.lines / LINES_PER_LEVEL).unwrap_or(u8::MAX);
+        if self.level < self.start_level
+            || (self.progression && self.level > self.start_level.saturating_add(advanced))
+        {
+            return Err(InvariantViolation::Level {
+                level: self.level,
+                start_level: self.start_level,
+            });
+        }
+        match self.combo {
+            Some(combo) if combo >= self.lines => Err(InvariantViolation::Combo {
+                combo,
+                lines: self.lines,
+            }),
+            _ if self.back_to_back && self.lines == 0 => Err(InvariantViolation::BackToBack),
+            _ => Ok(()),
+        }
+    }
+
+    /// Overwrite the totals, to start a test close to overflowing them.
+    /// # Arguments
+    /// - `points` - The total number of points
+    /// - `lines` - The total number of cleared lines, which does not change the level until the next clear
+    #[cfg(test)]
+    pub(crate) fn set_totals(&mut self, points: u32, lines: u32) {
+        self.points = points;
+        self.lines = lines;
+    }
+}
+
+impl Persist for Score {
+    fn save(&self, out: &mut Writer) {
+        out.u32(self.points);
+        out.u32(self.lines);
+        out.u8(self.level);
+        out.u8(self.start_level);
+        out.bool(self.progression);
+        out.option_u32(self.combo);
+        out.bool(self.back_to_back);
+    }
+
+    fn restore(&mut self, input: &mut Reader) -> Result<(), TetrisError> {
+        self.points = input.u32()?;
+        self.lines = input.u32()?;
+        self.level = input.u8()?;
+        self.start_level = input.u8()?;
+        self.progression = input.bool()?;
+        self.combo = input.option_u32()?;
+        self.back_to_back = input.bool()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ClearEvent, Score, TSpin};
+    use crate::invariants::InvariantViolation;
+
+    const NONE: ClearEvent = ClearEvent {
+        lines: 0,
+        t_spin: TSpin::None,
+    };
+    const SINGLE: ClearEvent = ClearEvent {
+        lines: 1,
+        t_spin: TSpin::None,
+    };
+    const TETRIS: ClearEvent = ClearEvent {
+        lines: 4,
+        t_spin: TSpin::None,
+    };
+
+    #[test]
+    fn test_back_to_back_broken() {
+        // Tetris -> single -> tetris, the single breaks the chain.
+        let mut score = Score::new(1, true);
+        assert_eq!(score.apply(&TETRIS), 800);
+        assert!(score.get_back_to_back());
+        score.apply(&NONE);
+        score.apply(&SINGLE);
+        assert!(!score.get_back_to_back());
+        score.apply(&NONE);
+        assert_eq!(score.apply(&TETRIS), 800);
+        assert_eq!(score.get_points(), 800 + 100 + 800);
+    }
+
+    #[test]
+    fn test_back_to_back_kept() {
+        // Tetris -> no clear -> tetris, non-clearing locks keep the chain.
+        let mut score = Score::new(1, true);
+        score.apply(&TETRIS);
+        score.apply(&NONE);
+        assert!(score.get_back_to_back());
+        assert_eq!(score.apply(&TETRIS), 1200);
+        // A T-spin single continues the chain as well.
+        score.apply(&NONE);
+        let t_spin_single = ClearEvent {
+            lines: 1,
+            t_spin: TSpin::Full,
+        };
+        assert_eq!(score.apply(&t_spin_single), 1200);
+    }
+
+    #[test]
+    fn test_combo() {
+        // Three consecutive clearing pieces, the second and third earn a combo bonus.
+        let mut score = Score::new(2, true);
+        assert_eq!(score.apply(&SINGLE), 200);
+        assert_eq!(score.get_combo(), 0);
+        assert_eq!(score.apply(&SINGLE), 200 + 100);
+        assert_eq!(score.get_combo(), 1);
+        assert_eq!(score.apply(&SINGLE), 200 + 200);
+        assert_eq!(score.get_combo(), 2);
+        score.apply(&NONE);
+        assert_eq!(score.get_combo(), 0);
+        assert_eq!(score.apply(&SINGLE), 200);
+    }
+
+    #[test]
+    fn test_level() {
+        // Every 10 lines advance the level.
+        let mut score = Score::new(1, true);
+        for _ in 0..3 {
+            score.apply(&TETRIS);
+            score.apply(&NONE);
+        }
+        assert_eq!(score.get_lines(), 12);
+        assert_eq!(score.get_level(), 2);
+        // A level jump keeps advancing from the new level.
+        score.set_level(5);
+        for _ in 0..2 {
+            score.apply(&TETRIS);
+        }
+        assert_eq!(score.get_level(), 6);
+        // Without progression, the level stays put.
+        let mut score = Score::new(1, false);
+        for _ in 0..3 {
+            score.apply(&TETRIS);
+        }
+        assert_eq!(score.get_level(), 1);
+    }
+
+    #[test]
+    fn test_overflow() {
+        // A level-multiplied back-to-back tetris close to the maximum saturates instead of wrapping or panicking.
+        let mut score = Score::new(u8::MAX, true);
+        score.set_totals(u32::MAX - 100, u32::MAX - 2);
+        assert_eq!(score.apply(&TETRIS), 800 * 255);
+        assert_eq!(score.get_points(), u32::MAX);
+        assert_eq!(score.get_lines(), u32::MAX);
+        assert_eq!(score.get_level(), u8::MAX);
+        score.apply(&NONE);
+        assert_eq!(score.apply(&TETRIS), 800 * 255 * 3 / 2);
+        assert_eq!(score.get_points(), u32::MAX);
+        // A long combo saturates its bonus as well.
+        score.combo = Some(u32::MAX - 1);
+        assert_eq!(score.apply(&SINGLE), u32::MAX);
+        assert_eq!(score.get_combo(), u32::MAX);
+        // Without progression the level stays, at any number of lines.
+        let mut score = Score::new(3, false);
+        score.set_totals(0, u32::MAX);
+        score.set_level(200);
+        assert_eq!(score.apply(&SINGLE), 100 * 200);
+        assert_eq!(score.get_level(), 200);
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut score = Score::new(3, true);
+        for event in [SINGLE, TETRIS, TETRIS, TETRIS, NONE] {
+            score.apply(&event);
+            assert_eq!(score.validate(), Ok(()));
+        }
+        score.level = 2;
+        assert_eq!(
+            score.validate(),
+            Err(InvariantViolation::Level {
+                level: 2,
+                start_level: 3
+            })
+        );
+        // 13 lines advance a single level.
+        score.level = 5;
+        assert!(score.validate().is_err());
+        score.level = 4;
+        score.combo = Some(13);
+        assert_eq!(
+            score.validate(),
+            Err(InvariantViolation::Combo {
+                combo: 13,
+                lines: 13
+            })
+        );
+        let mut score = Score::new(1, true);
+        score.back_to_back = true;
+        assert_eq!(score.validate(), Err(InvariantViolation::BackToBack));
+    }
+}
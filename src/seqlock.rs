@@ -0,0 +1,158 @@
+//! A sequence lock of plain words, handing data from a single writer to readers on other cores without blocking
+//! the writer.
+//!
+//! The writer makes the sequence number odd, stores the words and makes it even again. A reader copies the words
+//! between two reads of the sequence number and retries if it was odd or changed, so it never returns a torn copy.
+//! Every word is an atomic, so a racing copy is merely discarded rather than undefined behavior, and only atomic
+//! loads and stores are used, which the Cortex-M0+ of the RP2040 supports without compare-and-swap.
+use core::sync::atomic::{fence, AtomicU32, Ordering};
+
+/// A sequence lock of `N` words.
+///
+/// Only a single thread or core may write at a time. Concurrent writers are memory safe, but readers may then see
+/// a mix of both writes.
+/// # Attributes
+/// - `sequence` - Twice the number of completed writes, plus 1 while a write is in progress
+/// - `words` - The data
+#[derive(Debug)]
+pub struct SeqLock<const N: usize> {
+    sequence: AtomicU32,
+    words: [AtomicU32; N],
+}
+
+impl<const N: usize> SeqLock<N> {
+    /// Create a sequence lock of zeroes, e.g. in a `static`.
+    /// # Returns
+    /// - `SeqLock<N>` - The sequence lock without any completed writes
+    pub const fn new() -> Self {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const ZERO: AtomicU32 = AtomicU32::new(0);
+        SeqLock {
+            sequence: AtomicU32::new(0),
+            words: [ZERO; N],
+        }
+    }
+
+    /// Get the number of completed writes.
+    pub fn get_writes(&self) -> u32 {
+        self.sequence.load(Ordering::Acquire) / 2
+    }
+
+    /// Store new data.
+    /// # Arguments
+    /// - `words` - A reference to the words to store
+    pub fn write(&self, words: &[u32; N]) {
+        let sequence = self.sequence.load(Ordering::Relaxed);
+        self.sequence
+            .store(sequence.wrapping_add(1), Ordering::Relaxed);
+        // Order the odd sequence number before the words.
+        fence(Ordering::Release);
+        for (word, value) in self.words.iter().zip(words) {
+            word.store(*value, Ordering::Relaxed);
+        }
+        self.sequence
+            .store(sequence.wrapping_add(2), Ordering::Release);
+    }
+
+    /// Try to copy the data once.
+    /// # Arguments
+    /// - `out` - A muteable reference to the words to copy into, garbage if the copy failed
+    /// # Returns
+    /// - `Option<u32>` - The number of completed writes of the copy, or `None` if a write was in progress
+    pub fn try_read(&self, out: &mut [u32; N]) -> Option<u32> {
+        let before = self.sequence.load(Ordering::Acquire);
+        if before & 1 == 1 {
+            return None;
+        }
+        for (value, word) in out.iter_mut().zip(&self.words) {
+            *value = word.load(Ordering::Relaxed);
+        }
+        // Order the words before the second read of the sequence number.
+        fence(Ordering::Acquire);
+        match self.sequence.load(Ordering::Relaxed) == before {
+            true => Some(before / 2),
+            false => None,
+        }
+    }
+
+    /// Copy the data, retrying until no write interferes.
+    /// # Arguments
+    /// - `out` - A muteable reference to the words to copy into
+    /// # Returns
+    /// - `u32` - The number of completed writes of the copy
+    pub fn read(&self, out: &mut [u32; N]) -> u32 {
+        loop {
+            if let Some(writes) = self.try_read(out) {
+                return writes;
+            }
+            core::hint::spin_loop();
+        }
+    }
+}
+
+impl<const N: usize> Default for SeqLock<N> {
+    fn default() -> Self {
+        SeqLock::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SeqLock;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn test_sequence() {
+        let lock = SeqLock::<3>::new();
+        let mut out = [7; 3];
+        assert_eq!(lock.try_read(&mut out), Some(0));
+        assert_eq!(out, [0; 3]);
+        lock.write(&[1, 2, 3]);
+        lock.write(&[4, 5, 6]);
+        assert_eq!(lock.get_writes(), 2);
+        assert_eq!(lock.read(&mut out), 2);
+        assert_eq!(out, [4, 5, 6]);
+        // A reader racing a write in progress fails.
+        lock.sequence.store(5, Ordering::Relaxed);
+        assert_eq!(lock.try_read(&mut out), None);
+    }
+
+    #[test]
+    fn test_no_torn_reads() {
+        // A writer keeps storing words that are all equal, readers on other threads must never see a mix.
+        const WORDS: usize = 256;
+        let lock = Arc::new(SeqLock::<WORDS>::new());
+        let done = Arc::new(AtomicBool::new(false));
+        let readers: Vec<_> = (0..3)
+            .map(|_| {
+                let (lock, done) = (lock.clone(), done.clone());
+                thread::spawn(move || {
+                    let mut out = [0; WORDS];
+                    let mut last = 0;
+                    // Read at least once, even if the writer finished before the thread started.
+                    loop {
+                        let finished = done.load(Ordering::Acquire);
+                        let writes = lock.read(&mut out);
+                        assert!(out.iter().all(|word| *word == out[0]), "torn read");
+                        assert_eq!(out[0], writes);
+                        assert!(writes >= last);
+                        last = writes;
+                        if finished {
+                            return last;
+                        }
+                    }
+                })
+            })
+            .collect();
+        for value in 1..=20_000 {
+            lock.write(&[value; WORDS]);
+        }
+        done.store(true, Ordering::Release);
+        // A read after the writer finished sees the last write.
+        for reader in readers {
+            assert_eq!(reader.join().unwrap(), 20_000);
+        }
+    }
+}
@@ -0,0 +1,697 @@
+use crate::ai::{AiConfig, Weights, MAX_DEPTH};
+use crate::color::{ColorCorrection, Rgb};
+use crate::input::keymap::{KeyMap, KEYMAP_INPUTS};
+use crate::input::Action;
+use crate::tetrominoes::{TetrominoShape, SHAPES};
+use core::fmt;
+
+/// The number of ticks in 2 minutes at the nominal 60 ticks per second.
+const ULTRA_TICKS: u32 = 2 * 60 * 60;
+
+/// The number of ticks in 2 minutes at the nominal 60 ticks per second, before the panel dims.
+const DIM_TICKS: u32 = 2 * 60 * 60;
+
+/// The number of ticks in 10 minutes at the nominal 60 ticks per second, before the game sleeps.
+const SLEEP_TICKS: u32 = 10 * 60 * 60;
+
+/// The version of the byte layout written by `Settings::to_bytes()`.
+pub const SETTINGS_VERSION: u8 = 8;
+
+/// The number of bytes of serialized settings.
+pub const SETTINGS_BYTES: usize = 100;
+
+/// The narrowest board, fitting the I piece lying flat.
+const MIN_COLS: usize = 4;
+
+/// The widest board, leaving room for the previews on the 32 columns of the panel.
+pub(crate) const MAX_COLS: usize = 27;
+
+/// The shortest board, fitting the digits of the countdown with a few rows to spare below a spawned piece.
+const MIN_ROWS: usize = 8;
+
+/// The tallest board, matching the 64 rows of the panel.
+const MAX_ROWS: usize = 64;
+
+/// Why settings are invalid or could not be read.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SettingsError {
+    /// The board has fewer than 4 or more than 27 columns.
+    Cols(usize),
+    /// The board has fewer than 8 or more than 64 rows.
+    Rows(usize),
+    /// The start level is 0.
+    StartLevel,
+    /// The auto-repeat rate is slower than the delay before it starts.
+    DasBelowArr,
+    /// The soft drop factor is 0.
+    SoftDropFactor,
+    /// The game sleeps before the panel dims.
+    SleepBeforeDim,
+    /// The cheese race starts without garbage, or with garbage filling more than half of the board.
+    CheeseRows(u8),
+    /// The serialized settings were written by an unsupported version.
+    Version(u8),
+    /// The serialized game mode is unknown.
+    Mode(u8),
+    /// The serialized color scheme is unknown.
+    ColorScheme(u8),
+    /// The serialized stack color mode is unknown, or its gradient names an unknown shape.
+    StackColors(u8),
+    /// An action is not mapped to exactly one input of the key map.
+    KeyMap(Action),
+    /// The serialized key map holds an unknown action.
+    Action(u8),
+    /// The attract mode looks 0 or more than 2 pieces ahead.
+    AiDepth(u8),
+    /// The attract mode places the next piece after none of the placements of the active piece.
+    AiBeam,
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SettingsError::Cols(cols) => {
+                write!(f, "{} columns, expected {} to {}", cols, MIN_COLS, MAX_COLS)
+            }
+            SettingsError::Rows(rows) => {
+                write!(f, "{} rows, expected {} to {}", rows, MIN_ROWS, MAX_ROWS)
+            }
+            SettingsError::StartLevel => f.write_str("the start level is 0"),
+            SettingsError::DasBelowArr => {
+                f.write_str("the auto-repeat rate is slower than its delay")
+            }
+            SettingsError::SoftDropFactor => f.write_str("the soft drop factor is 0"),
+            SettingsError::SleepBeforeDim => f.write_str("the game sleeps before the panel dims"),
+            SettingsError::CheeseRows(rows) => {
+                write!(f, "{} rows of cheese, expected 1 to half the board", rows)
+            }
+            SettingsError::Version(version) => write!(f, "unsupported version {}", version),
+            SettingsError::Mode(mode) => write!(f, "unknown game mode {}", mode),
+            SettingsError::ColorScheme(colors) => write!(f, "unknown color scheme {}", colors),
+            SettingsError::StackColors(mode) => write!(f, "unknown stack color mode {}", mode),
+            SettingsError::KeyMap(action) => {
+                write!(f, "{:?} is not mapped to exactly one input", action)
+            }
+            SettingsError::Action(action) => write!(f, "unknown action {}", action),
+            SettingsError::AiDepth(depth) => {
+                write!(
+                    f,
+                    "lookahead of {} pieces, expected 1 to {}",
+                    depth, MAX_DEPTH
+                )
+            }
+            SettingsError::AiBeam => f.write_str("the beam of the lookahead is 0"),
+        }
+    }
+}
+
+/// The goal of a game.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum GameMode {
+    /// Endless, with the level advancing every 10 lines.
+    #[default]
+    Marathon,
+    /// Finished once the total number of cleared lines reaches `lines`.
+    Sprint { lines: u32 },
+    /// Finished once `ticks` ticks were played, scoring as many points as possible.
+    Ultra { ticks: u32 },
+    /// Starting on `rows` rows of garbage with random gaps, finished once all of them are cleared. With
+    /// `regenerate`, every cleared row of it pushes a new garbage row in from the bottom, keeping the depth constant.
+    Cheese { rows: u8, regenerate: bool },
+}
+
+/// The colors to draw a game with.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ColorScheme {
+    /// The guideline colors stored in every `Tetromino`.
+    #[default]
+    Guideline,
+    /// Colors changing with the level, like classic Tetris.
+    Levels,
+    /// High-contrast colorblind-safe colors, with a pattern per shape on upscaled displays.
+    Accessible,
+}
+
+/// The colors of the locked cells of the stack.
+///
+/// Applied when drawing rather than when locking, so switching modes mid-game recolors the whole stack at once.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum StackColorMode {
+    /// Every cell in the color of the shape it was locked from.
+    #[default]
+    PerPiece,
+    /// Every cell in one color.
+    Monochrome(Rgb),
+    /// A gradient over the rows of the board, from the palette color of `top` on the top row to the palette color
+    /// of `bottom` on the bottom row.
+    ByRow {
+        top: TetrominoShape,
+        bottom: TetrominoShape,
+    },
+}
+
+impl StackColorMode {
+    /// A gray stack.
+    pub const MONOCHROME: StackColorMode = StackColorMode::Monochrome(Rgb::GRAY);
+    /// A gradient from the color of the I piece on top to the color of the T piece at the bottom.
+    pub const GRADIENT: StackColorMode = StackColorMode::ByRow {
+        top: TetrominoShape::I,
+        bottom: TetrominoShape::T,
+    };
+}
+
+/// The debug overlays drawn over a game, to tune the timings by eye, as a bit set.
+///
+/// Toggled from the USB console with `debug <das|lock|reset>`, see `console`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DebugOverlay(u8);
+
+impl DebugOverlay {
+    /// No overlays.
+    pub const NONE: DebugOverlay = DebugOverlay(0);
+    /// A column on each side of the board, filling up while the direction charges its auto-shift.
+    pub const DAS: DebugOverlay = DebugOverlay(1 << 0);
+    /// A bar under the board, filling up while the grounded piece waits to lock.
+    pub const LOCK_DELAY: DebugOverlay = DebugOverlay(1 << 1);
+    /// A tint on the active piece once it used its last lock delay reset.
+    pub const FINAL_RESET: DebugOverlay = DebugOverlay(1 << 2);
+    /// All overlays.
+    pub const ALL: DebugOverlay = DebugOverlay(0b111);
+
+    /// Create the overlays of a bit set, ignoring unknown bits.
+    /// # Arguments
+    /// - `bits` - The bit set, as returned by `.get_bits()`
+    /// # Returns
+    /// - `DebugOverlay` - The overlays
+    pub fn from_bits(bits: u8) -> Self {
+        DebugOverlay(bits & DebugOverlay::ALL.0)
+    }
+
+    /// Get the bit set of the overlays.
+    /// # Returns
+    /// - `u8` - A bit per overlay
+    pub fn get_bits(self) -> u8 {
+        self.0
+    }
+
+    /// Check if all of the given overlays are enabled.
+    /// # Arguments
+    /// - `other` - The overlays to check
+    /// # Returns
+    /// - `bool` - Whether (`true`) or not (`false`) all of them are enabled
+    pub fn contains(self, other: DebugOverlay) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Enable the overlays that are disabled and disable the overlays that are enabled.
+    /// # Arguments
+    /// - `other` - The overlays to toggle
+    pub fn toggle(&mut self, other: DebugOverlay) {
+        self.0 ^= other.0;
+    }
+}
+
+impl GameMode {
+    /// The standard 40 line sprint.
+    pub const SPRINT: GameMode = GameMode::Sprint { lines: 40 };
+    /// The standard 2 minute ultra.
+    pub const ULTRA: GameMode = GameMode::Ultra { ticks: ULTRA_TICKS };
+    /// The standard 10 row cheese race.
+    pub const CHEESE: GameMode = GameMode::Cheese {
+        rows: 10,
+        regenerate: false,
+    };
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Settings {
+    /// The tunables of a game.
+    /// # Attributes
+    /// - `mode` - The goal of the game
+    /// - `rows` - The number of rows of the board
+    /// - `cols` - The number of columns of the board
+    /// - `start_level` - The level to start at
+    /// - `previews` - The number of upcoming pieces to show
+    /// - `ghost` - Whether to show where the active piece will land
+    /// - `hold` - Whether holding a piece is allowed
+    /// - `lock_delay` - The number of ticks a grounded piece waits before locking
+    /// - `max_lock_resets` - The number of times moving a grounded piece restarts the lock delay
+    /// - `soft_drop_factor` - How many times faster a piece falls while soft dropping
+    /// - `das` - The number of ticks left or right is held before the piece starts shifting repeatedly
+    /// - `arr` - The number of ticks between repeated shifts, 0 shifts the piece to the wall at once
+    /// - `countdown_ticks` - The number of ticks of every step of the 3-2-1 countdown before a game starts
+    /// - `sweep_ticks` - The number of ticks the board takes to fill up with gray after a game over
+    /// - `clear_frames` - The number of frames completed rows flash before they are removed, 0 removes them instantly
+    /// - `brightness` - The global brightness of the LEDs, from 0 (off) to 255 (full current)
+    /// - `gamma` - Whether to gamma correct the colors sent to the LEDs
+    /// - `colors` - The color scheme to draw the game with
+    /// - `correction` - The white balance calibration of the LED panel
+    /// - `depth_fog` - Whether the locked cells get gradually darker towards the bottom of the board
+    /// - `grid` - Whether the empty cells are drawn as a faint checkerboard
+    /// - `dim_ticks` - The number of ticks without input before the panel dims, 0 never dims
+    /// - `dim_level` - The brightness of the dimmed panel, relative to `brightness`, from 0 (off) to 255 (unchanged)
+    /// - `sleep_ticks` - The number of ticks without input before the panel goes black and the game sleeps, 0 never
+    ///   sleeps
+    /// - `input_display` - Whether the recent actions of the player are drawn below the board, e.g. for recording
+    /// - `debug` - The debug overlays of the timings, see `DebugOverlay`
+    /// - `keymap` - The action of every button, see `KeyMap`
+    /// - `attract` - The planner of the attract mode, see `AiConfig`
+    /// - `finesse` - Whether placements taking more inputs than needed beep and their number shows after the game, see
+    ///   `finesse::Finesse`
+    /// - `stack_colors` - The colors of the locked cells, see `StackColorMode`
+    pub mode: GameMode,
+    pub rows: usize,
+    pub cols: usize,
+    pub start_level: u8,
+    pub previews: usize,
+    pub ghost: bool,
+    pub hold: bool,
+    pub lock_delay: u32,
+    pub max_lock_resets: u8,
+    pub soft_drop_factor: u32,
+    pub das: u32,
+    pub arr: u32,
+    pub countdown_ticks: u32,
+    pub sweep_ticks: u32,
+    pub clear_frames: u8,
+    pub brightness: u8,
+    pub gamma: bool,
+    pub colors: ColorScheme,
+    pub correction: ColorCorrection,
+    pub depth_fog: bool,
+    pub grid: bool,
+    pub dim_ticks: u32,
+    pub dim_level: u8,
+    pub sleep_ticks: u32,
+    pub input_display: bool,
+    pub debug: DebugOverlay,
+    pub keymap: KeyMap,
+    pub attract: AiConfig,
+    pub finesse: bool,
+    pub stack_colors: StackColorMode,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            mode: GameMode::Marathon,
+            rows: 20,
+            cols: 10,
+            start_level: 1,
+            previews: 3,
+            ghost: true,
+            hold: true,
+            lock_delay: 30,
+            max_lock_resets: 15,
+            soft_drop_factor: 20,
+            das: 10,
+            arr: 2,
+            countdown_ticks: 60,
+            sweep_ticks: 60,
+            clear_frames: 16,
+            brightness: 64,
+            gamma: true,
+            colors: ColorScheme::Guideline,
+            correction: ColorCorrection::UNCORRECTED,
+            depth_fog: false,
+            grid: false,
+            dim_ticks: DIM_TICKS,
+            dim_level: 64,
+            sleep_ticks: SLEEP_TICKS,
+            input_display: false,
+            debug: DebugOverlay::NONE,
+            keymap: KeyMap::default(),
+            attract: AiConfig::ATTRACT,
+            finesse: false,
+            stack_colors: StackColorMode::PerPiece,
+        }
+    }
+}
+
+impl Settings {
+    /// Check if the settings can be played with.
+    /// # Returns
+    /// - `Result<(), SettingsError>` - Nothing, or an error describing the first invalid setting
+    pub fn validate(&self) -> Result<(), SettingsError> {
+        if !(MIN_COLS..=MAX_COLS).contains(&self.cols) {
+            return Err(SettingsError::Cols(self.cols));
+        }
+        if !(MIN_ROWS..=MAX_ROWS).contains(&self.rows) {
+            return Err(SettingsError::Rows(self.rows));
+        }
+        if self.start_level == 0 {
+            return Err(SettingsError::StartLevel);
+        }
+        if self.das < self.arr {
+            return Err(SettingsError::DasBelowArr);
+        }
+        if self.soft_drop_factor == 0 {
+            return Err(SettingsError::SoftDropFactor);
+        }
+        if self.dim_ticks > 0 && self.sleep_ticks > 0 && self.sleep_ticks < self.dim_ticks {
+            return Err(SettingsError::SleepBeforeDim);
+        }
+        if let GameMode::Cheese { rows, .. } = self.mode {
+            if rows == 0 || rows as usize > self.rows / 2 {
+                return Err(SettingsError::CheeseRows(rows));
+            }
+        }
+        self.keymap.validate()?;
+        self.attract.validate()
+    }
+
+    /// Serialize the settings for persistent storage.
+    /// # Returns
+    /// - `[u8; SETTINGS_BYTES]` - The version byte followed by the little endian settings
+    pub fn to_bytes(self) -> [u8; SETTINGS_BYTES] {
+        let (mode, goal) = match self.mode {
+            GameMode::Marathon => (0, 0),
+            GameMode::Sprint { lines } => (1, lines),
+            GameMode::Ultra { ticks } => (2, ticks),
+            GameMode::Cheese { rows, regenerate } => (3, rows as u32 | (regenerate as u32) << 8),
+        };
+        let mut bytes = [0; SETTINGS_BYTES];
+        bytes[0] = SETTINGS_VERSION;
+        bytes[1] = mode;
+        bytes[2..6].copy_from_slice(&goal.to_le_bytes());
+        bytes[6] = self.rows as u8;
+        bytes[7] = self.cols as u8;
+        bytes[8] = self.start_level;
+        bytes[9] = self.previews as u8;
+        bytes[10] = self.ghost as u8;
+        bytes[11] = self.hold as u8;
+        bytes[12..16].copy_from_slice(&self.lock_delay.to_le_bytes());
+        bytes[16] = self.max_lock_resets;
+        bytes[17..21].copy_from_slice(&self.soft_drop_factor.to_le_bytes());
+        bytes[21..25].copy_from_slice(&self.das.to_le_bytes());
+        bytes[25..29].copy_from_slice(&self.arr.to_le_bytes());
+        bytes[29..33].copy_from_slice(&self.countdown_ticks.to_le_bytes());
+        bytes[33..37].copy_from_slice(&self.sweep_ticks.to_le_bytes());
+        bytes[37] = self.clear_frames;
+        bytes[38] = self.brightness;
+        bytes[39] = self.gamma as u8;
+        bytes[40] = self.colors as u8;
+        bytes[41] = self.correction.r;
+        bytes[42] = self.correction.g;
+        bytes[43] = self.correction.b;
+        bytes[44] = self.depth_fog as u8;
+        bytes[45] = self.grid as u8;
+        bytes[46..50].copy_from_slice(&self.dim_ticks.to_le_bytes());
+        bytes[50] = self.dim_level;
+        bytes[51..55].copy_from_slice(&self.sleep_ticks.to_le_bytes());
+        bytes[55] = self.input_display as u8;
+        bytes[56] = self.debug.get_bits();
+        bytes[57..57 + KEYMAP_INPUTS].copy_from_slice(&self.keymap.to_bytes());
+        bytes[73] = self.attract.depth;
+        bytes[74] = self.attract.beam;
+        bytes[75..79].copy_from_slice(&self.attract.weights.height.to_le_bytes());
+        bytes[79..83].copy_from_slice(&self.attract.weights.lines.to_le_bytes());
+        bytes[83..87].copy_from_slice(&self.attract.weights.holes.to_le_bytes());
+        bytes[87..91].copy_from_slice(&self.attract.weights.bumpiness.to_le_bytes());
+        bytes[91..95].copy_from_slice(&self.attract.weights.well.to_le_bytes());
+        bytes[95] = self.finesse as u8;
+        bytes[96..100].copy_from_slice(&match self.stack_colors {
+            StackColorMode::PerPiece => [0; 4],
+            StackColorMode::Monochrome(color) => [1, color.r, color.g, color.b],
+            StackColorMode::ByRow { top, bottom } => [2, top as u8, bottom as u8, 0],
+        });
+        bytes
+    }
+
+    /// Deserialize and validate settings written by `.to_bytes()`.
+    /// # Arguments
+    /// - `bytes` - A reference to the serialized settings
+    /// # Returns
+    /// - `Result<Settings, SettingsError>` - The settings, or an error if they are from another version or invalid
+    pub fn from_bytes(bytes: &[u8; SETTINGS_BYTES]) -> Result<Self, SettingsError> {
+        if bytes[0] != SETTINGS_VERSION {
+            return Err(SettingsError::Version(bytes[0]));
+        }
+        let u32_at = |index: usize| {
+            u32::from_le_bytes(
+                bytes[index..index + 4]
+                    .try_into()
+                    .expect("4 bytes make a u32"),
+            )
+        };
+        let mode = match bytes[1] {
+            0 => GameMode::Marathon,
+            1 => GameMode::Sprint { lines: u32_at(2) },
+            2 => GameMode::Ultra { ticks: u32_at(2) },
+            3 => GameMode::Cheese {
+                rows: bytes[2],
+                regenerate: bytes[3] != 0,
+            },
+            mode => return Err(SettingsError::Mode(mode)),
+        };
+        let colors = match bytes[40] {
+            0 => ColorScheme::Guideline,
+            1 => ColorScheme::Levels,
+            2 => ColorScheme::Accessible,
+            colors => return Err(SettingsError::ColorScheme(colors)),
+        };
+        let shape = |byte: u8| {
+            SHAPES
+                .get(byte as usize)
+                .copied()
+                .ok_or(SettingsError::StackColors(byte))
+        };
+        let stack_colors = match bytes[96] {
+            0 => StackColorMode::PerPiece,
+            1 => StackColorMode::Monochrome(Rgb::from_array(&[bytes[97], bytes[98], bytes[99]])),
+            2 => StackColorMode::ByRow {
+                top: shape(bytes[97])?,
+                bottom: shape(bytes[98])?,
+            },
+            mode => return Err(SettingsError::StackColors(mode)),
+        };
+        let settings = Settings {
+            mode,
+            rows: bytes[6] as usize,
+            cols: bytes[7] as usize,
+            start_level: bytes[8],
+            previews: bytes[9] as usize,
+            ghost: bytes[10] != 0,
+            hold: bytes[11] != 0,
+            lock_delay: u32_at(12),
+            max_lock_resets: bytes[16],
+            soft_drop_factor: u32_at(17),
+            das: u32_at(21),
+            arr: u32_at(25),
+            countdown_ticks: u32_at(29),
+            sweep_ticks: u32_at(33),
+            clear_frames: bytes[37],
+            brightness: bytes[38],
+            gamma: bytes[39] != 0,
+            colors,
+            correction: ColorCorrection {
+                r: bytes[41],
+                g: bytes[42],
+                b: bytes[43],
+            },
+            depth_fog: bytes[44] != 0,
+            grid: bytes[45] != 0,
+            dim_ticks: u32_at(46),
+            dim_level: bytes[50],
+            sleep_ticks: u32_at(51),
+            input_display: bytes[55] != 0,
+            debug: DebugOverlay::from_bits(bytes[56]),
+            keymap: KeyMap::from_bytes(
+                bytes[57..57 + KEYMAP_INPUTS]
+                    .try_into()
+                    .expect("the key map fits the settings"),
+            )?,
+            attract: AiConfig {
+                depth: bytes[73],
+                beam: bytes[74],
+                weights: Weights {
+                    height: u32_at(75) as i32,
+                    lines: u32_at(79) as i32,
+                    holes: u32_at(83) as i32,
+                    bumpiness: u32_at(87) as i32,
+                    well: u32_at(91) as i32,
+                },
+            },
+            finesse: bytes[95] != 0,
+            stack_colors,
+        };
+        settings.validate()?;
+        Ok(settings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        ColorScheme, DebugOverlay, GameMode, Settings, SettingsError, StackColorMode,
+        SETTINGS_VERSION,
+    };
+    use crate::ai::{AiConfig, Weights};
+    use crate::color::ColorCorrection;
+    use crate::input::keymap::KeyMap;
+    use crate::input::{Action, ACTIONS};
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(Settings::default().validate(), Ok(()));
+        let narrow = Settings {
+            cols: 2,
+            ..Default::default()
+        };
+        assert_eq!(narrow.validate(), Err(SettingsError::Cols(2)));
+        let wide = Settings {
+            cols: 28,
+            ..Default::default()
+        };
+        assert_eq!(wide.validate(), Err(SettingsError::Cols(28)));
+        let short = Settings {
+            rows: 7,
+            ..Default::default()
+        };
+        assert_eq!(short.validate(), Err(SettingsError::Rows(7)));
+        let slow = Settings {
+            das: 2,
+            arr: 5,
+            ..Default::default()
+        };
+        assert_eq!(slow.validate(), Err(SettingsError::DasBelowArr));
+        let sleepy = Settings {
+            dim_ticks: 600,
+            sleep_ticks: 300,
+            ..Default::default()
+        };
+        assert_eq!(sleepy.validate(), Err(SettingsError::SleepBeforeDim));
+        let undimmed = Settings {
+            dim_ticks: 0,
+            ..sleepy
+        };
+        assert_eq!(undimmed.validate(), Ok(()));
+        let deep = Settings {
+            mode: GameMode::Cheese {
+                rows: 11,
+                regenerate: false,
+            },
+            ..Default::default()
+        };
+        assert_eq!(deep.validate(), Err(SettingsError::CheeseRows(11)));
+        // Every action needs a button, also the hard drop.
+        let mut keymap = KeyMap::unmapped();
+        for (input, action) in ACTIONS.iter().enumerate() {
+            if *action != Action::HardDrop {
+                keymap.set(input, *action);
+            }
+        }
+        let undroppable = Settings {
+            keymap,
+            ..Default::default()
+        };
+        assert_eq!(
+            undroppable.validate(),
+            Err(SettingsError::KeyMap(Action::HardDrop))
+        );
+        // The attract mode looks 1 or 2 pieces ahead, through at least one placement.
+        let deep = Settings {
+            attract: AiConfig {
+                depth: 3,
+                ..AiConfig::ATTRACT
+            },
+            ..Default::default()
+        };
+        assert_eq!(deep.validate(), Err(SettingsError::AiDepth(3)));
+        let narrow = Settings {
+            attract: AiConfig {
+                beam: 0,
+                ..AiConfig::ATTRACT
+            },
+            ..Default::default()
+        };
+        assert_eq!(narrow.validate(), Err(SettingsError::AiBeam));
+    }
+
+    #[test]
+    fn test_bytes() {
+        let settings = Settings {
+            mode: GameMode::SPRINT,
+            rows: 40,
+            ghost: false,
+            das: 7,
+            arr: 0,
+            clear_frames: 0,
+            colors: ColorScheme::Accessible,
+            correction: ColorCorrection::TYPICAL_SMD5050,
+            depth_fog: true,
+            grid: true,
+            dim_ticks: 0,
+            dim_level: 16,
+            sleep_ticks: 1800,
+            input_display: true,
+            debug: DebugOverlay::LOCK_DELAY,
+            keymap: swapped_keymap(),
+            attract: AiConfig {
+                depth: 1,
+                beam: 2,
+                weights: Weights {
+                    well: -250,
+                    ..Weights::CLASSIC
+                },
+            },
+            finesse: true,
+            stack_colors: StackColorMode::GRADIENT,
+            ..Default::default()
+        };
+        assert_eq!(Settings::from_bytes(&settings.to_bytes()), Ok(settings));
+        let mono = Settings {
+            stack_colors: StackColorMode::MONOCHROME,
+            ..Default::default()
+        };
+        assert_eq!(Settings::from_bytes(&mono.to_bytes()), Ok(mono));
+        assert_eq!(
+            Settings::from_bytes(&Settings::default().to_bytes()),
+            Ok(Settings::default())
+        );
+        let cheese = Settings {
+            mode: GameMode::Cheese {
+                rows: 7,
+                regenerate: true,
+            },
+            ..Default::default()
+        };
+        assert_eq!(Settings::from_bytes(&cheese.to_bytes()), Ok(cheese));
+        // Settings from a future version or with an invalid board are rejected.
+        let mut bytes = settings.to_bytes();
+        bytes[0] = SETTINGS_VERSION + 1;
+        assert_eq!(
+            Settings::from_bytes(&bytes),
+            Err(SettingsError::Version(SETTINGS_VERSION + 1))
+        );
+        let mut bytes = settings.to_bytes();
+        bytes[7] = 2;
+        assert_eq!(Settings::from_bytes(&bytes), Err(SettingsError::Cols(2)));
+        let mut bytes = settings.to_bytes();
+        bytes[40] = 3;
+        assert_eq!(
+            Settings::from_bytes(&bytes),
+            Err(SettingsError::ColorScheme(3))
+        );
+        let mut bytes = settings.to_bytes();
+        bytes[98] = 7;
+        assert_eq!(
+            Settings::from_bytes(&bytes),
+            Err(SettingsError::StackColors(7))
+        );
+        // Unmapping the fourth button, the clockwise rotation of the swapped map, invalidates the map.
+        let mut bytes = settings.to_bytes();
+        bytes[57 + 3] = u8::MAX;
+        assert_eq!(
+            Settings::from_bytes(&bytes),
+            Err(SettingsError::KeyMap(Action::RotateCw))
+        );
+    }
+
+    /// The default key map with the two rotations swapped.
+    fn swapped_keymap() -> KeyMap {
+        let mut keymap = KeyMap::default();
+        keymap.set(2, Action::RotateCcw);
+        keymap
+    }
+}
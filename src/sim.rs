@@ -0,0 +1,186 @@
+//! Headless games for batch simulations on the host, e.g. to tune the weights of the AI or to check the difficulty
+//! curves of the gravity.
+//!
+//! `run_headless()` plays a whole game on the heap-backed board without rendering or waiting for the clock, asking a
+//! `Policy` for every action. Every action is followed by a tick without input, so the game sees it pressed once
+//! instead of held, and the same settings, seed and policy always give the same `SimResult`.
+use crate::events::GameEvent;
+use crate::game::{Cause, Game, GameState};
+use crate::input::{Action, InputState};
+use crate::settings::Settings;
+
+/// The number of ticks a game may spend per piece before it is cut short, a minute at 60 ticks per second, e.g. for a
+/// policy that keeps pausing.
+pub const MAX_TICKS_PER_PIECE: u64 = 60 * 60;
+
+/// A player of headless games.
+pub trait Policy {
+    /// Choose the next action, pressed for a single tick.
+    /// # Arguments
+    /// - `game` - A reference to the `Game`, in the `GameState::Playing` state
+    /// # Returns
+    /// - `Action` - The action to press
+    fn decide(&mut self, game: &Game) -> Action;
+}
+
+/// A policy pressing a fixed sequence of actions, starting over after the last one, e.g. for tests.
+/// # Attributes
+/// - `actions` - The actions to press in order, not empty
+/// - `next` - The index of the next action to press
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Scripted<'a> {
+    actions: &'a [Action],
+    next: usize,
+}
+
+impl<'a> Scripted<'a> {
+    /// Create a policy starting at the first action.
+    /// # Arguments
+    /// - `actions` - The actions to press in order, not empty
+    /// # Returns
+    /// - `Scripted` - The policy
+    pub fn new(actions: &'a [Action]) -> Self {
+        Scripted { actions, next: 0 }
+    }
+}
+
+impl Policy for Scripted<'_> {
+    fn decide(&mut self, _game: &Game) -> Action {
+        let action = self.actions[self.next];
+        self.next = (self.next + 1) % self.actions.len();
+        action
+    }
+}
+
+/// The outcome of a headless game.
+/// # Attributes
+/// - `pieces` - The number of pieces locked on the board
+/// - `lines` - The number of cleared lines
+/// - `points` - The final number of points
+/// - `ticks` - The number of ticks the game ran, including the countdown
+/// - `top_out` - Why the game ended, or `None` if it reached `max_pieces`, the goal of its mode or the tick limit
+/// - `board_hash` - The hash of the final board, see `Game::board_hash()`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SimResult {
+    pub pieces: u32,
+    pub lines: u32,
+    pub points: u32,
+    pub ticks: u64,
+    pub top_out: Option<Cause>,
+    pub board_hash: u64,
+}
+
+/// Play a game without rendering until it ends or has locked a number of pieces.
+/// # Arguments
+/// - `settings` - A reference to the `Settings` to play with
+/// - `seed` - The seed for the random number generator, the same seed deals the same pieces
+/// - `max_pieces` - The number of pieces after which the game is stopped
+/// - `policy` - A muteable reference to the `Policy` choosing the actions
+/// # Returns
+/// - `SimResult` - The outcome of the game
+pub fn run_headless(
+    settings: &Settings,
+    seed: u64,
+    max_pieces: u32,
+    policy: &mut dyn Policy,
+) -> SimResult {
+    let mut game = Game::new(*settings, seed);
+    game.tick(&InputState::from_action(Action::Start));
+    let mut result = SimResult {
+        pieces: 0,
+        lines: 0,
+        points: 0,
+        ticks: 1,
+        top_out: None,
+        board_hash: 0,
+    };
+    let max_ticks = (max_pieces as u64 + 1) * MAX_TICKS_PER_PIECE;
+    // Release the previous action for a tick before pressing the next one.
+    let mut released = true;
+    let mut ended = false;
+    while result.pieces < max_pieces && result.ticks < max_ticks && !ended {
+        let input = match game.get_state() == GameState::Playing && released {
+            true => InputState::from_action(policy.decide(&game)),
+            false => InputState::default(),
+        };
+        released = input.get_held_bits() == 0;
+        let output = game.tick(&input);
+        result.ticks += 1;
+        result.pieces += output.locked.is_some() as u32;
+        for event in output.events.iter() {
+            match event {
+                GameEvent::GameOver { cause, .. } => result.top_out = Some(*cause),
+                GameEvent::Finished { .. } => (),
+                _ => continue,
+            }
+            ended = true;
+        }
+    }
+    result.lines = game.get_score().get_lines();
+    result.points = game.get_score().get_points();
+    result.board_hash = game.board_hash();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{run_headless, Scripted, SimResult};
+    use crate::ai::Autopilot;
+    use crate::game::Cause;
+    use crate::input::Action;
+    use crate::settings::{GameMode, Settings};
+
+    /// Play a game with the autopilot for every seed.
+    fn batch(settings: &Settings, seeds: u64, max_pieces: u32) -> Vec<SimResult> {
+        (0..seeds)
+            .map(|seed| run_headless(settings, seed, max_pieces, &mut Autopilot::default()))
+            .collect()
+    }
+
+    #[test]
+    fn test_batch() {
+        // The same seeds give the same games, and other seeds other games.
+        let settings = Settings::default();
+        let results = batch(&settings, 4, 40);
+        assert_eq!(results, batch(&settings, 4, 40));
+        for result in &results {
+            assert_eq!(result.pieces, 40);
+            assert_eq!(result.top_out, None);
+            assert!(result.lines >= 10);
+        }
+        assert_ne!(results[0].board_hash, results[1].board_hash);
+        // A sprint ends at its goal, before the pieces run out.
+        let sprint = Settings {
+            mode: GameMode::Sprint { lines: 4 },
+            ..Settings::default()
+        };
+        for result in batch(&sprint, 2, 100) {
+            assert!(result.lines >= 4 && result.pieces < 100);
+            assert_eq!(result.top_out, None);
+        }
+    }
+
+    #[test]
+    fn test_scripted() {
+        // Hard dropping every piece in the middle stacks up to a block out.
+        let result = run_headless(
+            &Settings::default(),
+            3,
+            1000,
+            &mut Scripted::new(&[Action::HardDrop]),
+        );
+        assert_eq!(result.top_out, Some(Cause::BlockOut));
+        assert!(result.pieces < 20);
+        assert_eq!(result.lines, 0);
+        // Shifting the pieces apart, the game is stopped after the pieces run out.
+        let actions = [
+            Action::Left,
+            Action::HardDrop,
+            Action::Right,
+            Action::HardDrop,
+        ];
+        let result = run_headless(&Settings::default(), 3, 5, &mut Scripted::new(&actions));
+        assert_eq!(result.pieces, 5);
+        assert_eq!(result.top_out, None);
+    }
+}
@@ -0,0 +1,546 @@
+//! The sound effects and the music of a piezo buzzer, as sequences of notes timed in game ticks.
+//!
+//! The `Sequencer` is ticked with the game and tells the PWM layer which frequency to play, so the timing of
+//! every note is exact in ticks and the sequencing runs on the host like the rest of the game logic.
+use crate::events::{EventSink, GameEvent};
+
+/// The frequencies of the notes of the sequences, in Hz.
+const G4: u16 = 392;
+const A4: u16 = 440;
+const B4: u16 = 494;
+const C5: u16 = 523;
+const CS5: u16 = 554;
+const D5: u16 = 587;
+const DS5: u16 = 622;
+const E5: u16 = 659;
+const F5: u16 = 698;
+const G5: u16 = 784;
+const A5: u16 = 880;
+const C6: u16 = 1047;
+
+/// The number of ticks of a quarter note of the music, 150 beats per minute at 60 ticks per second.
+const QUARTER: u8 = 24;
+const EIGHTH: u8 = QUARTER / 2;
+const DOTTED: u8 = QUARTER + EIGHTH;
+const HALF: u8 = 2 * QUARTER;
+
+/// A single note of a sequence.
+/// # Attributes
+/// - `freq_hz` - The frequency of the note, 0 for a rest
+/// - `duration_ticks` - The number of ticks the note lasts
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Note {
+    pub freq_hz: u16,
+    pub duration_ticks: u8,
+}
+
+impl Note {
+    /// Create a note.
+    /// # Arguments
+    /// - `freq_hz` - The frequency of the note, 0 for a rest
+    /// - `duration_ticks` - The number of ticks the note lasts
+    /// # Returns
+    /// - `Note` - The note
+    pub const fn new(freq_hz: u16, duration_ticks: u8) -> Self {
+        Note {
+            freq_hz,
+            duration_ticks,
+        }
+    }
+
+    /// Create a rest.
+    /// # Arguments
+    /// - `duration_ticks` - The number of ticks the rest lasts
+    /// # Returns
+    /// - `Note` - The silent note
+    pub const fn rest(duration_ticks: u8) -> Self {
+        Note::new(0, duration_ticks)
+    }
+
+    /// Get the frequency to play.
+    /// # Returns
+    /// - `Option<u16>` - The frequency in Hz, or `None` for a rest
+    pub fn get_frequency(&self) -> Option<u16> {
+        match self.freq_hz {
+            0 => None,
+            freq_hz => Some(freq_hz),
+        }
+    }
+}
+
+/// A sound effect, interrupting a playing effect of a lower priority.
+/// # Attributes
+/// - `priority` - The priority of the effect, higher effects interrupt lower ones
+/// - `notes` - The notes of the effect
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sequence {
+    pub priority: u8,
+    pub notes: &'static [Note],
+}
+
+/// A short blip when a piece locks, e.g. after a hard drop.
+pub const LOCK: Sequence = Sequence {
+    priority: 0,
+    notes: &[Note::new(G4, 2), Note::new(C5, 2)],
+};
+
+/// A single high tick when a rotation needed a wall kick, rotating in place is silent.
+pub const KICK: Sequence = Sequence {
+    priority: 0,
+    notes: &[Note::new(A5, 2)],
+};
+
+/// A low double tick when a rotation is blocked.
+pub const BLOCKED: Sequence = Sequence {
+    priority: 0,
+    notes: &[Note::new(G4, 2), Note::rest(1), Note::new(G4, 2)],
+};
+
+/// A low buzz when a piece took more inputs than needed, with `Settings::finesse`.
+pub const FAULT: Sequence = Sequence {
+    priority: 0,
+    notes: &[Note::new(G4, 6)],
+};
+
+/// A rising arpeggio when clearing one to three lines.
+pub const CLEAR: Sequence = Sequence {
+    priority: 1,
+    notes: &[Note::new(C5, 4), Note::new(E5, 4), Note::new(G5, 6)],
+};
+
+/// A fanfare when clearing four lines at once.
+pub const TETRIS: Sequence = Sequence {
+    priority: 2,
+    notes: &[
+        Note::new(C5, 3),
+        Note::new(E5, 3),
+        Note::new(G5, 3),
+        Note::new(C6, 3),
+        Note::new(G5, 3),
+        Note::new(C6, 9),
+    ],
+};
+
+/// A two-step rise when the level advances.
+pub const LEVEL_UP: Sequence = Sequence {
+    priority: 3,
+    notes: &[
+        Note::new(G4, 4),
+        Note::new(C5, 4),
+        Note::new(E5, 4),
+        Note::rest(2),
+        Note::new(A4, 4),
+        Note::new(D5, 4),
+        Note::new(F5, 8),
+    ],
+};
+
+/// A falling chromatic line when the game is over.
+pub const GAME_OVER: Sequence = Sequence {
+    priority: 4,
+    notes: &[
+        Note::new(E5, 10),
+        Note::new(DS5, 10),
+        Note::new(D5, 10),
+        Note::new(CS5, 30),
+    ],
+};
+
+/// The first part of Korobeiniki, the theme of Tetris, looped as the background music.
+pub const KOROBEINIKI: &[Note] = &[
+    Note::new(E5, QUARTER),
+    Note::new(B4, EIGHTH),
+    Note::new(C5, EIGHTH),
+    Note::new(D5, QUARTER),
+    Note::new(C5, EIGHTH),
+    Note::new(B4, EIGHTH),
+    Note::new(A4, QUARTER),
+    Note::new(A4, EIGHTH),
+    Note::new(C5, EIGHTH),
+    Note::new(E5, QUARTER),
+    Note::new(D5, EIGHTH),
+    Note::new(C5, EIGHTH),
+    Note::new(B4, DOTTED),
+    Note::new(C5, EIGHTH),
+    Note::new(D5, QUARTER),
+    Note::new(E5, QUARTER),
+    Note::new(C5, QUARTER),
+    Note::new(A4, QUARTER),
+    Note::new(A4, HALF),
+    Note::rest(EIGHTH),
+    Note::new(D5, QUARTER),
+    Note::new(F5, EIGHTH),
+    Note::new(A5, QUARTER),
+    Note::new(G5, EIGHTH),
+    Note::new(F5, EIGHTH),
+    Note::new(E5, DOTTED),
+    Note::new(C5, EIGHTH),
+    Note::new(E5, QUARTER),
+    Note::new(D5, EIGHTH),
+    Note::new(C5, EIGHTH),
+    Note::new(B4, QUARTER),
+    Note::new(B4, EIGHTH),
+    Note::new(C5, EIGHTH),
+    Note::new(D5, QUARTER),
+    Note::new(E5, QUARTER),
+    Note::new(C5, QUARTER),
+    Note::new(A4, QUARTER),
+    Note::new(A4, QUARTER),
+    Note::rest(QUARTER),
+];
+
+/// Get the sound effect of an event.
+/// # Arguments
+/// - `event` - A reference to the `GameEvent`
+/// # Returns
+/// - `Option<&'static Sequence>` - The effect, or `None` if the event is silent
+pub fn sequence_for(event: &GameEvent) -> Option<&'static Sequence> {
+    match event {
+        GameEvent::PieceLocked { .. } => Some(&LOCK),
+        GameEvent::Rotated { kicked: true } => Some(&KICK),
+        GameEvent::RotationFailed => Some(&BLOCKED),
+        GameEvent::FinesseFault { .. } => Some(&FAULT),
+        GameEvent::LinesCleared { n, .. } if *n >= 4 => Some(&TETRIS),
+        GameEvent::LinesCleared { .. } => Some(&CLEAR),
+        GameEvent::LevelUp { .. } => Some(&LEVEL_UP),
+        GameEvent::GameOver { .. } => Some(&GAME_OVER),
+        _ => None,
+    }
+}
+
+/// The position in a sequence of notes.
+/// # Attributes
+/// - `notes` - The notes of the sequence
+/// - `index` - The index of the current note
+/// - `elapsed` - The number of ticks the current note has played
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Cursor {
+    notes: &'static [Note],
+    index: usize,
+    elapsed: u8,
+}
+
+impl Cursor {
+    /// Start at the first note of a sequence.
+    fn new(notes: &'static [Note]) -> Self {
+        Cursor {
+            notes,
+            index: 0,
+            elapsed: 0,
+        }
+    }
+
+    /// Get the current note.
+    fn get_note(&self) -> Option<&Note> {
+        self.notes.get(self.index)
+    }
+
+    /// Advance by a tick, skipping notes without duration.
+    /// # Returns
+    /// - `bool` - Whether (`true`) or not (`false`) the sequence ended
+    fn advance(&mut self) -> bool {
+        self.elapsed += 1;
+        while self
+            .get_note()
+            .is_some_and(|note| self.elapsed >= note.duration_ticks)
+        {
+            self.index += 1;
+            self.elapsed = 0;
+        }
+        self.index >= self.notes.len()
+    }
+}
+
+/// The player of the buzzer, mixing the sound effects over the looping music by playing one at a time.
+/// # Attributes
+/// - `effect` - The playing effect and its priority, if any
+/// - `music` - The position in the music, if any
+/// - `finesse` - Whether the finesse faults beep, see `Settings::finesse`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Sequencer {
+    effect: Option<(u8, Cursor)>,
+    music: Option<Cursor>,
+    finesse: bool,
+}
+
+impl Sequencer {
+    /// Create a silent sequencer.
+    pub fn new() -> Self {
+        Sequencer::default()
+    }
+
+    /// Play a sound effect, unless an effect of a higher priority is playing.
+    /// # Arguments
+    /// - `sequence` - A reference to the `Sequence` of the effect
+    pub fn play(&mut self, sequence: &Sequence) {
+        let interrupts = match self.effect {
+            Some((priority, _)) => sequence.priority >= priority,
+            None => true,
+        };
+        if interrupts {
+            self.effect = Some((sequence.priority, Cursor::new(sequence.notes)));
+        }
+    }
+
+    /// Start looping music from its first note, or stop the music.
+    /// # Arguments
+    /// - `music` - The notes of the music, e.g. `KOROBEINIKI`, or `None` to stop
+    pub fn set_music(&mut self, music: Option<&'static [Note]>) {
+        self.music = music.map(Cursor::new);
+    }
+
+    /// Let the finesse faults beep or keep them silent.
+    /// # Arguments
+    /// - `finesse` - Whether (`true`) or not (`false`) a `GameEvent::FinesseFault` plays `FAULT`
+    pub fn set_finesse(&mut self, finesse: bool) {
+        self.finesse = finesse;
+    }
+
+    /// Check if a sound effect is playing.
+    pub fn is_playing(&self) -> bool {
+        self.effect.is_some()
+    }
+
+    /// Get the frequency of the current tick and advance to the next tick.
+    ///
+    /// A playing effect silences the music, which keeps time meanwhile so it continues on the beat.
+    /// # Returns
+    /// - `Option<u16>` - The frequency to play in Hz during this tick, or `None` for silence
+    pub fn tick(&mut self) -> Option<u16> {
+        let note = match (&self.effect, &self.music) {
+            (Some((_, effect)), _) => effect.get_note(),
+            (None, Some(music)) => music.get_note(),
+            (None, None) => None,
+        };
+        let freq_hz = note.and_then(Note::get_frequency);
+        if let Some((_, effect)) = &mut self.effect {
+            if effect.advance() {
+                self.effect = None;
+            }
+        }
+        if let Some(music) = &mut self.music {
+            if music.advance() {
+                *music = Cursor::new(music.notes);
+            }
+        }
+        freq_hz
+    }
+}
+
+impl EventSink for Sequencer {
+    /// Play the sound effect of an event, see `sequence_for()`, the finesse faults only with `.set_finesse()`.
+    fn on_event(&mut self, event: &GameEvent) {
+        if matches!(event, GameEvent::FinesseFault { .. }) && !self.finesse {
+            return;
+        }
+        if let Some(sequence) = sequence_for(event) {
+            self.play(sequence);
+        }
+    }
+}
+
+/// Get the clock divisor and wrap value of a PWM slice to sound a frequency.
+///
+/// The divisor is the smallest one in 1/16 steps whose counter still fits 16 bits, for the finest pitch.
+/// # Arguments
+/// - `sys_hz` - The frequency of the system clock driving the slice
+/// - `freq_hz` - The frequency to sound, larger than 0
+/// # Returns
+/// - `(u8, u8, u16)` - The integer and 1/16 fractional part of the divisor, and the wrap value of the counter
+pub fn pwm_config(sys_hz: u32, freq_hz: u16) -> (u8, u8, u16) {
+    let cycles = sys_hz as u64 * 16 / freq_hz as u64;
+    let divisor = (cycles / 65536 + (cycles % 65536).min(1)).clamp(16, 0xFFF);
+    let top = (cycles / divisor).clamp(1, 65536) - 1;
+    ((divisor >> 4) as u8, (divisor & 0xF) as u8, top as u16)
+}
+
+#[cfg(feature = "rp2040")]
+pub use pwm::Buzzer;
+
+#[cfg(feature = "rp2040")]
+mod pwm {
+    use super::pwm_config;
+    use embedded_hal::pwm::SetDutyCycle;
+    use rp2040_hal::gpio::AnyPin;
+    use rp2040_hal::pwm::{FreeRunning, Slice, SliceId, ValidPwmOutputPin, A};
+
+    /// A piezo buzzer on channel A of a PWM slice, driven by a square wave.
+    /// # Attributes
+    /// - `slice` - The PWM slice
+    /// - `sys_hz` - The frequency of the system clock driving the slice
+    /// - `freq_hz` - The frequency sounding, if any
+    pub struct Buzzer<S: SliceId> {
+        slice: Slice<S, FreeRunning>,
+        sys_hz: u32,
+        freq_hz: Option<u16>,
+    }
+
+    impl<S: SliceId> Buzzer<S> {
+        /// Route the slice to the buzzer pin and keep it silent.
+        /// # Arguments
+        /// - `slice` - The PWM slice of the pin
+        /// - `pin` - The buzzer pin, on channel A of the slice, e.g. GPIO 18 of slice 1
+        /// - `sys_hz` - The frequency of the system clock driving the slice
+        /// # Returns
+        /// - `Buzzer<S>` - The silent buzzer
+        pub fn new<P: AnyPin>(mut slice: Slice<S, FreeRunning>, pin: P, sys_hz: u32) -> Self
+        where
+            P::Id: ValidPwmOutputPin<S, A>,
+        {
+            slice.default_config();
+            slice.channel_a.output_to(pin);
+            slice.channel_a.set_duty_cycle(0).ok();
+            slice.enable();
+            Buzzer {
+                slice,
+                sys_hz,
+                freq_hz: None,
+            }
+        }
+
+        /// Sound a frequency with a square wave, or go silent.
+        /// # Arguments
+        /// - `freq_hz` - The frequency in Hz, or `None` to go silent, e.g. from `Sequencer::tick()`
+        pub fn set_frequency(&mut self, freq_hz: Option<u16>) {
+            if freq_hz == self.freq_hz {
+                // Reconfiguring the slice restarts the wave, which clicks.
+                return;
+            }
+            self.freq_hz = freq_hz;
+            match freq_hz.filter(|freq_hz| *freq_hz > 0) {
+                Some(freq_hz) => {
+                    let (div_int, div_frac, top) = pwm_config(self.sys_hz, freq_hz);
+                    self.slice.set_div_int(div_int);
+                    self.slice.set_div_frac(div_frac);
+                    self.slice.set_top(top);
+                    self.slice.channel_a.set_duty_cycle_fraction(1, 2).ok();
+                }
+                None => {
+                    self.slice.channel_a.set_duty_cycle(0).ok();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        pwm_config, sequence_for, Note, Sequencer, BLOCKED, CLEAR, FAULT, KICK, KOROBEINIKI, LOCK,
+        TETRIS,
+    };
+    use crate::events::{EventSink, GameEvent};
+    use crate::scoring::TSpin;
+    use crate::tetrominoes::TetrominoShape;
+
+    /// Tick the sequencer a number of times and collect the frequencies.
+    fn timeline(sequencer: &mut Sequencer, ticks: usize) -> Vec<Option<u16>> {
+        (0..ticks).map(|_| sequencer.tick()).collect()
+    }
+
+    /// Expand notes into the frequency of every tick they play.
+    fn expand(notes: &[Note]) -> Vec<Option<u16>> {
+        notes
+            .iter()
+            .flat_map(|note| vec![note.get_frequency(); note.duration_ticks as usize])
+            .collect()
+    }
+
+    #[test]
+    fn test_clear_jingle() {
+        let mut sequencer = Sequencer::new();
+        assert_eq!(sequencer.tick(), None);
+        sequencer.on_event(&GameEvent::LinesCleared {
+            n: 2,
+            t_spin: TSpin::None,
+            b2b: false,
+        });
+        assert!(sequencer.is_playing());
+        // C5 for 4 ticks, E5 for 4 ticks, G5 for 6 ticks, then silence.
+        let mut expected = vec![Some(523); 4];
+        expected.extend([Some(659); 4]);
+        expected.extend([Some(784); 6]);
+        expected.extend([None; 2]);
+        assert_eq!(timeline(&mut sequencer, 16), expected);
+        assert!(!sequencer.is_playing());
+    }
+
+    #[test]
+    fn test_priority() {
+        // A tick locking a piece and clearing four lines plays the tetris, not the blip.
+        let mut sequencer = Sequencer::new();
+        let events = [
+            GameEvent::PieceLocked {
+                shape: TetrominoShape::I,
+                cells: [[19, 3], [19, 4], [19, 5], [19, 6]],
+            },
+            GameEvent::LinesCleared {
+                n: 4,
+                t_spin: TSpin::None,
+                b2b: false,
+            },
+        ];
+        for event in events.iter() {
+            sequencer.on_event(event);
+        }
+        assert_eq!(sequence_for(&events[0]), Some(&LOCK));
+        // Only kicked and blocked rotations have a cue.
+        assert_eq!(sequence_for(&GameEvent::Rotated { kicked: false }), None);
+        assert_eq!(
+            sequence_for(&GameEvent::Rotated { kicked: true }),
+            Some(&KICK)
+        );
+        assert_eq!(sequence_for(&GameEvent::RotationFailed), Some(&BLOCKED));
+        assert_eq!(timeline(&mut sequencer, 24), expand(TETRIS.notes));
+        // A finesse fault only beeps once enabled, over the blip of the lock.
+        let fault = GameEvent::FinesseFault { excess: 1 };
+        sequencer.on_event(&fault);
+        assert!(!sequencer.is_playing());
+        sequencer.set_finesse(true);
+        sequencer.on_event(&events[0]);
+        sequencer.on_event(&fault);
+        assert_eq!(timeline(&mut sequencer, 6), expand(FAULT.notes));
+        // A lower priority effect does not interrupt, an equal or higher one restarts.
+        sequencer.play(&TETRIS);
+        sequencer.tick();
+        sequencer.play(&CLEAR);
+        assert_eq!(sequencer.tick(), Some(523));
+        sequencer.play(&TETRIS);
+        assert_eq!(sequencer.tick(), Some(523));
+        sequencer.play(&LOCK);
+        assert_eq!(
+            timeline(&mut sequencer, 3),
+            [Some(523), Some(523), Some(659)]
+        );
+    }
+
+    #[test]
+    fn test_music() {
+        // The music loops, and keeps time under an effect.
+        let mut sequencer = Sequencer::new();
+        sequencer.set_music(Some(KOROBEINIKI));
+        let music = expand(KOROBEINIKI);
+        let mut expected = music.clone();
+        expected.extend(&music[..30]);
+        let mut played = timeline(&mut sequencer, 10);
+        sequencer.play(&CLEAR);
+        played.extend(timeline(&mut sequencer, music.len() + 20));
+        expected[10..24].copy_from_slice(&expand(CLEAR.notes));
+        assert_eq!(played, expected);
+        sequencer.set_music(None);
+        assert_eq!(sequencer.tick(), None);
+    }
+
+    #[test]
+    fn test_pwm_config() {
+        // A4 at 125 MHz needs a divisor of 4 + 6 / 16 to fit the counter.
+        assert_eq!(pwm_config(125_000_000, 440), (4, 6, 64934));
+        for freq_hz in [100, 440, 1047, 4000] {
+            let (div_int, div_frac, top) = pwm_config(125_000_000, freq_hz);
+            let divisor = div_int as f64 + div_frac as f64 / 16.0;
+            let actual = 125_000_000.0 / (divisor * (top as f64 + 1.0));
+            assert!((actual - freq_hz as f64).abs() < freq_hz as f64 * 0.001);
+        }
+    }
+}
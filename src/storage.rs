@@ -0,0 +1,854 @@
+use crate::board::BoardOps;
+use crate::cell::Cell;
+use crate::game::Game;
+use crate::heatmap::PlacementHeatmap;
+use crate::highscores::{HighScores, Ranking, HIGH_SCORES_BYTES};
+use crate::lifetime::{LifetimeStats, LIFETIME_BYTES};
+use crate::replay::Replay;
+use crate::settings::{Settings, SETTINGS_BYTES};
+use alloc::vec::Vec;
+
+/// The number of bytes of the flash sector holding the records, the smallest unit the RP2040 flash erases.
+pub const SECTOR_BYTES: usize = 4096;
+
+/// The maximum number of bytes of a stored replay, leaving room in the sector for the other kinds and for appending.
+pub const MAX_REPLAY_BYTES: usize = 1024;
+
+/// The value of every byte of an erased sector.
+const ERASED: u8 = 0xFF;
+
+/// The number of bytes before the payload of a record: the kind, the payload length and the sequence number.
+const HEADER_BYTES: usize = 1 + 2 + 4;
+
+/// The number of bytes after the payload of a record: its CRC-32.
+const CRC_BYTES: usize = 4;
+
+/// The kind of payload a record holds, so several kinds share a sector.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RecordKind {
+    HighScores = 1,
+    Settings = 2,
+    Heatmap = 3,
+    Lifetime = 4,
+    Replay = 5,
+    SprintTimes = 6,
+}
+
+impl RecordKind {
+    /// All kinds, in the order they are rewritten after erasing the sector.
+    pub const ALL: [RecordKind; 6] = [
+        RecordKind::HighScores,
+        RecordKind::Settings,
+        RecordKind::Heatmap,
+        RecordKind::Lifetime,
+        RecordKind::Replay,
+        RecordKind::SprintTimes,
+    ];
+}
+
+/// Why a record could not be stored.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StorageError {
+    /// The record does not fit in the sector next to the latest records of the other kinds.
+    TooLarge(usize),
+}
+
+/// A single valid record in a sector.
+/// # Attributes
+/// - `kind` - The kind of the payload, or the unknown kind byte
+/// - `seq` - The sequence number, higher for more recent records
+/// - `offset` - The offset of the record in the sector
+/// - `payload` - The bytes of the payload
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Record<'a> {
+    pub kind: u8,
+    pub seq: u32,
+    pub offset: usize,
+    pub payload: &'a [u8],
+}
+
+/// A flash sector, read as memory and written by erasing it as a whole and programming bytes.
+///
+/// Like NOR flash, programming can only clear bits, so bytes are programmed once after every erase.
+pub trait FlashOps {
+    /// Get the `SECTOR_BYTES` bytes of the sector.
+    fn as_bytes(&self) -> &[u8];
+
+    /// Set every byte of the sector to 0xFF.
+    fn erase(&mut self);
+
+    /// Program bytes into the sector, which must have been erased since they were last programmed.
+    /// # Arguments
+    /// - `offset` - The offset of the first byte in the sector
+    /// - `data` - The bytes to program
+    fn program(&mut self, offset: usize, data: &[u8]);
+}
+
+/// Get the number of bytes of a record.
+/// # Arguments
+/// - `payload` - The number of bytes of the payload
+/// # Returns
+/// - `usize` - The number of bytes of the header, the payload and the CRC
+pub fn record_bytes(payload: usize) -> usize {
+    HEADER_BYTES + payload + CRC_BYTES
+}
+
+/// Serialize a record: the kind, the little endian payload length and sequence number, the payload and the
+/// little endian CRC-32 of all of them.
+/// # Arguments
+/// - `kind` - The `RecordKind` of the payload
+/// - `seq` - The sequence number of the record
+/// - `payload` - The bytes of the payload, at most 65535
+/// # Returns
+/// - `Vec<u8>` - The `record_bytes()` bytes of the record
+pub fn encode_record(kind: RecordKind, seq: u32, payload: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(record_bytes(payload.len()));
+    bytes.push(kind as u8);
+    bytes.extend_from_slice(&(payload.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(&seq.to_le_bytes());
+    bytes.extend_from_slice(payload);
+    let crc = _crc32(&bytes);
+    bytes.extend_from_slice(&crc.to_le_bytes());
+    bytes
+}
+
+/// Iterate over the valid records of a sector, from the start to the erased space.
+///
+/// A record with a wrong CRC, e.g. torn by a power loss while programming it or corrupted later, is skipped
+/// as long as its length fits in the sector. The scan stops at the first erased byte or at a record whose
+/// header is too damaged to find the next record.
+/// # Arguments
+/// - `sector` - The bytes of the sector
+/// # Returns
+/// - `Records` - An iterator over the valid records
+pub fn records(sector: &[u8]) -> Records<'_> {
+    Records { sector, offset: 0 }
+}
+
+/// Find the most recent valid record of a kind.
+/// # Arguments
+/// - `sector` - The bytes of the sector
+/// - `kind` - The `RecordKind` to find
+/// # Returns
+/// - `Option<Record>` - The valid record with the highest sequence number, or `None` if there is none
+pub fn latest(sector: &[u8], kind: RecordKind) -> Option<Record<'_>> {
+    records(sector)
+        .filter(|record| record.kind == kind as u8)
+        .max_by_key(|record| record.seq)
+}
+
+/// Find the offset to append the next record at, after the last record found by `records()`.
+///
+/// A torn record is skipped over by its length, so the next record is appended after the space it claims.
+/// # Arguments
+/// - `sector` - The bytes of the sector
+/// # Returns
+/// - `Option<usize>` - The offset of the erased space at the end of the sector, or `None` if the scan stopped
+///   at a damaged header, so a record appended after it would never be found
+pub fn append_offset(sector: &[u8]) -> Option<usize> {
+    let mut scan = records(sector);
+    scan.by_ref().for_each(drop);
+    sector[scan.offset..]
+        .iter()
+        .all(|byte| *byte == ERASED)
+        .then_some(scan.offset)
+}
+
+/// An iterator over the valid records of a sector, see `records()`.
+pub struct Records<'a> {
+    sector: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Iterator for Records<'a> {
+    type Item = Record<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let header = self.sector.get(self.offset..self.offset + HEADER_BYTES)?;
+            if header[0] == ERASED {
+                return None;
+            }
+            let len = u16::from_le_bytes([header[1], header[2]]) as usize;
+            let offset = self.offset;
+            let bytes = self.sector.get(offset..offset + record_bytes(len))?;
+            self.offset += bytes.len();
+            let (body, crc) = bytes.split_at(HEADER_BYTES + len);
+            if _crc32(body).to_le_bytes() == crc {
+                return Some(Record {
+                    kind: header[0],
+                    seq: u32::from_le_bytes(header[3..7].try_into().expect("4 bytes make a u32")),
+                    offset,
+                    payload: &body[HEADER_BYTES..],
+                });
+            }
+        }
+    }
+}
+
+/// A wear-leveling store of records in a single flash sector.
+///
+/// Every store appends a record with the next sequence number, so the sector is only erased once it is
+/// full, after which the latest record of every kind is rewritten. A power loss between erasing and
+/// rewriting loses the records, a power loss while appending only loses the record being appended.
+/// # Attributes
+/// - `flash` - The `FlashOps` of the sector
+pub struct RecordStore<F: FlashOps> {
+    flash: F,
+}
+
+impl<F: FlashOps> RecordStore<F> {
+    /// Create a store over a flash sector, keeping the records already in it.
+    /// # Arguments
+    /// - `flash` - The `FlashOps` of the sector
+    /// # Returns
+    /// - `RecordStore<F>` - The store
+    pub fn new(flash: F) -> Self {
+        RecordStore { flash }
+    }
+
+    /// Get the flash sector of the store.
+    pub fn get_flash(&self) -> &F {
+        &self.flash
+    }
+
+    /// Get the payload of the most recent valid record of a kind.
+    /// # Arguments
+    /// - `kind` - The `RecordKind` to load
+    /// # Returns
+    /// - `Option<&[u8]>` - The payload, or `None` if no valid record of the kind was stored
+    pub fn load(&self, kind: RecordKind) -> Option<&[u8]> {
+        latest(self.flash.as_bytes(), kind).map(|record| record.payload)
+    }
+
+    /// Append a record, erasing the sector and rewriting the latest record of the other kinds if it is full or
+    /// damaged.
+    /// # Arguments
+    /// - `kind` - The `RecordKind` of the payload
+    /// - `payload` - The bytes of the payload
+    /// # Returns
+    /// - `Result<(), StorageError>` - An error if the record does not fit, leaving the sector unchanged
+    pub fn store(&mut self, kind: RecordKind, payload: &[u8]) -> Result<(), StorageError> {
+        let sector = self.flash.as_bytes();
+        let seq = records(sector)
+            .map(|record| record.seq)
+            .max()
+            .map_or(0, |seq| seq + 1);
+        match append_offset(sector) {
+            Some(offset) if offset + record_bytes(payload.len()) <= SECTOR_BYTES => {
+                self.flash
+                    .program(offset, &encode_record(kind, seq, payload));
+                return Ok(());
+            }
+            _ => (),
+        }
+        // Keep the other kinds, then append the new record after them.
+        let mut kept: Vec<Vec<u8>> = Vec::new();
+        for other in RecordKind::ALL.into_iter().filter(|other| *other != kind) {
+            if let Some(record) = latest(sector, other) {
+                kept.push(encode_record(
+                    other,
+                    seq + kept.len() as u32,
+                    record.payload,
+                ));
+            }
+        }
+        kept.push(encode_record(kind, seq + kept.len() as u32, payload));
+        let total = kept.iter().map(|record| record.len()).sum();
+        if total > SECTOR_BYTES {
+            return Err(StorageError::TooLarge(total));
+        }
+        self.flash.erase();
+        let mut offset = 0;
+        for record in kept {
+            self.flash.program(offset, &record);
+            offset += record.len();
+        }
+        Ok(())
+    }
+
+    /// Load the most recent valid high-score table.
+    /// # Returns
+    /// - `Option<HighScores>` - The table, or `None` if none was stored or it is corrupted
+    pub fn load_high_scores(&self) -> Option<HighScores> {
+        HighScores::from_bytes(self.load(RecordKind::HighScores)?.try_into().ok()?)
+    }
+
+    /// Store the high-score table, see `.store()`.
+    /// # Arguments
+    /// - `high_scores` - A reference to the `HighScores` to store
+    /// # Returns
+    /// - `Result<(), StorageError>` - An error if the record does not fit
+    pub fn store_high_scores(&mut self, high_scores: &HighScores) -> Result<(), StorageError> {
+        let bytes: [u8; HIGH_SCORES_BYTES] = high_scores.to_bytes();
+        self.store(RecordKind::HighScores, &bytes)
+    }
+
+    /// Load the most recent valid table of the best sprint times.
+    /// # Returns
+    /// - `Option<HighScores>` - The table, or `None` if none was stored, it is corrupted or not ranked by time
+    pub fn load_sprint_times(&self) -> Option<HighScores> {
+        HighScores::from_bytes(self.load(RecordKind::SprintTimes)?.try_into().ok()?)
+            .filter(|sprint_times| sprint_times.get_ranking() == Ranking::Time)
+    }
+
+    /// Store the table of the best sprint times, see `.store()`.
+    /// # Arguments
+    /// - `sprint_times` - A reference to the `HighScores` to store, ranked by `Ranking::Time`
+    /// # Returns
+    /// - `Result<(), StorageError>` - An error if the record does not fit
+    pub fn store_sprint_times(&mut self, sprint_times: &HighScores) -> Result<(), StorageError> {
+        let bytes: [u8; HIGH_SCORES_BYTES] = sprint_times.to_bytes();
+        self.store(RecordKind::SprintTimes, &bytes)
+    }
+
+    /// Load the most recent valid settings.
+    /// # Returns
+    /// - `Option<Settings>` - The settings, or `None` if none were stored, they are from another version or
+    ///   invalid
+    pub fn load_settings(&self) -> Option<Settings> {
+        Settings::from_bytes(self.load(RecordKind::Settings)?.try_into().ok()?).ok()
+    }
+
+    /// Store the settings, see `.store()`.
+    /// # Arguments
+    /// - `settings` - A reference to the `Settings` to store
+    /// # Returns
+    /// - `Result<(), StorageError>` - An error if the record does not fit
+    pub fn store_settings(&mut self, settings: &Settings) -> Result<(), StorageError> {
+        let bytes: [u8; SETTINGS_BYTES] = settings.to_bytes();
+        self.store(RecordKind::Settings, &bytes)
+    }
+
+    /// Load the most recent valid all-time placement heatmap.
+    /// # Returns
+    /// - `Option<PlacementHeatmap>` - The heatmap, or `None` if none was stored or it is of another board size
+    pub fn load_heatmap<const ROWS: usize, const COLS: usize>(
+        &self,
+    ) -> Option<PlacementHeatmap<ROWS, COLS>> {
+        PlacementHeatmap::from_bytes(self.load(RecordKind::Heatmap)?)
+    }
+
+    /// Store the all-time placement heatmap, see `.store()`.
+    /// # Arguments
+    /// - `heatmap` - A reference to the `PlacementHeatmap` to store
+    /// # Returns
+    /// - `Result<(), StorageError>` - An error if the record does not fit
+    pub fn store_heatmap<const ROWS: usize, const COLS: usize>(
+        &mut self,
+        heatmap: &PlacementHeatmap<ROWS, COLS>,
+    ) -> Result<(), StorageError> {
+        self.store(RecordKind::Heatmap, &heatmap.to_bytes())
+    }
+
+    /// Load the most recent valid lifetime totals.
+    /// # Returns
+    /// - `LifetimeStats` - The totals, or all 0 if none were stored, e.g. on a freshly formatted flash, or they are
+    ///   of another size
+    pub fn load_lifetime_stats(&self) -> LifetimeStats {
+        self.load(RecordKind::Lifetime)
+            .and_then(|payload| payload.try_into().ok())
+            .map(LifetimeStats::from_bytes)
+            .unwrap_or_default()
+    }
+
+    /// Store the lifetime totals, see `.store()`.
+    /// # Arguments
+    /// - `lifetime` - A reference to the `LifetimeStats` to store
+    /// # Returns
+    /// - `Result<(), StorageError>` - An error if the record does not fit
+    pub fn store_lifetime_stats(&mut self, lifetime: &LifetimeStats) -> Result<(), StorageError> {
+        let bytes: [u8; LIFETIME_BYTES] = lifetime.to_bytes();
+        self.store(RecordKind::Lifetime, &bytes)
+    }
+
+    /// Load the most recent valid replay, e.g. the one frozen on the last game over before a power cycle.
+    /// # Returns
+    /// - `Option<Replay>` - The replay, or `None` if none was stored or it is of another version
+    pub fn load_replay(&self) -> Option<Replay> {
+        Replay::from_bytes(self.load(RecordKind::Replay)?).ok()
+    }
+
+    /// Store a replay, see `.store()`.
+    /// # Arguments
+    /// - `replay` - A reference to the `Replay` to store, at most `MAX_REPLAY_BYTES` to leave room for the other
+    ///   kinds, see `ReplayRecorder::get_frozen_within()`
+    /// # Returns
+    /// - `Result<(), StorageError>` - An error if the record does not fit
+    pub fn store_replay(&mut self, replay: &Replay) -> Result<(), StorageError> {
+        self.store(RecordKind::Replay, &replay.to_bytes())
+    }
+}
+
+/// The high scores, sprint times and menu settings of a game as last stored, to store each again once it changes.
+/// # Attributes
+/// - `high_scores` - The stored high-score table
+/// - `sprint_times` - The stored table of the best sprint times
+/// - `settings` - The stored settings of the menu, which the next game starts with
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Persisted {
+    high_scores: HighScores,
+    sprint_times: HighScores,
+    settings: Settings,
+}
+
+impl Persisted {
+    /// Restore the high scores and sprint times of a store into a game, and take them with the settings of its menu
+    /// as stored, e.g. at boot after creating the game with the stored settings.
+    /// # Arguments
+    /// - `store` - A reference to the `RecordStore` to load from
+    /// - `game` - A muteable reference to the `Game` to restore into
+    /// # Returns
+    /// - `Persisted` - What is stored for the game
+    pub fn restore<F: FlashOps, B: BoardOps<Cell> + Clone>(
+        store: &RecordStore<F>,
+        game: &mut Game<B>,
+    ) -> Self {
+        if let Some(high_scores) = store.load_high_scores() {
+            game.set_high_scores(high_scores);
+        }
+        if let Some(sprint_times) = store.load_sprint_times() {
+            game.set_sprint_times(sprint_times);
+        }
+        Persisted {
+            high_scores: *game.get_high_scores(),
+            sprint_times: *game.get_sprint_times(),
+            settings: *game.get_menu().get_settings(),
+        }
+    }
+
+    /// Store the tables and settings that changed since they were last stored, e.g. after every tick, so an entered
+    /// name or an option changed in the menu survives a power cycle.
+    /// # Arguments
+    /// - `store` - A muteable reference to the `RecordStore` to store into
+    /// - `game` - A reference to the `Game`
+    /// # Returns
+    /// - `Result<(), StorageError>` - An error if a record does not fit, it is stored again on the next call
+    pub fn sync<F: FlashOps, B: BoardOps<Cell> + Clone>(
+        &mut self,
+        store: &mut RecordStore<F>,
+        game: &Game<B>,
+    ) -> Result<(), StorageError> {
+        if *game.get_high_scores() != self.high_scores {
+            store.store_high_scores(game.get_high_scores())?;
+            self.high_scores = *game.get_high_scores();
+        }
+        if *game.get_sprint_times() != self.sprint_times {
+            store.store_sprint_times(game.get_sprint_times())?;
+            self.sprint_times = *game.get_sprint_times();
+        }
+        if *game.get_menu().get_settings() != self.settings {
+            store.store_settings(game.get_menu().get_settings())?;
+            self.settings = *game.get_menu().get_settings();
+        }
+        Ok(())
+    }
+}
+
+/// The CRC-32 of some bytes, with the reflected polynomial 0xEDB88320 of zlib and Ethernet.
+pub(crate) fn _crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0, |crc: u32, byte| {
+        (0..8).fold(crc ^ *byte as u32, |crc, _| match crc & 1 {
+            1 => (crc >> 1) ^ 0xEDB8_8320,
+            _ => crc >> 1,
+        })
+    })
+}
+
+#[cfg(feature = "rp2040")]
+pub use xip::XipFlash;
+
+#[cfg(feature = "rp2040")]
+mod xip {
+    use super::{FlashOps, SECTOR_BYTES};
+    use rp2040_hal::rom_data;
+
+    /// The address the flash is mapped to by the execute-in-place (XIP) cache.
+    const XIP_BASE: usize = 0x1000_0000;
+
+    /// The number of bytes of the flash of the Raspberry Pi Pico.
+    const FLASH_BYTES: usize = 2048 * 1024;
+
+    /// The number of bytes the boot ROM programs at once.
+    const PAGE_BYTES: usize = 256;
+
+    /// The number of bytes of the second stage bootloader at the start of the flash.
+    const BOOT2_BYTES: usize = 256;
+
+    /// The last sector of the flash of the RP2040, reserved in `memory.x`.
+    ///
+    /// Erasing and programming go through the boot ROM with XIP disabled, so they run from RAM with the
+    /// interrupts disabled, and the second stage bootloader is rerun afterwards to restore the fast XIP mode.
+    /// Both stall the other core if it runs from flash, which the firmware does not use.
+    pub struct XipFlash {
+        _private: (),
+    }
+
+    impl XipFlash {
+        /// Take the last sector of the flash.
+        /// # Returns
+        /// - `XipFlash` - The sector, only to be taken once
+        pub fn new() -> Self {
+            XipFlash { _private: () }
+        }
+    }
+
+    impl Default for XipFlash {
+        fn default() -> Self {
+            XipFlash::new()
+        }
+    }
+
+    impl FlashOps for XipFlash {
+        fn as_bytes(&self) -> &[u8] {
+            let address = XIP_BASE + FLASH_BYTES - SECTOR_BYTES;
+            unsafe { core::slice::from_raw_parts(address as *const u8, SECTOR_BYTES) }
+        }
+
+        fn erase(&mut self) {
+            _write((FLASH_BYTES - SECTOR_BYTES) as u32, None);
+        }
+
+        fn program(&mut self, offset: usize, data: &[u8]) {
+            // The boot ROM programs whole pages, where the erased bytes leave the programmed bytes unchanged.
+            let start = offset - offset % PAGE_BYTES;
+            for page_start in (start..offset + data.len()).step_by(PAGE_BYTES) {
+                let mut page = [0xFF; PAGE_BYTES];
+                for (index, byte) in page.iter_mut().enumerate() {
+                    if let Some(value) = (page_start + index)
+                        .checked_sub(offset)
+                        .and_then(|at| data.get(at))
+                    {
+                        *byte = *value;
+                    }
+                }
+                _write(
+                    (FLASH_BYTES - SECTOR_BYTES + page_start) as u32,
+                    Some(&page),
+                );
+            }
+        }
+    }
+
+    /// The boot ROM functions to write the flash, looked up while the flash can still be read.
+    struct RomFunctions {
+        connect_internal_flash: unsafe extern "C" fn(),
+        flash_exit_xip: unsafe extern "C" fn(),
+        flash_range_erase: unsafe extern "C" fn(u32, usize, u32, u8),
+        flash_range_program: unsafe extern "C" fn(u32, *const u8, usize),
+        flash_flush_cache: unsafe extern "C" fn(),
+    }
+
+    /// Erase the sector at an offset in the flash, or program a page at it.
+    fn _write(address: u32, page: Option<&[u8; PAGE_BYTES]>) {
+        let functions = RomFunctions {
+            connect_internal_flash: rom_data::connect_internal_flash::ptr(),
+            flash_exit_xip: rom_data::flash_exit_xip::ptr(),
+            flash_range_erase: rom_data::flash_range_erase::ptr(),
+            flash_range_program: rom_data::flash_range_program::ptr(),
+            flash_flush_cache: rom_data::flash_flush_cache::ptr(),
+        };
+        let mut boot2 = [0u32; BOOT2_BYTES / 4];
+        unsafe {
+            rom_data::memcpy44(
+                boot2.as_mut_ptr(),
+                XIP_BASE as *const u32,
+                BOOT2_BYTES as u32,
+            );
+        }
+        cortex_m::interrupt::free(|_| unsafe {
+            _write_from_ram(address, page, &functions, &boot2)
+        });
+    }
+
+    /// Write the flash with XIP disabled, running from RAM so it does not fetch instructions from the flash.
+    #[inline(never)]
+    #[link_section = ".data.ram_func"]
+    unsafe fn _write_from_ram(
+        address: u32,
+        page: Option<&[u8; PAGE_BYTES]>,
+        functions: &RomFunctions,
+        boot2: &[u32; BOOT2_BYTES / 4],
+    ) {
+        (functions.connect_internal_flash)();
+        (functions.flash_exit_xip)();
+        match page {
+            // The 0x20 sector erase command, erasing 4 KB at a time.
+            None => (functions.flash_range_erase)(address, SECTOR_BYTES, SECTOR_BYTES as u32, 0x20),
+            Some(page) => (functions.flash_range_program)(address, page.as_ptr(), PAGE_BYTES),
+        }
+        (functions.flash_flush_cache)();
+        // Rerun the second stage bootloader from RAM, entering the XIP mode it configures, thumb bit set.
+        let boot2: extern "C" fn() =
+            core::mem::transmute((boot2.as_ptr() as usize + 1) as *const ());
+        boot2();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        append_offset, encode_record, latest, record_bytes, records, FlashOps, Persisted,
+        RecordKind, RecordStore, StorageError, MAX_REPLAY_BYTES, SECTOR_BYTES,
+    };
+    use crate::coordinate::Coordinate;
+    use crate::game::{Game, GameState, NameEntry};
+    use crate::heatmap::PlacementHeatmap;
+    use crate::highscores::{HighScores, Ranking, HIGH_SCORES_BYTES};
+    use crate::input::{Action, InputState};
+    use crate::lifetime::{LifetimeStats, LIFETIME_BYTES};
+    use crate::replay::Replay;
+    use crate::settings::{GameMode, Settings, StackColorMode, SETTINGS_BYTES};
+    use crate::testing::GameFixture;
+
+    /// A sector in memory behaving like NOR flash, where programming only clears bits.
+    struct FakeFlash {
+        bytes: Vec<u8>,
+        erases: usize,
+    }
+
+    impl FakeFlash {
+        fn new() -> Self {
+            FakeFlash {
+                bytes: vec![0xFF; SECTOR_BYTES],
+                erases: 0,
+            }
+        }
+    }
+
+    impl FlashOps for FakeFlash {
+        fn as_bytes(&self) -> &[u8] {
+            &self.bytes
+        }
+
+        fn erase(&mut self) {
+            self.bytes.fill(0xFF);
+            self.erases += 1;
+        }
+
+        fn program(&mut self, offset: usize, data: &[u8]) {
+            for (byte, value) in self.bytes[offset..offset + data.len()].iter_mut().zip(data) {
+                *byte &= value;
+            }
+        }
+    }
+
+    #[test]
+    fn test_crc() {
+        // The check value of CRC-32.
+        assert_eq!(super::_crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn test_latest_wins() {
+        let mut store = RecordStore::new(FakeFlash::new());
+        assert_eq!(store.load(RecordKind::Settings), None);
+        store.store(RecordKind::Settings, &[1, 2, 3]).unwrap();
+        store.store(RecordKind::HighScores, &[4]).unwrap();
+        store.store(RecordKind::Settings, &[5, 6]).unwrap();
+        assert_eq!(store.load(RecordKind::Settings), Some(&[5, 6][..]));
+        assert_eq!(store.load(RecordKind::HighScores), Some(&[4][..]));
+        let sector = store.get_flash().as_bytes();
+        assert_eq!(records(sector).count(), 3);
+        assert_eq!(latest(sector, RecordKind::Settings).unwrap().seq, 2);
+        assert_eq!(
+            append_offset(sector),
+            Some(record_bytes(3) + record_bytes(1) + record_bytes(2))
+        );
+    }
+
+    #[test]
+    fn test_torn_write() {
+        let mut store = RecordStore::new(FakeFlash::new());
+        store.store(RecordKind::Settings, &[1; 40]).unwrap();
+        // Power is lost halfway through the next record.
+        let offset = append_offset(store.get_flash().as_bytes()).unwrap();
+        let torn = encode_record(RecordKind::Settings, 1, &[2; 40]);
+        store.flash.program(offset, &torn[..torn.len() / 2]);
+        assert_eq!(store.load(RecordKind::Settings), Some(&[1; 40][..]));
+        // The next record is appended after the space the torn one claims.
+        store.store(RecordKind::Settings, &[3; 40]).unwrap();
+        assert_eq!(store.load(RecordKind::Settings), Some(&[3; 40][..]));
+        assert_eq!(
+            latest(store.get_flash().as_bytes(), RecordKind::Settings)
+                .unwrap()
+                .offset,
+            offset + torn.len()
+        );
+        // Power is lost right after the kind of the next record, so its length is unreadable.
+        let offset = append_offset(store.get_flash().as_bytes()).unwrap();
+        store.flash.program(offset, &[RecordKind::Settings as u8]);
+        assert_eq!(append_offset(store.get_flash().as_bytes()), None);
+        assert_eq!(store.load(RecordKind::Settings), Some(&[3; 40][..]));
+        // The next store erases the sector rather than appending a record the scan would never reach.
+        store.store(RecordKind::Settings, &[4; 40]).unwrap();
+        assert_eq!(store.get_flash().erases, 1);
+        assert_eq!(store.load(RecordKind::Settings), Some(&[4; 40][..]));
+    }
+
+    #[test]
+    fn test_corrupted_crc() {
+        let mut store = RecordStore::new(FakeFlash::new());
+        store.store(RecordKind::HighScores, &[1; 8]).unwrap();
+        store.store(RecordKind::HighScores, &[2; 8]).unwrap();
+        store.store(RecordKind::Settings, &[3; 8]).unwrap();
+        // A flipped bit in the second record falls back to the first, and the scan continues after it.
+        store.flash.bytes[record_bytes(8) + 9] ^= 0x01;
+        assert_eq!(store.load(RecordKind::HighScores), Some(&[1; 8][..]));
+        assert_eq!(store.load(RecordKind::Settings), Some(&[3; 8][..]));
+        // A damaged length stops the scan.
+        store.flash.bytes[2] = 0x7F;
+        assert_eq!(store.load(RecordKind::Settings), None);
+    }
+
+    #[test]
+    fn test_heatmap() {
+        // The all-time heatmap keeps its counts next to the other records, and only loads for its own shape.
+        let mut store = RecordStore::new(FakeFlash::new());
+        assert_eq!(store.load_heatmap::<22, 10>(), None);
+        let mut heatmap = PlacementHeatmap::<22, 10>::new();
+        for row in 0..22 {
+            for _ in 0..row * 100 {
+                heatmap.add(Coordinate { row, col: row % 10 });
+            }
+        }
+        store.store_settings(&Settings::default()).unwrap();
+        store.store_heatmap(&heatmap).unwrap();
+        assert_eq!(store.load_heatmap(), Some(heatmap));
+        assert_eq!(store.load_heatmap::<20, 10>(), None);
+        assert_eq!(store.load_settings(), Some(Settings::default()));
+    }
+
+    #[test]
+    fn test_lifetime() {
+        // A blank sector starts from zeros, and the totals survive the sector filling up with other records.
+        let mut store = RecordStore::new(FakeFlash::new());
+        assert_eq!(store.load_lifetime_stats(), LifetimeStats::new());
+        let mut lifetime = LifetimeStats::new();
+        lifetime.merge(&LifetimeStats::from_bytes(&[1; LIFETIME_BYTES]));
+        store.store_lifetime_stats(&lifetime).unwrap();
+        while store.get_flash().erases == 0 {
+            store.store_settings(&Settings::default()).unwrap();
+        }
+        assert_eq!(store.load_lifetime_stats(), lifetime);
+        assert_eq!(store.load_lifetime_stats().get_games(), 0x0101_0101);
+    }
+
+    #[test]
+    fn test_replay() {
+        // A replay of the maximum size survives the sector filling up with the other kinds.
+        let mut store = RecordStore::new(FakeFlash::new());
+        assert!(store.load_replay().is_none());
+        let mut replay = Replay::new(Settings::default(), 5, usize::MAX);
+        while replay.get_serialized_size() + 4 <= MAX_REPLAY_BYTES {
+            replay.record(&InputState::from_action(Action::Left));
+            replay.record(&InputState::default());
+        }
+        store.store_high_scores(&HighScores::default()).unwrap();
+        store.store_lifetime_stats(&LifetimeStats::new()).unwrap();
+        store
+            .store_heatmap(&PlacementHeatmap::<22, 10>::new())
+            .unwrap();
+        store.store_replay(&replay).unwrap();
+        while store.get_flash().erases == 0 {
+            store.store_settings(&Settings::default()).unwrap();
+        }
+        assert_eq!(store.load_replay().unwrap().to_bytes(), replay.to_bytes());
+    }
+
+    #[test]
+    fn test_persisted() {
+        // The tables and settings of the last power cycle are restored, and stored again only once they change.
+        let mut store = RecordStore::new(FakeFlash::new());
+        let mut high_scores = HighScores::default();
+        high_scores.insert(*b"ABC", 1000, 10, 2);
+        store.store_high_scores(&high_scores).unwrap();
+        let settings = Settings {
+            ghost: false,
+            ..Settings::default()
+        };
+        store.store_settings(&settings).unwrap();
+        let mut game = GameFixture::new(store.load_settings().unwrap())
+            .phase(GameState::NameEntry(NameEntry {
+                initials: *b"XYZ",
+                cursor: 2,
+            }))
+            .score(2000, 20, 3)
+            .build()
+            .unwrap();
+        let mut persisted = Persisted::restore(&store, &mut game);
+        assert_eq!(game.get_high_scores(), &high_scores);
+        assert_eq!(game.get_sprint_times(), &HighScores::new(Ranking::Time));
+        let count = records(store.get_flash().as_bytes()).count();
+        persisted.sync(&mut store, &game).unwrap();
+        assert_eq!(records(store.get_flash().as_bytes()).count(), count);
+        // Accepting the last initial inserts the entry, an option changes in the menu and a sprint ends in a name.
+        game.tick(&InputState::from_action(Action::RotateCw));
+        game.set_stack_colors(StackColorMode::MONOCHROME);
+        persisted.sync(&mut store, &game).unwrap();
+        assert_eq!(records(store.get_flash().as_bytes()).count(), count + 2);
+        let mut sprint = GameFixture::new(Settings {
+            mode: GameMode::SPRINT,
+            ..settings
+        })
+        .build()
+        .unwrap();
+        let mut persisted = Persisted::restore(&store, &mut sprint);
+        for _ in 0..30 {
+            sprint.tick(&InputState::default());
+        }
+        sprint.set_state(GameState::NameEntry(NameEntry {
+            initials: *b"SPR",
+            cursor: 2,
+        }));
+        sprint.tick(&InputState::from_action(Action::RotateCw));
+        persisted.sync(&mut store, &sprint).unwrap();
+        // After a power cycle, the game boots with all of them.
+        let mut booted = Game::new(store.load_settings().unwrap(), 3);
+        Persisted::restore(&store, &mut booted);
+        let initials = |table: &HighScores| -> Vec<[u8; 3]> {
+            table.iter().map(|entry| entry.initials).collect()
+        };
+        assert_eq!(initials(booted.get_high_scores()), [*b"XYZ", *b"ABC"]);
+        assert_eq!(initials(booted.get_sprint_times()), [*b"SPR"]);
+        assert_eq!(
+            booted.get_settings().stack_colors,
+            StackColorMode::MONOCHROME
+        );
+        assert!(!booted.get_settings().ghost);
+    }
+
+    #[test]
+    fn test_wear_leveling() {
+        let mut store = RecordStore::new(FakeFlash::new());
+        let settings = Settings::default();
+        let mut high_scores = HighScores::default();
+        high_scores.insert(*b"ABC", 1000, 10, 2);
+        store.store_high_scores(&high_scores).unwrap();
+        // The sector fills up with settings, then is erased once keeping the high scores, by the store that no
+        // longer fits.
+        let per_sector = SECTOR_BYTES / record_bytes(SETTINGS_BYTES);
+        let mut stores = 0;
+        while store.get_flash().erases == 0 {
+            store.store_settings(&settings).unwrap();
+            stores += 1;
+        }
+        assert!(stores <= per_sector + 1);
+        assert_eq!(store.get_flash().erases, 1);
+        assert_eq!(store.load_high_scores(), Some(high_scores));
+        assert_eq!(store.load_settings(), Some(settings));
+        assert_eq!(records(store.get_flash().as_bytes()).count(), 2);
+        // Sequence numbers keep increasing across erases.
+        let sector = store.get_flash().as_bytes();
+        assert!(
+            latest(sector, RecordKind::Settings).unwrap().seq
+                > latest(sector, RecordKind::HighScores).unwrap().seq
+        );
+        assert_eq!(
+            store.store(RecordKind::Settings, &[0; SECTOR_BYTES]),
+            Err(StorageError::TooLarge(
+                record_bytes(HIGH_SCORES_BYTES) + record_bytes(SECTOR_BYTES)
+            ))
+        );
+    }
+}
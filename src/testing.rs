@@ -0,0 +1,395 @@
+//! A builder of games in the middle of play, so a test starts at the state it is about instead of playing out a long
+//! script of inputs to reach it.
+//!
+//! The board is written as text in the format the `board` command of the console prints, a line per row with `.` for
+//! an empty cell, `#` for garbage and the letter of a shape for a locked cell. The lines are aligned to the bottom of
+//! the board, so a test only writes the rows that matter. The built game has to pass `Game::validate()`, so a fixture
+//! that could never happen in play, e.g. an active piece overlapping the stack, is rejected with a `FixtureError`.
+use crate::board::{Board, BoardOps};
+use crate::cell::Cell;
+use crate::coordinate::Coordinate;
+use crate::game::{Game, GameState};
+use crate::hold::HoldSlot;
+use crate::invariants::InvariantViolation;
+use crate::piece::ActivePiece;
+use crate::rotation::kicks::RotationState;
+use crate::settings::Settings;
+use crate::tetrominoes::{Tetromino, TetrominoShape};
+use alloc::vec::Vec;
+use core::fmt;
+
+/// The most upcoming shapes a fixture can set, the queue shows them ahead of a full shuffled bag.
+pub const MAX_NEXT: usize = 7;
+
+/// Why a fixture does not make a valid game.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FixtureError {
+    /// The board has more lines than the board has rows.
+    TooManyRows { lines: usize, rows: usize },
+    /// A line of the board, counted from the top of the text, is not as wide as the board.
+    RowWidth { line: usize, width: usize },
+    /// A character of the board is not `.`, `#` or the letter of a shape.
+    UnknownCell(char),
+    /// More upcoming shapes than `MAX_NEXT`.
+    QueueTooLong(usize),
+    /// The active piece overlaps the locked cells of the board at the [row, col] of each of `cells`.
+    Overlap { cells: Vec<[usize; 2]> },
+    /// The game does not pass `Game::validate()`.
+    Invalid(InvariantViolation),
+}
+
+impl fmt::Display for FixtureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FixtureError::TooManyRows { lines, rows } => {
+                write!(f, "{} lines for a board of {} rows", lines, rows)
+            }
+            FixtureError::RowWidth { line, width } => {
+                write!(f, "line {} is not {} cells wide", line, width)
+            }
+            FixtureError::UnknownCell(char) => write!(f, "unknown cell {:?}", char),
+            FixtureError::QueueTooLong(len) => {
+                write!(f, "{} upcoming shapes, at most {}", len, MAX_NEXT)
+            }
+            FixtureError::Overlap { cells } => {
+                write!(f, "the active piece overlaps the board at {:?}", cells)
+            }
+            FixtureError::Invalid(violation) => write!(f, "invalid game: {}", violation),
+        }
+    }
+}
+
+/// Parse a board written as text onto a board, aligned to its bottom rows.
+/// # Arguments
+/// - `text` - The lines of the board, surrounding whitespace and empty lines ignored
+/// - `board` - A muteable reference to the board to fill, emptied first
+/// # Returns
+/// - `Result<(), FixtureError>` - An error if the text does not fit the board or has an unknown cell
+pub fn parse_board<B: BoardOps<Cell> + ?Sized>(
+    text: &str,
+    board: &mut B,
+) -> Result<(), FixtureError> {
+    let shape = board.get_shape();
+    let lines: Vec<&str> = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect();
+    if lines.len() > shape.row {
+        return Err(FixtureError::TooManyRows {
+            lines: lines.len(),
+            rows: shape.row,
+        });
+    }
+    board.clear();
+    let top = shape.row - lines.len();
+    for (line, text) in lines.iter().enumerate() {
+        if text.chars().count() != shape.col {
+            return Err(FixtureError::RowWidth {
+                line,
+                width: shape.col,
+            });
+        }
+        for (col, char) in text.chars().enumerate() {
+            let cell = match char {
+                '.' => continue,
+                '#' => Cell::Garbage,
+                _ => TetrominoShape::try_from(char)
+                    .map(Cell::locked)
+                    .map_err(FixtureError::UnknownCell)?,
+            };
+            board.set(
+                Coordinate {
+                    row: top + line,
+                    col,
+                },
+                cell,
+            );
+        }
+    }
+    Ok(())
+}
+
+/// A builder of a `Game` in the middle of play, see the module documentation.
+/// # Attributes
+/// - `settings` - The settings of the game
+/// - `seed` - The seed of the game, dealing the pieces after the given ones
+/// - `board` - The text of the board, or `None` for an empty board
+/// - `active` - The shape, rotation and top-left [row, col] of the active piece, or `None` for the spawned piece
+/// - `next` - The upcoming shapes, ahead of a shuffled bag
+/// - `hold` - The held shape and whether the slot is locked, or `None` for an empty slot
+/// - `score` - The points, lines and level, or `None` for a new score
+/// - `phase` - The state of the game
+pub struct GameFixture<'a> {
+    settings: Settings,
+    seed: u64,
+    board: Option<&'a str>,
+    active: Option<(TetrominoShape, RotationState, Coordinate)>,
+    next: Vec<TetrominoShape>,
+    hold: Option<(TetrominoShape, bool)>,
+    score: Option<(u32, u32, u8)>,
+    phase: GameState,
+}
+
+impl<'a> GameFixture<'a> {
+    /// Create a fixture of a game in play on an empty board.
+    /// # Arguments
+    /// - `settings` - The `Settings` of the game
+    /// # Returns
+    /// - `GameFixture` - The fixture in the `GameState::Playing` state, with the pieces of seed 7
+    pub fn new(settings: Settings) -> Self {
+        GameFixture {
+            settings,
+            seed: 7,
+            board: None,
+            active: None,
+            next: Vec::new(),
+            hold: None,
+            score: None,
+            phase: GameState::Playing,
+        }
+    }
+
+    /// Set the seed, dealing the pieces after the given ones.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Set the locked cells, see `parse_board()`.
+    pub fn board_str(mut self, text: &'a str) -> Self {
+        self.board = Some(text);
+        self
+    }
+
+    /// Set the active piece.
+    /// # Arguments
+    /// - `shape` - The `TetrominoShape` of the piece
+    /// - `rotation` - The `RotationState` of the piece
+    /// - `coord` - The top-left corner of the mask of the piece on the board
+    pub fn active(
+        mut self,
+        shape: TetrominoShape,
+        rotation: RotationState,
+        coord: Coordinate,
+    ) -> Self {
+        self.active = Some((shape, rotation, coord));
+        self
+    }
+
+    /// Set the upcoming shapes, at most `MAX_NEXT`.
+    pub fn queue(mut self, shapes: &[TetrominoShape]) -> Self {
+        self.next = shapes.to_vec();
+        self
+    }
+
+    /// Put a shape in the hold slot.
+    /// # Arguments
+    /// - `shape` - The held `TetrominoShape`
+    /// - `locked` - Whether the slot was already used for the active piece
+    pub fn hold(mut self, shape: TetrominoShape, locked: bool) -> Self {
+        self.hold = Some((shape, locked));
+        self
+    }
+
+    /// Set the score.
+    /// # Arguments
+    /// - `points` - The total number of points
+    /// - `lines` - The total number of cleared lines
+    /// - `level` - The current level
+    pub fn score(mut self, points: u32, lines: u32, level: u8) -> Self {
+        self.score = Some((points, lines, level));
+        self
+    }
+
+    /// Set the state of the game, pausing, sleeping or entering a name over a game in play.
+    pub fn phase(mut self, phase: GameState) -> Self {
+        self.phase = phase;
+        self
+    }
+
+    /// Build the game.
+    /// # Returns
+    /// - `Result<Game, FixtureError>` - The game, or why the fixture does not make a valid game
+    pub fn build(self) -> Result<Game, FixtureError> {
+        if self.next.len() > MAX_NEXT {
+            return Err(FixtureError::QueueTooLong(self.next.len()));
+        }
+        let mut game = Game::new(self.settings, self.seed);
+        let mut board: Board<Cell> = game.get_board().clone();
+        if let Some(text) = self.board {
+            parse_board(text, &mut board)?;
+        }
+        let piece = match self.active {
+            Some((shape, rotation, coord)) => {
+                let mut tetromino = Tetromino::from(shape);
+                while tetromino.get_rotation() != rotation {
+                    tetromino.rotate_cw();
+                }
+                ActivePiece::new(tetromino, coord)
+            }
+            None => game.get_piece().clone(),
+        };
+        let overlap: Vec<[usize; 2]> = piece
+            .get_cells()
+            .iter()
+            .filter(|cell| board.get(**cell).is_some_and(|cell| cell != Cell::Empty))
+            .map(|cell| [cell.row, cell.col])
+            .collect();
+        if !overlap.is_empty() && matches!(self.phase, GameState::Playing | GameState::Countdown(_))
+        {
+            return Err(FixtureError::Overlap { cells: overlap });
+        }
+        game.set_board(board);
+        game.set_piece(piece);
+        game.set_next(&self.next);
+        let mut hold = HoldSlot::default();
+        if let Some((shape, locked)) = self.hold {
+            hold.swap(shape);
+            if !locked {
+                hold.unlock();
+            }
+        }
+        game.set_hold(hold);
+        if let Some((points, lines, level)) = self.score {
+            game.set_totals(points, lines);
+            game.set_level(level);
+        }
+        game.set_state(self.phase);
+        game.validate().map_err(FixtureError::Invalid)?;
+        Ok(game)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FixtureError, GameFixture, MAX_NEXT};
+    use crate::board::BoardOps;
+    use crate::cell::Cell;
+    use crate::coordinate::Coordinate;
+    use crate::game::GameState;
+    use crate::invariants::InvariantViolation;
+    use crate::rotation::kicks::RotationState;
+    use crate::settings::Settings;
+    use crate::tetrominoes::TetrominoShape::{self, I, L, O, S, T, Z};
+
+    #[test]
+    fn test_build() {
+        let game = GameFixture::new(Settings::default())
+            .board_str(
+                "
+                ..........
+                #########.
+                ",
+            )
+            .active(I, RotationState::Right, Coordinate::from_array([0, 9]))
+            .queue(&[O, S, Z])
+            .hold(T, true)
+            .score(1200, 12, 2)
+            .build()
+            .unwrap();
+        let board = game.get_board();
+        assert_eq!(
+            board.get(Coordinate::from_array([19, 0])),
+            Some(Cell::Garbage)
+        );
+        assert_eq!(
+            board.get(Coordinate::from_array([19, 9])),
+            Some(Cell::Empty)
+        );
+        assert_eq!(
+            board
+                .cells()
+                .filter(|(_, cell)| *cell != Cell::Empty)
+                .count(),
+            9
+        );
+        let cells = game.get_piece().get_cells();
+        assert!(cells.iter().all(|cell| cell.col == 9));
+        assert_eq!(
+            game.get_piece().get_tetromino().get_rotation(),
+            RotationState::Right
+        );
+        let next: Vec<TetrominoShape> = game.get_queue().peek(4).copied().collect();
+        assert_eq!(next[..3], [O, S, Z]);
+        assert_eq!(
+            (game.get_hold().get_shape(), game.get_hold().can_hold()),
+            (Some(T), false)
+        );
+        assert_eq!(game.get_score().get_points(), 1200);
+        assert_eq!(game.get_score().get_level(), 2);
+        assert_eq!(game.get_state(), GameState::Playing);
+        // The locked cells of the shapes keep their shape, under a paused game.
+        let game = GameFixture::new(Settings::default())
+            .seed(3)
+            .board_str("LLLOO.....")
+            .phase(GameState::Paused)
+            .build()
+            .unwrap();
+        assert_eq!(game.seed(), 3);
+        assert_eq!(game.get_state(), GameState::Paused);
+        assert_eq!(
+            game.get_board().get(Coordinate::from_array([19, 3])),
+            Some(Cell::locked(O))
+        );
+    }
+
+    #[test]
+    fn test_rejected() {
+        let rejected = |fixture: GameFixture| fixture.build().err().unwrap();
+        let fixture = || GameFixture::new(Settings::default());
+        assert_eq!(
+            rejected(fixture().board_str("..........\n.........")),
+            FixtureError::RowWidth { line: 1, width: 10 }
+        );
+        assert_eq!(
+            rejected(fixture().board_str("....x.....")),
+            FixtureError::UnknownCell('x')
+        );
+        assert_eq!(
+            rejected(fixture().board_str(&"..........\n".repeat(21))),
+            FixtureError::TooManyRows {
+                lines: 21,
+                rows: 20
+            }
+        );
+        assert_eq!(
+            rejected(fixture().queue(&[I; MAX_NEXT + 1])),
+            FixtureError::QueueTooLong(MAX_NEXT + 1)
+        );
+        // The active piece lies on the board where the stack is.
+        let error = rejected(fixture().board_str("OO........\nOO........").active(
+            I,
+            RotationState::Spawn,
+            Coordinate::from_array([18, 0]),
+        ));
+        assert_eq!(
+            error,
+            FixtureError::Overlap {
+                cells: vec![[18, 0], [18, 1]]
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "the active piece overlaps the board at [[18, 0], [18, 1]]"
+        );
+        // The active piece hangs over the right wall.
+        assert_eq!(
+            rejected(fixture().active(O, RotationState::Spawn, Coordinate::from_array([0, 9]))),
+            FixtureError::Invalid(InvariantViolation::PieceOutOfBounds)
+        );
+        // A locked hold slot without holding, or before the first piece is in play.
+        let settings = Settings {
+            hold: false,
+            ..Default::default()
+        };
+        assert_eq!(
+            rejected(GameFixture::new(settings).hold(L, true)),
+            FixtureError::Invalid(InvariantViolation::HoldLocked)
+        );
+        assert_eq!(
+            rejected(fixture().hold(L, true).phase(GameState::Countdown(3))),
+            FixtureError::Invalid(InvariantViolation::HoldLocked)
+        );
+    }
+}
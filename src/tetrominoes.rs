@@ -1,12 +1,17 @@
-#![allow(dead_code)]
-
-use crate::color::{Color, ColorRgb};
+use crate::color::{Color, Rgb};
 use crate::coordinate::Coordinate;
+use crate::grid::Array2D;
 use crate::rotation::generate_matrices;
-use array2d::Array2D;
+use crate::rotation::kicks::RotationState;
+use alloc::borrow::Cow;
+use alloc::boxed::Box;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, Ordering};
 
 // TODO: how to save a list of TetrominoShapes, each with color and array. Generate e.g. Vec<Tetromino>
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum TetrominoShape {
     I,
     J,
@@ -17,16 +22,29 @@ pub enum TetrominoShape {
     Z,
 }
 
-pub struct Tetromino<T> {
+/// All seven shapes, in the order of their discriminants.
+pub const SHAPES: [TetrominoShape; 7] = [
+    TetrominoShape::I,
+    TetrominoShape::J,
+    TetrominoShape::L,
+    TetrominoShape::O,
+    TetrominoShape::S,
+    TetrominoShape::T,
+    TetrominoShape::Z,
+];
+
+#[derive(Clone, Debug)]
+pub struct Tetromino<T: Clone + 'static> {
     /// A struct reflecting a Tetromino block.
     /// # Attributes
     /// - `shape` - A public `TetrominoShape` enum variant representing the shape
-    /// - `color` - A public `ColorRgb` struct representing the LED color
-    /// - `masks` - An array of binary masks for the 4 rotation states
+    /// - `color` - A public `Rgb` struct representing the LED color
+    /// - `masks` - An array of binary masks for the 4 rotation states, borrowed from `PIECE_CACHE` for the standard
+    ///   pieces so spawning them does not allocate
     /// - `index` - The index of the currently used mask
     pub shape: TetrominoShape,
-    pub color: ColorRgb,
-    masks: [Array2D<T>; 4],
+    pub color: Rgb,
+    masks: Cow<'static, [Array2D<T>; 4]>,
     index: usize,
 }
 
@@ -37,15 +55,15 @@ where
     /// Create a new `Tetromino` based on a shape.
     /// # Arguments
     /// - `shape` - A `Tetrominoshape` enum variant representing the shape
-    /// - `color` - A `ColorRgb` struct representing the red, green and blue component
+    /// - `color` - An `Rgb` struct representing the red, green and blue component
     /// - `mask` - An initial mask as an `Array2D<T>`, to be rotated three times
     /// # Returns
     /// - `Tetromino` - An instance of a Tetromino struct
-    pub fn new(shape: TetrominoShape, color: ColorRgb, mask: Array2D<T>) -> Self {
+    pub fn new(shape: TetrominoShape, color: Rgb, mask: Array2D<T>) -> Self {
         Tetromino {
             shape,
             color,
-            masks: generate_matrices(mask),
+            masks: Cow::Owned(generate_matrices(mask)),
             index: 0,
         }
     }
@@ -71,6 +89,13 @@ where
         self.get_shape() - [1, 1]
     }
 
+    /// Get the rotation state of the current mask.
+    /// # Returns
+    /// - `RotationState` - The rotation state, relative to the spawn state
+    pub fn get_rotation(&self) -> RotationState {
+        RotationState::from_index(self.index)
+    }
+
     /// Increment the index, representing a rotation of 90 degrees clockwise.
     pub fn rotate_cw(&mut self) {
         self.index = (self.index + 1) % self.masks.len();
@@ -82,138 +107,192 @@ where
     }
 }
 
-impl From<TetrominoShape> for Tetromino<bool> {
-    /// Convert from a `TetrominoShape` to a `Tetromino`.
+impl From<TetrominoShape> for Color {
+    /// Convert from a `TetrominoShape` to its guideline `Color`.
     fn from(shape: TetrominoShape) -> Self {
         match shape {
-            TetrominoShape::I => Tetromino {
-                shape: TetrominoShape::I,
-                color: ColorRgb::from(Color::Cyan),
-                index: 0,
-                masks: generate_matrices(
-                    Array2D::from_row_major(
-                        &[
-                            true, true, true, true, // o o o o
-                        ],
-                        1,
-                        4,
-                    )
-                    .unwrap(),
-                ),
-            },
+            TetrominoShape::I => Color::Cyan,
+            TetrominoShape::J => Color::Blue,
+            TetrominoShape::L => Color::Orange,
+            TetrominoShape::O => Color::Yellow,
+            TetrominoShape::S => Color::Green,
+            TetrominoShape::T => Color::Purple,
+            TetrominoShape::Z => Color::Red,
+        }
+    }
+}
 
-            TetrominoShape::J => Tetromino {
-                shape: TetrominoShape::J,
-                color: ColorRgb::from(Color::Blue),
-                index: 0,
-                masks: generate_matrices(
-                    Array2D::from_row_major(
-                        &[
-                            true, false, false, //  o . .
-                            true, true, true, //    o o o
-                        ],
-                        2,
-                        3,
-                    )
-                    .unwrap(),
-                ),
-            },
+impl From<TetrominoShape> for char {
+    /// Convert from a `TetrominoShape` to its uppercase letter.
+    fn from(shape: TetrominoShape) -> Self {
+        match shape {
+            TetrominoShape::I => 'I',
+            TetrominoShape::J => 'J',
+            TetrominoShape::L => 'L',
+            TetrominoShape::O => 'O',
+            TetrominoShape::S => 'S',
+            TetrominoShape::T => 'T',
+            TetrominoShape::Z => 'Z',
+        }
+    }
+}
 
-            TetrominoShape::L => Tetromino {
-                shape: TetrominoShape::L,
-                color: ColorRgb::from(Color::Orange),
-                index: 0,
-                masks: generate_matrices(
-                    Array2D::from_row_major(
-                        &[
-                            false, false, true, //  . . o
-                            true, true, true, //    o o o
-                        ],
-                        2,
-                        3,
-                    )
-                    .unwrap(),
-                ),
-            },
+impl TryFrom<char> for TetrominoShape {
+    type Error = char;
 
-            TetrominoShape::O => Tetromino {
-                shape: TetrominoShape::O,
-                color: ColorRgb::from(Color::Yellow),
-                index: 0,
-                masks: generate_matrices(
-                    Array2D::from_row_major(
-                        &[
-                            true, true, // o o
-                            true, true, // o o
-                        ],
-                        2,
-                        2,
-                    )
-                    .unwrap(),
-                ),
-            },
+    /// Convert from a letter to its `TetrominoShape`, in either case, returning the letter if it is not a shape.
+    fn try_from(letter: char) -> Result<Self, Self::Error> {
+        match letter.to_ascii_uppercase() {
+            'I' => Ok(TetrominoShape::I),
+            'J' => Ok(TetrominoShape::J),
+            'L' => Ok(TetrominoShape::L),
+            'O' => Ok(TetrominoShape::O),
+            'S' => Ok(TetrominoShape::S),
+            'T' => Ok(TetrominoShape::T),
+            'Z' => Ok(TetrominoShape::Z),
+            _ => Err(letter),
+        }
+    }
+}
 
-            TetrominoShape::S => Tetromino {
-                shape: TetrominoShape::S,
-                color: ColorRgb::from(Color::Green),
-                index: 0,
-                masks: generate_matrices(
-                    Array2D::from_row_major(
-                        &[
-                            false, true, true, // . x x
-                            true, true, false, // x x .
-                        ],
-                        2,
-                        3,
-                    )
-                    .unwrap(),
-                ),
-            },
+impl From<TetrominoShape> for Tetromino<bool> {
+    /// Convert from a `TetrominoShape` to a `Tetromino`, borrowing its masks from `PIECE_CACHE`.
+    fn from(shape: TetrominoShape) -> Self {
+        Tetromino {
+            shape,
+            color: Rgb::from(Color::from(shape)),
+            masks: Cow::Borrowed(_cached_masks(shape)),
+            index: 0,
+        }
+    }
+}
 
-            TetrominoShape::T => Tetromino {
-                shape: TetrominoShape::T,
-                color: ColorRgb::from(Color::Purple),
-                index: 0,
-                masks: generate_matrices(
-                    Array2D::from_row_major(
-                        &[
-                            false, true, false, //  . x .
-                            true, true, true, //    x x x
-                        ],
-                        2,
-                        3,
-                    )
-                    .unwrap(),
-                ),
-            },
+/// The panic message of a spawn mask whose number of cells does not match its shape, a bug in the table below.
+const MALFORMED_MASK: &str = "the spawn mask has rows * cols cells";
 
-            TetrominoShape::Z => Tetromino {
-                shape: TetrominoShape::Z,
-                color: ColorRgb::from(Color::Red),
-                index: 0,
-                masks: generate_matrices(
-                    Array2D::from_row_major(
-                        &[
-                            true, true, false, //   x x .
-                            false, true, true, //   . x x
-                        ],
-                        2,
-                        3,
-                    )
-                    .unwrap(),
-                ),
-            },
+/// The spawn mask of every shape, to be rotated three times.
+fn _spawn_mask(shape: TetrominoShape) -> Array2D<bool> {
+    match shape {
+        TetrominoShape::I => Array2D::from_row_major(
+            &[
+                true, true, true, true, // o o o o
+            ],
+            1,
+            4,
+        )
+        .expect(MALFORMED_MASK),
+        TetrominoShape::J => Array2D::from_row_major(
+            &[
+                true, false, false, //  o . .
+                true, true, true, //    o o o
+            ],
+            2,
+            3,
+        )
+        .expect(MALFORMED_MASK),
+        TetrominoShape::L => Array2D::from_row_major(
+            &[
+                false, false, true, //  . . o
+                true, true, true, //    o o o
+            ],
+            2,
+            3,
+        )
+        .expect(MALFORMED_MASK),
+        TetrominoShape::O => Array2D::from_row_major(
+            &[
+                true, true, // o o
+                true, true, // o o
+            ],
+            2,
+            2,
+        )
+        .expect(MALFORMED_MASK),
+        TetrominoShape::S => Array2D::from_row_major(
+            &[
+                false, true, true, // . x x
+                true, true, false, // x x .
+            ],
+            2,
+            3,
+        )
+        .expect(MALFORMED_MASK),
+        TetrominoShape::T => Array2D::from_row_major(
+            &[
+                false, true, false, //  . x .
+                true, true, true, //    x x x
+            ],
+            2,
+            3,
+        )
+        .expect(MALFORMED_MASK),
+        TetrominoShape::Z => Array2D::from_row_major(
+            &[
+                true, true, false, //   x x .
+                false, true, true, //   . x x
+            ],
+            2,
+            3,
+        )
+        .expect(MALFORMED_MASK),
+    }
+}
+
+/// The masks of the 4 rotation states of every shape, in the order of `TetrominoShape`, generated on first use.
+///
+/// Every slot is either null or a leaked allocation that is never freed. Only atomic loads and stores are used,
+/// which the Cortex-M0+ of the RP2040 supports. When two threads race on an empty slot, both generate the masks
+/// and one of them leaks, which is harmless for the few bytes of a tetromino.
+#[allow(clippy::declare_interior_mutable_const)]
+static PIECE_CACHE: [AtomicPtr<[Array2D<bool>; 4]>; 7] = {
+    const EMPTY: AtomicPtr<[Array2D<bool>; 4]> = AtomicPtr::new(ptr::null_mut());
+    [EMPTY; 7]
+};
+
+/// Generate the masks of all seven shapes into `PIECE_CACHE`, so spawning a piece never allocates afterwards.
+pub fn preload_masks() {
+    for shape in SHAPES {
+        _cached_masks(shape);
+    }
+}
+
+/// Get the masks of a shape from `PIECE_CACHE`, generating them on first use.
+fn _cached_masks(shape: TetrominoShape) -> &'static [Array2D<bool>; 4] {
+    let slot = &PIECE_CACHE[shape as usize];
+    let cached = slot.load(Ordering::Acquire);
+    match cached.is_null() {
+        // SAFETY: a non-null slot points to a leaked allocation, which lives forever and is never mutated.
+        false => unsafe { &*cached },
+        true => {
+            let masks: &'static mut [Array2D<bool>; 4] =
+                Box::leak(Box::new(generate_matrices(_spawn_mask(shape))));
+            slot.store(masks, Ordering::Release);
+            masks
         }
     }
 }
 
 #[cfg(test)]
-
 mod tests {
     use crate::rotation::{rotate_ccw, rotate_cw};
 
     use super::{Tetromino, TetrominoShape};
-    use array2d::Array2D;
+    use crate::coordinate::Coordinate;
+    use crate::grid::Array2D;
+
+    #[test]
+    fn test_letters() {
+        // Every shape converts to its letter and back, in either case.
+        for letter in "IJLOSTZ".chars() {
+            let shape = TetrominoShape::try_from(letter).unwrap();
+            assert_eq!(char::from(shape), letter);
+            assert_eq!(
+                TetrominoShape::try_from(letter.to_ascii_lowercase()),
+                Ok(shape)
+            );
+        }
+        assert_eq!(TetrominoShape::try_from('X'), Err('X'));
+    }
 
     #[test]
     fn test_tetromino_init() {
@@ -231,6 +310,23 @@ mod tests {
         assert_eq!(t_s.get_mask(), &m_s);
     }
 
+    #[test]
+    fn test_tetromino_cache() {
+        // Separately spawned pieces share the masks of the cache instead of generating their own.
+        let mut first = Tetromino::from(TetrominoShape::I);
+        let mut second = Tetromino::from(TetrominoShape::I);
+        assert!(core::ptr::eq(first.get_mask(), second.get_mask()));
+        first.rotate_cw();
+        assert!(!core::ptr::eq(first.get_mask(), second.get_mask()));
+        second.rotate_cw();
+        assert!(core::ptr::eq(first.get_mask(), second.get_mask()));
+        assert_eq!(first.get_shape(), Coordinate::from_array([4, 1]));
+        // A custom tetromino owns its masks.
+        let custom = Tetromino::new(TetrominoShape::I, first.color, first.get_mask().clone());
+        assert!(!core::ptr::eq(custom.get_mask(), first.get_mask()));
+        assert_eq!(custom.get_mask(), first.get_mask());
+    }
+
     #[test]
     fn test_tetromino_rotate_cw() {
         // Create J Tetromino, rotate 10 times clockwise, check if all match.
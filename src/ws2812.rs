@@ -0,0 +1,390 @@
+//! The output stage for WS2812 LEDs, streaming a `FrameBuffer` through a PIO state machine of the RP2040.
+//!
+//! # Timing
+//! The state machine runs the WS2812 program of the Raspberry Pi pico-examples, spending `CYCLES_PER_BIT` PIO
+//! cycles on every bit at `BIT_RATE_HZ`, i.e. a PIO clock of 8 MHz and 125 ns per cycle:
+//! - a 1 is high for `T1 + T2` cycles (875 ns) and low for `T3` cycles (375 ns)
+//! - a 0 is high for `T1` cycles (250 ns) and low for `T2 + T3` cycles (1000 ns)
+//!
+//! The LEDs latch the frame once the line stays low for `RESET_MICROS`, the 280 µs of the WS2812B V5 which
+//! also covers the 50 µs of older parts. A frame of `N` LEDs takes `N * 30` µs plus the latch, so a panel
+//! of 2048 LEDs refreshes at most 16 times per second.
+//!
+//! # DMA
+//! Instead of pushing every word from the CPU, a frame can be encoded up front with
+//! `FrameBuffer::encode_dma_buffer()` and streamed by a DMA channel paced by the TX FIFO. The next frame is encoded
+//! into the other buffer of a `PingPong` meanwhile, and only the latch is waited for.
+use crate::color::Rgb;
+
+/// The number of PIO cycles the line is high at the start of every bit.
+pub const T1: u8 = 2;
+
+/// The number of PIO cycles the line stays high for a 1, or is already low for a 0.
+pub const T2: u8 = 5;
+
+/// The number of PIO cycles the line is low at the end of every bit.
+pub const T3: u8 = 3;
+
+/// The number of PIO cycles of a single bit.
+pub const CYCLES_PER_BIT: u32 = (T1 + T2 + T3) as u32;
+
+/// The bit rate of the WS2812 protocol.
+pub const BIT_RATE_HZ: u32 = 800_000;
+
+/// The number of microseconds the line is held low after a frame, so the LEDs latch it.
+pub const RESET_MICROS: u32 = 280;
+
+/// Pack a color into the word pushed into the TX FIFO, G, R and B from the most significant byte down.
+///
+/// The state machine shifts the word out to the left and pulls the next one after 24 bits, so the lowest
+/// byte is never sent.
+/// # Arguments
+/// - `color` - The color of the LED
+/// # Returns
+/// - `u32` - The color in the 0xGGRRBB00 layout
+pub fn to_word(color: Rgb) -> u32 {
+    color.to_u32_grb() << 8
+}
+
+/// Expand a single bit into the line level of every PIO cycle it takes, like the state machine does.
+/// # Arguments
+/// - `bit` - The bit to send
+/// # Returns
+/// - `u16` - The `CYCLES_PER_BIT` levels in the lowest bits, the first cycle in the most significant of them
+pub fn expand_bit(bit: bool) -> u16 {
+    let high = match bit {
+        true => T1 + T2,
+        false => T1,
+    };
+    ((1 << high) - 1) << (CYCLES_PER_BIT as u8 - high)
+}
+
+/// Expand a color into the line levels of its 24 bits in the order they are sent, e.g. for a software or SPI
+/// fallback, or to check the encoding on the host.
+/// # Arguments
+/// - `color` - The color of the LED
+/// - `out` - The buffer to write the expanded bits into, see `expand_bit()`
+pub fn expand_color(color: Rgb, out: &mut [u16; 24]) {
+    let word = to_word(color);
+    for (index, levels) in out.iter_mut().enumerate() {
+        *levels = expand_bit(word & (1 << (31 - index)) != 0);
+    }
+}
+
+/// A pair of DMA buffers, encoding the next frame into one while the other is streamed to the LEDs.
+///
+/// The buffer being streamed is moved out into the transfer, so it cannot be written until the finished transfer
+/// hands it back with `.release()`.
+/// # Attributes
+/// - `back` - The buffer to encode the next frame into
+/// - `spare` - The buffer of the previous frame, or `None` while it is being streamed
+#[derive(Debug)]
+pub struct PingPong<B> {
+    back: B,
+    spare: Option<B>,
+}
+
+impl<B> PingPong<B> {
+    /// Create a pair of idle buffers.
+    /// # Arguments
+    /// - `first` - The buffer to encode the first frame into
+    /// - `second` - The buffer to encode the second frame into
+    /// # Returns
+    /// - `PingPong<B>` - The buffers, ready to send the first frame
+    pub fn new(first: B, second: B) -> Self {
+        PingPong {
+            back: first,
+            spare: Some(second),
+        }
+    }
+
+    /// Get the buffer to encode the next frame into, never the one being streamed.
+    pub fn get_back_mut(&mut self) -> &mut B {
+        &mut self.back
+    }
+
+    /// Check if the buffer of the previous frame was released, so the next frame can be sent.
+    pub fn is_ready(&self) -> bool {
+        self.spare.is_some()
+    }
+
+    /// Hand out the encoded frame to stream it, continuing with the other buffer.
+    /// # Returns
+    /// - `Option<B>` - The buffer to stream, or `None` if the previous frame is still being streamed
+    pub fn send(&mut self) -> Option<B> {
+        let spare = self.spare.take()?;
+        Some(core::mem::replace(&mut self.back, spare))
+    }
+
+    /// Take back the buffer of a finished transfer.
+    /// # Arguments
+    /// - `buffer` - The buffer handed out by the last `.send()`
+    pub fn release(&mut self, buffer: B) {
+        assert!(self.spare.is_none(), "no buffer is being streamed");
+        self.spare = Some(buffer);
+    }
+}
+
+#[cfg(feature = "rp2040")]
+pub use driver::{Ws2812, Ws2812Dma};
+
+#[cfg(feature = "rp2040")]
+mod driver {
+    use super::{to_word, BIT_RATE_HZ, CYCLES_PER_BIT, RESET_MICROS, T1, T2, T3};
+    use crate::framebuffer::{DoubleBuffer, FrameBuffer};
+    use crate::settings::Settings;
+    use rp2040_hal::clocks::{Clock, ClocksManager};
+    use rp2040_hal::dma::{single_buffer, SingleChannel};
+    use rp2040_hal::gpio::{AnyPin, SpecificPin};
+    use rp2040_hal::pio::{
+        PIOBuilder, PIOExt, PinDir, Running, ShiftDirection, StateMachine, StateMachineIndex, Tx,
+        UninitStateMachine, PIO,
+    };
+
+    /// The number of microseconds the last word may still be shifting out once the TX FIFO is empty.
+    const LAST_WORD_MICROS: u32 = 30;
+
+    /// A DMA transfer of the words of a frame into the TX FIFO of a state machine.
+    type FrameTransfer<P, SM, CH> = single_buffer::Transfer<CH, &'static mut [u32], Tx<(P, SM)>>;
+
+    /// A chain of WS2812 LEDs driven by a PIO state machine, blocking until every frame is latched.
+    /// # Attributes
+    /// - `tx` - The TX FIFO of the state machine
+    /// - `latch_cycles` - The number of system clock cycles to wait for the last word and the latch
+    /// - `_sm` - The running state machine, kept so it is not reconfigured elsewhere
+    /// - `_pin` - The data pin, kept so it is not reconfigured elsewhere
+    pub struct Ws2812<P: PIOExt, SM: StateMachineIndex, I: AnyPin<Function = P::PinFunction>> {
+        tx: Tx<(P, SM)>,
+        latch_cycles: u32,
+        _sm: StateMachine<(P, SM), Running>,
+        _pin: SpecificPin<I>,
+    }
+
+    impl<P, SM, I> Ws2812<P, SM, I>
+    where
+        P: PIOExt,
+        SM: StateMachineIndex,
+        I: AnyPin<Function = P::PinFunction>,
+    {
+        /// Install the WS2812 program and start a state machine driving the data pin.
+        /// # Arguments
+        /// - `pio` - A muteable reference to the PIO block to install the program in
+        /// - `sm` - The state machine to run the program on
+        /// - `pin` - The data pin, already set to the function of the PIO block
+        /// - `clocks` - A reference to the initialized clocks, deriving the PIO clock from the system clock
+        /// # Returns
+        /// - `Ws2812<P, SM, I>` - The driver, ready to write frames
+        pub fn new(
+            pio: &mut PIO<P>,
+            sm: UninitStateMachine<(P, SM)>,
+            pin: I,
+            clocks: &ClocksManager,
+        ) -> Self {
+            let program = pio::pio_asm!(
+                ".side_set 1",
+                ".wrap_target",
+                "bitloop:",
+                "    out x, 1        side 0 [2]",
+                "    jmp !x do_zero  side 1 [1]",
+                "do_one:",
+                "    jmp bitloop     side 1 [4]",
+                "do_zero:",
+                "    nop             side 0 [4]",
+                ".wrap",
+            );
+            // The delays above are T3 - 1, T1 - 1 and T2 - 1, the assembler needs literals.
+            debug_assert_eq!((T1, T2, T3), (2, 5, 3));
+            let installed = pio
+                .install(&program.program)
+                .expect("the WS2812 program fits in the instruction memory of the PIO");
+            let pin: SpecificPin<I> = pin.into();
+            let id = pin.id().num;
+            // The clock divisor in 16.8 fixed point, e.g. 15 + 160 / 256 at a system clock of 125 MHz.
+            let sys_hz = clocks.system_clock.freq().to_Hz();
+            let divisor = sys_hz as u64 * 256 / (BIT_RATE_HZ * CYCLES_PER_BIT) as u64;
+            let (mut sm, _, tx) = PIOBuilder::from_installed_program(installed)
+                .side_set_pin_base(id)
+                .out_shift_direction(ShiftDirection::Left)
+                .autopull(true)
+                .pull_threshold(24)
+                .clock_divisor_fixed_point((divisor >> 8) as u16, divisor as u8)
+                .build(sm);
+            sm.set_pindirs([(id, PinDir::Output)]);
+            Ws2812 {
+                tx,
+                latch_cycles: sys_hz / 1_000_000 * (LAST_WORD_MICROS + RESET_MICROS),
+                _sm: sm.start(),
+                _pin: pin,
+            }
+        }
+
+        /// Send a frame to the LEDs in the order of its `PanelLayout`, corrected like `FrameBuffer::flush()`,
+        /// and wait until the LEDs latched it.
+        /// # Arguments
+        /// - `frame` - A reference to the `FrameBuffer` to show
+        /// - `settings` - A reference to the `Settings` holding the color correction, the brightness and the
+        ///   gamma correction flag
+        pub fn write<const N: usize>(&mut self, frame: &FrameBuffer<N>, settings: &Settings) {
+            frame.flush(settings, |pixel| while !self.tx.write(to_word(pixel)) {});
+            self._latch();
+        }
+
+        /// Send only the prefix of the chain covering the changed pixels of the front buffer, see
+        /// `FrameBuffer::write_dirty()`, and wait until the LEDs latched it.
+        /// # Arguments
+        /// - `buffers` - A muteable reference to the `DoubleBuffer`, marking the front buffer as sent
+        /// - `settings` - A reference to the `Settings` holding the color correction, the brightness and the
+        ///   gamma correction flag
+        /// # Returns
+        /// - `usize` - The number of LEDs written, 0 without waiting for a latch if no pixel changed
+        pub fn write_dirty<const N: usize>(
+            &mut self,
+            buffers: &mut DoubleBuffer<N>,
+            settings: &Settings,
+        ) -> usize {
+            let written =
+                buffers.write_dirty(settings, |pixel| while !self.tx.write(to_word(pixel)) {});
+            if written > 0 {
+                self._latch();
+            }
+            written
+        }
+
+        /// Stream the frames through a DMA channel instead of the CPU.
+        /// # Arguments
+        /// - `channel` - The DMA channel to stream the frames with
+        /// # Returns
+        /// - `Ws2812Dma<P, SM, I, CH>` - The driver, ready to start the first frame
+        pub fn into_dma<CH: SingleChannel>(self, channel: CH) -> Ws2812Dma<P, SM, I, CH> {
+            Ws2812Dma {
+                idle: Some((channel, self.tx)),
+                transfer: None,
+                latch_cycles: self.latch_cycles,
+                _sm: self._sm,
+                _pin: self._pin,
+            }
+        }
+
+        /// Wait until the last word is shifted out and the LEDs latched the frame.
+        fn _latch(&mut self) {
+            _latch(&self.tx, self.latch_cycles);
+        }
+    }
+
+    /// A chain of WS2812 LEDs driven by a PIO state machine fed by a DMA channel, only waiting for the latch.
+    ///
+    /// Exactly one of `idle` and `transfer` holds the channel and the TX FIFO.
+    /// # Attributes
+    /// - `idle` - The DMA channel and the TX FIFO of the state machine while no frame is being streamed
+    /// - `transfer` - The transfer of the frame being streamed, owning its buffer
+    /// - `latch_cycles` - The number of system clock cycles to wait for the last word and the latch
+    /// - `_sm` - The running state machine, kept so it is not reconfigured elsewhere
+    /// - `_pin` - The data pin, kept so it is not reconfigured elsewhere
+    pub struct Ws2812Dma<P, SM, I, CH>
+    where
+        P: PIOExt,
+        SM: StateMachineIndex,
+        I: AnyPin<Function = P::PinFunction>,
+        CH: SingleChannel,
+    {
+        idle: Option<(CH, Tx<(P, SM)>)>,
+        transfer: Option<FrameTransfer<P, SM, CH>>,
+        latch_cycles: u32,
+        _sm: StateMachine<(P, SM), Running>,
+        _pin: SpecificPin<I>,
+    }
+
+    impl<P, SM, I, CH> Ws2812Dma<P, SM, I, CH>
+    where
+        P: PIOExt,
+        SM: StateMachineIndex,
+        I: AnyPin<Function = P::PinFunction>,
+        CH: SingleChannel,
+    {
+        /// Start streaming a frame encoded by `FrameBuffer::encode_dma_buffer()`, without waiting for it.
+        /// # Arguments
+        /// - `buf` - The words of the frame, owned by the transfer until `.wait()` hands them back
+        /// # Returns
+        /// - `Result<(), &'static mut [u32]>` - The words back as the error if the previous frame was not
+        ///   waited for
+        pub fn start_dma(&mut self, buf: &'static mut [u32]) -> Result<(), &'static mut [u32]> {
+            match self.idle.take() {
+                Some((channel, tx)) => {
+                    self.transfer = Some(single_buffer::Config::new(channel, buf, tx).start());
+                    Ok(())
+                }
+                None => Err(buf),
+            }
+        }
+
+        /// Check if a frame is still being streamed, not counting the latch after it.
+        pub fn is_busy(&self) -> bool {
+            self.transfer
+                .as_ref()
+                .is_some_and(|transfer| !transfer.is_done())
+        }
+
+        /// Wait until the frame being streamed is shifted out and the LEDs latched it.
+        /// # Returns
+        /// - `Option<&'static mut [u32]>` - The words of the frame, or `None` if no frame was started
+        pub fn wait(&mut self) -> Option<&'static mut [u32]> {
+            let (channel, buf, tx) = self.transfer.take()?.wait();
+            _latch(&tx, self.latch_cycles);
+            self.idle = Some((channel, tx));
+            Some(buf)
+        }
+    }
+
+    /// Wait until the last word is shifted out of a TX FIFO and the LEDs latched the frame.
+    /// # Arguments
+    /// - `tx` - A reference to the TX FIFO of the state machine
+    /// - `latch_cycles` - The number of system clock cycles to wait for the last word and the latch
+    fn _latch<P: PIOExt, SM: StateMachineIndex>(tx: &Tx<(P, SM)>, latch_cycles: u32) {
+        while !tx.is_empty() {}
+        cortex_m::asm::delay(latch_cycles);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{expand_bit, expand_color, to_word, PingPong, CYCLES_PER_BIT};
+    use crate::color::Rgb;
+
+    #[test]
+    fn test_expand_bit() {
+        // A 1 is high for 7 of the 10 cycles, a 0 for 2, both starting high.
+        assert_eq!(CYCLES_PER_BIT, 10);
+        assert_eq!(expand_bit(true), 0b11_1111_1000);
+        assert_eq!(expand_bit(false), 0b11_0000_0000);
+    }
+
+    #[test]
+    fn test_expand_color() {
+        // The green byte goes first, most significant bit first.
+        let color = Rgb::from_array(&[0x0F, 0x80, 0x01]);
+        assert_eq!(to_word(color), 0x800F_0100);
+        let mut out = [0; 24];
+        expand_color(color, &mut out);
+        let one = expand_bit(true);
+        let zero = expand_bit(false);
+        assert_eq!(out[..8], [one, zero, zero, zero, zero, zero, zero, zero]);
+        assert_eq!(out[8..16], [zero, zero, zero, zero, one, one, one, one]);
+        assert_eq!(out[16..], [zero, zero, zero, zero, zero, zero, zero, one]);
+    }
+
+    #[test]
+    fn test_ping_pong() {
+        let mut buffers = PingPong::new([0; 2], [0; 2]);
+        assert!(buffers.is_ready());
+        *buffers.get_back_mut() = [1, 1];
+        let first = buffers.send().unwrap();
+        assert_eq!(first, [1, 1]);
+        // The next frame goes into the other buffer, and waits for the first one to be released.
+        *buffers.get_back_mut() = [2, 2];
+        assert!(!buffers.is_ready());
+        assert_eq!(buffers.send(), None);
+        buffers.release(first);
+        assert_eq!(buffers.send(), Some([2, 2]));
+        assert_eq!(*buffers.get_back_mut(), [1, 1]);
+    }
+}